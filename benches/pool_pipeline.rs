@@ -0,0 +1,103 @@
+//! Parse, filter, score and rank over a pool list the size of the live yields
+//! endpoint's. The pools are generated, so no network is needed.
+
+use asam::agents::defi_optimizer::{parse_pools, DefiOptimizer};
+use asam::config::{OptimizerConfig, ScoringMethod};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+const POOLS: usize = 16_000;
+const CHAINS: &[&str] = &["Ethereum", "Arbitrum", "Optimism", "Base", "Polygon", "BSC", "Avalanche", "Solana"];
+
+/// A pool API response of `count` protocol entries mixing the shapes the parser
+/// accepts, from a fixed seed so every run ranks the same pools.
+fn fixture(count: usize) -> String {
+    let mut seed = 0x2545_f491_4f6c_dd1d_u64;
+    let mut next = move || {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        seed
+    };
+    let entries: Vec<serde_json::Value> = (0..count)
+        .map(|i| {
+            let r = next();
+            let chain = CHAINS[r as usize % CHAINS.len()];
+            let tvl = (r >> 8) % 1_000_000_000;
+            let apy = ((r >> 40) % 4_000) as f64 / 100.0;
+            let mut entry = match r % 4 {
+                0 => serde_json::json!({ "name": format!("protocol-{i}"), "chain": chain, "tvl": tvl, "apy": apy }),
+                1 => serde_json::json!({
+                    "slug": format!("protocol-{i}"),
+                    "chains": [chain, "Ethereum"],
+                    "totalLiquidityUSD": tvl,
+                    "apy": { "total": apy },
+                }),
+                2 => serde_json::json!({ "name": format!("protocol-{i}"), "chain": chain, "tvl": tvl, "apyBase": apy }),
+                _ => serde_json::json!({ "name": format!("protocol-{i}"), "chain": chain, "tvl": tvl }),
+            };
+            // Fields the live endpoint also sends, which the optimizer does not use
+            let extra = serde_json::json!({
+                "pool": format!("{:016x}-{:08x}", r, i),
+                "symbol": "USDC-WETH",
+                "stablecoin": r % 3 == 0,
+                "apyPct7D": -0.25,
+                "underlyingTokens": [format!("0x{:040x}", r), format!("0x{:040x}", r >> 1)],
+                "predictions": { "predictedClass": "Stable/Up", "predictedProbability": 71, "binnedConfidence": 2 },
+            });
+            entry.as_object_mut().unwrap().extend(extra.as_object().unwrap().clone());
+            entry
+        })
+        .collect();
+    serde_json::to_string(&entries).unwrap()
+}
+
+/// The configurations a cycle ranks with: the defaults, a chain filter and APY-only scoring.
+fn optimizers() -> Vec<DefiOptimizer> {
+    let config = OptimizerConfig::default();
+    vec![
+        DefiOptimizer::from_config(&config),
+        DefiOptimizer::from_config(&OptimizerConfig {
+            chains: vec!["arbitrum".to_string(), "BASE".to_string(), "Optimism".to_string()],
+            min_apy: 2.0,
+            ..config.clone()
+        }),
+        DefiOptimizer::from_config(&OptimizerConfig { scoring: ScoringMethod::Apy, min_tvl: 0.0, ..config }),
+    ]
+}
+
+fn pool_pipeline(c: &mut Criterion) {
+    let body = fixture(POOLS);
+    let pools = parse_pools(&body).unwrap().unwrap();
+    let optimizers = optimizers();
+
+    let mut group = c.benchmark_group("pool_pipeline");
+    group.bench_function("parse", |b| {
+        b.iter(|| parse_pools(black_box(&body)).unwrap().unwrap())
+    });
+    group.bench_function("rank", |b| {
+        b.iter(|| {
+            for optimizer in &optimizers {
+                black_box(optimizer.rank_for(black_box(&pools), Some(50_000.0)).unwrap());
+            }
+        })
+    });
+    group.bench_function("rank_top", |b| {
+        b.iter(|| {
+            for optimizer in &optimizers {
+                black_box(optimizer.rank_top(black_box(&pools), Some(50_000.0), 10).unwrap());
+            }
+        })
+    });
+    group.bench_function("parse_and_rank", |b| {
+        b.iter(|| {
+            let pools = parse_pools(black_box(&body)).unwrap().unwrap();
+            for optimizer in &optimizers {
+                black_box(optimizer.rank_for(&pools, Some(50_000.0)).unwrap());
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, pool_pipeline);
+criterion_main!(benches);
@@ -0,0 +1,44 @@
+//! Embeds the git commit and build time for `asam --version`, the startup banner and
+//! `/status`.
+
+use std::env;
+use std::path::Path;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn main() {
+    // A hash given by the build environment wins, for builds outside a checkout
+    let hash = env::var("ASAM_GIT_HASH")
+        .ok()
+        .or_else(|| {
+            Command::new("git")
+                .args(["rev-parse", "--short=12", "HEAD"])
+                .output()
+                .ok()
+                .filter(|output| output.status.success())
+                .and_then(|output| String::from_utf8(output.stdout).ok())
+                .map(|hash| hash.trim().to_string())
+        })
+        .unwrap_or_else(|| "unknown".to_string());
+    // SOURCE_DATE_EPOCH keeps reproducible builds reproducible
+    let built = env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|epoch| epoch.parse().ok())
+        .unwrap_or_else(|| SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0));
+    println!("cargo:rustc-env=ASAM_GIT_HASH={}", hash);
+    println!("cargo:rustc-env=ASAM_BUILD_TIMESTAMP={}", built);
+
+    println!("cargo:rerun-if-env-changed=ASAM_GIT_HASH");
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+    // Watching a missing file would rerun on every build
+    let head = Path::new(".git/HEAD");
+    if head.exists() {
+        println!("cargo:rerun-if-changed=.git/HEAD");
+        let contents = std::fs::read_to_string(head).unwrap_or_default();
+        if let Some(branch) = contents.strip_prefix("ref: ").map(str::trim) {
+            if Path::new(".git").join(branch).exists() {
+                println!("cargo:rerun-if-changed=.git/{}", branch);
+            }
+        }
+    }
+}
@@ -1,34 +0,0 @@
-use ethers::prelude::*;
-use anyhow::Result;
-use std::str::FromStr;
-
-pub async fn simulate_defi_operations() -> Result<()> {
-	// Simulate DeFi pool interactions
-	let pool_addresses = vec![
-		"0x1234567890123456789012345678901234567890",
-		"0x0987654321098765432109876543210987654321",
-	];
-
-	for address in pool_addresses {
-		let pool_address = Address::from_str(address)?;
-		println!("Simulating interaction with pool: {}", pool_address);
-		
-		// Simulate yield farming
-		println!("Simulating yield farming...");
-		tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
-		
-		// Simulate token swaps
-		println!("Simulating token swaps...");
-		tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
-	}
-
-	Ok(())
-}
-
-#[tokio::main]
-async fn main() -> Result<()> {
-	println!("Starting DeFi operations simulation...");
-	simulate_defi_operations().await?;
-	println!("Simulation completed successfully");
-	Ok(())
-}
\ No newline at end of file
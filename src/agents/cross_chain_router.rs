@@ -1,11 +1,21 @@
 use anyhow::Result;
+use ethers::core::types::H256;
 use log::{debug, info, error};
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
 use thiserror::Error;
-use tokio::time::{sleep, Duration};
+use tokio::time::Duration;
+
+use crate::config::{RouterConfig, RunMode};
+use crate::kill_switch::KillSwitch;
+use crate::notifier::{AgentEvent, EventSender};
+use crate::portfolio::TOKEN;
+use crate::recent_actions::{route_key, same_amount, RecentActions};
+use crate::util::{Clock, SystemClock};
 
 #[derive(Error, Debug)]
+#[non_exhaustive]
 pub enum CrossChainError {
 	#[error("Invalid chain '{0}'. Supported chains: {1}")]
 	InvalidChain(String, String),
@@ -15,9 +25,11 @@ pub enum CrossChainError {
 	AmountTooLow { amount: f64, minimum: f64 },
 	#[error("Bridge error: {0}")]
 	BridgeError(String),
+	#[error("Route of {amount} from {source_chain} to {target_chain} repeats one completed at {completed_at}; force it to route again")]
+	DuplicateRoute { amount: f64, source_chain: String, target_chain: String, completed_at: u64 },
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChainInfo {
 	pub name: String,
 	pub chain_id: u64,
@@ -25,34 +37,338 @@ pub struct ChainInfo {
 	pub min_transfer: f64,
 }
 
+/// Bridge aggregator a live route was submitted through.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BridgeProvider {
+	/// Local simulation only, nothing to poll.
+	Simulated,
+	LiFi,
+	Socket,
+}
+
+/// Lifecycle of a route, from submission to the release on the target chain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RouteState {
+	Pending,
+	Locking,
+	/// Source transaction confirmed, waiting for the destination chain.
+	Releasing,
+	Completed,
+	Failed,
+}
+
+impl RouteState {
+	pub fn is_terminal(&self) -> bool {
+		matches!(self, RouteState::Completed | RouteState::Failed)
+	}
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RouteReceipt {
+	pub route_id: String,
+	pub amount: f64,
+	pub source_chain: String,
+	pub target_chain: String,
+	pub provider: BridgeProvider,
+	pub state: RouteState,
+	pub source_tx_hash: Option<H256>,
+	pub destination_tx_hash: Option<H256>,
+	pub failure_reason: Option<String>,
+	pub updated_at: u64,
+}
+
+/// Shared, cloneable view of every route the router has started.
+#[derive(Debug, Clone)]
+pub struct RouteTracker {
+	routes: Arc<RwLock<HashMap<String, RouteReceipt>>>,
+	clock: Arc<dyn Clock>,
+}
+
+impl Default for RouteTracker {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl RouteTracker {
+	pub fn new() -> Self {
+		Self::with_clock(SystemClock::shared())
+	}
+
+	/// Stamps state changes with `clock`'s time.
+	pub fn with_clock(clock: Arc<dyn Clock>) -> Self {
+		Self { routes: Arc::default(), clock }
+	}
+
+	pub fn insert(&self, receipt: RouteReceipt) {
+		self.routes
+			.write()
+			.expect("route tracker lock poisoned")
+			.insert(receipt.route_id.clone(), receipt);
+	}
+
+	pub fn get(&self, route_id: &str) -> Option<RouteReceipt> {
+		self.routes
+			.read()
+			.expect("route tracker lock poisoned")
+			.get(route_id)
+			.cloned()
+	}
+
+	/// Moves a route to `state`, returning the updated receipt. Terminal routes are left untouched.
+	pub fn transition(&self, route_id: &str, state: RouteState) -> Option<RouteReceipt> {
+		let mut routes = self.routes.write().expect("route tracker lock poisoned");
+		let receipt = routes.get_mut(route_id)?;
+		if receipt.state.is_terminal() {
+			return None;
+		}
+		debug!("Route {} transition: {:?} -> {:?}", route_id, receipt.state, state);
+		receipt.state = state;
+		receipt.updated_at = self.clock.now_utc();
+		Some(receipt.clone())
+	}
+
+	pub fn complete(&self, route_id: &str, destination_tx_hash: Option<H256>) -> Option<RouteReceipt> {
+		let mut routes = self.routes.write().expect("route tracker lock poisoned");
+		let receipt = routes.get_mut(route_id)?;
+		if receipt.state.is_terminal() {
+			return None;
+		}
+		receipt.state = RouteState::Completed;
+		receipt.destination_tx_hash = destination_tx_hash.or(receipt.destination_tx_hash);
+		receipt.updated_at = self.clock.now_utc();
+		Some(receipt.clone())
+	}
+
+	pub fn fail(&self, route_id: &str, reason: &str) -> Option<RouteReceipt> {
+		let mut routes = self.routes.write().expect("route tracker lock poisoned");
+		let receipt = routes.get_mut(route_id)?;
+		if receipt.state.is_terminal() {
+			return None;
+		}
+		receipt.state = RouteState::Failed;
+		receipt.failure_reason = Some(reason.to_string());
+		receipt.updated_at = self.clock.now_utc();
+		Some(receipt.clone())
+	}
+
+	/// Live routes whose source leg is done and which still wait for the destination chain.
+	pub fn awaiting_destination(&self) -> Vec<RouteReceipt> {
+		self.routes
+			.read()
+			.expect("route tracker lock poisoned")
+			.values()
+			.filter(|r| r.state == RouteState::Releasing && r.provider != BridgeProvider::Simulated)
+			.cloned()
+			.collect()
+	}
+
+	pub fn all(&self) -> Vec<RouteReceipt> {
+		self.routes
+			.read()
+			.expect("route tracker lock poisoned")
+			.values()
+			.cloned()
+			.collect()
+	}
+
+	pub fn in_flight(&self) -> Vec<RouteReceipt> {
+		self.routes
+			.read()
+			.expect("route tracker lock poisoned")
+			.values()
+			.filter(|r| !r.state.is_terminal())
+			.cloned()
+			.collect()
+	}
+}
+
 pub struct CrossChainRouter {
-	supported_chains: HashSet<String>,
+	supported_chains: HashMap<String, ChainInfo>,
 	min_amount: f64,
+	max_amount: f64,
+	tracker: RouteTracker,
+	mode: RunMode,
+	events: EventSender,
+	kill_switch: KillSwitch,
+	recent_actions: RecentActions,
+	dedup_window: Duration,
+	clock: Arc<dyn Clock>,
+}
+
+impl Default for CrossChainRouter {
+	fn default() -> Self {
+		Self::new()
+	}
 }
 
 impl CrossChainRouter {
 	pub fn new() -> Self {
-		let mut supported_chains = HashSet::new();
-		supported_chains.insert("Ethereum".to_string());
-		supported_chains.insert("Arbitrum".to_string());
-		supported_chains.insert("Optimism".to_string());
-		supported_chains.insert("Polygon".to_string());
-		supported_chains.insert("Fantom".to_string());
+		Self::from_config(&RouterConfig::default())
+	}
+
+	pub fn from_config(config: &RouterConfig) -> Self {
+		Self::with_clock(config, SystemClock::shared())
+	}
+
+	/// Reads the time for route timestamps and the dedup window from `clock`.
+	pub fn with_clock(config: &RouterConfig, clock: Arc<dyn Clock>) -> Self {
+		let supported_chains = config.chains
+			.iter()
+			.map(|chain| {
+				(chain.name.clone(), ChainInfo {
+					name: chain.name.clone(),
+					chain_id: chain.chain_id,
+					is_active: chain.active,
+					min_transfer: chain.min_transfer,
+				})
+			})
+			.collect();
 		
 		Self {
 			supported_chains,
-			min_amount: 0.1,
+			min_amount: config.min_amount,
+			max_amount: config.max_amount,
+			tracker: RouteTracker::with_clock(clock.clone()),
+			mode: RunMode::Live,
+			events: EventSender::disabled(),
+			kill_switch: KillSwitch::default(),
+			recent_actions: RecentActions::new(config.route_dedup_window()),
+			dedup_window: config.route_dedup_window(),
+			clock,
 		}
 	}
 
-	pub async fn route_funds(&self, amount: f64, source_chain: &str, target_chain: &str) -> Result<()> {
+	/// In dry-run mode routes are fully validated but never submitted; in observe
+	/// mode they are not even prepared.
+	pub fn set_mode(&mut self, mode: RunMode) {
+		self.mode = mode;
+	}
+
+	pub fn mode(&self) -> RunMode {
+		self.mode
+	}
+
+	pub fn set_events(&mut self, events: EventSender) {
+		self.events = events;
+	}
+
+	/// Routes are refused while `kill_switch` is on.
+	pub fn set_kill_switch(&mut self, kill_switch: KillSwitch) {
+		self.kill_switch = kill_switch;
+	}
+
+	/// Checks routes against, and records completed ones in, `recent_actions`
+	/// instead of a cache private to this router.
+	pub fn set_recent_actions(&mut self, recent_actions: RecentActions) {
+		self.recent_actions = recent_actions;
+	}
+
+	pub fn recent_actions(&self) -> RecentActions {
+		self.recent_actions.clone()
+	}
+
+	pub fn tracker(&self) -> RouteTracker {
+		self.tracker.clone()
+	}
+
+	pub fn chain_id(&self, chain: &str) -> Option<u64> {
+		self.supported_chains.get(chain).map(|c| c.chain_id)
+	}
+
+	pub fn is_chain_active(&self, chain: &str) -> bool {
+		self.supported_chains.get(chain).map(|c| c.is_active).unwrap_or(false)
+	}
+
+	/// Allows or stops routes to and from `chain`; unknown chains are ignored.
+	pub fn set_chain_active(&mut self, chain: &str, active: bool) {
+		if let Some(info) = self.supported_chains.get_mut(chain) {
+			info.is_active = active;
+		}
+	}
+
+	/// Every chain the router knows, in chain id order.
+	pub fn chains(&self) -> Vec<ChainInfo> {
+		let mut chains: Vec<ChainInfo> = self.supported_chains.values().cloned().collect();
+		chains.sort_by_key(|c| c.chain_id);
+		chains
+	}
+
+	pub fn chain_ids(&self) -> HashMap<String, u64> {
+		self.supported_chains
+			.values()
+			.map(|c| (c.name.clone(), c.chain_id))
+			.collect()
+	}
+
+	/// Registers a route whose source transaction was submitted through a bridge provider,
+	/// so the status poller can follow it to the destination chain.
+	pub fn track_live_route(
+		&self,
+		amount: f64,
+		source_chain: &str,
+		target_chain: &str,
+		provider: BridgeProvider,
+		source_tx_hash: H256,
+	) -> Result<String> {
+		self.validate_chain(source_chain)?;
+		self.validate_chain(target_chain)?;
+
+		let route_id = format!("{:?}", source_tx_hash);
+		info!(
+			"Tracking live route {} via {:?}: {} tokens from {} to {}",
+			route_id, provider, amount, source_chain, target_chain
+		);
+		self.tracker.insert(RouteReceipt {
+			route_id: route_id.clone(),
+			amount,
+			source_chain: source_chain.to_string(),
+			target_chain: target_chain.to_string(),
+			provider,
+			state: RouteState::Releasing,
+			source_tx_hash: Some(source_tx_hash),
+			destination_tx_hash: None,
+			failure_reason: None,
+			updated_at: self.clock.now_utc(),
+		});
+		self.events.emit(AgentEvent::RouteStarted {
+			route_id: route_id.clone(),
+			amount,
+			source_chain: source_chain.to_string(),
+			target_chain: target_chain.to_string(),
+		});
+		Ok(route_id)
+	}
+
+	/// Validates and executes a route, returning its tracker id. Dry-run and observe
+	/// modes return `None` as nothing is tracked. A route repeating one completed within
+	/// `router.route_dedup_window_secs` is refused.
+	pub async fn route_funds(&self, amount: f64, source_chain: &str, target_chain: &str) -> Result<Option<String>> {
+		self.route(amount, source_chain, target_chain, false).await
+	}
+
+	/// [`CrossChainRouter::route_funds`] without the check for a repeated route.
+	pub async fn route_funds_forced(&self, amount: f64, source_chain: &str, target_chain: &str) -> Result<Option<String>> {
+		self.route(amount, source_chain, target_chain, true).await
+	}
+
+	#[tracing::instrument(skip(self), fields(route_id = tracing::field::Empty))]
+	async fn route(&self, amount: f64, source_chain: &str, target_chain: &str, force: bool) -> Result<Option<String>> {
+		self.kill_switch.ensure_running()?;
+		if self.mode == RunMode::Observe {
+			info!(
+				"[OBSERVE] Not preparing route of {} tokens from {} to {}",
+				amount, source_chain, target_chain
+			);
+			return Ok(None);
+		}
 		debug!("Starting cross-chain transfer validation");
 		debug!("Validating source chain: {}", source_chain);
 		
 		self.validate_chain(source_chain)
 			.map_err(|e| {
 				error!("Source chain validation failed: {}", e);
-				error!("Supported chains: {}", self.supported_chains.iter().cloned().collect::<Vec<_>>().join(", "));
+				error!("Supported chains: {}", self.get_supported_chains().join(", "));
 				e
 			})?;
 			
@@ -60,7 +376,7 @@ impl CrossChainRouter {
 		self.validate_chain(target_chain)
 			.map_err(|e| {
 				error!("Target chain validation failed: {}", e);
-				error!("Supported chains: {}", self.supported_chains.iter().cloned().collect::<Vec<_>>().join(", "));
+				error!("Supported chains: {}", self.get_supported_chains().join(", "));
 				e
 			})?;
 
@@ -82,6 +398,17 @@ impl CrossChainRouter {
 				error!("Please try again with a smaller amount or wait for liquidity to increase");
 				e
 			})?;
+		if !force {
+			self.check_not_repeated(amount, source_chain, target_chain)?;
+		}
+
+		if self.mode == RunMode::DryRun {
+			info!(
+				"[DRY-RUN] Would route {} tokens from {} to {}",
+				amount, source_chain, target_chain
+			);
+			return Ok(None);
+		}
 
 		info!(
 			"Initiating cross-chain transfer: {} tokens from {} to {}",
@@ -89,13 +416,44 @@ impl CrossChainRouter {
 		);
 		debug!("All validations passed, proceeding with bridge transaction");
 
-		self.simulate_bridge_transaction(amount, source_chain, target_chain)
-			.await
-			.map_err(|e| {
-				error!("Bridge transaction failed: {}", e);
-				error!("Transaction simulation encountered an error - please check network conditions");
-				e
-			})?;
+		let route_id = format!("sim-{}-{}-{}", source_chain, target_chain, self.clock.now_utc());
+		tracing::Span::current().record("route_id", route_id.as_str());
+		self.tracker.insert(RouteReceipt {
+			route_id: route_id.clone(),
+			amount,
+			source_chain: source_chain.to_string(),
+			target_chain: target_chain.to_string(),
+			provider: BridgeProvider::Simulated,
+			state: RouteState::Pending,
+			source_tx_hash: None,
+			destination_tx_hash: None,
+			failure_reason: None,
+			updated_at: self.clock.now_utc(),
+		});
+		self.events.emit(AgentEvent::RouteStarted {
+			route_id: route_id.clone(),
+			amount,
+			source_chain: source_chain.to_string(),
+			target_chain: target_chain.to_string(),
+		});
+
+		if let Err(e) = self.simulate_bridge_transaction(&route_id, amount, source_chain, target_chain).await {
+			error!("Bridge transaction failed: {}", e);
+			error!("Transaction simulation encountered an error - please check network conditions");
+			self.tracker.fail(&route_id, &e.to_string());
+			self.events.emit(AgentEvent::RouteFailed {
+				route_id,
+				reason: e.to_string(),
+			});
+			return Err(e);
+		}
+		if let Some(receipt) = self.tracker.complete(&route_id, None) {
+			self.recent_actions.route_completed(&receipt).await;
+		}
+		self.events.emit(AgentEvent::RouteCompleted {
+			route_id: route_id.clone(),
+			destination_tx_hash: None,
+		});
 
 		info!(
 			"Successfully routed {} tokens from {} to {}",
@@ -103,16 +461,29 @@ impl CrossChainRouter {
 		);
 		debug!("Cross-chain transfer completed successfully");
 		
-		Ok(())
+		Ok(Some(route_id))
+	}
+
+	fn check_not_repeated(&self, amount: f64, source_chain: &str, target_chain: &str) -> Result<()> {
+		let key = route_key(source_chain, target_chain, TOKEN);
+		let Some(last) = self.recent_actions.within(&key, self.dedup_window, self.clock.now_utc()) else {
+			return Ok(());
+		};
+		if !last.amount.is_some_and(|last| same_amount(last, amount)) {
+			return Ok(());
+		}
+		Err(CrossChainError::DuplicateRoute {
+			amount,
+			source_chain: source_chain.to_string(),
+			target_chain: target_chain.to_string(),
+			completed_at: last.at,
+		}
+		.into())
 	}
 
 	fn validate_chain(&self, chain: &str) -> Result<()> {
-		if !self.supported_chains.contains(chain) {
-			let supported = self.supported_chains
-				.iter()
-				.cloned()
-				.collect::<Vec<_>>()
-				.join(", ");
+		if !self.is_chain_active(chain) {
+			let supported = self.get_supported_chains().join(", ");
 				
 			return Err(CrossChainError::InvalidChain(
 				chain.to_string(),
@@ -123,7 +494,7 @@ impl CrossChainRouter {
 	}
 
 	fn check_liquidity(&self, amount: f64, _source_chain: &str, _target_chain: &str) -> Result<()> {
-		let simulated_liquidity = 1000.0;
+		let simulated_liquidity = self.max_amount;
 		
 		if amount > simulated_liquidity {
 			return Err(CrossChainError::InsufficientLiquidity {
@@ -139,21 +510,23 @@ impl CrossChainRouter {
 		Ok(())
 	}
 
-	async fn simulate_bridge_transaction(&self, amount: f64, source_chain: &str, target_chain: &str) -> Result<()> {
+	async fn simulate_bridge_transaction(&self, route_id: &str, amount: f64, source_chain: &str, target_chain: &str) -> Result<()> {
 		debug!("Starting bridge transaction simulation");
 		debug!("Simulating lock transaction on source chain");
 		
+		self.tracker.transition(route_id, RouteState::Locking);
 		info!("Step 1: Locking {} tokens on {}", amount, source_chain);
 		debug!("Waiting for lock transaction confirmation...");
-		sleep(Duration::from_secs(1)).await;
+		self.clock.sleep(Duration::from_secs(1)).await;
 		
 		info!("Step 2: Generating proof for {} tokens {} -> {}", amount, source_chain, target_chain);
 		debug!("Computing merkle proof for bridge transaction...");
-		sleep(Duration::from_secs(1)).await;
+		self.clock.sleep(Duration::from_secs(1)).await;
 		
+		self.tracker.transition(route_id, RouteState::Releasing);
 		info!("Step 3: Releasing {} tokens on {}", amount, target_chain);
 		debug!("Simulating release transaction on target chain...");
-		sleep(Duration::from_secs(1)).await;
+		self.clock.sleep(Duration::from_secs(1)).await;
 		
 		debug!("Bridge transaction simulation completed successfully");
 		debug!("All bridge steps executed without errors");
@@ -161,13 +534,16 @@ impl CrossChainRouter {
 	}
 
 	pub fn get_supported_chains(&self) -> Vec<String> {
-		self.supported_chains.iter().cloned().collect()
+		let mut chains: Vec<String> = self.supported_chains.keys().cloned().collect();
+		chains.sort();
+		chains
 	}
 }
 
 #[cfg(test)]
 mod tests {
 	use super::*;
+	use crate::util::MockClock;
 
 	#[tokio::test]
 	async fn test_unsupported_chain() {
@@ -212,8 +588,94 @@ mod tests {
 
 	#[tokio::test]
 	async fn test_successful_transfer() {
+		let clock = MockClock::new(1_700_000_000);
+		let router = CrossChainRouter::with_clock(&RouterConfig::default(), clock.shared());
+		let route_id = router.route_funds(100.0, "Ethereum", "Arbitrum").await.unwrap().unwrap();
+		assert_eq!(route_id, "sim-Ethereum-Arbitrum-1700000000");
+		assert!(router.tracker().in_flight().is_empty());
+		let receipt = router.tracker().get(&route_id).unwrap();
+		// Each of the three bridge steps waits a second of the mock clock
+		assert_eq!((receipt.state, receipt.updated_at), (RouteState::Completed, 1_700_000_003));
+	}
+
+	#[tokio::test]
+	async fn test_from_config_inactive_chain() {
+		let mut config = RouterConfig::default();
+		config.chains.iter_mut()
+			.filter(|c| c.name == "Fantom")
+			.for_each(|c| c.active = false);
+		config.max_amount = 50.0;
+		let router = CrossChainRouter::from_config(&config);
+
+		let result = router.route_funds(10.0, "Ethereum", "Fantom").await;
+		assert!(matches!(
+			result.unwrap_err().downcast::<CrossChainError>(),
+			Ok(CrossChainError::InvalidChain(_, _))
+		));
+		let result = router.route_funds(60.0, "Ethereum", "Arbitrum").await;
+		assert!(matches!(
+			result.unwrap_err().downcast::<CrossChainError>(),
+			Ok(CrossChainError::InsufficientLiquidity { available, .. }) if available == 50.0
+		));
+	}
+
+	#[tokio::test]
+	async fn test_dry_run_and_observe_do_not_track_routes() {
+		let mut router = CrossChainRouter::new();
+		router.set_mode(RunMode::DryRun);
+		assert_eq!(router.route_funds(100.0, "Ethereum", "Arbitrum").await.unwrap(), None);
+		assert!(router.tracker().in_flight().is_empty());
+
+		// Validation still applies in dry-run mode
+		assert!(router.route_funds(0.05, "Ethereum", "Arbitrum").await.is_err());
+
+		// Observe mode does not even validate
+		router.set_mode(RunMode::Observe);
+		assert!(router.route_funds(0.05, "Ethereum", "Arbitrum").await.is_ok());
+		assert!(router.tracker().all().is_empty());
+	}
+
+	#[tokio::test]
+	async fn test_repeated_route_is_refused_unless_forced() {
+		let clock = MockClock::new(1_700_000_000);
+		let mut router = CrossChainRouter::with_clock(&RouterConfig::default(), clock.shared());
+		router.set_mode(RunMode::DryRun);
+		let window = RouterConfig::default().route_dedup_window_secs;
+		let mut receipt = crate::storage::tests::route("r-1", RouteState::Completed);
+		receipt.updated_at = clock.now_utc();
+		router.recent_actions().route_completed(&receipt).await;
+		clock.advance(Duration::from_secs(60));
+
+		let result = router.route_funds(100.5, "Ethereum", "Arbitrum").await;
+		assert!(matches!(
+			result.unwrap_err().downcast::<CrossChainError>(),
+			Ok(CrossChainError::DuplicateRoute { completed_at, .. }) if completed_at == receipt.updated_at
+		));
+		assert!(router.route_funds_forced(100.5, "Ethereum", "Arbitrum").await.is_ok());
+		// A different amount or direction is not a repeat
+		assert!(router.route_funds(102.0, "Ethereum", "Arbitrum").await.is_ok());
+		assert!(router.route_funds(100.0, "Arbitrum", "Ethereum").await.is_ok());
+
+		// Nor is one that completed before the window
+		clock.advance(Duration::from_secs(window - 60));
+		assert!(router.route_funds(100.5, "Ethereum", "Arbitrum").await.is_ok());
+	}
+
+	#[tokio::test]
+	async fn test_live_route_tracking() {
 		let router = CrossChainRouter::new();
-		let result = router.route_funds(100.0, "Ethereum", "Arbitrum").await;
-		assert!(result.is_ok());
+		let tracker = router.tracker();
+		let route_id = router
+			.track_live_route(50.0, "Ethereum", "Optimism", BridgeProvider::LiFi, H256::repeat_byte(0xab))
+			.unwrap();
+
+		assert_eq!(tracker.awaiting_destination().len(), 1);
+		let done = tracker.complete(&route_id, Some(H256::repeat_byte(0xcd))).unwrap();
+		assert_eq!(done.state, RouteState::Completed);
+		assert_eq!(done.destination_tx_hash, Some(H256::repeat_byte(0xcd)));
+
+		// Terminal routes can no longer change state
+		assert!(tracker.fail(&route_id, "late failure").is_none());
+		assert!(tracker.awaiting_destination().is_empty());
 	}
 }
\ No newline at end of file
@@ -1,11 +1,19 @@
+use serde::de::{Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
 use serde::{Deserialize, Serialize};
 use anyhow::{Result, Context, anyhow};
 use log::{info, warn, error, debug};
 use reqwest::Client;
+use std::sync::{Arc, RwLock};
 use std::time::Duration;
 use thiserror::Error as ThisError;
 
+use crate::config::{OptimizerConfig, ScoringMethod};
+use crate::notifier::{AgentEvent, EventSender};
+use crate::secret::redact_url;
+use crate::version::USER_AGENT;
+
 #[derive(ThisError, Debug)]
+#[non_exhaustive]
 pub enum DefiError {
 	#[error("No pools found in response")]
 	NoPoolsFound,
@@ -19,6 +27,8 @@ pub enum DefiError {
 #[cfg(test)]
 mod tests {
 	use super::*;
+	use crate::agents::test_utils::arbitrary_json;
+	use proptest::prelude::*;
 
 	#[tokio::test]
 	async fn test_pool_validation() {
@@ -65,6 +75,59 @@ mod tests {
 		assert_eq!(best_pool.tvl, 1_000_000.0);
 	}
 
+	#[tokio::test]
+	async fn test_ranked_pools_order() {
+		let optimizer = DefiOptimizer::with_mock();
+		let pools = optimizer.get_ranked_pools().await.unwrap();
+		assert_eq!(pools.len(), 2);
+		assert_eq!(pools[0].protocol, "Aave");
+		assert_eq!(pools[1].protocol, "Compound");
+		assert!(pools[0].score() >= pools[1].score());
+	}
+
+	#[tokio::test]
+	async fn test_config_filters() {
+		let config = OptimizerConfig {
+			use_mock: true,
+			min_apy: 5.0,
+			..OptimizerConfig::default()
+		};
+		let pools = DefiOptimizer::from_config(&config).get_ranked_pools().await.unwrap();
+		assert_eq!(pools.len(), 1);
+		assert_eq!(pools[0].protocol, "Aave");
+
+		let config = OptimizerConfig {
+			use_mock: true,
+			chains: vec!["Arbitrum".to_string()],
+			..OptimizerConfig::default()
+		};
+		let result = DefiOptimizer::from_config(&config).get_ranked_pools().await;
+		assert!(matches!(
+			result.unwrap_err().downcast::<DefiError>(),
+			Ok(DefiError::NoValidPools)
+		));
+	}
+
+	#[test]
+	fn test_depth_check_uses_position_value() {
+		let pool = |protocol: &str, tvl: f64| PoolData {
+			protocol: protocol.to_string(),
+			chain: "Ethereum".to_string(),
+			apy: Some(5.0),
+			tvl,
+		};
+		let pools = vec![pool("Deep", 1_000_000.0), pool("Shallow", 50_000.0)];
+		let optimizer = DefiOptimizer::new();
+		// $10k is 20% of the shallow pool, over the default 10%
+		let ranked = optimizer.rank_for(&pools, Some(10_000.0)).unwrap();
+		assert_eq!(ranked.len(), 1);
+		assert_eq!(ranked[0].protocol, "Deep");
+		// Without a valuation, or with the check off, both pass
+		assert_eq!(optimizer.rank_for(&pools, None).unwrap().len(), 2);
+		let config = OptimizerConfig { max_pool_share: 0.0, ..OptimizerConfig::default() };
+		assert_eq!(DefiOptimizer::from_config(&config).rank_for(&pools, Some(10_000.0)).unwrap().len(), 2);
+	}
+
 	#[tokio::test]
 	async fn test_empty_pool_handling() {
 		let mut optimizer = DefiOptimizer::with_mock();
@@ -76,8 +139,186 @@ mod tests {
 			Ok(DefiError::NoPoolsFound)
 		));
 	}
+
+	#[test]
+	fn test_parse_pools_drops_non_finite_numbers() {
+		let response = serde_json::json!([
+			{ "name": "Aave", "chain": "Ethereum", "apy": "NaN", "tvl": 1e6 },
+			{ "slug": "compound", "chains": ["Arbitrum"], "apy": { "base": 4.5 }, "totalLiquidityUSD": 2e6 },
+			{ "chain": "Optimism", "apy": 3.0 },
+		]);
+		let pools = parse_pools(&response.to_string()).unwrap().unwrap();
+		assert_eq!(pools.len(), 2);
+		assert_eq!((pools[0].apy, pools[0].tvl), (None, 1e6));
+		assert_eq!((pools[1].protocol.as_str(), pools[1].chain.as_str()), ("compound", "Arbitrum"));
+		assert_eq!(pools[1].apy, Some(4.5));
+		assert!(parse_pools(r#"{ "data": [] }"#).unwrap().is_none());
+		assert!(parse_pools("[{").is_err());
+	}
+
+	const POOL_KEYS: &[&str] = &["name", "slug", "tvl", "totalLiquidityUSD", "chain", "chains", "apy", "apyBase", "total", "base"];
+
+	fn any_number() -> impl Strategy<Value = f64> {
+		prop_oneof![
+			any::<f64>(),
+			Just(f64::NAN),
+			Just(f64::INFINITY),
+			Just(f64::NEG_INFINITY),
+			Just(0.0),
+			0.0..1e12,
+		]
+	}
+
+	/// The parser as it was before it read the body directly, to check the two agree.
+	fn parse_pools_before(response: &serde_json::Value) -> Option<Vec<PoolData>> {
+		let finite = |v: &serde_json::Value| v.as_f64().filter(|n| n.is_finite());
+		let pools = response
+			.as_array()?
+			.iter()
+			.filter_map(|protocol| {
+				// Only require name for basic validation
+				let name = protocol.get("name")
+					.and_then(|v| v.as_str())
+					.or_else(|| protocol.get("slug").and_then(|v| v.as_str()))?;
+	
+				// Get TVL - try multiple possible fields
+				let tvl = protocol.get("tvl")
+					.and_then(finite)
+					.or_else(|| protocol.get("totalLiquidityUSD").and_then(finite));
+	
+				// Get chain - try multiple possible fields
+				let chain = protocol.get("chain")
+					.and_then(|v| v.as_str())
+					.or_else(|| protocol.get("chains")
+						.and_then(|v| v.as_array())
+						.and_then(|arr| arr.first())
+						.and_then(|v| v.as_str()))
+					.unwrap_or("Unknown");
+	
+				// Get APY - handle multiple formats
+				let apy = protocol.get("apy")
+					.and_then(|apy_value| match apy_value {
+						serde_json::Value::Object(obj) => {
+							obj.get("total")
+								.or_else(|| obj.get("base"))
+								.and_then(finite)
+						},
+						serde_json::Value::Number(_) => finite(apy_value),
+						serde_json::Value::String(s) => s.parse::<f64>().ok().filter(|n| n.is_finite()),
+						_ => None,
+					})
+					.or_else(|| protocol.get("apyBase").and_then(finite));
+	
+				Some(PoolData {
+					protocol: name.to_string(),
+					chain: chain.to_string(),
+					apy,
+					tvl: tvl.unwrap_or(0.0),
+				})
+			})
+			.collect();
+		Some(pools)
+	}
+
+	/// Ranking as it was before pools were ranked by position with cached scores.
+	fn rank_before(optimizer: &DefiOptimizer, pools: &[PoolData], position_usd: Option<f64>) -> Vec<PoolData> {
+		let mut valid: Vec<PoolData> = pools
+			.iter()
+			.filter(|p| optimizer.passes_filters(p) && optimizer.deep_enough(p, position_usd))
+			.cloned()
+			.collect();
+		valid.sort_by(|a, b| optimizer.score(b).total_cmp(&optimizer.score(a)));
+		valid
+	}
+
+	fn any_pool() -> impl Strategy<Value = PoolData> {
+		// Few distinct values, so that equal scores are common
+		let chain = prop::sample::select(vec!["Ethereum", "arbitrum", "Base"]);
+		let apy = prop::option::of(prop::sample::select(vec![0.0, 2.5, 5.0, -1.0, f64::NAN]));
+		let tvl = prop::sample::select(vec![0.0, 5_000.0, 20_000.0, 1e6, 1e9, f64::INFINITY]);
+		(0u8..32, chain, apy, tvl).prop_map(|(id, chain, apy, tvl)| PoolData {
+			protocol: format!("P{}", id),
+			chain: chain.to_string(),
+			apy,
+			tvl,
+		})
+	}
+
+	proptest! {
+		/// Whatever the pool API answers, parsing never panics and every number it keeps
+		/// is finite.
+		#[test]
+		fn test_pool_parser_keeps_numbers_finite(response in arbitrary_json(POOL_KEYS)) {
+			let wrapped = serde_json::Value::Array(vec![response.clone()]);
+			for response in [response, wrapped] {
+				for pool in parse_pools(&response.to_string()).unwrap().unwrap_or_default() {
+					prop_assert!(pool.tvl.is_finite(), "{:?}", pool);
+					prop_assert!(pool.apy.is_none_or(f64::is_finite), "{:?}", pool);
+				}
+			}
+		}
+
+		/// Raw bodies too: garbage and deep nesting are parse errors, not panics.
+		#[test]
+		fn test_pool_response_text_never_panics(text in ".{0,128}", depth in 0usize..4_096) {
+			let _ = parse_pools(&format!("{}{}", "[".repeat(depth), text));
+		}
+
+		/// Scores are finite whatever a pool holds, and ranking orders valid pools from
+		/// best to worst score.
+		#[test]
+		fn test_ranking_is_a_total_order(
+			pools in prop::collection::vec((prop::option::of(any_number()), any_number()), 0..24),
+		) {
+			let pools: Vec<PoolData> = pools
+				.into_iter()
+				.map(|(apy, tvl)| PoolData { protocol: "P".to_string(), chain: "Ethereum".to_string(), apy, tvl })
+				.collect();
+			for pool in &pools {
+				prop_assert!(pool.score().is_finite() && pool.score_with(ScoringMethod::Apy).is_finite());
+			}
+			let optimizer = DefiOptimizer::from_config(&OptimizerConfig { min_tvl: 0.0, ..OptimizerConfig::default() });
+			if let Ok(ranked) = optimizer.rank(&pools) {
+				prop_assert!(ranked.iter().all(|p| p.is_valid()));
+				prop_assert!(ranked.windows(2).all(|w| optimizer.score(w[0]) >= optimizer.score(w[1])));
+			}
+		}
+
+		/// Parsing the body directly finds the same pools as going through a `Value`.
+		#[test]
+		fn test_pool_parser_matches_value_parser(response in arbitrary_json(POOL_KEYS)) {
+			let wrapped = serde_json::Value::Array(vec![response.clone()]);
+			for response in [response, wrapped] {
+				// Both from the same text, as floats need not survive a round trip
+				let text = response.to_string();
+				prop_assert_eq!(parse_pools(&text).unwrap(), parse_pools_before(&serde_json::from_str(&text).unwrap()));
+			}
+		}
+
+		/// Ranking with cached scores, and its top pools, match a full sort of the pools.
+		#[test]
+		fn test_ranking_matches_full_sort(
+			pools in prop::collection::vec(any_pool(), 0..64),
+			apy_scoring: bool,
+			chains: bool,
+			position_usd in prop::option::of(prop::sample::select(vec![0.0, 1_000.0, 1e5])),
+			count in 0usize..70,
+		) {
+			let optimizer = DefiOptimizer::from_config(&OptimizerConfig {
+				min_tvl: 1_000.0,
+				scoring: if apy_scoring { ScoringMethod::Apy } else { ScoringMethod::ApyLogTvl },
+				chains: if chains { vec!["ARBITRUM".to_string(), "base".to_string()] } else { Vec::new() },
+				..OptimizerConfig::default()
+			});
+			let before = rank_before(&optimizer, &pools, position_usd);
+			let ranked = optimizer.rank_for(&pools, position_usd).unwrap_or_default();
+			prop_assert!(ranked.iter().copied().eq(&before));
+			let top = optimizer.rank_top(&pools, position_usd, count).unwrap_or_default();
+			prop_assert!(top.iter().copied().eq(before.iter().take(count)));
+		}
+	}
 }
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct PoolData {
 	pub protocol: String,
 	pub chain: String,
@@ -86,32 +327,153 @@ pub struct PoolData {
 }
 
 impl PoolData {
+	/// Finite, non-negative TVL and APY; an unknown APY counts as zero.
 	pub fn is_valid(&self) -> bool {
-		self.tvl >= 0.0 && self.apy.unwrap_or(0.0) >= 0.0
+		let apy = self.apy.unwrap_or(0.0);
+		self.tvl.is_finite() && self.tvl >= 0.0 && apy.is_finite() && apy >= 0.0
+	}
+
+	/// Ranking score: APY weighted by the order of magnitude of TVL.
+	pub fn score(&self) -> f64 {
+		self.score_with(ScoringMethod::ApyLogTvl)
+	}
+
+	/// Always finite: an overflowing score becomes the largest finite one, and an
+	/// undefined one, such as an empty pool's without APY, the lowest.
+	pub fn score_with(&self, method: ScoringMethod) -> f64 {
+		let score = match method {
+			ScoringMethod::ApyLogTvl => self.apy.unwrap_or(0.0) * self.tvl.log10(),
+			ScoringMethod::Apy => self.apy.unwrap_or(0.0),
+		};
+		if score.is_nan() {
+			f64::MIN
+		} else {
+			score.clamp(f64::MIN, f64::MAX)
+		}
+	}
+}
+
+/// Pools handed to the optimizer instead of fetched, e.g. replayed from a simulation
+/// scenario. Clones share the pools, so the caller can change them between cycles.
+#[derive(Debug, Clone, Default)]
+pub struct StaticSource {
+	pools: Arc<RwLock<Vec<PoolData>>>,
+}
+
+impl StaticSource {
+	pub fn set(&self, pools: Vec<PoolData>) {
+		*self.pools.write().unwrap_or_else(|e| e.into_inner()) = pools;
+	}
+
+	pub fn pools(&self) -> Vec<PoolData> {
+		self.pools.read().unwrap_or_else(|e| e.into_inner()).clone()
 	}
 }
 
 pub struct DefiOptimizer {
 	client: Client,
 	pub use_mock: bool,
+	api_url: String,
+	min_tvl: f64,
+	min_apy: f64,
+	chains: Vec<String>,
+	scoring: ScoringMethod,
+	max_pool_share: f64,
+	events: EventSender,
+	source: Option<StaticSource>,
+}
+
+impl Default for DefiOptimizer {
+	fn default() -> Self {
+		Self::new()
+	}
 }
 
 impl DefiOptimizer {
 	pub fn new() -> Self {
+		Self::from_config(&OptimizerConfig::default())
+	}
+
+	/// Builds an optimizer from the `[optimizer]` config section.
+	///
+	/// ```
+	/// use asam::agents::defi_optimizer::DefiOptimizer;
+	/// use asam::config::OptimizerConfig;
+	///
+	/// # #[tokio::main(flavor = "current_thread")]
+	/// # async fn main() -> anyhow::Result<()> {
+	/// let config = OptimizerConfig { use_mock: true, min_apy: 5.0, ..OptimizerConfig::default() };
+	/// let optimizer = DefiOptimizer::from_config(&config);
+	/// let best = optimizer.get_best_pool().await?;
+	/// assert!(best.apy >= Some(5.0));
+	/// # Ok(())
+	/// # }
+	/// ```
+	pub fn from_config(config: &OptimizerConfig) -> Self {
 		Self { 
 			client: Client::builder()
-				.timeout(Duration::from_secs(10))
+				.timeout(Duration::from_secs(config.timeout_secs))
+				.user_agent(USER_AGENT)
 				.build()
 				.unwrap_or_default(),
-			use_mock: false,
+			use_mock: config.use_mock,
+			api_url: config.api_url.clone(),
+			min_tvl: config.min_tvl,
+			min_apy: config.min_apy,
+			chains: config.chains.clone(),
+			scoring: config.scoring,
+			max_pool_share: config.max_pool_share,
+			events: EventSender::disabled(),
+			source: None,
 		}
 	}
 
 	#[allow(dead_code)]
 	pub fn with_mock() -> Self {
 		Self {
-			client: Client::new(),
 			use_mock: true,
+			..Self::new()
+		}
+	}
+
+	pub fn set_events(&mut self, events: EventSender) {
+		self.events = events;
+	}
+
+	/// Takes pools from `source` instead of the API or the mock data.
+	pub fn set_source(&mut self, source: StaticSource) {
+		self.source = Some(source);
+	}
+
+	/// Replaces the data source, filters and scoring, keeping the event sender and
+	/// any static source.
+	pub fn set_config(&mut self, config: &OptimizerConfig) {
+		let events = std::mem::take(&mut self.events);
+		let source = self.source.take();
+		*self = Self::from_config(config);
+		self.events = events;
+		self.source = source;
+	}
+
+	/// Scores `pool` with the configured scoring method.
+	pub fn score(&self, pool: &PoolData) -> f64 {
+		pool.score_with(self.scoring)
+	}
+
+	/// Applies the configured TVL, APY and chain filters on top of basic validity.
+	fn passes_filters(&self, pool: &PoolData) -> bool {
+		pool.is_valid()
+			&& pool.tvl >= self.min_tvl
+			&& pool.apy.unwrap_or(0.0) >= self.min_apy
+			&& (self.chains.is_empty() || self.chains.iter().any(|c| c.eq_ignore_ascii_case(&pool.chain)))
+	}
+
+	/// Whether a position worth `position_usd` stays within the configured share of
+	/// `pool`'s TVL. Passes when the position's value is unknown.
+	fn deep_enough(&self, pool: &PoolData, position_usd: Option<f64>) -> bool {
+		match position_usd {
+			Some(usd) if self.max_pool_share > 0.0 => usd <= pool.tvl * self.max_pool_share,
+			_ => true,
 		}
 	}
 
@@ -156,7 +518,36 @@ impl DefiOptimizer {
 
 	pub async fn get_best_pool(&self) -> Result<PoolData> {
 		debug!("Starting DeFi pool optimization process");
-		let pools = if self.use_mock {
+		let best_pool = self.get_ranked_pools().await?
+			.into_iter()
+			.next()
+			.context("Failed to find best pool")?;
+
+		info!(
+			"Optimal pool identified: {} on {} (APY: {:.2}%, TVL: ${:.2})",
+			best_pool.protocol,
+			best_pool.chain,
+			best_pool.apy.unwrap_or(0.0),
+			best_pool.tvl
+		);
+		debug!("Pool optimization process completed successfully");
+
+		Ok(best_pool)
+	}
+
+	/// Returns all valid pools ordered from best to worst score.
+	pub async fn get_ranked_pools(&self) -> Result<Vec<PoolData>> {
+		let pools = self.fetch_all().await?;
+		let ranked = self.rank(&pools).inspect_err(|_| error!("All pools failed validation criteria"))?;
+		Ok(ranked.into_iter().cloned().collect())
+	}
+
+	/// Every pool the data source returns, before any filtering.
+	pub async fn fetch_all(&self) -> Result<Vec<PoolData>> {
+		let pools = if let Some(source) = &self.source {
+			debug!("Using static pool data");
+			source.pools()
+		} else if self.use_mock {
 			debug!("Using mock data for pool analysis");
 			Self::get_mock_data()
 		} else {
@@ -171,49 +562,77 @@ impl DefiOptimizer {
 			error!("Please check API connectivity and try again");
 			return Err(anyhow!(DefiError::NoPoolsFound));
 		}
+		Ok(pools)
+	}
+
+	/// The `pools` passing the configured filters, ordered from best to worst score.
+	pub fn rank<'a>(&self, pools: &'a [PoolData]) -> Result<Vec<&'a PoolData>> {
+		self.rank_for(pools, None)
+	}
+
+	/// Like [`rank`](Self::rank), also leaving out pools too shallow for a position
+	/// worth `position_usd`.
+	pub fn rank_for<'a>(&self, pools: &'a [PoolData], position_usd: Option<f64>) -> Result<Vec<&'a PoolData>> {
+		self.rank_top(pools, position_usd, usize::MAX)
+	}
+
+	/// The best `count` pools of [`rank_for`](Self::rank_for), in the same order, found
+	/// without sorting the rest.
+	pub fn rank_top<'a>(
+		&self,
+		pools: &'a [PoolData],
+		position_usd: Option<f64>,
+		count: usize,
+	) -> Result<Vec<&'a PoolData>> {
+		Ok(self.ranked_indices(pools, position_usd, count)?.into_iter().map(|i| &pools[i]).collect())
+	}
 
+	/// Positions in `pools` of the best `count` passing the filters, best first; equal
+	/// scores keep the order of `pools`. Each pool is scored once and only the selected
+	/// ones are sorted.
+	pub(crate) fn ranked_indices(&self, pools: &[PoolData], position_usd: Option<f64>, count: usize) -> Result<Vec<usize>> {
 		debug!("Filtering pools based on APY and TVL criteria");
-		let valid_pools: Vec<_> = pools.into_iter()
-			.filter(|p| p.is_valid())
+		let mut filtered = 0;
+		let mut scored: Vec<(f64, usize)> = pools
+			.iter()
+			.enumerate()
+			.filter(|(_, p)| self.passes_filters(p))
+			.inspect(|_| filtered += 1)
+			.filter(|(_, p)| self.deep_enough(p, position_usd))
+			.map(|(i, p)| (self.score(p), i))
 			.collect();
+		if scored.len() < filtered {
+			info!(
+				"Skipped {} pools too shallow for a ${:.0} position",
+				filtered - scored.len(),
+				position_usd.unwrap_or_default()
+			);
+		}
 
-		info!("Found {} pools with valid APY and TVL metrics", valid_pools.len());
+		info!("Found {} pools with valid APY and TVL metrics", scored.len());
 
-		if valid_pools.is_empty() {
+		if scored.is_empty() {
 			warn!("No pools found with valid APY and TVL values");
-			error!("All pools failed validation criteria");
 			return Err(anyhow!(DefiError::NoValidPools));
 		}
 
-		debug!("Calculating optimal pool based on APY and TVL metrics");
-		let best_pool = valid_pools.into_iter()
-			.max_by(|a, b| {
-				let a_score = a.apy.unwrap_or(0.0) * a.tvl.log10();
-				let b_score = b.apy.unwrap_or(0.0) * b.tvl.log10();
-				a_score.partial_cmp(&b_score).unwrap_or(std::cmp::Ordering::Equal)
-			})
-			.context("Failed to find best pool")?;
-
-		info!(
-			"Optimal pool identified: {} on {} (APY: {:.2}%, TVL: ${:.2})",
-			best_pool.protocol,
-			best_pool.chain,
-			best_pool.apy.unwrap_or(0.0),
-			best_pool.tvl
-		);
-		debug!("Pool optimization process completed successfully");
-
-		Ok(best_pool)
+		debug!("Ranking pools based on APY and TVL metrics");
+		let best_first = |a: &(f64, usize), b: &(f64, usize)| b.0.total_cmp(&a.0).then(a.1.cmp(&b.1));
+		if count < scored.len() {
+			scored.select_nth_unstable_by(count, best_first);
+			scored.truncate(count);
+		}
+		scored.sort_unstable_by(best_first);
+		Ok(scored.into_iter().map(|(_, i)| i).collect())
 	}
 
 	async fn fetch_pools(&self) -> Result<Vec<PoolData>> {
-		let url = std::env::var("DEFI_API_URL")
-			.unwrap_or_else(|_| "https://api.llama.fi/protocols".to_string());
+		let url = &self.api_url;
 		
-		info!("Initiating pool data fetch from {}", url);
+		info!("Initiating pool data fetch from {}", redact_url(url));
 		debug!("Sending API request to DeFi data provider");
 
-		let response = self.client.get(&url)
+		let response = self.client.get(url)
 			.send()
 			.await
 			.context("Failed to send API request")?;
@@ -229,74 +648,335 @@ impl DefiOptimizer {
 		let text = response.text().await
 			.context("Failed to read response body")?;
 		
-		let protocols: serde_json::Value = serde_json::from_str(&text)
-			.context("Failed to parse API response")?;
-
 		debug!("Processing protocol data from response");
+		let parsed = parse_pools(&text).context("Failed to parse API response")?;
+		let Some(pools) = parsed else {
+			let error_msg = "API response is not an array of protocols";
+			error!("{}", error_msg);
+			error!("Unexpected API response format");
+			self.events.emit(AgentEvent::SchemaDriftSuspected {
+				source: url.clone(),
+				detail: error_msg.to_string(),
+			});
+			return Err(DefiError::ApiError(error_msg.to_string()).into());
+		};
+		// Counting the entries takes another pass, only needed when none was a pool
+		let entries = if pools.is_empty() {
+			serde_json::from_str::<Vec<IgnoredAny>>(&text).map_or(0, |entries| entries.len())
+		} else {
+			0
+		};
+		if entries > 0 {
+			warn!("None of the {} protocol entries had a name - the API schema may have changed", entries);
+			self.events.emit(AgentEvent::SchemaDriftSuspected {
+				source: url.clone(),
+				detail: format!("no named protocols in {} entries", entries),
+			});
+		}
 
+		info!("Successfully processed {} pools from API", pools.len());
+		debug!("Pool data fetch and processing completed");
+		Ok(pools)
+	}
+}
 
+/// Pools in a pool API response body: an array of protocol entries, of which those
+/// with a name become pools. `None` when the response is not an array, an error when
+/// it is not JSON.
+///
+/// Whatever the entries hold, every pool comes out with a finite APY or none and a
+/// finite TVL, so nothing non-finite reaches scoring. The body is read in one pass
+/// without building a `serde_json::Value`; fields other than the pool's are skipped.
+pub fn parse_pools(body: &str) -> serde_json::Result<Option<Vec<PoolData>>> {
+	serde_json::from_str::<PoolResponse>(body).map(|response| response.0)
+}
 
-		let mut pools = Vec::new();
+/// Visitor methods taking any JSON scalar as `$value`.
+macro_rules! accept_scalars {
+	($value:expr) => {
+		fn visit_bool<E>(self, _: bool) -> Result<Self::Value, E> {
+			Ok($value)
+		}
+		fn visit_i64<E>(self, _: i64) -> Result<Self::Value, E> {
+			Ok($value)
+		}
+		fn visit_u64<E>(self, _: u64) -> Result<Self::Value, E> {
+			Ok($value)
+		}
+		fn visit_f64<E>(self, _: f64) -> Result<Self::Value, E> {
+			Ok($value)
+		}
+		fn visit_str<E>(self, _: &str) -> Result<Self::Value, E> {
+			Ok($value)
+		}
+		fn visit_unit<E>(self) -> Result<Self::Value, E> {
+			Ok($value)
+		}
+	};
+}
 
-		if let Some(protocol_array) = protocols.as_array() {
-			for protocol in protocol_array.iter() {
-				// Get protocol name
-				let name = protocol.get("name")
-					.and_then(|v| v.as_str())
-					.or_else(|| protocol.get("slug").and_then(|v| v.as_str()));
+/// The pools of a response, `None` unless it is an array.
+struct PoolResponse(Option<Vec<PoolData>>);
 
-				// Get TVL - try multiple possible fields
-				let tvl = protocol.get("tvl")
-					.and_then(|v| v.as_f64())
-					.or_else(|| protocol.get("totalLiquidityUSD").and_then(|v| v.as_f64()));
+impl<'de> Deserialize<'de> for PoolResponse {
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		struct ResponseVisitor;
 
-				// Get chain - try multiple possible fields
-				let chain = protocol.get("chain")
-					.and_then(|v| v.as_str())
-					.or_else(|| protocol.get("chains")
-						.and_then(|v| v.as_array())
-						.and_then(|arr| arr.first())
-						.and_then(|v| v.as_str()))
-					.unwrap_or("Unknown");
+		impl<'de> Visitor<'de> for ResponseVisitor {
+			type Value = PoolResponse;
 
-				// Get APY - handle multiple formats
-				let apy = protocol.get("apy")
-					.and_then(|apy_value| match apy_value {
-						serde_json::Value::Object(obj) => {
-							obj.get("total")
-								.or_else(|| obj.get("base"))
-								.and_then(|v| v.as_f64())
-						},
-						serde_json::Value::Number(num) => num.as_f64(),
-						serde_json::Value::String(s) => s.parse::<f64>().ok(),
-						_ => None,
-					})
-					.or_else(|| {
-						protocol.get("apyBase")
-							.and_then(|v| v.as_f64())
-					});
+			fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+				f.write_str("a pool API response")
+			}
 
-				// Only require name for basic validation
-				if let Some(name) = name {
-					pools.push(PoolData {
-						protocol: name.to_string(),
-						chain: chain.to_string(),
-						apy,
-						tvl: tvl.unwrap_or(0.0),
-					});
+			accept_scalars!(PoolResponse(None));
+
+			fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+				while map.next_entry::<IgnoredAny, IgnoredAny>()?.is_some() {}
+				Ok(PoolResponse(None))
+			}
+
+			fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+				let mut pools = Vec::with_capacity(seq.size_hint().unwrap_or_default());
+				while let Some(entry) = seq.next_element::<ProtocolEntry>()? {
+					pools.extend(entry.into_pool());
 				}
+				Ok(PoolResponse(Some(pools)))
 			}
-		} else {
-			let error_msg = "API response is not an array of protocols";
-			error!("{}", error_msg);
-			error!("Unexpected API response format");
-			return Err(DefiError::ApiError(error_msg.to_string()).into());
 		}
 
-		info!("Successfully processed {} pools from API", pools.len());
-		debug!("Pool data fetch and processing completed");
-		Ok(pools)
+		deserializer.deserialize_any(ResponseVisitor)
+	}
+}
+
+/// The fields of a protocol entry that make up a pool; each one keeps the last value
+/// given for its key, as a JSON object would.
+#[derive(Default)]
+struct ProtocolEntry {
+	name: Option<Field>,
+	slug: Option<Field>,
+	tvl: Option<Field>,
+	total_liquidity: Option<Field>,
+	chain: Option<Field>,
+	chains: Option<Field>,
+	apy: Option<Field>,
+	apy_base: Option<Field>,
+}
+
+impl ProtocolEntry {
+	fn into_pool(self) -> Option<PoolData> {
+		// Only require name for basic validation
+		let name = self.name.and_then(Field::into_text).or_else(|| self.slug.and_then(Field::into_text))?;
+
+		// Get TVL - try multiple possible fields
+		let tvl = self.tvl.and_then(Field::finite).or_else(|| self.total_liquidity.and_then(Field::finite));
+
+		// Get chain - try multiple possible fields
+		let chain = self.chain
+			.and_then(Field::into_text)
+			.or_else(|| self.chains.and_then(Field::first_text))
+			.unwrap_or_else(|| "Unknown".to_string());
+
+		// Get APY - handle multiple formats
+		let apy = self.apy
+			.and_then(|apy| match apy {
+				Field::Object { total, base } => total.or(base).flatten(),
+				Field::Number(_) => apy.finite(),
+				Field::Text(s) => s.parse::<f64>().ok().filter(|n| n.is_finite()),
+				_ => None,
+			})
+			.or_else(|| self.apy_base.and_then(Field::finite));
+
+		Some(PoolData { protocol: name, chain, apy, tvl: tvl.unwrap_or(0.0) })
+	}
+}
+
+impl<'de> Deserialize<'de> for ProtocolEntry {
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		struct EntryVisitor;
+
+		impl<'de> Visitor<'de> for EntryVisitor {
+			type Value = ProtocolEntry;
+
+			fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+				f.write_str("a protocol entry")
+			}
+
+			accept_scalars!(ProtocolEntry::default());
+
+			fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+				while seq.next_element::<IgnoredAny>()?.is_some() {}
+				Ok(ProtocolEntry::default())
+			}
+
+			fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+				let mut entry = ProtocolEntry::default();
+				while let Some(key) = map.next_key::<Key>()? {
+					let field = match key {
+						Key::Name => &mut entry.name,
+						Key::Slug => &mut entry.slug,
+						Key::Tvl => &mut entry.tvl,
+						Key::TotalLiquidity => &mut entry.total_liquidity,
+						Key::Chain => &mut entry.chain,
+						Key::Chains => &mut entry.chains,
+						Key::Apy => &mut entry.apy,
+						Key::ApyBase => &mut entry.apy_base,
+						_ => {
+							map.next_value::<IgnoredAny>()?;
+							continue;
+						}
+					};
+					*field = Some(map.next_value()?);
+				}
+				Ok(entry)
+			}
+		}
+
+		deserializer.deserialize_any(EntryVisitor)
 	}
 }
 
+/// Keys of the objects the parser looks into, read without allocating.
+enum Key {
+	Name,
+	Slug,
+	Tvl,
+	TotalLiquidity,
+	Chain,
+	Chains,
+	Apy,
+	ApyBase,
+	Total,
+	Base,
+	Other,
+}
+
+impl<'de> Deserialize<'de> for Key {
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		struct KeyVisitor;
+
+		impl Visitor<'_> for KeyVisitor {
+			type Value = Key;
+
+			fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+				f.write_str("an object key")
+			}
+
+			fn visit_str<E>(self, key: &str) -> Result<Key, E> {
+				Ok(match key {
+					"name" => Key::Name,
+					"slug" => Key::Slug,
+					"tvl" => Key::Tvl,
+					"totalLiquidityUSD" => Key::TotalLiquidity,
+					"chain" => Key::Chain,
+					"chains" => Key::Chains,
+					"apy" => Key::Apy,
+					"apyBase" => Key::ApyBase,
+					"total" => Key::Total,
+					"base" => Key::Base,
+					_ => Key::Other,
+				})
+			}
+		}
+
+		deserializer.deserialize_identifier(KeyVisitor)
+	}
+}
+
+/// As much of a field's value as a pool can use: a number, a string, the first
+/// element of an array if it is a string, or an object's `total` and `base`.
+enum Field {
+	Number(f64),
+	Text(String),
+	List(Option<String>),
+	/// Each is `Some` when the key is present, holding its value if finite.
+	Object { total: Option<Option<f64>>, base: Option<Option<f64>> },
+	Other,
+}
+
+impl Field {
+	fn finite(self) -> Option<f64> {
+		match self {
+			Field::Number(n) if n.is_finite() => Some(n),
+			_ => None,
+		}
+	}
 
+	fn into_text(self) -> Option<String> {
+		match self {
+			Field::Text(s) => Some(s),
+			_ => None,
+		}
+	}
+
+	fn first_text(self) -> Option<String> {
+		match self {
+			Field::List(first) => first,
+			_ => None,
+		}
+	}
+}
+
+impl<'de> Deserialize<'de> for Field {
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		struct FieldVisitor;
+
+		impl<'de> Visitor<'de> for FieldVisitor {
+			type Value = Field;
+
+			fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+				f.write_str("a JSON value")
+			}
+
+			fn visit_bool<E>(self, _: bool) -> Result<Field, E> {
+				Ok(Field::Other)
+			}
+
+			fn visit_i64<E>(self, n: i64) -> Result<Field, E> {
+				Ok(Field::Number(n as f64))
+			}
+
+			fn visit_u64<E>(self, n: u64) -> Result<Field, E> {
+				Ok(Field::Number(n as f64))
+			}
+
+			fn visit_f64<E>(self, n: f64) -> Result<Field, E> {
+				Ok(Field::Number(n))
+			}
+
+			fn visit_str<E>(self, s: &str) -> Result<Field, E> {
+				Ok(Field::Text(s.to_string()))
+			}
+
+			fn visit_string<E>(self, s: String) -> Result<Field, E> {
+				Ok(Field::Text(s))
+			}
+
+			fn visit_unit<E>(self) -> Result<Field, E> {
+				Ok(Field::Other)
+			}
+
+			fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Field, A::Error> {
+				let first = seq.next_element::<Field>()?.and_then(Field::into_text);
+				while seq.next_element::<IgnoredAny>()?.is_some() {}
+				Ok(Field::List(first))
+			}
+
+			fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Field, A::Error> {
+				let (mut total, mut base) = (None, None);
+				while let Some(key) = map.next_key::<Key>()? {
+					match key {
+						Key::Total => total = Some(map.next_value::<Field>()?.finite()),
+						Key::Base => base = Some(map.next_value::<Field>()?.finite()),
+						_ => {
+							map.next_value::<IgnoredAny>()?;
+						}
+					}
+				}
+				Ok(Field::Object { total, base })
+			}
+		}
+
+		deserializer.deserialize_any(FieldVisitor)
+	}
+}
@@ -1,11 +1,19 @@
 pub mod safe_manager;
+pub mod signer;
 pub mod defi_optimizer;
 pub mod cross_chain_router;
+pub mod route_status;
+pub mod transaction_service;
+pub mod swap;
 
-#[cfg(test)]
-pub(crate) mod test_utils {
+/// Fixtures shared by the unit tests; enable the `test-utils` feature to use them downstream.
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_utils {
 	use ethers::core::types::{Address, U256};
 	use std::str::FromStr;
+	use std::time::Duration;
+
+	use crate::rpc::{FailoverClient, RpcProvider};
 
 	pub fn get_test_address() -> Address {
 		Address::from_str("0x0000000000000000000000000000000000000000").unwrap()
@@ -16,9 +24,70 @@ pub(crate) mod test_utils {
 		U256::from(wei as u64)
 	}
 
-	pub fn setup_test_env() {
-		std::env::set_var("RUST_LOG", "debug");
-		std::env::set_var("ETH_RPC_URL", "http://localhost:8545");
-		std::env::set_var("ACCOUNT_ADDRESS", "0x0000000000000000000000000000000000000000");
+	/// Provider for a single endpoint, as the agent builds it from config.
+	pub fn test_provider(url: &str) -> RpcProvider {
+		FailoverClient::new(&[url], Duration::from_secs(10), Duration::from_secs(30))
+			.unwrap()
+			.into_provider()
+			.0
+	}
+
+	/// A node answering each JSON-RPC method of `answers` with its result, whatever the
+	/// params.
+	#[cfg(test)]
+	pub async fn mock_node(answers: &[(&str, serde_json::Value)]) -> wiremock::MockServer {
+		let node = wiremock::MockServer::start().await;
+		mount_answers(&node, answers).await;
+		node
+	}
+
+	/// Mounts the answers of [`mock_node`] on `node`, after the mocks already there.
+	#[cfg(test)]
+	pub async fn mount_answers(node: &wiremock::MockServer, answers: &[(&str, serde_json::Value)]) {
+		use wiremock::matchers::body_partial_json;
+		use wiremock::{Mock, ResponseTemplate};
+
+		for (method, result) in answers {
+			Mock::given(body_partial_json(serde_json::json!({ "method": method })))
+				.respond_with(
+					ResponseTemplate::new(200).set_body_json(serde_json::json!({ "jsonrpc": "2.0", "id": 1, "result": result })),
+				)
+				.mount(node)
+				.await;
+		}
+	}
+
+	/// Adversarial JSON for parser property tests: nested arrays and objects whose keys
+	/// are mostly `keys`, with wrong types, extreme numbers, numeric-looking strings
+	/// such as "NaN" and "1e999", and long strings among the leaves.
+	#[cfg(test)]
+	pub fn arbitrary_json(
+		keys: &'static [&'static str],
+	) -> impl proptest::strategy::Strategy<Value = serde_json::Value> {
+		use proptest::prelude::*;
+		use serde_json::Value;
+
+		let number = prop_oneof![
+			any::<i64>().prop_map(Value::from),
+			any::<u64>().prop_map(Value::from),
+			// Non-finite floats become null
+			any::<f64>().prop_map(Value::from),
+			prop::sample::select(vec![f64::MAX, f64::MIN, f64::MIN_POSITIVE, -0.0, 1e300]).prop_map(Value::from),
+		];
+		let text = prop_oneof![
+			".{0,32}".prop_map(Value::String),
+			prop::sample::select(vec!["NaN", "inf", "-inf", "1e999", "-5", "0x10", "", "DONE", "COMPLETED", "FAILED"])
+				.prop_map(|s| Value::String(s.to_string())),
+			(0usize..20_000).prop_map(|n| Value::String("9".repeat(n))),
+		];
+		let leaf = prop_oneof![Just(Value::Null), any::<bool>().prop_map(Value::Bool), number, text];
+		let key = prop_oneof![4 => prop::sample::select(keys).prop_map(str::to_string), 1 => ".{0,8}"];
+		leaf.prop_recursive(5, 64, 8, move |inner| {
+			prop_oneof![
+				prop::collection::vec(inner.clone(), 0..8).prop_map(Value::Array),
+				prop::collection::vec((key.clone(), inner), 0..8)
+					.prop_map(|entries| Value::Object(entries.into_iter().collect())),
+			]
+		})
 	}
 }
@@ -0,0 +1,476 @@
+use anyhow::{Context, Result};
+use ethers::core::types::H256;
+use log::{debug, info, warn, error};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::str::FromStr;
+use thiserror::Error;
+use tokio::sync::broadcast;
+use tokio::time::{sleep, Duration};
+use tokio_util::sync::CancellationToken;
+
+use super::cross_chain_router::{BridgeProvider, RouteReceipt, RouteTracker};
+use crate::config::RouteStatusConfig;
+use crate::secret::Secret;
+use crate::version::USER_AGENT;
+
+#[derive(Error, Debug)]
+#[non_exhaustive]
+pub enum RouteStatusError {
+	#[error("Status request to {provider} failed: {reason}")]
+	RequestFailed { provider: String, reason: String },
+	#[error("Unknown chain '{0}' for status lookup")]
+	UnknownChain(String),
+	#[error("Route {0} has no source transaction hash")]
+	MissingSourceHash(String),
+}
+
+/// Outcome reported by the poller once a live route reaches a terminal state.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum RouteEvent {
+	Completed { route_id: String, destination_tx_hash: Option<H256> },
+	Failed { route_id: String, reason: String },
+}
+
+/// Normalized status across bridge providers.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProviderStatus {
+	Pending,
+	Done { destination_tx_hash: Option<H256> },
+	Failed(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct StatusPollerConfig {
+	pub interval: Duration,
+	pub max_backoff: Duration,
+	pub lifi_base_url: String,
+	pub socket_base_url: String,
+	pub socket_api_key: Option<Secret<String>>,
+}
+
+impl Default for StatusPollerConfig {
+	fn default() -> Self {
+		Self {
+			interval: Duration::from_secs(30),
+			max_backoff: Duration::from_secs(600),
+			lifi_base_url: "https://li.quest".to_string(),
+			socket_base_url: "https://api.socket.tech".to_string(),
+			socket_api_key: None,
+		}
+	}
+}
+
+impl StatusPollerConfig {
+	pub fn from_config(config: &RouteStatusConfig) -> Self {
+		Self {
+			interval: Duration::from_secs(config.interval_secs),
+			max_backoff: Duration::from_secs(config.max_backoff_secs),
+			lifi_base_url: config.lifi_api_url.clone(),
+			socket_base_url: config.socket_api_url.clone(),
+			socket_api_key: config.socket_api_key.clone(),
+		}
+	}
+}
+
+#[derive(Debug, Deserialize)]
+struct LiFiStatusResponse {
+	status: String,
+	#[serde(default)]
+	substatus: Option<String>,
+	#[serde(default)]
+	receiving: Option<LiFiTxInfo>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LiFiTxInfo {
+	#[serde(default)]
+	tx_hash: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SocketStatusResponse {
+	result: SocketStatusResult,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SocketStatusResult {
+	#[serde(default)]
+	source_tx_status: Option<String>,
+	#[serde(default)]
+	destination_tx_status: Option<String>,
+	#[serde(default)]
+	destination_transaction_hash: Option<String>,
+}
+
+/// Background task following live routes in `Releasing` state until the provider
+/// reports them done or failed on the destination chain.
+#[derive(Clone)]
+pub struct RouteStatusPoller {
+	client: Client,
+	tracker: RouteTracker,
+	config: StatusPollerConfig,
+	chain_ids: HashMap<String, u64>,
+	events: broadcast::Sender<RouteEvent>,
+}
+
+impl RouteStatusPoller {
+	pub fn new(tracker: RouteTracker, chain_ids: HashMap<String, u64>, config: StatusPollerConfig) -> Self {
+		let (events, _) = broadcast::channel(64);
+		Self {
+			client: Client::builder()
+				.timeout(Duration::from_secs(10))
+				.user_agent(USER_AGENT)
+				.build()
+				.unwrap_or_default(),
+			tracker,
+			config,
+			chain_ids,
+			events,
+		}
+	}
+
+	pub fn subscribe(&self) -> broadcast::Receiver<RouteEvent> {
+		self.events.subscribe()
+	}
+
+	/// Polls until `shutdown` fires, backing off exponentially while providers error.
+	pub async fn run(self, shutdown: CancellationToken) {
+		info!("Route status poller started (interval: {:?})", self.config.interval);
+		let mut delay = self.config.interval;
+
+		loop {
+			tokio::select! {
+				_ = shutdown.cancelled() => {
+					info!("Route status poller shutting down");
+					break;
+				}
+				_ = sleep(delay) => {}
+			}
+
+			let failures = self.poll_once().await;
+			delay = if failures > 0 {
+				let next = (delay * 2).min(self.config.max_backoff);
+				warn!("{} route status request(s) failed, next poll in {:?}", failures, next);
+				next
+			} else {
+				self.config.interval
+			};
+		}
+	}
+
+	/// Checks every route awaiting its destination once. Returns the number of failed lookups.
+	pub async fn poll_once(&self) -> usize {
+		let routes = self.tracker.awaiting_destination();
+		if routes.is_empty() {
+			return 0;
+		}
+		debug!("Polling status for {} route(s) awaiting destination", routes.len());
+
+		let mut failures = 0;
+		for route in routes {
+			match self.fetch_status(&route).await {
+				Ok(status) => self.apply_status(&route, status),
+				Err(e) => {
+					warn!("Status lookup for route {} failed: {}", route.route_id, e);
+					failures += 1;
+				}
+			}
+		}
+		failures
+	}
+
+	fn apply_status(&self, route: &RouteReceipt, status: ProviderStatus) {
+		match status {
+			ProviderStatus::Pending => {
+				debug!("Route {} still pending on {}", route.route_id, route.target_chain);
+			}
+			ProviderStatus::Done { destination_tx_hash } => {
+				if self.tracker.complete(&route.route_id, destination_tx_hash).is_some() {
+					info!(
+						"Route {} completed on {} (destination tx: {:?})",
+						route.route_id, route.target_chain, destination_tx_hash
+					);
+					let _ = self.events.send(RouteEvent::Completed {
+						route_id: route.route_id.clone(),
+						destination_tx_hash,
+					});
+				}
+			}
+			ProviderStatus::Failed(reason) => {
+				if self.tracker.fail(&route.route_id, &reason).is_some() {
+					error!("Route {} failed: {}", route.route_id, reason);
+					let _ = self.events.send(RouteEvent::Failed {
+						route_id: route.route_id.clone(),
+						reason,
+					});
+				}
+			}
+		}
+	}
+
+	async fn fetch_status(&self, route: &RouteReceipt) -> Result<ProviderStatus> {
+		let tx_hash = route.source_tx_hash
+			.ok_or_else(|| RouteStatusError::MissingSourceHash(route.route_id.clone()))?;
+		let from_chain = self.lookup_chain_id(&route.source_chain)?;
+		let to_chain = self.lookup_chain_id(&route.target_chain)?;
+
+		match route.provider {
+			BridgeProvider::LiFi => self.fetch_lifi_status(tx_hash, from_chain, to_chain).await,
+			BridgeProvider::Socket => self.fetch_socket_status(tx_hash, from_chain, to_chain).await,
+			BridgeProvider::Simulated => Ok(ProviderStatus::Pending),
+		}
+	}
+
+	fn lookup_chain_id(&self, chain: &str) -> Result<u64> {
+		self.chain_ids
+			.get(chain)
+			.copied()
+			.ok_or_else(|| RouteStatusError::UnknownChain(chain.to_string()).into())
+	}
+
+	async fn fetch_lifi_status(&self, tx_hash: H256, from_chain: u64, to_chain: u64) -> Result<ProviderStatus> {
+		let url = format!("{}/v1/status", self.config.lifi_base_url);
+		let response = self.client.get(&url)
+			.query(&[
+				("txHash", format!("{:?}", tx_hash)),
+				("fromChain", from_chain.to_string()),
+				("toChain", to_chain.to_string()),
+			])
+			.send()
+			.await
+			.context("Failed to send LI.FI status request")?;
+
+		if !response.status().is_success() {
+			return Err(RouteStatusError::RequestFailed {
+				provider: "LI.FI".to_string(),
+				reason: format!("status {}", response.status()),
+			}.into());
+		}
+
+		let body: LiFiStatusResponse = response.json().await
+			.context("Failed to parse LI.FI status response")?;
+		Ok(lifi_status(body))
+	}
+
+	async fn fetch_socket_status(&self, tx_hash: H256, from_chain: u64, to_chain: u64) -> Result<ProviderStatus> {
+		let url = format!("{}/v2/bridge-status", self.config.socket_base_url);
+		let mut request = self.client.get(&url)
+			.query(&[
+				("transactionHash", format!("{:?}", tx_hash)),
+				("fromChainId", from_chain.to_string()),
+				("toChainId", to_chain.to_string()),
+			]);
+		if let Some(key) = &self.config.socket_api_key {
+			request = request.header("API-KEY", key.expose());
+		}
+
+		let response = request.send()
+			.await
+			.context("Failed to send Socket status request")?;
+
+		if !response.status().is_success() {
+			return Err(RouteStatusError::RequestFailed {
+				provider: "Socket".to_string(),
+				reason: format!("status {}", response.status()),
+			}.into());
+		}
+
+		let body: SocketStatusResponse = response.json().await
+			.context("Failed to parse Socket status response")?;
+		Ok(socket_status(body.result))
+	}
+}
+
+/// What a LI.FI status answer says; unknown statuses are still pending and a
+/// malformed destination hash is left out.
+fn lifi_status(body: LiFiStatusResponse) -> ProviderStatus {
+	match body.status.as_str() {
+		"DONE" => ProviderStatus::Done {
+			destination_tx_hash: body.receiving
+				.and_then(|r| r.tx_hash)
+				.and_then(|h| H256::from_str(&h).ok()),
+		},
+		"FAILED" | "INVALID" => ProviderStatus::Failed(
+			body.substatus.unwrap_or_else(|| body.status.clone())
+		),
+		_ => ProviderStatus::Pending,
+	}
+}
+
+/// What a Socket status answer says, read the same way as [`lifi_status`].
+fn socket_status(result: SocketStatusResult) -> ProviderStatus {
+	if result.source_tx_status.as_deref() == Some("FAILED") {
+		return ProviderStatus::Failed("source transaction failed".to_string());
+	}
+	match result.destination_tx_status.as_deref() {
+		Some("COMPLETED") => ProviderStatus::Done {
+			destination_tx_hash: result.destination_transaction_hash
+				.and_then(|h| H256::from_str(&h).ok()),
+		},
+		Some("FAILED") => ProviderStatus::Failed("destination transaction failed".to_string()),
+		_ => ProviderStatus::Pending,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::agents::cross_chain_router::{CrossChainRouter, RouteState};
+	use crate::agents::test_utils::arbitrary_json;
+	use proptest::prelude::*;
+	use wiremock::matchers::{header, method, path, query_param};
+	use wiremock::{Mock, MockServer, ResponseTemplate};
+
+	fn test_config(server: &MockServer) -> StatusPollerConfig {
+		StatusPollerConfig {
+			interval: Duration::from_millis(10),
+			max_backoff: Duration::from_millis(40),
+			lifi_base_url: server.uri(),
+			socket_base_url: server.uri(),
+			socket_api_key: Some("test-key".into()),
+		}
+	}
+
+	fn chain_ids() -> HashMap<String, u64> {
+		[("Ethereum".to_string(), 1), ("Arbitrum".to_string(), 42161)].into_iter().collect()
+	}
+
+	#[tokio::test]
+	async fn test_lifi_pending_then_done() {
+		let server = MockServer::start().await;
+		let dest_hash = format!("{:?}", H256::repeat_byte(0x22));
+
+		Mock::given(method("GET"))
+			.and(path("/v1/status"))
+			.and(query_param("fromChain", "1"))
+			.and(query_param("toChain", "42161"))
+			.respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+				"status": "PENDING",
+				"substatus": "WAIT_DESTINATION_TRANSACTION"
+			})))
+			.up_to_n_times(1)
+			.mount(&server)
+			.await;
+		Mock::given(method("GET"))
+			.and(path("/v1/status"))
+			.respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+				"status": "DONE",
+				"substatus": "COMPLETED",
+				"receiving": { "txHash": dest_hash }
+			})))
+			.mount(&server)
+			.await;
+
+		let router = CrossChainRouter::new();
+		let tracker = router.tracker();
+		let route_id = router
+			.track_live_route(10.0, "Ethereum", "Arbitrum", BridgeProvider::LiFi, H256::repeat_byte(0x11))
+			.unwrap();
+		let poller = RouteStatusPoller::new(tracker.clone(), chain_ids(), test_config(&server));
+		let mut events = poller.subscribe();
+
+		assert_eq!(poller.poll_once().await, 0);
+		assert_eq!(tracker.get(&route_id).unwrap().state, RouteState::Releasing);
+
+		assert_eq!(poller.poll_once().await, 0);
+		let receipt = tracker.get(&route_id).unwrap();
+		assert_eq!(receipt.state, RouteState::Completed);
+		assert_eq!(receipt.destination_tx_hash, Some(H256::repeat_byte(0x22)));
+		assert!(matches!(events.try_recv(), Ok(RouteEvent::Completed { .. })));
+
+		// Terminal routes are no longer polled
+		assert_eq!(poller.poll_once().await, 0);
+		assert_eq!(server.received_requests().await.unwrap().len(), 2);
+	}
+
+	#[tokio::test]
+	async fn test_socket_failed_route() {
+		let server = MockServer::start().await;
+		Mock::given(method("GET"))
+			.and(path("/v2/bridge-status"))
+			.and(header("API-KEY", "test-key"))
+			.respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+				"success": true,
+				"result": { "sourceTxStatus": "COMPLETED", "destinationTxStatus": "FAILED" }
+			})))
+			.mount(&server)
+			.await;
+
+		let router = CrossChainRouter::new();
+		let tracker = router.tracker();
+		let route_id = router
+			.track_live_route(10.0, "Ethereum", "Arbitrum", BridgeProvider::Socket, H256::repeat_byte(0x33))
+			.unwrap();
+		let poller = RouteStatusPoller::new(tracker.clone(), chain_ids(), test_config(&server));
+		let mut events = poller.subscribe();
+
+		poller.poll_once().await;
+		assert_eq!(tracker.get(&route_id).unwrap().state, RouteState::Failed);
+		assert!(matches!(events.try_recv(), Ok(RouteEvent::Failed { .. })));
+	}
+
+	#[tokio::test]
+	async fn test_provider_errors_are_survived() {
+		let server = MockServer::start().await;
+		Mock::given(method("GET"))
+			.and(path("/v1/status"))
+			.respond_with(ResponseTemplate::new(503))
+			.up_to_n_times(2)
+			.mount(&server)
+			.await;
+		Mock::given(method("GET"))
+			.and(path("/v1/status"))
+			.respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({ "status": "DONE" })))
+			.mount(&server)
+			.await;
+
+		let router = CrossChainRouter::new();
+		let tracker = router.tracker();
+		let route_id = router
+			.track_live_route(10.0, "Ethereum", "Arbitrum", BridgeProvider::LiFi, H256::repeat_byte(0x44))
+			.unwrap();
+		let poller = RouteStatusPoller::new(tracker.clone(), chain_ids(), test_config(&server));
+		let shutdown = CancellationToken::new();
+		let handle = tokio::spawn(poller.run(shutdown.clone()));
+
+		for _ in 0..100 {
+			if tracker.get(&route_id).unwrap().state.is_terminal() {
+				break;
+			}
+			sleep(Duration::from_millis(10)).await;
+		}
+		assert_eq!(tracker.get(&route_id).unwrap().state, RouteState::Completed);
+
+		shutdown.cancel();
+		tokio::time::timeout(Duration::from_secs(1), handle)
+			.await
+			.expect("poller did not stop on shutdown")
+			.unwrap();
+	}
+
+	const STATUS_KEYS: &[&str] = &[
+		"status", "substatus", "receiving", "txHash", "result", "sourceTxStatus", "destinationTxStatus",
+		"destinationTransactionHash",
+	];
+
+	proptest! {
+		/// Any status answer either fails to parse or maps to a status; a `Done` never
+		/// carries a hash the answer did not spell out in full.
+		#[test]
+		fn test_status_answers_never_panic(answer in arbitrary_json(STATUS_KEYS)) {
+			if let Ok(body) = serde_json::from_value::<LiFiStatusResponse>(answer.clone()) {
+				let hash = body.receiving.as_ref().and_then(|r| r.tx_hash.clone());
+				if let ProviderStatus::Done { destination_tx_hash: Some(found) } = lifi_status(body) {
+					prop_assert_eq!(Some(found), hash.and_then(|h| H256::from_str(&h).ok()));
+				}
+			}
+			if let Ok(body) = serde_json::from_value::<SocketStatusResponse>(answer) {
+				socket_status(body.result);
+			}
+		}
+	}
+}
@@ -1,13 +1,47 @@
-use ethers::providers::{Middleware, Provider, Http};
-use ethers::core::types::{Address, TransactionRequest, U256};
+use ethers::contract::{abigen, ContractError};
+use ethers::providers::{Middleware, MiddlewareError, ProviderError};
+use ethers::abi::{self, AbiEncode, Token};
+use ethers::core::types::{
+	Address, BlockId, BlockNumber, Bytes, Eip1559TransactionRequest, Filter, Log, Transaction, TransactionReceipt, TransactionRequest,
+	H160, H256, RecoveryMessage, Signature, U256,
+};
 use ethers::types::transaction::eip2718::TypedTransaction;
+use ethers::utils::{get_contract_address, keccak256};
 use anyhow::{Result, Context};
+use rand::Rng;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt;
+use std::future::Future;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
 use log::{info, warn, error, debug};
 use thiserror::Error;
 use serde::{Deserialize, Serialize};
+use tokio::sync::{broadcast, Mutex as AsyncMutex, Notify};
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
 
+use super::signer::OwnerSigner;
+use super::transaction_service::{Proposal, ServiceTransaction, TransactionService};
+use crate::config::{RunMode, SafeConfig, SafeExecution, TokenConfig, MULTI_SEND_CALL_ONLY};
+use crate::ens::{is_ens_name, EnsResolver};
+use crate::kill_switch::KillSwitch;
+use crate::util::{Clock, SystemClock};
+use crate::notifier::{AgentEvent, EventSender};
+use crate::portfolio::HOME_CHAIN;
+use crate::prices::{eth_value_usd, PriceSource};
+use crate::protocol_actions::{self, Allowance, Erc20};
+use crate::report::{ChainBalance, MultiChainBalances};
+use crate::status::BalanceStatus;
+use crate::rpc::{is_rate_limited, is_transient, timed_out, RetryPolicy, RpcProvider};
+use crate::storage::{Store, TransactionRecord};
+use crate::units::{format_units_prec, TokenAmount};
 
 #[derive(Error, Debug)]
+#[non_exhaustive]
 pub enum SafeError {
 	#[error("Transaction failed: {0}")]
 	TransactionFailed(String),
@@ -17,13 +51,549 @@ pub enum SafeError {
 	InvalidAddress(String),
 	#[error("Provider error: {0}")]
 	ProviderError(String),
+	#[error("{operation} got no answer within {after:?}")]
+	Timeout { operation: String, after: Duration },
 	#[error("Gas estimation failed: {0}")]
 	GasEstimationFailed(String),
-	#[error("Balance below critical threshold. Current: {current}, Minimum: {minimum}. Action required: Please fund the account with at least {minimum} wei")]
-	CriticalBalance { current: U256, minimum: U256 },
+	#[error("Token call to {token:?} failed: {reason}")]
+	TokenCallFailed { token: Address, reason: String },
+	#[error("{0:?} is not a Safe: it has no code or does not answer getThreshold")]
+	NotASafe(Address),
+	#[error("Signing failed: {0}")]
+	SigningFailed(String),
+	#[error("Safe nonce {0} is already used")]
+	NonceAlreadyUsed(U256),
+	#[error("The Safe Transaction Service refused the signature: {0}")]
+	InvalidSignature(String),
+	#[error("The Safe Transaction Service rejected the proposal: {0}")]
+	ProposalRejected(String),
+	#[error("Safe Transaction Service request failed: {0}")]
+	TransactionServiceUnavailable(String),
+	#[error("No signer configured: set safe.signer_key (or PRIVATE_KEY), or safe.execution = \"simulate\" to only simulate")]
+	NoSigner,
+	#[error("Signer unavailable: {0}; the transaction was not signed, retry once the signer is back")]
+	SignerUnavailable(String),
+	#[error("Critical balance {critical} wei must be below the minimum balance {minimum} wei")]
+	InvalidThresholds { critical: U256, minimum: U256 },
+	#[error("Transaction {tx_hash:?} was not confirmed within {waited:?}; it may have been dropped or replaced")]
+	NotConfirmed { tx_hash: H256, waited: Duration },
+	#[error("Invalid operation {0}: a Safe transaction is a call (0) or a delegatecall (1)")]
+	InvalidOperation(u8),
+	#[error("Delegatecall to {0:?} refused: set safe.allow_delegatecall and list it in safe.delegatecall_targets")]
+	DelegatecallNotAllowed(Address),
+	#[error("Gas buffer {0} must be a finite multiplier of at least 1")]
+	InvalidGasBuffer(f64),
+	#[error("Balance recovery factor {0} must be a finite multiplier of at least 1")]
+	InvalidRecoveryFactor(f64),
+	#[error("No state at block {block:?} on this node; historical balances need an archive node: {reason}")]
+	HistoricalDataUnavailable { block: BlockId, reason: String },
+	#[error("The RPC endpoint serves chain {actual}, not the expected chain {expected}; check safe.rpc_url and safe.chain_id")]
+	ChainIdMismatch { expected: u64, actual: u64 },
+	#[error("Refusing to broadcast a transaction in {0} mode")]
+	BroadcastDisabled(RunMode),
+	#[error("Malformed Safe transaction: {0}")]
+	MalformedTransaction(String),
+	#[error("Spending limit of {limit} wei {window} exceeded: {attempted} wei attempted")]
+	SpendingLimitExceeded { limit: U256, attempted: U256, window: SpendingWindow },
+	#[error("No pending Safe transaction {0:?}; prepare it first")]
+	UnknownPendingTransaction(H256),
+	#[error("{0:?} is not an owner of the Safe")]
+	NotAnOwner(Address),
+	#[error("{0:?} signed this transaction already")]
+	DuplicateSignature(Address),
+	#[error("Malformed signature: {0}")]
+	MalformedSignature(String),
+	#[error("Unexpected module or guard {0:?} on the Safe; list it in safe.allowed_modules if it belongs there")]
+	UnexpectedModule(Address),
+	#[error("{address:?} has no contract code ({status}); deploy the Safe before transacting")]
+	SafeNotDeployed { address: Address, status: DeploymentStatus },
+	#[error("{0:?} is an owner of the Safe already")]
+	AlreadyAnOwner(Address),
+	#[error("Threshold {threshold} is impossible with {owners} owners; it must be from 1 to the number of owners")]
+	InvalidThreshold { threshold: U256, owners: usize },
+	#[error("Gas budget of {budget} wei per 24 hours exhausted: {spent} wei with this transaction's estimated fee")]
+	GasBudgetExhausted { spent: U256, budget: U256 },
+	#[error("Transaction {0:?} is not pending: it was mined already or the node does not know it")]
+	NotPending(H256),
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Which spending limit a transaction ran into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpendingWindow {
+	/// `max_value_per_tx`, on the transaction's own value.
+	Transaction,
+	/// `max_value_per_day`, on the value executed over the last 24 hours.
+	Day,
+}
+
+impl fmt::Display for SpendingWindow {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(match self {
+			SpendingWindow::Transaction => "per transaction",
+			SpendingWindow::Day => "per 24 hours",
+		})
+	}
+}
+
+/// Blocks of `eth_feeHistory` the tip is taken from.
+const FEE_HISTORY_BLOCKS: u64 = 10;
+
+/// Balance samples kept for the burn rate; older ones are dropped.
+const BALANCE_HISTORY_CAPACITY: usize = 256;
+/// Latest samples the burn rate is fitted over.
+const BURN_RATE_SAMPLES: usize = 12;
+
+/// Multiple of the minimum balance a low balance must reach to count as healthy
+/// again, unless configured.
+const DEFAULT_RECOVERY_FACTOR: f64 = 1.1;
+/// Multiplier applied to gas estimates unless configured.
+const DEFAULT_GAS_BUFFER: f64 = 1.2;
+/// Gas above which a transaction is refused unless configured.
+const DEFAULT_MAX_GAS_LIMIT: u64 = 5_000_000;
+
+/// `value` times `factor`, rounded up; `factor` is taken to four decimals so the
+/// product stays in integers.
+fn scale_up(value: U256, factor: f64) -> U256 {
+	let basis_points = U256::from((factor * 10_000.0).round() as u64);
+	let Some(scaled) = value.checked_mul(basis_points) else {
+		return (value / 10_000).saturating_mul(basis_points);
+	};
+	let (quotient, remainder) = scaled.div_mod(10_000.into());
+	if remainder.is_zero() {
+		quotient
+	} else {
+		quotient + 1
+	}
+}
+
+/// `estimate` times `buffer`, rounded up to whole gas.
+pub fn buffered_gas(estimate: U256, buffer: f64) -> U256 {
+	scale_up(estimate, buffer)
+}
+
+/// How long the balance of a day ago is kept before it is looked up again.
+const DAY_AGO_REFRESH_SECS: u64 = 3600;
+
+/// Whether `error` says the node pruned the state asked for, as nodes without
+/// archive data do beyond their latest blocks.
+fn is_missing_state(error: &ProviderError) -> bool {
+	let message = error.to_string().to_lowercase();
+	["missing trie node", "state not available", "state is not available", "historical state"]
+		.iter()
+		.any(|pattern| message.contains(pattern))
+}
+
+/// A request to the node for `operation` that failed: [`SafeError::Timeout`] when no
+/// answer came in time, or else `other`.
+fn unanswered_or(operation: &str, error: &ProviderError, other: impl FnOnce() -> SafeError) -> SafeError {
+	match timed_out(error) {
+		Some(after) => SafeError::Timeout { operation: operation.to_string(), after },
+		None => other(),
+	}
+}
+
+/// [`unanswered_or`] a [`SafeError::ProviderError`] with the node's error.
+fn provider_error(operation: &str, error: ProviderError) -> SafeError {
+	unanswered_or(operation, &error, || SafeError::ProviderError(error.to_string()))
+}
+
+/// Whether an `eth_getLogs` failure is the node refusing the block range or the
+/// size of the answer, which a smaller range gets past. A node asking us to slow
+/// down is not, however its message is worded.
+fn is_log_range_limit(error: &ProviderError) -> bool {
+	if error.as_error_response().is_some_and(is_rate_limited) {
+		return false;
+	}
+	let message = error.to_string().to_lowercase();
+	["block range", "response size", "too many blocks", "query returned more than"]
+		.iter()
+		.any(|pattern| message.contains(pattern))
+}
+
+/// Blocks one `eth_getLogs` call covers unless configured, within what public
+/// endpoints accept.
+pub const DEFAULT_LOG_BLOCK_RANGE: u64 = 2_000;
+
+/// Queued transactions submitted in any minute unless configured.
+pub const DEFAULT_SUBMISSIONS_PER_MINUTE: u32 = 2;
+
+/// Window of the submission queue's rate limit.
+const SUBMISSION_WINDOW: Duration = Duration::from_secs(60);
+
+/// An EIP-681 request to pay `wei` to `address` on `chain_id`.
+pub fn payment_uri(address: Address, chain_id: u64, wei: U256) -> String {
+	format!("ethereum:{}@{}?value={}", ethers::utils::to_checksum(&address, None), chain_id, wei)
+}
+
+/// Span of the rolling daily spending limit, in seconds.
+const SPENDING_WINDOW_SECS: u64 = 24 * 3600;
+
+/// How often a sent transaction's receipt is looked for.
+const RECEIPT_POLL_INTERVAL: Duration = Duration::from_secs(3);
+
+/// Blocks an executed transaction waits for on `chain_id`, its own included, unless
+/// configured: reorgs of a block or two still happen on Ethereum mainnet, while the
+/// sequencer of an L2 orders its blocks alone.
+pub fn default_confirmations(chain_id: U256) -> usize {
+	if chain_id == U256::one() {
+		2
+	} else {
+		1
+	}
+}
+
+/// Why a call reverted, from its revert data: the message of an `Error(string)`,
+/// e.g. `GS013`, the code of a compiler `Panic(uint256)` with its meaning, or the
+/// selector of a custom error. `None` when the call reverted without data.
+pub fn decode_revert(data: &[u8]) -> Option<String> {
+	if let Some(encoded) = data.strip_prefix(&ethers::utils::id("Error(string)")[..]) {
+		if let Some(message) = abi::decode(&[abi::ParamType::String], encoded).ok()?.pop()?.into_string() {
+			return Some(message);
+		}
+	}
+	if let Some(encoded) = data.strip_prefix(&ethers::utils::id("Panic(uint256)")[..]) {
+		let code = abi::decode(&[abi::ParamType::Uint(256)], encoded).ok()?.pop()?.into_uint()?;
+		return Some(match panic_meaning(code) {
+			Some(meaning) => format!("panic 0x{:02x}: {}", code, meaning),
+			None => format!("panic 0x{:02x}", code),
+		});
+	}
+	let selector = data.get(..4)?;
+	Some(format!("custom error 0x{}", hex::encode(selector)))
+}
+
+/// What the Solidity compiler means by a `Panic(uint256)` code.
+fn panic_meaning(code: U256) -> Option<&'static str> {
+	if code > U256::from(u8::MAX) {
+		return None;
+	}
+	Some(match code.as_u32() {
+		0x00 => "generic compiler panic",
+		0x01 => "assertion failed",
+		0x11 => "arithmetic overflow or underflow",
+		0x12 => "division or modulo by zero",
+		0x21 => "invalid enum value",
+		0x22 => "invalid storage byte array",
+		0x31 => "pop on an empty array",
+		0x32 => "array index out of bounds",
+		0x41 => "out of memory",
+		0x51 => "call to an uninitialized function",
+		_ => return None,
+	})
+}
+
+/// Fee fields of a transaction on the home chain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TxFees {
+	/// A chain without base fees: one price per unit of gas.
+	Legacy { gas_price: U256 },
+	/// EIP-1559: the base fee plus a tip of `max_priority_fee_per_gas`, at most
+	/// `max_fee_per_gas` in all.
+	Eip1559 { max_fee_per_gas: U256, max_priority_fee_per_gas: U256 },
+}
+
+/// Least fee increase, in percent, a node accepts from a transaction replacing a
+/// pending one with the same nonce.
+pub const MIN_REPLACEMENT_BUMP_PERCENT: u64 = 10;
+
+/// Gas of a plain ETH transfer to an account without code.
+const TRANSFER_GAS: u64 = 21_000;
+
+impl TxFees {
+	/// The fees `tx` offered.
+	fn of(tx: &Transaction) -> TxFees {
+		match (tx.max_fee_per_gas, tx.max_priority_fee_per_gas) {
+			(Some(max_fee_per_gas), Some(max_priority_fee_per_gas)) => {
+				TxFees::Eip1559 { max_fee_per_gas, max_priority_fee_per_gas }
+			}
+			_ => TxFees::Legacy { gas_price: tx.gas_price.unwrap_or_default() },
+		}
+	}
+
+	/// These fees raised by `percent`, and by at least
+	/// [`MIN_REPLACEMENT_BUMP_PERCENT`] so that nodes take a transaction offering them
+	/// as a replacement. Rounded up, since nodes compare with the exact increase.
+	pub fn bumped(&self, percent: u64) -> TxFees {
+		let factor = U256::from(percent.max(MIN_REPLACEMENT_BUMP_PERCENT)) + 100;
+		let bump = |fee: U256| {
+			let (raised, rest) = fee.saturating_mul(factor).div_mod(U256::from(100));
+			if rest.is_zero() { raised } else { raised + 1 }
+		};
+		match *self {
+			TxFees::Legacy { gas_price } => TxFees::Legacy { gas_price: bump(gas_price) },
+			TxFees::Eip1559 { max_fee_per_gas, max_priority_fee_per_gas } => TxFees::Eip1559 {
+				max_fee_per_gas: bump(max_fee_per_gas),
+				max_priority_fee_per_gas: bump(max_priority_fee_per_gas),
+			},
+		}
+	}
+
+	/// Most a unit of gas can cost.
+	pub fn max_gas_price(&self) -> U256 {
+		match self {
+			TxFees::Legacy { gas_price } => *gas_price,
+			TxFees::Eip1559 { max_fee_per_gas, .. } => *max_fee_per_gas,
+		}
+	}
+
+	/// A call of `to` from `from` as a transaction of this type.
+	fn request(&self, from: Address, to: Address, value: U256, data: Bytes) -> TypedTransaction {
+		match *self {
+			TxFees::Legacy { gas_price } => {
+				TransactionRequest::new().to(to).value(value).from(from).data(data).gas_price(gas_price).into()
+			}
+			TxFees::Eip1559 { max_fee_per_gas, max_priority_fee_per_gas } => Eip1559TransactionRequest::new()
+				.to(to)
+				.value(value)
+				.from(from)
+				.data(data)
+				.max_fee_per_gas(max_fee_per_gas)
+				.max_priority_fee_per_gas(max_priority_fee_per_gas)
+				.into(),
+		}
+	}
+}
+
+/// Safe's SimulateTxAccessor v1.3.0, at the same address on every chain.
+pub const SIMULATE_TX_ACCESSOR: &str = "0x59AD6735bCd8152B84860Cb256dD9e96b85F69Da";
+/// Safe's CreateCall v1.3.0, at the same address on every chain.
+pub const CREATE_CALL: &str = "0x7cbB62EaA69F79e6873cD1ecB2392971036cFAa4";
+/// Multicall3, at the same address on most chains.
+pub const MULTICALL3: &str = "0xcA11bde05977b3631167028862bE2a173976CA11";
+
+/// Start and end of the Safe's linked list of modules.
+const SENTINEL_MODULES: Address = H160([
+	0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1,
+]);
+/// Start and end of the Safe's linked list of owners, the same sentinel.
+const SENTINEL_OWNERS: Address = SENTINEL_MODULES;
+/// Modules read per `getModulesPaginated` call.
+const MODULES_PAGE_SIZE: u64 = 50;
+
+/// Storage slot of the guard of Safe 1.3.0 and later,
+/// `keccak256("guard_manager.guard.address")`.
+fn guard_storage_slot() -> H256 {
+	H256(keccak256("guard_manager.guard.address"))
+}
+
+/// The owner before `owner` in the Safe's linked list of `owners`, as `getOwners`
+/// returns them: the sentinel for the first.
+fn prev_owner(owners: &[Address], owner: Address) -> Result<Address, SafeError> {
+	match owners.iter().position(|o| *o == owner) {
+		Some(0) => Ok(SENTINEL_OWNERS),
+		Some(i) => Ok(owners[i - 1]),
+		None => Err(SafeError::NotAnOwner(owner)),
+	}
+}
+
+/// Refuses a `threshold` a Safe with `owners` owners cannot have.
+fn check_threshold(threshold: U256, owners: usize) -> Result<(), SafeError> {
+	if threshold.is_zero() || threshold > U256::from(owners) {
+		return Err(SafeError::InvalidThreshold { threshold, owners });
+	}
+	Ok(())
+}
+
+/// EIP-712 domain of Safe 1.3.0 and later, which includes the chain id.
+const SAFE_DOMAIN_TYPE: &str = "EIP712Domain(uint256 chainId,address verifyingContract)";
+const SAFE_TX_TYPE: &str = "SafeTx(address to,uint256 value,bytes data,uint8 operation,uint256 safeTxGas,uint256 baseGas,uint256 gasPrice,address gasToken,address refundReceiver,uint256 nonce)";
+
+abigen!(
+	GnosisSafe,
+	r#"[
+		function getOwners() external view returns (address[])
+		function getThreshold() external view returns (uint256)
+		function nonce() external view returns (uint256)
+		function VERSION() external view returns (string)
+		function getModulesPaginated(address start, uint256 pageSize) external view returns (address[] array, address next)
+		function addOwnerWithThreshold(address owner, uint256 threshold) external
+		function removeOwner(address prevOwner, address owner, uint256 threshold) external
+		function swapOwner(address prevOwner, address oldOwner, address newOwner) external
+		function execTransaction(address to, uint256 value, bytes data, uint8 operation, uint256 safeTxGas, uint256 baseGas, uint256 gasPrice, address gasToken, address refundReceiver, bytes signatures) external payable returns (bool)
+	]"#
+);
+
+/// What [`SafeManager::execute_transaction`] did with a transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExecutionOutcome {
+	/// Sent, and confirmed when confirmations are waited for.
+	Executed(ExecutionResult),
+	/// Posted to the Safe Transaction Service for the other owners to confirm.
+	Proposed { nonce: U256, safe_tx_hash: H256 },
+	/// Prepared, estimated and checked against the balance, then not sent: dry-run
+	/// mode or `safe.execution = "simulate"`.
+	Simulated(SimulatedTransaction),
+	/// Not prepared at all, in observe mode.
+	Observed,
+}
+
+impl ExecutionOutcome {
+	/// Hash of the transaction sent, for executed transactions only.
+	pub fn tx_hash(&self) -> Option<H256> {
+		match self {
+			ExecutionOutcome::Executed(result) => result.tx_hash,
+			_ => None,
+		}
+	}
+
+	/// The gas and cost of an executed or simulated transaction, to record; `None`
+	/// for proposed and observed ones.
+	pub fn result(&self) -> Option<ExecutionResult> {
+		match self {
+			ExecutionOutcome::Executed(result) => Some(*result),
+			ExecutionOutcome::Simulated(simulated) => Some(ExecutionResult {
+				tx_hash: None,
+				gas_estimate: simulated.estimated_gas,
+				gas_used: None,
+				total_cost_wei: simulated.total_cost,
+				block: None,
+				simulated: true,
+				contract_address: simulated.contract_address,
+			}),
+			ExecutionOutcome::Proposed { .. } | ExecutionOutcome::Observed => None,
+		}
+	}
+}
+
+/// What executing a transaction took, or would have: the record kept of it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct ExecutionResult {
+	/// `None` when simulated.
+	pub tx_hash: Option<H256>,
+	/// Gas estimate the transaction was sent with, buffer included.
+	pub gas_estimate: U256,
+	/// Gas the receipt reports; `None` unless confirmations were waited for.
+	pub gas_used: Option<U256>,
+	/// Value plus fee: what the receipt says it paid, or else the most the gas could
+	/// cost at the offered fees.
+	pub total_cost_wei: U256,
+	/// Block the transaction was mined in, when confirmed.
+	pub block: Option<u64>,
+	pub simulated: bool,
+	/// Address of the contract a deployment creates; `None` for calls.
+	pub contract_address: Option<Address>,
+}
+
+impl ExecutionResult {
+	/// Takes the gas used, the block and, with the price paid, the actual cost of a
+	/// transaction sending `value` from its receipt.
+	fn record_receipt(&mut self, receipt: &TransactionReceipt, value: U256) {
+		self.gas_used = receipt.gas_used;
+		self.block = receipt.block_number.map(|block| block.as_u64());
+		if let (Some(gas_used), Some(gas_price)) = (receipt.gas_used, receipt.effective_gas_price) {
+			self.total_cost_wei = value + gas_used * gas_price;
+		}
+	}
+}
+
+impl fmt::Display for ExecutionResult {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self.tx_hash {
+			Some(tx_hash) => write!(f, "transaction {:?}", tx_hash)?,
+			None => f.write_str("simulated transaction")?,
+		}
+		if let Some(block) = self.block {
+			write!(f, " in block {}", block)?;
+		}
+		match self.gas_used {
+			Some(gas_used) => write!(f, ", gas used {} of {} estimated", gas_used, self.gas_estimate)?,
+			None => write!(f, ", gas estimate {}", self.gas_estimate)?,
+		}
+		write!(f, ", total cost {} ETH", ethers::utils::format_ether(self.total_cost_wei))?;
+		if let Some(contract_address) = self.contract_address {
+			write!(f, ", deploying {:?}", contract_address)?;
+		}
+		Ok(())
+	}
+}
+
+/// Exactly what a simulated transaction would have sent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SimulatedTransaction {
+	pub to: Address,
+	pub value: U256,
+	/// Keccak-256 of the Safe transaction's calldata.
+	pub calldata_hash: H256,
+	pub safe_tx_hash: H256,
+	pub estimated_gas: U256,
+	/// Value plus the most the gas could cost at the offered fees.
+	pub total_cost: U256,
+	/// Address of the contract a deployment would create; `None` for calls.
+	pub contract_address: Option<Address>,
+}
+
+impl fmt::Display for SimulatedTransaction {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(
+			f,
+			"to {:?}, value {} wei, calldata {:?}, estimated gas {}, total cost {} wei, safeTxHash {:?}",
+			self.to, self.value, self.calldata_hash, self.estimated_gas, self.total_cost, self.safe_tx_hash
+		)?;
+		if let Some(contract_address) = self.contract_address {
+			write!(f, ", deploying {:?}", contract_address)?;
+		}
+		Ok(())
+	}
+}
+
+/// A contract for the Safe to deploy: `init_code`, the creation bytecode with its
+/// constructor arguments, run with `value` wei from the Safe's balance. A Safe
+/// transaction always has a target, so it is sent as a delegatecall to
+/// [`CREATE_CALL`], see [`protocol_actions::deploy`], and serializes as neither a call
+/// nor a transaction to the zero address.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeploymentTransaction {
+	pub value: U256,
+	pub init_code: Bytes,
+}
+
+impl DeploymentTransaction {
+	/// The Safe transaction deploying it through CreateCall at `create_call`.
+	pub fn safe_transaction(&self, create_call: Address) -> SafeTransaction {
+		protocol_actions::deploy(create_call, self.value, self.init_code.clone())
+	}
+}
+
+/// Whether the Safe's address holds a contract, from [`SafeManager::check_deployment`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeploymentStatus {
+	/// Not checked yet, or the check failed.
+	#[default]
+	Unknown,
+	Deployed,
+	/// No code and no transactions: a counterfactual address the Safe will be
+	/// deployed to. It can hold funds, but not transact.
+	NotDeployed,
+	/// No code, but transactions sent from it: an externally owned account.
+	Eoa,
+}
+
+impl fmt::Display for DeploymentStatus {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(match self {
+			DeploymentStatus::Unknown => "unknown",
+			DeploymentStatus::Deployed => "deployed",
+			DeploymentStatus::NotDeployed => "not deployed",
+			DeploymentStatus::Eoa => "an externally owned account",
+		})
+	}
+}
+
+/// What the Safe contract reports about itself.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SafeInfo {
+	pub version: String,
+	pub owners: Vec<Address>,
+	/// Owner signatures a transaction needs.
+	pub threshold: U256,
+	pub nonce: U256,
+}
+
+/// A transaction of the Safe. Its JSON is the Safe Transaction Service's, see
+/// [`ServiceTransaction`], which it converts to and from.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(into = "ServiceTransaction", try_from = "ServiceTransaction")]
 pub struct SafeTransaction {
 	pub to: Address,
 	pub value: U256,
@@ -33,226 +603,5769 @@ pub struct SafeTransaction {
 	pub nonce: Option<U256>,
 }
 
+impl SafeTransaction {
+	/// The EIP-712 hash owners sign to approve this transaction as `nonce` of the Safe
+	/// at `safe` on `chain_id`, the `safeTxHash` the Safe UI shows. The refund fields
+	/// (`baseGas`, `gasPrice`, `gasToken`, `refundReceiver`) are zero: the executor
+	/// pays its own gas.
+	pub fn safe_tx_hash(&self, chain_id: U256, safe: Address, nonce: U256) -> H256 {
+		let domain_separator = keccak256(abi::encode(&[
+			Token::FixedBytes(keccak256(SAFE_DOMAIN_TYPE).to_vec()),
+			Token::Uint(chain_id),
+			Token::Address(safe),
+		]));
+		let struct_hash = keccak256(abi::encode(&[
+			Token::FixedBytes(keccak256(SAFE_TX_TYPE).to_vec()),
+			Token::Address(self.to),
+			Token::Uint(self.value),
+			Token::FixedBytes(keccak256(&self.data).to_vec()),
+			Token::Uint(self.operation.into()),
+			Token::Uint(self.safe_tx_gas),
+			Token::Uint(U256::zero()),
+			Token::Uint(U256::zero()),
+			Token::Address(Address::zero()),
+			Token::Address(Address::zero()),
+			Token::Uint(nonce),
+		]));
+		keccak256([&[0x19, 0x01][..], &domain_separator, &struct_hash].concat()).into()
+	}
+}
+
+/// A Safe transaction collecting owner signatures until the threshold is met, from
+/// [`SafeManager::prepare_transaction`] to [`SafeManager::execute_when_ready`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct PendingSafeTx {
+	/// The transaction, its nonce fixed.
+	pub tx: SafeTransaction,
+	pub safe_tx_hash: H256,
+	/// Each owner's signature, ordered by owner address as the Safe checks them.
+	signatures: BTreeMap<Address, Bytes>,
+}
+
+impl PendingSafeTx {
+	/// The owners who signed, in ascending order.
+	pub fn signers(&self) -> Vec<Address> {
+		self.signatures.keys().copied().collect()
+	}
+
+	pub fn signature_count(&self) -> usize {
+		self.signatures.len()
+	}
+
+	/// The signatures joined in ascending owner order, the `signatures` argument of
+	/// `execTransaction`.
+	pub fn packed_signatures(&self) -> Bytes {
+		self.signatures.values().flat_map(|signature| signature.iter().copied()).collect::<Vec<u8>>().into()
+	}
+}
+
+/// The owner whose key made `signature` of the `safeTxHash` `hash`: 65 bytes signing
+/// the hash itself (`v` of 27 or 28), or an `eth_sign` message of it (`v` of 31 or
+/// 32). Contract signatures and approved hashes are not recovered.
+pub fn recover_signer(hash: H256, signature: &[u8]) -> Result<Address, SafeError> {
+	let mut signature = Signature::try_from(signature).map_err(|e| SafeError::MalformedSignature(e.to_string()))?;
+	let message = match signature.v {
+		27 | 28 => RecoveryMessage::Hash(hash),
+		31 | 32 => {
+			signature.v -= 4;
+			RecoveryMessage::Hash(ethers::utils::hash_message(hash))
+		}
+		v => return Err(SafeError::MalformedSignature(format!("v of {} is not an owner key's signature", v))),
+	};
+	signature.recover(message).map_err(|e| SafeError::MalformedSignature(e.to_string()))
+}
+
+/// A balance that rose between two reads, e.g. a deposit or a bridge landing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BalanceIncrease {
+	pub chain: String,
+	pub previous: U256,
+	pub current: U256,
+}
+
+/// Funds that moved into or out of the Safe, found by
+/// [`SafeManager::watch_transfers`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TransferEvent {
+	/// The ERC-20 contract, `None` for ETH.
+	pub token: Option<Address>,
+	/// `None` for the other side of an ETH balance change, which logs do not show.
+	pub from: Option<Address>,
+	pub to: Option<Address>,
+	/// In the token's smallest unit, wei for ETH.
+	#[serde(serialize_with = "crate::report::decimal")]
+	pub amount: U256,
+	/// `None` for an ETH balance change, which may span several transactions.
+	pub tx_hash: Option<H256>,
+	pub block: u64,
+}
+
+impl TransferEvent {
+	/// One line for logs and notifications.
+	pub fn describe(&self) -> String {
+		let amount = match self.token {
+			Some(token) => format!("{} units of token {:?}", self.amount, token),
+			None => format!("{} ETH", ethers::utils::format_ether(self.amount)),
+		};
+		let tx = self.tx_hash.map(|hash| format!(" ({:?})", hash)).unwrap_or_default();
+		match (self.from, self.to) {
+			(Some(from), Some(to)) => format!("{} from {:?} to {:?} in block {}{}", amount, from, to, self.block, tx),
+			(None, Some(to)) => format!("{} into {:?} by block {}", amount, to, self.block),
+			(Some(from), None) => format!("{} out of {:?} by block {}", amount, from, self.block),
+			(None, None) => format!("{} by block {}", amount, self.block),
+		}
+	}
+}
+
+/// Where [`SafeManager::watch_transfers`] left off: the last block scanned, and the
+/// Safe's ETH balance at it when known.
+#[derive(Debug, Clone, Copy)]
+struct TransferScan {
+	block: u64,
+	balance: Option<U256>,
+}
+
+/// A home chain balance read by [`SafeManager::get_balance`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct BalanceSample {
+	/// Unix time of the read, in seconds.
+	pub timestamp: u64,
+	pub balance: U256,
+}
+
+/// Whether the Safe has let a spender pull enough of a token, from
+/// [`SafeManager::ensure_allowance`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum AllowanceStatus {
+	/// The allowance covers the amount; nothing to approve.
+	Sufficient,
+	/// The approvals to execute first, in order: one, or a reset to zero and then the
+	/// approval when an allowance is left, as tokens like USDT require.
+	Approve(Vec<SafeTransaction>),
+}
+
+/// Balance of one configured ERC-20 token.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenBalance {
+	pub symbol: String,
+	pub token: Address,
+	pub amount: TokenAmount,
+}
+
+/// One balance for [`SafeManager::get_balances_batch`] to read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BalanceQuery {
+	/// ETH balance of an account, in wei.
+	Native(Address),
+	/// Balance of the ERC-20 `token` held by `holder`, in its smallest unit.
+	Token { token: Address, holder: Address },
+}
+
+impl BalanceQuery {
+	/// Target and calldata of the call Multicall3 at `multicall` makes for the query.
+	fn call(&self, multicall: Address) -> (Address, Vec<u8>) {
+		let (target, signature, account) = match *self {
+			BalanceQuery::Native(account) => (multicall, "getEthBalance(address)", account),
+			BalanceQuery::Token { token, holder } => (token, "balanceOf(address)", holder),
+		};
+		let mut data = ethers::utils::id(signature).to_vec();
+		data.extend(abi::encode(&[Token::Address(account)]));
+		(target, data)
+	}
+
+	/// The balance in what Multicall3 answered for the query: whether the call
+	/// succeeded, and what it returned.
+	fn balance(&self, success: bool, data: &[u8]) -> Result<U256> {
+		if success && data.len() == 32 {
+			return Ok(U256::from_big_endian(data));
+		}
+		let reason = match (success, data.is_empty()) {
+			(false, _) => format!("call reverted: 0x{}", hex::encode(data)),
+			// An address without code answers every call with no data
+			(true, true) => "no contract at this address".to_string(),
+			(true, false) => format!("unexpected answer: 0x{}", hex::encode(data)),
+		};
+		Err(match *self {
+			BalanceQuery::Native(account) => SafeError::ProviderError(format!("getEthBalance of {:?}: {}", account, reason)),
+			BalanceQuery::Token { token, .. } => {
+				warn!("Token call to {:?} failed: {}", token, reason);
+				SafeError::TokenCallFailed { token, reason }
+			}
+		}
+		.into())
+	}
+}
+
+/// Refilling the Safe with plain ETH transfers from a funding wallet once its
+/// balance turns critical.
+#[derive(Debug)]
+pub struct TopUp {
+	/// The funding wallet, which signs and pays for the transfers; not an owner.
+	pub funder: OwnerSigner,
+	/// Balance the Safe is brought back to, in wei.
+	pub target: U256,
+	/// Most wei sent over any 24 hours.
+	pub max_per_day: U256,
+	/// Wei the funding wallet keeps, the transfer's fee included.
+	pub reserve: U256,
+}
+
+/// Another chain the Safe's address is watched on.
+struct WatchedChain {
+	name: String,
+	provider: RpcProvider,
+	min_balance: U256,
+}
+
 pub struct SafeManager {
 	address: Address,
-	provider: Provider<Http>,
+	provider: RpcProvider,
+	watched: Vec<WatchedChain>,
 	min_balance: U256,
 	critical_balance: U256,
+	/// Multiple of the minimum a balance below it must reach to be healthy again.
+	recovery_factor: f64,
+	/// Status of the last balance checked with
+	/// [`update_balance_status`](Self::update_balance_status).
+	balance_state: Mutex<BalanceStatus>,
+	events: EventSender,
+	store: Option<Arc<dyn Store>>,
+	mode: RunMode,
+	kill_switch: KillSwitch,
+	increases: broadcast::Sender<BalanceIncrease>,
+	prices: Option<Arc<dyn PriceSource>>,
+	clock: Arc<dyn Clock>,
+	/// Retries of balance reads, gas prices and gas estimates.
+	retry: RetryPolicy,
+	tokens: Vec<TokenConfig>,
+	/// Decimals by token, configured or read once from the contract.
+	decimals: Mutex<HashMap<Address, u32>>,
+	/// Whether the address answered as a Safe.
+	verified_safe: AtomicBool,
+	/// Whether the address holds a contract, as last checked.
+	deployment: Mutex<DeploymentStatus>,
+	/// Recent home chain balances, oldest first, since the last top-up.
+	history: Mutex<VecDeque<BalanceSample>>,
+	/// Other accounts monitored on the home chain, with their minimum balances.
+	accounts: HashMap<Address, U256>,
+	/// Configured tip of EIP-1559 transactions.
+	priority_fee: Option<U256>,
+	execution: SafeExecution,
+	/// The owner who signs and sends transactions and proposals.
+	signer: Option<OwnerSigner>,
+	/// The signer's next account nonce, counted locally between submissions; read
+	/// from the node when unknown. Held while a transaction is sent, so concurrent
+	/// submissions take turns.
+	next_nonce: AsyncMutex<Option<U256>>,
+	/// Where proposals go.
+	transaction_service: Option<TransactionService>,
+	/// Prepared transactions still collecting signatures, by `safeTxHash`.
+	pending: Mutex<HashMap<H256, PendingSafeTx>>,
+	/// Blocks an executed transaction waits for; by chain when unset.
+	confirmations: Option<usize>,
+	confirmation_timeout: Duration,
+	/// Whether delegatecalls may be simulated or sent at all.
+	allow_delegatecall: bool,
+	/// The only contracts a delegatecall may target, such as MultiSendCallOnly.
+	delegatecall_targets: Vec<Address>,
+	/// Modules and guards the Safe is expected to have.
+	allowed_modules: Vec<Address>,
+	/// Multiplier of gas estimates, for the Safe's own overhead.
+	gas_buffer: f64,
+	/// Most gas a transaction may be sent with.
+	max_gas_limit: Option<U256>,
+	/// Most ETH value one transaction may send.
+	max_value_per_tx: Option<U256>,
+	/// Most ETH value transactions may send over any 24 hours.
+	max_value_per_day: Option<U256>,
+	/// When the balance of a day ago was last looked up, and what it was.
+	day_ago: Mutex<Option<(u64, Option<U256>)>>,
+	/// Cleared once the node turns out to keep no historical state.
+	historical_state: AtomicBool,
+	/// Cleared once the chain turns out to have no Multicall3.
+	multicall: AtomicBool,
+	/// Value of the transactions executed within the last 24 hours, oldest first,
+	/// with the Unix time each was counted at.
+	spent: Mutex<VecDeque<(u64, U256)>>,
+	/// Most wei executed transactions may pay in gas over any 24 hours.
+	daily_gas_budget: Option<U256>,
+	/// Gas fees of the transactions executed within the last 24 hours, oldest first:
+	/// the Unix time each was counted at, its hash once sent, and the estimate until
+	/// its receipt gives the fee paid.
+	gas_spent: Mutex<VecDeque<(u64, Option<H256>, U256)>>,
+	/// Chain id the endpoint must serve.
+	expected_chain_id: u64,
+	/// Where a critical balance is refilled from.
+	top_up: Option<TopUp>,
+	/// Wei sent by top-ups within the last 24 hours, oldest first, with the Unix
+	/// time each was sent at.
+	topped_up: Mutex<VecDeque<(u64, U256)>>,
+	/// When the last top-up was sent, and its hash.
+	last_top_up: Mutex<Option<(u64, H256)>>,
+	/// Most blocks one `eth_getLogs` call asks for.
+	log_block_range: u64,
+	/// Where the transfer scan left off; held while it runs.
+	transfer_scan: AsyncMutex<Option<TransferScan>>,
+	/// Chain id the endpoint served at [`init`](Self::init).
+	chain_id: OnceLock<u64>,
+	/// ENS names of the addresses shown in logs.
+	names: EnsResolver,
+	/// Transactions waiting for [`drain_queue`](Self::drain_queue), oldest first.
+	queue: Mutex<VecDeque<SafeTransaction>>,
+	/// Woken by every [`enqueue`](Self::enqueue).
+	queued: Notify,
+	/// Most queued transactions submitted in any minute.
+	submissions_per_minute: u32,
+	/// When queued transactions were submitted within the last minute, oldest first.
+	submitted: Mutex<VecDeque<Instant>>,
 }
 
-impl SafeManager {
-	pub fn new(address: Address, provider: Provider<Http>) -> Result<Self> {
-		let min_balance = U256::from(1_000_000_000_000_000_u64); // 0.001 ETH
-		let critical_balance = min_balance / 2; // 0.0005 ETH
+impl SafeManager {
+	/// Creates a manager for the Safe at `address` with the default balance thresholds.
+	/// Nothing is sent to the endpoint until the first call.
+	///
+	/// ```
+	/// use std::time::Duration;
+	/// use asam::agents::safe_manager::SafeManager;
+	/// use asam::rpc::FailoverClient;
+	///
+	/// # fn main() -> anyhow::Result<()> {
+	/// let (provider, _health) =
+	///     FailoverClient::new(&["http://localhost:8545"], Duration::from_secs(10), Duration::from_secs(30))?
+	///         .into_provider();
+	/// let manager = SafeManager::new("0x0000000000000000000000000000000000000001".parse()?, provider)?;
+	/// # drop(manager);
+	/// # Ok(())
+	/// # }
+	/// ```
+	pub fn new(address: Address, provider: RpcProvider) -> Result<Self> {
+		Self::with_clock(address, provider, SystemClock::shared())
+	}
+
+	/// Like [`SafeManager::new`], with transient provider failures retried as `retry`
+	/// says instead of the [default](RetryPolicy::default).
+	pub fn with_retry(address: Address, provider: RpcProvider, retry: RetryPolicy) -> Result<Self> {
+		let mut manager = Self::new(address, provider)?;
+		debug!("Retrying transient RPC failures up to {} attempts", retry.max_attempts);
+		manager.retry = retry;
+		Ok(manager)
+	}
+
+	/// Like [`SafeManager::new`], with transaction records stamped and retries timed
+	/// by `clock`.
+	pub fn with_clock(address: Address, provider: RpcProvider, clock: Arc<dyn Clock>) -> Result<Self> {
+		let min_balance = U256::from(1_000_000_000_000_000_u64); // 0.001 ETH
+		let critical_balance = min_balance / 2; // 0.0005 ETH
+
+		debug!("Initializing SafeManager for address: {:?}", address);
+		debug!("Minimum balance threshold: {} wei", min_balance);
+		debug!("Critical balance threshold: {} wei", critical_balance);
+
+		Ok(Self {
+			address,
+			watched: Vec::new(),
+			min_balance,
+			critical_balance,
+			recovery_factor: DEFAULT_RECOVERY_FACTOR,
+			balance_state: Mutex::new(BalanceStatus::Unknown),
+			events: EventSender::disabled(),
+			store: None,
+			mode: RunMode::Live,
+			kill_switch: KillSwitch::default(),
+			increases: broadcast::channel(16).0,
+			prices: None,
+			clock,
+			retry: RetryPolicy::default(),
+			tokens: Vec::new(),
+			decimals: Mutex::default(),
+			verified_safe: AtomicBool::new(false),
+			deployment: Mutex::default(),
+			history: Mutex::default(),
+			accounts: HashMap::new(),
+			priority_fee: None,
+			execution: SafeExecution::Execute,
+			signer: None,
+			next_nonce: AsyncMutex::new(None),
+			transaction_service: None,
+			pending: Mutex::default(),
+			confirmations: None,
+			confirmation_timeout: Duration::from_secs(300),
+			allow_delegatecall: false,
+			delegatecall_targets: vec![Address::from_str(MULTI_SEND_CALL_ONLY)?],
+			allowed_modules: Vec::new(),
+			gas_buffer: DEFAULT_GAS_BUFFER,
+			max_gas_limit: Some(DEFAULT_MAX_GAS_LIMIT.into()),
+			max_value_per_tx: None,
+			max_value_per_day: None,
+			day_ago: Mutex::default(),
+			historical_state: AtomicBool::new(true),
+			multicall: AtomicBool::new(true),
+			spent: Mutex::default(),
+			daily_gas_budget: None,
+			gas_spent: Mutex::default(),
+			expected_chain_id: 1,
+			top_up: None,
+			topped_up: Mutex::default(),
+			last_top_up: Mutex::default(),
+			log_block_range: DEFAULT_LOG_BLOCK_RANGE,
+			transfer_scan: AsyncMutex::new(None),
+			chain_id: OnceLock::new(),
+			names: EnsResolver::new(provider.clone()),
+			provider,
+			queue: Mutex::default(),
+			queued: Notify::new(),
+			submissions_per_minute: DEFAULT_SUBMISSIONS_PER_MINUTE,
+			submitted: Mutex::default(),
+		})
+	}
+
+	pub fn from_config(config: &SafeConfig, provider: RpcProvider) -> Result<Self> {
+		let mut manager = Self::with_retry(config.address()?, provider, RetryPolicy::from_config(config))?;
+		manager.set_balance_thresholds(config.min_balance(), config.critical_balance())?;
+		manager.set_recovery_factor(config.balance_recovery_factor)?;
+		manager.set_tokens(config.tokens.clone())?;
+		if let Some(name) = config.address.as_deref().filter(|raw| is_ens_name(raw)) {
+			manager.names.remember(manager.address, name);
+		}
+		for account in &config.accounts {
+			let address = account.address()?;
+			if is_ens_name(&account.address) {
+				manager.names.remember(address, &account.address);
+			}
+			manager.add_account(address);
+			manager.set_account_min_balance(address, account.min_balance_wei(config));
+		}
+		manager.set_priority_fee(config.priority_fee_wei());
+		if let Some(signer) = config.signer()? {
+			manager.set_signer(signer);
+		}
+		if let Some(url) = config.transaction_service_url(HOME_CHAIN) {
+			manager.set_transaction_service(TransactionService::new(url));
+		}
+		manager.set_execution(config.execution)?;
+		manager.set_confirmations(config.confirmations, Duration::from_secs(config.confirmation_timeout_secs));
+		manager.set_spending_limits(config.max_value_per_tx(), config.max_value_per_day());
+		manager.set_daily_gas_budget(config.daily_gas_budget());
+		manager.set_delegatecalls(config.allow_delegatecall, config.delegatecall_targets()?);
+		manager.set_allowed_modules(config.allowed_modules()?);
+		manager.set_gas_buffer(config.gas_buffer)?;
+		manager.set_max_gas_limit(Some(config.max_gas_limit.into()));
+		if let Some(chain_id) = config.chain_id {
+			manager.set_expected_chain_id(chain_id);
+		}
+		manager.set_top_up(config.top_up()?);
+		manager.set_log_block_range(config.transfers.max_block_range);
+		manager.set_submissions_per_minute(config.max_submissions_per_minute);
+		Ok(manager)
+	}
+
+	/// Chain id [`init`](Self::init) requires of the endpoint; 1, Ethereum mainnet,
+	/// unless set.
+	pub fn set_expected_chain_id(&mut self, chain_id: u64) {
+		self.expected_chain_id = chain_id;
+	}
+
+	/// Reads the endpoint's chain id and refuses one other than expected with
+	/// [`SafeError::ChainIdMismatch`], so the Safe's address is never read on another
+	/// chain, where it would look empty. Returns the chain id, which
+	/// [`chain_id`](Self::chain_id) reports from then on.
+	pub async fn init(&self) -> Result<u64> {
+		let actual = self
+			.retrying("eth_chainId", || self.provider.get_chainid())
+			.await
+			.map_err(|e| provider_error("eth_chainId", e))?
+			.low_u64();
+		if actual != self.expected_chain_id {
+			return Err(SafeError::ChainIdMismatch { expected: self.expected_chain_id, actual }.into());
+		}
+		if self.chain_id.set(actual).is_ok() {
+			info!("Safe {:?} is on chain {}", self.address, actual);
+		}
+		// Balances can be monitored either way; transactions check again
+		if let Err(e) = self.check_deployment().await {
+			warn!("Could not check whether the Safe is deployed: {:#}", e);
+		}
+		Ok(actual)
+	}
+
+	/// Looks up whether the Safe's address holds a contract and records it: an
+	/// address without code is counterfactual, or an externally owned account once
+	/// it has sent transactions.
+	pub async fn check_deployment(&self) -> Result<DeploymentStatus> {
+		let code = self
+			.retrying("eth_getCode", || self.provider.get_code(self.address, None))
+			.await
+			.map_err(|e| provider_error("eth_getCode", e))?;
+		let status = if !code.is_empty() {
+			DeploymentStatus::Deployed
+		} else if self
+			.retrying("eth_getTransactionCount", || self.provider.get_transaction_count(self.address, None))
+			.await
+			.map_err(|e| provider_error("eth_getTransactionCount", e))?
+			.is_zero()
+		{
+			DeploymentStatus::NotDeployed
+		} else {
+			DeploymentStatus::Eoa
+		};
+		let previous = std::mem::replace(&mut *self.deployment.lock().unwrap_or_else(|e| e.into_inner()), status);
+		if status != previous {
+			match status {
+				DeploymentStatus::Deployed => info!("Safe {:?} is deployed", self.address),
+				_ => warn!("{:?} has no contract code ({}); only its balance is monitored", self.address, status),
+			}
+		}
+		Ok(status)
+	}
+
+	/// Whether the Safe's address holds a contract, as last
+	/// [checked](Self::check_deployment).
+	pub fn deployment_status(&self) -> DeploymentStatus {
+		*self.deployment.lock().unwrap_or_else(|e| e.into_inner())
+	}
+
+	/// Refuses to transact from an address without code, checking again unless it
+	/// was seen deployed: a counterfactual Safe may have been deployed since. A check
+	/// that fails lets the transaction go on to fail on its own.
+	async fn ensure_deployed(&self) -> Result<()> {
+		if self.deployment_status() == DeploymentStatus::Deployed {
+			return Ok(());
+		}
+		match self.check_deployment().await {
+			Ok(DeploymentStatus::Deployed) => Ok(()),
+			Ok(status) => Err(SafeError::SafeNotDeployed { address: self.address, status }.into()),
+			Err(e) => {
+				debug!("Could not check whether the Safe is deployed: {:#}", e);
+				Ok(())
+			}
+		}
+	}
+
+	/// The chain id found by [`init`](Self::init), `None` before it succeeded.
+	pub fn chain_id(&self) -> Option<u64> {
+		self.chain_id.get().copied()
+	}
+
+	/// The chain id from [`init`](Self::init), or else as the endpoint reports it.
+	async fn current_chain_id(&self) -> Result<U256> {
+		match self.chain_id() {
+			Some(chain_id) => Ok(chain_id.into()),
+			None => Ok(self.provider.get_chainid().await.map_err(|e| provider_error("eth_chainId", e))?),
+		}
+	}
+
+	/// Current gas price on the home chain, in wei.
+	pub async fn gas_price(&self) -> Result<U256> {
+		Ok(self.retrying("eth_gasPrice", || self.provider.get_gas_price()).await.map_err(|e| provider_error("eth_gasPrice", e))?)
+	}
+
+	pub async fn get_balance(&self) -> Result<U256> {
+		debug!("Fetching balance for address: {:?}", self.address);
+		
+		let balance = self
+			.retrying("eth_getBalance", || self.provider.get_balance(self.address, None))
+			.await
+			.map_err(|e| {
+				error!("Provider error while fetching balance: {}", e);
+				unanswered_or("eth_getBalance", &e, || SafeError::ProviderError(format!("Failed to fetch balance: {}", e)))
+			})?;
+		self.record_balance(BalanceSample { timestamp: self.clock.now_utc(), balance });
+		Ok(balance)
+	}
+
+	/// Balance at `block`. A node that no longer holds the state of that block, like
+	/// one without archive data asked about more than its latest blocks, fails with
+	/// [`SafeError::HistoricalDataUnavailable`].
+	pub async fn get_balance_at(&self, block: BlockId) -> Result<U256> {
+		debug!("Fetching balance for address {:?} at {:?}", self.address, block);
+		self.retrying("eth_getBalance", || self.provider.get_balance(self.address, Some(block))).await.map_err(|e| {
+			if is_missing_state(&e) {
+				self.historical_state.store(false, Ordering::Relaxed);
+				SafeError::HistoricalDataUnavailable { block, reason: e.to_string() }.into()
+			} else {
+				unanswered_or("eth_getBalance", &e, || SafeError::ProviderError(format!("balance at {:?}: {}", block, e))).into()
+			}
+		})
+	}
+
+	/// Number of the last block mined at or before the Unix time `timestamp`,
+	/// binary-searched over block timestamps.
+	pub async fn block_at_timestamp(&self, timestamp: u64) -> Result<u64> {
+		let mined_at = |number: u64| async move {
+			let block = self
+				.retrying("eth_getBlockByNumber", || self.provider.get_block(number))
+				.await
+				.map_err(|e| unanswered_or("eth_getBlockByNumber", &e, || SafeError::ProviderError(format!("block {}: {}", number, e))))?
+				.ok_or_else(|| SafeError::ProviderError(format!("block {} not found", number)))?;
+			Ok::<_, anyhow::Error>(block.timestamp.low_u64())
+		};
+		let latest = self
+			.retrying("eth_blockNumber", || self.provider.get_block_number())
+			.await
+			.map_err(|e| provider_error("eth_blockNumber", e))?
+			.as_u64();
+		if mined_at(latest).await? <= timestamp {
+			return Ok(latest);
+		}
+		if mined_at(0).await? > timestamp {
+			return Err(SafeError::HistoricalDataUnavailable {
+				block: BlockNumber::Earliest.into(),
+				reason: format!("{} is before the first block", timestamp),
+			}
+			.into());
+		}
+		// Block `low` was mined at or before `timestamp`, block `high` after it
+		let (mut low, mut high) = (0, latest);
+		while high - low > 1 {
+			let middle = low + (high - low) / 2;
+			if mined_at(middle).await? <= timestamp {
+				low = middle;
+			} else {
+				high = middle;
+			}
+		}
+		debug!("Block {} is the last one mined by {}", low, timestamp);
+		Ok(low)
+	}
+
+	/// Balance as of the Unix time `timestamp`, at the last block mined by then.
+	pub async fn get_balance_at_timestamp(&self, timestamp: u64) -> Result<U256> {
+		let block = self.block_at_timestamp(timestamp).await?;
+		self.get_balance_at(BlockNumber::Number(block.into()).into()).await
+	}
+
+	/// The balance of about 24 hours ago, for comparison with the current one. It is
+	/// looked up at most once an hour, and not at all once the node turned out to
+	/// keep no historical state; `None` when it cannot be had.
+	pub async fn balance_day_ago(&self) -> Option<U256> {
+		if !self.historical_state.load(Ordering::Relaxed) {
+			return None;
+		}
+		let now = self.clock.now_utc();
+		if let Some((looked_up, balance)) = *self.day_ago.lock().unwrap_or_else(|e| e.into_inner()) {
+			if now < looked_up + DAY_AGO_REFRESH_SECS {
+				return balance;
+			}
+		}
+		let balance = match self.get_balance_at_timestamp(now.saturating_sub(24 * 3600)).await {
+			Ok(balance) => Some(balance),
+			Err(e) => {
+				debug!("No balance of a day ago: {:#}", e);
+				None
+			}
+		};
+		*self.day_ago.lock().unwrap_or_else(|e| e.into_inner()) = Some((now, balance));
+		balance
+	}
+
+	/// Transfers into and out of the Safe since the last call: ERC-20 `Transfer` logs
+	/// naming it as sender or recipient, oldest first, then the change of its ETH
+	/// balance over the same blocks, if any. The first call only notes the latest
+	/// block, so history from before the agent started is not reported. The last
+	/// block scanned is kept in the [store](Self::set_store), so a restart picks up
+	/// where the previous run left off instead of reporting the same transfers
+	/// again. Logs are asked for in ranges of at most
+	/// [`set_log_block_range`](Self::set_log_block_range) blocks, halved whenever
+	/// the node refuses a range as too large.
+	pub async fn watch_transfers(&self) -> Result<Vec<TransferEvent>> {
+		let mut scan = self.transfer_scan.lock().await;
+		let cursor = format!("transfers:{:?}", self.address);
+		if scan.is_none() {
+			if let Some(store) = &self.store {
+				match store.block_cursor(&cursor).await {
+					Ok(block) => *scan = block.map(|block| TransferScan { block, balance: None }),
+					Err(e) => warn!("Failed to load the last block scanned for transfers: {:#}", e),
+				}
+			}
+		}
+		let latest = self
+			.retrying("eth_blockNumber", || self.provider.get_block_number())
+			.await
+			.map_err(|e| provider_error("eth_blockNumber", e))?
+			.as_u64();
+		let Some(last) = *scan else {
+			let balance = self.get_balance_at(latest.into()).await?;
+			info!("Watching transfers of {:?} from block {}", self.address, latest + 1);
+			*scan = Some(TransferScan { block: latest, balance: Some(balance) });
+			self.save_transfer_cursor(&cursor, latest).await;
+			return Ok(Vec::new());
+		};
+		// Nothing new, or a node behind the one asked before
+		if latest <= last.block {
+			return Ok(Vec::new());
+		}
+
+		let mut transfers = self.transfer_logs(last.block + 1, latest).await?;
+		let previous = match last.balance {
+			Some(balance) => Some(balance),
+			// Restarted: the balance at the stored block, if the node still has it
+			None => self.get_balance_at(last.block.into()).await.ok(),
+		};
+		let balance = self.get_balance_at(latest.into()).await?;
+		match previous {
+			Some(previous) if balance > previous => transfers.push(TransferEvent {
+				token: None,
+				from: None,
+				to: Some(self.address),
+				amount: balance - previous,
+				tx_hash: None,
+				block: latest,
+			}),
+			Some(previous) if balance < previous => transfers.push(TransferEvent {
+				token: None,
+				from: Some(self.address),
+				to: None,
+				amount: previous - balance,
+				tx_hash: None,
+				block: latest,
+			}),
+			_ => {}
+		}
+		debug!("Scanned blocks {}..={} for transfers: {} found", last.block + 1, latest, transfers.len());
+		*scan = Some(TransferScan { block: latest, balance: Some(balance) });
+		self.save_transfer_cursor(&cursor, latest).await;
+		Ok(transfers)
+	}
+
+	async fn save_transfer_cursor(&self, cursor: &str, block: u64) {
+		if let Some(store) = &self.store {
+			if let Err(e) = store.set_block_cursor(cursor, block).await {
+				warn!("Failed to store the last block scanned for transfers: {:#}", e);
+			}
+		}
+	}
+
+	/// ERC-20 transfers from or to the Safe within blocks `from..=to`, in the order
+	/// they were logged.
+	async fn transfer_logs(&self, from: u64, to: u64) -> Result<Vec<TransferEvent>> {
+		let topic = H256::from(keccak256("Transfer(address,address,uint256)"));
+		let account = H256::from(self.address);
+		let filters = [Filter::new().topic0(topic).topic1(account), Filter::new().topic0(topic).topic2(account)];
+		let mut logs: BTreeMap<(u64, U256, Option<H256>), Log> = BTreeMap::new();
+		let mut start = from;
+		let mut range = self.log_block_range;
+		while start <= to {
+			let end = to.min(start.saturating_add(range - 1));
+			let mut found = Vec::new();
+			let mut refused = None;
+			for filter in &filters {
+				let filter = filter.clone().from_block(start).to_block(end);
+				// A refused range is made smaller rather than asked for again
+				let page = match self.provider.get_logs(&filter).await {
+					Err(e) if is_transient(&e) && !is_log_range_limit(&e) => {
+						self.retrying("eth_getLogs", || self.provider.get_logs(&filter)).await
+					}
+					page => page,
+				};
+				match page {
+					Ok(page) => found.extend(page),
+					Err(e) => {
+						refused = Some(e);
+						break;
+					}
+				}
+			}
+			match refused {
+				None => {
+					// A transfer to itself matches both filters
+					for log in found.into_iter().filter(|log| log.removed != Some(true)) {
+						let key = (
+							log.block_number.unwrap_or_default().as_u64(),
+							log.log_index.unwrap_or_default(),
+							log.transaction_hash,
+						);
+						logs.insert(key, log);
+					}
+					start = end + 1;
+				}
+				// Down to a single block there is nothing left to split
+				Some(e) if is_log_range_limit(&e) && end > start => {
+					range = (range.min(end - start + 1) / 2).max(1);
+					debug!("eth_getLogs refused blocks {}..={}, asking for {} at a time: {}", start, end, range, e);
+				}
+				Some(e) => {
+					return Err(unanswered_or("eth_getLogs", &e, || {
+						SafeError::ProviderError(format!("eth_getLogs for blocks {}..={}: {}", start, end, e))
+					})
+					.into())
+				}
+			}
+		}
+		// An ERC-721 transfer has the same signature, with the token id as a fourth topic
+		Ok(logs
+			.into_values()
+			.filter(|log| log.topics.len() == 3 && log.data.len() == 32)
+			.map(|log| TransferEvent {
+				token: Some(log.address),
+				from: Some(Address::from(log.topics[1])),
+				to: Some(Address::from(log.topics[2])),
+				amount: U256::from_big_endian(&log.data),
+				tx_hash: log.transaction_hash,
+				block: log.block_number.unwrap_or_default().as_u64(),
+			})
+			.collect())
+	}
+
+	/// Adds `sample` to the history. A read in the same second replaces the previous
+	/// one; a top-up starts the history over, since the spending before it says
+	/// nothing about how long the new funds last.
+	fn record_balance(&self, sample: BalanceSample) {
+		let mut history = self.history.lock().unwrap_or_else(|e| e.into_inner());
+		match history.back() {
+			Some(last) if sample.balance > last.balance => {
+				debug!("Balance rose from {} to {} wei, restarting the burn rate", last.balance, sample.balance);
+				history.clear();
+			}
+			Some(last) if last.timestamp == sample.timestamp => {
+				history.pop_back();
+			}
+			_ => {}
+		}
+		if history.len() == BALANCE_HISTORY_CAPACITY {
+			history.pop_front();
+		}
+		history.push_back(sample);
+	}
+
+	/// Home chain balances read since the last top-up, oldest first; at most the
+	/// latest 256.
+	pub fn balance_history(&self) -> Vec<BalanceSample> {
+		self.history.lock().unwrap_or_else(|e| e.into_inner()).iter().copied().collect()
+	}
+
+	/// How long until the balance drops below the minimum at the rate it fell over
+	/// the latest samples, fitted as a line. `None` while there are fewer than two
+	/// samples or the balance is not falling; zero once it is below the minimum.
+	pub fn estimated_depletion(&self) -> Option<Duration> {
+		let history = self.history.lock().unwrap_or_else(|e| e.into_inner());
+		let samples: Vec<(f64, f64)> = history
+			.iter()
+			.skip(history.len().saturating_sub(BURN_RATE_SAMPLES))
+			.map(|sample| (sample.timestamp as f64, TokenAmount::native(sample.balance).as_f64()))
+			.collect();
+		let (_, current) = *samples.last()?;
+		let n = samples.len() as f64;
+		let mean_t = samples.iter().map(|(t, _)| t).sum::<f64>() / n;
+		let mean_b = samples.iter().map(|(_, b)| b).sum::<f64>() / n;
+		let covariance: f64 = samples.iter().map(|(t, b)| (t - mean_t) * (b - mean_b)).sum();
+		let variance: f64 = samples.iter().map(|(t, _)| (t - mean_t).powi(2)).sum();
+		if variance == 0.0 {
+			return None;
+		}
+		// ETH per second
+		let slope = covariance / variance;
+		if slope.is_nan() || slope >= 0.0 {
+			return None;
+		}
+		let left = current - TokenAmount::native(self.min_balance).as_f64();
+		Duration::try_from_secs_f64((left / -slope).max(0.0)).ok()
+	}
+
+	/// Makes the provider call `call`, again after a backoff while it fails
+	/// [transiently](is_transient) and the retry policy allows more attempts.
+	async fn retrying<T, F, Fut>(&self, method: &str, call: F) -> Result<T, ProviderError>
+	where
+		F: Fn() -> Fut,
+		Fut: Future<Output = Result<T, ProviderError>>,
+	{
+		let mut attempt = 1;
+		loop {
+			match call().await {
+				Err(e) if attempt < self.retry.max_attempts && is_transient(&e) => {
+					let delay = self.retry.delay(attempt, rand::thread_rng().gen_range(-1.0..=1.0));
+					warn!(
+						"{} failed (attempt {} of {}), retrying in {:?}: {}",
+						method, attempt, self.retry.max_attempts, delay, e
+					);
+					self.clock.sleep(delay).await;
+					attempt += 1;
+				}
+				result => return result,
+			}
+		}
+	}
+
+	/// Balance of the ERC-20 `token` in its smallest unit. A call that reverts, or an
+	/// address that holds no contract, is a [`SafeError::TokenCallFailed`].
+	pub async fn get_token_balance(&self, token: Address) -> Result<U256> {
+		self.token_balance_of(token, self.address).await
+	}
+
+	/// Balance of the ERC-20 `token` held by `holder`.
+	async fn token_balance_of(&self, token: Address, holder: Address) -> Result<U256> {
+		debug!("Fetching {:?} balance for address: {:?}", token, holder);
+		match Erc20::new(token, Arc::new(self.provider.clone())).balance_of(holder).call().await {
+			Ok(balance) => Ok(balance),
+			Err(e) => Err(self.token_error(token, e).await.into()),
+		}
+	}
+
+	/// Balances of all `queries`, in order, read with a single `eth_call` to
+	/// [Multicall3](MULTICALL3), or with one call each on a chain without it. A query
+	/// that fails, like a token whose `balanceOf` reverts, has its own error and
+	/// leaves the others unaffected; the batch as a whole fails only when the node
+	/// does not answer in time.
+	pub async fn get_balances_batch(&self, queries: &[BalanceQuery]) -> Result<Vec<Result<U256>>> {
+		if queries.len() > 1 && self.multicall.load(Ordering::Relaxed) {
+			if let Some(balances) = self.multicall_balances(queries).await? {
+				return Ok(balances);
+			}
+		}
+		let reads = queries.iter().map(|query| async move {
+			match *query {
+				BalanceQuery::Native(account) => self.balance_of(account).await,
+				BalanceQuery::Token { token, holder } => self.token_balance_of(token, holder).await,
+			}
+		});
+		Ok(futures::future::join_all(reads).await)
+	}
+
+	/// The balances of `queries` through Multicall3's `aggregate3`, each call allowed
+	/// to fail on its own; `None` when Multicall3 is missing or its answer unusable,
+	/// for the balances to be read one by one.
+	async fn multicall_balances(&self, queries: &[BalanceQuery]) -> Result<Option<Vec<Result<U256>>>> {
+		let multicall = Address::from_str(MULTICALL3)?;
+		let calls = queries
+			.iter()
+			.map(|query| {
+				let (target, data) = query.call(multicall);
+				Token::Tuple(vec![Token::Address(target), Token::Bool(true), Token::Bytes(data)])
+			})
+			.collect();
+		let mut data = ethers::utils::id("aggregate3((address,bool,bytes)[])").to_vec();
+		data.extend(abi::encode(&[Token::Array(calls)]));
+		let call = TypedTransaction::Legacy(TransactionRequest::new().to(multicall).data(data));
+
+		let output = match self.retrying("eth_call", || self.provider.call(&call, None)).await {
+			Ok(output) => output,
+			Err(e) if timed_out(&e).is_some() => return Err(provider_error("eth_call", e).into()),
+			Err(e) => {
+				warn!("Multicall3 balance read failed, reading balances one by one: {}", e);
+				return Ok(None);
+			}
+		};
+		if output.is_empty() {
+			info!("No Multicall3 at {:?} on this chain; reading balances one by one", multicall);
+			self.multicall.store(false, Ordering::Relaxed);
+			return Ok(None);
+		}
+		// (bool success, bytes returnData)[], one per call
+		let result_type = abi::ParamType::Tuple(vec![abi::ParamType::Bool, abi::ParamType::Bytes]);
+		let results = abi::decode(&[abi::ParamType::Array(Box::new(result_type))], &output)
+			.ok()
+			.and_then(|decoded| decoded.into_iter().next()?.into_array())
+			.filter(|results| results.len() == queries.len());
+		let Some(results) = results else {
+			warn!("Unexpected Multicall3 answer 0x{}; reading balances one by one", hex::encode(&output));
+			return Ok(None);
+		};
+		debug!("Read {} balances with one Multicall3 call", queries.len());
+		let balances = queries
+			.iter()
+			.zip(results)
+			.map(|(query, result)| match result.into_tuple().as_deref() {
+				Some([Token::Bool(success), Token::Bytes(data)]) => query.balance(*success, data),
+				_ => Err(SafeError::ProviderError("unexpected Multicall3 result".to_string()).into()),
+			})
+			.collect();
+		Ok(Some(balances))
+	}
+
+	/// How much of the ERC-20 `token` `spender` may pull from the Safe, in its smallest
+	/// unit. A call that reverts, or an address that holds no contract, is a
+	/// [`SafeError::TokenCallFailed`].
+	pub async fn get_allowance(&self, token: Address, spender: Address) -> Result<U256> {
+		match Erc20::new(token, Arc::new(self.provider.clone())).allowance(self.address, spender).call().await {
+			Ok(allowance) => {
+				debug!("Allowance of {:?} for {:?}: {}", token, spender, allowance);
+				Ok(allowance)
+			}
+			Err(e) => Err(self.token_error(token, e).await.into()),
+		}
+	}
+
+	/// Whether `spender` may already pull `amount` of `token` from the Safe, or else
+	/// the approvals for [`execute_transaction`](Self::execute_transaction) to send
+	/// first, approving exactly `amount`.
+	pub async fn ensure_allowance(&self, token: Address, spender: Address, amount: U256) -> Result<AllowanceStatus> {
+		let allowance = self.get_allowance(token, spender).await?;
+		Ok(match protocol_actions::approvals(token, spender, allowance, amount) {
+			approvals if approvals.is_empty() => AllowanceStatus::Sufficient,
+			approvals => AllowanceStatus::Approve(approvals),
+		})
+	}
+
+	/// Decimals of the ERC-20 `token`: as configured, or read from the contract once.
+	pub async fn token_decimals(&self, token: Address) -> Result<u32> {
+		if let Some(decimals) = self.decimals.lock().unwrap_or_else(|e| e.into_inner()).get(&token) {
+			return Ok(*decimals);
+		}
+		let decimals = match Erc20::new(token, Arc::new(self.provider.clone())).decimals().call().await {
+			Ok(decimals) => u32::from(decimals),
+			Err(e) => return Err(self.token_error(token, e).await.into()),
+		};
+		self.decimals.lock().unwrap_or_else(|e| e.into_inner()).insert(token, decimals);
+		Ok(decimals)
+	}
+
+	/// Balances of every configured token, read in one
+	/// [batch](Self::get_balances_batch), in config order.
+	pub async fn get_all_balances(&self) -> Result<Vec<TokenBalance>> {
+		let addresses = self.tokens.iter().map(|token| token.address()).collect::<Result<Vec<_>>>()?;
+		let queries: Vec<_> = addresses.iter().map(|&token| BalanceQuery::Token { token, holder: self.address }).collect();
+		let decimals = self.tokens.iter().zip(&addresses).map(|(token, &address)| async move {
+			self.token_decimals(address).await.with_context(|| format!("Failed to read the {} balance", token.symbol))
+		});
+		let (balances, decimals) =
+			tokio::try_join!(self.get_balances_batch(&queries), futures::future::try_join_all(decimals))?;
+
+		let mut all = Vec::with_capacity(self.tokens.len());
+		for (((token, address), balance), decimals) in self.tokens.iter().zip(addresses).zip(balances).zip(decimals) {
+			let raw = balance.with_context(|| format!("Failed to read the {} balance", token.symbol))?;
+			info!("{} balance: {} ({:?})", token.symbol, format_units_prec(raw, decimals, 6), address);
+			all.push(TokenBalance { symbol: token.symbol.clone(), token: address, amount: TokenAmount { raw, decimals } });
+		}
+		Ok(all)
+	}
+
+	/// A reverted call or a missing contract as [`SafeError::TokenCallFailed`]; a node
+	/// that could not be reached as [`SafeError::ProviderError`].
+	async fn token_error(&self, token: Address, error: ContractError<RpcProvider>) -> SafeError {
+		match self.call_failure(token, error).await {
+			Ok(reason) => {
+				warn!("Token call to {:?} failed: {}", token, reason);
+				SafeError::TokenCallFailed { token, reason }
+			}
+			Err(e) => e,
+		}
+	}
+
+	/// Why a call to `contract` failed when the contract is at fault: it reverted,
+	/// there is no code at the address, or the answer did not decode. A node that
+	/// could not be reached is a [`SafeError::ProviderError`] instead.
+	async fn call_failure(&self, contract: Address, error: ContractError<RpcProvider>) -> Result<String, SafeError> {
+		let reverted = error.is_revert()
+			|| error.as_middleware_error().and_then(|e| e.as_error_response()).is_some_and(|e| e.message.contains("revert"));
+		match error {
+			_ if reverted => Ok(format!("call reverted: {}", error)),
+			ContractError::MiddlewareError { e } => Err(provider_error("eth_call", e)),
+			ContractError::ProviderError { e } => Err(provider_error("eth_call", e)),
+			// An address without code answers every call with no data
+			other => match self.provider.get_code(contract, None).await {
+				Ok(code) if code.is_empty() => Ok("no contract at this address".to_string()),
+				_ => Ok(format!("unexpected answer: {}", other)),
+			},
+		}
+	}
+
+	fn safe_contract(&self) -> GnosisSafe<RpcProvider> {
+		GnosisSafe::new(self.address, Arc::new(self.provider.clone()))
+	}
+
+	/// A Safe call that failed as [`SafeError::NotASafe`], unless the node could not be
+	/// reached.
+	async fn safe_error(&self, error: ContractError<RpcProvider>) -> anyhow::Error {
+		match self.call_failure(self.address, error).await {
+			Ok(reason) => {
+				warn!("{:?} does not answer as a Safe: {}", self.address, reason);
+				SafeError::NotASafe(self.address).into()
+			}
+			Err(e) => e.into(),
+		}
+	}
+
+	pub async fn get_owners(&self) -> Result<Vec<Address>> {
+		match self.safe_contract().get_owners().call().await {
+			Ok(owners) => Ok(owners),
+			Err(e) => Err(self.safe_error(e).await),
+		}
+	}
+
+	/// Number of owner signatures a Safe transaction needs.
+	pub async fn get_threshold(&self) -> Result<U256> {
+		match self.safe_contract().get_threshold().call().await {
+			Ok(threshold) => Ok(threshold),
+			Err(e) => Err(self.safe_error(e).await),
+		}
+	}
+
+	/// Nonce of the next Safe transaction, which differs from the account nonce.
+	pub async fn get_safe_nonce(&self) -> Result<U256> {
+		match self.safe_contract().nonce().call().await {
+			Ok(nonce) => Ok(nonce),
+			Err(e) => Err(self.safe_error(e).await),
+		}
+	}
+
+	/// Version of the Safe contract, e.g. "1.3.0".
+	pub async fn get_version(&self) -> Result<String> {
+		match self.safe_contract().version().call().await {
+			Ok(version) => Ok(version),
+			Err(e) => Err(self.safe_error(e).await),
+		}
+	}
+
+	/// Every module enabled on the Safe, in the order the Safe lists them, read a page
+	/// at a time.
+	pub async fn get_enabled_modules(&self) -> Result<Vec<Address>> {
+		let mut modules: Vec<Address> = Vec::new();
+		let mut start = SENTINEL_MODULES;
+		loop {
+			let (page, next) = match self.safe_contract().get_modules_paginated(start, MODULES_PAGE_SIZE.into()).call().await {
+				Ok(page) => page,
+				Err(e) => return Err(self.safe_error(e).await),
+			};
+			// Safe 1.3.0 answers with the first module left out as `next`, later versions
+			// with the last one returned; both list what follows `start`, so the next
+			// page starts after the last module of this one
+			let Some(&last) = page.last() else { break };
+			if page.iter().any(|module| modules.contains(module)) {
+				warn!("The module list of {:?} loops back at {:?}", self.address, last);
+				return Err(SafeError::NotASafe(self.address).into());
+			}
+			modules.extend(page);
+			if next == SENTINEL_MODULES || next.is_zero() {
+				break;
+			}
+			start = last;
+		}
+		Ok(modules)
+	}
+
+	/// The guard checking every Safe transaction, if one is set. Safes before 1.3.0
+	/// have none.
+	pub async fn get_guard(&self) -> Result<Option<Address>> {
+		let slot = self
+			.retrying("eth_getStorageAt", || self.provider.get_storage_at(self.address, guard_storage_slot(), None))
+			.await
+			.map_err(|e| provider_error("eth_getStorageAt", e))?;
+		let guard = Address::from_slice(&slot[12..]);
+		Ok((!guard.is_zero()).then_some(guard))
+	}
+
+	/// Fails with [`SafeError::UnexpectedModule`] for the first enabled module, or a
+	/// guard, missing from the [allowlist](Self::set_allowed_modules); every one is
+	/// logged.
+	pub async fn check_modules(&self) -> Result<()> {
+		let (modules, guard) = tokio::try_join!(self.get_enabled_modules(), self.get_guard())?;
+		debug!("Safe {:?} modules: {:?}, guard: {:?}", self.address, modules, guard);
+		let mut unexpected = None;
+		for (kind, address) in modules.into_iter().map(|m| ("Module", m)).chain(guard.map(|g| ("Guard", g))) {
+			if !self.allowed_modules.contains(&address) {
+				error!("{} {:?} on Safe {:?} is not in safe.allowed_modules", kind, address, self.address);
+				unexpected.get_or_insert(address);
+			}
+		}
+		match unexpected {
+			Some(address) => Err(SafeError::UnexpectedModule(address).into()),
+			None => Ok(()),
+		}
+	}
+
+	/// Reads the Safe's version, owners, threshold and nonce, and logs them.
+	pub async fn inspect_safe(&self) -> Result<SafeInfo> {
+		let (version, owners, threshold, nonce) =
+			tokio::try_join!(self.get_version(), self.get_owners(), self.get_threshold(), self.get_safe_nonce())?;
+		let listed: Vec<String> = owners.iter().map(|owner| format!("{:?}", owner)).collect();
+		info!(
+			"Safe {:?} v{}: {} of {} owners ({}), nonce {}",
+			self.address,
+			version,
+			threshold,
+			owners.len(),
+			listed.join(", "),
+			nonce
+		);
+		Ok(SafeInfo { version, owners, threshold, nonce })
+	}
+
+	/// The `safeTxHash` of `tx` on this Safe, as its `nonce` or else as the Safe's next
+	/// transaction.
+	pub async fn safe_tx_hash(&self, tx: &SafeTransaction) -> Result<H256> {
+		let nonce = match tx.nonce {
+			Some(nonce) => nonce,
+			None => self.get_safe_nonce().await?,
+		};
+		let chain_id = self.current_chain_id().await?;
+		Ok(tx.safe_tx_hash(chain_id, self.address, nonce))
+	}
+
+	/// An owner's signature of `tx` in the form the Safe checks: `r`, `s` and a `v` of
+	/// 27 or 28, 65 bytes signing the `safeTxHash` itself, or an `eth_sign` message
+	/// of it (`v` of 31 or 32) from a Ledger.
+	pub async fn sign_safe_tx(&self, tx: &SafeTransaction, signer: &OwnerSigner) -> Result<Bytes> {
+		let hash = self.safe_tx_hash(tx).await?;
+		signer.sign_safe_tx_hash(hash).await
+	}
+
+	/// Signs `tx` and proposes it to the Safe Transaction Service, where the other
+	/// owners confirm it. Returns its `safeTxHash`.
+	pub async fn propose_transaction(&self, tx: &SafeTransaction) -> Result<H256> {
+		self.check_operation(tx)?;
+		let signer = self.signer.as_ref().ok_or(SafeError::NoSigner)?;
+		let service = self.transaction_service.as_ref().ok_or_else(|| {
+			SafeError::ProposalRejected("no Safe Transaction Service for this chain (safe.transaction_service_urls)".to_string())
+		})?;
+		let nonce = match tx.nonce {
+			Some(nonce) => nonce,
+			None => self.get_safe_nonce().await?,
+		};
+		let tx = SafeTransaction { nonce: Some(nonce), ..tx.clone() };
+		let safe_tx_hash = self.safe_tx_hash(&tx).await?;
+		let signature = self.sign_safe_tx(&tx, signer).await?;
+		let proposal = Proposal::new(&tx, nonce, safe_tx_hash, signer.address().await?, &signature);
+		service.propose(self.address, &proposal).await?;
+		info!("Proposed Safe transaction {:?} as nonce {} to {}", safe_tx_hash, nonce, service.base_url());
+		Ok(safe_tx_hash)
+	}
+
+	/// Proposes `tx`, and once more with a fresh nonce when the one read from the node
+	/// turns out executed already.
+	async fn propose_with_fresh_nonce(&self, tx: &SafeTransaction) -> Result<(U256, H256)> {
+		let nonce = match tx.nonce {
+			Some(nonce) => nonce,
+			None => self.get_safe_nonce().await?,
+		};
+		let at = |nonce| SafeTransaction { nonce: Some(nonce), ..tx.clone() };
+		match self.propose_transaction(&at(nonce)).await {
+			Err(e) if tx.nonce.is_none() && matches!(e.downcast_ref(), Some(SafeError::NonceAlreadyUsed(_))) => {
+				let fresh = self.get_safe_nonce().await?.max(nonce + 1);
+				warn!("Safe nonce {} is already used, proposing as {}", nonce, fresh);
+				Ok((fresh, self.propose_transaction(&at(fresh)).await?))
+			}
+			result => Ok((nonce, result?)),
+		}
+	}
+
+	/// Signs `tx` as the configured owner and keeps it, as its nonce or else the Safe's
+	/// next one, for the other owners to [sign](Self::add_signature) before it is
+	/// [executed](Self::execute_when_ready). Returns its `safeTxHash`; preparing a
+	/// pending transaction again keeps the signatures collected.
+	pub async fn prepare_transaction(&self, tx: &SafeTransaction) -> Result<H256> {
+		self.check_operation(tx)?;
+		let signer = self.signer.as_ref().ok_or(SafeError::NoSigner)?;
+		let nonce = match tx.nonce {
+			Some(nonce) => nonce,
+			None => self.get_safe_nonce().await?,
+		};
+		let tx = SafeTransaction { nonce: Some(nonce), ..tx.clone() };
+		let safe_tx_hash = self.safe_tx_hash(&tx).await?;
+		// Kept before signing, which can take minutes on a Ledger, so that a second
+		// prepare of the same transaction meanwhile finds it pending
+		match self.pending.lock().unwrap_or_else(|e| e.into_inner()).entry(safe_tx_hash) {
+			Entry::Occupied(_) => {
+				info!("Safe transaction {:?} is already pending", safe_tx_hash);
+				return Ok(safe_tx_hash);
+			}
+			Entry::Vacant(entry) => {
+				entry.insert(PendingSafeTx { tx, safe_tx_hash, signatures: BTreeMap::new() });
+			}
+		}
+		let signed = match signer.sign_safe_tx_hash(safe_tx_hash).await {
+			Ok(signature) => self.add_signature(safe_tx_hash, signature).await,
+			Err(e) => Err(e),
+		};
+		if let Err(e) = signed {
+			self.pending.lock().unwrap_or_else(|e| e.into_inner()).remove(&safe_tx_hash);
+			return Err(e);
+		}
+		info!("Prepared Safe transaction {:?} as nonce {}, awaiting the other owners' signatures", safe_tx_hash, nonce);
+		Ok(safe_tx_hash)
+	}
+
+	/// Adds an owner's `signature` to the pending transaction `safe_tx_hash` and
+	/// returns the owner. A signature that recovers to someone not among the Safe's
+	/// owners fails with [`SafeError::NotAnOwner`], a second one of the same owner
+	/// with [`SafeError::DuplicateSignature`].
+	pub async fn add_signature(&self, safe_tx_hash: H256, signature: Bytes) -> Result<Address> {
+		if !self.pending.lock().unwrap_or_else(|e| e.into_inner()).contains_key(&safe_tx_hash) {
+			return Err(SafeError::UnknownPendingTransaction(safe_tx_hash).into());
+		}
+		let signer = recover_signer(safe_tx_hash, &signature)?;
+		if !self.get_owners().await?.contains(&signer) {
+			warn!("Refusing a signature of {:?} by {:?}, not an owner", safe_tx_hash, signer);
+			return Err(SafeError::NotAnOwner(signer).into());
+		}
+		let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+		let tx = pending.get_mut(&safe_tx_hash).ok_or(SafeError::UnknownPendingTransaction(safe_tx_hash))?;
+		if tx.signatures.contains_key(&signer) {
+			return Err(SafeError::DuplicateSignature(signer).into());
+		}
+		tx.signatures.insert(signer, signature);
+		info!("Added the signature of {:?} to {:?} ({} collected)", signer, safe_tx_hash, tx.signature_count());
+		Ok(signer)
+	}
+
+	/// The pending transaction `safe_tx_hash`, with the signatures collected so far.
+	pub fn pending_transaction(&self, safe_tx_hash: H256) -> Option<PendingSafeTx> {
+		self.pending.lock().unwrap_or_else(|e| e.into_inner()).get(&safe_tx_hash).cloned()
+	}
+
+	/// Executes the pending transaction `safe_tx_hash` once it has as many signatures
+	/// as the Safe's threshold, like [`execute_transaction`](Self::execute_transaction)
+	/// but with the collected signatures, sent by the configured owner. `None` while
+	/// signatures are missing. An executed transaction is no longer pending.
+	pub async fn execute_when_ready(&self, safe_tx_hash: H256) -> Result<Option<ExecutionOutcome>> {
+		let pending = self.pending_transaction(safe_tx_hash).ok_or(SafeError::UnknownPendingTransaction(safe_tx_hash))?;
+		let threshold = self.get_threshold().await?;
+		if U256::from(pending.signature_count()) < threshold {
+			info!("Safe transaction {:?} has {} of {} signatures", safe_tx_hash, pending.signature_count(), threshold);
+			return Ok(None);
+		}
+		let signatures = pending.packed_signatures();
+		let outcome = self.execute_signed(pending.tx, Some(signatures)).await?;
+		if let ExecutionOutcome::Executed(_) = outcome {
+			self.pending.lock().unwrap_or_else(|e| e.into_inner()).remove(&safe_tx_hash);
+		}
+		Ok(Some(outcome))
+	}
+
+	/// Fails with [`SafeError::NotASafe`] unless the address answers `getThreshold`
+	/// with a threshold; checked once, since a Safe stays one.
+	async fn ensure_safe(&self) -> Result<()> {
+		if self.verified_safe.load(Ordering::Relaxed) {
+			return Ok(());
+		}
+		if self.get_threshold().await?.is_zero() {
+			warn!("{:?} is a Safe without owners set up", self.address);
+			return Err(SafeError::NotASafe(self.address).into());
+		}
+		self.verified_safe.store(true, Ordering::Relaxed);
+		Ok(())
+	}
+
+	/// Reads the native balance on every watched chain concurrently. A chain whose
+	/// endpoint fails is reported as unknown; the others are unaffected.
+	pub async fn watched_balances(&self) -> MultiChainBalances {
+		let mut reads = tokio::task::JoinSet::new();
+		for chain in &self.watched {
+			let (name, provider, min_balance, address) =
+				(chain.name.clone(), chain.provider.clone(), chain.min_balance, self.address);
+			reads.spawn(async move {
+				let balance = match provider.get_balance(address, None).await {
+					Ok(balance) => ChainBalance::observed(balance, min_balance),
+					Err(e) => {
+						warn!("Failed to fetch balance on {}: {}", name, e);
+						ChainBalance::unknown(min_balance, e.to_string())
+					}
+				};
+				(name, balance)
+			});
+		}
+
+		let mut balances = MultiChainBalances::new();
+		while let Some(read) = reads.join_next().await {
+			match read {
+				Ok((name, balance)) => {
+					balances.insert(name, balance);
+				}
+				Err(e) => error!("Balance read task failed: {}", e),
+			}
+		}
+		balances
+	}
+
+	/// Balance of another account on the home chain.
+	async fn balance_of(&self, address: Address) -> Result<U256> {
+		self.retrying("eth_getBalance", || self.provider.get_balance(address, None))
+			.await
+			.map_err(|e| unanswered_or("eth_getBalance", &e, || SafeError::ProviderError(format!("balance of {:?}: {}", address, e))).into())
+	}
+
+	/// Balances of every monitored account, the primary one included, read in one
+	/// [batch](Self::get_balances_batch). An account that cannot be read has its own
+	/// error; the others are unaffected.
+	pub async fn get_balances(&self) -> HashMap<Address, Result<U256>> {
+		let accounts = self.accounts();
+		let balances = self.native_balances(&accounts).await;
+		for (address, balance) in accounts.iter().zip(&balances) {
+			if let (true, Ok(balance)) = (*address == self.address, balance) {
+				self.record_balance(BalanceSample { timestamp: self.clock.now_utc(), balance: *balance });
+			}
+		}
+		accounts.into_iter().zip(balances).collect()
+	}
+
+	/// ETH balances of `accounts` through [`get_balances_batch`](Self::get_balances_batch),
+	/// each failing with the batch when the node does not answer in time.
+	async fn native_balances(&self, accounts: &[Address]) -> Vec<Result<U256>> {
+		let queries: Vec<_> = accounts.iter().map(|&account| BalanceQuery::Native(account)).collect();
+		match self.get_balances_batch(&queries).await {
+			Ok(balances) => balances,
+			Err(e) => {
+				let reason = format!("{:#}", e);
+				error!("Failed to fetch balances: {}", reason);
+				accounts.iter().map(|_| Err(anyhow::anyhow!("{}", reason))).collect()
+			}
+		}
+	}
+
+	/// [`get_balances`](Self::get_balances) evaluated against each account's own
+	/// thresholds.
+	pub async fn check_balance_thresholds(&self) -> HashMap<Address, Result<BalanceStatus>> {
+		self.get_balances()
+			.await
+			.into_iter()
+			.map(|(address, balance)| (address, balance.map(|balance| self.account_status(address, balance))))
+			.collect()
+	}
+
+	/// Where `balance` stands against the thresholds of `address`: the primary
+	/// account's own, or the account's minimum with critical at half of it.
+	pub fn account_status(&self, address: Address, balance: U256) -> BalanceStatus {
+		match self.accounts.get(&address) {
+			Some(min_balance) if address != self.address => BalanceStatus::evaluate(balance, *min_balance),
+			_ => self.balance_status(balance),
+		}
+	}
+
+	/// Balances of the accounts added with [`add_account`](Self::add_account), read
+	/// in one [batch](Self::get_balances_batch), for reports; the primary account is
+	/// left to [`get_balance`](Self::get_balance).
+	pub async fn account_balances(&self) -> BTreeMap<Address, ChainBalance> {
+		let accounts: Vec<_> = self.accounts.keys().copied().collect();
+		let balances = self.native_balances(&accounts).await;
+		accounts
+			.into_iter()
+			.zip(balances)
+			.map(|(address, balance)| {
+				let min_balance = self.accounts[&address];
+				let balance = match balance {
+					Ok(balance) => ChainBalance::observed(balance, min_balance),
+					Err(e) => {
+						warn!("Failed to fetch the balance of {:?}: {:#}", address, e);
+						ChainBalance::unknown(min_balance, format!("{:#}", e))
+					}
+				};
+				(address, balance)
+			})
+			.collect()
+	}
+
+	/// Balance rises published by [`publish_increases`](Self::publish_increases).
+	pub fn subscribe_increases(&self) -> broadcast::Receiver<BalanceIncrease> {
+		self.increases.subscribe()
+	}
+
+	/// Publishes every chain whose balance in `current` is above the one in `previous`.
+	/// Chains not read in both are skipped.
+	pub fn publish_increases(&self, previous: &MultiChainBalances, current: &MultiChainBalances) {
+		for (chain, balance) in current {
+			let was = previous.get(chain).and_then(|b| b.balance);
+			if let (Some(previous), Some(current)) = (was, balance.balance) {
+				if current > previous {
+					debug!("Balance on {} rose from {} to {} wei", chain, previous, current);
+					// Nobody listening is fine
+					let _ = self.increases.send(BalanceIncrease { chain: chain.clone(), previous, current });
+				}
+			}
+		}
+	}
+
+	/// Reads the balance and evaluates it against the thresholds, with the
+	/// hysteresis of [`update_balance_status`](Self::update_balance_status). A low or
+	/// critical balance is a status, not an error; only a balance that cannot be read
+	/// fails.
+	pub async fn check_balance_threshold(&self) -> Result<BalanceStatus> {
+		let balance = self.get_balance().await?;
+		Ok(self.update_balance_status(balance))
+	}
+
+	/// Where `balance` stands against the thresholds given the status of the last
+	/// balance checked, which it then replaces. A balance that went below the minimum
+	/// stays low until it exceeds the minimum times the
+	/// [recovery factor](Self::set_recovery_factor), so one hovering around the
+	/// minimum does not flip back and forth. Changes of status are logged as
+	/// warnings, or errors when critical; an unchanged one only at debug level.
+	pub fn update_balance_status(&self, balance: U256) -> BalanceStatus {
+		let mut state = self.balance_state.lock().unwrap_or_else(|e| e.into_inner());
+		let previous = *state;
+		let status = match self.balance_status(balance) {
+			BalanceStatus::Healthy
+				if matches!(previous, BalanceStatus::Low | BalanceStatus::Critical) && balance <= self.recovery_balance() =>
+			{
+				BalanceStatus::Low
+			}
+			status => status,
+		};
+		*state = status;
+		drop(state);
+		match status {
+			_ if status == previous => debug!("Balance status still {:?}: {} wei", status, balance),
+			BalanceStatus::Critical => error!(
+				"CRITICAL: Balance extremely low! Current: {} wei, Critical: {} wei. Action required: Please fund the account with at least {} wei",
+				balance, self.critical_balance, self.min_balance
+			),
+			BalanceStatus::Low => warn!(
+				"WARNING: Balance ({} wei) is below minimum threshold ({} wei); healthy again above {} wei. Consider funding the account soon.",
+				balance, self.min_balance, self.recovery_balance()
+			),
+			_ if previous == BalanceStatus::Unknown => {
+				info!("Balance is sufficient. Current: {} wei, Minimum required: {} wei", balance, self.min_balance)
+			}
+			_ => warn!(
+				"Balance status {:?} -> {:?}: {} wei, minimum required {} wei",
+				previous, status, balance, self.min_balance
+			),
+		}
+		status
+	}
+
+	/// Balance a low one has to exceed to count as healthy again: the minimum times
+	/// the recovery factor, rounded up to whole wei.
+	pub fn recovery_balance(&self) -> U256 {
+		scale_up(self.min_balance, self.recovery_factor)
+	}
+
+	/// Where `balance` stands against this manager's thresholds.
+	pub fn balance_status(&self, balance: U256) -> BalanceStatus {
+		if balance <= self.critical_balance {
+			BalanceStatus::Critical
+		} else if balance < self.min_balance {
+			BalanceStatus::Low
+		} else {
+			BalanceStatus::Healthy
+		}
+	}
+
+	/// What it takes to bring `balance` up to the minimum balance, not just above the
+	/// critical one, rounded up to a whole 0.001 ETH; zero when it is not below it.
+	pub fn funding_shortfall(&self, balance: U256) -> U256 {
+		let unit = U256::exp10(15);
+		let (units, rest) = self.min_balance.saturating_sub(balance).div_mod(unit);
+		(if rest.is_zero() { units } else { units + 1 }) * unit
+	}
+
+	/// An EIP-681 payment request, e.g. `ethereum:0x…@1?value=1000000000000000`, for
+	/// the [shortfall](Self::funding_shortfall) of the Safe's current balance on the
+	/// chain the endpoint serves. Wallets open it as a prefilled transfer.
+	pub async fn funding_request(&self) -> Result<String> {
+		let balance = self.get_balance().await?;
+		self.funding_request_for(balance).await
+	}
+
+	/// Like [`funding_request`](Self::funding_request), for a balance just read.
+	pub async fn funding_request_for(&self, balance: U256) -> Result<String> {
+		let chain_id = self.current_chain_id().await?;
+		Ok(payment_uri(self.address, chain_id.low_u64(), self.funding_shortfall(balance)))
+	}
+
+	/// The fees a transaction offers now. EIP-1559 when `eth_feeHistory` reports base
+	/// fees: twice the next block's base fee plus the tip, so the transaction stays
+	/// valid through several full blocks, with the configured tip or else the median
+	/// of recent blocks' median tips. A chain without base fees is priced with
+	/// `eth_gasPrice` as legacy.
+	pub async fn fees(&self) -> Result<TxFees> {
+		let history = match self.provider.fee_history(FEE_HISTORY_BLOCKS, BlockNumber::Latest, &[50.0]).await {
+			Ok(history) => Some(history),
+			Err(e) => {
+				debug!("eth_feeHistory failed, pricing gas as legacy: {}", e);
+				None
+			}
+		};
+		let base_fee = history.as_ref().and_then(|history| history.base_fee_per_gas.last().copied());
+		let (Some(history), Some(base_fee)) = (history, base_fee) else {
+			let gas_price = self
+				.retrying("eth_gasPrice", || self.provider.get_gas_price())
+				.await
+				.map_err(|e| provider_error("eth_gasPrice", e))?;
+			return Ok(TxFees::Legacy { gas_price });
+		};
+		let tip = self.priority_fee.unwrap_or_else(|| {
+			let mut tips: Vec<U256> = history.reward.iter().filter_map(|rewards| rewards.first().copied()).collect();
+			tips.sort();
+			tips.get(tips.len() / 2).copied().unwrap_or_default()
+		});
+		Ok(TxFees::Eip1559 { max_fee_per_gas: base_fee * 2 + tip, max_priority_fee_per_gas: tip })
+	}
+
+	/// `txs` as one delegatecall to the canonical MultiSendCallOnly, so they run in
+	/// order and all revert if one does. A single call is returned as it is; calls
+	/// that are themselves delegatecalls cannot be batched.
+	pub fn build_multisend(&self, txs: Vec<SafeTransaction>) -> Result<SafeTransaction> {
+		let batch = protocol_actions::multi_send(Address::from_str(MULTI_SEND_CALL_ONLY)?, &txs)?;
+		debug!("Batched {} calls into {} bytes of MultiSend data", txs.len(), batch.data.len());
+		Ok(batch)
+	}
+
+	/// A call from the Safe approving `spender` to pull `amount` of the ERC-20 `token`:
+	/// an exact amount, as a `U256` in the token's smallest unit, or
+	/// [`Allowance::Unlimited`].
+	pub fn build_approve_tx(&self, token: Address, spender: Address, amount: impl Into<Allowance>) -> SafeTransaction {
+		let amount = amount.into();
+		debug!("Approving {:?} to pull {:?} of {:?}", spender, amount, token);
+		protocol_actions::approve(token, spender, amount.amount())
+	}
+
+	/// A call from the Safe setting the allowance of `spender` on `token` back to zero.
+	pub fn build_revoke_tx(&self, token: Address, spender: Address) -> SafeTransaction {
+		self.build_approve_tx(token, spender, U256::zero())
+	}
+
+	/// A call from the Safe to itself adding `new_owner` and setting the threshold to
+	/// `new_threshold`, which must be possible with the owners it then has.
+	pub async fn build_add_owner_tx(&self, new_owner: Address, new_threshold: U256) -> Result<SafeTransaction> {
+		let owners = self.get_owners().await?;
+		self.check_new_owner(&owners, new_owner)?;
+		check_threshold(new_threshold, owners.len() + 1)?;
+		info!("Adding owner {:?}, threshold {} of {}", new_owner, new_threshold, owners.len() + 1);
+		Ok(protocol_actions::call(
+			self.address,
+			AddOwnerWithThresholdCall { owner: new_owner, threshold: new_threshold }.encode(),
+		))
+	}
+
+	/// A call from the Safe to itself removing `owner` and setting the threshold to
+	/// `new_threshold`, which must be possible with the owners left.
+	pub async fn build_remove_owner_tx(&self, owner: Address, new_threshold: U256) -> Result<SafeTransaction> {
+		let owners = self.get_owners().await?;
+		let prev_owner = prev_owner(&owners, owner)?;
+		check_threshold(new_threshold, owners.len() - 1)?;
+		info!("Removing owner {:?}, threshold {} of {}", owner, new_threshold, owners.len() - 1);
+		Ok(protocol_actions::call(
+			self.address,
+			RemoveOwnerCall { prev_owner, owner, threshold: new_threshold }.encode(),
+		))
+	}
+
+	/// A call from the Safe to itself replacing the owner `old` with `new`, such as a
+	/// compromised key with a fresh one. The threshold stays.
+	pub async fn build_swap_owner_tx(&self, old: Address, new: Address) -> Result<SafeTransaction> {
+		let owners = self.get_owners().await?;
+		let prev_owner = prev_owner(&owners, old)?;
+		self.check_new_owner(&owners, new)?;
+		info!("Swapping owner {:?} for {:?}", old, new);
+		Ok(protocol_actions::call(
+			self.address,
+			SwapOwnerCall { prev_owner, old_owner: old, new_owner: new }.encode(),
+		))
+	}
+
+	/// Refuses an owner the Safe would reject: no address, the sentinel, the Safe
+	/// itself, or one of its `owners` already.
+	fn check_new_owner(&self, owners: &[Address], owner: Address) -> Result<()> {
+		if owner.is_zero() || owner == SENTINEL_OWNERS || owner == self.address {
+			return Err(SafeError::InvalidAddress(format!("{:?} cannot be an owner of the Safe", owner)).into());
+		}
+		if owners.contains(&owner) {
+			return Err(SafeError::AlreadyAnOwner(owner).into());
+		}
+		Ok(())
+	}
+
+	/// Estimates the gas of `tx`. A delegatecall, such as a MultiSend batch, is run in
+	/// the Safe's own context through [`simulate_delegate_call`](Self::simulate_delegate_call),
+	/// since a plain estimate would make its calls from the wrong sender.
+	pub async fn simulate_transaction(&self, tx: &SafeTransaction) -> Result<U256> {
+		self.check_operation(tx)?;
+		self.ensure_deployed().await?;
+		let fees = self.fees().await?;
+		self.simulate_with(tx, &fees).await
+	}
+
+	/// Estimates the gas of deploying `deployment` from the Safe, as a creation
+	/// without a target, and the address the contract would get. The estimate is
+	/// buffered like any other.
+	pub async fn simulate_deployment(&self, deployment: &DeploymentTransaction) -> Result<(U256, Address)> {
+		let balance = self.get_balance().await?;
+		if balance < deployment.value {
+			return Err(SafeError::InsufficientBalance { required: deployment.value, available: balance }.into());
+		}
+		let request: TypedTransaction = TransactionRequest::new()
+			.from(self.address)
+			.value(deployment.value)
+			.data(deployment.init_code.clone())
+			.into();
+		let estimate = self
+			.retrying("eth_estimateGas", || self.provider.estimate_gas(&request, None))
+			.await
+			.map_err(|e| SafeError::GasEstimationFailed(e.to_string()))?;
+		let gas = self.buffered(estimate);
+		self.check_gas_limit(gas)?;
+		let contract_address = self.deployment_address().await?;
+		info!("Deploying {} bytes of init code to {:?} takes {} gas", deployment.init_code.len(), contract_address, gas);
+		Ok((gas, contract_address))
+	}
+
+	/// Where the next contract the Safe creates ends up, from its account nonce.
+	pub async fn deployment_address(&self) -> Result<Address> {
+		Ok(get_contract_address(self.address, self.pending_nonce(self.address).await?))
+	}
+
+	/// [`simulate_transaction`](Self::simulate_transaction) offering `fees`.
+	async fn simulate_with(&self, tx: &SafeTransaction, fees: &TxFees) -> Result<U256> {
+		info!("Simulating transaction to: {:?}", tx.to);
+		debug!("Transaction details: value={}, data_len={}", tx.value, tx.data.len());
+		
+		let balance = self.get_balance().await?;
+		if balance < tx.value {
+			error!(
+				"Insufficient balance for transaction. Required: {} wei, Available: {} wei. Please fund the account with at least {} wei",
+				tx.value, balance, tx.value - balance
+			);
+			return Err(SafeError::InsufficientBalance {
+				required: tx.value,
+				available: balance,
+			}.into());
+		}
+
+		let estimate = if tx.operation == protocol_actions::DELEGATE_CALL {
+			self.simulate_delegate_call(tx).await?
+		} else {
+			let typed_tx = fees.request(self.address, tx.to, tx.value, tx.data.clone().into());
+			self.check_call(&typed_tx, tx.to).await?;
+			self.retrying("eth_estimateGas", || self.provider.estimate_gas(&typed_tx, None)).await
+				.map_err(|e| {
+					error!("Gas estimation failed: {}. Please verify transaction parameters and network conditions", e);
+					SafeError::GasEstimationFailed(e.to_string())
+				})?
+		};
+		Ok(self.buffered(estimate))
+	}
+
+	/// `estimate` with the [gas buffer](Self::set_gas_buffer) applied.
+	fn buffered(&self, estimate: U256) -> U256 {
+		let buffered = buffered_gas(estimate, self.gas_buffer);
+		debug!("Gas estimate {} buffered by {}x to {}", estimate, self.gas_buffer, buffered);
+		buffered
+	}
+
+	/// Refuses `gas` above the [cap](Self::set_max_gas_limit), most likely a node
+	/// glitch rather than a transaction worth its fee.
+	fn check_gas_limit(&self, gas: U256) -> Result<()> {
+		match self.max_gas_limit {
+			Some(cap) if gas > cap => Err(SafeError::GasEstimationFailed(format!(
+				"{} gas is above the cap of {} (safe.max_gas_limit)",
+				gas, cap
+			))
+			.into()),
+			_ => Ok(()),
+		}
+	}
+
+	/// Runs `typed_tx` with `eth_call` at the latest block, so a call that would
+	/// revert fails as a [`SafeError::TransactionFailed`] with its
+	/// [decoded](decode_revert) reason rather than an opaque gas estimation error.
+	async fn check_call(&self, typed_tx: &TypedTransaction, to: Address) -> Result<()> {
+		let e = match self.retrying("eth_call", || self.provider.call(typed_tx, None)).await {
+			Ok(_) => return Ok(()),
+			Err(e) => e,
+		};
+		match e.as_error_response() {
+			Some(response) if response.code == 3 || response.message.contains("revert") => {
+				let data = response.as_revert_data().unwrap_or_default();
+				let reason = decode_revert(&data).unwrap_or_else(|| response.message.clone());
+				error!("Simulated call to {:?} reverts: {}", to, reason);
+				Err(SafeError::TransactionFailed(format!("call to {:?} reverts: {}", to, reason)).into())
+			}
+			_ => Err(unanswered_or("eth_call", &e, || SafeError::ProviderError(format!("eth_call to {:?} failed: {}", to, e))).into()),
+		}
+	}
+
+	/// Carries out `tx` as the run mode and [`SafeExecution`] say. An executed
+	/// transaction is returned once it is [confirmed](Self::set_confirmations); in
+	/// dry-run mode everything up to sending runs and the outcome is
+	/// [`ExecutionOutcome::Simulated`].
+	#[tracing::instrument(skip_all, fields(safe = ?self.address, to = ?tx.to, value_wei = %tx.value))]
+	pub async fn execute_transaction(&self, tx: SafeTransaction) -> Result<ExecutionOutcome> {
+		self.execute_signed(tx, None).await
+	}
+
+	/// Deploys `deployment` from the Safe as [`execute_transaction`](Self::execute_transaction)
+	/// would execute a call, through CreateCall, which must be an allowed
+	/// [delegatecall target](Self::set_delegatecalls). Its value counts against the
+	/// spending limits. The address the contract gets, from the Safe's nonce, is
+	/// logged and returned in the outcome's result.
+	#[tracing::instrument(skip_all, fields(safe = ?self.address, value_wei = %deployment.value))]
+	pub async fn execute_deployment(&self, deployment: &DeploymentTransaction) -> Result<ExecutionOutcome> {
+		let tx = deployment.safe_transaction(Address::from_str(CREATE_CALL)?);
+		if self.mode == RunMode::Observe {
+			return self.execute_spending(tx, None, deployment.value).await;
+		}
+		let contract_address = self.deployment_address().await?;
+		info!("Deploying {} bytes of init code to {:?}", deployment.init_code.len(), contract_address);
+		let outcome = match self.execute_spending(tx, None, deployment.value).await? {
+			ExecutionOutcome::Executed(result) => {
+				ExecutionOutcome::Executed(ExecutionResult { contract_address: Some(contract_address), ..result })
+			}
+			ExecutionOutcome::Simulated(simulated) => ExecutionOutcome::Simulated(SimulatedTransaction {
+				contract_address: Some(contract_address),
+				..simulated
+			}),
+			outcome => outcome,
+		};
+		Ok(outcome)
+	}
+
+	/// Executes `tx` with the owners' `signatures`, or signed by the configured owner
+	/// alone without them.
+	async fn execute_signed(&self, tx: SafeTransaction, signatures: Option<Bytes>) -> Result<ExecutionOutcome> {
+		let value = tx.value;
+		self.execute_spending(tx, signatures, value).await
+	}
+
+	/// [`execute_signed`](Self::execute_signed), counting `value` against the spending
+	/// limits: more than `tx.value` for a deployment funding the contract from a
+	/// delegatecall.
+	async fn execute_spending(&self, tx: SafeTransaction, signatures: Option<Bytes>, value: U256) -> Result<ExecutionOutcome> {
+		self.kill_switch.ensure_running()?;
+		if self.mode == RunMode::Observe {
+			info!("[OBSERVE] Not preparing transaction to {:?} ({} wei)", tx.to, value);
+			return Ok(ExecutionOutcome::Observed);
+		}
+		let result = match self.check_operation(&tx).and_then(|()| self.reserve_spending(value)) {
+			Ok(counted_at) => {
+				let result = self.prepare_and_execute(&tx, signatures.as_ref()).await;
+				// Only value that left, or may have, stays counted
+				let sent = match &result {
+					Ok(outcome) => outcome.tx_hash().is_some(),
+					Err(e) => matches!(e.downcast_ref(), Some(SafeError::NotConfirmed { .. })),
+				};
+				if !sent {
+					self.release_spending(counted_at, value);
+				}
+				result
+			}
+			Err(e) => Err(e),
+		};
+		if let Err(e) = &result {
+			if self.mode == RunMode::Live {
+				self.events.emit(AgentEvent::TransactionFailed {
+					to: tx.to,
+					value_wei: value,
+					reason: format!("{:#}", e),
+				});
+			}
+		}
+		result
+	}
+
+	/// Refuses an operation other than a call or a delegatecall, and a delegatecall
+	/// unless [allowed](Self::set_delegatecalls) to its target, before anything is
+	/// sent to the node: a delegatecall runs foreign code with the Safe's own storage
+	/// and funds.
+	fn check_operation(&self, tx: &SafeTransaction) -> Result<()> {
+		match tx.operation {
+			protocol_actions::CALL => Ok(()),
+			protocol_actions::DELEGATE_CALL if self.allow_delegatecall && self.delegatecall_targets.contains(&tx.to) => Ok(()),
+			protocol_actions::DELEGATE_CALL => {
+				error!("Refusing a delegatecall to {:?}", tx.to);
+				Err(SafeError::DelegatecallNotAllowed(tx.to).into())
+			}
+			operation => Err(SafeError::InvalidOperation(operation).into()),
+		}
+	}
+
+	/// Counts `value` against the spending limits, before anything is simulated, so
+	/// concurrent transactions cannot slip past them together. Returns the time it
+	/// was counted at.
+	fn reserve_spending(&self, value: U256) -> Result<u64> {
+		if let Some(limit) = self.max_value_per_tx.filter(|limit| value > *limit) {
+			return Err(SafeError::SpendingLimitExceeded { limit, attempted: value, window: SpendingWindow::Transaction }.into());
+		}
+		let now = self.clock.now_utc();
+		let mut spent = self.spent.lock().unwrap_or_else(|e| e.into_inner());
+		while spent.front().is_some_and(|(at, _)| at + SPENDING_WINDOW_SECS <= now) {
+			spent.pop_front();
+		}
+		let attempted = spent.iter().fold(value, |total, (_, value)| total.saturating_add(*value));
+		if let Some(limit) = self.max_value_per_day.filter(|limit| attempted > *limit) {
+			return Err(SafeError::SpendingLimitExceeded { limit, attempted, window: SpendingWindow::Day }.into());
+		}
+		spent.push_back((now, value));
+		Ok(now)
+	}
+
+	/// Uncounts `value` counted at `counted_at` for a transaction that was not sent.
+	fn release_spending(&self, counted_at: u64, value: U256) {
+		let mut spent = self.spent.lock().unwrap_or_else(|e| e.into_inner());
+		if let Some(i) = spent.iter().rposition(|entry| *entry == (counted_at, value)) {
+			spent.remove(i);
+		}
+	}
+
+	/// ETH value of the transactions executed within the last 24 hours.
+	pub fn spent_last_day(&self) -> U256 {
+		let now = self.clock.now_utc();
+		let spent = self.spent.lock().unwrap_or_else(|e| e.into_inner());
+		spent
+			.iter()
+			.filter(|(at, _)| at + SPENDING_WINDOW_SECS > now)
+			.fold(U256::zero(), |total, (_, value)| total.saturating_add(*value))
+	}
+
+	/// Forgets the value executed so far, so the daily limit starts over.
+	pub fn reset_spending(&self) {
+		info!("Spending over the last 24 hours reset");
+		self.spent.lock().unwrap_or_else(|e| e.into_inner()).clear();
+	}
+
+	/// Counts the estimated `fee` of a transaction about to be broadcast against the
+	/// gas budget, in place of the fee counted for the transaction it `replaces`, since
+	/// only one of them can be mined. Returns the time it was counted at.
+	fn reserve_gas(&self, fee: U256, replaces: Option<H256>) -> Result<u64> {
+		let now = self.clock.now_utc();
+		let mut gas_spent = self.gas_spent.lock().unwrap_or_else(|e| e.into_inner());
+		while gas_spent.front().is_some_and(|(at, _, _)| at + SPENDING_WINDOW_SECS <= now) {
+			gas_spent.pop_front();
+		}
+		let spent = gas_spent
+			.iter()
+			.filter(|(_, tx_hash, _)| replaces.is_none() || *tx_hash != replaces)
+			.fold(fee, |total, (_, _, fee)| total.saturating_add(*fee));
+		if let Some(budget) = self.daily_gas_budget.filter(|budget| spent > *budget) {
+			error!("Gas budget of {} wei exhausted: {} wei with this transaction", budget, spent);
+			return Err(SafeError::GasBudgetExhausted { spent, budget }.into());
+		}
+		gas_spent.push_back((now, None, fee));
+		Ok(now)
+	}
+
+	/// Settles the estimated `fee` counted at `counted_at`: uncounted when nothing was
+	/// sent, or else tagged with the transaction's hash for [`settle_gas`](Self::settle_gas).
+	fn sent_gas(&self, counted_at: u64, fee: U256, tx_hash: Option<H256>) {
+		let mut gas_spent = self.gas_spent.lock().unwrap_or_else(|e| e.into_inner());
+		let Some(i) = gas_spent.iter().rposition(|entry| *entry == (counted_at, None, fee)) else { return };
+		match tx_hash {
+			Some(tx_hash) => gas_spent[i].1 = Some(tx_hash),
+			None => {
+				gas_spent.remove(i);
+			}
+		}
+	}
+
+	/// Moves the fee counted for `replaced` onto its replacement `tx_hash`, at the
+	/// replacement's estimated `fee` reserved at `counted_at`. Without a fee counted
+	/// for `replaced`, as after a restart, the reservation stays, tagged with `tx_hash`.
+	fn replaced_gas(&self, replaced: H256, counted_at: u64, fee: U256, tx_hash: H256) {
+		let mut gas_spent = self.gas_spent.lock().unwrap_or_else(|e| e.into_inner());
+		let Some(reserved) = gas_spent.iter().rposition(|entry| *entry == (counted_at, None, fee)) else { return };
+		match gas_spent.iter().position(|(_, hash, _)| *hash == Some(replaced)) {
+			Some(i) => {
+				gas_spent[i].1 = Some(tx_hash);
+				gas_spent[i].2 = fee;
+				gas_spent.remove(reserved);
+			}
+			None => gas_spent[reserved].1 = Some(tx_hash),
+		}
+	}
+
+	/// Replaces the estimate counted for the transaction of `receipt` with the fee it
+	/// paid.
+	fn settle_gas(&self, receipt: &TransactionReceipt) {
+		let (Some(gas_used), Some(gas_price)) = (receipt.gas_used, receipt.effective_gas_price) else { return };
+		let mut gas_spent = self.gas_spent.lock().unwrap_or_else(|e| e.into_inner());
+		if let Some(entry) = gas_spent.iter_mut().rev().find(|(_, tx_hash, _)| *tx_hash == Some(receipt.transaction_hash)) {
+			entry.2 = gas_used.saturating_mul(gas_price);
+		}
+	}
+
+	/// Wei paid in gas, or estimated until the receipt arrives, by the transactions
+	/// executed within the last 24 hours. With `confirmations` of 0 no receipt is
+	/// fetched outside [`drain_queue`](Self::drain_queue), so such transactions keep
+	/// their estimate, the most they could pay.
+	pub fn gas_spent_today(&self) -> U256 {
+		let now = self.clock.now_utc();
+		let gas_spent = self.gas_spent.lock().unwrap_or_else(|e| e.into_inner());
+		gas_spent
+			.iter()
+			.filter(|(at, _, _)| at + SPENDING_WINDOW_SECS > now)
+			.fold(U256::zero(), |total, (_, _, fee)| total.saturating_add(*fee))
+	}
+
+	/// Adds `tx` to the end of the submission queue, for [`drain_queue`](Self::drain_queue)
+	/// or [`run_queue`](Self::run_queue) to execute in order.
+	pub fn enqueue(&self, tx: SafeTransaction) {
+		let pending = {
+			let mut queue = self.queue.lock().unwrap_or_else(|e| e.into_inner());
+			queue.push_back(tx);
+			queue.len()
+		};
+		debug!("Transaction queued, {} pending", pending);
+		self.queued.notify_one();
+	}
+
+	/// Queued transactions not submitted yet.
+	pub fn pending_count(&self) -> usize {
+		self.queue.lock().unwrap_or_else(|e| e.into_inner()).len()
+	}
+
+	/// Executes the queued transactions one at a time, oldest first, until the queue
+	/// is empty or `shutdown` is cancelled. No more than the
+	/// [rate](Self::set_submissions_per_minute) are submitted in any minute, and each
+	/// waits for its confirmations, at least one, before the next is submitted, so
+	/// they never race for the signer's nonce. A failed transaction is reported and
+	/// the queue moves on. Returns each submitted transaction with its outcome.
+	pub async fn drain_queue(&self, shutdown: &CancellationToken) -> Vec<(SafeTransaction, Result<ExecutionOutcome>)> {
+		let mut outcomes = Vec::new();
+		while self.pending_count() > 0 {
+			let wait = self.submission_delay();
+			if !wait.is_zero() {
+				debug!("Submission rate reached, next queued transaction in {:?}", wait);
+				tokio::select! {
+					() = shutdown.cancelled() => break,
+					() = self.clock.sleep(wait) => continue,
+				}
+			}
+			if shutdown.is_cancelled() {
+				break;
+			}
+			let Some(tx) = self.queue.lock().unwrap_or_else(|e| e.into_inner()).pop_front() else { break };
+			self.submitted.lock().unwrap_or_else(|e| e.into_inner()).push_back(self.clock.now_instant());
+			let outcome = match self.execute_transaction(tx.clone()).await {
+				// Sent without waiting: the next one waits for this one to be mined
+				Ok(ExecutionOutcome::Executed(mut result)) if self.confirmations == Some(0) => {
+					let tx_hash = result.tx_hash.unwrap_or_default();
+					self.wait_for_confirmation(tx_hash, 1, self.confirmation_timeout).await.map(|receipt| {
+						self.settle_gas(&receipt);
+						result.record_receipt(&receipt, tx.value);
+						ExecutionOutcome::Executed(result)
+					})
+				}
+				outcome => outcome,
+			};
+			if let Err(e) = &outcome {
+				error!("Queued transaction to {:?} failed: {:#}", tx.to, e);
+			}
+			outcomes.push((tx, outcome));
+		}
+		outcomes
+	}
+
+	/// Drains the queue whenever transactions are [enqueued](Self::enqueue) until
+	/// `shutdown` is cancelled, then reports what is left with
+	/// [`shutdown_queue`](Self::shutdown_queue).
+	pub async fn run_queue(&self, shutdown: CancellationToken) {
+		loop {
+			tokio::select! {
+				() = shutdown.cancelled() => break,
+				() = self.queued.notified() => {
+					self.drain_queue(&shutdown).await;
+				}
+			}
+		}
+		self.shutdown_queue().await;
+	}
+
+	/// Empties the queue at shutdown, logging every transaction that was never
+	/// submitted and saving them to the store, where the next start reports them.
+	/// They are not submitted again: the cycle that decided on them is over.
+	pub async fn shutdown_queue(&self) -> Vec<SafeTransaction> {
+		let unsent: Vec<SafeTransaction> = self.queue.lock().unwrap_or_else(|e| e.into_inner()).drain(..).collect();
+		for tx in &unsent {
+			warn!("Queued transaction to {:?} ({} wei) was not submitted before shutdown", tx.to, tx.value);
+		}
+		if let (Some(store), false) = (&self.store, unsent.is_empty()) {
+			if let Err(e) = store.set_unsent_transactions(&unsent).await {
+				warn!("Failed to save {} unsent transactions: {:#}", unsent.len(), e);
+			}
+		}
+		unsent
+	}
+
+	/// Logs, then forgets, the transactions the previous run left unsent at shutdown.
+	pub async fn report_unsent(&self) -> Result<Vec<SafeTransaction>> {
+		let Some(store) = &self.store else { return Ok(Vec::new()) };
+		let unsent = store.unsent_transactions().await?;
+		if !unsent.is_empty() {
+			warn!("{} queued transactions were not submitted before the last shutdown:", unsent.len());
+			for tx in &unsent {
+				warn!("  to {:?} ({} wei): {}", tx.to, tx.value, serde_json::to_string(tx).unwrap_or_default());
+			}
+			store.set_unsent_transactions(&[]).await?;
+		}
+		Ok(unsent)
+	}
+
+	/// How long until another queued transaction may be submitted.
+	fn submission_delay(&self) -> Duration {
+		let now = self.clock.now_instant();
+		let mut submitted = self.submitted.lock().unwrap_or_else(|e| e.into_inner());
+		while submitted.front().is_some_and(|at| now.saturating_duration_since(*at) >= SUBMISSION_WINDOW) {
+			submitted.pop_front();
+		}
+		match submitted.front() {
+			Some(oldest) if submitted.len() >= self.submissions_per_minute as usize => {
+				(*oldest + SUBMISSION_WINDOW).saturating_duration_since(now)
+			}
+			_ => Duration::ZERO,
+		}
+	}
+
+	async fn prepare_and_execute(&self, tx: &SafeTransaction, signatures: Option<&Bytes>) -> Result<ExecutionOutcome> {
+		info!("Preparing to execute transaction to: {:?}", tx.to);
+		debug!("Transaction value: {} wei", tx.value);
+		self.ensure_deployed().await?;
+		if self.mode == RunMode::Live && self.execution == SafeExecution::Execute && self.signer.is_none() {
+			return Err(SafeError::NoSigner.into());
+		}
+		self.ensure_safe().await?;
+		let safe_tx_hash = self.safe_tx_hash(tx).await?;
+		info!("safeTxHash: {:?}", safe_tx_hash);
+
+		// First simulate to get gas estimate
+		let fees = self.fees().await?;
+		let estimated_gas = self.simulate_with(tx, &fees).await?;
+		info!("Gas estimation successful: {} units", estimated_gas);
+		self.check_gas_limit(estimated_gas)?;
+
+		// At most, since an EIP-1559 transaction pays the base fee plus the tip
+		let fee = estimated_gas * fees.max_gas_price();
+		let total_required = tx.value + fee;
+		let balance = self.get_balance().await?;
+		
+		if balance < total_required {
+			return Err(SafeError::InsufficientBalance {
+				required: total_required,
+				available: balance,
+			}.into());
+		}
+
+		let simulated = SimulatedTransaction {
+			to: tx.to,
+			value: tx.value,
+			calldata_hash: H256::from(keccak256(&tx.data)),
+			safe_tx_hash,
+			estimated_gas,
+			total_cost: total_required,
+			contract_address: None,
+		};
+		if self.mode == RunMode::DryRun {
+			let action =
+				if self.execution == SafeExecution::Propose && signatures.is_none() { "propose" } else { "execute" };
+			info!("[DRY-RUN] Would {} transaction: {}", action, simulated);
+			return Ok(ExecutionOutcome::Simulated(simulated));
+		}
+
+		match self.execution {
+			// Signatures collected here need no proposal
+			SafeExecution::Execute => {}
+			SafeExecution::Propose if signatures.is_some() => {}
+			SafeExecution::Propose => {
+				let (nonce, safe_tx_hash) = self.propose_with_fresh_nonce(tx).await?;
+				self.events.emit(AgentEvent::TransactionProposed { to: tx.to, value_wei: tx.value, nonce, safe_tx_hash });
+				return Ok(ExecutionOutcome::Proposed { nonce, safe_tx_hash });
+			}
+			SafeExecution::Simulate => {
+				info!("[SIMULATE] Not sending transaction: {}", simulated);
+				return Ok(ExecutionOutcome::Simulated(simulated));
+			}
+		}
+
+		let counted_at = self.reserve_gas(fee, None)?;
+		let sent = match signatures {
+			Some(signatures) => self.send_signed_exec(tx, &fees, signatures.clone()).await,
+			None => self.send_exec_transaction(tx, &fees).await,
+		};
+		self.sent_gas(counted_at, fee, sent.as_ref().ok().copied());
+		let tx_hash = sent?;
+		info!("Sent transaction {:?} to {:?} ({} wei)", tx_hash, tx.to, tx.value);
+		debug!("Estimated gas: {}", estimated_gas);
+		let confirmations = match self.confirmations {
+			Some(confirmations) => confirmations,
+			None => default_confirmations(self.current_chain_id().await?),
+		};
+		let mut result = ExecutionResult {
+			tx_hash: Some(tx_hash),
+			gas_estimate: estimated_gas,
+			gas_used: None,
+			total_cost_wei: total_required,
+			block: None,
+			simulated: false,
+			contract_address: None,
+		};
+		if confirmations > 0 {
+			let receipt = self.wait_for_confirmation(tx_hash, confirmations, self.confirmation_timeout).await?;
+			self.settle_gas(&receipt);
+			result.record_receipt(&receipt, tx.value);
+		}
+		let fee = result.total_cost_wei - tx.value;
+		self.events.emit(AgentEvent::TransactionExecuted {
+			to: tx.to,
+			value_wei: tx.value,
+			tx_hash: Some(tx_hash),
+		});
+		if let Some(store) = &self.store {
+			let record = TransactionRecord {
+				timestamp: self.clock.now_utc(),
+				chain: "Ethereum".to_string(),
+				tx_hash: Some(tx_hash),
+				to: tx.to,
+				value_wei: tx.value,
+				fee_wei: fee,
+			};
+			if let Err(e) = store.record_transaction(&record).await {
+				warn!("Failed to record transaction: {:#}", e);
+			}
+		}
+		Ok(ExecutionOutcome::Executed(result))
+	}
+
+	/// Guards every broadcast: nothing leaves for the network outside live mode,
+	/// whichever path got there.
+	fn ensure_live(&self) -> Result<()> {
+		if self.mode != RunMode::Live {
+			error!("Refusing to broadcast a transaction in {} mode", self.mode);
+			return Err(SafeError::BroadcastDisabled(self.mode).into());
+		}
+		Ok(())
+	}
+
+	/// Signs `tx` as the configured owner and sends it as the owner's
+	/// `execTransaction` call to the Safe, offering `fees`. Returns once the node
+	/// accepted the transaction, with its hash. Only a Safe whose threshold the owner
+	/// meets alone can be executed this way.
+	async fn send_exec_transaction(&self, tx: &SafeTransaction, fees: &TxFees) -> Result<H256> {
+		let signer = self.signer.as_ref().ok_or(SafeError::NoSigner)?;
+		let threshold = self.get_threshold().await?;
+		if threshold > U256::one() {
+			return Err(SafeError::SigningFailed(format!(
+				"the Safe needs {} owner signatures; propose the transaction instead (safe.execution = \"propose\"), or collect them with prepare_transaction",
+				threshold
+			))
+			.into());
+		}
+		let signature = self.sign_safe_tx(tx, signer).await?;
+		self.send_signed_exec(tx, fees, signature).await
+	}
+
+	/// Sends `tx` as the configured owner's `execTransaction` call with the owners'
+	/// packed `signatures`, offering `fees`.
+	async fn send_signed_exec(&self, tx: &SafeTransaction, fees: &TxFees, signatures: Bytes) -> Result<H256> {
+		let signer = self.signer.as_ref().ok_or(SafeError::NoSigner)?;
+		let owner = signer.address().await?;
+		let call = self.safe_contract().exec_transaction(
+			tx.to,
+			tx.value,
+			tx.data.clone().into(),
+			tx.operation,
+			tx.safe_tx_gas,
+			U256::zero(),
+			U256::zero(),
+			Address::zero(),
+			Address::zero(),
+			signatures,
+		);
+		// The Safe pays `tx.value` from its own balance
+		let mut request = fees.request(owner, self.address, U256::zero(), call.calldata().unwrap_or_default());
+		// Estimated as the owner's call to the Safe, signature checks included
+		let gas = self
+			.retrying("eth_estimateGas", || self.provider.estimate_gas(&request, None))
+			.await
+			.map_err(|e| SafeError::GasEstimationFailed(e.to_string()))?;
+		let gas = self.buffered(gas);
+		self.check_gas_limit(gas)?;
+		request.set_gas(gas);
+
+		let chain_id = self.current_chain_id().await?;
+		request.set_chain_id(chain_id.as_u64());
+		let mut next_nonce = self.next_nonce.lock().await;
+		let nonce = match *next_nonce {
+			Some(nonce) => nonce,
+			None => self.pending_nonce(owner).await?,
+		};
+		request.set_nonce(nonce);
+		// Nothing is sent when signing fails, so the nonce stays as counted
+		let signed = signer.sign_transaction(&request).await?;
+		self.ensure_live()?;
+		let sent = self.provider.send_raw_transaction(request.rlp_signed(&signed)).await.map(|pending| pending.tx_hash());
+		match sent {
+			Ok(tx_hash) => {
+				*next_nonce = Some(nonce + 1);
+				debug!("Sent execTransaction with nonce {}", nonce);
+				Ok(tx_hash)
+			}
+			Err(e) => {
+				// Whether it was refused or lost on the way, the node knows what to count from
+				*next_nonce = None;
+				error!("Sending execTransaction from {:?} with nonce {} failed: {}", owner, nonce, e);
+				Err(SafeError::TransactionFailed(e.to_string()).into())
+			}
+		}
+	}
+
+	/// Re-sends the transaction `tx_hash`, still pending, with the same nonce, payload
+	/// and gas limit but fees raised by `fee_bump_percent`, at least
+	/// [`MIN_REPLACEMENT_BUMP_PERCENT`], so that it is mined in place of the stuck one.
+	/// Only transactions of the configured signer can be replaced. Returns the hash of
+	/// the replacement; a caller waiting for the original sees it never confirm.
+	/// Refused while the kill switch is on, unlike [`cancel`](Self::cancel).
+	pub async fn speed_up(&self, tx_hash: H256, fee_bump_percent: u64) -> Result<H256> {
+		self.kill_switch.ensure_running()?;
+		let stuck = self.stuck_transaction(tx_hash).await?;
+		let Some(to) = stuck.to else {
+			return Err(SafeError::MalformedTransaction(format!("{:?} deploys a contract", tx_hash)).into());
+		};
+		let fees = TxFees::of(&stuck).bumped(fee_bump_percent);
+		let mut request = fees.request(stuck.from, to, stuck.value, stuck.input.clone());
+		request.set_gas(stuck.gas);
+		self.send_replacement(&stuck, request, &fees).await
+	}
+
+	/// Replaces the transaction `tx_hash`, still pending, with a transfer of nothing
+	/// from the signer to itself with the same nonce and the least fee increase nodes
+	/// accept, so that the original is never mined. Returns the hash of the
+	/// replacement.
+	pub async fn cancel(&self, tx_hash: H256) -> Result<H256> {
+		let stuck = self.stuck_transaction(tx_hash).await?;
+		let fees = TxFees::of(&stuck).bumped(MIN_REPLACEMENT_BUMP_PERCENT);
+		let mut request = fees.request(stuck.from, stuck.from, U256::zero(), Bytes::new());
+		request.set_gas(TRANSFER_GAS);
+		self.send_replacement(&stuck, request, &fees).await
+	}
+
+	/// The transaction `tx_hash` as the node has it, sent by the configured signer and
+	/// not mined yet.
+	async fn stuck_transaction(&self, tx_hash: H256) -> Result<Transaction> {
+		let signer = self.signer.as_ref().ok_or(SafeError::NoSigner)?;
+		let stuck = self
+			.retrying("eth_getTransactionByHash", || self.provider.get_transaction(tx_hash))
+			.await
+			.map_err(|e| provider_error("eth_getTransactionByHash", e))?
+			.ok_or(SafeError::NotPending(tx_hash))?;
+		let receipt = self
+			.retrying("eth_getTransactionReceipt", || self.provider.get_transaction_receipt(tx_hash))
+			.await
+			.map_err(|e| provider_error("eth_getTransactionReceipt", e))?;
+		if receipt.is_some() || stuck.block_number.is_some() {
+			return Err(SafeError::NotPending(tx_hash).into());
+		}
+		let owner = signer.address().await?;
+		if stuck.from != owner {
+			return Err(SafeError::SigningFailed(format!(
+				"{:?} was sent by {:?}, not by the configured signer {:?}",
+				tx_hash, stuck.from, owner
+			))
+			.into());
+		}
+		Ok(stuck)
+	}
+
+	/// Signs `request` with the nonce of `stuck` and sends it, offering `fees`. The
+	/// most it may pay in gas is counted against the budget in place of what `stuck` may.
+	async fn send_replacement(&self, stuck: &Transaction, mut request: TypedTransaction, fees: &TxFees) -> Result<H256> {
+		let signer = self.signer.as_ref().ok_or(SafeError::NoSigner)?;
+		let chain_id = self.current_chain_id().await?;
+		request.set_chain_id(chain_id.as_u64());
+		request.set_nonce(stuck.nonce);
+		let signed = signer.sign_transaction(&request).await?;
+		self.ensure_live()?;
+		let fee = request.gas().copied().unwrap_or_default().saturating_mul(fees.max_gas_price());
+		let counted_at = self.reserve_gas(fee, Some(stuck.hash))?;
+		let sent = self.provider.send_raw_transaction(request.rlp_signed(&signed)).await.map(|pending| pending.tx_hash());
+		let tx_hash = match sent {
+			Ok(tx_hash) => {
+				self.replaced_gas(stuck.hash, counted_at, fee, tx_hash);
+				tx_hash
+			}
+			Err(e) => {
+				self.sent_gas(counted_at, fee, None);
+				return Err(SafeError::TransactionFailed(e.to_string()).into());
+			}
+		};
+		info!(
+			"Replaced {:?} with {:?} (nonce {}), offering up to {} wei per gas",
+			stuck.hash,
+			tx_hash,
+			stuck.nonce,
+			fees.max_gas_price()
+		);
+		Ok(tx_hash)
+	}
+
+	/// Next nonce of `account`, counting its transactions still in the mempool.
+	async fn pending_nonce(&self, account: Address) -> Result<U256> {
+		let nonce = self
+			.retrying("eth_getTransactionCount", || {
+				self.provider.get_transaction_count(account, Some(BlockNumber::Pending.into()))
+			})
+			.await
+			.map_err(|e| provider_error("eth_getTransactionCount", e))?;
+		debug!("Nonce of {:?} from the node: {}", account, nonce);
+		Ok(nonce)
+	}
+
+	/// Refills a critical `balance` from the [funding wallet](Self::set_top_up) up to
+	/// the target, with a plain ETH transfer. The transfer is capped by what is left
+	/// of the daily top-up allowance and by what the funding wallet holds above its
+	/// reserve, fee included. Returns the hash of the transfer once the node accepted
+	/// it, or `None` when nothing was sent: no top-up configured, a balance at or
+	/// above the target, observe or dry-run mode, an earlier top-up still pending, or
+	/// nothing left to send.
+	#[tracing::instrument(skip_all, fields(safe = ?self.address, balance_wei = %balance))]
+	pub async fn top_up(&self, balance: U256) -> Result<Option<H256>> {
+		let Some(top_up) = &self.top_up else {
+			return Ok(None);
+		};
+		if balance >= top_up.target {
+			return Ok(None);
+		}
+		self.kill_switch.ensure_running()?;
+		let wanted = top_up.target - balance;
+		match self.mode {
+			RunMode::Live => {}
+			RunMode::DryRun => {
+				info!("[DRY-RUN] Would top up the Safe with up to {} wei from funding wallet {}", wanted, top_up.funder);
+				return Ok(None);
+			}
+			RunMode::Observe => {
+				info!("[OBSERVE] Not topping up the Safe ({} wei short of the target)", wanted);
+				return Ok(None);
+			}
+		}
+		if let Some(pending) = self.pending_top_up().await? {
+			info!("Top-up {:?} is not mined yet; not topping up again", pending);
+			return Ok(None);
+		}
+		let allowance = top_up.max_per_day.saturating_sub(self.topped_up_last_day());
+		if allowance.is_zero() {
+			warn!(
+				"Not topping up the Safe: the daily top-up cap of {} wei is used up (safe.top_up.max_per_day_eth)",
+				top_up.max_per_day
+			);
+			return Ok(None);
+		}
+
+		let funder = top_up.funder.address().await?;
+		let funds = self
+			.retrying("eth_getBalance", || self.provider.get_balance(funder, None))
+			.await
+			.map_err(|e| provider_error("eth_getBalance", e))?;
+		let fees = self.fees().await?;
+		let mut request = fees.request(funder, self.address, U256::zero(), Bytes::default());
+		let gas = self
+			.retrying("eth_estimateGas", || self.provider.estimate_gas(&request, None))
+			.await
+			.map_err(|e| SafeError::GasEstimationFailed(e.to_string()))?;
+		let gas = self.buffered(gas);
+		self.check_gas_limit(gas)?;
+		let spendable = funds.saturating_sub(top_up.reserve).saturating_sub(gas * fees.max_gas_price());
+		let value = wanted.min(allowance).min(spendable);
+		if value.is_zero() {
+			warn!(
+				"Not topping up the Safe: funding wallet {:?} holds {} wei, no more than its reserve of {} wei and the fee",
+				funder, funds, top_up.reserve
+			);
+			return Ok(None);
+		}
+		request.set_value(value);
+		request.set_gas(gas);
+		request.set_chain_id(self.current_chain_id().await?.as_u64());
+		request.set_nonce(self.pending_nonce(funder).await?);
+
+		let signed = top_up.funder.sign_transaction(&request).await?;
+		self.ensure_live()?;
+		let tx_hash = self
+			.provider
+			.send_raw_transaction(request.rlp_signed(&signed))
+			.await
+			.map(|pending| pending.tx_hash())
+			.map_err(|e| {
+				error!("Top-up of {} wei from {:?} failed: {}", value, funder, e);
+				SafeError::TransactionFailed(e.to_string())
+			})?;
+		let now = self.clock.now_utc();
+		self.topped_up.lock().unwrap_or_else(|e| e.into_inner()).push_back((now, value));
+		*self.last_top_up.lock().unwrap_or_else(|e| e.into_inner()) = Some((now, tx_hash));
+		warn!(
+			"Topped up the Safe with {} wei from funding wallet {:?} in {:?}: {} wei of the daily cap of {} wei left",
+			value,
+			funder,
+			tx_hash,
+			allowance - value,
+			top_up.max_per_day
+		);
+		Ok(Some(tx_hash))
+	}
+
+	/// The last top-up while it has no receipt, for at most the confirmation
+	/// timeout, after which it counts as dropped.
+	async fn pending_top_up(&self) -> Result<Option<H256>> {
+		let last = *self.last_top_up.lock().unwrap_or_else(|e| e.into_inner());
+		let Some((sent_at, tx_hash)) = last else {
+			return Ok(None);
+		};
+		if sent_at + self.confirmation_timeout.as_secs() <= self.clock.now_utc() {
+			return Ok(None);
+		}
+		let receipt = self
+			.retrying("eth_getTransactionReceipt", || self.provider.get_transaction_receipt(tx_hash))
+			.await
+			.map_err(|e| provider_error("eth_getTransactionReceipt", e))?;
+		Ok(receipt.is_none().then_some(tx_hash))
+	}
+
+	/// Wei sent by top-ups within the last 24 hours.
+	pub fn topped_up_last_day(&self) -> U256 {
+		let now = self.clock.now_utc();
+		let mut topped_up = self.topped_up.lock().unwrap_or_else(|e| e.into_inner());
+		while topped_up.front().is_some_and(|(at, _)| at + SPENDING_WINDOW_SECS <= now) {
+			topped_up.pop_front();
+		}
+		topped_up.iter().fold(U256::zero(), |total, (_, value)| total.saturating_add(*value))
+	}
+
+	/// Waits until the transaction `tx_hash` is `confirmations` blocks deep, its own
+	/// block included, and returns its receipt. A transaction that reverted, or whose
+	/// call the Safe reported as failed, is a [`SafeError::TransactionFailed`] with the
+	/// reason; one still without a receipt after `timeout` is a
+	/// [`SafeError::NotConfirmed`]. Receipts are polled, so a reorg that drops the
+	/// transaction's block only delays the answer.
+	pub async fn wait_for_confirmation(
+		&self,
+		tx_hash: H256,
+		confirmations: usize,
+		timeout: Duration,
+	) -> Result<TransactionReceipt> {
+		let started = self.clock.now_instant();
+		loop {
+			match self.confirmed_receipt(tx_hash, confirmations).await {
+				Ok(Some(receipt)) => return Ok(receipt),
+				Ok(None) => {}
+				Err(e) if e.is::<SafeError>() => return Err(e),
+				// The transaction is out; a node that does not answer for a moment
+				// does not change that
+				Err(e) => warn!("Checking transaction {:?} failed: {:#}", tx_hash, e),
+			}
+			let waited = self.clock.now_instant().saturating_duration_since(started);
+			if waited >= timeout {
+				error!("Transaction {:?} not confirmed after {:?}", tx_hash, waited);
+				return Err(SafeError::NotConfirmed { tx_hash, waited }.into());
+			}
+			self.clock.sleep(RECEIPT_POLL_INTERVAL.min(timeout - waited)).await;
+		}
+	}
+
+	/// The receipt of `tx_hash` once it is `confirmations` blocks deep; `None` before.
+	async fn confirmed_receipt(&self, tx_hash: H256, confirmations: usize) -> Result<Option<TransactionReceipt>> {
+		let Some(receipt) = self.provider.get_transaction_receipt(tx_hash).await? else { return Ok(None) };
+		if receipt.status == Some(0.into()) {
+			let reason = self.revert_reason(&receipt).await;
+			error!("Transaction {:?} reverted: {}", tx_hash, reason);
+			return Err(SafeError::TransactionFailed(format!("{:?} reverted: {}", tx_hash, reason)).into());
+		}
+		let execution_failure = H256::from(keccak256("ExecutionFailure(bytes32,uint256)"));
+		if receipt.logs.iter().any(|log| log.address == self.address && log.topics.first() == Some(&execution_failure)) {
+			error!("Transaction {:?} was mined, but the Safe's call failed", tx_hash);
+			return Err(SafeError::TransactionFailed(format!("{:?} was mined, but the Safe's call failed", tx_hash)).into());
+		}
+		let Some(mined) = receipt.block_number else { return Ok(None) };
+		let head = self.provider.get_block_number().await?;
+		let depth = head.saturating_sub(mined).as_usize() + 1;
+		debug!("Transaction {:?} is {} of {} blocks deep", tx_hash, depth, confirmations);
+		Ok((head >= mined && depth >= confirmations).then_some(receipt))
+	}
+
+	/// Why the mined transaction of `receipt` reverted, found by replaying it on the
+	/// state before its block.
+	async fn revert_reason(&self, receipt: &TransactionReceipt) -> String {
+		let tx = match self.provider.get_transaction(receipt.transaction_hash).await {
+			Ok(Some(tx)) => tx,
+			_ => return "reason unknown".to_string(),
+		};
+		let block = receipt.block_number.map(|mined| BlockId::from(mined.saturating_sub(1.into())));
+		match self.provider.call(&(&tx).into(), block).await {
+			Err(e) => match e.as_error_response() {
+				Some(response) => {
+					let data = response.as_revert_data().unwrap_or_default();
+					decode_revert(&data).unwrap_or_else(|| response.message.clone())
+				}
+				None => format!("reason unknown: {}", e),
+			},
+			// It did not revert on its own: it ran out of the gas it was given
+			Ok(_) => format!("out of gas ({} used)", receipt.gas_used.unwrap_or_default()),
+		}
+	}
+
+	/// Runs `tx` as the Safe would, through the `simulate` of its fallback handler,
+	/// which has the Safe delegatecall the SimulateTxAccessor and revert afterwards.
+	/// Returns the gas the transaction used inside the Safe.
+	pub async fn simulate_delegate_call(&self, tx: &SafeTransaction) -> Result<U256> {
+		self.check_operation(tx)?;
+		let accessor = Address::from_str(SIMULATE_TX_ACCESSOR)?;
+		let mut payload = ethers::utils::id("simulate(address,uint256,bytes,uint8)").to_vec();
+		payload.extend(abi::encode(&[
+			Token::Address(tx.to),
+			Token::Uint(tx.value),
+			Token::Bytes(tx.data.clone()),
+			Token::Uint(tx.operation.into()),
+		]));
+		let mut data = ethers::utils::id("simulate(address,bytes)").to_vec();
+		data.extend(abi::encode(&[Token::Address(accessor), Token::Bytes(payload)]));
+		let call = TypedTransaction::Legacy(TransactionRequest::new().to(self.address).data(data));
+
+		let output = match self.provider.call(&call, None).await {
+			Ok(output) => output,
+			Err(e) if e.as_error_response().is_some_and(|e| e.message.contains("revert")) => {
+				return Err(SafeError::GasEstimationFailed(format!("the Safe's simulation reverted: {}", e)).into());
+			}
+			Err(e) => return Err(provider_error("eth_call", e).into()),
+		};
+		// bytes response, holding (uint256 estimate, bool success, bytes returnData)
+		let result = abi::decode(&[abi::ParamType::Bytes], &output)
+			.ok()
+			.and_then(|response| response.into_iter().next()?.into_bytes())
+			.and_then(|response| {
+				abi::decode(&[abi::ParamType::Uint(256), abi::ParamType::Bool, abi::ParamType::Bytes], &response).ok()
+			});
+		let Some([Token::Uint(estimate), Token::Bool(success), Token::Bytes(return_data)]) = result.as_deref() else {
+			return Err(SafeError::GasEstimationFailed(
+				"unexpected simulation answer; the Safe may have no fallback handler".to_string(),
+			)
+			.into());
+		};
+		if !success {
+			error!("Simulated delegatecall to {:?} reverted: 0x{}", tx.to, hex::encode(return_data));
+			return Err(SafeError::GasEstimationFailed(format!("reverted: 0x{}", hex::encode(return_data))).into());
+		}
+		debug!("Simulated delegatecall to {:?} used {} gas", tx.to, estimate);
+		Ok(*estimate)
+	}
+
+	/// The primary account: the Safe whose transactions this manager executes.
+	pub fn get_address(&self) -> Address {
+		self.address
+	}
+
+	/// `address` for logs, as `treasury.mydao.eth (0x2c75…5c23)` when it was configured
+	/// by that name or its reverse record says so. Looked up once per address.
+	pub async fn display_name(&self, address: Address) -> String {
+		self.names.display(address).await
+	}
+
+	/// Also monitors the balance of `address` on the home chain, against the current
+	/// minimum until [`set_account_min_balance`](Self::set_account_min_balance) says
+	/// otherwise. Adding the primary account or one already monitored does nothing.
+	pub fn add_account(&mut self, address: Address) {
+		if address == self.address || self.accounts.contains_key(&address) {
+			return;
+		}
+		info!("Monitoring the balance of {:?} (minimum: {} wei)", address, self.min_balance);
+		self.accounts.insert(address, self.min_balance);
+	}
+
+	/// Stops monitoring `address`; returns whether it was monitored. The primary
+	/// account cannot be removed.
+	pub fn remove_account(&mut self, address: Address) -> bool {
+		let removed = self.accounts.remove(&address).is_some();
+		if removed {
+			info!("No longer monitoring the balance of {:?}", address);
+		}
+		removed
+	}
+
+	/// Every monitored account, the primary one first and the others in address order.
+	pub fn accounts(&self) -> Vec<Address> {
+		let mut others: Vec<Address> = self.accounts.keys().copied().collect();
+		others.sort_unstable();
+		std::iter::once(self.address).chain(others).collect()
+	}
+
+	/// Minimum balance of a monitored account; `None` when `address` is not monitored.
+	pub fn account_min_balance(&self, address: Address) -> Option<U256> {
+		if address == self.address {
+			return Some(self.min_balance);
+		}
+		self.accounts.get(&address).copied()
+	}
+
+	/// Changes the minimum of an account added with [`add_account`](Self::add_account),
+	/// critical at half of it; other addresses are ignored.
+	pub fn set_account_min_balance(&mut self, address: Address, min_balance: U256) {
+		if let Some(account) = self.accounts.get_mut(&address) {
+			*account = min_balance;
+		}
+	}
+
+	pub fn set_events(&mut self, events: EventSender) {
+		self.events = events;
+	}
+
+	pub fn mode(&self) -> RunMode {
+		self.mode
+	}
+
+	/// Provider of the home chain, where the Safe transacts.
+	pub fn provider(&self) -> &RpcProvider {
+		&self.provider
+	}
+
+	/// Observe skips transactions entirely; dry-run simulates them without broadcasting.
+	pub fn set_mode(&mut self, mode: RunMode) {
+		self.mode = mode;
+	}
+
+	/// Multiplies every gas estimate by `buffer`, 1.2 by default, since a plain
+	/// estimate leaves out the Safe's signature checks and can fall short as state
+	/// moves. Less than 1 is refused with [`SafeError::InvalidGasBuffer`].
+	pub fn set_gas_buffer(&mut self, buffer: f64) -> Result<()> {
+		if !(buffer.is_finite() && buffer >= 1.0) {
+			return Err(SafeError::InvalidGasBuffer(buffer).into());
+		}
+		self.gas_buffer = buffer;
+		Ok(())
+	}
+
+	/// Once the balance went below the minimum, it counts as healthy again only from
+	/// the minimum times `factor`, 1.1 by default; 1 turns the hysteresis off. Less
+	/// than 1 is refused with [`SafeError::InvalidRecoveryFactor`].
+	pub fn set_recovery_factor(&mut self, factor: f64) -> Result<()> {
+		if !(factor.is_finite() && factor >= 1.0) {
+			return Err(SafeError::InvalidRecoveryFactor(factor).into());
+		}
+		self.recovery_factor = factor;
+		Ok(())
+	}
+
+	/// Refuses to execute transactions whose buffered gas is above `max_gas_limit`;
+	/// `None` lifts the cap.
+	pub fn set_max_gas_limit(&mut self, max_gas_limit: Option<U256>) {
+		self.max_gas_limit = max_gas_limit;
+	}
+
+	/// Tip of EIP-1559 transactions; `None` follows recent blocks.
+	pub fn set_priority_fee(&mut self, priority_fee: Option<U256>) {
+		self.priority_fee = priority_fee;
+	}
+
+	/// Whether live transactions are executed, proposed or only simulated. Executing
+	/// needs a [signer](Self::set_signer) by the time a transaction is sent; proposing
+	/// needs one up front.
+	pub fn set_execution(&mut self, execution: SafeExecution) -> Result<()> {
+		if execution == SafeExecution::Propose && self.signer.is_none() {
+			return Err(SafeError::NoSigner.into());
+		}
+		self.execution = execution;
+		Ok(())
+	}
+
+	pub fn execution(&self) -> SafeExecution {
+		self.execution
+	}
+
+	/// Transactions and proposals are signed by the owner `signer`, a key or a
+	/// Ledger, which also pays the gas of executed transactions.
+	pub fn set_signer(&mut self, signer: impl Into<OwnerSigner>) {
+		let signer = signer.into();
+		info!("Safe transactions are signed by owner {}", signer);
+		self.signer = Some(signer);
+		*self.next_nonce.get_mut() = None;
+	}
+
+	/// The signing owner's address, `None` without a signer. Reaching a Ledger
+	/// fails with [`SafeError::SignerUnavailable`] while it is locked or unplugged.
+	pub async fn signer_address(&self) -> Result<Option<Address>> {
+		match &self.signer {
+			Some(signer) => signer.address().await.map(Some),
+			None => Ok(None),
+		}
+	}
+
+	/// `eth_getLogs` calls ask for at most `blocks` blocks, 1 at least; a node
+	/// refusing a range can still make them smaller.
+	pub fn set_log_block_range(&mut self, blocks: u64) {
+		self.log_block_range = blocks.max(1);
+	}
+
+	/// A critical balance is refilled from `top_up`'s funding wallet; `None` only
+	/// reports it.
+	pub fn set_top_up(&mut self, top_up: Option<TopUp>) {
+		if let Some(top_up) = &top_up {
+			info!(
+				"Critical balances are topped up to {} wei by {}, at most {} wei a day, keeping {} wei",
+				top_up.target, top_up.funder, top_up.max_per_day, top_up.reserve
+			);
+		}
+		self.top_up = top_up;
+	}
+
+	/// Whether a critical balance is [topped up](Self::top_up).
+	pub fn tops_up(&self) -> bool {
+		self.top_up.is_some()
+	}
+
+	/// Most queued transactions [`drain_queue`](Self::drain_queue) submits in any
+	/// minute; at least 1.
+	pub fn set_submissions_per_minute(&mut self, per_minute: u32) {
+		self.submissions_per_minute = per_minute.max(1);
+	}
+
+	/// Executed transactions wait for `confirmations` blocks, [by chain](default_confirmations)
+	/// when `None`, for at most `timeout`. 0 returns as soon as the node accepted one.
+	pub fn set_confirmations(&mut self, confirmations: Option<usize>, timeout: Duration) {
+		self.confirmations = confirmations;
+		self.confirmation_timeout = timeout;
+	}
+
+	/// Whether delegatecalls are simulated and sent, and the only `targets` they may
+	/// have. Others fail with [`SafeError::DelegatecallNotAllowed`]; by default none
+	/// is allowed, with MultiSendCallOnly as the only target once they are.
+	pub fn set_delegatecalls(&mut self, allowed: bool, targets: Vec<Address>) {
+		if allowed {
+			info!("Delegatecalls allowed to {:?}", targets);
+		}
+		self.allow_delegatecall = allowed;
+		self.delegatecall_targets = targets;
+	}
+
+	/// The modules and guard [`check_modules`](Self::check_modules) expects on the
+	/// Safe; none by default.
+	pub fn set_allowed_modules(&mut self, modules: Vec<Address>) {
+		if !modules.is_empty() {
+			info!("Modules allowed on the Safe: {:?}", modules);
+		}
+		self.allowed_modules = modules;
+	}
+
+	/// Transactions sending more ETH value than `per_tx`, or taking the value executed
+	/// over the last 24 hours above `per_day`, fail with
+	/// [`SafeError::SpendingLimitExceeded`] before they are simulated. `None` lifts a
+	/// limit.
+	pub fn set_spending_limits(&mut self, per_tx: Option<U256>, per_day: Option<U256>) {
+		self.max_value_per_tx = per_tx;
+		self.max_value_per_day = per_day;
+	}
+
+	/// Transactions whose estimated fee would take the gas paid over the last 24 hours
+	/// above `budget` fail with [`SafeError::GasBudgetExhausted`] before they are
+	/// broadcast. `None` lifts the budget.
+	pub fn set_daily_gas_budget(&mut self, budget: Option<U256>) {
+		self.daily_gas_budget = budget;
+	}
+
+	/// Proposals go to `service`.
+	pub fn set_transaction_service(&mut self, service: TransactionService) {
+		debug!("Safe transactions are proposed to {}", service.base_url());
+		self.transaction_service = Some(service);
+	}
+
+	/// Transactions are refused while `kill_switch` is on.
+	pub fn set_kill_switch(&mut self, kill_switch: KillSwitch) {
+		self.kill_switch = kill_switch;
+	}
+
+	/// Values balances in USD; without a price source they are only reported in ETH.
+	pub fn set_prices(&mut self, prices: Arc<dyn PriceSource>) {
+		self.prices = Some(prices);
+	}
+
+	pub fn prices(&self) -> Option<&dyn PriceSource> {
+		self.prices.as_deref()
+	}
+
+	/// USD value of `wei`, when the price source has a fresh ETH price.
+	pub async fn value_usd(&self, wei: U256) -> Option<f64> {
+		eth_value_usd(self.prices.as_deref()?, wei).await
+	}
+
+	/// Executed transactions are recorded in `store`.
+	pub fn set_store(&mut self, store: Arc<dyn Store>) {
+		self.store = Some(store);
+	}
+
+	pub fn min_balance(&self) -> U256 {
+		self.min_balance
+	}
+
+	/// Also watches the address on `chain` through `provider`, with its own threshold.
+	pub fn watch_chain(&mut self, chain: impl Into<String>, provider: RpcProvider, min_balance: U256) {
+		let name = chain.into();
+		info!("Watching balance on {} (minimum: {} wei)", name, min_balance);
+		self.watched.push(WatchedChain { name, provider, min_balance });
+	}
+
+	pub fn watched_chains(&self) -> impl Iterator<Item = &str> {
+		self.watched.iter().map(|chain| chain.name.as_str())
+	}
+
+	/// Minimum balance on a watched chain; `None` when `chain` is not watched.
+	pub fn watched_min_balance(&self, chain: &str) -> Option<U256> {
+		self.watched.iter().find(|c| c.name == chain).map(|c| c.min_balance)
+	}
+
+	/// Changes the threshold of a watched chain; unknown chains are ignored.
+	pub fn set_watched_min_balance(&mut self, chain: &str, min_balance: U256) {
+		if let Some(watched) = self.watched.iter_mut().find(|c| c.name == chain) {
+			watched.min_balance = min_balance;
+		}
+	}
+
+	/// ERC-20 tokens read by [`get_all_balances`](Self::get_all_balances); their
+	/// configured decimals are used instead of reading them from the contract.
+	pub fn set_tokens(&mut self, tokens: Vec<TokenConfig>) -> Result<()> {
+		let mut decimals = self.decimals.lock().unwrap_or_else(|e| e.into_inner());
+		for token in &tokens {
+			if let Some(configured) = token.decimals {
+				decimals.insert(token.address()?, configured);
+			}
+		}
+		drop(decimals);
+		self.tokens = tokens;
+		Ok(())
+	}
+
+	/// Sets the minimum balance, with the critical threshold at half of it.
+	pub fn set_min_balance(&mut self, min_balance: U256) {
+		self.min_balance = min_balance;
+		self.critical_balance = min_balance / 2;
+		info!(
+			"Updated balance thresholds - Minimum: {} wei, Critical: {} wei",
+			min_balance, self.critical_balance
+		);
+	}
+
+	/// Sets both balance thresholds. The critical one must be below the minimum,
+	/// unless both are zero; otherwise the thresholds are left as they were.
+	pub fn set_balance_thresholds(&mut self, min_balance: U256, critical_balance: U256) -> Result<()> {
+		if critical_balance >= min_balance && !(critical_balance.is_zero() && min_balance.is_zero()) {
+			return Err(SafeError::InvalidThresholds { critical: critical_balance, minimum: min_balance }.into());
+		}
+		self.min_balance = min_balance;
+		self.critical_balance = critical_balance;
+		info!(
+			"Updated balance thresholds - Minimum: {} wei, Critical: {} wei",
+			min_balance, critical_balance
+		);
+		Ok(())
+	}
+
+	pub fn critical_balance(&self) -> U256 {
+		self.critical_balance
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::agents::test_utils::{mock_node, mount_answers, test_provider};
+	use crate::errors::ErrorCategory;
+	use ethers::abi::Token;
+	use ethers::signers::{LocalWallet, Signer};
+	use std::str::FromStr;
+
+	/// A manager whose node reports `balance` wei for every address.
+	async fn setup_test_manager(balance: u64) -> (SafeManager, wiremock::MockServer) {
+		use wiremock::{matchers::method, Mock, MockServer, ResponseTemplate};
+
+		let server = MockServer::start().await;
+		mount_answers(&server, &[("eth_getCode", serde_json::json!("0x6080"))]).await;
+		Mock::given(method("POST"))
+			.respond_with(ResponseTemplate::new(200).set_body_json(
+				serde_json::json!({ "jsonrpc": "2.0", "id": 1, "result": format!("{:#x}", balance) }),
+			))
+			.mount(&server)
+			.await;
+		let address = Address::from_str("0x0000000000000000000000000000000000000000")
+			.expect("Failed to parse address");
+		(SafeManager::new(address, test_provider(&server.uri())).unwrap(), server)
+	}
+
+	/// Receipt of `tx_hash`, mined in `block` with `status`, with the Safe's `logs`.
+	fn receipt(tx_hash: H256, block: u64, status: u64, logs: serde_json::Value) -> serde_json::Value {
+		serde_json::json!({
+			"transactionHash": tx_hash,
+			"transactionIndex": "0x0",
+			"blockHash": H256::repeat_byte(0xbb),
+			"blockNumber": format!("{:#x}", block),
+			"from": Address::repeat_byte(0xaa),
+			"to": Address::repeat_byte(0x11),
+			"cumulativeGasUsed": "0x186a0",
+			"gasUsed": "0x186a0",
+			"effectiveGasPrice": "0x3b9aca00",
+			"contractAddress": null,
+			"logs": logs,
+			"logsBloom": format!("0x{}", "00".repeat(256)),
+			"status": format!("{:#x}", status),
+		})
+	}
+
+	#[tokio::test]
+	async fn test_balance_threshold() {
+		let (mut manager, _node) = setup_test_manager(800_000_000_000_000).await; // 0.0008 ETH
+		manager.set_min_balance(U256::from(1_000_000_000_000_000_u64)); // 0.001 ETH
+		assert_eq!(manager.check_balance_threshold().await.unwrap(), BalanceStatus::Low);
+
+		manager.set_min_balance(U256::from(500_000_000_000_000_u64)); // 0.0005 ETH
+		assert_eq!(manager.check_balance_threshold().await.unwrap(), BalanceStatus::Healthy);
+	}
+
+	#[tokio::test]
+	async fn test_balance_hysteresis() {
+		let (mut manager, _node) = setup_test_manager(0).await;
+		manager.set_balance_thresholds(U256::from(1_000), U256::from(500)).unwrap();
+		assert_eq!(manager.recovery_balance(), U256::from(1_100));
+		let walk = |manager: &SafeManager, balances: &[u64]| {
+			balances.iter().map(|balance| manager.update_balance_status(U256::from(*balance))).collect::<Vec<_>>()
+		};
+		use BalanceStatus::{Critical, Healthy, Low};
+
+		// Hovering around the minimum: low from the first dip until 1,100 is exceeded
+		assert_eq!(
+			walk(&manager, &[1_050, 990, 1_020, 980, 1_099, 1_100, 1_101, 1_050, 999, 1_200]),
+			vec![Healthy, Low, Low, Low, Low, Low, Healthy, Healthy, Low, Healthy]
+		);
+		// Back from critical the same way; out of critical as soon as it is above it
+		assert_eq!(walk(&manager, &[500, 1_000, 501, 1_090, 1_150]), vec![Critical, Low, Low, Low, Healthy]);
+
+		// A factor of 1 flips just above the minimum itself
+		manager.set_recovery_factor(1.0).unwrap();
+		assert_eq!(walk(&manager, &[999, 1_000, 1_001, 999]), vec![Low, Low, Healthy, Low]);
+		let error = manager.set_recovery_factor(0.95).unwrap_err();
+		assert!(matches!(error.downcast_ref(), Some(SafeError::InvalidRecoveryFactor(_))), "{:#}", error);
+		assert_eq!(manager.recovery_balance(), U256::from(1_000));
+	}
+
+	#[tokio::test]
+	async fn test_deployment_status() {
+		let node = |code: &'static str, nonce: &'static str| async move {
+			mock_node(&[
+				("eth_chainId", serde_json::json!("0x1")),
+				("eth_getCode", serde_json::json!(code)),
+				("eth_getTransactionCount", serde_json::json!(nonce)),
+				("eth_getBalance", serde_json::json!("0xde0b6b3a7640000")),
+			])
+			.await
+		};
+		let transfer = SafeTransaction {
+			to: Address::repeat_byte(0x22),
+			value: U256::one(),
+			data: Vec::new(),
+			operation: 0,
+			safe_tx_gas: U256::zero(),
+			nonce: None,
+		};
+
+		let server = node("0x6080", "0x1").await;
+		let manager = SafeManager::new(Address::repeat_byte(0x11), test_provider(&server.uri())).unwrap();
+		assert_eq!(manager.deployment_status(), DeploymentStatus::Unknown);
+		manager.init().await.unwrap();
+		assert_eq!(manager.deployment_status(), DeploymentStatus::Deployed);
+
+		for (nonce, expected) in [("0x0", DeploymentStatus::NotDeployed), ("0x5", DeploymentStatus::Eoa)] {
+			let server = node("0x", nonce).await;
+			let manager = SafeManager::new(Address::repeat_byte(0x11), test_provider(&server.uri())).unwrap();
+			manager.init().await.unwrap();
+			assert_eq!(manager.deployment_status(), expected);
+			// The balance is still monitored, but nothing is simulated or executed
+			assert_eq!(manager.get_balance().await.unwrap(), U256::exp10(18));
+			for error in [
+				manager.simulate_transaction(&transfer).await.unwrap_err(),
+				manager.execute_transaction(transfer.clone()).await.unwrap_err(),
+			] {
+				match error.downcast::<SafeError>() {
+					Ok(SafeError::SafeNotDeployed { status, .. }) => assert_eq!(status, expected),
+					other => panic!("expected an undeployed Safe, got {:?}", other),
+				}
+			}
+		}
+		assert_eq!(serde_json::to_value(DeploymentStatus::NotDeployed).unwrap(), serde_json::json!("not_deployed"));
+
+		// A rate limit at startup is retried rather than leaving the status unknown
+		let server = wiremock::MockServer::start().await;
+		wiremock::Mock::given(wiremock::matchers::body_partial_json(serde_json::json!({ "method": "eth_getCode" })))
+			.respond_with(wiremock::ResponseTemplate::new(200).set_body_json(serde_json::json!({
+				"jsonrpc": "2.0", "id": 1, "error": { "code": 429, "message": "too many requests" },
+			})))
+			.up_to_n_times(1)
+			.mount(&server)
+			.await;
+		mount_answers(&server, &[("eth_chainId", serde_json::json!("0x1")), ("eth_getCode", serde_json::json!("0x6080"))]).await;
+		let retry = RetryPolicy { initial_backoff: Duration::from_millis(1), ..RetryPolicy::default() };
+		let manager = SafeManager::with_retry(Address::repeat_byte(0x11), test_provider(&server.uri()), retry).unwrap();
+		manager.init().await.unwrap();
+		assert_eq!(manager.deployment_status(), DeploymentStatus::Deployed);
+	}
+
+	#[tokio::test]
+	async fn test_critical_balance() {
+		let (mut manager, _node) = setup_test_manager(400_000_000_000_000).await; // 0.0004 ETH
+		manager.set_min_balance(U256::from(1_000_000_000_000_000_u64)); // 0.001 ETH
+		assert_eq!(manager.check_balance_threshold().await.unwrap(), BalanceStatus::Critical);
+		assert_eq!(manager.balance_status(U256::from(500_000_000_000_000_u64)), BalanceStatus::Critical);
+		assert_eq!(manager.balance_status(U256::from(500_000_000_000_001_u64)), BalanceStatus::Low);
+
+		// Only a balance that cannot be read is an error
+		let once = RetryPolicy { max_attempts: 1, ..RetryPolicy::default() };
+		let manager = SafeManager::with_retry(Address::zero(), test_provider("http://127.0.0.1:1"), once).unwrap();
+		let error = manager.check_balance_threshold().await.unwrap_err();
+		assert!(matches!(error.downcast_ref(), Some(SafeError::ProviderError(_))), "{:#}", error);
+	}
+
+	#[tokio::test]
+	async fn test_independent_thresholds() {
+		let (mut manager, _node) = setup_test_manager(300_000_000_000_000).await; // 0.0003 ETH
+		manager.set_balance_thresholds(U256::exp10(15), U256::exp10(14) * 2).unwrap(); // 0.001 and 0.0002 ETH
+		assert_eq!((manager.min_balance(), manager.critical_balance()), (U256::exp10(15), U256::exp10(14) * 2));
+		// Low, though below half of the minimum
+		assert_eq!(manager.check_balance_threshold().await.unwrap(), BalanceStatus::Low);
+
+		// Refused without touching the thresholds
+		for critical in [U256::exp10(15), U256::exp10(16)] {
+			let error = manager.set_balance_thresholds(U256::exp10(15), critical).unwrap_err();
+			assert!(matches!(error.downcast_ref(), Some(SafeError::InvalidThresholds { .. })), "{:#}", error);
+		}
+		assert_eq!(manager.critical_balance(), U256::exp10(14) * 2);
+		manager.set_balance_thresholds(U256::zero(), U256::zero()).unwrap();
+	}
+
+	#[tokio::test]
+	async fn test_transaction_validation() {
+		let (manager, _node) = setup_test_manager(500_000_000_000_000_000).await; // 0.5 ETH
+		let invalid_tx = SafeTransaction {
+			to: Address::zero(),
+			value: U256::from(1_000_000_000_000_000_000_u64), // 1 ETH
+			data: vec![],
+			operation: 0,
+			safe_tx_gas: U256::zero(),
+			nonce: None,
+		};
+
+		let result = manager.simulate_transaction(&invalid_tx).await;
+		assert!(matches!(
+			result.unwrap_err().downcast::<SafeError>(),
+			Ok(SafeError::InsufficientBalance { .. })
+		));
+	}
+
+	#[test]
+	fn test_decode_revert() {
+		let mut error = ethers::utils::id("Error(string)").to_vec();
+		error.extend(abi::encode(&[Token::String("GS013".to_string())]));
+		assert_eq!(decode_revert(&error).as_deref(), Some("GS013"));
+
+		let panic = |code: u64| {
+			let mut data = ethers::utils::id("Panic(uint256)").to_vec();
+			data.extend(abi::encode(&[Token::Uint(code.into())]));
+			decode_revert(&data)
+		};
+		assert_eq!(panic(0x11).as_deref(), Some("panic 0x11: arithmetic overflow or underflow"));
+		assert_eq!(panic(0x01).as_deref(), Some("panic 0x01: assertion failed"));
+		assert_eq!(panic(0x99).as_deref(), Some("panic 0x99"));
+
+		// InsufficientBalance(uint256,uint256), declared by the contract
+		let mut custom = hex::decode("cf479181").unwrap();
+		custom.extend(abi::encode(&[Token::Uint(1.into()), Token::Uint(2.into())]));
+		assert_eq!(decode_revert(&custom).as_deref(), Some("custom error 0xcf479181"));
+		assert_eq!(decode_revert(&[]), None);
+		assert_eq!(decode_revert(&[0x12, 0x34]), None);
+	}
+
+	#[tokio::test]
+	async fn test_simulation_reports_revert_reason() {
+		use wiremock::matchers::{body_partial_json, method};
+		use wiremock::{Mock, MockServer, ResponseTemplate};
+
+		let node = MockServer::start().await;
+		let reverting = |data: Vec<u8>| {
+			ResponseTemplate::new(200).set_body_json(serde_json::json!({
+				"jsonrpc": "2.0",
+				"id": 1,
+				"error": { "code": 3, "message": "execution reverted", "data": Bytes::from(data) },
+			}))
+		};
+		let mut panic = ethers::utils::id("Panic(uint256)").to_vec();
+		panic.extend(abi::encode(&[Token::Uint(0x12.into())]));
+		Mock::given(body_partial_json(serde_json::json!({ "method": "eth_call" })))
+			.respond_with(reverting(panic))
+			.up_to_n_times(1)
+			.mount(&node)
+			.await;
+		Mock::given(body_partial_json(serde_json::json!({ "method": "eth_call" })))
+			.respond_with(reverting(hex::decode("deadbeef").unwrap()))
+			.up_to_n_times(1)
+			.mount(&node)
+			.await;
+		mount_answers(&node, &[("eth_getCode", serde_json::json!("0x6080"))]).await;
+		Mock::given(method("POST"))
+			.respond_with(ResponseTemplate::new(200).set_body_json(
+				serde_json::json!({ "jsonrpc": "2.0", "id": 1, "result": "0xde0b6b3a7640000" }),
+			))
+			.mount(&node)
+			.await;
+		let manager = SafeManager::new(Address::zero(), test_provider(&node.uri())).unwrap();
+		let tx = SafeTransaction {
+			to: Address::repeat_byte(0x11),
+			value: U256::zero(),
+			data: vec![0xab; 4],
+			operation: 0,
+			safe_tx_gas: U256::zero(),
+			nonce: None,
+		};
+
+		for reason in ["panic 0x12: division or modulo by zero", "custom error 0xdeadbeef"] {
+			let error = manager.simulate_transaction(&tx).await.unwrap_err();
+			assert!(
+				matches!(error.downcast_ref(), Some(SafeError::TransactionFailed(message)) if message.ends_with(reason)),
+				"{:#}",
+				error
+			);
+		}
+	}
+
+	#[test]
+	fn test_buffered_gas() {
+		assert_eq!(buffered_gas(21_000.into(), 1.2), U256::from(25_200));
+		// Rounded up to whole gas
+		assert_eq!(buffered_gas(100_001.into(), 1.2), U256::from(120_002));
+		assert_eq!(buffered_gas(3.into(), 1.15), U256::from(4));
+		assert_eq!(buffered_gas(50_000.into(), 1.0), U256::from(50_000));
+		assert_eq!(buffered_gas(U256::zero(), 1.2), U256::zero());
+		// Far beyond u64, and saturating rather than overflowing
+		assert_eq!(buffered_gas(U256::exp10(30), 1.5), U256::exp10(30) * 3 / 2);
+		assert_eq!(buffered_gas(U256::MAX, 2.0), U256::MAX);
+	}
+
+	#[tokio::test]
+	async fn test_gas_limits() {
+		let node = mock_node(&[
+			("eth_getBalance", serde_json::json!("0xde0b6b3a7640000")),
+			("eth_estimateGas", serde_json::json!(format!("{:#x}", 1_000_000))),
+			("eth_gasPrice", serde_json::json!("0x3b9aca00")),
+			("eth_chainId", serde_json::json!("0x1")),
+			("eth_getCode", serde_json::json!("0x6080")),
+			("eth_call", serde_json::json!(format!("0x{:064x}", 1))),
+		]).await;
+		let mut manager = SafeManager::new(Address::repeat_byte(0x11), test_provider(&node.uri())).unwrap();
+		manager.set_signer(
+			LocalWallet::from_str("4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318").unwrap(),
+		);
+		let tx = SafeTransaction {
+			to: Address::repeat_byte(0x22),
+			value: U256::zero(),
+			data: vec![0xab; 4],
+			operation: 0,
+			safe_tx_gas: U256::zero(),
+			nonce: None,
+		};
+
+		for buffer in [0.9, f64::NAN] {
+			let error = manager.set_gas_buffer(buffer).unwrap_err();
+			assert!(matches!(error.downcast_ref(), Some(SafeError::InvalidGasBuffer(_))), "{:#}", error);
+		}
+		manager.set_gas_buffer(1.5).unwrap();
+		assert_eq!(manager.simulate_transaction(&tx).await.unwrap(), U256::from(1_500_000));
+
+		// Simulating reports the estimate; executing refuses it, before anything is signed
+		manager.set_max_gas_limit(Some(1_000_000.into()));
+		assert_eq!(manager.simulate_transaction(&tx).await.unwrap(), U256::from(1_500_000));
+		let error = manager.execute_transaction(tx.clone()).await.unwrap_err();
+		match error.downcast_ref() {
+			Some(SafeError::GasEstimationFailed(reason)) => {
+				assert!(reason.contains("1500000 gas is above the cap of 1000000"), "{}", reason)
+			}
+			other => panic!("expected the gas cap, got {:?}", other),
+		}
+		manager.set_mode(RunMode::DryRun);
+		assert!(manager.execute_transaction(tx.clone()).await.is_err());
+		manager.set_max_gas_limit(None);
+		assert!(matches!(manager.execute_transaction(tx).await.unwrap(), ExecutionOutcome::Simulated(_)));
+	}
+
+	#[tokio::test]
+	async fn test_transient_failures_are_retried() {
+		use std::time::Duration;
+		use wiremock::matchers::{body_partial_json, method};
+		use wiremock::{Mock, MockServer, ResponseTemplate};
+
+		let answer = |result: serde_json::Value| {
+			ResponseTemplate::new(200).set_body_json(serde_json::json!({ "jsonrpc": "2.0", "id": 1, "result": result }))
+		};
+		/// A node failing with `failure` `times` times, then answering every call with 1 ETH.
+		async fn flaky(failure: ResponseTemplate, times: u64, answer: ResponseTemplate) -> MockServer {
+			let server = MockServer::start().await;
+			Mock::given(method("POST")).respond_with(failure).up_to_n_times(times).mount(&server).await;
+			Mock::given(method("POST")).respond_with(answer).mount(&server).await;
+			server
+		}
+		let retry = |max_attempts| RetryPolicy {
+			max_attempts,
+			initial_backoff: Duration::from_millis(1),
+			max_backoff: Duration::from_millis(5),
+			jitter: 0.2,
+		};
+		let rate_limited = || ResponseTemplate::new(429).set_body_string("Too Many Requests");
+		let one_eth = || answer(serde_json::json!("0xde0b6b3a7640000"));
+
+		// Rate limited twice: the third attempt reads the balance
+		let node = flaky(rate_limited(), 2, one_eth()).await;
+		let manager = SafeManager::with_retry(Address::zero(), test_provider(&node.uri()), retry(3)).unwrap();
+		assert_eq!(manager.get_balance().await.unwrap(), U256::exp10(18));
+		assert_eq!(node.received_requests().await.unwrap().len(), 3);
+
+		// One attempt short, the cycle still sees the rate limiting
+		let node = flaky(rate_limited(), 2, one_eth()).await;
+		let manager = SafeManager::with_retry(Address::zero(), test_provider(&node.uri()), retry(2)).unwrap();
+		let error = manager.get_balance().await.unwrap_err();
+		assert_eq!(ErrorCategory::of(&error), ErrorCategory::RpcRateLimit, "{:#}", error);
+		assert_eq!(node.received_requests().await.unwrap().len(), 2);
+
+		// A gateway error in front of the node, then the gas price and estimate
+		let node = flaky(ResponseTemplate::new(502).set_body_string("Bad Gateway"), 1, one_eth()).await;
+		let manager = SafeManager::with_retry(Address::zero(), test_provider(&node.uri()), retry(3)).unwrap();
+		assert_eq!(manager.gas_price().await.unwrap(), U256::exp10(18));
+		let tx = SafeTransaction {
+			to: Address::repeat_byte(0x22),
+			value: U256::zero(),
+			data: vec![],
+			operation: 0,
+			safe_tx_gas: U256::zero(),
+			nonce: None,
+		};
+		node.reset().await;
+		Mock::given(body_partial_json(serde_json::json!({ "method": "eth_estimateGas" })))
+			.respond_with(ResponseTemplate::new(503))
+			.up_to_n_times(1)
+			.mount(&node)
+			.await;
+		Mock::given(method("POST")).respond_with(answer(serde_json::json!("0x5208"))).mount(&node).await;
+		// With the 1.2x buffer
+		assert_eq!(manager.simulate_transaction(&tx).await.unwrap(), U256::from(25_200));
+
+		// Invalid params would fail the same way again
+		let invalid = ResponseTemplate::new(200).set_body_json(serde_json::json!({
+			"jsonrpc": "2.0", "id": 1, "error": { "code": -32602, "message": "invalid params" },
+		}));
+		let node = flaky(invalid, 1, one_eth()).await;
+		let manager = SafeManager::with_retry(Address::zero(), test_provider(&node.uri()), retry(3)).unwrap();
+		assert!(manager.get_balance().await.is_err());
+		assert_eq!(node.received_requests().await.unwrap().len(), 1);
+	}
+
+	#[tokio::test]
+	async fn test_unanswered_requests_time_out() {
+		use crate::rpc::FailoverClient;
+		use std::time::Duration;
+		use wiremock::matchers::method;
+		use wiremock::{Mock, MockServer, ResponseTemplate};
+
+		let hanging = || {
+			ResponseTemplate::new(200)
+				.set_body_json(serde_json::json!({ "jsonrpc": "2.0", "id": 1, "result": "0x1" }))
+				.set_delay(Duration::from_secs(30))
+		};
+		let retry = |max_attempts| RetryPolicy { max_attempts, initial_backoff: Duration::from_millis(1), ..RetryPolicy::default() };
+		let provider = |node: &MockServer| {
+			FailoverClient::new(&[node.uri()], Duration::from_secs(1), Duration::from_secs(30)).unwrap().into_provider().0
+		};
+
+		let node = MockServer::start().await;
+		Mock::given(method("POST")).respond_with(hanging()).mount(&node).await;
+		let manager = SafeManager::with_retry(Address::zero(), provider(&node), retry(2)).unwrap();
+		let started = std::time::Instant::now();
+		let error = manager.get_balance().await.unwrap_err();
+		match error.downcast_ref() {
+			Some(SafeError::Timeout { operation, after }) => {
+				assert_eq!((operation.as_str(), *after), ("eth_getBalance", Duration::from_secs(1)))
+			}
+			_ => panic!("expected a timeout, got {:#}", error),
+		}
+		assert_eq!(ErrorCategory::of(&error), ErrorCategory::RpcTransport);
+		assert!(started.elapsed() < Duration::from_secs(10), "gave up after {:?}", started.elapsed());
+		assert_eq!(node.received_requests().await.unwrap().len(), 2);
+
+		// A timeout is transient: the retry gets the answer
+		let node = MockServer::start().await;
+		Mock::given(method("POST")).respond_with(hanging()).up_to_n_times(1).mount(&node).await;
+		Mock::given(method("POST"))
+			.respond_with(ResponseTemplate::new(200).set_body_json(
+				serde_json::json!({ "jsonrpc": "2.0", "id": 1, "result": "0xde0b6b3a7640000" }),
+			))
+			.mount(&node)
+			.await;
+		let manager = SafeManager::with_retry(Address::zero(), provider(&node), retry(2)).unwrap();
+		assert_eq!(manager.get_balance().await.unwrap(), U256::exp10(18));
+	}
+
+	#[tokio::test]
+	async fn test_get_address() {
+		let (manager, _node) = setup_test_manager(0).await;
+		let addr = manager.get_address();
+		assert_eq!(
+			addr,
+			Address::from_str("0x0000000000000000000000000000000000000000").unwrap()
+		);
+	}
+
+	#[tokio::test]
+	async fn test_set_min_balance() {
+		let (mut manager, _node) = setup_test_manager(0).await;
+		let new_min = U256::from(3_000_000_000_000_000_u64); // 0.003 ETH
+		manager.set_min_balance(new_min);
+		
+		assert_eq!(manager.critical_balance, new_min / 2);
+		assert_eq!(manager.min_balance, new_min);
+	}
+
+	#[tokio::test]
+	async fn test_funding_request() {
+		let milli = U256::exp10(15);
+		// Every call answers 10: a balance of 10 wei, on chain 10
+		let (mut manager, _node) = setup_test_manager(10).await;
+		assert_eq!(manager.funding_shortfall(milli * 4 / 10), milli);
+		assert_eq!(manager.funding_shortfall(milli), U256::zero());
+		assert_eq!(manager.funding_shortfall(milli * 2), U256::zero());
+		assert_eq!(
+			manager.funding_request().await.unwrap(),
+			"ethereum:0x0000000000000000000000000000000000000000@10?value=1000000000000000"
+		);
+
+		manager.set_min_balance(U256::exp10(18));
+		assert_eq!(manager.funding_shortfall(milli * 300), milli * 700);
+		assert_eq!(manager.funding_shortfall(milli * 300 + 1), milli * 700);
+		assert_eq!(
+			payment_uri(Address::from_str("0x2c7536e3605d9c16a7a3d7b1898e529396a65c23").unwrap(), 1, milli * 700),
+			"ethereum:0x2c7536E3605D9C16a7a3D7b1898e529396a65c23@1?value=700000000000000000"
+		);
+	}
+
+	#[tokio::test]
+	async fn test_run_modes_gate_execution() {
+		use wiremock::matchers::body_partial_json;
+		use wiremock::{Mock, ResponseTemplate};
+
+		let server = mock_node(&[
+			("eth_getBalance", serde_json::json!("0xde0b6b3a7640000")),
+			("eth_estimateGas", serde_json::json!("0x5208")),
+			("eth_gasPrice", serde_json::json!("0x3b9aca00")),
+			("eth_chainId", serde_json::json!("0x1")),
+			("eth_getCode", serde_json::json!("0x6080")),
+			// getThreshold, and the nonce
+			("eth_call", serde_json::json!("0x0000000000000000000000000000000000000000000000000000000000000001")),
+			("eth_getTransactionCount", serde_json::json!("0x0")),
+			("eth_sendRawTransaction", serde_json::json!("0x4242424242424242424242424242424242424242424242424242424242424242")),
+			("eth_blockNumber", serde_json::json!("0x11")),
+		]).await;
+		Mock::given(body_partial_json(serde_json::json!({ "method": "eth_getTransactionReceipt" })))
+			.respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+				"jsonrpc": "2.0", "id": 1, "result": receipt(H256::repeat_byte(0x42), 0x10, 1, serde_json::json!([])),
+			})))
+			.mount(&server)
+			.await;
+		let tx = SafeTransaction {
+			to: Address::repeat_byte(0x22),
+			value: U256::from(1_000_000_000_000_000_u64),
+			data: vec![],
+			operation: 0,
+			safe_tx_gas: U256::zero(),
+			nonce: None,
+		};
+		let owner = LocalWallet::from_str("4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318").unwrap();
+
+		// (mode, prepares the transaction, executes it)
+		for (mode, prepared, executed) in [
+			(RunMode::Observe, false, false),
+			(RunMode::DryRun, true, false),
+			(RunMode::Live, true, true),
+		] {
+			let provider = test_provider(&server.uri());
+			let mut manager = SafeManager::new(Address::repeat_byte(0x11), provider).unwrap();
+			let (events, mut rx) = EventSender::channel(4);
+			manager.set_events(events);
+			manager.set_mode(mode);
+			manager.set_signer(owner.clone());
+
+			let before = server.received_requests().await.unwrap().len();
+			let outcome = manager.execute_transaction(tx.clone()).await.unwrap();
+			let calls = server.received_requests().await.unwrap()[before..].to_vec();
+			let broadcasts = calls
+				.iter()
+				.filter(|request| String::from_utf8_lossy(&request.body).contains("eth_sendRawTransaction"))
+				.count();
+
+			assert_eq!(!calls.is_empty(), prepared, "{} made {} RPC calls", mode, calls.len());
+			assert_eq!(broadcasts, executed as usize, "{}", mode);
+			assert_eq!(outcome.tx_hash().is_some(), executed, "{}", mode);
+			assert_eq!(rx.try_recv().is_ok(), executed, "{}", mode);
+			match mode {
+				RunMode::Observe => assert_eq!(outcome, ExecutionOutcome::Observed),
+				// Everything but the broadcast, reported as what would have been sent
+				RunMode::DryRun => match outcome {
+					ExecutionOutcome::Simulated(simulated) => {
+						assert_eq!((simulated.to, simulated.value), (tx.to, tx.value));
+						assert_eq!(simulated.calldata_hash, H256::from(keccak256(&tx.data)));
+						assert!(simulated.estimated_gas > U256::zero());
+						assert!(simulated.total_cost > tx.value);
+						let result = outcome.result().unwrap();
+						assert!(result.simulated);
+						assert_eq!((result.gas_estimate, result.total_cost_wei), (simulated.estimated_gas, simulated.total_cost));
+						assert_eq!((result.gas_used, result.block), (None, None));
+					}
+					other => panic!("expected a simulated transaction, got {:?}", other),
+				},
+				// Gas used and cost come from the receipt: 100k gas at 1 gwei
+				RunMode::Live => match outcome {
+					ExecutionOutcome::Executed(result) => {
+						assert_eq!(result.tx_hash, Some(H256::repeat_byte(0x42)));
+						assert!(!result.simulated);
+						assert!(result.gas_estimate > U256::zero());
+						assert_eq!(result.gas_used, Some(U256::from(100_000)));
+						assert_eq!(result.block, Some(0x10));
+						assert_eq!(result.total_cost_wei, tx.value + U256::from(100_000_000_000_000_u64));
+						let json = serde_json::to_value(result).unwrap();
+						assert_eq!(json["gas_used"], serde_json::json!("0x186a0"));
+						assert_eq!(json["simulated"], serde_json::json!(false));
+					}
+					other => panic!("expected an executed transaction, got {:?}", other),
+				},
+			}
+		}
+
+		// The broadcast itself refuses outside live mode, however it is reached
+		let mut manager = SafeManager::new(Address::repeat_byte(0x11), test_provider(&server.uri())).unwrap();
+		manager.set_mode(RunMode::DryRun);
+		manager.set_signer(owner.clone());
+		let fees = manager.fees().await.unwrap();
+		let before = server.received_requests().await.unwrap().len();
+		let error = manager.send_exec_transaction(&tx, &fees).await.unwrap_err();
+		assert!(matches!(error.downcast_ref(), Some(SafeError::BroadcastDisabled(RunMode::DryRun))), "{:#}", error);
+		let calls = server.received_requests().await.unwrap()[before..].to_vec();
+		assert!(calls.iter().all(|request| !String::from_utf8_lossy(&request.body).contains("eth_sendRawTransaction")));
+
+		// More than the 1 ETH balance: only a live failure raises an alert
+		let too_much = SafeTransaction { value: U256::exp10(19), ..tx };
+		for (mode, alerted) in [(RunMode::DryRun, false), (RunMode::Live, true)] {
+			let mut manager = SafeManager::new(Address::repeat_byte(0x11), test_provider(&server.uri())).unwrap();
+			let (events, mut rx) = EventSender::channel(4);
+			manager.set_events(events);
+			manager.set_mode(mode);
+			manager.set_signer(owner.clone());
+
+			assert!(manager.execute_transaction(too_much.clone()).await.is_err());
+			match rx.try_recv() {
+				Ok(AgentEvent::TransactionFailed { value_wei, reason, .. }) => {
+					assert!(alerted, "{}", mode);
+					assert_eq!(value_wei, too_much.value);
+					assert!(reason.contains("Insufficient"), "{}", reason);
+				}
+				other => assert!(!alerted && other.is_err(), "{}: {:?}", mode, other),
+			}
+		}
+	}
+
+	#[tokio::test]
+	async fn test_execute_sends_exec_transaction() {
+		use ethers::abi::AbiDecode;
+		use ethers::types::{NameOrAddress, Signature};
+		use ethers::utils::rlp::Rlp;
+		use wiremock::matchers::body_partial_json;
+		use wiremock::{Mock, ResponseTemplate};
+
+		let sent_hash = H256::repeat_byte(0x42);
+		let server = mock_node(&[
+			("eth_getBalance", serde_json::json!("0xde0b6b3a7640000")),
+			("eth_estimateGas", serde_json::json!("0x5208")),
+			("eth_gasPrice", serde_json::json!("0x3b9aca00")),
+			("eth_chainId", serde_json::json!("0x1")),
+			("eth_getCode", serde_json::json!("0x6080")),
+			// getThreshold, and the Safe nonce
+			("eth_call", serde_json::json!(format!("0x{:064x}", 1))),
+			("eth_getTransactionCount", serde_json::json!("0x7")),
+			("eth_sendRawTransaction", serde_json::json!(format!("{:?}", sent_hash))),
+			("eth_blockNumber", serde_json::json!("0x11")),
+		]).await;
+		Mock::given(body_partial_json(serde_json::json!({ "method": "eth_getTransactionReceipt" })))
+			.respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+				"jsonrpc": "2.0", "id": 1, "result": receipt(sent_hash, 0x10, 1, serde_json::json!([])),
+			})))
+			.mount(&server)
+			.await;
+		let raw_transactions = || async {
+			let requests = server.received_requests().await.unwrap();
+			requests
+				.iter()
+				.map(|request| serde_json::from_slice::<serde_json::Value>(&request.body).unwrap())
+				.filter(|call| call["method"] == "eth_sendRawTransaction")
+				.map(|call| call["params"][0].as_str().unwrap().parse::<Bytes>().unwrap())
+				.collect::<Vec<_>>()
+		};
+		let safe = Address::repeat_byte(0x11);
+		let tx = SafeTransaction {
+			to: Address::repeat_byte(0x22),
+			value: U256::exp10(15),
+			data: vec![0xde, 0xad],
+			operation: 0,
+			safe_tx_gas: U256::zero(),
+			nonce: None,
+		};
+
+		// Without a key nothing is sent, and nothing pretends it was
+		let mut manager = SafeManager::new(safe, test_provider(&server.uri())).unwrap();
+		let (events, mut rx) = EventSender::channel(4);
+		manager.set_events(events);
+		let error = manager.execute_transaction(tx.clone()).await.unwrap_err();
+		assert!(matches!(error.downcast_ref(), Some(SafeError::NoSigner)), "{:#}", error);
+		assert_eq!(ErrorCategory::of(&error), ErrorCategory::Validation);
+		assert!(matches!(rx.try_recv(), Ok(AgentEvent::TransactionFailed { .. })));
+
+		// Simulating is asked for explicitly
+		manager.set_execution(SafeExecution::Simulate).unwrap();
+		let simulated = manager.execute_transaction(tx.clone()).await.unwrap();
+		assert!(matches!(simulated, ExecutionOutcome::Simulated(_)), "{:?}", simulated);
+		assert!(rx.try_recv().is_err());
+		assert!(raw_transactions().await.is_empty());
+
+		let owner = LocalWallet::from_str("4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318").unwrap();
+		manager.set_signer(owner.clone());
+		manager.set_execution(SafeExecution::Execute).unwrap();
+		assert_eq!(manager.execute_transaction(tx.clone()).await.unwrap().tx_hash(), Some(sent_hash));
+		match rx.try_recv() {
+			Ok(AgentEvent::TransactionExecuted { tx_hash, .. }) => assert_eq!(tx_hash, Some(sent_hash)),
+			other => panic!("expected an executed transaction, got {:?}", other),
+		}
+
+		// The owner's execTransaction on the Safe, carrying the owner's signature of the safeTxHash
+		let raw = raw_transactions().await;
+		assert_eq!(raw.len(), 1);
+		let (sent, signature) = TypedTransaction::decode_signed(&Rlp::new(&raw[0])).unwrap();
+		assert_eq!(signature.recover(sent.sighash()).unwrap(), owner.address());
+		assert_eq!(sent.to(), Some(&NameOrAddress::Address(safe)));
+		assert_eq!(sent.chain_id(), Some(1.into()));
+		assert_eq!(sent.nonce(), Some(&7.into()));
+		// The owner's own estimate, buffered
+		assert_eq!(sent.gas(), Some(&25_200.into()));
+		assert_eq!(sent.value().copied().unwrap_or_default(), U256::zero());
+		let call = ExecTransactionCall::decode(sent.data().unwrap()).unwrap();
+		assert_eq!((call.to, call.value, call.data.to_vec(), call.operation), (tx.to, tx.value, tx.data.clone(), 0));
+		let safe_tx_hash = tx.safe_tx_hash(1.into(), safe, 1.into());
+		let owner_signature = Signature::try_from(call.signatures.as_ref()).unwrap();
+		assert_eq!(owner_signature.recover(safe_tx_hash).unwrap(), owner.address());
+	}
+
+	#[tokio::test]
+	async fn test_submission_queue() {
+		use crate::storage::MemoryStore;
+		use crate::util::MockClock;
+		use ethers::utils::rlp::Rlp;
+
+		let sent_hash = H256::repeat_byte(0x42);
+		let node = mock_node(&[
+			("eth_getBalance", serde_json::json!(format!("{:#x}", U256::exp10(19)))),
+			("eth_estimateGas", serde_json::json!("0x5208")),
+			("eth_gasPrice", serde_json::json!("0x3b9aca00")),
+			("eth_chainId", serde_json::json!("0x1")),
+			("eth_getCode", serde_json::json!("0x6080")),
+			// getThreshold, the Safe nonce, and the simulated call
+			("eth_call", serde_json::json!(format!("0x{:064x}", 1))),
+			("eth_getTransactionCount", serde_json::json!("0x7")),
+			("eth_sendRawTransaction", serde_json::json!(sent_hash)),
+			("eth_blockNumber", serde_json::json!("0x10")),
+			("eth_getTransactionReceipt", receipt(sent_hash, 0x10, 1, serde_json::json!([]))),
+		]).await;
+		let clock = MockClock::new(1_700_000_000);
+		let mut manager =
+			SafeManager::with_clock(Address::repeat_byte(0x11), test_provider(&node.uri()), clock.shared()).unwrap();
+		manager.set_signer(
+			LocalWallet::from_str("4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318").unwrap(),
+		);
+		manager.set_confirmations(Some(0), Duration::from_secs(300));
+		manager.set_submissions_per_minute(2);
+		let store = Arc::new(MemoryStore::new());
+		manager.set_store(store.clone());
+		let transfer = |byte: u8| SafeTransaction {
+			to: Address::repeat_byte(byte),
+			value: U256::from(byte),
+			data: Vec::new(),
+			operation: 0,
+			safe_tx_gas: U256::zero(),
+			nonce: None,
+		};
+
+		for byte in 1..=5 {
+			manager.enqueue(transfer(byte));
+		}
+		assert_eq!(manager.pending_count(), 5);
+		let started = clock.now_instant();
+		let outcomes = manager.drain_queue(&CancellationToken::new()).await;
+		assert_eq!(manager.pending_count(), 0);
+		// In order, two a minute: at 0s, 0s, 60s, 60s and 120s
+		assert_eq!(outcomes.iter().map(|(tx, _)| tx.to).collect::<Vec<_>>(), (1..=5).map(Address::repeat_byte).collect::<Vec<_>>());
+		assert!(outcomes.iter().all(|(_, outcome)| outcome.as_ref().is_ok_and(|outcome| outcome.tx_hash() == Some(sent_hash))));
+		assert_eq!(clock.now_instant() - started, Duration::from_secs(120));
+
+		// Reached the node in that order, each only after the previous one's receipt
+		let calls: Vec<serde_json::Value> = node
+			.received_requests()
+			.await
+			.unwrap()
+			.iter()
+			.map(|request| serde_json::from_slice(&request.body).unwrap())
+			.filter(|call: &serde_json::Value| {
+				call["method"] == "eth_sendRawTransaction" || call["method"] == "eth_getTransactionReceipt"
+			})
+			.collect();
+		let targets: Vec<Address> = calls
+			.iter()
+			.filter(|call| call["method"] == "eth_sendRawTransaction")
+			.map(|call| {
+				let raw = call["params"][0].as_str().unwrap().parse::<Bytes>().unwrap();
+				let (sent, _) = TypedTransaction::decode_signed(&Rlp::new(&raw)).unwrap();
+				// execTransaction's first argument
+				Address::from_slice(&sent.data().unwrap()[16..36])
+			})
+			.collect();
+		assert_eq!(targets, (1..=5).map(Address::repeat_byte).collect::<Vec<_>>());
+		let methods: Vec<&str> = calls.iter().map(|call| call["method"].as_str().unwrap()).collect();
+		assert_eq!(methods, ["eth_sendRawTransaction", "eth_getTransactionReceipt"].repeat(5));
+
+		// A shutdown before the queue drains leaves the rest unsent
+		for byte in 6..=8 {
+			manager.enqueue(transfer(byte));
+		}
+		let shutdown = CancellationToken::new();
+		shutdown.cancel();
+		assert!(manager.drain_queue(&shutdown).await.is_empty());
+		assert_eq!(manager.pending_count(), 3);
+		let unsent = manager.shutdown_queue().await;
+		assert_eq!(unsent, [transfer(6), transfer(7), transfer(8)]);
+		assert_eq!(manager.pending_count(), 0);
+		assert_eq!(store.unsent_transactions().await.unwrap(), unsent);
+		// Reported once by the next start, not submitted again
+		assert_eq!(manager.report_unsent().await.unwrap(), unsent);
+		assert!(manager.report_unsent().await.unwrap().is_empty());
+	}
+
+	#[tokio::test]
+	async fn test_spending_limits() {
+		use crate::util::MockClock;
+
+		let node = mock_node(&[
+			("eth_getBalance", serde_json::json!(format!("{:#x}", U256::exp10(19)))),
+			("eth_estimateGas", serde_json::json!("0x5208")),
+			("eth_gasPrice", serde_json::json!("0x3b9aca00")),
+			("eth_chainId", serde_json::json!("0x1")),
+			("eth_getCode", serde_json::json!("0x6080")),
+			// getThreshold, the Safe nonce, and the simulated call
+			("eth_call", serde_json::json!(format!("0x{:064x}", 1))),
+			("eth_getTransactionCount", serde_json::json!("0x7")),
+			("eth_sendRawTransaction", serde_json::json!(format!("{:?}", H256::repeat_byte(0x42)))),
+		]).await;
+		let sent = || async {
+			let requests = node.received_requests().await.unwrap();
+			requests
+				.iter()
+				.map(|request| serde_json::from_slice::<serde_json::Value>(&request.body).unwrap())
+				.filter(|call| call["method"] == "eth_sendRawTransaction")
+				.count()
+		};
+		let clock = MockClock::new(1_700_000_000);
+		let mut manager =
+			SafeManager::with_clock(Address::repeat_byte(0x11), test_provider(&node.uri()), clock.shared()).unwrap();
+		manager.set_signer(
+			LocalWallet::from_str("4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318").unwrap(),
+		);
+		manager.set_confirmations(Some(0), Duration::from_secs(300));
+		let eth = |tenths: u64| U256::exp10(17) * tenths;
+		manager.set_spending_limits(Some(eth(5)), Some(eth(10))); // 0.5 ETH, 1 ETH a day
+		let transfer = |value: U256| SafeTransaction {
+			to: Address::repeat_byte(0x22),
+			value,
+			data: Vec::new(),
+			operation: 0,
+			safe_tx_gas: U256::zero(),
+			nonce: None,
+		};
+		let refused = |error: anyhow::Error| match error.downcast::<SafeError>() {
+			Ok(SafeError::SpendingLimitExceeded { limit, attempted, window }) => (limit, attempted, window),
+			other => panic!("expected a spending limit, got {:?}", other),
+		};
+
+		for _ in 0..2 {
+			manager.execute_transaction(transfer(eth(4))).await.unwrap().tx_hash().unwrap();
+		}
+		assert_eq!(manager.spent_last_day(), eth(8));
+		let error = manager.execute_transaction(transfer(eth(4))).await.unwrap_err();
+		assert_eq!(ErrorCategory::of(&error), ErrorCategory::Validation);
+		assert_eq!(refused(error), (eth(10), eth(12), SpendingWindow::Day));
+		assert_eq!(
+			refused(manager.execute_transaction(transfer(eth(6))).await.unwrap_err()),
+			(eth(5), eth(6), SpendingWindow::Transaction)
+		);
+		assert_eq!(sent().await, 2);
+
+		// Up to the limit itself, half a day later
+		clock.advance(Duration::from_secs(12 * 3600));
+		manager.execute_transaction(transfer(eth(2))).await.unwrap().tx_hash().unwrap();
+		assert!(manager.execute_transaction(transfer(eth(1))).await.is_err());
+
+		// A day after the first two they no longer count
+		clock.advance(Duration::from_secs(12 * 3600));
+		assert_eq!(manager.spent_last_day(), eth(2));
+		manager.execute_transaction(transfer(eth(5))).await.unwrap().tx_hash().unwrap();
+		assert!(manager.execute_transaction(transfer(eth(4))).await.is_err());
+
+		// Transactions that are not sent are not counted
+		manager.set_execution(SafeExecution::Simulate).unwrap();
+		manager.execute_transaction(transfer(eth(3))).await.unwrap();
+		assert_eq!(manager.spent_last_day(), eth(7));
+
+		manager.reset_spending();
+		assert_eq!(manager.spent_last_day(), U256::zero());
+		manager.set_execution(SafeExecution::Execute).unwrap();
+		manager.execute_transaction(transfer(eth(5))).await.unwrap().tx_hash().unwrap();
+		assert_eq!(sent().await, 5);
+	}
+
+	#[tokio::test]
+	async fn test_daily_gas_budget() {
+		use crate::util::MockClock;
+
+		let mut mined = receipt(H256::repeat_byte(0x42), 0x10, 1, serde_json::json!([]));
+		mined["gasUsed"] = serde_json::json!("0x5208");
+		let node = mock_node(&[
+			("eth_getBalance", serde_json::json!(format!("{:#x}", U256::exp10(19)))),
+			("eth_estimateGas", serde_json::json!("0x5208")),
+			("eth_gasPrice", serde_json::json!("0x3b9aca00")),
+			("eth_chainId", serde_json::json!("0x1")),
+			("eth_getCode", serde_json::json!("0x6080")),
+			// getThreshold, the Safe nonce, and the simulated call
+			("eth_call", serde_json::json!(format!("0x{:064x}", 1))),
+			("eth_getTransactionCount", serde_json::json!("0x7")),
+			("eth_sendRawTransaction", serde_json::json!(format!("{:?}", H256::repeat_byte(0x42)))),
+			("eth_blockNumber", serde_json::json!("0x10")),
+			("eth_getTransactionReceipt", mined),
+		]).await;
+		let sent = || async {
+			let requests = node.received_requests().await.unwrap();
+			requests
+				.iter()
+				.map(|request| serde_json::from_slice::<serde_json::Value>(&request.body).unwrap())
+				.filter(|call| call["method"] == "eth_sendRawTransaction")
+				.count()
+		};
+		let clock = MockClock::new(1_700_000_000);
+		let mut manager =
+			SafeManager::with_clock(Address::repeat_byte(0x11), test_provider(&node.uri()), clock.shared()).unwrap();
+		manager.set_signer(
+			LocalWallet::from_str("4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318").unwrap(),
+		);
+		manager.set_confirmations(Some(1), Duration::from_secs(300));
+		let gwei = |gwei: u64| U256::from(gwei) * U256::exp10(9);
+		// 25,200 gas, buffered, at 1 gwei before broadcast; 21,000 once mined
+		manager.set_daily_gas_budget(Some(gwei(70_000)));
+		let transfer = SafeTransaction {
+			to: Address::repeat_byte(0x22),
+			value: U256::exp10(15),
+			data: Vec::new(),
+			operation: 0,
+			safe_tx_gas: U256::zero(),
+			nonce: None,
+		};
+		let refused = |error: anyhow::Error| match error.downcast::<SafeError>() {
+			Ok(SafeError::GasBudgetExhausted { spent, budget }) => (spent, budget),
+			other => panic!("expected an exhausted gas budget, got {:?}", other),
+		};
+
+		// The third fits only because the first two were trued up to the gas they used
+		for _ in 0..3 {
+			manager.execute_transaction(transfer.clone()).await.unwrap().tx_hash().unwrap();
+		}
+		assert_eq!(manager.gas_spent_today(), gwei(63_000));
+		let error = manager.execute_transaction(transfer.clone()).await.unwrap_err();
+		assert_eq!(refused(error), (gwei(88_200), gwei(70_000)));
+		assert_eq!(sent().await, 3);
+
+		// Transactions that are not sent are not counted
+		manager.set_execution(SafeExecution::Simulate).unwrap();
+		manager.execute_transaction(transfer.clone()).await.unwrap();
+		assert_eq!(manager.gas_spent_today(), gwei(63_000));
+		manager.set_execution(SafeExecution::Execute).unwrap();
+
+		// Without waiting for the receipt, the estimate stays counted
+		clock.advance(Duration::from_secs(SPENDING_WINDOW_SECS));
+		assert_eq!(manager.gas_spent_today(), U256::zero());
+		manager.set_confirmations(Some(0), Duration::from_secs(300));
+		manager.execute_transaction(transfer.clone()).await.unwrap().tx_hash().unwrap();
+		assert_eq!(manager.gas_spent_today(), gwei(25_200));
+
+		manager.set_daily_gas_budget(None);
+		for _ in 0..3 {
+			manager.execute_transaction(transfer.clone()).await.unwrap().tx_hash().unwrap();
+		}
+		assert_eq!(sent().await, 7);
+	}
+
+	#[tokio::test]
+	async fn test_submissions_take_consecutive_nonces() {
+		use ethers::utils::rlp::Rlp;
+		use wiremock::matchers::body_partial_json;
+		use wiremock::{Mock, MockServer, ResponseTemplate};
+
+		let node = MockServer::start().await;
+		let mount = |transaction_count: &'static str| {
+			let node = &node;
+			async move {
+				mount_answers(node, &[
+					("eth_chainId", serde_json::json!("0x1")),
+					// getThreshold, and the Safe nonce
+					("eth_call", serde_json::json!(format!("0x{:064x}", 1))),
+					("eth_getTransactionCount", serde_json::json!(transaction_count.to_string())),
+					("eth_estimateGas", serde_json::json!("0x30d40")),
+					("eth_sendRawTransaction", serde_json::json!(format!("{:?}", H256::repeat_byte(0x42)))),
+				]).await;
+			}
+		};
+		let calls = |method: &'static str| {
+			let node = &node;
+			async move {
+				node.received_requests()
+					.await
+					.unwrap()
+					.iter()
+					.map(|request| serde_json::from_slice::<serde_json::Value>(&request.body).unwrap())
+					.filter(|call| call["method"] == method)
+					.collect::<Vec<_>>()
+			}
+		};
+		let sent_nonces = || async {
+			calls("eth_sendRawTransaction")
+				.await
+				.iter()
+				.map(|call| {
+					let raw = call["params"][0].as_str().unwrap().parse::<Bytes>().unwrap();
+					let (sent, _) = TypedTransaction::decode_signed(&Rlp::new(&raw)).unwrap();
+					sent.nonce().unwrap().as_u64()
+				})
+				.collect::<Vec<_>>()
+		};
+		let mut manager = SafeManager::new(Address::repeat_byte(0x11), test_provider(&node.uri())).unwrap();
+		manager.set_signer(
+			LocalWallet::from_str("4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318").unwrap(),
+		);
+		let (approve, deposit) = (
+			SafeTransaction {
+				to: Address::repeat_byte(0x22),
+				value: U256::zero(),
+				data: vec![0x09, 0x5e, 0xa7, 0xb3],
+				operation: 0,
+				safe_tx_gas: U256::zero(),
+				nonce: None,
+			},
+			SafeTransaction {
+				to: Address::repeat_byte(0x33),
+				value: U256::zero(),
+				data: vec![0x6e, 0x55, 0x3f, 0x65],
+				operation: 0,
+				safe_tx_gas: U256::zero(),
+				nonce: None,
+			},
+		);
+		let fees = TxFees::Legacy { gas_price: U256::exp10(9) };
+
+		// Submitted together, before either is mined: the node is asked once
+		mount("0x7").await;
+		let (first, second) =
+			tokio::join!(manager.send_exec_transaction(&approve, &fees), manager.send_exec_transaction(&deposit, &fees));
+		first.unwrap();
+		second.unwrap();
+		let mut nonces = sent_nonces().await;
+		nonces.sort_unstable();
+		assert_eq!(nonces, [7, 8]);
+		assert_eq!(calls("eth_getTransactionCount").await.len(), 1);
+
+		// A refused submission resynchronizes from the node's pending count
+		node.reset().await;
+		Mock::given(body_partial_json(serde_json::json!({ "method": "eth_sendRawTransaction" })))
+			.respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+				"jsonrpc": "2.0", "id": 1, "error": { "code": -32000, "message": "nonce too low" },
+			})))
+			.up_to_n_times(1)
+			.mount(&node)
+			.await;
+		mount("0xa").await;
+		assert!(manager.send_exec_transaction(&approve, &fees).await.is_err());
+		manager.send_exec_transaction(&approve, &fees).await.unwrap();
+		assert_eq!(sent_nonces().await, [9, 10]);
+		let counts = calls("eth_getTransactionCount").await;
+		assert_eq!(counts.len(), 1);
+		assert_eq!(counts[0]["params"][1], "pending");
+	}
+
+	#[tokio::test]
+	async fn test_wait_for_confirmation() {
+		use crate::util::MockClock;
+		use std::time::Duration;
+		use wiremock::matchers::body_partial_json;
+		use wiremock::{Mock, MockServer, ResponseTemplate};
+
+		let answer = |result: serde_json::Value| {
+			ResponseTemplate::new(200).set_body_json(serde_json::json!({ "jsonrpc": "2.0", "id": 1, "result": result }))
+		};
+		let on = |method: &str| Mock::given(body_partial_json(serde_json::json!({ "method": method })));
+		let tx_hash = H256::repeat_byte(0x42);
+		let safe = Address::repeat_byte(0x11);
+		let timeout = Duration::from_secs(60);
+		let node = MockServer::start().await;
+		let clock = MockClock::new(1_700_000_000);
+		let manager = SafeManager::with_clock(safe, test_provider(&node.uri()), clock.shared()).unwrap();
+
+		// Pending for a poll, then mined in block 16: two confirmations need block 17
+		on("eth_getTransactionReceipt").respond_with(answer(serde_json::Value::Null)).up_to_n_times(1).mount(&node).await;
+		on("eth_getTransactionReceipt")
+			.respond_with(answer(receipt(tx_hash, 16, 1, serde_json::json!([]))))
+			.mount(&node)
+			.await;
+		on("eth_blockNumber").respond_with(answer(serde_json::json!("0x10"))).up_to_n_times(1).mount(&node).await;
+		on("eth_blockNumber").respond_with(answer(serde_json::json!("0x11"))).mount(&node).await;
+		let started = clock.now_instant();
+		let confirmed = manager.wait_for_confirmation(tx_hash, 2, timeout).await.unwrap();
+		assert_eq!(confirmed.transaction_hash, tx_hash);
+		assert_eq!(clock.now_instant() - started, RECEIPT_POLL_INTERVAL * 2);
+
+		// Never mined: dropped, or replaced by another transaction with its nonce
+		node.reset().await;
+		on("eth_getTransactionReceipt").respond_with(answer(serde_json::Value::Null)).mount(&node).await;
+		let error = manager.wait_for_confirmation(tx_hash, 1, timeout).await.unwrap_err();
+		match error.downcast_ref() {
+			Some(SafeError::NotConfirmed { tx_hash: hash, waited }) => assert_eq!((*hash, *waited), (tx_hash, timeout)),
+			other => panic!("expected an unconfirmed transaction, got {:?}", other),
+		}
+
+		// Reverted: the replay at the block before says why
+		node.reset().await;
+		on("eth_getTransactionReceipt")
+			.respond_with(answer(receipt(tx_hash, 16, 0, serde_json::json!([]))))
+			.mount(&node)
+			.await;
+		on("eth_getTransactionByHash")
+			.respond_with(answer(serde_json::json!({
+				"hash": tx_hash,
+				"nonce": "0x7",
+				"blockHash": H256::repeat_byte(0xbb),
+				"blockNumber": "0x10",
+				"transactionIndex": "0x0",
+				"from": Address::repeat_byte(0xaa),
+				"to": safe,
+				"value": "0x0",
+				"gasPrice": "0x3b9aca00",
+				"gas": "0x30d40",
+				"input": "0x6a761202",
+				"v": "0x25",
+				"r": "0x1",
+				"s": "0x1",
+			})))
+			.mount(&node)
+			.await;
+		let mut revert_data = ethers::utils::id("Error(string)").to_vec();
+		revert_data.extend(abi::encode(&[Token::String("GS013".to_string())]));
+		on("eth_call")
+			.and(body_partial_json(serde_json::json!({ "params": [{}, "0xf"] })))
+			.respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+				"jsonrpc": "2.0",
+				"id": 1,
+				"error": { "code": 3, "message": "execution reverted: GS013", "data": Bytes::from(revert_data) },
+			})))
+			.mount(&node)
+			.await;
+		let error = manager.wait_for_confirmation(tx_hash, 2, timeout).await.unwrap_err();
+		assert!(matches!(error.downcast_ref(), Some(SafeError::TransactionFailed(reason)) if reason.ends_with("reverted: GS013")), "{:#}", error);
+		assert_eq!(ErrorCategory::of(&error), ErrorCategory::Internal);
+
+		// Mined, but the Safe reports its call failed
+		node.reset().await;
+		let logs = serde_json::json!([{
+			"address": safe,
+			"topics": [H256::from(keccak256("ExecutionFailure(bytes32,uint256)"))],
+			"data": format!("0x{}", "00".repeat(64)),
+		}]);
+		on("eth_getTransactionReceipt").respond_with(answer(receipt(tx_hash, 16, 1, logs))).mount(&node).await;
+		let error = manager.wait_for_confirmation(tx_hash, 1, timeout).await.unwrap_err();
+		assert!(error.to_string().contains("the Safe's call failed"), "{:#}", error);
+	}
+
+	#[tokio::test]
+	async fn test_token_balances() {
+		use wiremock::matchers::{body_partial_json, body_string_contains};
+		use wiremock::{Mock, MockServer, ResponseTemplate};
+
+		let (usdc, dai, reverting, account) =
+			(Address::repeat_byte(0x01), Address::repeat_byte(0x02), Address::repeat_byte(0x03), Address::repeat_byte(0x04));
+		let server = MockServer::start().await;
+		let answer = |result: serde_json::Value| {
+			ResponseTemplate::new(200).set_body_json(serde_json::json!({ "jsonrpc": "2.0", "id": 1, "result": result }))
+		};
+		let word = |value: u64| serde_json::json!(format!("0x{:064x}", value));
+		// balanceOf(address), and decimals() only once
+		for (token, selector, result) in [
+			(usdc, "70a08231", word(1_234_500_000)),
+			(dai, "70a08231", word(2_500_000_000_000_000_000)),
+			(account, "70a08231", serde_json::json!("0x")),
+		] {
+			Mock::given(body_string_contains(format!("{:x}", token)))
+				.and(body_string_contains(selector))
+				.respond_with(answer(result))
+				.mount(&server)
+				.await;
+		}
+		Mock::given(body_string_contains(format!("{:x}", usdc)))
+			.and(body_string_contains("313ce567"))
+			.respond_with(answer(word(6)))
+			.expect(1)
+			.mount(&server)
+			.await;
+		Mock::given(body_string_contains(format!("{:x}", reverting)))
+			.respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+				"jsonrpc": "2.0",
+				"id": 1,
+				"error": { "code": 3, "message": "execution reverted" },
+			})))
+			.mount(&server)
+			.await;
+		Mock::given(body_partial_json(serde_json::json!({ "method": "eth_getCode" })))
+			.respond_with(answer(serde_json::json!("0x")))
+			.mount(&server)
+			.await;
+
+		let token = |symbol: &str, address: Address, decimals| TokenConfig {
+			symbol: symbol.to_string(),
+			address: format!("{:?}", address),
+			decimals,
+		};
+		let mut manager = SafeManager::new(Address::repeat_byte(0x11), test_provider(&server.uri())).unwrap();
+		manager.set_tokens(vec![token("USDC", usdc, None), token("DAI", dai, Some(18))]).unwrap();
+		let balances = manager.get_all_balances().await.unwrap();
+		let shown: Vec<(&str, String)> = balances.iter().map(|b| (b.symbol.as_str(), b.amount.to_string())).collect();
+		assert_eq!(shown, vec![("USDC", "1234.500000".to_string()), ("DAI", "2.500000000000000000".to_string())]);
+		assert_eq!(manager.token_decimals(usdc).await.unwrap(), 6);
+		assert_eq!(manager.get_token_balance(usdc).await.unwrap(), U256::from(1_234_500_000_u64));
+
+		for (address, expected) in [(reverting, "call reverted"), (account, "no contract at this address")] {
+			match manager.get_token_balance(address).await.unwrap_err().downcast::<SafeError>() {
+				Ok(SafeError::TokenCallFailed { token, reason }) => {
+					assert_eq!(token, address);
+					assert!(reason.starts_with(expected), "{}", reason);
+				}
+				other => panic!("expected a failed token call, got {:?}", other),
+			}
+		}
+		manager.set_tokens(vec![token("USDC", usdc, Some(6)), token("NOPE", account, Some(18))]).unwrap();
+		let error = manager.get_all_balances().await.unwrap_err();
+		assert!(error.to_string().contains("NOPE"), "{:#}", error);
+		assert!(matches!(error.root_cause().downcast_ref(), Some(SafeError::TokenCallFailed { .. })), "{:#}", error);
+		assert_eq!(ErrorCategory::of(&error), ErrorCategory::Validation);
+	}
+
+	#[tokio::test]
+	async fn test_balances_batch() {
+		use wiremock::matchers::{body_partial_json, body_string_contains};
+		use wiremock::{Mock, MockServer, ResponseTemplate};
+
+		let (safe, other, usdc, reverting, account) = (
+			Address::repeat_byte(0x11),
+			Address::repeat_byte(0x12),
+			Address::repeat_byte(0x01),
+			Address::repeat_byte(0x03),
+			Address::repeat_byte(0x04),
+		);
+		let queries = [
+			BalanceQuery::Native(safe),
+			BalanceQuery::Native(other),
+			BalanceQuery::Token { token: usdc, holder: safe },
+			BalanceQuery::Token { token: reverting, holder: safe },
+			BalanceQuery::Token { token: account, holder: safe },
+		];
+		let answer = |result: serde_json::Value| {
+			ResponseTemplate::new(200).set_body_json(serde_json::json!({ "jsonrpc": "2.0", "id": 1, "result": result }))
+		};
+		let word = |value: u64| {
+			let mut word = [0u8; 32];
+			U256::from(value).to_big_endian(&mut word);
+			word.to_vec()
+		};
+		let multicall = || {
+			Mock::given(body_string_contains(MULTICALL3[2..].to_lowercase())).and(body_string_contains("82ad56cb"))
+		};
+
+		// One aggregate3 call answers everything; the reverting token fails alone
+		let server = MockServer::start().await;
+		let results = [(true, word(5)), (true, word(7)), (true, word(1_000)), (false, vec![0xde, 0xad]), (true, vec![])]
+			.into_iter()
+			.map(|(success, data)| Token::Tuple(vec![Token::Bool(success), Token::Bytes(data)]))
+			.collect();
+		multicall()
+			.respond_with(answer(serde_json::json!(format!("0x{}", hex::encode(abi::encode(&[Token::Array(results)]))))))
+			.expect(2)
+			.mount(&server)
+			.await;
+		let manager = SafeManager::new(safe, test_provider(&server.uri())).unwrap();
+		let balances = manager.get_balances_batch(&queries).await.unwrap();
+		assert_eq!(balances.len(), queries.len());
+		let ok: Vec<_> = balances[..3].iter().map(|b| *b.as_ref().unwrap()).collect();
+		assert_eq!(ok, [U256::from(5), U256::from(7), U256::from(1_000)]);
+		for (balance, expected, reason) in
+			[(&balances[3], reverting, "call reverted: 0xdead"), (&balances[4], account, "no contract at this address")]
+		{
+			match balance.as_ref().unwrap_err().downcast_ref::<SafeError>() {
+				Some(SafeError::TokenCallFailed { token, reason: why }) => {
+					assert_eq!(*token, expected);
+					assert_eq!(why, reason);
+				}
+				other => panic!("expected a failed token call, got {:?}", other),
+			}
+		}
+		let balances = manager.get_balances_batch(&queries).await.unwrap();
+		assert_eq!(*balances[1].as_ref().unwrap(), U256::from(7));
+
+		// Without Multicall3 the balances are read one by one, and it is not asked again
+		let server = MockServer::start().await;
+		multicall().respond_with(answer(serde_json::json!("0x"))).expect(1).mount(&server).await;
+		Mock::given(body_string_contains(format!("{:x}", usdc)))
+			.and(body_string_contains("70a08231"))
+			.respond_with(answer(serde_json::json!(format!("0x{}", hex::encode(word(1_000))))))
+			.mount(&server)
+			.await;
+		Mock::given(body_partial_json(serde_json::json!({ "method": "eth_getBalance", "params": [other] })))
+			.respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+				"jsonrpc": "2.0", "id": 1, "error": { "code": -32602, "message": "invalid params" }
+			})))
+			.mount(&server)
+			.await;
+		Mock::given(body_partial_json(serde_json::json!({ "method": "eth_getBalance" })))
+			.respond_with(answer(serde_json::json!("0x5")))
+			.mount(&server)
+			.await;
+		let manager = SafeManager::new(safe, test_provider(&server.uri())).unwrap();
+		for _ in 0..2 {
+			let balances = manager.get_balances_batch(&queries[..3]).await.unwrap();
+			assert_eq!(*balances[0].as_ref().unwrap(), U256::from(5));
+			assert!(balances[1].is_err());
+			assert_eq!(*balances[2].as_ref().unwrap(), U256::from(1_000));
+		}
+	}
+
+	#[tokio::test]
+	async fn test_allowances() {
+		use wiremock::matchers::body_string_contains;
+		use wiremock::{Mock, MockServer, ResponseTemplate};
+
+		let server = MockServer::start().await;
+		let (usdt, reverting) = (Address::repeat_byte(0xdd), Address::repeat_byte(0xee));
+		let (fresh, partly, approved) = (Address::repeat_byte(0x01), Address::repeat_byte(0x02), Address::repeat_byte(0x03));
+		// allowance(address,address), answered per spender
+		for (spender, allowance) in [(fresh, 0_u64), (partly, 400), (approved, 1_000)] {
+			Mock::given(body_string_contains(format!("{:x}", usdt)))
+				.and(body_string_contains("dd62ed3e"))
+				.and(body_string_contains(format!("{:x}", spender)))
+				.respond_with(ResponseTemplate::new(200).set_body_json(
+					serde_json::json!({ "jsonrpc": "2.0", "id": 1, "result": format!("0x{:064x}", allowance) }),
+				))
+				.mount(&server)
+				.await;
+		}
+		Mock::given(body_string_contains(format!("{:x}", reverting)))
+			.respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+				"jsonrpc": "2.0",
+				"id": 1,
+				"error": { "code": 3, "message": "execution reverted" },
+			})))
+			.mount(&server)
+			.await;
+		let manager = SafeManager::new(Address::repeat_byte(0x11), test_provider(&server.uri())).unwrap();
+		let amount = U256::from(1_000);
+
+		assert_eq!(manager.get_allowance(usdt, partly).await.unwrap(), U256::from(400));
+		assert_eq!(manager.ensure_allowance(usdt, approved, amount).await.unwrap(), AllowanceStatus::Sufficient);
+		assert_eq!(manager.ensure_allowance(usdt, approved, U256::from(999)).await.unwrap(), AllowanceStatus::Sufficient);
+		assert_eq!(
+			manager.ensure_allowance(usdt, fresh, amount).await.unwrap(),
+			AllowanceStatus::Approve(vec![manager.build_approve_tx(usdt, fresh, amount)])
+		);
+		// A left-over allowance is reset to zero first
+		assert_eq!(
+			manager.ensure_allowance(usdt, partly, amount).await.unwrap(),
+			AllowanceStatus::Approve(vec![manager.build_revoke_tx(usdt, partly), manager.build_approve_tx(usdt, partly, amount)])
+		);
+
+		let error = manager.ensure_allowance(reverting, fresh, amount).await.unwrap_err();
+		assert!(matches!(error.downcast_ref(), Some(SafeError::TokenCallFailed { token, .. }) if *token == reverting), "{:#}", error);
+	}
+
+	#[tokio::test]
+	async fn test_owner_management() {
+		use ethers::abi::AbiDecode;
+		use wiremock::matchers::body_partial_json;
+		use wiremock::{Mock, MockServer, ResponseTemplate};
+
+		let safe = Address::repeat_byte(0x11);
+		let (first, second, third) = (Address::repeat_byte(0xa1), Address::repeat_byte(0xa2), Address::repeat_byte(0xa3));
+		let fresh = Address::repeat_byte(0xf0);
+		let server = MockServer::start().await;
+		// getOwners, in the order of the Safe's linked list
+		let owners = abi::encode(&[Token::Array(vec![Token::Address(first), Token::Address(second), Token::Address(third)])]);
+		Mock::given(body_partial_json(serde_json::json!({ "method": "eth_call" })))
+			.respond_with(ResponseTemplate::new(200).set_body_json(
+				serde_json::json!({ "jsonrpc": "2.0", "id": 1, "result": format!("0x{}", hex::encode(owners)) }),
+			))
+			.mount(&server)
+			.await;
+		let manager = SafeManager::new(safe, test_provider(&server.uri())).unwrap();
+		let refused = |error: anyhow::Error| error.downcast::<SafeError>().unwrap();
+
+		let tx = manager.build_add_owner_tx(fresh, U256::from(3)).await.unwrap();
+		assert_eq!((tx.to, tx.value, tx.operation), (safe, U256::zero(), protocol_actions::CALL));
+		let call = AddOwnerWithThresholdCall::decode(&tx.data).unwrap();
+		assert_eq!((call.owner, call.threshold), (fresh, U256::from(3)));
+
+		// The owner before the removed one in the list, the sentinel for the first
+		for (owner, prev) in [(first, SENTINEL_OWNERS), (second, first), (third, second)] {
+			let tx = manager.build_remove_owner_tx(owner, U256::from(2)).await.unwrap();
+			let call = RemoveOwnerCall::decode(&tx.data).unwrap();
+			assert_eq!((call.prev_owner, call.owner, call.threshold), (prev, owner, U256::from(2)));
+		}
+		let tx = manager.build_swap_owner_tx(third, fresh).await.unwrap();
+		assert_eq!(tx.to, safe);
+		let call = SwapOwnerCall::decode(&tx.data).unwrap();
+		assert_eq!((call.prev_owner, call.old_owner, call.new_owner), (second, third, fresh));
+
+		// Thresholds the resulting owners cannot meet
+		for (result, threshold, owners) in [
+			(manager.build_add_owner_tx(fresh, U256::from(5)).await, 5, 4),
+			(manager.build_add_owner_tx(fresh, U256::zero()).await, 0, 4),
+			(manager.build_remove_owner_tx(first, U256::from(3)).await, 3, 2),
+		] {
+			match refused(result.unwrap_err()) {
+				SafeError::InvalidThreshold { threshold: t, owners: o } => assert_eq!((t, o), (U256::from(threshold), owners)),
+				other => panic!("expected an invalid threshold, got {:?}", other),
+			}
+		}
+		// Owners that are not, or already are, and addresses that cannot be
+		assert!(matches!(
+			refused(manager.build_remove_owner_tx(fresh, U256::one()).await.unwrap_err()),
+			SafeError::NotAnOwner(owner) if owner == fresh
+		));
+		assert!(matches!(
+			refused(manager.build_swap_owner_tx(fresh, third).await.unwrap_err()),
+			SafeError::NotAnOwner(owner) if owner == fresh
+		));
+		assert!(matches!(
+			refused(manager.build_swap_owner_tx(first, second).await.unwrap_err()),
+			SafeError::AlreadyAnOwner(owner) if owner == second
+		));
+		assert!(matches!(
+			refused(manager.build_add_owner_tx(third, U256::one()).await.unwrap_err()),
+			SafeError::AlreadyAnOwner(owner) if owner == third
+		));
+		for invalid in [Address::zero(), SENTINEL_OWNERS, safe] {
+			let error = refused(manager.build_swap_owner_tx(first, invalid).await.unwrap_err());
+			assert!(matches!(error, SafeError::InvalidAddress(_)), "{:?}", error);
+		}
+	}
+
+	#[test]
+	fn test_safe_tx_hash_vectors() {
+		let safe = Address::from_str("0x5afe00000000000000000000000000000000cafe").unwrap();
+		let transfer = SafeTransaction {
+			to: Address::repeat_byte(0x22),
+			value: U256::exp10(18),
+			data: vec![],
+			operation: 0,
+			safe_tx_gas: U256::zero(),
+			nonce: Some(7.into()),
+		};
+		assert_eq!(
+			transfer.safe_tx_hash(1.into(), safe, 7.into()),
+			H256::from_str("0x098b7bf8fd78fd2e67b55fd7c9a58e6f4ac9fb57d3248e35dd6d52e4b1208ec0").unwrap()
+		);
+
+		// USDC transfer(0x3333…, 2500 USDC) on Arbitrum as a delegate call
+		let data = hex::decode(concat!(
+			"a9059cbb",
+			"0000000000000000000000003333333333333333333333333333333333333333",
+			"000000000000000000000000000000000000000000000000000000009502f900",
+		))
+		.unwrap();
+		let call = SafeTransaction {
+			to: Address::from_str("0xaf88d065e77c8cc2239327c5edb3a432268e5831").unwrap(),
+			value: U256::zero(),
+			data,
+			operation: 1,
+			safe_tx_gas: 50_000.into(),
+			nonce: None,
+		};
+		assert_eq!(
+			call.safe_tx_hash(42161.into(), safe, U256::zero()),
+			H256::from_str("0x63c079412e520a60f8d21b1adcf0d552329706b62bde48209dbeb155ccdd9de3").unwrap()
+		);
+		// Every field is part of the hash
+		assert_ne!(call.safe_tx_hash(1.into(), safe, U256::zero()), call.safe_tx_hash(42161.into(), safe, U256::zero()));
+		assert_ne!(call.safe_tx_hash(42161.into(), safe, U256::one()), call.safe_tx_hash(42161.into(), safe, U256::zero()));
+	}
+
+	#[tokio::test]
+	async fn test_sign_safe_tx() {
+		use ethers::signers::Signer;
+		use ethers::types::Signature;
+
+		let (manager, _node) = setup_test_manager(1).await;
+		let owner = LocalWallet::from_str("4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318").unwrap();
+		let tx = SafeTransaction {
+			to: Address::repeat_byte(0x22),
+			value: U256::exp10(18),
+			data: vec![],
+			operation: 0,
+			safe_tx_gas: U256::zero(),
+			nonce: Some(7.into()),
+		};
+
+		let signature = manager.sign_safe_tx(&tx, &owner.clone().into()).await.unwrap();
+		assert_eq!(signature.len(), 65);
+		assert!(matches!(signature[64], 27 | 28), "v = {}", signature[64]);
+		let hash = manager.safe_tx_hash(&tx).await.unwrap();
+		assert_eq!(hash, tx.safe_tx_hash(1.into(), manager.get_address(), 7.into()));
+		let recovered = Signature::try_from(signature.as_ref()).unwrap().recover(hash).unwrap();
+		assert_eq!(recovered, owner.address());
+	}
+
+	#[tokio::test]
+	async fn test_propose_transaction() {
+		use ethers::signers::Signer;
+		use ethers::types::Signature;
+		use wiremock::matchers::{body_partial_json, body_string_contains, method};
+		use wiremock::{Mock, MockServer, ResponseTemplate};
+
+		let node = MockServer::start().await;
+		let answer = |result: String| {
+			ResponseTemplate::new(200).set_body_json(serde_json::json!({ "jsonrpc": "2.0", "id": 1, "result": result }))
+		};
+		// getThreshold and nonce: a node lagging behind at nonce 4
+		for (selector, word) in [("e75235b8", 2), ("affed0e0", 4)] {
+			Mock::given(body_string_contains(selector)).respond_with(answer(format!("0x{:064x}", word))).mount(&node).await;
+		}
+		for (rpc_method, result) in [
+			("eth_getBalance", "0xde0b6b3a7640000"),
+			("eth_call", "0x"),
+			("eth_estimateGas", "0x5208"),
+			("eth_gasPrice", "0x3b9aca00"),
+			("eth_chainId", "0x1"),
+			("eth_getCode", "0x6080"),
+		] {
+			Mock::given(body_partial_json(serde_json::json!({ "method": rpc_method })))
+				.respond_with(answer(result.to_string()))
+				.mount(&node)
+				.await;
+		}
+		let service = MockServer::start().await;
+		Mock::given(body_partial_json(serde_json::json!({ "nonce": "4" })))
+			.respond_with(ResponseTemplate::new(422).set_body_json(serde_json::json!({
+				"nonFieldErrors": ["Tx with nonce 4 for safe 0x11 already executed in tx-hash 0xdead"],
+			})))
+			.mount(&service)
+			.await;
+		Mock::given(method("POST")).respond_with(ResponseTemplate::new(201)).mount(&service).await;
+
+		let owner = LocalWallet::from_str("4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318").unwrap();
+		let mut manager = SafeManager::new(Address::repeat_byte(0x11), test_provider(&node.uri())).unwrap();
+		assert!(matches!(
+			manager.set_execution(SafeExecution::Propose).unwrap_err().downcast_ref(),
+			Some(SafeError::NoSigner)
+		));
+		manager.set_signer(owner.clone());
+		manager.set_transaction_service(TransactionService::new(service.uri()));
+		manager.set_execution(SafeExecution::Propose).unwrap();
+		let (events, mut rx) = EventSender::channel(4);
+		manager.set_events(events);
+		let tx = SafeTransaction {
+			to: Address::repeat_byte(0x22),
+			value: U256::exp10(15),
+			data: vec![],
+			operation: 0,
+			safe_tx_gas: U256::zero(),
+			nonce: None,
+		};
+
+		manager.execute_transaction(tx.clone()).await.unwrap();
+		let expected = tx.safe_tx_hash(1.into(), manager.get_address(), 5.into());
+		match rx.try_recv() {
+			Ok(AgentEvent::TransactionProposed { nonce, safe_tx_hash, .. }) => {
+				assert_eq!((nonce, safe_tx_hash), (5.into(), expected));
+			}
+			other => panic!("expected a proposal, got {:?}", other),
+		}
+		let requests = service.received_requests().await.unwrap();
+		assert_eq!(requests.len(), 2);
+		let body: serde_json::Value = serde_json::from_slice(&requests[1].body).unwrap();
+		assert_eq!(body["contractTransactionHash"], format!("{:?}", expected));
+		assert_eq!(body["sender"], ethers::utils::to_checksum(&owner.address(), None));
+		let signature: Bytes = body["signature"].as_str().unwrap().parse().unwrap();
+		assert_eq!(Signature::try_from(signature.as_ref()).unwrap().recover(expected).unwrap(), owner.address());
+
+		// A nonce given by the caller is not replaced
+		let pinned = SafeTransaction { nonce: Some(4.into()), ..tx };
+		let error = manager.execute_transaction(pinned).await.unwrap_err();
+		assert!(matches!(error.downcast_ref(), Some(SafeError::NonceAlreadyUsed(nonce)) if *nonce == U256::from(4)), "{:#}", error);
+		assert!(matches!(rx.try_recv(), Ok(AgentEvent::TransactionFailed { .. })));
+	}
+
+	#[tokio::test]
+	async fn test_transaction_type_follows_fee_history() {
+		use wiremock::matchers::body_partial_json;
+		use wiremock::{Mock, MockServer, ResponseTemplate};
+
+		let gwei = |amount: f64| U256::from((amount * 1e9) as u64);
+		let hex = |value: U256| format!("{:#x}", value);
+		// The node's answer to eth_feeHistory, an error for `None`
+		let node = |history: Option<serde_json::Value>| async move {
+			let server = MockServer::start().await;
+			let answer = match history {
+				Some(history) => serde_json::json!({ "jsonrpc": "2.0", "id": 1, "result": history }),
+				None => serde_json::json!({
+					"jsonrpc": "2.0",
+					"id": 1,
+					"error": { "code": -32601, "message": "the method eth_feeHistory does not exist" },
+				}),
+			};
+			Mock::given(body_partial_json(serde_json::json!({ "method": "eth_feeHistory" })))
+				.respond_with(ResponseTemplate::new(200).set_body_json(answer))
+				.mount(&server)
+				.await;
+			mount_answers(&server, &[
+				("eth_getBalance", serde_json::json!(hex(U256::exp10(18)))),
+				("eth_estimateGas", serde_json::json!("0x5208")),
+				("eth_gasPrice", serde_json::json!(hex(gwei(1.0)))),
+				("eth_chainId", serde_json::json!("0x1")),
+				("eth_getCode", serde_json::json!("0x6080")),
+				// getThreshold and nonce
+				("eth_call", serde_json::json!(format!("0x{:064x}", 1))),
+			]).await;
+			server
+		};
+		/// The fee fields of the last gas estimate.
+		async fn estimated(server: &MockServer) -> (Option<String>, Option<String>) {
+			let requests = server.received_requests().await.unwrap();
+			let calls: Vec<serde_json::Value> =
+				requests.iter().map(|request| serde_json::from_slice(&request.body).unwrap()).collect();
+			let estimate = calls.iter().rev().find(|call| call["method"] == "eth_estimateGas").unwrap();
+			let tx = &estimate["params"][0];
+			(tx["maxFeePerGas"].as_str().map(str::to_string), tx["maxPriorityFeePerGas"].as_str().map(str::to_string))
+		}
+		let tx = SafeTransaction {
+			to: Address::repeat_byte(0x22),
+			value: U256::exp10(17),
+			data: vec![],
+			operation: 0,
+			safe_tx_gas: U256::zero(),
+			nonce: None,
+		};
+
+		// Base fees of the last two blocks and the next; tips of 1, 2 and 1.5 gwei
+		let london = node(Some(serde_json::json!({
+			"oldestBlock": "0x10",
+			"baseFeePerGas": [hex(gwei(10.0)), hex(gwei(11.0)), hex(gwei(12.0))],
+			"gasUsedRatio": [0.9, 0.8],
+			"reward": [[hex(gwei(1.0))], [hex(gwei(2.0))], [hex(gwei(1.5))]],
+		})))
+		.await;
+		let mut manager = SafeManager::new(Address::repeat_byte(0x11), test_provider(&london.uri())).unwrap();
+		let fees = manager.fees().await.unwrap();
+		assert_eq!(fees, TxFees::Eip1559 { max_fee_per_gas: gwei(25.5), max_priority_fee_per_gas: gwei(1.5) });
+		assert_eq!(fees.max_gas_price(), gwei(25.5));
+		manager.simulate_transaction(&tx).await.unwrap();
+		assert_eq!(estimated(&london).await, (Some(hex(gwei(25.5))), Some(hex(gwei(1.5)))));
+		manager.set_priority_fee(Some(gwei(3.0)));
+		assert_eq!(manager.fees().await.unwrap(), TxFees::Eip1559 { max_fee_per_gas: gwei(27.0), max_priority_fee_per_gas: gwei(3.0) });
 
-		debug!("Initializing SafeManager for address: {:?}", address);
-		debug!("Minimum balance threshold: {} wei", min_balance);
-		debug!("Critical balance threshold: {} wei", critical_balance);
+		// The cost check pays the maximum fee: 21000 gas, buffered to 25200, at 27 gwei does not
+		// fit, at 1 gwei it would
+		manager.set_mode(RunMode::DryRun);
+		let fits_legacy = SafeTransaction { value: U256::exp10(18) - gwei(21_000.0 * 2.0), ..tx.clone() };
+		let error = manager.execute_transaction(fits_legacy.clone()).await.unwrap_err();
+		match error.downcast_ref() {
+			Some(SafeError::InsufficientBalance { required, .. }) => {
+				assert_eq!(*required, fits_legacy.value + gwei(27.0) * 25_200)
+			}
+			other => panic!("expected an insufficient balance, got {:?}", other),
+		}
 
-		Ok(Self {
-			address,
-			provider,
-			min_balance,
-			critical_balance,
-		})
+		// No base fees, or no eth_feeHistory at all: legacy at eth_gasPrice
+		let legacy = serde_json::json!({ "oldestBlock": "0x10", "gasUsedRatio": [0.5], "reward": [] });
+		for history in [Some(legacy), None] {
+			let server = node(history).await;
+			let mut manager = SafeManager::new(Address::repeat_byte(0x11), test_provider(&server.uri())).unwrap();
+			assert_eq!(manager.fees().await.unwrap(), TxFees::Legacy { gas_price: gwei(1.0) });
+			manager.simulate_transaction(&tx).await.unwrap();
+			assert_eq!(estimated(&server).await, (None, None));
+			manager.set_mode(RunMode::DryRun);
+			manager.execute_transaction(fits_legacy.clone()).await.unwrap();
+		}
 	}
 
-	pub async fn get_balance(&self) -> Result<U256> {
-		debug!("Fetching balance for address: {:?}", self.address);
-		
-		self.provider
-			.get_balance(self.address, None)
-			.await
-			.context("Failed to fetch balance")
-			.map_err(|e| {
-				error!("Provider error while fetching balance: {}", e);
-				SafeError::ProviderError(e.to_string()).into()
-			})
+	/// The calls packed in the `multiSend(bytes)` calldata of a batch.
+	fn unpack_multisend(data: &[u8]) -> Vec<SafeTransaction> {
+		assert_eq!(data[..4], ethers::utils::id("multiSend(bytes)"));
+		let packed = abi::decode(&[abi::ParamType::Bytes], &data[4..]).unwrap().remove(0).into_bytes().unwrap();
+		let mut calls = Vec::new();
+		let mut rest = &packed[..];
+		while !rest.is_empty() {
+			let length = U256::from_big_endian(&rest[53..85]).as_usize();
+			calls.push(SafeTransaction {
+				to: Address::from_slice(&rest[1..21]),
+				value: U256::from_big_endian(&rest[21..53]),
+				data: rest[85..85 + length].to_vec(),
+				operation: rest[0],
+				safe_tx_gas: U256::zero(),
+				nonce: None,
+			});
+			rest = &rest[85 + length..];
+		}
+		calls
 	}
 
-	pub async fn check_balance_threshold(&self) -> Result<bool> {
-		let balance = self.get_balance().await?;
-		let is_below = balance < self.min_balance;
-		
-		if balance <= self.critical_balance {
-			error!(
-				"CRITICAL: Balance extremely low! Current: {} wei, Minimum: {} wei. Action required: Please fund the account with at least {} wei",
-				balance, self.critical_balance, self.min_balance
-			);
-			return Err(SafeError::CriticalBalance {
-				current: balance,
-				minimum: self.critical_balance,
-			}.into());
+	#[tokio::test]
+	async fn test_build_multisend() {
+		let (manager, _node) = setup_test_manager(0).await;
+		let (token, pool) = (Address::repeat_byte(0xaa), Address::repeat_byte(0xbb));
+		let amount = U256::from(2_500_000_000_u64);
+		let calls = vec![
+			protocol_actions::approve(token, pool, amount),
+			protocol_actions::compound_v3::supply(pool, token, amount),
+			SafeTransaction { value: U256::exp10(16), ..protocol_actions::call(Address::repeat_byte(0xcc), Vec::<u8>::new()) },
+		];
+
+		let batch = manager.build_multisend(calls.clone()).unwrap();
+		assert_eq!(batch.to, Address::from_str(MULTI_SEND_CALL_ONLY).unwrap());
+		assert_eq!(batch.operation, protocol_actions::DELEGATE_CALL);
+		assert_eq!(batch.value, U256::zero());
+		assert_eq!(unpack_multisend(&batch.data), calls);
+
+		assert_eq!(manager.build_multisend(calls[..1].to_vec()).unwrap(), calls[0]);
+		assert!(manager.build_multisend(Vec::new()).is_err());
+		let nested = SafeTransaction { operation: protocol_actions::DELEGATE_CALL, ..calls[0].clone() };
+		assert!(manager.build_multisend(vec![calls[1].clone(), nested]).is_err());
+	}
+
+	#[tokio::test]
+	async fn test_build_approve_tx() {
+		use crate::protocol_actions::ApproveCall;
+		use ethers::abi::AbiDecode;
+
+		let (manager, _node) = setup_test_manager(0).await;
+		let (token, spender) = (Address::repeat_byte(0xaa), Address::repeat_byte(0xbb));
+		for (tx, amount) in [
+			(manager.build_approve_tx(token, spender, U256::from(1_000_000)), U256::from(1_000_000)),
+			(manager.build_approve_tx(token, spender, Allowance::Unlimited), U256::MAX),
+			(manager.build_revoke_tx(token, spender), U256::zero()),
+		] {
+			assert_eq!((tx.to, tx.value, tx.operation), (token, U256::zero(), protocol_actions::CALL));
+			assert_eq!(&tx.data[..4], &ethers::utils::id("approve(address,uint256)"));
+			assert_eq!(ApproveCall::decode(&tx.data).unwrap(), ApproveCall { spender, amount });
 		}
-		
-		if is_below {
-			warn!(
-				"WARNING: Balance ({} wei) is below minimum threshold ({} wei). Consider funding the account soon.",
-				balance, self.min_balance
-			);
-		} else {
-			info!(
-				"Balance is sufficient. Current: {} wei, Minimum required: {} wei",
-				balance, self.min_balance
-			);
+	}
+
+	/// Two owners and a stranger, as well-known development keys.
+	fn signing_keys() -> [LocalWallet; 3] {
+		[
+			"4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318",
+			"59c6995e998f97a5a0044966f0945389dc9e86dae88c7a8412f4603b6b78690d",
+			"5de4111afa1a4b94908f83103eb1f1706367c2e68ca870fc3fb9a804cdab365a",
+		]
+		.map(|key| LocalWallet::from_str(key).unwrap())
+	}
+
+	#[test]
+	fn test_recover_signer() {
+		let hash = H256::repeat_byte(0x42);
+		for key in &signing_keys()[..2] {
+			let signature = key.sign_hash(hash).unwrap();
+			assert_eq!(recover_signer(hash, &signature.to_vec()).unwrap(), key.address());
+			// As a Ledger signs it: an eth_sign message of the hash, v raised by 4
+			let mut message = futures::executor::block_on(key.sign_message(hash)).unwrap();
+			message.v += 4;
+			assert_eq!(recover_signer(hash, &message.to_vec()).unwrap(), key.address());
+			assert_ne!(recover_signer(H256::repeat_byte(0x43), &signature.to_vec()).unwrap(), key.address());
 		}
-		
-		Ok(is_below)
+		let mut contract_signature = signing_keys()[0].sign_hash(hash).unwrap().to_vec();
+		contract_signature[64] = 0;
+		assert!(matches!(recover_signer(hash, &contract_signature), Err(SafeError::MalformedSignature(_))));
+		assert!(matches!(recover_signer(hash, &[27; 64]), Err(SafeError::MalformedSignature(_))));
 	}
 
-	pub async fn simulate_transaction(&self, tx: &SafeTransaction) -> Result<U256> {
-		info!("Simulating transaction to: {:?}", tx.to);
-		debug!("Transaction details: value={}, data_len={}", tx.value, tx.data.len());
-		
-		let balance = self.get_balance().await?;
-		if balance < tx.value {
-			error!(
-				"Insufficient balance for transaction. Required: {} wei, Available: {} wei. Please fund the account with at least {} wei",
-				tx.value, balance, tx.value - balance
-			);
-			return Err(SafeError::InsufficientBalance {
-				required: tx.value,
-				available: balance,
-			}.into());
+	#[tokio::test]
+	async fn test_signature_collection() {
+		use wiremock::matchers::body_partial_json;
+		use wiremock::{Mock, MockServer, ResponseTemplate};
+
+		let [first, second, stranger] = signing_keys();
+		let server = MockServer::start().await;
+		let respond = |result: serde_json::Value| {
+			ResponseTemplate::new(200).set_body_json(serde_json::json!({ "jsonrpc": "2.0", "id": 1, "result": result }))
+		};
+		// A Safe of two owners needing both; mounted first, so these win over the other calls
+		for (function, result) in [
+			("getOwners()", abi::encode(&[Token::Array(vec![Token::Address(first.address()), Token::Address(second.address())])])),
+			("getThreshold()", abi::encode(&[Token::Uint(2.into())])),
+		] {
+			let data = Bytes::from(ethers::utils::id(function).to_vec());
+			Mock::given(body_partial_json(serde_json::json!({ "method": "eth_call", "params": [{ "data": data }] })))
+				.respond_with(respond(serde_json::json!(Bytes::from(result))))
+				.mount(&server)
+				.await;
 		}
+		mount_answers(&server, &[
+			("eth_getBalance", serde_json::json!("0xde0b6b3a7640000")),
+			("eth_estimateGas", serde_json::json!("0x5208")),
+			("eth_gasPrice", serde_json::json!("0x3b9aca00")),
+			("eth_chainId", serde_json::json!("0x1")),
+			("eth_getCode", serde_json::json!("0x6080")),
+			// The simulated call
+			("eth_call", serde_json::json!("0x0000000000000000000000000000000000000000000000000000000000000001")),
+			("eth_getTransactionCount", serde_json::json!("0x0")),
+			("eth_sendRawTransaction", serde_json::json!("0x4242424242424242424242424242424242424242424242424242424242424242")),
+			("eth_blockNumber", serde_json::json!("0x11")),
+		]).await;
+		Mock::given(body_partial_json(serde_json::json!({ "method": "eth_getTransactionReceipt" })))
+			.respond_with(respond(receipt(H256::repeat_byte(0x42), 0x10, 1, serde_json::json!([]))))
+			.mount(&server)
+			.await;
+		let safe = Address::repeat_byte(0x11);
+		let mut manager = SafeManager::new(safe, test_provider(&server.uri())).unwrap();
+		manager.set_signer(first.clone());
+		let tx = SafeTransaction {
+			to: Address::repeat_byte(0x22),
+			value: U256::exp10(15),
+			data: vec![],
+			operation: 0,
+			safe_tx_gas: U256::zero(),
+			nonce: Some(7.into()),
+		};
+		let error = |result: Result<Address>| match result.unwrap_err().downcast::<SafeError>() {
+			Ok(e) => e,
+			Err(other) => panic!("unexpected error: {:#}", other),
+		};
 
-		let tx_request = TransactionRequest::new()
-			.to(tx.to)
-			.value(tx.value)
-			.from(self.address)
-			.data(tx.data.clone());
+		// Overlapping prepares of the same transaction keep one entry, signed once
+		let (hash, again) = tokio::join!(manager.prepare_transaction(&tx), manager.prepare_transaction(&tx));
+		let hash = hash.unwrap();
+		assert_eq!(hash, tx.safe_tx_hash(U256::one(), safe, 7.into()));
+		assert_eq!(again.unwrap(), hash);
+		assert_eq!(manager.prepare_transaction(&tx).await.unwrap(), hash);
+		assert_eq!(manager.pending_transaction(hash).unwrap().signers(), [first.address()]);
 
-		let typed_tx = TypedTransaction::Legacy(tx_request);
+		let sign = |key: &LocalWallet| Bytes::from(key.sign_hash(hash).unwrap().to_vec());
+		assert!(matches!(error(manager.add_signature(hash, sign(&first)).await), SafeError::DuplicateSignature(a) if a == first.address()));
+		assert!(matches!(error(manager.add_signature(hash, sign(&stranger)).await), SafeError::NotAnOwner(a) if a == stranger.address()));
+		assert!(matches!(
+			error(manager.add_signature(H256::zero(), sign(&second)).await),
+			SafeError::UnknownPendingTransaction(_)
+		));
+		assert_eq!(manager.pending_transaction(hash).unwrap().signature_count(), 1);
 
-		self.provider.estimate_gas(&typed_tx, None).await
-			.map_err(|e| {
-				error!("Gas estimation failed: {}. Please verify transaction parameters and network conditions", e);
-				SafeError::GasEstimationFailed(e.to_string()).into()
-			})
+		// One of two signatures: nothing is sent
+		assert_eq!(manager.execute_when_ready(hash).await.unwrap(), None);
+		let sent = || async {
+			server
+				.received_requests()
+				.await
+				.unwrap()
+				.into_iter()
+				.map(|request| String::from_utf8_lossy(&request.body).into_owned())
+				.filter(|body| body.contains("eth_sendRawTransaction"))
+				.collect::<Vec<_>>()
+		};
+		assert!(sent().await.is_empty());
+
+		assert_eq!(manager.add_signature(hash, sign(&second)).await.unwrap(), second.address());
+		let pending = manager.pending_transaction(hash).unwrap();
+		let mut owners = [(first.address(), sign(&first)), (second.address(), sign(&second))];
+		owners.sort_by_key(|(owner, _)| *owner);
+		assert_eq!(pending.signers(), owners.iter().map(|(owner, _)| *owner).collect::<Vec<_>>());
+		let packed: Vec<u8> = owners.iter().flat_map(|(_, signature)| signature.to_vec()).collect();
+		assert_eq!(pending.packed_signatures().to_vec(), packed);
+
+		let outcome = manager.execute_when_ready(hash).await.unwrap();
+		assert_eq!(outcome.and_then(|outcome| outcome.tx_hash()), Some(H256::repeat_byte(0x42)));
+		let sent = sent().await;
+		assert_eq!(sent.len(), 1);
+		// The signatures travel in execTransaction's calldata, in owner order
+		assert!(sent[0].contains(&ethers::utils::hex::encode(&packed)), "{}", sent[0]);
+		assert!(manager.pending_transaction(hash).is_none());
 	}
 
+	#[tokio::test]
+	async fn test_check_modules() {
+		use wiremock::matchers::body_partial_json;
+		use wiremock::{Mock, MockServer, ResponseTemplate};
 
+		let [first, second, third, guard] = [0x11, 0x22, 0x33, 0x44].map(Address::repeat_byte);
+		let server = MockServer::start().await;
+		// Two pages: the first ends with a module to carry on from, the second with the sentinel
+		for (start, page, next) in [(SENTINEL_MODULES, vec![first, second], second), (second, vec![third], SENTINEL_MODULES)] {
+			let mut data = ethers::utils::id("getModulesPaginated(address,uint256)").to_vec();
+			data.extend(abi::encode(&[Token::Address(start), Token::Uint(MODULES_PAGE_SIZE.into())]));
+			let result = abi::encode(&[
+				Token::Array(page.into_iter().map(Token::Address).collect()),
+				Token::Address(next),
+			]);
+			Mock::given(body_partial_json(serde_json::json!({ "method": "eth_call", "params": [{ "data": Bytes::from(data) }] })))
+				.respond_with(ResponseTemplate::new(200).set_body_json(
+					serde_json::json!({ "jsonrpc": "2.0", "id": 1, "result": Bytes::from(result) }),
+				))
+				.mount(&server)
+				.await;
+		}
+		Mock::given(body_partial_json(serde_json::json!({ "method": "eth_getStorageAt" })))
+			.respond_with(ResponseTemplate::new(200).set_body_json(
+				serde_json::json!({ "jsonrpc": "2.0", "id": 1, "result": H256::from(guard) }),
+			))
+			.mount(&server)
+			.await;
+		let mut manager = SafeManager::new(Address::repeat_byte(0x55), test_provider(&server.uri())).unwrap();
 
-	pub async fn execute_transaction(&self, tx: SafeTransaction) -> Result<()> {
-		info!("Preparing to execute transaction to: {:?}", tx.to);
-		debug!("Transaction value: {} wei", tx.value);
+		assert_eq!(manager.get_enabled_modules().await.unwrap(), [first, second, third]);
+		assert_eq!(manager.get_guard().await.unwrap(), Some(guard));
+		let unexpected = |err: anyhow::Error| match err.downcast_ref() {
+			Some(SafeError::UnexpectedModule(address)) => *address,
+			_ => panic!("unexpected error: {:#}", err),
+		};
+		assert_eq!(unexpected(manager.check_modules().await.unwrap_err()), first);
+		manager.set_allowed_modules(vec![first, third, guard]);
+		assert_eq!(unexpected(manager.check_modules().await.unwrap_err()), second);
+		manager.set_allowed_modules(vec![first, second, third]);
+		assert_eq!(unexpected(manager.check_modules().await.unwrap_err()), guard);
+		manager.set_allowed_modules(vec![first, second, third, guard]);
+		manager.check_modules().await.unwrap();
+	}
 
-		// First simulate to get gas estimate
-		let estimated_gas = self.simulate_transaction(&tx).await?;
-		info!("Gas estimation successful: {} units", estimated_gas);
+	#[tokio::test]
+	async fn test_delegatecalls_are_guarded() {
+		// Nothing mounted: any request would show up below
+		let node = wiremock::MockServer::start().await;
+		let mut manager = SafeManager::new(Address::repeat_byte(0x11), test_provider(&node.uri())).unwrap();
+		manager.set_signer(
+			LocalWallet::from_str("4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318").unwrap(),
+		);
+		let multi_send = Address::from_str(MULTI_SEND_CALL_ONLY).unwrap();
+		let delegatecall = |to: Address| SafeTransaction {
+			to,
+			value: U256::zero(),
+			data: vec![0x8d, 0x80, 0xff, 0x0a],
+			operation: protocol_actions::DELEGATE_CALL,
+			safe_tx_gas: U256::zero(),
+			nonce: None,
+		};
+		let refused = |error: anyhow::Error| match error.downcast::<SafeError>() {
+			Ok(SafeError::DelegatecallNotAllowed(to)) => to,
+			other => panic!("expected a refused delegatecall, got {:?}", other),
+		};
 
-		// Additional validation here
-		let total_required = tx.value + (estimated_gas * self.provider.get_gas_price().await?);
-		let balance = self.get_balance().await?;
-		
-		if balance < total_required {
-			return Err(SafeError::InsufficientBalance {
-				required: total_required,
-				available: balance,
-			}.into());
-		}
+		// Off by default, even to MultiSendCallOnly
+		assert_eq!(refused(manager.simulate_transaction(&delegatecall(multi_send)).await.unwrap_err()), multi_send);
+		manager.set_delegatecalls(true, vec![multi_send]);
+		let stranger = Address::repeat_byte(0x66);
+		assert_eq!(refused(manager.simulate_transaction(&delegatecall(stranger)).await.unwrap_err()), stranger);
+		let error = manager.execute_transaction(delegatecall(stranger)).await.unwrap_err();
+		assert_eq!(ErrorCategory::of(&error), ErrorCategory::Validation);
+		assert_eq!(refused(error), stranger);
+		assert_eq!(refused(manager.propose_transaction(&delegatecall(stranger)).await.unwrap_err()), stranger);
 
-		// In a real implementation, this would:
-		// 1. Create the Safe transaction
-		// 2. Sign the transaction
-		// 3. Collect required signatures
-		// 4. Execute the transaction
-		
-		info!("Transaction executed successfully");
-		debug!("Gas used: {}", estimated_gas);
-		Ok(())
+		let error = manager
+			.simulate_transaction(&SafeTransaction { operation: 2, ..delegatecall(multi_send) })
+			.await
+			.unwrap_err();
+		assert!(matches!(error.downcast_ref(), Some(SafeError::InvalidOperation(2))), "{:#}", error);
+		assert!(node.received_requests().await.unwrap().is_empty());
 	}
 
-	pub fn get_address(&self) -> Address {
-		self.address
+	#[tokio::test]
+	async fn test_deployments() {
+		use wiremock::matchers::{body_partial_json, body_string_contains};
+		use wiremock::{Mock, MockServer, ResponseTemplate};
+
+		let safe = Address::repeat_byte(0x11);
+		let create_call = Address::from_str(CREATE_CALL).unwrap();
+		let node = MockServer::start().await;
+		// The Safe's simulation of the delegatecall to CreateCall, before every other eth_call
+		let response = abi::encode(&[Token::Uint(150_000.into()), Token::Bool(true), Token::Bytes(Vec::new())]);
+		Mock::given(body_partial_json(serde_json::json!({ "method": "eth_call" })))
+			.and(body_string_contains(format!("{:x}", Address::from_str(SIMULATE_TX_ACCESSOR).unwrap())))
+			.respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+				"jsonrpc": "2.0", "id": 1, "result": format!("0x{}", hex::encode(abi::encode(&[Token::Bytes(response)]))),
+			})))
+			.mount(&node)
+			.await;
+		mount_answers(&node, &[
+			("eth_getBalance", serde_json::json!(format!("{:#x}", U256::exp10(18)))),
+			("eth_estimateGas", serde_json::json!("0x30d40")),
+			("eth_gasPrice", serde_json::json!("0x3b9aca00")),
+			("eth_chainId", serde_json::json!("0x1")),
+			("eth_getCode", serde_json::json!("0x6080")),
+			// getThreshold and the Safe nonce
+			("eth_call", serde_json::json!(format!("0x{:064x}", 1))),
+			// The Safe's own account nonce, which contracts start at 1
+			("eth_getTransactionCount", serde_json::json!("0x1")),
+		]).await;
+		let deployment = DeploymentTransaction { value: U256::exp10(15), init_code: Bytes::from(vec![0x60, 0x80, 0x60, 0x40]) };
+		let expected = get_contract_address(safe, 1);
+
+		let mut manager = SafeManager::new(safe, test_provider(&node.uri())).unwrap();
+		let (gas, contract_address) = manager.simulate_deployment(&deployment).await.unwrap();
+		assert_eq!((gas, contract_address), (U256::from(240_000), expected));
+		// Estimated as a creation: no target, not even the zero address
+		let requests = node.received_requests().await.unwrap();
+		let estimate = requests
+			.iter()
+			.map(|request| serde_json::from_slice::<serde_json::Value>(&request.body).unwrap())
+			.find(|call| call["method"] == "eth_estimateGas")
+			.unwrap();
+		assert!(estimate["params"][0].get("to").is_none(), "{}", estimate);
+		assert_eq!(estimate["params"][0]["data"], serde_json::json!("0x60806040"));
+
+		// Through CreateCall, which has to be an allowed delegatecall target
+		let tx = deployment.safe_transaction(create_call);
+		assert_eq!((tx.to, tx.value, tx.operation), (create_call, U256::zero(), protocol_actions::DELEGATE_CALL));
+		let error = manager.execute_deployment(&deployment).await.unwrap_err();
+		assert!(matches!(error.downcast_ref(), Some(SafeError::DelegatecallNotAllowed(to)) if *to == create_call));
+		manager.set_delegatecalls(true, vec![create_call]);
+		manager.set_mode(RunMode::DryRun);
+		let outcome = manager.execute_deployment(&deployment).await.unwrap();
+		match outcome {
+			ExecutionOutcome::Simulated(simulated) => {
+				assert_eq!((simulated.to, simulated.contract_address), (create_call, Some(expected)));
+				assert!(simulated.to_string().ends_with(&format!("deploying {:?}", expected)), "{}", simulated);
+			}
+			other => panic!("expected a simulated deployment, got {:?}", other),
+		}
+		assert_eq!(outcome.result().unwrap().contract_address, Some(expected));
+
+		// The deployment's value counts against the spending limits
+		manager.set_spending_limits(Some(U256::exp10(14)), None);
+		let error = manager.execute_deployment(&deployment).await.unwrap_err();
+		assert!(matches!(error.downcast_ref(), Some(SafeError::SpendingLimitExceeded { attempted, .. }) if *attempted == deployment.value));
+
+		// Distinct from a call to the zero address when serialized
+		let json = serde_json::to_value(&deployment).unwrap();
+		assert_eq!(json, serde_json::json!({ "value": "0x38d7ea4c68000", "init_code": "0x60806040" }));
+		assert_eq!(serde_json::from_value::<DeploymentTransaction>(json).unwrap(), deployment);
 	}
 
-	pub fn set_min_balance(&mut self, min_balance: U256) {
-		self.min_balance = min_balance;
-		self.critical_balance = min_balance / 2;
-		info!(
-			"Updated balance thresholds - Minimum: {} wei, Critical: {} wei",
-			min_balance, self.critical_balance
-		);
+	#[tokio::test]
+	async fn test_simulate_batch_in_the_safe() {
+		use wiremock::matchers::{body_partial_json, body_string_contains};
+		use wiremock::{Mock, MockServer, ResponseTemplate};
+
+		let safe = Address::repeat_byte(0x5a);
+		let server = MockServer::start().await;
+		let simulation = |success: bool, return_data: Vec<u8>| {
+			let response = abi::encode(&[Token::Uint(120_000.into()), Token::Bool(success), Token::Bytes(return_data)]);
+			let output = abi::encode(&[Token::Bytes(response)]);
+			ResponseTemplate::new(200).set_body_json(
+				serde_json::json!({ "jsonrpc": "2.0", "id": 1, "result": format!("0x{}", hex::encode(output)) }),
+			)
+		};
+		let accessor = format!("{:x}", Address::from_str(SIMULATE_TX_ACCESSOR).unwrap());
+		let mount = |response: ResponseTemplate| {
+			// simulate(address,bytes) on the Safe, naming the accessor
+			Mock::given(body_partial_json(serde_json::json!({ "method": "eth_call" })))
+				.and(body_string_contains(format!("{:x}", safe)))
+				.and(body_string_contains(accessor.clone()))
+				.respond_with(response)
+		};
+		// Every other call, the balance and gas price included, answers zero
+		let zero = || {
+			Mock::given(wiremock::matchers::method("POST")).respond_with(
+				ResponseTemplate::new(200).set_body_json(serde_json::json!({ "jsonrpc": "2.0", "id": 1, "result": "0x0" })),
+			)
+		};
+		mount(simulation(true, Vec::new())).mount(&server).await;
+
+		let mut manager = SafeManager::new(safe, test_provider(&server.uri())).unwrap();
+		manager.set_delegatecalls(true, vec![Address::from_str(MULTI_SEND_CALL_ONLY).unwrap()]);
+		let calls = vec![
+			protocol_actions::approve(Address::repeat_byte(0xaa), Address::repeat_byte(0xbb), U256::one()),
+			protocol_actions::compound_v3::supply(Address::repeat_byte(0xbb), Address::repeat_byte(0xaa), U256::one()),
+		];
+		let batch = manager.build_multisend(calls).unwrap();
+		// The batch's value is zero, so no balance is needed
+		mount_answers(&server, &[("eth_getCode", serde_json::json!("0x6080"))]).await;
+		zero().mount(&server).await;
+		assert_eq!(manager.simulate_transaction(&batch).await.unwrap(), U256::from(144_000));
+
+		server.reset().await;
+		mount(simulation(false, vec![0x08, 0xc3, 0x79, 0xa0])).mount(&server).await;
+		mount_answers(&server, &[("eth_getCode", serde_json::json!("0x6080"))]).await;
+		zero().mount(&server).await;
+		let error = manager.simulate_transaction(&batch).await.unwrap_err();
+		match error.downcast_ref() {
+			Some(SafeError::GasEstimationFailed(reason)) => assert!(reason.contains("08c379a0"), "{}", reason),
+			other => panic!("expected a failed estimate, got {:?}", other),
+		}
 	}
-}
 
-#[cfg(test)]
-mod tests {
-	use super::*;
-	use ethers::providers::Provider;
-	use std::str::FromStr;
+	#[tokio::test]
+	async fn test_safe_contract() {
+		use wiremock::matchers::{body_partial_json, body_string_contains};
+		use wiremock::{Mock, MockServer, ResponseTemplate};
 
-	async fn setup_test_manager() -> Result<SafeManager> {
-		let provider = Provider::<Http>::try_from("http://localhost:8545")
-			.expect("Failed to create provider");
-		let address = Address::from_str("0x0000000000000000000000000000000000000000")
-			.expect("Failed to parse address");
-		SafeManager::new(address, provider)
+		let (safe, account, reverting) = (Address::repeat_byte(0x5a), Address::repeat_byte(0x04), Address::repeat_byte(0x03));
+		let owners = [Address::repeat_byte(0xa1), Address::repeat_byte(0xa2)];
+		let server = MockServer::start().await;
+		let answer = |output: Vec<u8>| {
+			ResponseTemplate::new(200).set_body_json(
+				serde_json::json!({ "jsonrpc": "2.0", "id": 1, "result": format!("0x{}", hex::encode(output)) }),
+			)
+		};
+		let owner_tokens = owners.iter().map(|owner| Token::Address(*owner)).collect();
+		for (selector, output) in [
+			("a0e67e2b", ethers::abi::encode(&[Token::Array(owner_tokens)])),
+			("e75235b8", ethers::abi::encode(&[Token::Uint(2.into())])),
+			("affed0e0", ethers::abi::encode(&[Token::Uint(41.into())])),
+			("ffa1ad74", ethers::abi::encode(&[Token::String("1.3.0".to_string())])),
+		] {
+			Mock::given(body_string_contains(format!("{:x}", safe)))
+				.and(body_string_contains(selector))
+				.respond_with(answer(output))
+				.mount(&server)
+				.await;
+		}
+		Mock::given(body_string_contains(format!("{:x}", reverting)))
+			.respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+				"jsonrpc": "2.0",
+				"id": 1,
+				"error": { "code": 3, "message": "execution reverted" },
+			})))
+			.mount(&server)
+			.await;
+		// An externally owned account: no code, and every call answers nothing
+		Mock::given(body_partial_json(serde_json::json!({ "method": "eth_call" })))
+			.respond_with(answer(Vec::new()))
+			.mount(&server)
+			.await;
+		Mock::given(body_partial_json(serde_json::json!({ "method": "eth_getCode" })))
+			.respond_with(answer(Vec::new()))
+			.mount(&server)
+			.await;
+
+		let manager = SafeManager::new(safe, test_provider(&server.uri())).unwrap();
+		let info = manager.inspect_safe().await.unwrap();
+		assert_eq!(
+			info,
+			SafeInfo { version: "1.3.0".to_string(), owners: owners.to_vec(), threshold: 2.into(), nonce: 41.into() }
+		);
+		assert_eq!(manager.get_safe_nonce().await.unwrap(), U256::from(41));
+
+		let tx = SafeTransaction {
+			to: Address::repeat_byte(0x22),
+			value: U256::exp10(15),
+			data: vec![],
+			operation: 0,
+			safe_tx_gas: U256::zero(),
+			nonce: None,
+		};
+		for address in [account, reverting] {
+			let mut manager = SafeManager::new(address, test_provider(&server.uri())).unwrap();
+			match manager.get_threshold().await.unwrap_err().downcast::<SafeError>() {
+				Ok(SafeError::NotASafe(not_a_safe)) => assert_eq!(not_a_safe, address),
+				other => panic!("expected NotASafe, got {:?}", other),
+			}
+			manager.set_mode(RunMode::DryRun);
+			let error = manager.execute_transaction(tx.clone()).await.unwrap_err();
+			assert!(matches!(error.downcast_ref(), Some(SafeError::NotASafe(_))), "{:#}", error);
+			assert_eq!(ErrorCategory::of(&error), ErrorCategory::Validation);
+		}
 	}
 
 	#[tokio::test]
-	async fn test_balance_threshold() {
-		let mut manager = setup_test_manager().await.unwrap();
-		manager.set_min_balance(U256::from(1_000_000_000_000_000_u64)); // 0.001 ETH
-		
-		let result = manager.check_balance_threshold().await;
-		assert!(result.is_err());
-		if let Err(e) = result {
-			let safe_error = e.downcast::<SafeError>();
-			assert!(safe_error.is_ok());
+	async fn test_burn_rate() {
+		use crate::util::MockClock;
+		use std::time::Duration;
+		use wiremock::matchers::method;
+		use wiremock::{Mock, MockServer, ResponseTemplate};
+
+		let node = MockServer::start().await;
+		// 10, 9 and 8 ETH, then a top-up to 20
+		for eth in [10_u64, 9, 8, 20] {
+			Mock::given(method("POST"))
+				.respond_with(ResponseTemplate::new(200).set_body_json(
+					serde_json::json!({ "jsonrpc": "2.0", "id": 1, "result": format!("{:#x}", U256::exp10(18) * eth) }),
+				))
+				.up_to_n_times(1)
+				.mount(&node)
+				.await;
+		}
+		let clock = MockClock::new(1_700_000_000);
+		let mut manager = SafeManager::with_clock(Address::zero(), test_provider(&node.uri()), clock.shared()).unwrap();
+		manager.set_min_balance(U256::exp10(18)); // 1 ETH
+		let hour = Duration::from_secs(3600);
+
+		manager.get_balance().await.unwrap();
+		assert_eq!(manager.estimated_depletion(), None);
+		for _ in 0..2 {
+			clock.advance(hour);
+			manager.get_balance().await.unwrap();
 		}
+		// 1 ETH an hour, 7 ETH above the minimum
+		let left = manager.estimated_depletion().unwrap();
+		assert!((left.as_secs_f64() / 3600.0 - 7.0).abs() < 1e-6, "{:?}", left);
+		assert_eq!(manager.balance_history().len(), 3);
+
+		clock.advance(hour);
+		manager.get_balance().await.unwrap();
+		assert_eq!(
+			manager.balance_history(),
+			[BalanceSample { timestamp: 1_700_000_000 + 3 * 3600, balance: U256::exp10(18) * 20 }]
+		);
+		assert_eq!(manager.estimated_depletion(), None);
+
+		// Below the minimum already, and capped however long it runs
+		for i in 0..(BALANCE_HISTORY_CAPACITY as u64 + 10) {
+			manager.record_balance(BalanceSample { timestamp: 1_800_000_000 + i, balance: U256::exp10(17) });
+		}
+		assert_eq!(manager.balance_history().len(), BALANCE_HISTORY_CAPACITY);
+		assert_eq!(manager.balance_history()[0].timestamp, 1_800_000_010);
+		assert_eq!(manager.estimated_depletion(), None);
+		manager.record_balance(BalanceSample { timestamp: 1_900_000_000, balance: U256::exp10(16) });
+		assert_eq!(manager.estimated_depletion(), Some(Duration::ZERO));
 	}
 
 	#[tokio::test]
-	async fn test_critical_balance() {
-		let mut manager = setup_test_manager().await.unwrap();
-		manager.set_min_balance(U256::from(1_000_000_000_000_000_u64)); // 0.001 ETH
-		
-		let result = manager.check_balance_threshold().await;
-		assert!(result.is_err());
-		if let Err(e) = result {
-			let safe_error = e.downcast::<SafeError>();
-			assert!(safe_error.is_ok());
+	async fn test_multiple_accounts() {
+		use wiremock::matchers::{body_partial_json, method};
+		use wiremock::{Mock, MockServer, ResponseTemplate};
+
+		let (good, bad) = (Address::repeat_byte(0x01), Address::repeat_byte(0x02));
+		let node = MockServer::start().await;
+		Mock::given(method("POST"))
+			.and(body_partial_json(serde_json::json!({ "params": [bad] })))
+			.respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+				"jsonrpc": "2.0", "id": 1, "error": { "code": -32602, "message": "invalid params" }
+			})))
+			.mount(&node)
+			.await;
+		Mock::given(method("POST"))
+			.and(body_partial_json(serde_json::json!({ "params": [good] })))
+			.respond_with(ResponseTemplate::new(200).set_body_json(
+				serde_json::json!({ "jsonrpc": "2.0", "id": 1, "result": format!("{:#x}", U256::exp10(17)) }),
+			))
+			.mount(&node)
+			.await;
+		Mock::given(method("POST"))
+			.respond_with(ResponseTemplate::new(200).set_body_json(
+				serde_json::json!({ "jsonrpc": "2.0", "id": 1, "result": format!("{:#x}", U256::exp10(18)) }),
+			))
+			.mount(&node)
+			.await;
+		let mut manager = SafeManager::new(Address::zero(), test_provider(&node.uri())).unwrap();
+		manager.set_min_balance(U256::exp10(17) * 5); // 0.5 ETH
+
+		// The primary account is always there and cannot be added twice
+		for address in [Address::zero(), bad, good, good] {
+			manager.add_account(address);
 		}
+		assert_eq!(manager.accounts(), [Address::zero(), good, bad]);
+		assert_eq!(manager.account_min_balance(good), Some(U256::exp10(17) * 5));
+		manager.set_account_min_balance(bad, U256::exp10(18));
+		assert_eq!(manager.account_min_balance(bad), Some(U256::exp10(18)));
+
+		let statuses = manager.check_balance_thresholds().await;
+		assert_eq!(statuses.len(), 3);
+		assert_eq!(statuses[&Address::zero()].as_ref().unwrap(), &BalanceStatus::Healthy);
+		assert_eq!(statuses[&good].as_ref().unwrap(), &BalanceStatus::Critical);
+		assert!(statuses[&bad].is_err());
+
+		let balances = manager.account_balances().await;
+		assert_eq!(balances.keys().copied().collect::<Vec<_>>(), [good, bad]);
+		assert_eq!(balances[&good].balance, Some(U256::exp10(17)));
+		assert_eq!(balances[&bad].status, BalanceStatus::Unknown);
+
+		assert!(manager.remove_account(bad));
+		assert!(!manager.remove_account(bad));
+		assert!(!manager.remove_account(Address::zero()));
+		assert_eq!(manager.accounts(), [Address::zero(), good]);
 	}
 
 	#[tokio::test]
-	async fn test_transaction_validation() {
-		let manager = setup_test_manager().await.unwrap();
-		let invalid_tx = SafeTransaction {
-			to: Address::zero(),
-			value: U256::from(1_000_000_000_000_000_000_u64), // 1 ETH
+	async fn test_chain_id_is_checked() {
+		use wiremock::matchers::body_partial_json;
+		use wiremock::{Mock, MockServer, ResponseTemplate};
+
+		// An Arbitrum node behind what should be the mainnet Safe's endpoint
+		let node = MockServer::start().await;
+		Mock::given(body_partial_json(serde_json::json!({ "method": "eth_chainId" })))
+			.respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({ "jsonrpc": "2.0", "id": 1, "result": "0xa4b1" })))
+			.mount(&node)
+			.await;
+		mount_answers(&node, &[("eth_getCode", serde_json::json!("0x6080"))]).await;
+		let mut manager = SafeManager::new(Address::repeat_byte(0x11), test_provider(&node.uri())).unwrap();
+		let error = manager.init().await.unwrap_err();
+		assert!(
+			matches!(error.downcast_ref(), Some(SafeError::ChainIdMismatch { expected: 1, actual: 42161 })),
+			"{:#}",
+			error
+		);
+		assert_eq!(ErrorCategory::of(&error), ErrorCategory::Validation);
+		assert_eq!(manager.chain_id(), None);
+
+		manager.set_expected_chain_id(42161);
+		assert_eq!(manager.init().await.unwrap(), 42161);
+		assert_eq!(manager.chain_id(), Some(42161));
+		// Later lookups use the chain id found
+		let requests = node.received_requests().await.unwrap().len();
+		let tx = SafeTransaction {
+			to: Address::repeat_byte(0x22),
+			value: U256::zero(),
 			data: vec![],
 			operation: 0,
 			safe_tx_gas: U256::zero(),
-			nonce: None,
+			nonce: Some(3.into()),
 		};
+		assert_eq!(
+			manager.safe_tx_hash(&tx).await.unwrap(),
+			tx.safe_tx_hash(42161.into(), manager.get_address(), 3.into())
+		);
+		assert_eq!(node.received_requests().await.unwrap().len(), requests);
+	}
 
-		let result = manager.simulate_transaction(&invalid_tx).await;
-		assert!(result.is_err());
+	#[tokio::test]
+	async fn test_fails_over_to_the_next_endpoint() {
+		use crate::rpc::FailoverClient;
+
+		// Nothing listens on the primary's port any more
+		let dead = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+		let primary = format!("http://{}", dead.local_addr().unwrap());
+		drop(dead);
+		let (_, backup) = setup_test_manager(1_000).await;
+		let (provider, client) = FailoverClient::new(&[primary, backup.uri()], Duration::from_secs(2), Duration::from_secs(30))
+			.unwrap()
+			.into_provider();
+		let manager = SafeManager::new(Address::repeat_byte(0x11), provider).unwrap();
+
+		assert_eq!(manager.get_balance().await.unwrap(), U256::from(1_000));
+		let health = client.health();
+		assert!(!health.endpoints[0].healthy);
+		assert!(health.endpoints[1].healthy);
+		assert_eq!(health.last_served_by.as_deref(), Some(health.endpoints[1].endpoint.as_str()));
+		// The dead endpoint is skipped until a probe finds it back
+		assert_eq!(manager.get_balance().await.unwrap(), U256::from(1_000));
+		assert_eq!(client.health().endpoints[0].errors, 1);
+		assert_eq!(client.health().endpoints[1].requests, 2);
 	}
 
 	#[tokio::test]
-	async fn test_get_address() {
-		let manager = setup_test_manager().await.unwrap();
-		let addr = manager.get_address();
+	async fn test_historical_balances() {
+		use crate::util::MockClock;
+		use wiremock::matchers::body_partial_json;
+		use wiremock::{Mock, MockServer, Request, ResponseTemplate};
+
+		// Blocks 0 to 100, 20 minutes apart; only blocks from 20 on still have their state
+		const GENESIS: u64 = 1_700_000_000;
+		let node = MockServer::start().await;
+		let answer = |result: serde_json::Value| {
+			ResponseTemplate::new(200).set_body_json(serde_json::json!({ "jsonrpc": "2.0", "id": 1, "result": result }))
+		};
+		let block_param = |request: &Request, index: usize| {
+			let call: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+			u64::from_str_radix(call["params"][index].as_str().unwrap().trim_start_matches("0x"), 16).unwrap()
+		};
+		Mock::given(body_partial_json(serde_json::json!({ "method": "eth_blockNumber" })))
+			.respond_with(answer(serde_json::json!("0x64")))
+			.mount(&node)
+			.await;
+		Mock::given(body_partial_json(serde_json::json!({ "method": "eth_getBlockByNumber" })))
+			.respond_with(move |request: &Request| {
+				let number = block_param(request, 0);
+				answer(serde_json::json!({
+					"hash": H256::from_low_u64_be(number + 1),
+					"parentHash": H256::from_low_u64_be(number),
+					"sha3Uncles": H256::zero(),
+					"miner": Address::zero(),
+					"stateRoot": H256::zero(),
+					"transactionsRoot": H256::zero(),
+					"receiptsRoot": H256::zero(),
+					"number": format!("{:#x}", number),
+					"gasUsed": "0x0",
+					"gasLimit": "0x1c9c380",
+					"extraData": "0x",
+					"logsBloom": format!("0x{}", "00".repeat(256)),
+					"timestamp": format!("{:#x}", GENESIS + number * 1200),
+					"difficulty": "0x0",
+					"uncles": [],
+					"transactions": [],
+				}))
+			})
+			.mount(&node)
+			.await;
+		Mock::given(body_partial_json(serde_json::json!({ "method": "eth_getBalance" })))
+			.respond_with(move |request: &Request| match block_param(request, 1) {
+				number if number >= 20 => answer(serde_json::json!(format!("{:#x}", U256::exp10(15) * number))),
+				number => ResponseTemplate::new(200).set_body_json(serde_json::json!({
+					"jsonrpc": "2.0",
+					"id": 1,
+					"error": { "code": -32000, "message": format!("missing trie node {:?} (path ) state {} is not available", H256::zero(), number) },
+				})),
+			})
+			.mount(&node)
+			.await;
+
+		let clock = MockClock::new(GENESIS + 100 * 1200 + 100);
+		let manager = SafeManager::with_clock(Address::repeat_byte(0x11), test_provider(&node.uri()), clock.shared()).unwrap();
+		assert_eq!(manager.block_at_timestamp(GENESIS + 37 * 1200 + 5).await.unwrap(), 37);
+		assert_eq!(manager.block_at_timestamp(GENESIS + 40 * 1200).await.unwrap(), 40);
+		assert_eq!(manager.block_at_timestamp(GENESIS + 40 * 1200 - 1).await.unwrap(), 39);
+		assert_eq!(manager.block_at_timestamp(GENESIS + 10_000_000).await.unwrap(), 100);
+		let error = manager.block_at_timestamp(GENESIS - 1).await.unwrap_err();
+		assert!(matches!(error.downcast_ref(), Some(SafeError::HistoricalDataUnavailable { .. })), "{:#}", error);
+		assert_eq!(manager.get_balance_at(BlockNumber::Number(64.into()).into()).await.unwrap(), U256::exp10(15) * 64);
+
+		// A day ago is block 28, then kept for the hour
+		assert_eq!(manager.balance_day_ago().await, Some(U256::exp10(15) * 28));
+		let requests = node.received_requests().await.unwrap().len();
+		clock.advance(Duration::from_secs(1800));
+		assert_eq!(manager.balance_day_ago().await, Some(U256::exp10(15) * 28));
+		assert_eq!(node.received_requests().await.unwrap().len(), requests);
+
+		// Pruned state is told apart, and not asked for again
+		let clock = MockClock::new(GENESIS + 10 * 1200 + 86_400);
+		let manager = SafeManager::with_clock(Address::repeat_byte(0x11), test_provider(&node.uri()), clock.shared()).unwrap();
+		let error = manager.get_balance_at(BlockNumber::Number(10.into()).into()).await.unwrap_err();
+		assert!(matches!(error.downcast_ref(), Some(SafeError::HistoricalDataUnavailable { .. })), "{:#}", error);
+		assert_eq!(ErrorCategory::of(&error), ErrorCategory::RpcTransport);
+		let requests = node.received_requests().await.unwrap().len();
+		assert_eq!(manager.balance_day_ago().await, None);
+		assert_eq!(node.received_requests().await.unwrap().len(), requests);
+	}
+
+	#[tokio::test]
+	async fn test_balance_increases_are_published() {
+		let (manager, _node) = setup_test_manager(0).await;
+		let mut increases = manager.subscribe_increases();
+		let min = U256::exp10(15);
+		let balances = |ethereum: u64, arbitrum: Option<u64>| {
+			MultiChainBalances::from([
+				("Ethereum".to_string(), ChainBalance::observed(U256::from(ethereum), min)),
+				(
+					"Arbitrum".to_string(),
+					arbitrum.map_or_else(|| ChainBalance::unknown(min, "timeout"), |b| ChainBalance::observed(U256::from(b), min)),
+				),
+			])
+		};
+
+		manager.publish_increases(&MultiChainBalances::new(), &balances(5, Some(5)));
+		manager.publish_increases(&balances(5, None), &balances(4, Some(9)));
+		assert!(increases.try_recv().is_err());
+
+		manager.publish_increases(&balances(5, Some(5)), &balances(7, Some(3)));
 		assert_eq!(
-			addr,
-			Address::from_str("0x0000000000000000000000000000000000000000").unwrap()
+			increases.try_recv().unwrap(),
+			BalanceIncrease { chain: "Ethereum".to_string(), previous: U256::from(5), current: U256::from(7) }
 		);
+		assert!(increases.try_recv().is_err());
 	}
 
 	#[tokio::test]
-	async fn test_set_min_balance() {
-		let mut manager = setup_test_manager().await.unwrap();
-		let new_min = U256::from(3_000_000_000_000_000_u64); // 0.003 ETH
-		manager.set_min_balance(new_min);
-		
-		assert_eq!(manager.critical_balance, new_min / 2);
-		assert_eq!(manager.min_balance, new_min);
+	async fn test_top_up() {
+		use crate::util::MockClock;
+		use ethers::types::NameOrAddress;
+		use ethers::utils::rlp::Rlp;
+		use wiremock::MockServer;
+
+		let eth = |eth: f64| ethers::utils::parse_ether(eth).unwrap();
+		// The funding wallet's node, where the transfer is still unmined
+		let node = |funds: U256| async move {
+			mock_node(&[
+				("eth_getBalance", serde_json::json!(format!("{:#x}", funds))),
+				("eth_estimateGas", serde_json::json!("0x5208")),
+				("eth_gasPrice", serde_json::json!("0x3b9aca00")),
+				("eth_chainId", serde_json::json!("0x1")),
+				("eth_getCode", serde_json::json!("0x6080")),
+				("eth_getTransactionCount", serde_json::json!("0x3")),
+				("eth_sendRawTransaction", serde_json::json!(format!("{:?}", H256::repeat_byte(0x42)))),
+				("eth_getTransactionReceipt", serde_json::Value::Null),
+			])
+			.await
+		};
+		async fn sent(node: &MockServer) -> Vec<(TypedTransaction, ethers::types::Signature)> {
+			let requests = node.received_requests().await.unwrap();
+			requests
+				.iter()
+				.map(|request| serde_json::from_slice::<serde_json::Value>(&request.body).unwrap())
+				.filter(|call| call["method"] == "eth_sendRawTransaction")
+				.map(|call| {
+					let raw = call["params"][0].as_str().unwrap().parse::<Bytes>().unwrap();
+					TypedTransaction::decode_signed(&Rlp::new(&raw)).unwrap()
+				})
+				.collect()
+		}
+		let funder = LocalWallet::from_str("4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318").unwrap();
+		let top_up = || TopUp { funder: funder.clone().into(), target: eth(0.01), max_per_day: eth(0.015), reserve: eth(0.01) };
+		let safe = Address::repeat_byte(0x11);
+		// 25,200 gas, buffered, at 1 gwei
+		let fee = U256::from(25_200) * U256::exp10(9);
+
+		let server = node(eth(1.0)).await;
+		let clock = MockClock::new(1_700_000_000);
+		let mut manager = SafeManager::with_clock(safe, test_provider(&server.uri()), clock.shared()).unwrap();
+		assert_eq!(manager.top_up(U256::zero()).await.unwrap(), None);
+		manager.set_top_up(Some(top_up()));
+		assert!(manager.tops_up());
+		assert_eq!(manager.top_up(eth(0.01)).await.unwrap(), None);
+
+		// Nothing is sent, or even looked up, outside live mode
+		for mode in [RunMode::DryRun, RunMode::Observe] {
+			manager.set_mode(mode);
+			assert_eq!(manager.top_up(eth(0.0001)).await.unwrap(), None);
+			assert!(server.received_requests().await.unwrap().is_empty(), "{}", mode);
+		}
+
+		// Back up to the target, from the funding wallet to the Safe
+		manager.set_mode(RunMode::Live);
+		assert_eq!(manager.top_up(eth(0.0001)).await.unwrap(), Some(H256::repeat_byte(0x42)));
+		let transfers = sent(&server).await;
+		assert_eq!(transfers.len(), 1);
+		let (transfer, signature) = &transfers[0];
+		assert_eq!(signature.recover(transfer.sighash()).unwrap(), funder.address());
+		assert_eq!(transfer.to(), Some(&NameOrAddress::Address(safe)));
+		assert_eq!(transfer.value(), Some(&eth(0.0099)));
+		assert_eq!((transfer.nonce(), transfer.chain_id()), (Some(&3.into()), Some(1.into())));
+		assert_eq!(manager.topped_up_last_day(), eth(0.0099));
+
+		// Not again while that one is unmined
+		assert_eq!(manager.top_up(eth(0.0001)).await.unwrap(), None);
+		assert_eq!(sent(&server).await.len(), 1);
+
+		// Once it counts as dropped, only what is left of the daily cap is sent
+		clock.advance(Duration::from_secs(301));
+		assert!(manager.top_up(eth(0.0001)).await.unwrap().is_some());
+		assert_eq!(sent(&server).await[1].0.value(), Some(&eth(0.0051)));
+		clock.advance(Duration::from_secs(301));
+		assert_eq!(manager.top_up(eth(0.0001)).await.unwrap(), None);
+		assert_eq!(sent(&server).await.len(), 2);
+		// A day later the cap is available again
+		clock.advance(Duration::from_secs(SPENDING_WINDOW_SECS));
+		assert_eq!(manager.topped_up_last_day(), U256::zero());
+
+		// The funding wallet keeps its reserve and the fee
+		let server = node(eth(0.0101)).await;
+		let mut manager = SafeManager::with_clock(safe, test_provider(&server.uri()), clock.shared()).unwrap();
+		manager.set_top_up(Some(top_up()));
+		assert!(manager.top_up(eth(0.0001)).await.unwrap().is_some());
+		assert_eq!(sent(&server).await[0].0.value(), Some(&(eth(0.0001) - fee)));
+
+		let server = node(eth(0.01)).await;
+		let mut manager = SafeManager::with_clock(safe, test_provider(&server.uri()), clock.shared()).unwrap();
+		manager.set_top_up(Some(top_up()));
+		assert_eq!(manager.top_up(eth(0.0001)).await.unwrap(), None);
+		assert!(sent(&server).await.is_empty());
+
+		// Not while the kill switch is on
+		let mut manager = SafeManager::with_clock(safe, test_provider(&server.uri()), clock.shared()).unwrap();
+		manager.set_top_up(Some(top_up()));
+		let kill_switch = KillSwitch::default();
+		kill_switch.pause().unwrap();
+		manager.set_kill_switch(kill_switch);
+		assert!(manager.top_up(eth(0.0001)).await.is_err());
 	}
-}
 
+	#[tokio::test]
+	async fn test_watch_transfers() {
+		use crate::storage::MemoryStore;
+		use std::sync::atomic::AtomicU64;
+		use wiremock::matchers::body_partial_json;
+		use wiremock::{Mock, MockServer, Request, ResponseTemplate};
+
+		let safe = Address::repeat_byte(0x11);
+		let (token, other) = (Address::repeat_byte(0x70), Address::repeat_byte(0x22));
+		let topic = H256::from(keccak256("Transfer(address,address,uint256)"));
+		let log = |block: u64, from: Address, to: Address, amount: u64, topics: usize| {
+			let mut all = vec![topic, from.into(), to.into(), H256::from_low_u64_be(7)];
+			all.truncate(topics);
+			serde_json::json!({
+				"address": token,
+				"topics": all,
+				"data": format!("0x{:064x}", amount),
+				"blockNumber": format!("{:#x}", block),
+				"transactionHash": H256::from_low_u64_be(block),
+				"logIndex": "0x0",
+			})
+		};
+		let logs = [
+			log(105, other, safe, 1_000, 3),
+			log(110, safe, safe, 5, 3),
+			// An NFT of the same event signature
+			log(112, other, safe, 0, 4),
+			log(125, safe, other, 400, 3),
+			log(126, other, Address::repeat_byte(0x33), 9, 3),
+		];
+
+		let head = Arc::new(AtomicU64::new(100));
+		// The block the node has no header for, and the one with too many logs to answer
+		let (missing, crowded) = (Arc::new(AtomicU64::new(141)), Arc::new(AtomicU64::new(0)));
+		let node = MockServer::start().await;
+		let answer = |result: serde_json::Value| {
+			ResponseTemplate::new(200).set_body_json(serde_json::json!({ "jsonrpc": "2.0", "id": 1, "result": result }))
+		};
+		let refuse = |code: i64, message: &str| {
+			ResponseTemplate::new(200).set_body_json(serde_json::json!({
+				"jsonrpc": "2.0", "id": 1, "error": { "code": code, "message": message },
+			}))
+		};
+		let number = |value: &serde_json::Value| u64::from_str_radix(value.as_str().unwrap().trim_start_matches("0x"), 16).unwrap();
+		let head_block = head.clone();
+		Mock::given(body_partial_json(serde_json::json!({ "method": "eth_blockNumber" })))
+			.respond_with(move |_: &Request| answer(serde_json::json!(format!("{:#x}", head_block.load(Ordering::SeqCst)))))
+			.mount(&node)
+			.await;
+		Mock::given(body_partial_json(serde_json::json!({ "method": "eth_getBalance" })))
+			.respond_with(move |request: &Request| {
+				let call: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+				let balance = match number(&call["params"][1]) {
+					..=129 => U256::exp10(18),
+					130..=139 => U256::exp10(18) * 3 / 2,
+					_ => U256::exp10(17) * 12,
+				};
+				answer(serde_json::json!(format!("{:#x}", balance)))
+			})
+			.mount(&node)
+			.await;
+		let (missing_block, crowded_block) = (missing.clone(), crowded.clone());
+		Mock::given(body_partial_json(serde_json::json!({ "method": "eth_getLogs" })))
+			.respond_with(move |request: &Request| {
+				let call: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+				let filter = &call["params"][0];
+				let (from, to) = (number(&filter["fromBlock"]), number(&filter["toBlock"]));
+				if from == missing_block.load(Ordering::SeqCst) {
+					return refuse(-32000, "header not found");
+				}
+				if (from..=to).contains(&crowded_block.load(Ordering::SeqCst)) {
+					return refuse(-32000, "log response size exceeded");
+				}
+				if to - from >= 10 {
+					return refuse(-32602, "query exceeds max block range 10");
+				}
+				let topics = filter["topics"].as_array().unwrap();
+				let matching: Vec<_> = logs
+					.iter()
+					.filter(|log| (from..=to).contains(&number(&log["blockNumber"])))
+					.filter(|log| {
+						topics.iter().enumerate().all(|(i, wanted)| wanted.is_null() || log["topics"].get(i) == Some(wanted))
+					})
+					.cloned()
+					.collect();
+				answer(serde_json::json!(matching))
+			})
+			.mount(&node)
+			.await;
+
+		let store = Arc::new(MemoryStore::new());
+		let mut manager = SafeManager::new(safe, test_provider(&node.uri())).unwrap();
+		manager.set_store(store.clone());
+		manager.set_log_block_range(50);
+		// The first scan only notes where to start
+		assert!(manager.watch_transfers().await.unwrap().is_empty());
+		assert_eq!(store.block_cursor(&format!("transfers:{:?}", safe)).await.unwrap(), Some(100));
+
+		// Blocks 101 to 130, asked for 10 at a time once the node refuses 30
+		head.store(130, Ordering::SeqCst);
+		let transfers = manager.watch_transfers().await.unwrap();
+		let transfer = |block: u64, from: Address, to: Address, amount: u64| TransferEvent {
+			token: Some(token),
+			from: Some(from),
+			to: Some(to),
+			amount: amount.into(),
+			tx_hash: Some(H256::from_low_u64_be(block)),
+			block,
+		};
+		assert_eq!(
+			transfers,
+			vec![
+				transfer(105, other, safe, 1_000),
+				transfer(110, safe, safe, 5),
+				transfer(125, safe, other, 400),
+				TransferEvent {
+					token: None,
+					from: None,
+					to: Some(safe),
+					amount: U256::exp10(17) * 5,
+					tx_hash: None,
+					block: 130,
+				},
+			]
+		);
+		assert_eq!(transfers[3].describe(), format!("0.500000000000000000 ETH into {:?} by block 130", safe));
+		assert!(transfers[0].describe().starts_with("1000 units of token"), "{}", transfers[0].describe());
+		assert!(manager.watch_transfers().await.unwrap().is_empty());
+
+		// Restarted, it carries on after block 130
+		head.store(140, Ordering::SeqCst);
+		let mut manager = SafeManager::new(safe, test_provider(&node.uri())).unwrap();
+		manager.set_store(store.clone());
+		let transfers = manager.watch_transfers().await.unwrap();
+		assert_eq!(transfers.len(), 1);
+		assert_eq!((transfers[0].from, transfers[0].to), (Some(safe), None));
+		assert_eq!(transfers[0].amount, U256::exp10(17) * 3);
+
+		// A failed scan is tried again over the same blocks
+		head.store(150, Ordering::SeqCst);
+		let error = manager.watch_transfers().await.unwrap_err();
+		assert!(error.to_string().contains("header not found"), "{:#}", error);
+		assert_eq!(store.block_cursor(&format!("transfers:{:?}", safe)).await.unwrap(), Some(140));
+
+		// The last block is refused on its own, so the scan fails there
+		missing.store(0, Ordering::SeqCst);
+		crowded.store(150, Ordering::SeqCst);
+		let error = manager.watch_transfers().await.unwrap_err();
+		assert!(error.to_string().contains("eth_getLogs for blocks 150..=150"), "{:#}", error);
+		assert_eq!(store.block_cursor(&format!("transfers:{:?}", safe)).await.unwrap(), Some(140));
+
+		// A rate limit is not taken for a range limit
+		let limited = ProviderError::JsonRpcClientError(Box::new(crate::rpc::TransportError::Http(
+			ethers::providers::HttpClientError::JsonRpcError(ethers::providers::JsonRpcError {
+				code: -32005,
+				message: "daily request count exceeded, request rate limited".to_string(),
+				data: None,
+			}),
+		)));
+		assert!(!is_log_range_limit(&limited));
+	}
+
+	#[test]
+	fn test_replacement_fees() {
+		let legacy = TxFees::Legacy { gas_price: U256::from(1_000_000_000) };
+		assert_eq!(legacy.bumped(25), TxFees::Legacy { gas_price: U256::from(1_250_000_000) });
+		// Below the least replacement increment, nodes would refuse it
+		assert_eq!(legacy.bumped(0), TxFees::Legacy { gas_price: U256::from(1_100_000_000) });
+		assert_eq!(legacy.bumped(5), legacy.bumped(MIN_REPLACEMENT_BUMP_PERCENT));
+		assert_eq!(legacy.bumped(100).max_gas_price(), U256::from(2_000_000_000));
+
+		// Rounded up, so the increase is never short of the percentage
+		let fees = TxFees::Eip1559 { max_fee_per_gas: U256::from(1_001), max_priority_fee_per_gas: U256::from(7) };
+		assert_eq!(
+			fees.bumped(10),
+			TxFees::Eip1559 { max_fee_per_gas: U256::from(1_102), max_priority_fee_per_gas: U256::from(8) }
+		);
+		let zero_tip = TxFees::Eip1559 { max_fee_per_gas: U256::from(100), max_priority_fee_per_gas: U256::zero() };
+		assert_eq!(
+			zero_tip.bumped(10),
+			TxFees::Eip1559 { max_fee_per_gas: U256::from(110), max_priority_fee_per_gas: U256::zero() }
+		);
+		assert_eq!(TxFees::Legacy { gas_price: U256::MAX }.bumped(10).max_gas_price(), U256::MAX / 100 + 1);
+	}
+
+	#[tokio::test]
+	async fn test_speed_up_and_cancel() {
+		use ethers::types::NameOrAddress;
+		use ethers::utils::rlp::Rlp;
+		use wiremock::MockServer;
+
+		let owner = LocalWallet::from_str("4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318").unwrap();
+		let stuck_hash = H256::repeat_byte(0x51);
+		let data = Bytes::from(vec![0x6a, 0x76, 0x12, 0x02]);
+		// The owner's execTransaction, still in the mempool: `mined` gives it a receipt
+		let node = |from: Address, mined: bool| {
+			let data = data.clone();
+			async move {
+				let receipt = if mined {
+					receipt(stuck_hash, 0x10, 1, serde_json::json!([]))
+				} else {
+					serde_json::Value::Null
+				};
+				mock_node(&[
+					("eth_getTransactionByHash", serde_json::json!({
+						"hash": stuck_hash,
+						"nonce": "0x7",
+						"blockHash": null,
+						"blockNumber": null,
+						"transactionIndex": null,
+						"from": from,
+						"to": Address::repeat_byte(0x11),
+						"value": "0x0",
+						"gas": "0x30d40",
+						"input": data,
+						"type": "0x2",
+						"maxFeePerGas": "0x77359400",
+						"maxPriorityFeePerGas": "0x3b9aca00",
+						"chainId": "0x1",
+						"v": "0x0",
+						"r": "0x1",
+						"s": "0x1",
+					})),
+					("eth_getTransactionReceipt", receipt),
+					("eth_chainId", serde_json::json!("0x1")),
+					("eth_sendRawTransaction", serde_json::json!(format!("{:?}", H256::repeat_byte(0x42)))),
+				])
+				.await
+			}
+		};
+		async fn sent(node: &MockServer) -> Vec<(TypedTransaction, ethers::types::Signature)> {
+			let requests = node.received_requests().await.unwrap();
+			requests
+				.iter()
+				.map(|request| serde_json::from_slice::<serde_json::Value>(&request.body).unwrap())
+				.filter(|call| call["method"] == "eth_sendRawTransaction")
+				.map(|call| {
+					let raw = call["params"][0].as_str().unwrap().parse::<Bytes>().unwrap();
+					TypedTransaction::decode_signed(&Rlp::new(&raw)).unwrap()
+				})
+				.collect()
+		}
+		let manager_for = |server: &MockServer| {
+			let mut manager = SafeManager::new(Address::repeat_byte(0x11), test_provider(&server.uri())).unwrap();
+			manager.set_signer(owner.clone());
+			manager
+		};
+
+		// The same payload, gas limit and nonce, with fees 20% higher, whose fee is
+		// counted against the gas budget in place of the stuck one's
+		let server = node(owner.address(), false).await;
+		let mut manager = manager_for(&server);
+		let gwei = |amount: u64| U256::from(amount) * U256::exp10(9);
+		let counted_at = manager.reserve_gas(gwei(100_000), None).unwrap();
+		manager.sent_gas(counted_at, gwei(100_000), Some(stuck_hash));
+		manager.set_daily_gas_budget(Some(gwei(400_000)));
+		let error = manager.speed_up(stuck_hash, 20).await.unwrap_err();
+		assert!(matches!(error.downcast_ref::<SafeError>(), Some(SafeError::GasBudgetExhausted { .. })), "{:#}", error);
+		assert!(sent(&server).await.is_empty());
+		manager.set_daily_gas_budget(Some(gwei(500_000)));
+		assert_eq!(manager.speed_up(stuck_hash, 20).await.unwrap(), H256::repeat_byte(0x42));
+		assert_eq!(manager.gas_spent_today(), gwei(480_000));
+		manager.settle_gas(&serde_json::from_value(receipt(H256::repeat_byte(0x42), 0x10, 1, serde_json::json!([]))).unwrap());
+		assert_eq!(manager.gas_spent_today(), gwei(100_000));
+		let (replacement, signature) = sent(&server).await.remove(0);
+		assert_eq!(signature.recover(replacement.sighash()).unwrap(), owner.address());
+		assert_eq!(replacement.to(), Some(&NameOrAddress::Address(Address::repeat_byte(0x11))));
+		assert_eq!((replacement.data(), replacement.gas()), (Some(&data), Some(&U256::from(200_000))));
+		assert_eq!((replacement.nonce(), replacement.chain_id()), (Some(&7.into()), Some(1.into())));
+		match &replacement {
+			TypedTransaction::Eip1559(request) => {
+				assert_eq!(request.max_fee_per_gas, Some(U256::from(2_400_000_000_u64)));
+				assert_eq!(request.max_priority_fee_per_gas, Some(U256::from(1_200_000_000)));
+			}
+			other => panic!("expected an EIP-1559 transaction, got {:?}", other),
+		}
+
+		// A transfer of nothing to the owner, with the least increase nodes accept
+		manager.cancel(stuck_hash).await.unwrap();
+		let (cancellation, _) = sent(&server).await.remove(1);
+		assert_eq!(cancellation.to(), Some(&NameOrAddress::Address(owner.address())));
+		assert_eq!(cancellation.value(), Some(&U256::zero()));
+		assert_eq!((cancellation.gas(), cancellation.nonce()), (Some(&U256::from(21_000)), Some(&7.into())));
+		assert_eq!(cancellation.gas_price(), Some(U256::from(2_200_000_000_u64)));
+
+		// Nothing is replaced once it was mined, or when someone else sent it
+		let server = node(owner.address(), true).await;
+		let error = manager_for(&server).speed_up(stuck_hash, 20).await.unwrap_err();
+		assert!(matches!(error.downcast_ref::<SafeError>(), Some(SafeError::NotPending(hash)) if *hash == stuck_hash));
+		let server = node(Address::repeat_byte(0xaa), false).await;
+		let error = manager_for(&server).cancel(stuck_hash).await.unwrap_err();
+		assert!(matches!(error.downcast_ref::<SafeError>(), Some(SafeError::SigningFailed(_))), "{:#}", error);
+		assert!(sent(&server).await.is_empty());
 
+		// While the kill switch is on, the stuck transaction can only be cancelled
+		let server = node(owner.address(), false).await;
+		let mut manager = manager_for(&server);
+		let kill_switch = KillSwitch::default();
+		kill_switch.pause().unwrap();
+		manager.set_kill_switch(kill_switch);
+		let error = manager.speed_up(stuck_hash, 20).await.unwrap_err();
+		assert!(matches!(error.downcast_ref(), Some(crate::kill_switch::KillSwitchError::Paused(_))), "{:#}", error);
+		assert!(sent(&server).await.is_empty());
+		manager.cancel(stuck_hash).await.unwrap();
+		let (cancellation, _) = sent(&server).await.remove(0);
+		assert_eq!(cancellation.to(), Some(&NameOrAddress::Address(owner.address())));
 
+		// Nor outside live mode
+		let server = node(owner.address(), false).await;
+		let mut manager = manager_for(&server);
+		manager.set_mode(RunMode::DryRun);
+		let error = manager.speed_up(stuck_hash, 20).await.unwrap_err();
+		assert!(matches!(error.downcast_ref::<SafeError>(), Some(SafeError::BroadcastDisabled(RunMode::DryRun))));
+		assert!(sent(&server).await.is_empty());
+	}
+}
@@ -0,0 +1,239 @@
+//! The owner that signs the Safe's transactions: a private key held in memory, or,
+//! with the `ledger` feature, a Ledger device that asks for each signature.
+
+use anyhow::Result;
+use ethers::core::types::transaction::eip2718::TypedTransaction;
+use ethers::core::types::{Address, Bytes, Signature, H256};
+use ethers::signers::{LocalWallet, Signer};
+use std::fmt;
+
+use super::safe_manager::SafeError;
+
+/// Signs Safe transaction hashes and the owner's own `execTransaction` calls.
+#[derive(Debug)]
+pub enum OwnerSigner {
+	/// A private key from `safe.signer_key`.
+	Key(LocalWallet),
+	/// A Ledger device, signing only once the transaction is confirmed on it.
+	#[cfg(feature = "ledger")]
+	Ledger(ledger::LedgerSigner),
+}
+
+impl OwnerSigner {
+	/// The owner's address; a Ledger is asked for it once it is reachable.
+	pub async fn address(&self) -> Result<Address> {
+		match self {
+			OwnerSigner::Key(wallet) => Ok(wallet.address()),
+			#[cfg(feature = "ledger")]
+			OwnerSigner::Ledger(ledger) => ledger.address().await,
+		}
+	}
+
+	/// A signature of the `safeTxHash` as the Safe checks it, 65 bytes: with a key the
+	/// hash itself is signed (`v` of 27 or 28); a Ledger can only sign it as an
+	/// `eth_sign` message, which the Safe tells apart by a `v` of 31 or 32.
+	pub async fn sign_safe_tx_hash(&self, hash: H256) -> Result<Bytes> {
+		let signature = match self {
+			OwnerSigner::Key(wallet) => wallet.sign_hash(hash).map_err(|e| SafeError::SigningFailed(e.to_string()))?,
+			#[cfg(feature = "ledger")]
+			OwnerSigner::Ledger(ledger) => {
+				let mut signature = ledger.sign_message(hash).await?;
+				signature.v += 4;
+				signature
+			}
+		};
+		Ok(signature.to_vec().into())
+	}
+
+	/// Signs `tx`, which carries its chain id, for `eth_sendRawTransaction`.
+	pub async fn sign_transaction(&self, tx: &TypedTransaction) -> Result<Signature> {
+		match self {
+			OwnerSigner::Key(wallet) => {
+				wallet.sign_transaction_sync(tx).map_err(|e| SafeError::SigningFailed(e.to_string()).into())
+			}
+			#[cfg(feature = "ledger")]
+			OwnerSigner::Ledger(ledger) => ledger.sign_transaction(tx).await,
+		}
+	}
+}
+
+impl From<LocalWallet> for OwnerSigner {
+	fn from(wallet: LocalWallet) -> Self {
+		OwnerSigner::Key(wallet)
+	}
+}
+
+impl fmt::Display for OwnerSigner {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			OwnerSigner::Key(wallet) => write!(f, "key of {:?}", wallet.address()),
+			#[cfg(feature = "ledger")]
+			OwnerSigner::Ledger(ledger) => write!(f, "Ledger at {}", ledger.path()),
+		}
+	}
+}
+
+#[cfg(feature = "ledger")]
+pub use ledger::LedgerSigner;
+
+#[cfg(feature = "ledger")]
+mod ledger {
+	use anyhow::Result;
+	use ethers::core::types::transaction::eip2718::TypedTransaction;
+	use ethers::core::types::{Address, Signature, H256};
+	use ethers::signers::{HDPath, Ledger, LedgerError, Signer};
+	use log::{info, warn};
+	use std::sync::Arc;
+	use std::time::Duration;
+	use tokio::sync::Mutex;
+
+	use crate::agents::safe_manager::SafeError;
+
+	/// A Ledger reached over USB HID, connected on first use and again after any
+	/// failure, so a device plugged back in or unlocked is picked up by the next
+	/// attempt.
+	///
+	/// The device library blocks its thread while waiting on the device, so every
+	/// exchange runs on a blocking thread and is given up after the timeout: a locked
+	/// device or an unanswered confirmation fails with
+	/// [`SafeError::SignerUnavailable`] instead of stalling the agent.
+	#[derive(Debug)]
+	pub struct LedgerSigner {
+		path: String,
+		timeout: Duration,
+		device: Mutex<Option<Connected>>,
+	}
+
+	#[derive(Debug)]
+	struct Connected {
+		ledger: Arc<Ledger>,
+		chain_id: u64,
+		address: Address,
+	}
+
+	impl LedgerSigner {
+		/// A signer for the account at derivation `path`, e.g. `m/44'/60'/0'/0/0`,
+		/// waiting up to `timeout` for the device, confirmations included.
+		pub fn new(path: impl Into<String>, timeout: Duration) -> Self {
+			Self { path: path.into(), timeout, device: Mutex::new(None) }
+		}
+
+		pub fn path(&self) -> &str {
+			&self.path
+		}
+
+		pub async fn address(&self) -> Result<Address> {
+			let mut device = self.device.lock().await;
+			Ok(self.connected(&mut device, None).await?.address)
+		}
+
+		pub async fn sign_message(&self, hash: H256) -> Result<Signature> {
+			let mut device = self.device.lock().await;
+			let ledger = self.connected(&mut device, None).await?.ledger.clone();
+			let signed = self
+				.on_device(&mut device, "signing the Safe transaction", move || {
+					futures::executor::block_on(ledger.sign_message(hash.as_bytes()))
+				})
+				.await?;
+			Ok(signed)
+		}
+
+		pub async fn sign_transaction(&self, tx: &TypedTransaction) -> Result<Signature> {
+			let chain_id = tx.chain_id().map(|id| id.as_u64());
+			let mut device = self.device.lock().await;
+			let ledger = self.connected(&mut device, chain_id).await?.ledger.clone();
+			let tx = tx.clone();
+			self.on_device(&mut device, "signing execTransaction", move || {
+				futures::executor::block_on(ledger.sign_tx(&tx))
+			})
+			.await
+		}
+
+		/// The open device, connecting first when there is none or it was opened for
+		/// another chain than `chain_id`.
+		async fn connected<'a>(
+			&self,
+			device: &'a mut Option<Connected>,
+			chain_id: Option<u64>,
+		) -> Result<&'a Connected> {
+			let stale = match (&*device, chain_id) {
+				(None, _) => true,
+				(Some(connected), Some(chain_id)) => connected.chain_id != chain_id,
+				(Some(_), None) => false,
+			};
+			if stale {
+				*device = None;
+				let path = self.path.clone();
+				let chain_id = chain_id.unwrap_or(1);
+				let ledger = self
+					.on_device(device, "connecting", move || {
+						futures::executor::block_on(Ledger::new(HDPath::Other(path), chain_id))
+					})
+					.await?;
+				let address = ledger.address();
+				info!("Ledger at {} connected as {:?}", self.path, address);
+				*device = Some(Connected { ledger: Arc::new(ledger), chain_id, address });
+			}
+			Ok(device.as_ref().expect("connected above"))
+		}
+
+		/// Runs `exchange` with the device, forgetting the connection when it fails so
+		/// the next attempt opens the device afresh.
+		async fn on_device<T: Send + 'static>(
+			&self,
+			device: &mut Option<Connected>,
+			what: &str,
+			exchange: impl FnOnce() -> Result<T, LedgerError> + Send + 'static,
+		) -> Result<T> {
+			let outcome = tokio::time::timeout(self.timeout, tokio::task::spawn_blocking(exchange)).await;
+			let reason = match outcome {
+				Ok(Ok(Ok(value))) => return Ok(value),
+				Ok(Ok(Err(e))) if is_rejection(&e) => {
+					return Err(SafeError::SigningFailed(format!("rejected on the Ledger while {}", what)).into());
+				}
+				Ok(Ok(Err(e))) => e.to_string(),
+				Ok(Err(e)) => e.to_string(),
+				Err(_) => format!("no answer within {:?}", self.timeout),
+			};
+			*device = None;
+			warn!(
+				"Ledger at {} unavailable while {}: {}. Connect and unlock it, open the Ethereum app and the next attempt retries",
+				self.path, what, reason
+			);
+			Err(SafeError::SignerUnavailable(format!("Ledger at {} while {}: {}", self.path, what, reason)).into())
+		}
+	}
+
+	/// Whether the user declined on the device, which retrying does not help with.
+	fn is_rejection(error: &LedgerError) -> bool {
+		let message = error.to_string().to_lowercase();
+		["denied", "rejected", "conditions of use not satisfied", "6985"].iter().any(|pattern| message.contains(pattern))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use ethers::core::types::TransactionRequest;
+	use std::str::FromStr;
+
+	#[tokio::test]
+	async fn test_key_signer() {
+		let wallet = LocalWallet::from_str("4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318").unwrap();
+		let signer = OwnerSigner::from(wallet.clone());
+		assert_eq!(signer.address().await.unwrap(), wallet.address());
+		assert_eq!(signer.to_string(), format!("key of {:?}", wallet.address()));
+
+		let hash = H256::repeat_byte(0x42);
+		let signature = signer.sign_safe_tx_hash(hash).await.unwrap();
+		assert!(matches!(signature[64], 27 | 28), "v = {}", signature[64]);
+		assert_eq!(Signature::try_from(signature.as_ref()).unwrap().recover(hash).unwrap(), wallet.address());
+
+		// Signed for the transaction's chain, not the wallet's default one
+		let tx: TypedTransaction =
+			TransactionRequest::new().to(Address::repeat_byte(0x11)).gas(21_000).gas_price(1).nonce(0).chain_id(42161).into();
+		let signature = signer.sign_transaction(&tx).await.unwrap();
+		assert_eq!(signature.v, 42161 * 2 + 35 + signature.recovery_id().unwrap().to_byte() as u64);
+		assert_eq!(signature.recover(tx.sighash()).unwrap(), wallet.address());
+	}
+}
@@ -0,0 +1,272 @@
+//! Swap cost estimates from Uniswap V3's QuoterV2.
+//!
+//! Moving into a pool whose underlying is not the held asset means swapping in, and
+//! out again on the way back. [`SwapQuoter`] asks a QuoterV2 contract what a swap of
+//! a given size returns, and prices the impact against a quote a thousand times
+//! smaller. [`SwapCosts`] keeps a quoter per configured pair and turns the quote into
+//! the round-trip cost of a route, in the held asset.
+
+use anyhow::{Context, Result};
+use ethers::abi::{ParamType, Token};
+use ethers::core::types::{Address, Bytes, TransactionRequest, U256};
+use ethers::providers::Middleware;
+use ethers::types::transaction::eip2718::TypedTransaction;
+use log::{debug, warn};
+use serde::Serialize;
+use std::time::Duration;
+use thiserror::Error;
+
+use super::defi_optimizer::PoolData;
+use crate::config::{Config, SwapConfig, SwapPairConfig};
+use crate::portfolio::HOME_CHAIN;
+use crate::rpc::{FailoverClient, RpcProvider};
+
+/// The reference quote is this many times smaller than the quoted amount.
+const REFERENCE_DIVISOR: u64 = 1000;
+
+#[derive(Error, Debug)]
+#[non_exhaustive]
+pub enum SwapError {
+	#[error("Quote from {quoter:?} failed: {reason}")]
+	QuoteFailed { quoter: Address, reason: String },
+	#[error("Unexpected quoter response: {0}")]
+	InvalidResponse(String),
+}
+
+/// Return values of QuoterV2's `quoteExactInputSingle`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuoterOutput {
+	pub amount_out: U256,
+	pub sqrt_price_x96_after: U256,
+	pub initialized_ticks_crossed: u32,
+	pub gas_estimate: U256,
+}
+
+impl QuoterOutput {
+	pub fn decode(data: &[u8]) -> Result<Self> {
+		let tokens = ethers::abi::decode(
+			&[ParamType::Uint(256), ParamType::Uint(160), ParamType::Uint(32), ParamType::Uint(256)],
+			data,
+		)
+		.map_err(|e| SwapError::InvalidResponse(e.to_string()))?;
+		let uint = |i: usize| tokens[i].clone().into_uint().unwrap_or_default();
+		Ok(Self {
+			amount_out: uint(0),
+			sqrt_price_x96_after: uint(1),
+			initialized_ticks_crossed: uint(2).low_u32(),
+			gas_estimate: uint(3),
+		})
+	}
+}
+
+/// Share of the value lost to price impact when `amount_in` returns `amount_out`,
+/// against the rate of a reference swap small enough to move nothing.
+pub fn price_impact(amount_in: U256, amount_out: U256, reference_in: U256, reference_out: U256) -> f64 {
+	let ratio = |out: U256, inp: U256| as_f64(out) / as_f64(inp);
+	if amount_in.is_zero() || reference_in.is_zero() || reference_out.is_zero() {
+		return 0.0;
+	}
+	(1.0 - ratio(amount_out, amount_in) / ratio(reference_out, reference_in)).max(0.0)
+}
+
+fn as_f64(value: U256) -> f64 {
+	value.to_string().parse().unwrap_or(f64::MAX)
+}
+
+/// What swapping `amount_in` through one pool returns.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SwapQuote {
+	pub amount_in: U256,
+	pub amount_out: U256,
+	/// Pool fee tier in hundredths of a basis point.
+	pub fee_tier: u32,
+	pub price_impact: f64,
+	pub gas_estimate: U256,
+}
+
+impl SwapQuote {
+	/// Share of the input lost to the pool fee and the price impact.
+	pub fn cost_fraction(&self) -> f64 {
+		self.fee_tier as f64 / 1_000_000.0 + self.price_impact
+	}
+
+	/// Cost of swapping `amount` in and back out at this quote's rates, in units of
+	/// the input.
+	pub fn round_trip_cost(&self, amount: f64) -> f64 {
+		amount * (1.0 - (1.0 - self.cost_fraction()).powi(2))
+	}
+}
+
+/// Calls a QuoterV2 contract through `provider`.
+pub struct SwapQuoter {
+	quoter: Address,
+	provider: RpcProvider,
+}
+
+impl SwapQuoter {
+	pub fn new(quoter: Address, provider: RpcProvider) -> Self {
+		Self { quoter, provider }
+	}
+
+	/// Quotes `amount_in` of `token_in` into `token_out` through the pool of `fee`.
+	pub async fn quote(&self, token_in: Address, token_out: Address, fee: u32, amount_in: U256) -> Result<SwapQuote> {
+		let reference_in = (amount_in / REFERENCE_DIVISOR).max(U256::one());
+		let (quoted, reference) = tokio::try_join!(
+			self.quote_exact_input_single(token_in, token_out, fee, amount_in),
+			self.quote_exact_input_single(token_in, token_out, fee, reference_in),
+		)?;
+		let impact = price_impact(amount_in, quoted.amount_out, reference_in, reference.amount_out);
+		debug!(
+			"Quoted {} {:?} -> {} {:?} (fee {}, impact {:.4}%)",
+			amount_in, token_in, quoted.amount_out, token_out, fee, impact * 100.0
+		);
+		Ok(SwapQuote {
+			amount_in,
+			amount_out: quoted.amount_out,
+			fee_tier: fee,
+			price_impact: impact,
+			gas_estimate: quoted.gas_estimate,
+		})
+	}
+
+	async fn quote_exact_input_single(
+		&self,
+		token_in: Address,
+		token_out: Address,
+		fee: u32,
+		amount_in: U256,
+	) -> Result<QuoterOutput> {
+		let mut data = ethers::utils::id("quoteExactInputSingle((address,address,uint256,uint24,uint160))").to_vec();
+		data.extend(ethers::abi::encode(&[Token::Tuple(vec![
+			Token::Address(token_in),
+			Token::Address(token_out),
+			Token::Uint(amount_in),
+			Token::Uint(fee.into()),
+			Token::Uint(U256::zero()),
+		])]));
+		let tx = TypedTransaction::Legacy(TransactionRequest::new().to(self.quoter).data(Bytes::from(data)));
+		let output = self.provider.call(&tx, None).await.map_err(|e| SwapError::QuoteFailed {
+			quoter: self.quoter,
+			reason: e.to_string(),
+		})?;
+		QuoterOutput::decode(&output)
+	}
+}
+
+/// The configured pairs with a quoter each, for pricing routes.
+pub struct SwapCosts {
+	pairs: Vec<(SwapPairConfig, SwapQuoter)>,
+	config: SwapConfig,
+}
+
+impl SwapCosts {
+	/// A quoter per `[[swap.pairs]]` entry on the chain's RPC endpoint. Pairs on chains
+	/// without an endpoint or a quoter are left out.
+	pub fn from_config(config: &Config) -> Result<Self> {
+		let timeout = Duration::from_secs(config.safe.rpc_timeout_secs);
+		let probe_interval = Duration::from_secs(config.safe.rpc_probe_interval_secs);
+		let mut pairs = Vec::new();
+		for pair in &config.swap.pairs {
+			let Some(quoter) = config.swap.quoter(&pair.chain)? else {
+				warn!("No swap quoter configured for {}; {} swaps are not priced", pair.chain, pair.asset);
+				continue;
+			};
+			let configured = config.router.chains.iter().find(|c| c.name == pair.chain);
+			let urls = match configured.map(|c| c.rpc_urls()).filter(|urls| !urls.is_empty()) {
+				Some(urls) => urls,
+				None if pair.chain == HOME_CHAIN && config.safe.rpc_urls().is_ok() => config.safe.rpc_urls()?,
+				None => {
+					warn!("No RPC endpoint for {}; {} swaps are not priced", pair.chain, pair.asset);
+					continue;
+				}
+			};
+			let (provider, _) = FailoverClient::new(&urls, timeout, probe_interval)?.into_provider();
+			pairs.push((pair.clone(), SwapQuoter::new(quoter, provider)));
+		}
+		Ok(Self { pairs, config: config.swap.clone() })
+	}
+
+	/// A quoter per `[[swap.pairs]]` entry whose chain has a quoter, every one asked
+	/// through `provider`, e.g. a simulated node.
+	pub fn with_provider(config: &Config, provider: RpcProvider) -> Result<Self> {
+		let mut pairs = Vec::new();
+		for pair in &config.swap.pairs {
+			if let Some(quoter) = config.swap.quoter(&pair.chain)? {
+				pairs.push((pair.clone(), SwapQuoter::new(quoter, provider.clone())));
+			}
+		}
+		Ok(Self { pairs, config: config.swap.clone() })
+	}
+
+	/// Round-trip swap cost of moving `amount` of the held asset into `pool`; `None`
+	/// when the pool holds the held asset or its pair is not configured.
+	pub async fn round_trip_cost(&self, pool: &PoolData, amount: f64) -> Result<Option<f64>> {
+		let Some(pair) = self.config.pair_for(&pool.protocol, &pool.chain) else {
+			return Ok(None);
+		};
+		let Some((_, quoter)) = self.pairs.iter().find(|(p, _)| p.chain == pair.chain && p.asset == pair.asset) else {
+			return Ok(None);
+		};
+		let amount_in = ethers::utils::parse_ether(amount).context("Invalid swap amount")?;
+		let quote = quoter.quote(pair.token_in()?, pair.token_out()?, pair.fee, amount_in).await?;
+		Ok(Some(quote.round_trip_cost(amount)))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	// 1 WETH -> 2000 USDC on the 0.05% pool, as QuoterV2 encodes it
+	const CANNED: &str = concat!(
+		"0000000000000000000000000000000000000000000000000000000077359400",
+		"0000000000000000000000000000000000000000000000000000000000000001",
+		"0000000000000000000000000000000000000000000000000000000000000002",
+		"00000000000000000000000000000000000000000000000000000000000186a0",
+	);
+
+	#[test]
+	fn test_decode_quoter_output() {
+		let output = QuoterOutput::decode(&hex::decode(CANNED).unwrap()).unwrap();
+		assert_eq!(output.amount_out, U256::from(2_000_000_000_u64));
+		assert_eq!(output.sqrt_price_x96_after, U256::one());
+		assert_eq!(output.initialized_ticks_crossed, 2);
+		assert_eq!(output.gas_estimate, U256::from(100_000));
+		assert!(QuoterOutput::decode(&[0; 31]).is_err());
+	}
+
+	#[test]
+	fn test_price_impact_and_round_trip_cost() {
+		let eth = U256::exp10(18);
+		// 1 ETH gets 1990 USDC where 0.001 ETH got 2.000 USDC: 0.5% impact
+		let impact = price_impact(eth, U256::from(1_990_000_000_u64), eth / 1000, U256::from(2_000_000));
+		assert!((impact - 0.005).abs() < 1e-12, "{}", impact);
+		// A better rate than the reference is no impact
+		assert_eq!(price_impact(eth, U256::from(2_010_000_000_u64), eth / 1000, U256::from(2_000_000)), 0.0);
+
+		let quote = SwapQuote {
+			amount_in: eth,
+			amount_out: U256::from(1_990_000_000_u64),
+			fee_tier: 500,
+			price_impact: impact,
+			gas_estimate: U256::zero(),
+		};
+		assert!((quote.cost_fraction() - 0.0055).abs() < 1e-12);
+		assert!((quote.round_trip_cost(2.0) - 2.0 * (1.0 - 0.9945_f64.powi(2))).abs() < 1e-12);
+	}
+
+	#[tokio::test]
+	#[ignore = "needs anvil and ASAM_FORK_URL"]
+	async fn fork_quoter_prices_weth_usdc() {
+		let Some(fork) = crate::test_support::AnvilFork::from_env().await.unwrap() else {
+			return;
+		};
+		let quoter = SwapQuoter::new(crate::config::MAINNET_QUOTER_V2.parse().unwrap(), fork.provider());
+		let weth: Address = "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2".parse().unwrap();
+		let usdc: Address = "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48".parse().unwrap();
+		let quote = quoter.quote(weth, usdc, 500, U256::exp10(18)).await.unwrap();
+		assert!(quote.amount_out > U256::zero());
+		assert!(quote.price_impact < 0.01, "{:?}", quote);
+		assert!(quote.gas_estimate > U256::zero());
+	}
+}
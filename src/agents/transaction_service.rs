@@ -0,0 +1,498 @@
+//! Client of the Safe Transaction Service, where a Safe transaction waits for the
+//! other owners' confirmations once one owner proposed it.
+
+use ethers::core::types::{Address, Bytes, H256, U256};
+use ethers::utils::to_checksum;
+use log::debug;
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+use std::str::FromStr;
+use std::time::Duration;
+
+use super::safe_manager::{SafeError, SafeTransaction};
+use crate::secret::redact_url;
+use crate::version::USER_AGENT;
+
+/// A signed Safe transaction in the form `POST /api/v1/safes/{safe}/multisig-transactions/`
+/// takes it.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Proposal {
+	/// The transaction, with its nonce.
+	#[serde(flatten)]
+	pub transaction: ServiceTransaction,
+	/// The `safeTxHash`, which the service recomputes and compares.
+	pub contract_transaction_hash: String,
+	/// The owner whose signature this is.
+	pub sender: String,
+	pub signature: String,
+	pub origin: String,
+}
+
+impl Proposal {
+	/// `tx` as `nonce`, signed by `sender`. The refund fields are zero, as in
+	/// [`SafeTransaction::safe_tx_hash`].
+	pub fn new(tx: &SafeTransaction, nonce: U256, safe_tx_hash: H256, sender: Address, signature: &Bytes) -> Self {
+		Self {
+			transaction: ServiceTransaction { nonce: Some(nonce.to_string()), ..tx.into() },
+			contract_transaction_hash: format!("{:?}", safe_tx_hash),
+			sender: to_checksum(&sender, None),
+			signature: signature.to_string(),
+			origin: "asam".to_string(),
+		}
+	}
+}
+
+/// A Safe transaction in the Safe Transaction Service's JSON: camelCase names, an
+/// EIP-55 checksummed `to`, decimal strings for amounts and `0x` hex `data`, `null`
+/// when empty. [`SafeTransaction`] is written as this and read from it, the
+/// service's other fields ignored, so its JSON goes to and from Safe tooling as is.
+///
+/// Reading also takes the numbers the service answers with for `safeTxGas` and
+/// `nonce`, and the snake_case form with hex amounts and a byte array for `data`
+/// that `SafeTransaction` was written in before, as in stored rebalance plans.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServiceTransaction {
+	pub to: String,
+	#[serde(deserialize_with = "quantity")]
+	pub value: String,
+	#[serde(default, deserialize_with = "hex_data")]
+	pub data: Option<String>,
+	pub operation: u8,
+	#[serde(default = "zero", alias = "safe_tx_gas", deserialize_with = "quantity")]
+	pub safe_tx_gas: String,
+	#[serde(default = "zero", deserialize_with = "quantity")]
+	pub base_gas: String,
+	#[serde(default = "zero", deserialize_with = "quantity")]
+	pub gas_price: String,
+	#[serde(default)]
+	pub gas_token: Option<String>,
+	#[serde(default)]
+	pub refund_receiver: Option<String>,
+	#[serde(default, deserialize_with = "optional_quantity", skip_serializing_if = "Option::is_none")]
+	pub nonce: Option<String>,
+}
+
+impl From<&SafeTransaction> for ServiceTransaction {
+	fn from(tx: &SafeTransaction) -> Self {
+		Self {
+			to: to_checksum(&tx.to, None),
+			value: tx.value.to_string(),
+			data: (!tx.data.is_empty()).then(|| format!("0x{}", hex::encode(&tx.data))),
+			operation: tx.operation,
+			safe_tx_gas: tx.safe_tx_gas.to_string(),
+			base_gas: zero(),
+			gas_price: zero(),
+			gas_token: None,
+			refund_receiver: None,
+			nonce: tx.nonce.map(|nonce| nonce.to_string()),
+		}
+	}
+}
+
+impl From<SafeTransaction> for ServiceTransaction {
+	fn from(tx: SafeTransaction) -> Self {
+		Self::from(&tx)
+	}
+}
+
+impl TryFrom<ServiceTransaction> for SafeTransaction {
+	type Error = SafeError;
+
+	/// Checks every field: `to` must be an address, checksummed correctly when it is
+	/// mixed case; `data` `0x`-prefixed hex; amounts decimal (or `0x` hex); and the
+	/// refund fields zero, since [`SafeTransaction::safe_tx_hash`] assumes the
+	/// executor pays its own gas.
+	fn try_from(tx: ServiceTransaction) -> Result<Self, SafeError> {
+		if tx.operation > 1 {
+			return Err(SafeError::InvalidOperation(tx.operation));
+		}
+		let data = match tx.data.as_deref() {
+			None => Vec::new(),
+			Some(data) => {
+				let digits = data
+					.strip_prefix("0x")
+					.ok_or_else(|| malformed(format!("data '{}' is not 0x-prefixed hex", abbreviate(data))))?;
+				hex::decode(digits).map_err(|e| malformed(format!("data is not valid hex: {}", e)))?
+			}
+		};
+		for (field, amount) in [("baseGas", &tx.base_gas), ("gasPrice", &tx.gas_price)] {
+			if !parse_quantity(field, amount)?.is_zero() {
+				return Err(malformed(format!("{} {} is not supported: the executor pays its own gas", field, amount)));
+			}
+		}
+		for (field, address) in [("gasToken", &tx.gas_token), ("refundReceiver", &tx.refund_receiver)] {
+			if let Some(address) = address {
+				if !parse_address(field, address)?.is_zero() {
+					return Err(malformed(format!("{} {} is not supported: the executor pays its own gas", field, address)));
+				}
+			}
+		}
+		Ok(SafeTransaction {
+			to: parse_address("to", &tx.to)?,
+			value: parse_quantity("value", &tx.value)?,
+			data,
+			operation: tx.operation,
+			safe_tx_gas: parse_quantity("safeTxGas", &tx.safe_tx_gas)?,
+			nonce: tx.nonce.as_deref().map(|nonce| parse_quantity("nonce", nonce)).transpose()?,
+		})
+	}
+}
+
+fn malformed(reason: String) -> SafeError {
+	SafeError::MalformedTransaction(reason)
+}
+
+/// The start of `text`, for errors about values that may be long.
+fn abbreviate(text: &str) -> String {
+	match text.char_indices().nth(16) {
+		Some((end, _)) => format!("{}…", &text[..end]),
+		None => text.to_string(),
+	}
+}
+
+/// An address, whose EIP-55 checksum must hold when it is written in mixed case.
+fn parse_address(field: &str, raw: &str) -> Result<Address, SafeError> {
+	let address =
+		Address::from_str(raw).map_err(|_| malformed(format!("{} '{}' is not an address", field, abbreviate(raw))))?;
+	let digits = raw.trim_start_matches("0x");
+	let mixed_case = digits.chars().any(|c| c.is_ascii_uppercase()) && digits.chars().any(|c| c.is_ascii_lowercase());
+	if mixed_case && to_checksum(&address, None)[2..] != *digits {
+		return Err(malformed(format!("{} {} fails its EIP-55 checksum", field, raw)));
+	}
+	Ok(address)
+}
+
+/// A decimal amount, or a `0x` hex one as `U256` writes itself.
+fn parse_quantity(field: &str, raw: &str) -> Result<U256, SafeError> {
+	let parsed = match raw.strip_prefix("0x") {
+		Some(digits) => U256::from_str_radix(digits, 16).ok(),
+		None => U256::from_dec_str(raw).ok(),
+	};
+	parsed.ok_or_else(|| malformed(format!("{} '{}' is not a decimal amount", field, abbreviate(raw))))
+}
+
+fn zero() -> String {
+	"0".to_string()
+}
+
+/// A string, or the number the service sometimes answers with instead.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Quantity {
+	Number(serde_json::Number),
+	Text(String),
+}
+
+impl From<Quantity> for String {
+	fn from(quantity: Quantity) -> Self {
+		match quantity {
+			Quantity::Number(number) => number.to_string(),
+			Quantity::Text(text) => text,
+		}
+	}
+}
+
+fn quantity<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+	Quantity::deserialize(deserializer).map(String::from)
+}
+
+fn optional_quantity<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<String>, D::Error> {
+	Ok(Option::<Quantity>::deserialize(deserializer)?.map(String::from))
+}
+
+/// `0x` hex, or the array of bytes `SafeTransaction` used to write.
+fn hex_data<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<String>, D::Error> {
+	#[derive(Deserialize)]
+	#[serde(untagged)]
+	enum Data {
+		Hex(String),
+		Bytes(Vec<u8>),
+	}
+	Ok(match Option::<Data>::deserialize(deserializer)? {
+		Some(Data::Hex(hex)) => Some(hex),
+		Some(Data::Bytes(bytes)) => Some(format!("0x{}", hex::encode(bytes))),
+		None => None,
+	})
+}
+
+/// One network's Safe Transaction Service.
+#[derive(Debug, Clone)]
+pub struct TransactionService {
+	client: Client,
+	base_url: String,
+}
+
+impl TransactionService {
+	pub fn new(base_url: impl Into<String>) -> Self {
+		Self {
+			client: Client::builder()
+				.timeout(Duration::from_secs(10))
+				.user_agent(USER_AGENT)
+				.build()
+				.unwrap_or_default(),
+			base_url: base_url.into().trim_end_matches('/').to_string(),
+		}
+	}
+
+	pub fn base_url(&self) -> &str {
+		&self.base_url
+	}
+
+	/// Submits `proposal` for the Safe at `safe`. A 422 answer is a typed error:
+	/// [`SafeError::NonceAlreadyUsed`] when the nonce was executed already,
+	/// [`SafeError::InvalidSignature`] when the signature or its signer is refused.
+	pub async fn propose(&self, safe: Address, proposal: &Proposal) -> Result<(), SafeError> {
+		let url = format!("{}/api/v1/safes/{}/multisig-transactions/", self.base_url, to_checksum(&safe, None));
+		debug!("Proposing Safe transaction {} to {}", proposal.contract_transaction_hash, redact_url(&url));
+		let response = self
+			.client
+			.post(&url)
+			.json(proposal)
+			.send()
+			.await
+			.map_err(|e| SafeError::TransactionServiceUnavailable(e.without_url().to_string()))?;
+		let status = response.status();
+		if status.is_success() {
+			return Ok(());
+		}
+		let body = response.text().await.unwrap_or_default();
+		Err(match status {
+			StatusCode::UNPROCESSABLE_ENTITY => rejection(proposal, &body),
+			status if status.is_server_error() => {
+				SafeError::TransactionServiceUnavailable(format!("status {}", status.as_u16()))
+			}
+			status => SafeError::ProposalRejected(format!("status {}: {}", status.as_u16(), body.trim())),
+		})
+	}
+}
+
+/// What a 422 answer's messages, e.g. `{"nonFieldErrors": ["Tx with nonce 4 for safe
+/// 0x… already executed in tx-hash 0x…"]}`, say was wrong.
+fn rejection(proposal: &Proposal, body: &str) -> SafeError {
+	let mut messages = Vec::new();
+	match serde_json::from_str::<Value>(body) {
+		Ok(value) => collect_messages(&value, &mut messages),
+		Err(_) => messages.push(body.trim().to_string()),
+	}
+	let reason = messages.join("; ");
+	let lower = reason.to_lowercase();
+	if lower.contains("nonce") && lower.contains("already executed") {
+		let nonce = proposal.transaction.nonce.as_deref().and_then(|nonce| U256::from_dec_str(nonce).ok());
+		SafeError::NonceAlreadyUsed(nonce.unwrap_or_default())
+	} else if ["signature", "signer", "owner"].iter().any(|word| lower.contains(word)) {
+		SafeError::InvalidSignature(reason)
+	} else {
+		SafeError::ProposalRejected(reason)
+	}
+}
+
+fn collect_messages(value: &Value, messages: &mut Vec<String>) {
+	match value {
+		Value::String(message) => messages.push(message.clone()),
+		Value::Array(values) => values.iter().for_each(|value| collect_messages(value, messages)),
+		Value::Object(fields) => fields.values().for_each(|value| collect_messages(value, messages)),
+		_ => {}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use wiremock::matchers::{body_partial_json, method, path};
+	use wiremock::{Mock, MockServer, ResponseTemplate};
+
+	fn proposal() -> Proposal {
+		let tx = SafeTransaction {
+			to: Address::repeat_byte(0x22),
+			value: U256::exp10(18),
+			data: vec![0xa9, 0x05, 0x9c, 0xbb],
+			operation: 0,
+			safe_tx_gas: U256::zero(),
+			nonce: None,
+		};
+		let signature = Bytes::from(vec![0x11; 65]);
+		Proposal::new(&tx, 4.into(), H256::repeat_byte(0xab), Address::repeat_byte(0xa1), &signature)
+	}
+
+	#[tokio::test]
+	async fn test_propose() {
+		let server = MockServer::start().await;
+		let safe = Address::repeat_byte(0x5a);
+		Mock::given(method("POST"))
+			.and(path(format!("/api/v1/safes/{}/multisig-transactions/", to_checksum(&safe, None))))
+			.and(body_partial_json(serde_json::json!({
+				"to": "0x2222222222222222222222222222222222222222",
+				"value": "1000000000000000000",
+				"data": "0xa9059cbb",
+				"nonce": "4",
+				"contractTransactionHash": format!("{:?}", H256::repeat_byte(0xab)),
+				"sender": to_checksum(&Address::repeat_byte(0xa1), None),
+				"signature": format!("0x{}", "11".repeat(65)),
+				"gasToken": null,
+			})))
+			.respond_with(ResponseTemplate::new(201))
+			.expect(1)
+			.mount(&server)
+			.await;
+
+		TransactionService::new(format!("{}/", server.uri())).propose(safe, &proposal()).await.unwrap();
+	}
+
+	#[tokio::test]
+	async fn test_rejections_are_typed() {
+		let cases = [
+			(
+				422,
+				r#"{"nonFieldErrors": ["Tx with nonce 4 for safe 0x5a already executed in tx-hash 0xdead"]}"#,
+				"nonce",
+			),
+			(422, r#"{"nonFieldErrors": ["Signer=0xA1 is not an owner or delegate"]}"#, "signature"),
+			(422, r#"{"signature": ["Signature=0x11 for owner=0xA1 is not valid"]}"#, "signature"),
+			(422, r#"{"to": ["Checksum address validation failed"]}"#, "rejected"),
+			(400, r#"{"detail": "bad request"}"#, "rejected"),
+			(503, "", "unavailable"),
+		];
+		for (status, body, expected) in cases {
+			let server = MockServer::start().await;
+			Mock::given(method("POST")).respond_with(ResponseTemplate::new(status).set_body_string(body)).mount(&server).await;
+			let error = TransactionService::new(server.uri()).propose(Address::zero(), &proposal()).await.unwrap_err();
+			let kind = match &error {
+				SafeError::NonceAlreadyUsed(nonce) => {
+					assert_eq!(*nonce, U256::from(4));
+					"nonce"
+				}
+				SafeError::InvalidSignature(_) => "signature",
+				SafeError::ProposalRejected(_) => "rejected",
+				SafeError::TransactionServiceUnavailable(_) => "unavailable",
+				other => panic!("unexpected error {:?}", other),
+			};
+			assert_eq!(kind, expected, "{} {}: {}", status, body, error);
+		}
+	}
+
+	/// An entry of `GET /api/v1/safes/{safe}/multisig-transactions/`: a USDC transfer
+	/// waiting for its second confirmation.
+	fn service_entry() -> Value {
+		serde_json::json!({
+			"safe": "0x5afe3855358e112b5647b952709e6165e1c1eeee",
+			"to": "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48",
+			"value": "0",
+			"data": "0xa9059cbb000000000000000000000000c02aaa39b223fe8d0a0e5c4f27ead9083c756cc2000000000000000000000000000000000000000000000000000000003b9aca00",
+			"operation": 0,
+			"gasToken": "0x0000000000000000000000000000000000000000",
+			"safeTxGas": 0,
+			"baseGas": 0,
+			"gasPrice": "0",
+			"refundReceiver": "0x0000000000000000000000000000000000000000",
+			"nonce": 42,
+			"executionDate": null,
+			"submissionDate": "2024-03-01T10:00:00.123456Z",
+			"modified": "2024-03-01T10:00:00.123456Z",
+			"blockNumber": null,
+			"transactionHash": null,
+			"safeTxHash": "0x4b0b2f9d8f7c2e1a6b3c5d7e9f0a1b2c3d4e5f60718293a4b5c6d7e8f9a0b1c2",
+			"proposer": "0x2c7536E3605D9C16a7a3D7b1898e529396a65c23",
+			"executor": null,
+			"isExecuted": false,
+			"isSuccessful": null,
+			"ethGasPrice": null,
+			"gasUsed": null,
+			"fee": null,
+			"origin": "{}",
+			"dataDecoded": {
+				"method": "transfer",
+				"parameters": [
+					{ "name": "to", "type": "address", "value": "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2" },
+					{ "name": "value", "type": "uint256", "value": "1000000000" }
+				]
+			},
+			"confirmationsRequired": 2,
+			"confirmations": [],
+			"trusted": true,
+			"signatures": null
+		})
+	}
+
+	#[test]
+	fn test_service_transaction_json() {
+		let entry = service_entry();
+		let tx: SafeTransaction = serde_json::from_value(entry.clone()).unwrap();
+		assert_eq!(tx.to, Address::from_str("0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48").unwrap());
+		assert_eq!((tx.value, tx.operation, tx.safe_tx_gas, tx.nonce), (U256::zero(), 0, U256::zero(), Some(42.into())));
+		assert_eq!(tx.data.len(), 4 + 32 + 32);
+		assert_eq!(tx.data[..4], [0xa9, 0x05, 0x9c, 0xbb]);
+
+		// Written back in the service's names and forms, amounts as strings
+		let written = serde_json::to_value(&tx).unwrap();
+		for field in ["to", "value", "data", "operation", "gasPrice"] {
+			assert_eq!(written[field], entry[field], "{}", field);
+		}
+		assert_eq!((&written["safeTxGas"], &written["baseGas"], &written["nonce"]), (&"0".into(), &"0".into(), &"42".into()));
+		assert_eq!(serde_json::from_value::<SafeTransaction>(written).unwrap(), tx);
+
+		// A plain ETH transfer has no data, and no nonce until one is picked
+		let transfer = SafeTransaction {
+			to: Address::repeat_byte(0xab),
+			value: U256::exp10(18),
+			data: vec![],
+			operation: 0,
+			safe_tx_gas: U256::zero(),
+			nonce: None,
+		};
+		let written = serde_json::to_value(&transfer).unwrap();
+		assert_eq!(written["to"], "0xABaBaBaBABabABabAbAbABAbABabababaBaBABaB");
+		assert_eq!((&written["value"], &written["data"]), (&"1000000000000000000".into(), &Value::Null));
+		assert!(written.get("nonce").is_none());
+		assert_eq!(serde_json::from_value::<SafeTransaction>(written).unwrap(), transfer);
+
+		// As SafeTransaction was written before, e.g. in stored rebalance plans
+		let legacy = serde_json::json!({
+			"to": "0xabababababababababababababababababababab",
+			"value": "0xde0b6b3a7640000",
+			"data": [169, 5, 156, 187],
+			"operation": 0,
+			"safe_tx_gas": "0x0",
+			"nonce": null,
+		});
+		let tx: SafeTransaction = serde_json::from_value(legacy).unwrap();
+		assert_eq!((tx.value, tx.data, tx.nonce), (U256::exp10(18), vec![0xa9, 0x05, 0x9c, 0xbb], None));
+	}
+
+	#[test]
+	fn test_malformed_service_transactions() {
+		let parse = |changes: Value| {
+			let mut entry = service_entry();
+			for (field, value) in changes.as_object().unwrap() {
+				entry[field] = value.clone();
+			}
+			SafeTransaction::try_from(serde_json::from_value::<ServiceTransaction>(entry).unwrap())
+		};
+		let malformed = |changes: Value, needle: &str| match parse(changes.clone()) {
+			Err(SafeError::MalformedTransaction(reason)) => assert!(reason.contains(needle), "{}: {}", changes, reason),
+			other => panic!("{} parsed as {:?}", changes, other),
+		};
+		malformed(serde_json::json!({ "data": "0xa9059cbz" }), "not valid hex");
+		malformed(serde_json::json!({ "data": "a9059cbb" }), "not 0x-prefixed");
+		malformed(serde_json::json!({ "to": "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606EB48" }), "EIP-55 checksum");
+		malformed(serde_json::json!({ "to": "0x1234" }), "not an address");
+		malformed(serde_json::json!({ "value": "1.5" }), "value '1.5' is not a decimal amount");
+		malformed(serde_json::json!({ "nonce": "-1" }), "nonce");
+		malformed(serde_json::json!({ "gasPrice": "1" }), "gasPrice 1 is not supported");
+		malformed(
+			serde_json::json!({ "refundReceiver": "0x2c7536E3605D9C16a7a3D7b1898e529396a65c23" }),
+			"refundReceiver",
+		);
+		assert!(matches!(parse(serde_json::json!({ "operation": 2 })), Err(SafeError::InvalidOperation(2))));
+		// An all lowercase address carries no checksum to check
+		parse(serde_json::json!({ "to": "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48" })).unwrap();
+
+		// Through serde the reason comes out as the error message
+		let mut entry = service_entry();
+		entry["data"] = "0xzz".into();
+		let error = serde_json::from_value::<SafeTransaction>(entry).unwrap_err();
+		assert!(error.to_string().contains("Malformed Safe transaction: data is not valid hex"), "{}", error);
+	}
+}
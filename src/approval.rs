@@ -0,0 +1,286 @@
+//! Operator sign-off before funds move.
+//!
+//! With `runtime.approval = "prompt"` every live route is summarized on stdout and
+//! waits for `y` on stdin; with `"file"` the summary is written to
+//! `runtime.approval_file` and the operator answers by replacing its contents with
+//! `yes` or `no`. No answer within `runtime.approval_timeout_secs` declines.
+
+use anyhow::{bail, Context, Result};
+use log::{info, warn};
+use std::fmt;
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, BufReader, Lines};
+use tokio::sync::Mutex;
+
+use crate::config::{ApprovalMode, RuntimeConfig};
+
+/// How often the approval file is checked for an answer.
+const FILE_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+type Input = Lines<Box<dyn AsyncBufRead + Unpin + Send>>;
+
+/// What an operator is asked to approve.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ApprovalRequest {
+    pub amount: f64,
+    pub source_chain: String,
+    pub target_chain: String,
+    pub protocol: String,
+    pub apy: f64,
+    pub current_apy: Option<f64>,
+    /// Bridge and gas fees in ETH, when quoted.
+    pub fee: Option<f64>,
+    /// Cost in ETH of swapping into the pool's underlying and back, when it is not
+    /// the held asset and the swap was quoted.
+    pub swap_cost: Option<f64>,
+}
+
+impl ApprovalRequest {
+    /// Fees and swap cost, whichever were quoted.
+    pub fn costs(&self) -> Option<f64> {
+        match (self.fee, self.swap_cost) {
+            (None, None) => None,
+            (fee, swap) => Some(fee.unwrap_or(0.0) + swap.unwrap_or(0.0)),
+        }
+    }
+
+    fn daily_gain(&self) -> f64 {
+        let improvement = self.apy - self.current_apy.unwrap_or(0.0);
+        self.amount * improvement / 100.0 / 365.0
+    }
+
+    /// Days until the APY improvement pays back the costs.
+    pub fn breakeven_days(&self) -> Option<f64> {
+        let daily_gain = self.daily_gain();
+        self.costs().filter(|_| daily_gain > 0.0).map(|costs| costs / daily_gain)
+    }
+
+    /// What the improvement earns over `days`, less the quoted costs, in ETH.
+    pub fn net_gain_estimate(&self, days: f64) -> f64 {
+        self.daily_gain() * days - self.costs().unwrap_or(0.0)
+    }
+}
+
+impl fmt::Display for ApprovalRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Route {} ETH from {} to {}", self.amount, self.source_chain, self.target_chain)?;
+        match self.current_apy {
+            Some(current) => writeln!(f, "Pool:      {} ({:.2}% APY, now {:.2}%)", self.protocol, self.apy, current)?,
+            None => writeln!(f, "Pool:      {} ({:.2}% APY)", self.protocol, self.apy)?,
+        }
+        match self.fee {
+            Some(fee) => writeln!(f, "Fees:      {} ETH", fee)?,
+            None => writeln!(f, "Fees:      not quoted")?,
+        }
+        if let Some(swap) = self.swap_cost {
+            writeln!(f, "Swap:      {:.6} ETH round trip", swap)?;
+        }
+        match self.breakeven_days() {
+            Some(days) => write!(f, "Breakeven: {:.1} days", days),
+            None => write!(f, "Breakeven: unknown"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApprovalDecision {
+    Approved,
+    Declined,
+    TimedOut,
+}
+
+/// Asks for approval in the configured [`ApprovalMode`].
+pub struct Approver {
+    mode: ApprovalMode,
+    timeout: Duration,
+    input: Mutex<Option<Input>>,
+    file: Option<PathBuf>,
+}
+
+impl Default for Approver {
+    fn default() -> Self {
+        Self::auto()
+    }
+}
+
+impl Approver {
+    /// Approves everything without asking.
+    pub fn auto() -> Self {
+        Self { mode: ApprovalMode::Auto, timeout: Duration::ZERO, input: Mutex::new(None), file: None }
+    }
+
+    /// Prompt mode needs an interactive stdin and refuses to start without one.
+    pub fn from_config(runtime: &RuntimeConfig) -> Result<Self> {
+        match runtime.approval {
+            ApprovalMode::Auto => Ok(Self::auto()),
+            ApprovalMode::Prompt => {
+                if !std::io::stdin().is_terminal() {
+                    bail!(
+                        "runtime.approval = \"prompt\" needs an interactive terminal on stdin; \
+                         use \"file\" or \"auto\" when running detached"
+                    );
+                }
+                Ok(Self::with_reader(BufReader::new(tokio::io::stdin()), runtime.approval_timeout()))
+            }
+            ApprovalMode::File => {
+                let path = runtime.approval_file.clone().context("runtime.approval_file is not set")?;
+                Ok(Self::with_file(path, runtime.approval_timeout()))
+            }
+        }
+    }
+
+    /// Prompt mode reading answers from `reader`.
+    pub fn with_reader(reader: impl AsyncBufRead + Unpin + Send + 'static, timeout: Duration) -> Self {
+        let reader: Box<dyn AsyncBufRead + Unpin + Send> = Box::new(reader);
+        Self { mode: ApprovalMode::Prompt, timeout, input: Mutex::new(Some(reader.lines())), file: None }
+    }
+
+    /// File mode exchanging requests and answers through `path`.
+    pub fn with_file(path: PathBuf, timeout: Duration) -> Self {
+        Self { mode: ApprovalMode::File, timeout, input: Mutex::new(None), file: Some(path) }
+    }
+
+    pub fn mode(&self) -> ApprovalMode {
+        self.mode
+    }
+
+    pub async fn decide(&self, request: &ApprovalRequest) -> ApprovalDecision {
+        let decision = match (self.mode, &self.file) {
+            (ApprovalMode::Auto, _) => return ApprovalDecision::Approved,
+            (ApprovalMode::File, Some(path)) => self.ask_file(path, request).await,
+            _ => self.ask_prompt(request).await,
+        };
+        info!("Operator decision on route to {}: {:?}", request.target_chain, decision);
+        decision
+    }
+
+    async fn ask_prompt(&self, request: &ApprovalRequest) -> ApprovalDecision {
+        let mut input = self.input.lock().await;
+        let Some(lines) = input.as_mut() else {
+            return ApprovalDecision::Declined;
+        };
+        let mut stdout = std::io::stdout().lock();
+        let _ = write!(stdout, "\n{}\nApprove? [y/N] ({}s) ", request, self.timeout.as_secs());
+        let _ = stdout.flush();
+        drop(stdout);
+        match tokio::time::timeout(self.timeout, lines.next_line()).await {
+            Ok(Ok(Some(answer))) if is_yes(&answer) => ApprovalDecision::Approved,
+            Ok(Ok(_)) => ApprovalDecision::Declined,
+            Ok(Err(e)) => {
+                warn!("Failed to read the approval answer: {}", e);
+                ApprovalDecision::Declined
+            }
+            Err(_) => {
+                println!();
+                ApprovalDecision::TimedOut
+            }
+        }
+    }
+
+    async fn ask_file(&self, path: &Path, request: &ApprovalRequest) -> ApprovalDecision {
+        let body = format!("{}\n\nReplace the contents of this file with \"yes\" to approve or \"no\" to decline.\n", request);
+        if let Err(e) = std::fs::write(path, &body) {
+            warn!("Failed to write approval request to {}: {}", path.display(), e);
+            return ApprovalDecision::Declined;
+        }
+        info!("Waiting up to {}s for an answer in {}", self.timeout.as_secs(), path.display());
+        let answer = tokio::time::timeout(self.timeout, async {
+            loop {
+                tokio::time::sleep(FILE_POLL_INTERVAL).await;
+                match std::fs::read_to_string(path).map(|s| s.trim().to_lowercase()) {
+                    Ok(answer) if is_yes(&answer) => return ApprovalDecision::Approved,
+                    Ok(answer) if answer == "n" || answer == "no" => return ApprovalDecision::Declined,
+                    // Still the request, or the operator is mid-edit
+                    Ok(_) => {}
+                    Err(_) => return ApprovalDecision::Declined,
+                }
+            }
+        })
+        .await
+        .unwrap_or(ApprovalDecision::TimedOut);
+        let _ = std::fs::remove_file(path);
+        answer
+    }
+}
+
+fn is_yes(answer: &str) -> bool {
+    matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request() -> ApprovalRequest {
+        ApprovalRequest {
+            amount: 10.0,
+            source_chain: "Ethereum".to_string(),
+            target_chain: "Arbitrum".to_string(),
+            protocol: "Aave".to_string(),
+            apy: 7.0,
+            current_apy: Some(3.35),
+            fee: Some(0.01),
+            swap_cost: None,
+        }
+    }
+
+    #[test]
+    fn test_request_summary() {
+        let summary = request().to_string();
+        assert!(summary.starts_with("Route 10 ETH from Ethereum to Arbitrum"), "{}", summary);
+        assert!(summary.contains("Aave (7.00% APY, now 3.35%)"), "{}", summary);
+        // 10 ETH earning 3.65 points more makes 0.001 ETH a day
+        assert_eq!(request().breakeven_days().map(|d| d.round()), Some(10.0));
+        let unquoted = ApprovalRequest { fee: None, ..request() };
+        assert!(unquoted.to_string().ends_with("Breakeven: unknown"));
+
+        // A swap into another underlying doubles the costs to pay back
+        let swapped = ApprovalRequest { swap_cost: Some(0.01), ..request() };
+        assert!(swapped.to_string().contains("Swap:      0.010000 ETH round trip"));
+        assert_eq!(swapped.breakeven_days().map(|d| d.round()), Some(20.0));
+        assert!((swapped.net_gain_estimate(30.0) - 0.01).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn test_prompt_answers() {
+        let approver = Approver::with_reader(&b"y\nno\n\n"[..], Duration::from_secs(5));
+        assert_eq!(approver.decide(&request()).await, ApprovalDecision::Approved);
+        assert_eq!(approver.decide(&request()).await, ApprovalDecision::Declined);
+        // Just Enter keeps the default
+        assert_eq!(approver.decide(&request()).await, ApprovalDecision::Declined);
+        // Closed input
+        assert_eq!(approver.decide(&request()).await, ApprovalDecision::Declined);
+        assert_eq!(Approver::auto().decide(&request()).await, ApprovalDecision::Approved);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_prompt_times_out_to_decline() {
+        let (_operator, input) = tokio::io::duplex(64);
+        let approver = Approver::with_reader(BufReader::new(input), Duration::from_secs(30));
+        assert_eq!(approver.decide(&request()).await, ApprovalDecision::TimedOut);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_file_answers() {
+        let path = std::env::temp_dir().join(format!("asam-approval-{}", std::process::id()));
+        let approver = Approver::with_file(path.clone(), Duration::from_secs(30));
+
+        let operator = {
+            let path = path.clone();
+            async move {
+                tokio::time::sleep(Duration::from_millis(500)).await;
+                assert!(std::fs::read_to_string(&path).unwrap().contains("Route 10 ETH"));
+                std::fs::write(&path, "yes\n").unwrap();
+            }
+        };
+        let request = request();
+        let (decision, ()) = tokio::join!(approver.decide(&request), operator);
+        assert_eq!(decision, ApprovalDecision::Approved);
+        assert!(!path.exists());
+
+        assert_eq!(approver.decide(&request).await, ApprovalDecision::TimedOut);
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,125 @@
+//! Audit trail of operator actions taken through the `/admin` endpoints.
+//!
+//! Every admin request, rejected ones included, is logged under the `audit` target,
+//! kept in memory for inspection and, with `http.audit_log_path`, appended to that
+//! file as one JSON object per line. Each start of the agent first appends a header
+//! record with the build that is running.
+
+use log::{info, warn};
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use crate::util::unix_now;
+use crate::version::BuildInfo;
+
+/// Entries kept in memory; the file keeps everything.
+const RECENT_ENTRIES: usize = 100;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AuditEntry {
+    pub timestamp: u64,
+    /// Remote address of the request.
+    pub caller: String,
+    /// Method and path, e.g. "POST /admin/pause".
+    pub action: String,
+    /// "ok", "unauthorized" or what went wrong.
+    pub outcome: String,
+}
+
+/// First record written by each process, so entries can be matched to a build.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AuditHeader {
+    pub timestamp: u64,
+    /// Always "start".
+    pub action: &'static str,
+    #[serde(flatten)]
+    pub build: BuildInfo,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    path: Option<PathBuf>,
+    recent: Mutex<VecDeque<AuditEntry>>,
+}
+
+/// Cloneable handle shared by the admin handlers.
+#[derive(Debug, Clone, Default)]
+pub struct AuditLog {
+    inner: Arc<Inner>,
+}
+
+impl AuditLog {
+    /// Also appends to `path` when set, starting with an [`AuditHeader`].
+    pub fn new(path: Option<PathBuf>) -> Self {
+        let log = Self { inner: Arc::new(Inner { path, recent: Mutex::default() }) };
+        log.append(&AuditHeader { timestamp: unix_now(), action: "start", build: BuildInfo::current() });
+        log
+    }
+
+    fn append(&self, record: &impl Serialize) {
+        let Some(path) = &self.inner.path else {
+            return;
+        };
+        let line = serde_json::to_string(record).unwrap_or_default();
+        let written = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut file| writeln!(file, "{}", line));
+        if let Err(e) = written {
+            warn!("Failed to append to audit log {}: {}", path.display(), e);
+        }
+    }
+
+    pub fn record(&self, caller: &str, action: &str, outcome: &str) {
+        let entry = AuditEntry {
+            timestamp: unix_now(),
+            caller: caller.to_string(),
+            action: action.to_string(),
+            outcome: outcome.to_string(),
+        };
+        info!(target: "audit", "{} by {}: {}", entry.action, entry.caller, entry.outcome);
+        self.append(&entry);
+        let mut recent = self.inner.recent.lock().unwrap_or_else(|e| e.into_inner());
+        if recent.len() == RECENT_ENTRIES {
+            recent.pop_front();
+        }
+        recent.push_back(entry);
+    }
+
+    /// Most recent entries, oldest first.
+    pub fn recent(&self) -> Vec<AuditEntry> {
+        self.inner.recent.lock().unwrap_or_else(|e| e.into_inner()).iter().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entries_are_appended_to_file() {
+        let path = std::env::temp_dir().join(format!("asam-audit-{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let audit = AuditLog::new(Some(path.clone()));
+        audit.record("10.0.0.1:5000", "POST /admin/pause", "ok");
+        audit.record("10.0.0.2:5000", "POST /admin/resume", "unauthorized");
+
+        let lines: Vec<serde_json::Value> = std::fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0]["action"], "start");
+        assert_eq!(lines[0]["version"], crate::version::VERSION);
+        assert_eq!(lines[2]["caller"], "10.0.0.2:5000");
+        assert_eq!(lines[2]["outcome"], "unauthorized");
+        assert_eq!(audit.recent()[0].action, "POST /admin/pause");
+        std::fs::remove_file(&path).unwrap();
+    }
+}
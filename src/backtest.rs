@@ -0,0 +1,617 @@
+//! Replays historical pool yields through the decision path, one day per cycle.
+//!
+//! History comes from a CSV file of `date,pool_id,apy,tvl` rows or from DefiLlama's
+//! per-pool `/chart/{pool}` endpoint. Neither names a pool's protocol and chain, so
+//! every pool id is labelled `PROTOCOL@CHAIN`, either by the id itself or by a
+//! `--pool ID=PROTOCOL@CHAIN` label. Chart responses are cached on disk, since the
+//! endpoint is slow and past days do not change.
+//!
+//! [`History::scenario`] turns the history into a [`Scenario`] with a level per pool
+//! and day, a fixed balance and the bridge fee and swap cost of the simulation's fee
+//! model; [`backtest`] runs it through [`simulation::simulate`] and compares the
+//! outcome with holding the pool that had the best APY on the first day.
+
+use anyhow::{anyhow, bail, Context, Result};
+use log::{debug, info};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::config::{Config, DEFAULT_STRATEGY};
+use crate::report::CycleDecision;
+use crate::secret::redact_url;
+use crate::simulation::{self, decision_kind, BalanceLevel, FeeLevel, PoolLevel, Scenario, SwapCostLevel};
+use crate::version::USER_AGENT;
+
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+const DAYS_PER_YEAR: f64 = 365.0;
+
+/// DefiLlama's yields API.
+pub const DEFAULT_YIELDS_URL: &str = "https://yields.llama.fi";
+
+/// Unix time of midnight UTC on `date`, given as `YYYY-MM-DD` between 1970 and 9999.
+pub fn parse_date(date: &str) -> Result<u64> {
+    let invalid = || anyhow!("'{}' is not a YYYY-MM-DD date", date);
+    let parts: Vec<i64> = date.split('-').map(|part| part.parse().map_err(|_| invalid())).collect::<Result<_>>()?;
+    let [year, month, day] = parts[..] else {
+        return Err(invalid());
+    };
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || !(1970..=9999).contains(&year) {
+        return Err(invalid());
+    }
+    // Days since the epoch of a proleptic Gregorian date, with March as the first month
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let year_of_era = y - era * 400;
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    Ok((era * 146_097 + day_of_era - 719_468) as u64 * SECS_PER_DAY)
+}
+
+/// `YYYY-MM-DD` of the UTC day holding unix time `secs`.
+pub fn format_date(secs: u64) -> String {
+    let days = (secs / SECS_PER_DAY) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Protocol and chain of a pool, as the optimizer names them.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PoolLabel {
+    pub protocol: String,
+    pub chain: String,
+}
+
+impl PoolLabel {
+    /// Parses `PROTOCOL@CHAIN`.
+    pub fn parse(text: &str) -> Result<Self> {
+        match text.rsplit_once('@') {
+            Some((protocol, chain)) if !protocol.trim().is_empty() && !chain.trim().is_empty() => {
+                Ok(Self { protocol: protocol.trim().to_string(), chain: chain.trim().to_string() })
+            }
+            _ => bail!("'{}' is not PROTOCOL@CHAIN", text),
+        }
+    }
+}
+
+/// Parses a `--pool ID=PROTOCOL@CHAIN` label; a bare `PROTOCOL@CHAIN` labels itself.
+pub fn parse_pool_arg(arg: &str) -> Result<(String, PoolLabel)> {
+    match arg.split_once('=') {
+        Some((id, label)) => Ok((id.trim().to_string(), PoolLabel::parse(label)?)),
+        None => Ok((arg.trim().to_string(), PoolLabel::parse(arg)?)),
+    }
+}
+
+/// One pool's APY and TVL on one day.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HistoryPoint {
+    /// Unix time of the day's midnight UTC.
+    pub date: u64,
+    pub pool_id: String,
+    pub apy: Option<f64>,
+    pub tvl: f64,
+}
+
+/// Daily pool history with a label for every pool.
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    pub labels: BTreeMap<String, PoolLabel>,
+    /// By date, then pool id; one point per pool and day.
+    pub points: Vec<HistoryPoint>,
+}
+
+impl History {
+    /// Labels every pool and keeps the last point of each pool and day. Ids without a
+    /// label must be `PROTOCOL@CHAIN` themselves.
+    pub fn new(mut labels: BTreeMap<String, PoolLabel>, points: Vec<HistoryPoint>) -> Result<Self> {
+        let mut daily: BTreeMap<(u64, String), HistoryPoint> = BTreeMap::new();
+        for mut point in points {
+            if !labels.contains_key(&point.pool_id) {
+                let label = PoolLabel::parse(&point.pool_id)
+                    .with_context(|| format!("No label for pool {}; pass --pool ID=PROTOCOL@CHAIN", point.pool_id))?;
+                labels.insert(point.pool_id.clone(), label);
+            }
+            point.date -= point.date % SECS_PER_DAY;
+            daily.insert((point.date, point.pool_id.clone()), point);
+        }
+        if daily.is_empty() {
+            bail!("The history has no data points");
+        }
+        Ok(Self { labels, points: daily.into_values().collect() })
+    }
+
+    /// Reads `date,pool_id,apy,tvl` rows after a header line. An empty APY is unknown.
+    pub fn from_csv(text: &str, labels: BTreeMap<String, PoolLabel>) -> Result<Self> {
+        let mut points = Vec::new();
+        for (i, line) in text.lines().enumerate().skip(1) {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let row = || format!("row {}", i + 1);
+            let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+            let [date, pool_id, apy, tvl] = fields[..] else {
+                bail!("{}: expected date,pool_id,apy,tvl", row());
+            };
+            points.push(HistoryPoint {
+                date: parse_date(date).with_context(row)?,
+                pool_id: pool_id.to_string(),
+                apy: match apy {
+                    "" => None,
+                    apy => Some(apy.parse().with_context(|| format!("{}: invalid APY '{}'", row(), apy))?),
+                },
+                tvl: tvl.parse().with_context(|| format!("{}: invalid TVL '{}'", row(), tvl))?,
+            });
+        }
+        Self::new(labels, points)
+    }
+
+    pub fn load_csv(path: &Path, labels: BTreeMap<String, PoolLabel>) -> Result<Self> {
+        let text = std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        Self::from_csv(&text, labels).with_context(|| format!("Invalid history {}", path.display()))
+    }
+
+    /// Drops the days before `from` and after `to`, both unix times of a day.
+    pub fn between(mut self, from: Option<u64>, to: Option<u64>) -> Result<Self> {
+        self.points.retain(|p| from.is_none_or(|from| p.date >= from) && to.is_none_or(|to| p.date <= to));
+        if self.points.is_empty() {
+            bail!("The history has no data points in the requested range");
+        }
+        Ok(self)
+    }
+
+    pub fn first_date(&self) -> u64 {
+        self.points.iter().map(|p| p.date).min().unwrap_or_default()
+    }
+
+    pub fn last_date(&self) -> u64 {
+        self.points.iter().map(|p| p.date).max().unwrap_or_default()
+    }
+
+    /// Days covered, the last one included.
+    pub fn days(&self) -> u64 {
+        (self.last_date() - self.first_date()) / SECS_PER_DAY + 1
+    }
+
+    /// The history as a scenario stepping one day per cycle: `balance_eth` throughout,
+    /// `bridge_fee_eth` per route and `swap_cost_pct` of a route into another underlying.
+    pub fn scenario(&self, name: &str, balance_eth: f64, bridge_fee_eth: f64, swap_cost_pct: f64) -> Scenario {
+        let start = self.first_date();
+        let pools = self
+            .points
+            .iter()
+            .map(|point| {
+                let label = &self.labels[&point.pool_id];
+                PoolLevel {
+                    day: ((point.date - start) / SECS_PER_DAY) as f64,
+                    protocol: label.protocol.clone(),
+                    chain: label.chain.clone(),
+                    apy: point.apy,
+                    tvl: point.tvl,
+                    asset: None,
+                }
+            })
+            .collect();
+        Scenario {
+            name: name.to_string(),
+            days: self.days() as f64,
+            interval_secs: SECS_PER_DAY,
+            start,
+            balances: vec![BalanceLevel { day: 0.0, eth: balance_eth }],
+            pools,
+            bridge_fees: vec![FeeLevel { day: 0.0, eth: bridge_fee_eth }],
+            swap_costs: vec![SwapCostLevel { day: 0.0, pct: swap_cost_pct }],
+            outages: Vec::new(),
+            prices: BTreeMap::new(),
+        }
+    }
+
+    /// The pool with the best APY on the first day.
+    fn first_best(&self) -> Option<&HistoryPoint> {
+        let start = self.first_date();
+        self.points
+            .iter()
+            .filter(|p| p.date == start)
+            .max_by(|a, b| a.apy.unwrap_or(0.0).total_cmp(&b.apy.unwrap_or(0.0)))
+    }
+
+    /// APY of `pool_id` on every day, each holding its last known value.
+    fn daily_apy(&self, pool_id: &str) -> Vec<f64> {
+        let start = self.first_date();
+        let mut apys = vec![0.0; self.days() as usize];
+        for point in self.points.iter().filter(|p| p.pool_id == pool_id) {
+            let day = ((point.date - start) / SECS_PER_DAY) as usize;
+            let apy = point.apy.unwrap_or(0.0);
+            apys[day..].iter_mut().for_each(|slot| *slot = apy);
+        }
+        apys
+    }
+}
+
+/// Fetched pool charts, kept as files named after the pool in `dir`.
+#[derive(Debug, Clone)]
+pub struct ChartCache {
+    pub dir: PathBuf,
+    /// Older files are fetched again; today's point changes until the day is over.
+    pub max_age: Duration,
+}
+
+impl ChartCache {
+    fn path(&self, pool_id: &str) -> PathBuf {
+        let safe = |c: char| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' };
+        let name: String = pool_id.chars().map(safe).collect();
+        self.dir.join(format!("chart-{}.json", name))
+    }
+
+    fn get(&self, pool_id: &str) -> Option<String> {
+        let path = self.path(pool_id);
+        let modified = std::fs::metadata(&path).and_then(|meta| meta.modified()).ok()?;
+        let age = SystemTime::now().duration_since(modified).unwrap_or_default();
+        if age > self.max_age {
+            return None;
+        }
+        std::fs::read_to_string(path).ok()
+    }
+
+    fn put(&self, pool_id: &str, body: &str) -> Result<()> {
+        std::fs::create_dir_all(&self.dir).with_context(|| format!("Failed to create {}", self.dir.display()))?;
+        let path = self.path(pool_id);
+        std::fs::write(&path, body).with_context(|| format!("Failed to write {}", path.display()))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ChartResponse {
+    data: Vec<ChartPoint>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ChartPoint {
+    /// ISO 8601, e.g. `2024-01-31T23:01:47.089Z`.
+    timestamp: String,
+    tvl_usd: f64,
+    apy: Option<f64>,
+}
+
+/// Daily history of each labelled pool from DefiLlama's `{yields_url}/chart/{pool}`,
+/// served from `cache` when fetched recently.
+pub async fn fetch_llama(yields_url: &str, labels: BTreeMap<String, PoolLabel>, cache: &ChartCache) -> Result<History> {
+    let client = Client::builder().timeout(Duration::from_secs(60)).user_agent(USER_AGENT).build()?;
+    let mut points = Vec::new();
+    for pool_id in labels.keys() {
+        let body = match cache.get(pool_id) {
+            Some(body) => {
+                debug!("Chart of pool {} served from {}", pool_id, cache.dir.display());
+                body
+            }
+            None => {
+                let url = format!("{}/chart/{}", yields_url.trim_end_matches('/'), pool_id);
+                info!("Fetching the history of pool {} from {}", pool_id, redact_url(&url));
+                let response = client.get(&url).send().await?.error_for_status()?;
+                let body = response.text().await?;
+                cache.put(pool_id, &body)?;
+                body
+            }
+        };
+        points.extend(parse_chart(pool_id, &body)?);
+    }
+    History::new(labels, points)
+}
+
+/// Daily points of a `/chart/{pool}` response body; a malformed body or date is an
+/// error. JSON numbers are always finite, so the points are too.
+fn parse_chart(pool_id: &str, body: &str) -> Result<Vec<HistoryPoint>> {
+    let chart: ChartResponse =
+        serde_json::from_str(body).with_context(|| format!("Unexpected chart response for pool {}", pool_id))?;
+    let mut points = Vec::new();
+    for point in chart.data {
+        let date = point.timestamp.get(..10).unwrap_or(&point.timestamp);
+        points.push(HistoryPoint {
+            date: parse_date(date).with_context(|| format!("Chart of pool {}", pool_id))?,
+            pool_id: pool_id.to_string(),
+            apy: point.apy,
+            tvl: point.tvl_usd,
+        });
+    }
+    Ok(points)
+}
+
+/// Makes the `[[strategies]]` entry `name` the one that acts, or none of them for
+/// [`DEFAULT_STRATEGY`].
+pub fn select_strategy(config: &mut Config, name: &str) -> Result<()> {
+    if name != DEFAULT_STRATEGY && !config.strategies.iter().any(|named| named.name == name) {
+        let known: Vec<&str> = config.strategies.iter().map(|named| named.name.as_str()).collect();
+        bail!("No strategy '{}'; the config has {}", name, [&[DEFAULT_STRATEGY], &known[..]].concat().join(", "));
+    }
+    for named in &mut config.strategies {
+        named.active = named.name == name;
+    }
+    Ok(())
+}
+
+/// Fee model and position of a backtest.
+#[derive(Debug, Clone)]
+pub struct BacktestOptions {
+    pub balance_eth: f64,
+    pub bridge_fee_eth: f64,
+    /// Round-trip swap cost of a route into another underlying, in percent.
+    pub swap_cost_pct: f64,
+}
+
+/// One day of the backtest.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TimelineEntry {
+    pub date: String,
+    pub day: u64,
+    /// Decision kind as in the simulation summary.
+    pub decision: String,
+    /// Why a route was blocked or declined, else the route or best pool.
+    pub detail: String,
+    /// Chain holding the funds after the day's decision.
+    pub chain: String,
+    pub apy: Option<f64>,
+}
+
+/// Holding the pool that was best on the first day, without moving.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Baseline {
+    pub pool_id: String,
+    pub protocol: String,
+    pub chain: String,
+    pub yield_eth: f64,
+    pub apy: f64,
+}
+
+/// Outcome of [`backtest`].
+#[derive(Debug, Clone, Serialize)]
+pub struct BacktestReport {
+    /// `[[strategies]]` name of the strategy that decided.
+    pub strategy: String,
+    pub from: String,
+    pub to: String,
+    pub days: u64,
+    pub balance_eth: f64,
+    pub rebalances: usize,
+    /// Bridge fees and swap costs of every rebalance.
+    pub fees_eth: f64,
+    /// Earned at the APY of the chain holding the funds each day, before fees.
+    pub yield_eth: f64,
+    /// Yield after fees, as an APY on the balance over the whole period.
+    pub realized_apy: f64,
+    pub baseline: Option<Baseline>,
+    pub timeline: Vec<TimelineEntry>,
+}
+
+/// APY of `eth` earned on `balance_eth` over `days`.
+fn annualized(eth: f64, balance_eth: f64, days: u64) -> f64 {
+    if balance_eth <= 0.0 || days == 0 {
+        return 0.0;
+    }
+    eth / balance_eth / (days as f64 / DAYS_PER_YEAR) * 100.0
+}
+
+/// Runs `history` day by day through the decision path of `config`'s acting strategy.
+pub fn backtest(config: &Config, history: &History, options: &BacktestOptions) -> Result<BacktestReport> {
+    let strategy = config.active_strategy().map_or(DEFAULT_STRATEGY, |named| named.name.as_str());
+    let scenario = history.scenario(
+        &format!("backtest-{}", strategy),
+        options.balance_eth,
+        options.bridge_fee_eth,
+        options.swap_cost_pct,
+    );
+    let summary = simulation::simulate(config, &scenario)?;
+    let days = history.days();
+    let fees_eth = summary.fees_eth + summary.swap_costs_eth;
+    let baseline = history.first_best().map(|first| {
+        let label = &history.labels[&first.pool_id];
+        let yield_eth = history
+            .daily_apy(&first.pool_id)
+            .iter()
+            .map(|apy| options.balance_eth * apy / 100.0 / DAYS_PER_YEAR)
+            .sum();
+        Baseline {
+            pool_id: first.pool_id.clone(),
+            protocol: label.protocol.clone(),
+            chain: label.chain.clone(),
+            yield_eth,
+            apy: annualized(yield_eth, options.balance_eth, days),
+        }
+    });
+    let timeline = summary
+        .timeline
+        .iter()
+        .map(|outcome| {
+            let day = outcome.day.round() as u64;
+            let detail = match &outcome.decision {
+                Some(CycleDecision::Blocked(reason) | CycleDecision::Declined(reason)) => reason.clone(),
+                _ => outcome.detail.clone(),
+            };
+            TimelineEntry {
+                date: format_date(history.first_date() + day * SECS_PER_DAY),
+                day,
+                decision: decision_kind(outcome.decision.as_ref()).to_string(),
+                detail,
+                chain: outcome.chain.clone(),
+                apy: outcome.apy,
+            }
+        })
+        .collect();
+    Ok(BacktestReport {
+        strategy: strategy.to_string(),
+        from: format_date(history.first_date()),
+        to: format_date(history.last_date()),
+        days,
+        balance_eth: options.balance_eth,
+        rebalances: summary.routes.len(),
+        fees_eth,
+        yield_eth: summary.earned_eth,
+        realized_apy: annualized(summary.earned_eth - fees_eth, options.balance_eth, days),
+        baseline,
+        timeline,
+    })
+}
+
+/// Writes the timeline to `path`: CSV for a `.csv` file, JSON otherwise.
+pub fn export_timeline(report: &BacktestReport, path: &Path) -> Result<()> {
+    let is_csv = path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("csv"));
+    let contents = if is_csv {
+        let mut csv = String::from("date,day,decision,chain,apy,detail\n");
+        for entry in &report.timeline {
+            csv.push_str(&format!(
+                "{},{},{},{},{},{}\n",
+                entry.date,
+                entry.day,
+                entry.decision,
+                entry.chain.replace(',', " "),
+                entry.apy.map(|apy| apy.to_string()).unwrap_or_default(),
+                entry.detail.replace(',', " ")
+            ));
+        }
+        csv
+    } else {
+        serde_json::to_string_pretty(&report.timeline)?
+    };
+    std::fs::write(path, contents).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agents::test_utils::arbitrary_json;
+    use proptest::prelude::*;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[test]
+    fn test_dates() {
+        assert_eq!(parse_date("1970-01-01").unwrap(), 0);
+        assert_eq!(parse_date("2024-02-29").unwrap(), 1_709_164_800);
+        assert_eq!(format_date(1_709_164_800 + 3600), "2024-02-29");
+        assert_eq!(format_date(parse_date("2023-12-31").unwrap()), "2023-12-31");
+        assert!(parse_date("2024-13-01").is_err());
+        assert!(parse_date("yesterday").is_err());
+    }
+
+    /// Ten days: Ethereum's pool pays 4% throughout, Arbitrum's 3% until day 4 and 8%
+    /// from then on.
+    const SWITCH_CSV: &str = "date,pool_id,apy,tvl
+2024-01-01,eth-pool,4.0,500000000
+2024-01-01,Aave@Arbitrum,3.0,200000000
+2024-01-05,Aave@Arbitrum,8.0,200000000
+2024-01-10,eth-pool,4.0,500000000
+";
+
+    fn switch_history() -> History {
+        let labels = BTreeMap::from([("eth-pool".to_string(), PoolLabel::parse("Aave@Ethereum").unwrap())]);
+        History::from_csv(SWITCH_CSV, labels).unwrap()
+    }
+
+    #[test]
+    fn test_backtest_finds_the_switch_and_counts_fees() {
+        let history = switch_history();
+        assert_eq!(history.days(), 10);
+        let options = BacktestOptions { balance_eth: 2.0, bridge_fee_eth: 0.002, swap_cost_pct: 0.0 };
+        let report = backtest(&Config::default(), &history, &options).unwrap();
+
+        assert_eq!((report.from.as_str(), report.to.as_str()), ("2024-01-01", "2024-01-10"));
+        assert_eq!(report.rebalances, 1);
+        let routed: Vec<(&str, &str)> = report
+            .timeline
+            .iter()
+            .filter(|entry| entry.decision == "route_initiated")
+            .map(|entry| (entry.date.as_str(), entry.chain.as_str()))
+            .collect();
+        assert_eq!(routed, [("2024-01-05", "Arbitrum")]);
+        assert_eq!(report.timeline.len(), 10);
+        assert_eq!(report.timeline[3].chain, "Ethereum");
+
+        // One route at the bridge fee; four days at 4% and six at 8% on 2 ETH
+        assert_eq!(report.fees_eth, 0.002);
+        let earned = 2.0 * (4.0 * 0.04 + 6.0 * 0.08) / 365.0;
+        assert!((report.yield_eth - earned).abs() < 1e-12, "{}", report.yield_eth);
+        let realized = (earned - 0.002) / 2.0 / (10.0 / 365.0) * 100.0;
+        assert!((report.realized_apy - realized).abs() < 1e-9, "{}", report.realized_apy);
+
+        // Ethereum's pool was best on day one and pays 4% throughout
+        let baseline = report.baseline.unwrap();
+        assert_eq!(baseline.pool_id, "eth-pool");
+        assert!((baseline.yield_eth - 2.0 * 0.04 * 10.0 / 365.0).abs() < 1e-12);
+        assert!((baseline.apy - 4.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_history_needs_labels_and_valid_rows() {
+        let err = History::from_csv("date,pool_id,apy,tvl\n2024-01-01,abc,1,2\n", BTreeMap::new()).unwrap_err();
+        assert!(format!("{:#}", err).contains("No label for pool abc"), "{:#}", err);
+        let err = History::from_csv("date,pool_id,apy,tvl\n2024-01-01,A@B,x,2\n", BTreeMap::new()).unwrap_err();
+        assert!(format!("{:#}", err).contains("row 2"), "{:#}", err);
+        assert_eq!(parse_pool_arg("uuid-1=Aave V3@Base").unwrap().1.protocol, "Aave V3");
+
+        let history = switch_history().between(Some(parse_date("2024-01-05").unwrap()), None).unwrap();
+        assert_eq!(history.days(), 6);
+    }
+
+    #[tokio::test]
+    async fn test_llama_charts_are_cached() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/chart/pool-1"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "status": "success",
+                "data": [
+                    { "timestamp": "2024-01-01T23:01:47.089Z", "tvlUsd": 1e8, "apy": 3.5, "apyBase": 3.5 },
+                    { "timestamp": "2024-01-02T23:01:12.000Z", "tvlUsd": 1.1e8, "apy": 3.7, "apyBase": 3.7 },
+                ],
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let dir = std::env::temp_dir().join(format!("asam-charts-{}", std::process::id()));
+        let cache = ChartCache { dir: dir.clone(), max_age: Duration::from_secs(3600) };
+        let labels = BTreeMap::from([("pool-1".to_string(), PoolLabel::parse("Aave@Ethereum").unwrap())]);
+
+        let fetched = fetch_llama(&server.uri(), labels.clone(), &cache).await.unwrap();
+        assert_eq!(fetched.days(), 2);
+        assert_eq!(fetched.points[1].apy, Some(3.7));
+        // The mock expects one request: the second history comes from the file
+        let cached = fetch_llama(&server.uri(), labels, &cache).await.unwrap();
+        assert_eq!(cached.points, fetched.points);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    proptest! {
+        /// Any string is a valid date or an error, and a valid date formats back to itself.
+        #[test]
+        fn test_dates_round_trip(date in prop_oneof!["[0-9]{1,20}-[0-9]{1,3}-[0-9]{1,3}", ".{0,24}"]) {
+            if let Ok(secs) = parse_date(&date) {
+                prop_assert_eq!(secs % SECS_PER_DAY, 0);
+                let formatted = format_date(secs);
+                prop_assert_eq!(parse_date(&formatted).unwrap(), secs);
+            }
+        }
+
+        /// A chart body is either an error or points on whole days, whatever it holds.
+        #[test]
+        fn test_chart_parser_never_panics(
+            body in arbitrary_json(&["data", "timestamp", "tvlUsd", "apy", "status"]),
+            garbage in ".{0,64}",
+        ) {
+            if let Ok(points) = parse_chart("pool", &body.to_string()) {
+                for point in points {
+                    prop_assert_eq!(point.date % SECS_PER_DAY, 0);
+                    prop_assert!(point.tvl.is_finite() && point.apy.is_none_or(f64::is_finite));
+                }
+            }
+            prop_assert!(parse_chart("pool", &garbage).map_or(true, |points| points.is_empty()));
+        }
+    }
+}
@@ -0,0 +1,232 @@
+//! Holds routes back when moving funds costs more than it earns.
+//!
+//! Every cycle that decides on the pools weighs the fees paid over the trailing
+//! [`WINDOW_SECS`] against the yield captured in that time: realized on the holding
+//! periods that closed, and projected on the open ones, whose value is not marked to
+//! market. Once the fees pass `strategy.max_cost_ratio` of those gains the
+//! [`CostBenefitGuard`] engages and routes are only reported, as in observe mode,
+//! until the ratio falls back under the limit or an operator overrides it through
+//! `POST /admin/budget/override`.
+
+use ethers::core::types::U256;
+use serde::Serialize;
+
+use crate::units::format_eth;
+use crate::yield_tracker::YieldReport;
+
+/// Length of the trailing window costs and gains are summed over.
+pub const WINDOW_SECS: u64 = 30 * 24 * 60 * 60;
+const SECS_PER_YEAR: f64 = 365.0 * 24.0 * 60.0 * 60.0;
+
+/// Costs against gains over the window ending at `at`, amounts in ETH.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BudgetCheck {
+    pub at: u64,
+    pub window_secs: u64,
+    /// Transaction fees paid by the Safe.
+    pub transaction_fees: f64,
+    /// Fees recorded against holding periods, such as bridge fees.
+    pub route_fees: f64,
+    /// Gain of the holding periods that closed, for the part held in the window.
+    pub realized_gain: f64,
+    /// What the open holding periods earn at their projected APY over the window.
+    pub projected_gain: f64,
+    /// Costs over gains; unset when there are no gains to weigh the costs against.
+    pub ratio: Option<f64>,
+    pub max_ratio: f64,
+}
+
+impl BudgetCheck {
+    /// Weighs `fees_wei`, the transaction fees paid since the window started, against
+    /// the holding periods in `reports`.
+    pub fn compute(fees_wei: U256, reports: &[YieldReport], now: u64, max_ratio: f64) -> Self {
+        let start = now.saturating_sub(WINDOW_SECS);
+        let (mut route_fees, mut realized_gain, mut projected_gain) = (0.0, 0.0, 0.0);
+        for report in reports {
+            let end = report.exited_at.unwrap_or(now);
+            let overlap = end.min(now).saturating_sub(report.entered_at.max(start));
+            if overlap == 0 {
+                continue;
+            }
+            let share = match report.held_secs {
+                0 => 1.0,
+                held => (overlap as f64 / held as f64).min(1.0),
+            };
+            route_fees += report.fees * share;
+            if report.exited_at.is_some() {
+                let gain = report.end_value - report.entry_value - report.net_flows;
+                realized_gain += gain * share;
+            } else if let Some(apy) = report.projected_apy {
+                projected_gain += report.end_value * apy / 100.0 * overlap as f64 / SECS_PER_YEAR;
+            }
+        }
+        let transaction_fees = format_eth(fees_wei).parse().unwrap_or_default();
+        let gains = realized_gain + projected_gain;
+        Self {
+            at: now,
+            window_secs: WINDOW_SECS,
+            transaction_fees,
+            route_fees,
+            realized_gain,
+            projected_gain,
+            ratio: (gains > 0.0).then(|| (transaction_fees + route_fees) / gains),
+            max_ratio,
+        }
+    }
+
+    pub fn costs(&self) -> f64 {
+        self.transaction_fees + self.route_fees
+    }
+
+    pub fn gains(&self) -> f64 {
+        self.realized_gain + self.projected_gain
+    }
+
+    /// Whether the costs are past `max_ratio` of the gains, or there are costs and no gains.
+    pub fn exceeded(&self) -> bool {
+        self.costs() > 0.0 && self.ratio.is_none_or(|ratio| ratio > self.max_ratio)
+    }
+
+    /// One line for logs and alerts.
+    pub fn describe(&self) -> String {
+        let ratio = self.ratio.map_or("n/a".to_string(), |ratio| format!("{:.2}", ratio));
+        format!(
+            "fees {:.6} ETH against gains {:.6} ETH over {} days (ratio {}, limit {:.2})",
+            self.costs(),
+            self.gains(),
+            self.window_secs / (24 * 60 * 60),
+            ratio,
+            self.max_ratio
+        )
+    }
+}
+
+/// Change of the guard caused by a check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GuardChange {
+    Engaged,
+    Released,
+}
+
+/// Whether routes are held back for cost. Lives in the status snapshot, where the
+/// admin endpoint can override it.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct CostBenefitGuard {
+    /// The last check exceeded the limit.
+    pub engaged: bool,
+    /// An operator let routes through while engaged; cleared once the ratio recovers.
+    pub overridden: bool,
+    pub last_check: Option<BudgetCheck>,
+}
+
+impl CostBenefitGuard {
+    /// Takes in the latest check.
+    pub fn record(&mut self, check: BudgetCheck) -> Option<GuardChange> {
+        let exceeded = check.exceeded();
+        self.last_check = Some(check);
+        match (self.engaged, exceeded) {
+            (false, true) => {
+                self.engaged = true;
+                Some(GuardChange::Engaged)
+            }
+            (true, false) => {
+                self.engaged = false;
+                self.overridden = false;
+                Some(GuardChange::Released)
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether fund-moving actions should be held back.
+    pub fn blocks(&self) -> bool {
+        self.engaged && !self.overridden
+    }
+
+    /// Lets routes through until the ratio recovers. False when the guard is not engaged.
+    pub fn override_guard(&mut self) -> bool {
+        if !self.engaged {
+            return false;
+        }
+        self.overridden = true;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: u64 = 24 * 60 * 60;
+    const NOW: u64 = 100 * DAY;
+
+    fn period(entered_at: u64, exited_at: Option<u64>, entry_value: f64, end_value: f64) -> YieldReport {
+        YieldReport {
+            chain: "Arbitrum".to_string(),
+            protocol: Some("Aave".to_string()),
+            entered_at,
+            exited_at,
+            held_secs: exited_at.unwrap_or(NOW) - entered_at,
+            entry_value,
+            end_value,
+            net_flows: 0.0,
+            fees: 0.0,
+            realized_return: 0.0,
+            realized_apy: None,
+            projected_apy: Some(7.3),
+            partial: false,
+        }
+    }
+
+    fn eth(amount: f64) -> U256 {
+        ethers::utils::parse_ether(amount).unwrap()
+    }
+
+    #[test]
+    fn test_gains_count_only_the_window() {
+        let reports = vec![
+            // Closed 40 days ago, outside the window
+            period(50 * DAY, Some(60 * DAY), 10.0, 11.0),
+            // Held 20 days, half of them in the window
+            period(60 * DAY, Some(80 * DAY), 10.0, 10.2),
+            // Open for the whole window: 10 ETH at 7.3% for 30 days
+            period(65 * DAY, None, 10.0, 10.0),
+        ];
+        let check = BudgetCheck::compute(eth(0.01), &reports, NOW, 0.75);
+        assert!((check.realized_gain - 0.1).abs() < 1e-9, "{:?}", check);
+        assert!((check.projected_gain - 0.06).abs() < 1e-9, "{:?}", check);
+        assert!((check.transaction_fees - 0.01).abs() < 1e-12);
+        assert!((check.ratio.unwrap() - 0.0625).abs() < 1e-9);
+        assert!(!check.exceeded());
+
+        // No gains at all: any fee exceeds the budget, none does not
+        assert!(BudgetCheck::compute(eth(0.01), &[], NOW, 0.75).exceeded());
+        assert!(!BudgetCheck::compute(U256::zero(), &[], NOW, 0.75).exceeded());
+    }
+
+    #[test]
+    fn test_guard_engages_overrides_and_releases() {
+        let reports = vec![period(60 * DAY, Some(80 * DAY), 10.0, 10.2)];
+        let over = || BudgetCheck::compute(eth(0.08), &reports, NOW, 0.75);
+        let under = || BudgetCheck::compute(eth(0.07), &reports, NOW, 0.75);
+        assert!(over().exceeded() && !under().exceeded());
+
+        let mut guard = CostBenefitGuard::default();
+        assert!(!guard.override_guard());
+        assert_eq!(guard.record(under()), None);
+        assert!(!guard.blocks());
+        assert_eq!(guard.record(over()), Some(GuardChange::Engaged));
+        assert!(guard.blocks());
+        assert_eq!(guard.record(over()), None);
+        assert!(guard.blocks());
+
+        // The override holds while the ratio stays high, and ends with it
+        assert!(guard.override_guard());
+        assert_eq!(guard.record(over()), None);
+        assert!(!guard.blocks());
+        assert_eq!(guard.record(under()), Some(GuardChange::Released));
+        assert!(!guard.overridden);
+        assert_eq!(guard.record(over()), Some(GuardChange::Engaged));
+        assert!(guard.blocks());
+    }
+}
@@ -0,0 +1,862 @@
+use anyhow::{Context, Result};
+use clap::{Args, Parser, Subcommand};
+use ethers::core::types::{Address, U256};
+use ethers::providers::Middleware;
+use serde::Serialize;
+use std::fmt;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::agents::cross_chain_router::CrossChainRouter;
+use crate::agents::defi_optimizer::PoolData;
+use crate::backtest::BacktestReport;
+use crate::config::Config;
+use crate::decision_log::{DecisionRecord, Replay};
+use crate::portfolio::{PortfolioState, HOME_CHAIN};
+use crate::report::CycleDecision;
+use crate::simulation::{ForkReport, SimulationSummary};
+use crate::units::TokenAmount;
+
+#[derive(Debug, Clone, Parser)]
+#[command(
+    name = "asam",
+    version = crate::version::SHORT_VERSION,
+    long_version = crate::version::long_version(),
+    about = "Autonomous Smart Account Manager"
+)]
+pub struct Cli {
+    /// Path to a configuration file (overrides ASAM_CONFIG)
+    #[arg(long, global = true, env = "ASAM_CONFIG")]
+    pub config: Option<PathBuf>,
+
+    /// Validate and simulate fund-moving actions without executing them
+    #[arg(long, global = true)]
+    pub dry_run: bool,
+
+    /// Log level filter, e.g. "info" or "asam=debug" (overrides RUST_LOG)
+    #[arg(long, global = true)]
+    pub log_level: Option<String>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum Command {
+    /// Run the monitoring loop (default)
+    Run,
+    /// Print the Safe's balances and exit
+    Balance,
+    /// Print the optimizer's ranked pool table
+    Pools(PoolsArgs),
+    /// Preview or execute a single cross-chain route
+    Route(RouteArgs),
+    /// Run the preflight checks: config, RPC, Safe, pool API, bridge quote, paths, notifiers
+    Check(CheckArgs),
+    /// Print where the funds currently live, as last recorded
+    Position,
+    /// Decide again on a recorded decision with the current config
+    Replay(ReplayArgs),
+    /// Print the chains the router supports and whether routes to them can run
+    Chains(ChainsArgs),
+    /// Replay a scenario file through the decision logic on a simulated clock
+    Simulate(SimulateArgs),
+    /// Replay historical pool yields through the decision logic, one day per cycle
+    Backtest(BacktestArgs),
+    /// Manage the configuration file
+    #[command(subcommand)]
+    Config(ConfigCommand),
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum ConfigCommand {
+    /// Write a commented configuration template
+    Init(ConfigInitArgs),
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct ConfigInitArgs {
+    /// Where to write the template
+    #[arg(long, short, default_value = "asam.toml")]
+    pub output: PathBuf,
+
+    /// Overwrite an existing file
+    #[arg(long)]
+    pub force: bool,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct CheckArgs {
+    /// Send a test message through every configured notifier
+    #[arg(long)]
+    pub notify: bool,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct PoolsArgs {
+    /// Number of pools to show
+    #[arg(long, default_value_t = 10)]
+    pub top: usize,
+
+    /// Only show pools on this chain
+    #[arg(long)]
+    pub chain: Option<String>,
+
+    /// Write the ranked pools to a file (.csv, otherwise JSON)
+    #[arg(long)]
+    pub export: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct RouteArgs {
+    /// Amount of tokens to route
+    #[arg(long)]
+    pub amount: f64,
+
+    /// Source chain
+    #[arg(long)]
+    pub from: String,
+
+    /// Target chain
+    #[arg(long)]
+    pub to: String,
+
+    /// Route even if an identical route completed within `router.route_dedup_window_secs`
+    #[arg(long)]
+    pub force: bool,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct ReplayArgs {
+    /// Decision log written with `storage.decision_log_path`
+    #[arg(long)]
+    pub file: PathBuf,
+
+    /// Replay the last decision made at or before this unix time (default: the latest)
+    #[arg(long)]
+    pub at: Option<u64>,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct ChainsArgs {
+    /// Print JSON instead of a table
+    #[arg(long)]
+    pub json: bool,
+
+    /// Ask each configured RPC endpoint and the bridge quote API whether they answer
+    #[arg(long)]
+    pub probe: bool,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct SimulateArgs {
+    /// Scenario TOML file
+    #[arg(required_unless_present = "fork")]
+    pub scenario: Option<PathBuf>,
+
+    /// Check the Safe's transaction paths on an Anvil fork of this RPC URL
+    #[arg(long, value_name = "RPC_URL")]
+    pub fork: Option<String>,
+
+    /// Print JSON instead of a summary
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct BacktestArgs {
+    /// History CSV of date,pool_id,apy,tvl rows; without it the pools' history comes from DefiLlama
+    #[arg(long, value_name = "FILE")]
+    pub csv: Option<PathBuf>,
+
+    /// Pool to replay as ID=PROTOCOL@CHAIN, e.g. a DefiLlama pool id; repeatable
+    #[arg(long = "pool", value_name = "ID=PROTOCOL@CHAIN", required_unless_present = "csv")]
+    pub pools: Vec<String>,
+
+    /// `[[strategies]]` entry that decides, or "default" for the top-level settings
+    #[arg(long)]
+    pub strategy: Option<String>,
+
+    /// First day to replay, YYYY-MM-DD
+    #[arg(long)]
+    pub from: Option<String>,
+
+    /// Last day to replay, YYYY-MM-DD
+    #[arg(long)]
+    pub to: Option<String>,
+
+    /// Position size in ETH
+    #[arg(long, default_value_t = 10.0)]
+    pub balance: f64,
+
+    /// Bridge fee of every rebalance, in ETH
+    #[arg(long, default_value_t = 0.002)]
+    pub bridge_fee: f64,
+
+    /// Round-trip swap cost of a rebalance into another underlying, in percent
+    #[arg(long, default_value_t = 0.0)]
+    pub swap_cost: f64,
+
+    /// DefiLlama yields API
+    #[arg(long, default_value = crate::backtest::DEFAULT_YIELDS_URL)]
+    pub yields_url: String,
+
+    /// Directory fetched pool histories are cached in
+    #[arg(long, default_value = ".asam-cache")]
+    pub cache_dir: PathBuf,
+
+    /// Fetch pool histories again even if cached today
+    #[arg(long)]
+    pub refresh: bool,
+
+    /// Write the per-day decision timeline to this file, CSV for .csv and JSON otherwise
+    #[arg(long, value_name = "FILE")]
+    pub timeline: Option<PathBuf>,
+
+    /// Print JSON instead of a report
+    #[arg(long)]
+    pub json: bool,
+}
+
+/// Outcome of asking an endpoint under `asam chains --probe`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Probe {
+    pub ok: bool,
+    pub detail: String,
+}
+
+impl Probe {
+    pub fn of<T: fmt::Display>(result: &Result<T>) -> Self {
+        match result {
+            Ok(detail) => Self { ok: true, detail: detail.to_string() },
+            Err(e) => Self { ok: false, detail: format!("{:#}", e) },
+        }
+    }
+}
+
+/// One line of `asam chains`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ChainRow {
+    pub name: String,
+    pub chain_id: u64,
+    /// Whether routes to and from the chain are allowed.
+    pub active: bool,
+    /// Bridge minimum for routes out of the chain.
+    pub min_transfer: f64,
+    /// Whether an RPC endpoint is configured to watch the balance there.
+    pub rpc_configured: bool,
+    /// Unset without `--probe` or an endpoint.
+    pub rpc: Option<Probe>,
+    /// Test quote from the home chain; unset without `--probe` and for the home chain.
+    pub quote: Option<Probe>,
+}
+
+/// The router's chains as `asam chains` lists them, before any probing.
+pub fn chain_rows(router: &CrossChainRouter, config: &Config) -> Vec<ChainRow> {
+    router
+        .chains()
+        .into_iter()
+        .map(|chain| {
+            let configured = config.router.chains.iter().find(|c| c.name == chain.name);
+            let rpc_configured = configured.is_some_and(|c| !c.rpc_urls().is_empty())
+                || (chain.name == HOME_CHAIN && config.safe.rpc_urls().is_ok());
+            ChainRow {
+                name: chain.name,
+                chain_id: chain.chain_id,
+                active: chain.is_active,
+                min_transfer: chain.min_transfer,
+                rpc_configured,
+                rpc: None,
+                quote: None,
+            }
+        })
+        .collect()
+}
+
+pub fn print_chains(rows: &[ChainRow], out: &mut impl Write) -> Result<()> {
+    let probe = |probe: &Option<Probe>| match probe {
+        Some(Probe { ok: true, .. }) => "ok".to_string(),
+        Some(Probe { ok: false, detail }) => format!("FAIL: {}", detail),
+        None => "-".to_string(),
+    };
+    writeln!(
+        out,
+        "{:<12} {:>10} {:<8} {:>12} {:<4} {:<12} QUOTE",
+        "CHAIN", "CHAIN ID", "ACTIVE", "MIN TRANSFER", "RPC", "RPC PROBE"
+    )?;
+    for row in rows {
+        writeln!(
+            out,
+            "{:<12} {:>10} {:<8} {:>12} {:<4} {:<12} {}",
+            row.name,
+            row.chain_id,
+            if row.active { "yes" } else { "no" },
+            row.min_transfer,
+            if row.rpc_configured { "yes" } else { "no" },
+            probe(&row.rpc),
+            probe(&row.quote)
+        )?;
+    }
+    Ok(())
+}
+
+/// Prints the outcome of `asam simulate`: every change of decision, the routes taken
+/// and the totals.
+pub fn print_simulation(summary: &SimulationSummary, out: &mut impl Write) -> Result<()> {
+    writeln!(out, "Scenario {}: {} cycles over {} days", summary.scenario, summary.cycles, summary.days)?;
+    writeln!(out, "{:>7} {:<18} DETAIL", "DAY", "DECISION")?;
+    for change in &summary.changes {
+        let (decision, reason) = match &change.decision {
+            None => ("failed", None),
+            Some(CycleDecision::NoAction) => ("no action", None),
+            Some(CycleDecision::NotWorthMoving) => ("not worth moving", None),
+            Some(CycleDecision::RouteInitiated) => ("route", None),
+            Some(CycleDecision::Blocked(reason)) => ("blocked", Some(reason)),
+            Some(CycleDecision::Declined(reason)) => ("declined", Some(reason)),
+        };
+        let detail = reason.map_or(change.detail.clone(), |reason| format!("{} ({})", change.detail, reason));
+        writeln!(out, "{:>7.2} {:<18} {}", change.day, decision, detail)?;
+    }
+    let counts: Vec<String> = summary.decisions.iter().map(|(kind, n)| format!("{} {}", kind, n)).collect();
+    writeln!(out, "Decisions: {}", counts.join(", "))?;
+    for route in &summary.routes {
+        writeln!(
+            out,
+            "Route on day {:.2}: {} {} -> {} (APY {}), fee {:.6} ETH{}",
+            route.day,
+            route.amount,
+            route.source_chain,
+            route.target_chain,
+            route.apy.map_or("n/a".to_string(), |apy| format!("{:.2}%", apy)),
+            route.fee_eth,
+            match route.swap_cost_eth {
+                cost if cost > 0.0 => format!(", swap {:.6} ETH", cost),
+                _ => String::new(),
+            }
+        )?;
+    }
+    writeln!(out, "Fees paid: {:.6} ETH", summary.fees_eth)?;
+    writeln!(out, "Swap costs: {:.6} ETH", summary.swap_costs_eth)?;
+    writeln!(out, "Yield earned: {:.6} ETH", summary.earned_eth)?;
+    match &summary.final_protocol {
+        Some(protocol) => writeln!(out, "Final position: {} on {}", protocol, summary.final_chain)?,
+        None => writeln!(out, "Final position: {}", summary.final_chain)?,
+    }
+    Ok(())
+}
+
+/// Prints the outcome of `asam backtest`: the rebalances, fees and realized APY next
+/// to holding the first day's best pool.
+pub fn print_backtest(report: &BacktestReport, out: &mut impl Write) -> Result<()> {
+    writeln!(
+        out,
+        "Backtest of strategy {}: {} to {} ({} days), {} ETH",
+        report.strategy, report.from, report.to, report.days, report.balance_eth
+    )?;
+    for entry in report.timeline.iter().filter(|entry| entry.decision == "route_initiated") {
+        writeln!(
+            out,
+            "Rebalance on {}: {} (APY {})",
+            entry.date,
+            entry.detail,
+            entry.apy.map_or("n/a".to_string(), |apy| format!("{:.2}%", apy))
+        )?;
+    }
+    writeln!(out, "Rebalances: {}", report.rebalances)?;
+    writeln!(out, "Fees paid: {:.6} ETH", report.fees_eth)?;
+    writeln!(out, "Yield earned: {:.6} ETH", report.yield_eth)?;
+    writeln!(out, "Realized APY: {:.2}% after fees", report.realized_apy)?;
+    match &report.baseline {
+        Some(baseline) => writeln!(
+            out,
+            "Holding {} on {} ({}): {:.6} ETH, APY {:.2}%",
+            baseline.protocol, baseline.chain, baseline.pool_id, baseline.yield_eth, baseline.apy
+        )?,
+        None => writeln!(out, "Holding: no pool on the first day")?,
+    }
+    Ok(())
+}
+
+/// Prints what `simulate --fork` checked.
+pub fn print_fork_report(report: &ForkReport, out: &mut impl Write) -> Result<()> {
+    writeln!(out, "Fork of chain {} at block {}, Safe {:?}", report.chain_id, report.block, report.safe)?;
+    writeln!(out, "Transfer of 1 ETH: {} gas, executed", report.transfer_gas)?;
+    match (report.approve_gas, report.approve_tx, report.allowance) {
+        (Some(gas), Some(tx), Some(allowance)) => {
+            writeln!(out, "USDC approval: {} gas, mined in {:?}, allowance {}", gas, tx, allowance)?
+        }
+        _ => writeln!(out, "USDC approval: skipped, not a mainnet fork")?,
+    }
+    Ok(())
+}
+
+/// Applies `--top` and `--chain` to an already ranked pool list.
+pub fn select_pools(pools: Vec<PoolData>, args: &PoolsArgs) -> Vec<PoolData> {
+    pools
+        .into_iter()
+        .filter(|p| {
+            args.chain
+                .as_ref()
+                .map(|c| p.chain.eq_ignore_ascii_case(c))
+                .unwrap_or(true)
+        })
+        .take(args.top)
+        .collect()
+}
+
+pub fn print_pools(pools: &[PoolData], out: &mut impl Write) -> Result<()> {
+    writeln!(
+        out,
+        "{:>4}  {:<28} {:<12} {:>8} {:>18} {:>8}",
+        "RANK", "PROTOCOL", "CHAIN", "APY %", "TVL (USD)", "SCORE"
+    )?;
+    for (i, pool) in pools.iter().enumerate() {
+        writeln!(
+            out,
+            "{:>4}  {:<28} {:<12} {:>8.2} {:>18.2} {:>8.2}",
+            i + 1,
+            pool.protocol,
+            pool.chain,
+            pool.apy.unwrap_or(0.0),
+            pool.tvl,
+            pool.score()
+        )?;
+    }
+    Ok(())
+}
+
+pub fn print_portfolio(portfolio: &PortfolioState, out: &mut impl Write) -> Result<()> {
+    if portfolio.entries.is_empty() {
+        writeln!(out, "No position recorded yet; funds are assumed to be on {}", portfolio.current_chain())?;
+        return Ok(());
+    }
+    writeln!(
+        out,
+        "{:<12} {:<20} {:<6} {:>14} {:>12}  PENDING ROUTE",
+        "CHAIN", "PROTOCOL", "TOKEN", "AMOUNT", "SINCE"
+    )?;
+    for entry in &portfolio.entries {
+        writeln!(
+            out,
+            "{:<12} {:<20} {:<6} {:>14.6} {:>12}  {}",
+            entry.chain,
+            entry.protocol.as_deref().unwrap_or("-"),
+            entry.token,
+            entry.amount,
+            entry.since,
+            entry.pending_route.as_deref().unwrap_or("-")
+        )?;
+    }
+    writeln!(out, "Current position: {}", portfolio.current_chain())?;
+    Ok(())
+}
+
+pub fn print_replay(record: &DecisionRecord, replay: &Replay, out: &mut impl Write) -> Result<()> {
+    let inputs = &replay.inputs;
+    writeln!(out, "Decision at {} (cycle {})", record.timestamp, record.cycle_id)?;
+    writeln!(
+        out,
+        "Inputs:   funds on {} (APY {}), best pool on {} ({:.2}%), balance {}",
+        inputs.current_chain,
+        inputs.current_apy.map(|apy| format!("{:.2}%", apy)).unwrap_or_else(|| "-".to_string()),
+        inputs.candidate_chain,
+        inputs.candidate_apy,
+        TokenAmount::native(inputs.balance)
+    )?;
+    writeln!(out, "Recorded: {:?} - {}", replay.recorded, record.reason)?;
+    writeln!(out, "Replayed: {:?} - {}", replay.replayed, replay.reason)?;
+    if replay.differs() {
+        writeln!(out, "The current config decides differently")?;
+    } else {
+        writeln!(out, "Same decision under the current config")?;
+    }
+    Ok(())
+}
+
+pub fn export_pools(pools: &[PoolData], path: &Path) -> Result<()> {
+    let is_csv = path
+        .extension()
+        .map(|ext| ext.eq_ignore_ascii_case("csv"))
+        .unwrap_or(false);
+
+    let contents = if is_csv {
+        let mut csv = String::from("rank,protocol,chain,apy,tvl,score\n");
+        for (i, pool) in pools.iter().enumerate() {
+            csv.push_str(&format!(
+                "{},{},{},{},{},{}\n",
+                i + 1,
+                pool.protocol.replace(',', " "),
+                pool.chain.replace(',', " "),
+                pool.apy.map(|a| a.to_string()).unwrap_or_default(),
+                pool.tvl,
+                pool.score()
+            ));
+        }
+        csv
+    } else {
+        serde_json::to_string_pretty(pools)?
+    };
+
+    std::fs::write(path, contents)
+        .with_context(|| format!("Failed to write pool export to {}", path.display()))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckStatus {
+    Pass,
+    Warn,
+    Fail,
+}
+
+impl fmt::Display for CheckStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CheckStatus::Pass => write!(f, "PASS"),
+            CheckStatus::Warn => write!(f, "WARN"),
+            CheckStatus::Fail => write!(f, "FAIL"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CheckResult {
+    pub name: String,
+    pub status: CheckStatus,
+    pub detail: String,
+}
+
+impl CheckResult {
+    pub fn new(name: impl Into<String>, status: CheckStatus, detail: impl Into<String>) -> Self {
+        Self { name: name.into(), status, detail: detail.into() }
+    }
+}
+
+/// Reports whether the configuration loaded and has the settings every command needs.
+pub fn check_config(loaded: &Result<Config>) -> Vec<CheckResult> {
+    let config = match loaded {
+        Ok(config) => config,
+        Err(e) => return vec![CheckResult::new("config", CheckStatus::Fail, e.to_string())],
+    };
+
+    let mut results = vec![CheckResult::new("config", CheckStatus::Pass, "loaded and validated")];
+    match config.safe.rpc_urls() {
+        Ok(urls) => results.push(CheckResult::new(
+            "config: safe.rpc_url",
+            CheckStatus::Pass,
+            format!("{} endpoint(s)", urls.len()),
+        )),
+        Err(e) => results.push(CheckResult::new("config: safe.rpc_url", CheckStatus::Fail, e.to_string())),
+    }
+    match config.safe.address() {
+        Ok(address) => results.push(CheckResult::new(
+            "config: safe.address",
+            CheckStatus::Pass,
+            format!("{:?}", address),
+        )),
+        Err(e) => results.push(CheckResult::new("config: safe.address", CheckStatus::Fail, e.to_string())),
+    }
+    results
+}
+
+/// Checks RPC connectivity, the chain id and whether the monitored account is a contract.
+pub async fn check_provider<M: Middleware>(
+    provider: &M,
+    account: Address,
+    expected_chain_id: Option<u64>,
+) -> Vec<CheckResult> {
+    let mut results = Vec::new();
+
+    match provider.get_block_number().await {
+        Ok(block) => results.push(CheckResult::new(
+            "rpc: connectivity",
+            CheckStatus::Pass,
+            format!("latest block {}", block),
+        )),
+        Err(e) => {
+            results.push(CheckResult::new(
+                "rpc: connectivity",
+                CheckStatus::Fail,
+                format!("RPC unreachable: {}", e),
+            ));
+            return results;
+        }
+    }
+
+    match provider.get_chainid().await {
+        Ok(chain_id) => {
+            match expected_chain_id {
+                Some(expected) if U256::from(expected) != chain_id => results.push(CheckResult::new(
+                    "rpc: chain id",
+                    CheckStatus::Fail,
+                    format!("expected {}, node reports {}", expected, chain_id),
+                )),
+                _ => results.push(CheckResult::new(
+                    "rpc: chain id",
+                    CheckStatus::Pass,
+                    chain_id.to_string(),
+                )),
+            }
+        }
+        Err(e) => results.push(CheckResult::new(
+            "rpc: chain id",
+            CheckStatus::Fail,
+            format!("Failed to fetch chain id: {}", e),
+        )),
+    }
+
+    // Ownership can only be verified once a signer is configured; for now make
+    // sure the address is at least a deployed contract.
+    match provider.get_code(account, None).await {
+        Ok(code) if !code.is_empty() => results.push(CheckResult::new(
+            "safe: contract",
+            CheckStatus::Pass,
+            format!("{} bytes of code", code.len()),
+        )),
+        Ok(_) => results.push(CheckResult::new(
+            "safe: contract",
+            CheckStatus::Warn,
+            "no contract code at address (EOA or undeployed Safe)",
+        )),
+        Err(e) => results.push(CheckResult::new(
+            "safe: contract",
+            CheckStatus::Fail,
+            format!("Failed to fetch code: {}", e),
+        )),
+    }
+
+    results
+}
+
+pub fn print_checks(results: &[CheckResult], out: &mut impl Write) -> Result<()> {
+    for result in results {
+        writeln!(out, "[{}] {:<24} {}", result.status, result.name, result.detail)?;
+    }
+    let count = |status| results.iter().filter(|r| r.status == status).count();
+    writeln!(
+        out,
+        "{} passed, {} warnings, {} failed",
+        count(CheckStatus::Pass),
+        count(CheckStatus::Warn),
+        count(CheckStatus::Fail)
+    )?;
+    Ok(())
+}
+
+pub fn has_failures(results: &[CheckResult]) -> bool {
+    results.iter().any(|r| r.status == CheckStatus::Fail)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agents::defi_optimizer::DefiOptimizer;
+    use ethers::core::types::{Bytes, U256, U64};
+    use ethers::providers::Provider;
+
+    #[test]
+    fn test_parse_default_is_run() {
+        let cli = Cli::try_parse_from(["asam"]).unwrap();
+        assert!(cli.command.is_none());
+        assert!(!cli.dry_run);
+    }
+
+    #[test]
+    fn test_parse_global_flags_and_subcommands() {
+        let cli = Cli::try_parse_from([
+            "asam", "--log-level", "warn", "pools", "--top", "3", "--chain", "Arbitrum",
+        ])
+        .unwrap();
+        assert_eq!(cli.log_level.as_deref(), Some("warn"));
+        match cli.command {
+            Some(Command::Pools(args)) => {
+                assert_eq!(args.top, 3);
+                assert_eq!(args.chain.as_deref(), Some("Arbitrum"));
+            }
+            other => panic!("unexpected command: {:?}", other),
+        }
+
+        let cli = Cli::try_parse_from([
+            "asam", "route", "--amount", "5", "--from", "Ethereum", "--to", "Optimism", "--dry-run",
+        ])
+        .unwrap();
+        assert!(cli.dry_run);
+        assert!(matches!(cli.command, Some(Command::Route(RouteArgs { amount, force: false, .. })) if amount == 5.0));
+        let cli = Cli::try_parse_from(["asam", "route", "--amount", "5", "--from", "Ethereum", "--to", "Optimism", "--force"])
+            .unwrap();
+        assert!(matches!(cli.command, Some(Command::Route(RouteArgs { force: true, .. }))));
+
+        assert!(Cli::try_parse_from(["asam", "route", "--amount", "5"]).is_err());
+
+        let cli = Cli::try_parse_from(["asam", "replay", "--file", "decisions.jsonl", "--at", "1700000000"]).unwrap();
+        assert!(matches!(cli.command, Some(Command::Replay(ReplayArgs { at: Some(1_700_000_000), .. }))));
+    }
+
+    #[tokio::test]
+    async fn test_pools_handler_with_mock() {
+        let optimizer = DefiOptimizer::with_mock();
+        let pools = optimizer.get_ranked_pools().await.unwrap();
+        let args = PoolsArgs { top: 1, chain: Some("ethereum".to_string()), export: None };
+        let selected = select_pools(pools, &args);
+        assert_eq!(selected.len(), 1);
+
+        let mut out = Vec::new();
+        print_pools(&selected, &mut out).unwrap();
+        let table = String::from_utf8(out).unwrap();
+        assert!(table.contains("Aave"));
+        assert!(!table.contains("Compound"));
+
+        let path = std::env::temp_dir().join(format!("asam-pools-{}.csv", std::process::id()));
+        export_pools(&selected, &path).unwrap();
+        let csv = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+        assert!(csv.starts_with("rank,protocol,chain,apy,tvl,score\n1,Aave,Ethereum,5.2"));
+    }
+
+    #[test]
+    fn test_print_portfolio() {
+        let mut out = Vec::new();
+        print_portfolio(&PortfolioState::default(), &mut out).unwrap();
+        assert!(String::from_utf8(out).unwrap().contains("assumed to be on Ethereum"));
+
+        let mut portfolio = PortfolioState::default();
+        portfolio.reconcile("Ethereum", 2.5, 0.05, 100);
+        let mut out = Vec::new();
+        print_portfolio(&portfolio, &mut out).unwrap();
+        let table = String::from_utf8(out).unwrap();
+        assert!(table.contains("2.500000"), "{}", table);
+        assert!(table.ends_with("Current position: Ethereum\n"), "{}", table);
+        assert!(matches!(Cli::try_parse_from(["asam", "position"]).unwrap().command, Some(Command::Position)));
+    }
+
+    #[test]
+    fn test_check_config() {
+        let mut config = Config::default();
+        config.safe.rpc_url = Some("http://localhost:8545".to_string());
+        let results = check_config(&Ok(config));
+        assert_eq!(results[1].status, CheckStatus::Pass);
+        assert_eq!(results[2].status, CheckStatus::Fail);
+        assert!(results[2].detail.contains("ACCOUNT_ADDRESS"));
+        assert!(has_failures(&results));
+
+        let results = check_config(&Config::from_toml_str("[runtime]\ncycle_interval_secs = \"soon\""));
+        assert_eq!(results.len(), 1);
+        assert!(has_failures(&results));
+    }
+
+    #[test]
+    fn test_parse_config_init() {
+        let cli = Cli::try_parse_from(["asam", "config", "init", "--output", "/tmp/x.toml"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Command::Config(ConfigCommand::Init(ConfigInitArgs { force: false, .. })))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_check_provider_with_mock() {
+        let (provider, mock) = Provider::mocked();
+        // Responses are popped in reverse order
+        mock.push::<Bytes, _>(Bytes::from(vec![0x60, 0x80])).unwrap();
+        mock.push(U256::from(1)).unwrap();
+        mock.push(U64::from(19_000_000)).unwrap();
+
+        let results = check_provider(&provider, Address::zero(), Some(1)).await;
+        assert_eq!(results.len(), 3);
+        assert!(!has_failures(&results));
+
+        let (provider, mock) = Provider::mocked();
+        mock.push::<Bytes, _>(Bytes::default()).unwrap();
+        mock.push(U256::from(42161)).unwrap();
+        mock.push(U64::from(1)).unwrap();
+
+        let results = check_provider(&provider, Address::zero(), Some(1)).await;
+        assert_eq!(results[1].status, CheckStatus::Fail);
+        assert_eq!(results[2].status, CheckStatus::Warn);
+        assert!(has_failures(&results));
+    }
+
+    #[test]
+    fn test_chains_table_and_json() {
+        let cli = Cli::try_parse_from(["asam", "chains", "--json", "--probe"]).unwrap();
+        assert!(matches!(cli.command, Some(Command::Chains(ChainsArgs { json: true, probe: true }))));
+
+        let mut config = Config::default();
+        config.safe.rpc_url = Some("http://localhost:8545".to_string());
+        config.router.chains.iter_mut().find(|c| c.name == "Optimism").unwrap().active = false;
+        let router = CrossChainRouter::from_config(&config.router);
+        let mut rows = chain_rows(&router, &config);
+        let ids: Vec<u64> = rows.iter().map(|r| r.chain_id).collect();
+        assert!(ids.windows(2).all(|w| w[0] < w[1]), "{:?}", ids);
+        let home = rows.iter().find(|r| r.name == HOME_CHAIN).unwrap();
+        assert!(home.rpc_configured && home.active);
+        assert!(!rows.iter().find(|r| r.name == "Optimism").unwrap().active);
+        assert!(!rows.iter().find(|r| r.name == "Arbitrum").unwrap().rpc_configured);
+
+        rows[0].rpc = Some(Probe::of(&Ok::<_, anyhow::Error>("ok")));
+        rows[1].quote = Some(Probe::of(&Err::<String, _>(anyhow::anyhow!("connection refused"))));
+        let mut out = Vec::new();
+        print_chains(&rows, &mut out).unwrap();
+        let printed = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = printed.lines().collect();
+        assert_eq!(lines.len(), rows.len() + 1);
+        assert!(lines[0].starts_with("CHAIN") && lines[0].ends_with("QUOTE"), "{}", lines[0]);
+        assert!(lines[1].starts_with("Ethereum") && lines[1].contains(" ok "), "{}", lines[1]);
+        assert!(lines[2].ends_with("FAIL: connection refused"), "{}", lines[2]);
+
+        let json = serde_json::to_value(&rows).unwrap();
+        for row in json.as_array().unwrap() {
+            let row = row.as_object().unwrap();
+            let keys: Vec<&str> = row.keys().map(String::as_str).collect();
+            assert_eq!(keys, ["active", "chain_id", "min_transfer", "name", "quote", "rpc", "rpc_configured"]);
+            assert!(row["chain_id"].is_u64() && row["min_transfer"].is_f64() && row["active"].is_boolean());
+            assert!(row["rpc"].is_null() || row["rpc"]["ok"].is_boolean());
+        }
+        assert_eq!(json[1]["quote"], serde_json::json!({ "ok": false, "detail": "connection refused" }));
+    }
+
+    #[test]
+    fn test_backtest_report() {
+        let cli = Cli::try_parse_from(["asam", "backtest", "--pool", "abc=Aave@Base", "--strategy", "fast"]).unwrap();
+        let Some(Command::Backtest(args)) = cli.command else { panic!("not a backtest") };
+        assert_eq!(args.pools, ["abc=Aave@Base"]);
+        assert_eq!((args.strategy.as_deref(), args.balance), (Some("fast"), 10.0));
+        assert!(Cli::try_parse_from(["asam", "backtest"]).is_err());
+
+        let csv = "date,pool_id,apy,tvl\n2024-01-01,Aave@Ethereum,4,1e8\n2024-01-01,Aave@Arbitrum,3,1e8\n\
+            2024-01-05,Aave@Arbitrum,8,1e8\n2024-01-10,Aave@Ethereum,4,1e8\n";
+        let history = crate::backtest::History::from_csv(csv, Default::default()).unwrap();
+        let options = crate::backtest::BacktestOptions { balance_eth: 2.0, bridge_fee_eth: 0.002, swap_cost_pct: 0.0 };
+        let report = crate::backtest::backtest(&Config::default(), &history, &options).unwrap();
+        let mut out = Vec::new();
+        print_backtest(&report, &mut out).unwrap();
+        let printed = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = printed.lines().collect();
+        assert_eq!(lines[0], "Backtest of strategy default: 2024-01-01 to 2024-01-10 (10 days), 2 ETH");
+        assert_eq!(lines[1], "Rebalance on 2024-01-05: route Ethereum -> Arbitrum (APY 8.00%)");
+        assert!(printed.contains("Rebalances: 1\nFees paid: 0.002000 ETH\n"), "{}", printed);
+        let holding = "Holding Aave on Ethereum (Aave@Ethereum): 0.002192 ETH, APY 4.00%\n";
+        assert!(printed.ends_with(holding), "{}", printed);
+    }
+
+    #[test]
+    fn test_simulation_summary() {
+        let cli = Cli::try_parse_from(["asam", "simulate", "scenario.toml", "--json"]).unwrap();
+        assert!(matches!(cli.command, Some(Command::Simulate(SimulateArgs { json: true, .. }))));
+        let cli = Cli::try_parse_from(["asam", "simulate", "--fork", "http://localhost:8545"]).unwrap();
+        assert!(matches!(cli.command, Some(Command::Simulate(SimulateArgs { scenario: None, fork: Some(_), .. }))));
+        assert!(Cli::try_parse_from(["asam", "simulate"]).is_err());
+
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/scenarios/apy_shift.toml");
+        let scenario = crate::simulation::Scenario::load(&path).unwrap();
+        let summary = crate::simulation::simulate(&Config::default(), &scenario).unwrap();
+        let mut out = Vec::new();
+        print_simulation(&summary, &mut out).unwrap();
+        let printed = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = printed.lines().collect();
+        assert_eq!(lines[0], "Scenario apy_shift: 40 cycles over 10 days");
+        assert!(lines[3].contains("5.00 route") && lines[3].ends_with("route Ethereum -> Arbitrum"), "{}", lines[3]);
+        assert!(printed.contains("Route on day 5.00: 1.791 Ethereum -> Arbitrum (APY 7.50%), fee 0.002000 ETH, swap 0.003582 ETH"));
+        assert!(printed.contains("Swap costs: 0.003582 ETH"));
+        assert!(printed.ends_with("Final position: Aave on Arbitrum\n"), "{}", printed);
+    }
+}
@@ -0,0 +1,174 @@
+//! Hypothetical performance of the configured strategies, side by side.
+//!
+//! Every full cycle each strategy decides on the same pools, but only the acting one
+//! moves funds. [`StrategyComparison`] follows each of them on paper: it holds the
+//! chain the strategy last decided to route to and accrues the APY it saw there
+//! between cycles. Once a day the totals go out as a strategy summary alert and
+//! start over.
+
+use serde::Serialize;
+
+use crate::report::{CycleDecision, StrategyDecision};
+
+const SECS_PER_YEAR: f64 = 365.0 * 24.0 * 60.0 * 60.0;
+/// Seconds between strategy summaries.
+pub const SUMMARY_INTERVAL_SECS: u64 = 24 * 60 * 60;
+
+/// How one strategy did since the last summary, had it been the one acting.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StrategyPerformance {
+    pub name: String,
+    pub active: bool,
+    /// Cycles it decided in.
+    pub cycles: u64,
+    /// Times it decided to route away from the chain it held.
+    pub routes: u64,
+    /// Chain it would hold now.
+    pub position: String,
+    /// APY last seen on `position`.
+    pub apy: Option<f64>,
+    /// What the held positions would have earned, in percent of the funds.
+    pub accrued_pct: f64,
+}
+
+/// Paper positions of every strategy, in the order they were first seen.
+#[derive(Debug, Clone, Default)]
+pub struct StrategyComparison {
+    strategies: Vec<StrategyPerformance>,
+    period_start: Option<u64>,
+    updated_at: Option<u64>,
+}
+
+impl StrategyComparison {
+    /// Accrues each position's APY since the last cycle, then takes in `decisions`.
+    pub fn record(&mut self, decisions: &[StrategyDecision], now: u64) {
+        let elapsed = self.updated_at.map_or(0, |at| now.saturating_sub(at));
+        self.period_start.get_or_insert(now);
+        self.updated_at = Some(now);
+        for entry in &mut self.strategies {
+            entry.accrued_pct += entry.apy.unwrap_or(0.0) * elapsed as f64 / SECS_PER_YEAR;
+        }
+        for decision in decisions {
+            let Some(inputs) = &decision.inputs else {
+                continue;
+            };
+            let i = match self.strategies.iter().position(|s| s.name == decision.name) {
+                Some(i) => i,
+                None => {
+                    self.strategies.push(StrategyPerformance {
+                        name: decision.name.clone(),
+                        active: decision.active,
+                        cycles: 0,
+                        routes: 0,
+                        position: inputs.current_chain.clone(),
+                        apy: None,
+                        accrued_pct: 0.0,
+                    });
+                    self.strategies.len() - 1
+                }
+            };
+            let entry = &mut self.strategies[i];
+            entry.active = decision.active;
+            entry.cycles += 1;
+            if decision.decision == CycleDecision::RouteInitiated && entry.position != inputs.candidate_chain {
+                entry.routes += 1;
+                entry.position = inputs.candidate_chain.clone();
+            }
+            if entry.position == inputs.candidate_chain {
+                entry.apy = Some(inputs.candidate_apy);
+            } else if entry.position == inputs.current_chain {
+                entry.apy = inputs.current_apy;
+            }
+        }
+    }
+
+    /// The performance so far, best accrued first.
+    pub fn standings(&self) -> Vec<StrategyPerformance> {
+        let mut standings = self.strategies.clone();
+        standings.sort_by(|a, b| b.accrued_pct.total_cmp(&a.accrued_pct));
+        standings
+    }
+
+    /// The standings once `interval_secs` have passed since the period started. The
+    /// counts and accruals then start over; the positions are kept.
+    pub fn take_summary(&mut self, now: u64, interval_secs: u64) -> Option<Vec<StrategyPerformance>> {
+        let start = self.period_start?;
+        if now.saturating_sub(start) < interval_secs {
+            return None;
+        }
+        let standings = self.standings();
+        self.period_start = Some(now);
+        for entry in &mut self.strategies {
+            entry.cycles = 0;
+            entry.routes = 0;
+            entry.accrued_pct = 0.0;
+        }
+        Some(standings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::DecisionInputs;
+    use ethers::core::types::U256;
+
+    fn decided(name: &str, active: bool, decision: CycleDecision, candidate: (&str, f64)) -> StrategyDecision {
+        let inputs = DecisionInputs {
+            current_chain: "Ethereum".to_string(),
+            current_apy: Some(4.0),
+            candidate_chain: candidate.0.to_string(),
+            candidate_apy: candidate.1,
+            candidate_tvl: 1_000_000.0,
+            balance: U256::exp10(18),
+            paused: false,
+            pending_route: None,
+            last_route_at: None,
+            observed_at: 0,
+            recent_route: None,
+            rebalance_gas: None,
+            gas_price_gwei: None,
+            min_apy_improvement: 0.0,
+            cooldown_remaining_secs: None,
+            amount: None,
+            sizing: None,
+            breakeven_days: None,
+        };
+        StrategyDecision {
+            name: name.to_string(),
+            active,
+            best_pool: None,
+            reason: String::new(),
+            decision,
+            inputs: Some(inputs),
+        }
+    }
+
+    #[test]
+    fn test_paper_positions_accrue_and_reset_daily() {
+        let mut comparison = StrategyComparison::default();
+        let cycle = || {
+            vec![
+                decided("conservative", true, CycleDecision::NoAction, ("Arbitrum", 7.3)),
+                decided("aggressive", false, CycleDecision::RouteInitiated, ("Arbitrum", 7.3)),
+            ]
+        };
+        comparison.record(&cycle(), 0);
+        comparison.record(&cycle(), SECS_PER_YEAR as u64 / 2);
+        assert!(comparison.take_summary(SECS_PER_YEAR as u64 / 2, SECS_PER_YEAR as u64).is_none());
+
+        let standings = comparison.take_summary(SECS_PER_YEAR as u64 / 2, SUMMARY_INTERVAL_SECS).unwrap();
+        assert_eq!(standings[0].name, "aggressive");
+        assert_eq!((standings[0].position.as_str(), standings[0].routes, standings[0].cycles), ("Arbitrum", 1, 2));
+        assert!((standings[0].accrued_pct - 3.65).abs() < 1e-9, "{:?}", standings[0]);
+        assert!(standings[1].active);
+        assert_eq!((standings[1].position.as_str(), standings[1].apy), ("Ethereum", Some(4.0)));
+        assert!((standings[1].accrued_pct - 2.0).abs() < 1e-9, "{:?}", standings[1]);
+
+        // The next period starts from the held positions
+        let standings = comparison.standings();
+        assert_eq!(standings.iter().map(|s| s.accrued_pct).sum::<f64>(), 0.0);
+        assert_eq!(standings.iter().find(|s| s.name == "aggressive").unwrap().position, "Arbitrum");
+        assert!(comparison.take_summary(SECS_PER_YEAR as u64 / 2 + 60, SUMMARY_INTERVAL_SECS).is_none());
+    }
+}
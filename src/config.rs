@@ -0,0 +1,3514 @@
+//! TOML configuration for ASAM.
+//!
+//! Settings are resolved with the following precedence (highest first):
+//!
+//! 1. Command line flags (`--dry-run`, `--log-level`)
+//! 2. Environment variables (see [`ENV_OVERRIDES`])
+//! 3. The config file given by `--config` or `ASAM_CONFIG`
+//! 4. Built-in defaults
+//!
+//! Every field is optional in the file; only `safe.address` and `safe.rpc_url`
+//! must be provided by either the file or the environment.
+
+use anyhow::Result;
+use ethers::core::types::{Address, U256};
+use ethers::signers::{LocalWallet, Signer};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+use thiserror::Error;
+
+use crate::agents::safe_manager::{TopUp, DEFAULT_LOG_BLOCK_RANGE, DEFAULT_SUBMISSIONS_PER_MINUTE};
+use crate::agents::signer::OwnerSigner;
+#[cfg(feature = "ledger")]
+use crate::agents::signer::LedgerSigner;
+use crate::degradation::Dependency;
+use crate::notifier::Severity;
+use crate::portfolio::HOME_CHAIN;
+use crate::rpc::EndpointKind;
+use crate::secret::{redact_url, Secret};
+
+#[derive(Error, Debug)]
+#[non_exhaustive]
+pub enum ConfigError {
+    #[error("Failed to read config file {path}: {reason}")]
+    Read { path: String, reason: String },
+    #[error("Failed to parse config file {path}: {reason}")]
+    Parse { path: String, reason: String },
+    #[error("Missing required setting `{key}` (set it in the config file or via {env})")]
+    Missing { key: &'static str, env: &'static str },
+    #[error("Invalid value for `{key}`: {reason}")]
+    Invalid { key: String, reason: String },
+}
+
+fn invalid(key: impl Into<String>, reason: impl Into<String>) -> ConfigError {
+    ConfigError::Invalid { key: key.into(), reason: reason.into() }
+}
+
+/// Checks the thresholds of a `[strategy]` table at `prefix`.
+fn validate_strategy(prefix: &str, strategy: &StrategyConfig) -> Result<(), ConfigError> {
+    let key = |field: &str| format!("{}.{}", prefix, field);
+    if !(strategy.min_apy_improvement.is_finite() && strategy.min_apy_improvement >= 0.0) {
+        return Err(invalid(key("min_apy_improvement"), "must be a non-negative number"));
+    }
+    if !(strategy.reserve_eth.is_finite() && strategy.reserve_eth >= 0.0) {
+        return Err(invalid(key("reserve_eth"), "must be a non-negative number"));
+    }
+    if !(strategy.max_deploy_fraction > 0.0 && strategy.max_deploy_fraction <= 1.0) {
+        return Err(invalid(key("max_deploy_fraction"), "must be above 0 and at most 1"));
+    }
+    if !(strategy.reconcile_tolerance_eth.is_finite() && strategy.reconcile_tolerance_eth >= 0.0) {
+        return Err(invalid(key("reconcile_tolerance_eth"), "must be a non-negative number"));
+    }
+    if !(strategy.max_cost_ratio.is_finite() && strategy.max_cost_ratio > 0.0) {
+        return Err(invalid(key("max_cost_ratio"), "must be a positive number"));
+    }
+    if !(strategy.max_breakeven_days.is_finite() && strategy.max_breakeven_days >= 0.0) {
+        return Err(invalid(key("max_breakeven_days"), "must be a non-negative number"));
+    }
+    if let Some(amount) = strategy.transfer_amount.filter(|a| !(a.is_finite() && *a > 0.0)) {
+        return Err(invalid(key("transfer_amount"), format!("{} must be a positive number", amount)));
+    }
+    Ok(())
+}
+
+/// Environment variables that override a config key, as `(variable, key)`.
+pub const ENV_OVERRIDES: &[(&str, &str)] = &[
+    // ETH_RPC_URLS wins when both are set
+    ("ETH_RPC_URL", "safe.rpc_url"),
+    ("ETH_RPC_URLS", "safe.rpc_url"),
+    ("CHAIN_ID", "safe.chain_id"),
+    ("RPC_TIMEOUT_SECS", "safe.rpc_timeout_secs"),
+    ("ACCOUNT_ADDRESS", "safe.address"),
+    ("MIN_BALANCE_ETH", "safe.min_balance_eth"),
+    ("MIN_BALANCE_WEI", "safe.min_balance_wei"),
+    ("CRITICAL_BALANCE_ETH", "safe.critical_balance_eth"),
+    ("CRITICAL_BALANCE_WEI", "safe.critical_balance_wei"),
+    ("BALANCE_RECOVERY_FACTOR", "safe.balance_recovery_factor"),
+    ("MAX_VALUE_PER_TX_WEI", "safe.max_value_per_tx_wei"),
+    ("MAX_VALUE_PER_DAY_WEI", "safe.max_value_per_day_wei"),
+    ("DAILY_GAS_BUDGET_WEI", "safe.daily_gas_budget_wei"),
+    ("ALLOW_DELEGATECALL", "safe.allow_delegatecall"),
+    ("GAS_BUFFER", "safe.gas_buffer"),
+    ("MAX_GAS_LIMIT", "safe.max_gas_limit"),
+    ("DRY_RUN", "safe.dry_run"),
+    ("SAFE_EXECUTION", "safe.execution"),
+    // SAFE_SIGNER_KEY wins when both are set
+    ("PRIVATE_KEY", "safe.signer_key"),
+    ("SAFE_SIGNER_KEY", "safe.signer_key"),
+    ("SIGNER", "safe.signer"),
+    ("LEDGER_PATH", "safe.ledger_path"),
+    ("TOP_UP_FUNDING_KEY", "safe.top_up.funding_key"),
+    ("WATCH_TRANSFERS", "safe.transfers.enabled"),
+    ("DEFI_API_URL", "optimizer.api_url"),
+    ("API_TIMEOUT_SECS", "optimizer.timeout_secs"),
+    ("ROUTE_STATUS_INTERVAL_SECS", "router.status.interval_secs"),
+    ("LIFI_API_URL", "router.status.lifi_api_url"),
+    ("SOCKET_API_URL", "router.status.socket_api_url"),
+    ("SOCKET_API_KEY", "router.status.socket_api_key"),
+    ("CYCLE_INTERVAL_SECS", "runtime.cycle_interval_secs"),
+    ("LOG_FORMAT", "runtime.log_format"),
+    ("LOG_FILE_PATH", "log.file_path"),
+    ("RUN_MODE", "runtime.mode"),
+    ("HTTP_BIND", "http.bind"),
+    ("WEBHOOK_URL", "notify.webhook.url"),
+    ("WEBHOOK_SECRET", "notify.webhook.secret"),
+    ("TELEGRAM_BOT_TOKEN", "notify.telegram.bot_token"),
+    ("TELEGRAM_CHAT_ID", "notify.telegram.chat_id"),
+    ("DISCORD_WEBHOOK_URL", "notify.discord.webhook_url"),
+    ("SMTP_HOST", "notify.email.smtp_host"),
+    ("SMTP_USERNAME", "notify.email.username"),
+    ("SMTP_PASSWORD", "notify.email.password"),
+    ("EMAIL_FROM", "notify.email.from"),
+    ("EMAIL_TO", "notify.email.to"),
+    ("STORAGE_PATH", "storage.path"),
+    ("KILL_SWITCH_PATH", "kill_switch.path"),
+    ("PAUSED", "kill_switch.paused"),
+    ("PREFLIGHT", "runtime.preflight"),
+    ("APPROVAL", "runtime.approval"),
+    ("SIMULATION_MODE", "runtime.simulation"),
+    ("SIMULATION_SEED", "runtime.simulation_seed"),
+    ("ADMIN_TOKEN", "http.admin_token"),
+    ("COINGECKO_API_KEY", "prices.coingecko_api_key"),
+];
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Network family; selects the default chain registry and data sources.
+    pub profile: Profile,
+    /// Required to run in live mode on the mainnet profile.
+    pub i_understand_this_moves_real_funds: bool,
+    pub safe: SafeConfig,
+    pub optimizer: OptimizerConfig,
+    pub router: RouterConfig,
+    pub strategy: StrategyConfig,
+    /// Named strategies evaluated side by side on the same pools every cycle.
+    pub strategies: Vec<NamedStrategyConfig>,
+    pub runtime: RuntimeConfig,
+    pub log: LogConfig,
+    pub http: HttpConfig,
+    pub notify: NotifyConfig,
+    pub storage: StorageConfig,
+    pub kill_switch: KillSwitchConfig,
+    pub swap: SwapConfig,
+    pub prices: PricesConfig,
+    pub actions: ActionsConfig,
+    pub degradation: DegradationPolicy,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SafeConfig {
+    /// Account (Safe) address to monitor, or an ENS name such as `treasury.mydao.eth`.
+    pub address: Option<String>,
+    /// What `address` resolved to when it is an ENS name; see [`crate::ens`].
+    #[serde(skip)]
+    pub resolved_address: Option<Address>,
+    /// Ethereum JSON-RPC endpoint, or several separated by commas in failover order.
+    pub rpc_url: Option<String>,
+    /// Failover endpoints as an array; used when `rpc_url` is unset.
+    pub rpc_urls: Vec<String>,
+    /// Chain id the endpoints must serve; the profile's Ethereum chain when unset.
+    pub chain_id: Option<u64>,
+    /// Requests taking longer than this fail over to the next endpoint.
+    pub rpc_timeout_secs: u64,
+    /// How often demoted endpoints are probed to be promoted back.
+    pub rpc_probe_interval_secs: u64,
+    /// Attempts of a balance read, gas price or gas estimate that fails transiently,
+    /// the first included.
+    pub rpc_retry_attempts: u32,
+    /// Delay before the first retry; it doubles with each one after.
+    pub rpc_retry_backoff_ms: u64,
+    /// Balance below which the account is considered underfunded.
+    pub min_balance_eth: f64,
+    /// Balance at or below which it is critical; half of the minimum when unset.
+    pub critical_balance_eth: Option<f64>,
+    /// The minimum in wei, as a decimal string; wins over `min_balance_eth`, for
+    /// amounts a float cannot hold exactly.
+    pub min_balance_wei: Option<String>,
+    /// The critical threshold in wei, as a decimal string; wins over
+    /// `critical_balance_eth`.
+    pub critical_balance_wei: Option<String>,
+    /// Once below the minimum, the balance counts as healthy again only above the
+    /// minimum times this factor, so one hovering around it does not flip every cycle.
+    pub balance_recovery_factor: f64,
+    /// Most ETH value one transaction may send, in wei as a decimal string; unlimited
+    /// when unset.
+    pub max_value_per_tx_wei: Option<String>,
+    /// Most ETH value the transactions executed over any 24 hours may send, in wei as
+    /// a decimal string; unlimited when unset.
+    pub max_value_per_day_wei: Option<String>,
+    /// Most ETH the transactions executed over any 24 hours may pay in gas, in wei as
+    /// a decimal string; unlimited when unset.
+    pub daily_gas_budget_wei: Option<String>,
+    /// Whether the Safe may delegatecall at all, e.g. to batch an approval and a
+    /// supply through MultiSendCallOnly.
+    pub allow_delegatecall: bool,
+    /// The only contracts a delegatecall may target; must include
+    /// `actions.multi_send` when delegatecalls are allowed.
+    pub delegatecall_targets: Vec<String>,
+    /// Modules, and the guard, the Safe is expected to have; any other fails the
+    /// cycle with an alert.
+    pub allowed_modules: Vec<String>,
+    /// Per-component override of `runtime.mode` for transaction execution. Without
+    /// `runtime.mode`, `true` selects dry-run mode as before.
+    pub dry_run: Option<bool>,
+    /// ERC-20 tokens whose balances are read alongside ETH.
+    pub tokens: Vec<TokenConfig>,
+    /// Other accounts whose ETH balances are monitored on the home chain.
+    pub accounts: Vec<AccountConfig>,
+    /// Multiplier of gas estimates, covering the Safe's signature checks and state
+    /// that moves before the transaction is mined.
+    pub gas_buffer: f64,
+    /// Most gas a transaction may be executed with, buffer included; a higher
+    /// estimate is refused.
+    pub max_gas_limit: u64,
+    /// Tip of EIP-1559 transactions; the median of recent blocks' tips when unset.
+    pub priority_fee_gwei: Option<f64>,
+    /// Whether live transactions are executed, proposed for the other owners to sign,
+    /// or only simulated.
+    pub execution: SafeExecution,
+    /// Blocks an executed transaction must be buried under before it counts as done,
+    /// its own included: 2 on Ethereum mainnet and 1 on other chains when unset. 0
+    /// returns as soon as the node accepts it.
+    pub confirmations: Option<usize>,
+    /// How long to wait for those confirmations before the transaction is reported as
+    /// not confirmed, e.g. dropped from the mempool.
+    pub confirmation_timeout_secs: u64,
+    /// Most queued transactions submitted in any minute, each after the previous one
+    /// is confirmed.
+    pub max_submissions_per_minute: u32,
+    /// Private key of the owner that signs and sends transactions, hex encoded.
+    pub signer_key: Option<Secret<String>>,
+    /// Whether that owner is `signer_key` or a Ledger device.
+    pub signer: SignerKind,
+    /// Derivation path of the owner's account on the Ledger.
+    pub ledger_path: String,
+    /// How long to wait on the Ledger, confirming a transaction on it included,
+    /// before signing fails as unavailable.
+    pub ledger_timeout_secs: u64,
+    /// Safe Transaction Service base URL by chain name, overriding the
+    /// `safe-transaction-<network>.safe.global` defaults.
+    pub transaction_service_urls: BTreeMap<String, String>,
+    /// Refilling a critical balance from a funding wallet; off when unset.
+    pub top_up: Option<TopUpConfig>,
+    /// Reporting transfers into and out of the Safe; off by default.
+    pub transfers: TransferWatchConfig,
+}
+
+/// Reporting the transfers into and out of the Safe each cycle.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TransferWatchConfig {
+    pub enabled: bool,
+    /// Whether each transfer is also sent as a `transfer_observed` notification,
+    /// besides being logged.
+    pub notify: bool,
+    /// Most blocks one `eth_getLogs` call asks for; halved while the node refuses.
+    pub max_block_range: u64,
+}
+
+impl Default for TransferWatchConfig {
+    fn default() -> Self {
+        Self { enabled: false, notify: false, max_block_range: DEFAULT_LOG_BLOCK_RANGE }
+    }
+}
+
+/// A funding wallet that tops up the Safe once its balance is critical.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TopUpConfig {
+    /// Private key of the funding wallet, hex encoded; a plain account, not an owner.
+    pub funding_key: Option<Secret<String>>,
+    /// Balance the Safe is brought back to; `safe.min_balance_eth` when unset.
+    pub target_balance_eth: Option<f64>,
+    /// Most ETH sent over any 24 hours.
+    pub max_per_day_eth: Option<f64>,
+    /// ETH the funding wallet keeps, fees included.
+    pub reserve_eth: f64,
+}
+
+impl TopUpConfig {
+    pub fn funding_wallet(&self) -> Result<LocalWallet> {
+        let key = self.funding_key.as_ref().ok_or(ConfigError::Missing {
+            key: "safe.top_up.funding_key",
+            env: "TOP_UP_FUNDING_KEY",
+        })?;
+        Ok(LocalWallet::from_str(key.expose().trim().trim_start_matches("0x"))
+            .map_err(|_| invalid("safe.top_up.funding_key", "is not a hex encoded private key"))?)
+    }
+}
+
+impl Default for TopUpConfig {
+    fn default() -> Self {
+        Self { funding_key: None, target_balance_eth: None, max_per_day_eth: None, reserve_eth: 0.01 }
+    }
+}
+
+/// How a live Safe transaction is carried out.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SafeExecution {
+    /// Executed by the agent, for a Safe whose threshold it meets alone.
+    #[default]
+    Execute,
+    /// Signed and proposed to the Safe Transaction Service, where the other owners
+    /// confirm and execute it.
+    Propose,
+    /// Prepared and estimated as if executed, but never signed or sent.
+    Simulate,
+}
+
+impl fmt::Display for SafeExecution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SafeExecution::Execute => "execute",
+            SafeExecution::Propose => "propose",
+            SafeExecution::Simulate => "simulate",
+        })
+    }
+}
+
+/// Where the owner's key lives.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SignerKind {
+    /// `safe.signer_key`, held in memory.
+    #[default]
+    Key,
+    /// A Ledger hardware wallet; needs the `ledger` feature.
+    Ledger,
+}
+
+/// An ERC-20 token held by the Safe.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TokenConfig {
+    pub symbol: String,
+    pub address: String,
+    /// Read from the contract when unset.
+    #[serde(default)]
+    pub decimals: Option<u32>,
+}
+
+impl TokenConfig {
+    pub fn address(&self) -> Result<Address> {
+        parse_address(&format!("safe.tokens.{}.address", self.symbol), &self.address)
+    }
+}
+
+/// Another account monitored alongside the Safe, e.g. an ops or treasury Safe.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AccountConfig {
+    /// Hex address or ENS name.
+    pub address: String,
+    /// What `address` resolved to when it is an ENS name.
+    #[serde(skip)]
+    pub resolved: Option<Address>,
+    /// Balance threshold of this account, critical at half of it; defaults to
+    /// `safe.min_balance_eth`.
+    #[serde(default)]
+    pub min_balance_eth: Option<f64>,
+}
+
+impl AccountConfig {
+    pub fn address(&self) -> Result<Address> {
+        resolved_address("safe.accounts.address", &self.address, self.resolved)
+    }
+
+    /// Minimum balance of this account, falling back to the Safe's threshold.
+    pub fn min_balance_wei(&self, safe: &SafeConfig) -> U256 {
+        match self.min_balance_eth {
+            Some(eth) => ethers::utils::parse_ether(eth).unwrap_or_default(),
+            None => safe.min_balance(),
+        }
+    }
+}
+
+impl Default for SafeConfig {
+    fn default() -> Self {
+        Self {
+            address: None,
+            resolved_address: None,
+            rpc_url: None,
+            rpc_urls: Vec::new(),
+            chain_id: None,
+            rpc_timeout_secs: 10,
+            rpc_probe_interval_secs: 30,
+            rpc_retry_attempts: 3,
+            rpc_retry_backoff_ms: 500,
+            min_balance_eth: 0.001,
+            critical_balance_eth: None,
+            min_balance_wei: None,
+            critical_balance_wei: None,
+            balance_recovery_factor: 1.1,
+            max_value_per_tx_wei: None,
+            max_value_per_day_wei: None,
+            daily_gas_budget_wei: None,
+            allow_delegatecall: false,
+            delegatecall_targets: vec![MULTI_SEND_CALL_ONLY.to_string()],
+            allowed_modules: Vec::new(),
+            dry_run: None,
+            tokens: Vec::new(),
+            accounts: Vec::new(),
+            gas_buffer: 1.2,
+            max_gas_limit: 5_000_000,
+            priority_fee_gwei: None,
+            execution: SafeExecution::Execute,
+            confirmations: None,
+            confirmation_timeout_secs: 300,
+            max_submissions_per_minute: DEFAULT_SUBMISSIONS_PER_MINUTE,
+            signer_key: None,
+            signer: SignerKind::Key,
+            ledger_path: "m/44'/60'/0'/0/0".to_string(),
+            ledger_timeout_secs: 120,
+            transaction_service_urls: BTreeMap::new(),
+            top_up: None,
+            transfers: TransferWatchConfig::default(),
+        }
+    }
+}
+
+impl SafeConfig {
+    /// Safe Transaction Service for `chain`: the configured URL, or the public service
+    /// of a network Safe hosts one for.
+    pub fn transaction_service_url(&self, chain: &str) -> Option<String> {
+        if let Some(url) = self.transaction_service_urls.get(chain) {
+            return Some(url.trim_end_matches('/').to_string());
+        }
+        let network = match chain {
+            "Ethereum" => "mainnet",
+            "Arbitrum" => "arbitrum",
+            "Optimism" => "optimism",
+            "Polygon" => "polygon",
+            "Base" => "base",
+            _ => return None,
+        };
+        Some(format!("https://safe-transaction-{}.safe.global", network))
+    }
+
+    /// The configured EIP-1559 tip in wei.
+    pub fn priority_fee_wei(&self) -> Option<U256> {
+        self.priority_fee_gwei.map(|gwei| U256::from((gwei * 1e9).round() as u128))
+    }
+
+    /// The owner that signs transactions and proposals, when one is configured.
+    pub fn signer(&self) -> Result<Option<OwnerSigner>> {
+        match self.signer {
+            SignerKind::Key => {
+                let Some(key) = &self.signer_key else { return Ok(None) };
+                let wallet = LocalWallet::from_str(key.expose().trim().trim_start_matches("0x"))
+                    .map_err(|_| invalid("safe.signer_key", "is not a hex encoded private key"))?;
+                Ok(Some(wallet.into()))
+            }
+            #[cfg(feature = "ledger")]
+            SignerKind::Ledger => Ok(Some(OwnerSigner::Ledger(LedgerSigner::new(
+                self.ledger_path.clone(),
+                Duration::from_secs(self.ledger_timeout_secs),
+            )))),
+            #[cfg(not(feature = "ledger"))]
+            SignerKind::Ledger => {
+                Err(invalid("safe.signer", "\"ledger\" needs asam built with the `ledger` feature").into())
+            }
+        }
+    }
+
+    /// The top-up of a critical balance, when `[safe.top_up]` is configured.
+    pub fn top_up(&self) -> Result<Option<TopUp>> {
+        let Some(top_up) = &self.top_up else { return Ok(None) };
+        let wallet = top_up.funding_wallet()?;
+        let max_per_day = top_up
+            .max_per_day_eth
+            .ok_or_else(|| invalid("safe.top_up.max_per_day_eth", "is required to cap the top-ups"))?;
+        Ok(Some(TopUp {
+            funder: wallet.into(),
+            target: match top_up.target_balance_eth {
+                Some(eth) => ethers::utils::parse_ether(eth).unwrap_or_default(),
+                None => self.min_balance(),
+            },
+            max_per_day: ethers::utils::parse_ether(max_per_day).unwrap_or_default(),
+            reserve: ethers::utils::parse_ether(top_up.reserve_eth).unwrap_or_default(),
+        }))
+    }
+
+    pub fn address(&self) -> Result<Address> {
+        let raw = self.address.as_deref().ok_or(ConfigError::Missing {
+            key: "safe.address",
+            env: "ACCOUNT_ADDRESS",
+        })?;
+        resolved_address("safe.address", raw, self.resolved_address)
+    }
+
+    /// RPC endpoints in failover order, the primary first.
+    pub fn rpc_urls(&self) -> Result<Vec<&str>> {
+        let urls: Vec<&str> = match &self.rpc_url {
+            Some(url) => url.split(',').map(str::trim).filter(|u| !u.is_empty()).collect(),
+            None => self.rpc_urls.iter().map(|u| u.trim()).collect(),
+        };
+        if urls.is_empty() {
+            return Err(ConfigError::Missing { key: "safe.rpc_url", env: "ETH_RPC_URL" }.into());
+        }
+        Ok(urls)
+    }
+
+    /// Balance below which the account is underfunded, in wei.
+    pub fn min_balance(&self) -> U256 {
+        match self.min_balance_wei.as_deref().and_then(|wei| parse_wei("safe.min_balance_wei", wei).ok()) {
+            Some(wei) => wei,
+            None => ethers::utils::parse_ether(self.min_balance_eth).unwrap_or_default(),
+        }
+    }
+
+    /// Balance at or below which the account is critical, in wei.
+    pub fn critical_balance(&self) -> U256 {
+        if let Some(wei) = self.critical_balance_wei.as_deref().and_then(|wei| parse_wei("safe.critical_balance_wei", wei).ok()) {
+            return wei;
+        }
+        match self.critical_balance_eth {
+            Some(eth) => ethers::utils::parse_ether(eth).unwrap_or_default(),
+            None => self.min_balance() / 2,
+        }
+    }
+
+    pub fn delegatecall_targets(&self) -> Result<Vec<Address>> {
+        self.delegatecall_targets.iter().map(|target| parse_address("safe.delegatecall_targets", target)).collect()
+    }
+
+    pub fn allowed_modules(&self) -> Result<Vec<Address>> {
+        self.allowed_modules.iter().map(|module| parse_address("safe.allowed_modules", module)).collect()
+    }
+
+    /// Most ETH value one transaction may send, in wei.
+    pub fn max_value_per_tx(&self) -> Option<U256> {
+        self.max_value_per_tx_wei.as_deref().and_then(|wei| parse_wei("safe.max_value_per_tx_wei", wei).ok())
+    }
+
+    /// Most ETH value executed over any 24 hours, in wei.
+    pub fn max_value_per_day(&self) -> Option<U256> {
+        self.max_value_per_day_wei.as_deref().and_then(|wei| parse_wei("safe.max_value_per_day_wei", wei).ok())
+    }
+
+    /// Most ETH paid in gas over any 24 hours, in wei.
+    pub fn daily_gas_budget(&self) -> Option<U256> {
+        self.daily_gas_budget_wei.as_deref().and_then(|wei| parse_wei("safe.daily_gas_budget_wei", wei).ok())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScoringMethod {
+    /// APY weighted by the order of magnitude of TVL.
+    ApyLogTvl,
+    /// Raw APY only.
+    Apy,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OptimizerConfig {
+    /// DeFi data source endpoint.
+    pub api_url: String,
+    pub timeout_secs: u64,
+    /// Serve built-in mock pools instead of calling the API.
+    pub use_mock: bool,
+    /// Pools below this TVL (USD) are ignored.
+    pub min_tvl: f64,
+    /// Pools below this APY (%) are ignored.
+    pub min_apy: f64,
+    /// Only consider pools on these chains; empty means all chains.
+    pub chains: Vec<String>,
+    pub scoring: ScoringMethod,
+    /// Largest share of a pool's TVL the valued position may make up; 0 disables
+    /// the depth check.
+    pub max_pool_share: f64,
+}
+
+impl Default for OptimizerConfig {
+    fn default() -> Self {
+        Self {
+            api_url: "https://api.llama.fi/protocols".to_string(),
+            timeout_secs: 10,
+            use_mock: false,
+            min_tvl: 0.0,
+            min_apy: 0.0,
+            chains: Vec::new(),
+            scoring: ScoringMethod::ApyLogTvl,
+            max_pool_share: 0.1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ChainConfig {
+    pub name: String,
+    pub chain_id: u64,
+    #[serde(default = "default_true")]
+    pub active: bool,
+    #[serde(default = "default_min_transfer")]
+    pub min_transfer: f64,
+    /// JSON-RPC endpoint(s) for watching the balance on this chain, comma separated in
+    /// failover order. The home chain uses `safe.rpc_url` when unset.
+    #[serde(default)]
+    pub rpc_url: Option<String>,
+    /// Balance threshold on this chain; defaults to `safe.min_balance_eth`.
+    #[serde(default)]
+    pub min_balance_eth: Option<f64>,
+}
+
+impl ChainConfig {
+    /// Balance watching endpoints in failover order; empty when none are configured.
+    pub fn rpc_urls(&self) -> Vec<&str> {
+        self.rpc_url
+            .iter()
+            .flat_map(|url| url.split(','))
+            .map(str::trim)
+            .filter(|u| !u.is_empty())
+            .collect()
+    }
+
+    /// Minimum balance on this chain, falling back to the Safe's threshold.
+    pub fn min_balance_wei(&self, safe: &SafeConfig) -> U256 {
+        match self.min_balance_eth {
+            Some(eth) => ethers::utils::parse_ether(eth).unwrap_or_default(),
+            None => safe.min_balance(),
+        }
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_min_transfer() -> f64 {
+    0.1
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RouteStatusConfig {
+    pub interval_secs: u64,
+    pub max_backoff_secs: u64,
+    pub lifi_api_url: String,
+    pub socket_api_url: String,
+    pub socket_api_key: Option<Secret<String>>,
+}
+
+impl Default for RouteStatusConfig {
+    fn default() -> Self {
+        Self {
+            interval_secs: 30,
+            max_backoff_secs: 600,
+            lifi_api_url: "https://li.quest".to_string(),
+            socket_api_url: "https://api.socket.tech".to_string(),
+            socket_api_key: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RouterConfig {
+    pub chains: Vec<ChainConfig>,
+    /// Smallest amount the router will move.
+    pub min_amount: f64,
+    /// Largest amount the router will move in a single route.
+    pub max_amount: f64,
+    /// Bridge provider status endpoints used for live routes.
+    pub status: RouteStatusConfig,
+    /// Per-component override of `runtime.mode` for bridge execution.
+    pub dry_run: Option<bool>,
+    /// A route with the same chains, token and amount (within 1%) as one completed this
+    /// many seconds ago is refused unless forced; 0 disables the check.
+    pub route_dedup_window_secs: u64,
+}
+
+impl Default for RouterConfig {
+    fn default() -> Self {
+        Self {
+            chains: Profile::Mainnet.default_chains(),
+            min_amount: 0.1,
+            max_amount: 1000.0,
+            status: RouteStatusConfig::default(),
+            dry_run: None,
+            route_dedup_window_secs: 24 * 60 * 60,
+        }
+    }
+}
+
+impl RouterConfig {
+    pub fn route_dedup_window(&self) -> Duration {
+        Duration::from_secs(self.route_dedup_window_secs)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StrategyConfig {
+    /// APY percentage points a pool must beat the current position by to bridge to it.
+    pub min_apy_improvement: f64,
+    /// Seconds after a route before another one in the same direction may start.
+    pub bridge_cooldown_secs: u64,
+    /// Fixed amount to move per route; defaults to `max_deploy_fraction` of the
+    /// balance minus `reserve_eth`. Never more than the balance minus the reserve.
+    pub transfer_amount: Option<f64>,
+    /// ETH always kept back for gas.
+    pub reserve_eth: f64,
+    /// Share of the balance above the reserve a single route may move.
+    pub max_deploy_fraction: f64,
+    /// Difference in ETH between the recorded portfolio and the on-chain balance
+    /// that raises a reconciliation warning.
+    pub reconcile_tolerance_eth: f64,
+    /// Share of the yield captured over the last 30 days that fees may eat before
+    /// routes are held back.
+    pub max_cost_ratio: f64,
+    /// Days of the extra yield a move may take to pay for the gas of exiting the
+    /// current protocol and entering the new one; 0 does not check.
+    pub max_breakeven_days: f64,
+}
+
+impl Default for StrategyConfig {
+    fn default() -> Self {
+        Self {
+            min_apy_improvement: 1.0,
+            bridge_cooldown_secs: 24 * 60 * 60,
+            transfer_amount: None,
+            reserve_eth: 0.01,
+            max_deploy_fraction: 0.9,
+            reconcile_tolerance_eth: 0.05,
+            max_cost_ratio: 0.75,
+            max_breakeven_days: 30.0,
+        }
+    }
+}
+
+impl StrategyConfig {
+    pub fn reserve_wei(&self) -> U256 {
+        ethers::utils::parse_ether(self.reserve_eth).unwrap_or_default()
+    }
+}
+
+/// Name of the strategy built from `[optimizer]` and `[strategy]` in comparisons.
+pub const DEFAULT_STRATEGY: &str = "default";
+
+/// One `[[strategies]]` entry: pool filters and decision thresholds under a name. Only
+/// the entry with `active = true` acts; the others record what they would have done.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NamedStrategyConfig {
+    pub name: String,
+    /// Acts in place of `[optimizer]` filters and `[strategy]`; at most one may be set.
+    #[serde(default)]
+    pub active: bool,
+    #[serde(default)]
+    pub optimizer: StrategyFilterConfig,
+    #[serde(default)]
+    pub strategy: StrategyConfig,
+}
+
+/// Pool filters and scoring of a named strategy. The pools themselves come from
+/// `[optimizer]`, fetched once per cycle for all strategies.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StrategyFilterConfig {
+    pub min_tvl: f64,
+    pub min_apy: f64,
+    /// Empty means all chains.
+    pub chains: Vec<String>,
+    pub scoring: ScoringMethod,
+}
+
+impl Default for StrategyFilterConfig {
+    fn default() -> Self {
+        let optimizer = OptimizerConfig::default();
+        Self {
+            min_tvl: optimizer.min_tvl,
+            min_apy: optimizer.min_apy,
+            chains: optimizer.chains,
+            scoring: optimizer.scoring,
+        }
+    }
+}
+
+impl StrategyFilterConfig {
+    /// `source` with these filters and scoring in place of its own.
+    pub fn apply(&self, source: &OptimizerConfig) -> OptimizerConfig {
+        OptimizerConfig {
+            min_tvl: self.min_tvl,
+            min_apy: self.min_apy,
+            chains: self.chains.clone(),
+            scoring: self.scoring,
+            ..source.clone()
+        }
+    }
+}
+
+/// Network family the agent runs against.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Profile {
+    #[default]
+    Mainnet,
+    /// Sepolia and the L2 test networks.
+    Testnet,
+    /// A development node such as anvil; never runs live.
+    Local,
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Profile::Mainnet => "mainnet",
+            Profile::Testnet => "testnet",
+            Profile::Local => "local",
+        })
+    }
+}
+
+impl Profile {
+    /// Chains used when the config file has no `[[router.chains]]`. Names stay the
+    /// same across profiles; only the chain ids change.
+    pub fn default_chains(self) -> Vec<ChainConfig> {
+        let chains: &[(&str, u64)] = match self {
+            Profile::Mainnet => &[
+                ("Ethereum", 1),
+                ("Arbitrum", 42161),
+                ("Optimism", 10),
+                ("Polygon", 137),
+                ("Fantom", 250),
+            ],
+            Profile::Testnet => &[
+                ("Ethereum", 11155111),
+                ("Arbitrum", 421614),
+                ("Optimism", 11155420),
+                ("Polygon", 80002),
+            ],
+            Profile::Local => &[("Ethereum", 31337)],
+        };
+        chains
+            .iter()
+            .map(|&(name, chain_id)| ChainConfig {
+                name: name.to_string(),
+                chain_id,
+                active: true,
+                min_transfer: default_min_transfer(),
+                rpc_url: None,
+                min_balance_eth: None,
+            })
+            .collect()
+    }
+
+    /// Default LI.FI endpoint for route quotes and status.
+    pub fn lifi_api_url(self) -> &'static str {
+        match self {
+            Profile::Testnet => "https://testnet.li.quest",
+            Profile::Mainnet | Profile::Local => "https://li.quest",
+        }
+    }
+
+    /// Pool data sources only cover mainnets, so other profiles default to mock pools.
+    pub fn uses_mock_pools(self) -> bool {
+        self != Profile::Mainnet
+    }
+
+    /// Refuses an RPC serving a chain from the wrong network family.
+    pub fn check_chain_id(self, chain_id: u64) -> Result<()> {
+        if self == Profile::Testnet && chain_id == 1 {
+            return Err(invalid(
+                "profile",
+                "the testnet profile is connected to Ethereum mainnet (chain id 1); check safe.rpc_url",
+            )
+            .into());
+        }
+        Ok(())
+    }
+
+    /// The mode a component actually runs in: the local profile never goes live.
+    fn cap(self, mode: RunMode) -> RunMode {
+        match (self, mode) {
+            (Profile::Local, RunMode::Live) => RunMode::DryRun,
+            _ => mode,
+        }
+    }
+}
+
+/// How far the agent may go towards moving funds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunMode {
+    /// Monitor and log only; no transactions or routes are prepared.
+    Observe,
+    /// Prepare and simulate everything, but never broadcast or execute a bridge.
+    DryRun,
+    /// Execute for real.
+    Live,
+}
+
+impl fmt::Display for RunMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            RunMode::Observe => "observe",
+            RunMode::DryRun => "dry-run",
+            RunMode::Live => "live",
+        })
+    }
+}
+
+/// The global run mode and the mode each fund-moving component runs in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct ModePlan {
+    pub global: RunMode,
+    pub safe: RunMode,
+    pub router: RunMode,
+}
+
+impl ModePlan {
+    pub fn uniform(mode: RunMode) -> Self {
+        Self { global: mode, safe: mode, router: mode }
+    }
+
+    pub fn is_mixed(&self) -> bool {
+        self.safe != self.global || self.router != self.global
+    }
+}
+
+impl fmt::Display for ModePlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_mixed() {
+            write!(f, "{} (safe: {}, router: {})", self.global, self.safe, self.router)
+        } else {
+            write!(f, "{}", self.global)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+    /// env_logger style lines.
+    Text,
+    /// One JSON object per line with span fields attached.
+    Json,
+}
+
+/// Who signs off on fund-moving decisions before they are carried out.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ApprovalMode {
+    /// Act without asking.
+    #[default]
+    Auto,
+    /// Ask y/N on the terminal; needs stdin to be a TTY.
+    Prompt,
+    /// Write the request to `runtime.approval_file` and wait for an answer in it.
+    File,
+}
+
+impl fmt::Display for ApprovalMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ApprovalMode::Auto => "auto",
+            ApprovalMode::Prompt => "prompt",
+            ApprovalMode::File => "file",
+        })
+    }
+}
+
+/// Where the agent's view of the world comes from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SimulationMode {
+    /// The configured RPC endpoints, pool API and price sources.
+    #[default]
+    Off,
+    /// A bundled scenario replayed in-process, the same on every run with the same
+    /// seed; nothing leaves the process.
+    Deterministic,
+}
+
+impl fmt::Display for SimulationMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SimulationMode::Off => "off",
+            SimulationMode::Deterministic => "deterministic",
+        })
+    }
+}
+
+/// Shortest interval accepted for any scheduled phase.
+pub const MIN_INTERVAL_SECS: u64 = 5;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RuntimeConfig {
+    /// Seconds between monitoring cycles.
+    pub cycle_interval_secs: u64,
+    /// Balance check cadence; defaults to `cycle_interval_secs`.
+    pub balance_interval_secs: Option<u64>,
+    /// Pool refresh and routing decision cadence; defaults to `cycle_interval_secs`.
+    pub pool_interval_secs: Option<u64>,
+    /// Route status reporting cadence; defaults to `cycle_interval_secs`.
+    pub route_interval_secs: Option<u64>,
+    /// Longest delay between retries after consecutive failed cycles.
+    pub max_backoff_secs: u64,
+    /// Longest a cycle may run before it is abandoned and counted as failed;
+    /// defaults to twice `cycle_interval_secs`.
+    pub cycle_timeout_secs: Option<u64>,
+    pub log_format: LogFormat,
+    /// Defaults to live, or dry-run when only the legacy `safe.dry_run = true` is set.
+    pub mode: Option<RunMode>,
+    /// Allow `safe.dry_run` / `router.dry_run` to contradict `mode`.
+    pub allow_mixed_mode: bool,
+    /// Finish a cycle with a warning instead of failing it when the pool fetch fails
+    /// but the balance check passed; `degradation.pool_api = "skip_phase"` as it was
+    /// spelled before.
+    pub allow_partial_cycles: bool,
+    /// Log filter such as "info" or "asam=debug"; `RUST_LOG` wins when set.
+    pub log_level: Option<String>,
+    /// Run the `asam check` preflight checks before the first cycle and refuse to
+    /// start when one fails.
+    pub preflight: bool,
+    /// Reload the config file when it changes, not only on SIGHUP.
+    pub watch_config: bool,
+    /// Whether routes wait for an operator's approval.
+    pub approval: ApprovalMode,
+    /// Seconds to wait for an answer before declining.
+    pub approval_timeout_secs: u64,
+    /// Request and answer file for `approval = "file"`.
+    pub approval_file: Option<PathBuf>,
+    /// Replace every external source with a scripted one, for demos.
+    pub simulation: SimulationMode,
+    /// Seed of the simulated pool yields; the same seed gives the same cycles.
+    pub simulation_seed: u64,
+}
+
+impl Default for RuntimeConfig {
+    fn default() -> Self {
+        Self {
+            cycle_interval_secs: 60,
+            balance_interval_secs: None,
+            pool_interval_secs: None,
+            route_interval_secs: None,
+            max_backoff_secs: 900,
+            cycle_timeout_secs: None,
+            log_format: LogFormat::Text,
+            mode: None,
+            allow_mixed_mode: false,
+            allow_partial_cycles: false,
+            log_level: None,
+            preflight: false,
+            watch_config: false,
+            approval: ApprovalMode::Auto,
+            approval_timeout_secs: 60,
+            approval_file: None,
+            simulation: SimulationMode::Off,
+            simulation_seed: 42,
+        }
+    }
+}
+
+impl RuntimeConfig {
+    pub fn cycle_timeout(&self) -> Duration {
+        Duration::from_secs(self.cycle_timeout_secs.unwrap_or(self.cycle_interval_secs * 2))
+    }
+
+    pub fn approval_timeout(&self) -> Duration {
+        Duration::from_secs(self.approval_timeout_secs)
+    }
+}
+
+/// What a cycle does when a dependency it needs is down.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Fallback {
+    /// Fail the cycle.
+    #[default]
+    Fail,
+    /// Carry on without what the dependency provides.
+    SkipPhase,
+    /// Carry on with its last answer while that is younger than
+    /// `degradation.max_cache_age_secs`, then as `skip_phase`.
+    UseCache,
+    /// Carry on with its last answer however old; as `skip_phase` without one.
+    UseLastKnown,
+}
+
+impl fmt::Display for Fallback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Fallback::Fail => "fail",
+            Fallback::SkipPhase => "skip_phase",
+            Fallback::UseCache => "use_cache",
+            Fallback::UseLastKnown => "use_last_known",
+        })
+    }
+}
+
+/// The [`Fallback`] for each dependency of a cycle.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DegradationPolicy {
+    /// Only `fail`: nothing in a cycle can be decided without the balance.
+    pub rpc: Fallback,
+    /// Skipping leaves the cycle's decision blocked.
+    pub pool_api: Fallback,
+    /// `fail` or `skip_phase`, which holds instead of starting the route.
+    pub bridge_api: Fallback,
+    /// Skipping leaves the cycle without USD values.
+    pub prices: Fallback,
+    /// Oldest answer `use_cache` carries on with.
+    pub max_cache_age_secs: u64,
+}
+
+impl Default for DegradationPolicy {
+    fn default() -> Self {
+        Self {
+            rpc: Fallback::Fail,
+            pool_api: Fallback::Fail,
+            bridge_api: Fallback::Fail,
+            prices: Fallback::SkipPhase,
+            max_cache_age_secs: 900,
+        }
+    }
+}
+
+impl DegradationPolicy {
+    pub fn fallback(&self, dependency: Dependency) -> Fallback {
+        match dependency {
+            Dependency::Rpc => self.rpc,
+            Dependency::PoolApi => self.pool_api,
+            Dependency::BridgeApi => self.bridge_api,
+            Dependency::Prices => self.prices,
+        }
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.rpc != Fallback::Fail {
+            return Err(invalid("degradation.rpc", "only \"fail\" is supported; a cycle needs the balance").into());
+        }
+        if !matches!(self.bridge_api, Fallback::Fail | Fallback::SkipPhase) {
+            let reason = "must be \"fail\" or \"skip_phase\"; a route cannot start from a cached answer";
+            return Err(invalid("degradation.bridge_api", reason).into());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    /// Also write logs to this file, rotating it by size; stderr only when unset.
+    pub file_path: Option<PathBuf>,
+    /// The file is rotated before it grows past this size.
+    pub max_size_mb: u64,
+    /// Files kept, the active one included; the oldest is deleted on rotation.
+    pub max_files: usize,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            file_path: None,
+            max_size_mb: 10,
+            max_files: 5,
+        }
+    }
+}
+
+impl LogConfig {
+    pub fn max_size_bytes(&self) -> u64 {
+        self.max_size_mb * 1024 * 1024
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HttpConfig {
+    /// Address for the `/healthz` and `/status` server, e.g. "127.0.0.1:9090"; disabled when unset.
+    pub bind: Option<String>,
+    /// Bearer token expected by the `/admin` endpoints; disabled when unset.
+    pub admin_token: Option<Secret<String>>,
+    /// Append every admin action to this file as JSON lines.
+    pub audit_log_path: Option<PathBuf>,
+}
+
+impl HttpConfig {
+    pub fn bind_addr(&self) -> Result<Option<SocketAddr>> {
+        self.bind
+            .as_deref()
+            .map(|raw| {
+                raw.parse()
+                    .map_err(|e| invalid("http.bind", format!("'{}' is not a socket address: {}", raw, e)).into())
+            })
+            .transpose()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NotifyConfig {
+    /// Events waiting for delivery beyond this are dropped.
+    pub queue_capacity: usize,
+    /// Repeats of an error category within this many seconds of its alert are only
+    /// counted, then reported in one summary; 0 alerts on every error.
+    pub suppression_window_secs: u64,
+    /// A rebalance alert for the same pool within this many seconds of the last one is
+    /// dropped; 0 alerts on every decision.
+    pub rebalance_alert_cooldown_secs: u64,
+    pub webhook: WebhookConfig,
+    pub telegram: TelegramConfig,
+    pub discord: DiscordConfig,
+    pub email: EmailConfig,
+}
+
+impl Default for NotifyConfig {
+    fn default() -> Self {
+        Self {
+            queue_capacity: 100,
+            suppression_window_secs: 30 * 60,
+            rebalance_alert_cooldown_secs: 6 * 60 * 60,
+            webhook: WebhookConfig::default(),
+            telegram: TelegramConfig::default(),
+            discord: DiscordConfig::default(),
+            email: EmailConfig::default(),
+        }
+    }
+}
+
+impl NotifyConfig {
+    pub fn suppression_window(&self) -> Duration {
+        Duration::from_secs(self.suppression_window_secs)
+    }
+
+    pub fn rebalance_alert_cooldown(&self) -> Duration {
+        Duration::from_secs(self.rebalance_alert_cooldown_secs)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WebhookConfig {
+    /// Endpoint events are POSTed to; disabled when unset.
+    pub url: Option<String>,
+    /// Shared secret for the HMAC-SHA256 signature header.
+    pub secret: Option<Secret<String>>,
+    /// Retries for transient failures (network errors, 429 and 5xx).
+    pub max_retries: u32,
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        Self {
+            url: None,
+            secret: None,
+            max_retries: 3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TelegramConfig {
+    /// Bot API token; Telegram alerts are disabled unless this and `chat_id` are set.
+    pub bot_token: Option<Secret<String>>,
+    pub chat_id: Option<String>,
+    /// Events below this severity are not sent.
+    pub min_severity: Severity,
+    pub api_url: String,
+    /// Telegram allows about 20 messages per minute to one chat.
+    pub max_per_minute: u32,
+    /// How long to wait for more events before sending, so bursts become one digest.
+    pub digest_window_ms: u64,
+}
+
+impl Default for TelegramConfig {
+    fn default() -> Self {
+        Self {
+            bot_token: None,
+            chat_id: None,
+            min_severity: Severity::Info,
+            api_url: "https://api.telegram.org".to_string(),
+            max_per_minute: 20,
+            digest_window_ms: 2000,
+        }
+    }
+}
+
+/// How the SMTP connection is encrypted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SmtpTls {
+    /// Plain connection upgraded with STARTTLS, which must succeed; port 587.
+    #[default]
+    Starttls,
+    /// TLS from the first byte; port 465.
+    Implicit,
+    /// Unencrypted, for a relay on localhost only.
+    None,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EmailConfig {
+    /// SMTP server; email alerts are disabled unless this, `from` and `to` are set.
+    pub smtp_host: Option<String>,
+    /// Defaults to the standard port for `tls`.
+    pub smtp_port: Option<u16>,
+    pub username: Option<String>,
+    pub password: Option<Secret<String>>,
+    pub from: Option<String>,
+    pub to: Vec<String>,
+    pub tls: SmtpTls,
+    /// Events below this severity are not sent.
+    pub min_severity: Severity,
+}
+
+impl Default for EmailConfig {
+    fn default() -> Self {
+        Self {
+            smtp_host: None,
+            smtp_port: None,
+            username: None,
+            password: None,
+            from: None,
+            to: Vec::new(),
+            tls: SmtpTls::Starttls,
+            min_severity: Severity::Critical,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DiscordConfig {
+    /// Discord webhook URL; disabled when unset.
+    pub webhook_url: Option<Secret<String>>,
+    /// Events below this severity are not sent.
+    pub min_severity: Severity,
+    /// Block explorer used for transaction and address links.
+    pub explorer_url: String,
+    /// Explorer used for bridge route links.
+    pub bridge_explorer_url: String,
+    /// Retries after rate limiting (429) or server errors.
+    pub max_retries: u32,
+}
+
+impl Default for DiscordConfig {
+    fn default() -> Self {
+        Self {
+            webhook_url: None,
+            min_severity: Severity::Info,
+            explorer_url: "https://etherscan.io".to_string(),
+            bridge_explorer_url: "https://scan.li.fi".to_string(),
+            max_retries: 3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StorageConfig {
+    /// SQLite database for cycle, route and transaction history; kept in memory when unset.
+    pub path: Option<PathBuf>,
+    /// How often position values are snapshotted for yield tracking.
+    pub snapshot_interval_secs: u64,
+    /// Every routing decision and its inputs are appended here as JSON lines.
+    pub decision_log_path: Option<PathBuf>,
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self { path: None, snapshot_interval_secs: 3600, decision_log_path: None }
+    }
+}
+
+impl StorageConfig {
+    pub fn snapshot_interval(&self) -> Duration {
+        Duration::from_secs(self.snapshot_interval_secs)
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KillSwitchConfig {
+    /// Fund movement is paused while a file exists at this path.
+    pub path: Option<PathBuf>,
+    /// Start paused; only lifted by unsetting it and restarting.
+    pub paused: bool,
+}
+
+/// Where USD prices come from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PriceSourceKind {
+    /// Chainlink feeds, then CoinGecko for the rest.
+    #[default]
+    Composite,
+    Chainlink,
+    Coingecko,
+    /// No USD values.
+    None,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PricesConfig {
+    pub source: PriceSourceKind,
+    pub coingecko_url: String,
+    /// Sent as `x-cg-pro-api-key`; pair it with the pro API URL.
+    pub coingecko_api_key: Option<Secret<String>>,
+    /// CoinGecko id by symbol.
+    pub coingecko_ids: BTreeMap<String, String>,
+    /// How long a CoinGecko answer is reused.
+    pub cache_ttl_secs: u64,
+    /// Least time between two CoinGecko requests.
+    pub min_request_interval_ms: u64,
+    pub timeout_secs: u64,
+    /// Chainlink USD feed on the home chain by symbol.
+    pub chainlink_feeds: BTreeMap<String, String>,
+    /// Prices the source updated longer ago than this are treated as unknown.
+    pub max_age_secs: u64,
+}
+
+impl Default for PricesConfig {
+    fn default() -> Self {
+        let map = |entries: &[(&str, &str)]| entries.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        Self {
+            source: PriceSourceKind::Composite,
+            coingecko_url: "https://api.coingecko.com/api/v3".to_string(),
+            coingecko_api_key: None,
+            coingecko_ids: map(&[
+                ("ETH", "ethereum"),
+                ("WETH", "weth"),
+                ("USDC", "usd-coin"),
+                ("USDT", "tether"),
+                ("DAI", "dai"),
+                ("WBTC", "wrapped-bitcoin"),
+            ]),
+            cache_ttl_secs: 60,
+            // The free tier allows about 30 requests a minute
+            min_request_interval_ms: 2000,
+            timeout_secs: 10,
+            chainlink_feeds: map(&[
+                ("ETH", "0x5f4eC3Df9cbd43714FE2740f5E3616155c5b8419"),
+                ("USDC", "0x8fFfFfd4AfB6115b954Bd326cbe7B4BA576818f6"),
+                ("USDT", "0x3E7d1eAB13ad0104d2750B8863b489D65364e32D"),
+                ("DAI", "0xAed0c38402a5d19df6E4c03F4E2DceD6e29c1ee9"),
+                ("WBTC", "0xF4030086522a5bEEa4988F8cA5B36dbC97BeE88c"),
+            ]),
+            max_age_secs: 3600,
+        }
+    }
+}
+
+impl PricesConfig {
+    /// Fails on a Chainlink feed that is not an address.
+    pub fn validate_feeds(&self) -> Result<()> {
+        for (symbol, raw) in &self.chainlink_feeds {
+            Address::from_str(raw).map_err(|e| {
+                invalid(format!("prices.chainlink_feeds.{}", symbol), format!("'{}' is not a valid address: {}", raw, e))
+            })?;
+        }
+        Ok(())
+    }
+}
+
+/// QuoterV2 on Ethereum mainnet.
+pub const MAINNET_QUOTER_V2: &str = "0x61fFE014bA17989E743c5F6cB21bF9697530dD6e";
+
+/// Swap cost estimation for pools whose underlying is not the held asset.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SwapConfig {
+    /// Asset the Safe holds between pools.
+    pub held_asset: String,
+    /// Underlying asset by protocol name; unlisted protocols take the held asset.
+    pub protocol_assets: BTreeMap<String, String>,
+    /// Uniswap V3 QuoterV2 address by chain name.
+    pub quoters: BTreeMap<String, String>,
+    /// Uniswap V3 pools to quote the swap from the held asset into each underlying.
+    pub pairs: Vec<SwapPairConfig>,
+}
+
+impl Default for SwapConfig {
+    fn default() -> Self {
+        Self {
+            held_asset: "ETH".to_string(),
+            protocol_assets: BTreeMap::new(),
+            quoters: BTreeMap::from([("Ethereum".to_string(), MAINNET_QUOTER_V2.to_string())]),
+            pairs: Vec::new(),
+        }
+    }
+}
+
+impl SwapConfig {
+    /// Underlying asset of `protocol`'s pools.
+    pub fn asset_of(&self, protocol: &str) -> &str {
+        self.protocol_assets.get(protocol).unwrap_or(&self.held_asset)
+    }
+
+    /// The pair to quote for a pool of `protocol` on `chain`; `None` when the pool holds
+    /// the held asset or no pair is configured for its underlying.
+    pub fn pair_for(&self, protocol: &str, chain: &str) -> Option<&SwapPairConfig> {
+        let asset = self.asset_of(protocol);
+        if asset.eq_ignore_ascii_case(&self.held_asset) {
+            return None;
+        }
+        self.pairs.iter().find(|pair| pair.chain == chain && pair.asset.eq_ignore_ascii_case(asset))
+    }
+
+    /// QuoterV2 address on `chain`, if one is configured.
+    pub fn quoter(&self, chain: &str) -> Result<Option<Address>> {
+        self.quoters
+            .get(chain)
+            .map(|raw| {
+                Address::from_str(raw).map_err(|e| {
+                    invalid(format!("swap.quoters.{}", chain), format!("'{}' is not a valid address: {}", raw, e)).into()
+                })
+            })
+            .transpose()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SwapPairConfig {
+    /// Underlying asset this pair swaps into, as named in `protocol_assets`.
+    pub asset: String,
+    #[serde(default = "default_swap_chain")]
+    pub chain: String,
+    /// Token the held asset is swapped from, e.g. WETH.
+    pub token_in: String,
+    pub token_out: String,
+    /// Pool fee tier in hundredths of a basis point: 100, 500, 3000 or 10000.
+    #[serde(default = "default_swap_fee")]
+    pub fee: u32,
+}
+
+fn default_swap_chain() -> String {
+    "Ethereum".to_string()
+}
+
+fn default_swap_fee() -> u32 {
+    3000
+}
+
+impl SwapPairConfig {
+    pub fn token_in(&self) -> Result<Address> {
+        Address::from_str(&self.token_in)
+            .map_err(|e| invalid("swap.pairs.token_in", format!("'{}' is not a valid address: {}", self.token_in, e)).into())
+    }
+
+    pub fn token_out(&self) -> Result<Address> {
+        Address::from_str(&self.token_out)
+            .map_err(|e| invalid("swap.pairs.token_out", format!("'{}' is not a valid address: {}", self.token_out, e)).into())
+    }
+}
+
+/// Safe's MultiSendCallOnly v1.3.0, at the same address on every chain.
+pub const MULTI_SEND_CALL_ONLY: &str = "0x40A2aCCbd92BCA938b02010E17A5b8929b49130D";
+/// Aave v3 Pool on Ethereum mainnet.
+pub const MAINNET_AAVE_V3_POOL: &str = "0x87870Bca3F3fD6335C3F4ce8392D69350B4fA4E2";
+/// Compound v3 WETH market (cWETHv3) on Ethereum mainnet.
+pub const MAINNET_COMET_WETH: &str = "0xA17581A9E3356d9A858b789D68B4d866e593aE94";
+/// Wrapped ether on Ethereum mainnet.
+pub const MAINNET_WETH: &str = "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2";
+
+/// Contracts for entering pools through the Safe. Pools of a protocol without a
+/// market here are only suggested.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ActionsConfig {
+    /// MultiSend contract the Safe delegatecalls to batch an approval with a supply.
+    pub multi_send: String,
+    pub aave_v3: Vec<AaveMarketConfig>,
+    pub compound_v3: Vec<CometMarketConfig>,
+}
+
+impl Default for ActionsConfig {
+    fn default() -> Self {
+        Self {
+            multi_send: MULTI_SEND_CALL_ONLY.to_string(),
+            aave_v3: vec![AaveMarketConfig {
+                chain: "Ethereum".to_string(),
+                pool: MAINNET_AAVE_V3_POOL.to_string(),
+                asset: MAINNET_WETH.to_string(),
+            }],
+            compound_v3: vec![CometMarketConfig {
+                chain: "Ethereum".to_string(),
+                comet: MAINNET_COMET_WETH.to_string(),
+                asset: MAINNET_WETH.to_string(),
+            }],
+        }
+    }
+}
+
+impl ActionsConfig {
+    pub fn multi_send(&self) -> Result<Address> {
+        parse_address("actions.multi_send", &self.multi_send)
+    }
+}
+
+/// One Aave v3 market: the Pool contract on a chain and the asset supplied to it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AaveMarketConfig {
+    pub chain: String,
+    pub pool: String,
+    /// ERC-20 token supplied, e.g. WETH.
+    pub asset: String,
+}
+
+impl AaveMarketConfig {
+    pub fn pool(&self) -> Result<Address> {
+        parse_address("actions.aave_v3.pool", &self.pool)
+    }
+
+    pub fn asset(&self) -> Result<Address> {
+        parse_address("actions.aave_v3.asset", &self.asset)
+    }
+}
+
+/// One Compound v3 market: the Comet proxy on a chain and its base asset.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CometMarketConfig {
+    pub chain: String,
+    pub comet: String,
+    /// The Comet's base asset, e.g. WETH for cWETHv3.
+    pub asset: String,
+}
+
+impl CometMarketConfig {
+    pub fn comet(&self) -> Result<Address> {
+        parse_address("actions.compound_v3.comet", &self.comet)
+    }
+
+    pub fn asset(&self) -> Result<Address> {
+        parse_address("actions.compound_v3.asset", &self.asset)
+    }
+}
+
+fn parse_address(key: &str, raw: &str) -> Result<Address> {
+    Address::from_str(raw).map_err(|e| invalid(key, format!("'{}' is not a valid address: {}", raw, e)).into())
+}
+
+/// An address that may be configured as an ENS name, which must have been resolved
+/// to `resolved` by then.
+fn resolved_address(key: &str, raw: &str, resolved: Option<Address>) -> Result<Address> {
+    if !crate::ens::is_ens_name(raw) {
+        return parse_address(key, raw);
+    }
+    resolved.ok_or_else(|| invalid(key, format!("ENS name '{}' has not been resolved", raw)).into())
+}
+
+/// A whole number of wei written in decimal, with optional `_` separators.
+fn parse_wei(key: &str, raw: &str) -> Result<U256> {
+    let digits = raw.trim().replace('_', "");
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(invalid(key, format!("'{}' is not a whole number of wei", raw)).into());
+    }
+    U256::from_dec_str(&digits).map_err(|_| invalid(key, format!("'{}' does not fit in 256 bits", raw)).into())
+}
+
+impl Config {
+    /// Loads the config file (if any), applies environment overrides and validates the result.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let mut config = match path {
+            Some(path) => Self::from_file(path)?,
+            None => Self::default(),
+        };
+        config.apply_overrides(|key| std::env::var(key).ok())?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path).map_err(|e| ConfigError::Read {
+            path: path.display().to_string(),
+            reason: e.to_string(),
+        })?;
+        Self::from_toml_str(&contents).map_err(|e| match e.downcast::<ConfigError>() {
+            Ok(ConfigError::Parse { reason, .. }) => ConfigError::Parse {
+                path: path.display().to_string(),
+                reason,
+            }
+            .into(),
+            Ok(other) => other.into(),
+            Err(other) => other,
+        })
+    }
+
+    pub fn from_toml_str(contents: &str) -> Result<Self> {
+        let parse_err = |e: toml::de::Error| ConfigError::Parse {
+            path: "<inline>".to_string(),
+            reason: e.to_string(),
+        };
+        let mut config: Self = toml::from_str(contents).map_err(parse_err)?;
+        let file: toml::Table = toml::from_str(contents).map_err(parse_err)?;
+        config.apply_profile_defaults(&file);
+        Ok(config)
+    }
+
+    /// Replaces mainnet defaults the file did not set with the profile's own.
+    fn apply_profile_defaults(&mut self, file: &toml::Table) {
+        let is_set = |path: &[&str]| {
+            let mut value = file.get(path[0]);
+            for key in &path[1..] {
+                value = value.and_then(|v| v.get(key));
+            }
+            value.is_some()
+        };
+        if !is_set(&["router", "chains"]) {
+            self.router.chains = self.profile.default_chains();
+        }
+        if !is_set(&["router", "status", "lifi_api_url"]) {
+            self.router.status.lifi_api_url = self.profile.lifi_api_url().to_string();
+        }
+        if !is_set(&["optimizer", "use_mock"]) {
+            self.optimizer.use_mock = self.profile.uses_mock_pools();
+        }
+        // Test tokens have no market price
+        if !is_set(&["prices", "source"]) && self.profile != Profile::Mainnet {
+            self.prices.source = PriceSourceKind::None;
+        }
+        // The default markets are mainnet's
+        if !is_set(&["actions", "aave_v3"]) && self.profile != Profile::Mainnet {
+            self.actions.aave_v3.clear();
+        }
+        if !is_set(&["actions", "compound_v3"]) && self.profile != Profile::Mainnet {
+            self.actions.compound_v3.clear();
+        }
+    }
+
+    /// Applies environment overrides, looking each variable up through `lookup`.
+    pub fn apply_overrides<F>(&mut self, lookup: F) -> Result<()>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        for (var, key) in ENV_OVERRIDES {
+            let Some(value) = lookup(var) else { continue };
+            let parse_err = |reason: String| invalid(*key, format!("{} (from {})", reason, var));
+
+            match *key {
+                "safe.rpc_url" => self.safe.rpc_url = Some(value),
+                "safe.chain_id" => {
+                    self.safe.chain_id = Some(value.parse().map_err(|e| parse_err(format!("{}", e)))?)
+                }
+                "safe.rpc_timeout_secs" => {
+                    self.safe.rpc_timeout_secs = value.parse().map_err(|e| parse_err(format!("{}", e)))?
+                }
+                "safe.address" => self.safe.address = Some(value),
+                "safe.min_balance_eth" => {
+                    self.safe.min_balance_eth = value.parse().map_err(|e| parse_err(format!("{}", e)))?
+                }
+                "safe.min_balance_wei" => self.safe.min_balance_wei = Some(value),
+                "safe.gas_buffer" => self.safe.gas_buffer = value.parse().map_err(|e| parse_err(format!("{}", e)))?,
+                "safe.balance_recovery_factor" => {
+                    self.safe.balance_recovery_factor = value.parse().map_err(|e| parse_err(format!("{}", e)))?
+                }
+                "safe.max_gas_limit" => {
+                    self.safe.max_gas_limit = value.parse().map_err(|e| parse_err(format!("{}", e)))?
+                }
+                "safe.critical_balance_eth" => {
+                    self.safe.critical_balance_eth = Some(value.parse().map_err(|e| parse_err(format!("{}", e)))?)
+                }
+                "safe.critical_balance_wei" => self.safe.critical_balance_wei = Some(value),
+                "safe.max_value_per_tx_wei" => self.safe.max_value_per_tx_wei = Some(value),
+                "safe.max_value_per_day_wei" => self.safe.max_value_per_day_wei = Some(value),
+                "safe.daily_gas_budget_wei" => self.safe.daily_gas_budget_wei = Some(value),
+                "safe.dry_run" => {
+                    self.safe.dry_run =
+                        Some(parse_bool(&value).ok_or_else(|| parse_err("expected true or false".into()))?)
+                }
+                "safe.allow_delegatecall" => {
+                    self.safe.allow_delegatecall =
+                        parse_bool(&value).ok_or_else(|| parse_err("expected true or false".into()))?
+                }
+                "safe.execution" => {
+                    self.safe.execution = toml::Value::String(value.to_lowercase())
+                        .try_into()
+                        .map_err(|_| parse_err(format!("expected execute, propose or simulate, got '{}'", value)))?
+                }
+                "safe.signer_key" => self.safe.signer_key = Some(value.into()),
+                "safe.signer" => {
+                    self.safe.signer = toml::Value::String(value.to_lowercase())
+                        .try_into()
+                        .map_err(|_| parse_err(format!("expected key or ledger, got '{}'", value)))?
+                }
+                "safe.ledger_path" => self.safe.ledger_path = value,
+                "safe.transfers.enabled" => {
+                    self.safe.transfers.enabled =
+                        parse_bool(&value).ok_or_else(|| parse_err("expected true or false".into()))?
+                }
+                "safe.top_up.funding_key" => {
+                    self.safe.top_up.get_or_insert_with(TopUpConfig::default).funding_key = Some(value.into())
+                }
+                "optimizer.api_url" => self.optimizer.api_url = value,
+                "optimizer.timeout_secs" => {
+                    self.optimizer.timeout_secs = value.parse().map_err(|e| parse_err(format!("{}", e)))?
+                }
+                "router.status.interval_secs" => {
+                    self.router.status.interval_secs = value.parse().map_err(|e| parse_err(format!("{}", e)))?
+                }
+                "router.status.lifi_api_url" => self.router.status.lifi_api_url = value,
+                "router.status.socket_api_url" => self.router.status.socket_api_url = value,
+                "router.status.socket_api_key" => self.router.status.socket_api_key = Some(value.into()),
+                "runtime.cycle_interval_secs" => {
+                    self.runtime.cycle_interval_secs = value.parse().map_err(|e| parse_err(format!("{}", e)))?
+                }
+                "runtime.log_format" => {
+                    self.runtime.log_format = toml::Value::String(value.to_lowercase())
+                        .try_into()
+                        .map_err(|_| parse_err(format!("unknown log format '{}'", value)))?
+                }
+                "runtime.mode" => {
+                    self.runtime.mode = Some(
+                        toml::Value::String(value.to_lowercase().replace('-', "_"))
+                            .try_into()
+                            .map_err(|_| parse_err(format!("expected observe, dry_run or live, got '{}'", value)))?,
+                    )
+                }
+                "log.file_path" => self.log.file_path = Some(PathBuf::from(value)),
+                "http.bind" => self.http.bind = Some(value),
+                "notify.webhook.url" => self.notify.webhook.url = Some(value),
+                "notify.webhook.secret" => self.notify.webhook.secret = Some(value.into()),
+                "notify.telegram.bot_token" => self.notify.telegram.bot_token = Some(value.into()),
+                "notify.telegram.chat_id" => self.notify.telegram.chat_id = Some(value),
+                "notify.discord.webhook_url" => self.notify.discord.webhook_url = Some(value.into()),
+                "notify.email.smtp_host" => self.notify.email.smtp_host = Some(value),
+                "notify.email.username" => self.notify.email.username = Some(value),
+                "notify.email.password" => self.notify.email.password = Some(value.into()),
+                "notify.email.from" => self.notify.email.from = Some(value),
+                "notify.email.to" => {
+                    self.notify.email.to = value
+                        .split(',')
+                        .map(|to| to.trim().to_string())
+                        .filter(|to| !to.is_empty())
+                        .collect()
+                }
+                "storage.path" => self.storage.path = Some(PathBuf::from(value)),
+                "kill_switch.path" => self.kill_switch.path = Some(PathBuf::from(value)),
+                "kill_switch.paused" => {
+                    self.kill_switch.paused =
+                        parse_bool(&value).ok_or_else(|| parse_err("expected true or false".into()))?
+                }
+                "http.admin_token" => self.http.admin_token = Some(value.into()),
+                "prices.coingecko_api_key" => self.prices.coingecko_api_key = Some(value.into()),
+                "runtime.preflight" => {
+                    self.runtime.preflight =
+                        parse_bool(&value).ok_or_else(|| parse_err("expected true or false".into()))?
+                }
+                "runtime.approval" => {
+                    self.runtime.approval = toml::Value::String(value.to_lowercase())
+                        .try_into()
+                        .map_err(|_| parse_err(format!("expected auto, prompt or file, got '{}'", value)))?
+                }
+                "runtime.simulation" => {
+                    self.runtime.simulation = toml::Value::String(value.to_lowercase())
+                        .try_into()
+                        .map_err(|_| parse_err(format!("expected off or deterministic, got '{}'", value)))?
+                }
+                "runtime.simulation_seed" => {
+                    self.runtime.simulation_seed = value.parse().map_err(|e| parse_err(format!("{}", e)))?
+                }
+                _ => unreachable!("unhandled override key {}", key),
+            }
+        }
+        Ok(())
+    }
+
+    /// The global run mode, after the profile's limits.
+    /// The degradation policy in effect; the legacy `runtime.allow_partial_cycles`
+    /// skips a failed pool fetch unless `degradation.pool_api` says otherwise.
+    pub fn degradation_policy(&self) -> DegradationPolicy {
+        let mut policy = self.degradation.clone();
+        if self.runtime.allow_partial_cycles && policy.pool_api == Fallback::Fail {
+            policy.pool_api = Fallback::SkipPhase;
+        }
+        policy
+    }
+
+    pub fn run_mode(&self) -> RunMode {
+        self.profile.cap(self.requested_mode())
+    }
+
+    fn requested_mode(&self) -> RunMode {
+        match (self.runtime.mode, self.safe.dry_run) {
+            (Some(mode), _) => mode,
+            (None, Some(true)) => RunMode::DryRun,
+            (None, _) => RunMode::Live,
+        }
+    }
+
+    /// Derives each component's mode from `runtime.mode`. A per-component `dry_run`
+    /// that contradicts it is an error unless `runtime.allow_mixed_mode` is set. On the
+    /// local profile live components run in dry-run mode instead.
+    pub fn mode_plan(&self) -> Result<ModePlan> {
+        let plan = self.requested_plan()?;
+        let cap = |mode| self.profile.cap(mode);
+        Ok(ModePlan { global: cap(plan.global), safe: cap(plan.safe), router: cap(plan.router) })
+    }
+
+    /// Whether the local profile turned a requested live mode into dry-run.
+    pub fn live_downgraded(&self) -> bool {
+        match (self.requested_plan(), self.mode_plan()) {
+            (Ok(requested), Ok(plan)) => requested != plan,
+            _ => false,
+        }
+    }
+
+    /// Chain id the Safe's endpoints must serve: `safe.chain_id`, else the
+    /// `[[router.chains]]` Ethereum entry's, else mainnet's.
+    pub fn home_chain_id(&self) -> u64 {
+        self.safe.chain_id.unwrap_or_else(|| {
+            self.router.chains.iter().find(|chain| chain.name == HOME_CHAIN).map_or(1, |chain| chain.chain_id)
+        })
+    }
+
+    /// The `[[strategies]]` entry that acts, if any.
+    pub fn active_strategy(&self) -> Option<&NamedStrategyConfig> {
+        self.strategies.iter().find(|named| named.active)
+    }
+
+    /// The pool source and filters of the strategy that acts.
+    pub fn acting_optimizer(&self) -> OptimizerConfig {
+        match self.active_strategy() {
+            Some(named) => named.optimizer.apply(&self.optimizer),
+            None => self.optimizer.clone(),
+        }
+    }
+
+    /// The decision thresholds of the strategy that acts.
+    pub fn acting_strategy(&self) -> &StrategyConfig {
+        self.active_strategy().map_or(&self.strategy, |named| &named.strategy)
+    }
+
+    /// How long recent actions are kept: the longest of the alert cooldown and the
+    /// route dedup window.
+    pub fn recent_action_retention(&self) -> Duration {
+        self.notify.rebalance_alert_cooldown().max(self.router.route_dedup_window())
+    }
+
+    /// Refuses live mode on the mainnet profile unless
+    /// `i_understand_this_moves_real_funds` is set.
+    pub fn check_live_acknowledged(&self) -> Result<()> {
+        let plan = self.mode_plan()?;
+        let live = [plan.global, plan.safe, plan.router].contains(&RunMode::Live);
+        // A simulated run has no real funds to move
+        let simulated = self.runtime.simulation != SimulationMode::Off;
+        if self.profile == Profile::Mainnet && live && !simulated && !self.i_understand_this_moves_real_funds {
+            return Err(invalid(
+                "i_understand_this_moves_real_funds",
+                "live mode on the mainnet profile moves real funds; set it to true, or use runtime.mode = \"dry_run\"",
+            )
+            .into());
+        }
+        Ok(())
+    }
+
+    fn requested_plan(&self) -> Result<ModePlan> {
+        let global = self.requested_mode();
+        let component = |key: &str, dry_run: Option<bool>| -> Result<RunMode> {
+            let Some(dry_run) = dry_run else { return Ok(global) };
+            if dry_run == (global != RunMode::Live) {
+                return Ok(global);
+            }
+            if !self.runtime.allow_mixed_mode {
+                return Err(invalid(
+                    key,
+                    format!(
+                        "dry_run = {} contradicts runtime.mode = {} (set runtime.allow_mixed_mode = true to allow)",
+                        dry_run, global
+                    ),
+                )
+                .into());
+            }
+            Ok(if dry_run { RunMode::DryRun } else { RunMode::Live })
+        };
+        Ok(ModePlan {
+            safe: component("safe.dry_run", self.safe.dry_run)?,
+            router: component("router.dry_run", self.router.dry_run)?,
+            ..ModePlan::uniform(global)
+        })
+    }
+
+    /// The [`Secret`] fields by dotted key, set or not.
+    pub fn secret_fields(&self) -> [(&'static str, Option<&Secret<String>>); 9] {
+        [
+            ("safe.signer_key", self.safe.signer_key.as_ref()),
+            ("safe.top_up.funding_key", self.safe.top_up.as_ref().and_then(|top_up| top_up.funding_key.as_ref())),
+            ("router.status.socket_api_key", self.router.status.socket_api_key.as_ref()),
+            ("notify.webhook.secret", self.notify.webhook.secret.as_ref()),
+            ("http.admin_token", self.http.admin_token.as_ref()),
+            ("notify.telegram.bot_token", self.notify.telegram.bot_token.as_ref()),
+            ("notify.discord.webhook_url", self.notify.discord.webhook_url.as_ref()),
+            ("notify.email.password", self.notify.email.password.as_ref()),
+            ("prices.coingecko_api_key", self.prices.coingecko_api_key.as_ref()),
+        ]
+    }
+
+    /// Configured values that must never be written to logs.
+    pub fn secrets(&self) -> Vec<String> {
+        let mut secrets: Vec<String> =
+            self.secret_fields().into_iter().filter_map(|(_, secret)| Some(secret?.expose().clone())).collect();
+        // Hosted RPC endpoints carry the API key in the path or query string
+        let chain_urls = self.router.chains.iter().filter_map(|c| c.rpc_url.as_ref());
+        for url in self.safe.rpc_url.iter().chain(&self.safe.rpc_urls).chain(chain_urls) {
+            secrets.extend(
+                url.split([',', '/', '?', '&', '='])
+                    .filter(|part| part.len() >= 16 && !part.contains('.'))
+                    .map(str::to_string),
+            );
+        }
+        secrets.retain(|secret| !secret.is_empty());
+        secrets
+    }
+
+    /// Checks cross-field constraints, naming the offending key on failure.
+    pub fn validate(&self) -> Result<()> {
+        if let Some(url) = &self.safe.rpc_url {
+            if url.trim().is_empty() {
+                return Err(invalid("safe.rpc_url", "must not be empty").into());
+            }
+        }
+        if self.safe.rpc_url.is_some() || !self.safe.rpc_urls.is_empty() {
+            for url in self.safe.rpc_urls()? {
+                if EndpointKind::parse(url).is_err() {
+                    return Err(invalid("safe.rpc_url", format!("'{}' is not an RPC endpoint", redact_url(url))).into());
+                }
+            }
+        }
+        if self.safe.rpc_timeout_secs == 0 || self.safe.rpc_probe_interval_secs == 0 {
+            return Err(invalid("safe.rpc_timeout_secs/rpc_probe_interval_secs", "must be at least 1 second").into());
+        }
+        if self.safe.rpc_retry_attempts == 0 {
+            return Err(invalid("safe.rpc_retry_attempts", "must be at least 1").into());
+        }
+        if self.safe.confirmation_timeout_secs == 0 {
+            return Err(invalid("safe.confirmation_timeout_secs", "must be at least 1 second").into());
+        }
+        if self.safe.max_submissions_per_minute == 0 {
+            return Err(invalid("safe.max_submissions_per_minute", "must be at least 1").into());
+        }
+        // ENS names are resolved once connected, see crate::ens::resolve_config
+        if self.safe.address.as_deref().is_some_and(|raw| !crate::ens::is_ens_name(raw)) {
+            self.safe.address()?;
+        }
+        self.mode_plan()?;
+        if !(self.safe.min_balance_eth.is_finite() && self.safe.min_balance_eth >= 0.0) {
+            return Err(invalid("safe.min_balance_eth", "must be a non-negative number").into());
+        }
+        if let Some(eth) = self.safe.critical_balance_eth.filter(|eth| !(eth.is_finite() && *eth >= 0.0)) {
+            return Err(invalid("safe.critical_balance_eth", format!("{} must be a non-negative number", eth)).into());
+        }
+        for (key, wei) in [
+            ("safe.min_balance_wei", &self.safe.min_balance_wei),
+            ("safe.max_value_per_tx_wei", &self.safe.max_value_per_tx_wei),
+            ("safe.max_value_per_day_wei", &self.safe.max_value_per_day_wei),
+            ("safe.daily_gas_budget_wei", &self.safe.daily_gas_budget_wei),
+        ] {
+            if let Some(wei) = wei {
+                parse_wei(key, wei)?;
+            }
+        }
+        let critical_key = match (&self.safe.critical_balance_wei, self.safe.critical_balance_eth) {
+            (Some(wei), _) => Some(parse_wei("safe.critical_balance_wei", wei).map(|_| "safe.critical_balance_wei")?),
+            (None, Some(_)) => Some("safe.critical_balance_eth"),
+            (None, None) => None,
+        };
+        if let Some(key) = critical_key.filter(|_| self.safe.critical_balance() >= self.safe.min_balance()) {
+            return Err(invalid(
+                key,
+                format!(
+                    "{} wei must be below the minimum balance of {} wei",
+                    self.safe.critical_balance(),
+                    self.safe.min_balance()
+                ),
+            )
+            .into());
+        }
+        for (i, token) in self.safe.tokens.iter().enumerate() {
+            if token.symbol.trim().is_empty() {
+                return Err(invalid(format!("safe.tokens[{}].symbol", i), "must not be empty").into());
+            }
+            token.address()?;
+            if token.decimals.is_some_and(|decimals| decimals > 77) {
+                return Err(invalid(format!("safe.tokens.{}.decimals", token.symbol), "must be at most 77").into());
+            }
+        }
+        let mut accounts = BTreeSet::new();
+        for (i, account) in self.safe.accounts.iter().enumerate() {
+            let unresolved = account.resolved.is_none() && crate::ens::is_ens_name(&account.address);
+            let address = if unresolved { None } else { Some(account.address()?) };
+            if let Some(address) = address.filter(|a| self.safe.address().ok() == Some(*a) || !accounts.insert(*a)) {
+                return Err(invalid(format!("safe.accounts[{}].address", i), format!("{:?} is already monitored", address)).into());
+            }
+            if let Some(eth) = account.min_balance_eth.filter(|eth| !(eth.is_finite() && *eth >= 0.0)) {
+                return Err(invalid(
+                    format!("safe.accounts[{}].min_balance_eth", i),
+                    format!("{} must be a non-negative number", eth),
+                )
+                .into());
+            }
+        }
+        if !(self.safe.balance_recovery_factor.is_finite() && self.safe.balance_recovery_factor >= 1.0) {
+            return Err(invalid(
+                "safe.balance_recovery_factor",
+                format!("{} must be a multiplier of at least 1", self.safe.balance_recovery_factor),
+            )
+            .into());
+        }
+        if !(self.safe.gas_buffer.is_finite() && self.safe.gas_buffer >= 1.0) {
+            return Err(invalid("safe.gas_buffer", format!("{} must be a multiplier of at least 1", self.safe.gas_buffer)).into());
+        }
+        if self.safe.max_gas_limit < 21_000 {
+            return Err(invalid("safe.max_gas_limit", "must be at least 21000, the gas of a plain transfer").into());
+        }
+        if let Some(gwei) = self.safe.priority_fee_gwei.filter(|gwei| !(gwei.is_finite() && *gwei >= 0.0)) {
+            return Err(invalid("safe.priority_fee_gwei", format!("{} must be a non-negative number", gwei)).into());
+        }
+        for (chain, url) in &self.safe.transaction_service_urls {
+            if !(url.starts_with("http://") || url.starts_with("https://")) {
+                return Err(invalid(
+                    format!("safe.transaction_service_urls.{}", chain),
+                    format!("'{}' is not an http(s) URL", redact_url(url)),
+                )
+                .into());
+            }
+        }
+        if self.safe.signer == SignerKind::Ledger {
+            let path = &self.safe.ledger_path;
+            if !path.starts_with("m/") || path[2..].split('/').any(|part| part.trim_end_matches('\'').parse::<u32>().is_err()) {
+                return Err(invalid("safe.ledger_path", format!("'{}' is not a derivation path like m/44'/60'/0'/0/0", path)).into());
+            }
+            if self.safe.ledger_timeout_secs == 0 {
+                return Err(invalid("safe.ledger_timeout_secs", "must be at least 1 second").into());
+            }
+        }
+        if self.safe.transfers.max_block_range == 0 {
+            return Err(invalid("safe.transfers.max_block_range", "must be at least 1 block").into());
+        }
+        if let Some(top_up) = &self.safe.top_up {
+            for (key, eth) in [
+                ("safe.top_up.target_balance_eth", top_up.target_balance_eth),
+                ("safe.top_up.max_per_day_eth", top_up.max_per_day_eth),
+                ("safe.top_up.reserve_eth", Some(top_up.reserve_eth)),
+            ] {
+                if let Some(eth) = eth.filter(|eth| !(eth.is_finite() && *eth >= 0.0)) {
+                    return Err(invalid(key, format!("{} is not a non-negative amount", eth)).into());
+                }
+            }
+            if top_up.max_per_day_eth == Some(0.0) {
+                return Err(invalid("safe.top_up.max_per_day_eth", "must be above 0").into());
+            }
+            if Some(top_up.funding_wallet()?.address()) == self.safe.address().ok() {
+                return Err(invalid("safe.top_up.funding_key", "is the Safe's own address").into());
+            }
+            if self.safe.top_up()?.is_some_and(|top_up| top_up.target <= self.safe.critical_balance()) {
+                return Err(invalid(
+                    "safe.top_up.target_balance_eth",
+                    "must be above the critical balance the top-up starts at",
+                )
+                .into());
+            }
+        }
+        let signer = self.safe.signer()?;
+        if self.safe.execution == SafeExecution::Propose && signer.is_none() {
+            return Err(invalid("safe.signer_key", "is required to propose transactions (or set SAFE_SIGNER_KEY)").into());
+        }
+        if self.optimizer.timeout_secs == 0 {
+            return Err(invalid("optimizer.timeout_secs", "must be at least 1 second").into());
+        }
+        if self.optimizer.min_tvl < 0.0 || self.optimizer.min_apy < 0.0 {
+            return Err(invalid("optimizer.min_tvl/min_apy", "filters must not be negative").into());
+        }
+        if !(0.0..=1.0).contains(&self.optimizer.max_pool_share) {
+            return Err(invalid("optimizer.max_pool_share", "must be between 0 and 1").into());
+        }
+        if self.router.chains.is_empty() {
+            return Err(invalid("router.chains", "at least one chain must be configured").into());
+        }
+        for (i, chain) in self.router.chains.iter().enumerate() {
+            if chain.name.trim().is_empty() {
+                return Err(invalid(format!("router.chains[{}].name", i), "must not be empty").into());
+            }
+            if self.router.chains[..i].iter().any(|c| c.name == chain.name) {
+                return Err(invalid(
+                    format!("router.chains[{}].name", i),
+                    format!("duplicate chain '{}'", chain.name),
+                )
+                .into());
+            }
+            for url in chain.rpc_urls() {
+                if EndpointKind::parse(url).is_err() {
+                    return Err(invalid(
+                        format!("router.chains[{}].rpc_url", i),
+                        format!("'{}' is not an RPC endpoint", redact_url(url)),
+                    )
+                    .into());
+                }
+            }
+            if let Some(eth) = chain.min_balance_eth.filter(|eth| !(eth.is_finite() && *eth >= 0.0)) {
+                return Err(invalid(
+                    format!("router.chains[{}].min_balance_eth", i),
+                    format!("{} must be a non-negative number", eth),
+                )
+                .into());
+            }
+        }
+        if self.router.min_amount <= 0.0 || self.router.max_amount < self.router.min_amount {
+            return Err(invalid(
+                "router.max_amount",
+                format!(
+                    "must be >= router.min_amount (min: {}, max: {})",
+                    self.router.min_amount, self.router.max_amount
+                ),
+            )
+            .into());
+        }
+        validate_strategy("strategy", &self.strategy)?;
+        let mut names = BTreeSet::new();
+        for (i, named) in self.strategies.iter().enumerate() {
+            let key = format!("strategies[{}]", i);
+            if named.name.trim().is_empty() || named.name == DEFAULT_STRATEGY {
+                return Err(invalid(
+                    format!("{}.name", key),
+                    format!("must be set and not \"{}\"", DEFAULT_STRATEGY),
+                )
+                .into());
+            }
+            if !names.insert(named.name.as_str()) {
+                return Err(invalid(format!("{}.name", key), format!("'{}' is used twice", named.name)).into());
+            }
+            validate_strategy(&format!("{}.strategy", key), &named.strategy)?;
+        }
+        if self.strategies.iter().filter(|named| named.active).count() > 1 {
+            return Err(invalid("strategies", "at most one strategy may be active").into());
+        }
+        if self.router.status.interval_secs == 0 {
+            return Err(invalid("router.status.interval_secs", "must be at least 1 second").into());
+        }
+        let intervals = [
+            ("runtime.cycle_interval_secs", Some(self.runtime.cycle_interval_secs)),
+            ("runtime.balance_interval_secs", self.runtime.balance_interval_secs),
+            ("runtime.pool_interval_secs", self.runtime.pool_interval_secs),
+            ("runtime.route_interval_secs", self.runtime.route_interval_secs),
+            ("runtime.max_backoff_secs", Some(self.runtime.max_backoff_secs)),
+            ("runtime.cycle_timeout_secs", self.runtime.cycle_timeout_secs),
+        ];
+        for (key, secs) in intervals {
+            if let Some(secs) = secs.filter(|s| *s < MIN_INTERVAL_SECS) {
+                return Err(invalid(
+                    key,
+                    format!("{}s is below the {}s safety floor", secs, MIN_INTERVAL_SECS),
+                )
+                .into());
+            }
+        }
+        if self.storage.snapshot_interval_secs == 0 {
+            return Err(invalid("storage.snapshot_interval_secs", "must be at least 1").into());
+        }
+        if self.runtime.approval_timeout_secs == 0 {
+            return Err(invalid("runtime.approval_timeout_secs", "must be at least 1").into());
+        }
+        if self.runtime.approval == ApprovalMode::File && self.runtime.approval_file.is_none() {
+            return Err(invalid("runtime.approval_file", "is required with approval = \"file\"").into());
+        }
+        if self.log.max_size_mb == 0 {
+            return Err(invalid("log.max_size_mb", "must be at least 1").into());
+        }
+        if self.log.max_files == 0 {
+            return Err(invalid("log.max_files", "must be at least 1").into());
+        }
+        self.http.bind_addr()?;
+        if self.http.admin_token.as_ref().is_some_and(|token| token.expose().trim().is_empty()) {
+            return Err(invalid("http.admin_token", "must not be empty").into());
+        }
+        if self.notify.queue_capacity == 0 {
+            return Err(invalid("notify.queue_capacity", "must be at least 1").into());
+        }
+        let urls = [
+            ("notify.webhook.url", self.notify.webhook.url.as_deref()),
+            ("notify.discord.webhook_url", self.notify.discord.webhook_url.as_ref().map(|url| url.expose().as_str())),
+        ];
+        for (key, url) in urls {
+            if let Some(url) = url.filter(|u| !(u.starts_with("http://") || u.starts_with("https://"))) {
+                return Err(invalid(key, format!("'{}' is not an http(s) URL", redact_url(url))).into());
+            }
+        }
+        let telegram = &self.notify.telegram;
+        if telegram.bot_token.is_some() != telegram.chat_id.is_some() {
+            return Err(invalid(
+                "notify.telegram",
+                "bot_token and chat_id must be set together (TELEGRAM_BOT_TOKEN, TELEGRAM_CHAT_ID)",
+            )
+            .into());
+        }
+        if telegram.max_per_minute == 0 {
+            return Err(invalid("notify.telegram.max_per_minute", "must be at least 1").into());
+        }
+        let email = &self.notify.email;
+        if email.smtp_host.is_some() && (email.from.is_none() || email.to.is_empty()) {
+            return Err(invalid("notify.email", "smtp_host needs from and at least one to address").into());
+        }
+        for address in email.from.iter().chain(&email.to) {
+            if address.parse::<lettre::message::Mailbox>().is_err() {
+                return Err(invalid("notify.email", format!("'{}' is not an email address", address)).into());
+            }
+        }
+        if email.username.is_some() != email.password.is_some() {
+            return Err(invalid("notify.email", "username and password must be set together").into());
+        }
+        if email.tls == SmtpTls::None
+            && email.smtp_host.as_deref().is_some_and(|host| !matches!(host, "localhost" | "127.0.0.1" | "::1"))
+        {
+            return Err(invalid("notify.email.tls", "\"none\" is only allowed for a relay on localhost").into());
+        }
+        self.prices.validate_feeds()?;
+        if self.prices.timeout_secs == 0 {
+            return Err(invalid("prices.timeout_secs", "must be at least 1 second").into());
+        }
+        self.degradation.validate()?;
+        for chain in self.swap.quoters.keys() {
+            self.swap.quoter(chain)?;
+        }
+        for pair in &self.swap.pairs {
+            pair.token_in()?;
+            pair.token_out()?;
+            if ![100, 500, 3000, 10000].contains(&pair.fee) {
+                return Err(invalid("swap.pairs.fee", format!("{} is not a Uniswap V3 fee tier", pair.fee)).into());
+            }
+        }
+        let multi_send = self.actions.multi_send()?;
+        let delegatecall_targets = self.safe.delegatecall_targets()?;
+        if self.safe.allow_delegatecall && !delegatecall_targets.contains(&multi_send) {
+            return Err(invalid(
+                "safe.delegatecall_targets",
+                format!("must include actions.multi_send ({:?}) to batch pool entries", multi_send),
+            )
+            .into());
+        }
+        self.safe.allowed_modules()?;
+        for market in &self.actions.aave_v3 {
+            market.pool()?;
+            market.asset()?;
+        }
+        for market in &self.actions.compound_v3 {
+            market.comet()?;
+            market.asset()?;
+        }
+        Ok(())
+    }
+}
+
+fn parse_bool(value: &str) -> Option<bool> {
+    match value.to_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Some(true),
+        "0" | "false" | "no" | "off" => Some(false),
+        _ => None,
+    }
+}
+
+/// Commented template written by `asam config init`.
+pub const CONFIG_TEMPLATE: &str = r#"# ASAM configuration
+#
+# Precedence (highest first): command line flags, environment variables,
+# this file, built-in defaults. Environment overrides:
+#   ETH_RPC_URL(S), RPC_TIMEOUT_SECS, ACCOUNT_ADDRESS, MIN_BALANCE_ETH, DRY_RUN,
+#   DEFI_API_URL, API_TIMEOUT_SECS, ROUTE_STATUS_INTERVAL_SECS, LIFI_API_URL,
+#   SOCKET_API_URL, SOCKET_API_KEY, CYCLE_INTERVAL_SECS, LOG_FORMAT,
+#   LOG_FILE_PATH, RUN_MODE, HTTP_BIND, WEBHOOK_URL, WEBHOOK_SECRET,
+#   TELEGRAM_BOT_TOKEN, TELEGRAM_CHAT_ID, DISCORD_WEBHOOK_URL, SMTP_HOST,
+#   SMTP_USERNAME, SMTP_PASSWORD, EMAIL_FROM, EMAIL_TO (comma separated),
+#   STORAGE_PATH, KILL_SWITCH_PATH, PAUSED, PREFLIGHT, ADMIN_TOKEN, APPROVAL,
+#   COINGECKO_API_KEY
+
+# "mainnet", "testnet" (Sepolia and L2 testnets) or "local" (a dev node such as
+# anvil). testnet and local default to their own chain ids and mock pool data,
+# testnet to the LI.FI testnet API; local never runs live.
+profile = "mainnet"
+# Live mode on mainnet moves real funds and refuses to start without this
+# i_understand_this_moves_real_funds = false
+
+[safe]
+# Account (Safe) address to monitor, or an ENS name resolved at startup
+address = "0x0000000000000000000000000000000000000000"
+# Ethereum JSON-RPC endpoint: an http(s):// or ws(s):// URL, or the path of a local
+# node's IPC socket. Separate several with commas (or use rpc_urls = [...]) to fail
+# over between them in order. All must serve the same chain.
+rpc_url = "https://mainnet.infura.io/v3/your-project-id"
+# Chain id that chain must have; startup stops on any other. Defaults to the
+# profile's Ethereum chain (1 on mainnet, 11155111 on testnet, 31337 locally)
+# chain_id = 1
+# Seconds an endpoint has to answer a request before it fails as timed out
+# rpc_timeout_secs = 10
+# rpc_probe_interval_secs = 30
+# Retries of balance reads, gas prices and gas estimates that time out or are rate
+# limited, with exponential backoff from rpc_retry_backoff_ms
+# rpc_retry_attempts = 3
+# rpc_retry_backoff_ms = 500
+# Warn below this balance; the critical threshold is half of it unless set
+min_balance_eth = 0.001
+# critical_balance_eth = 0.0002
+# Exact thresholds in wei (strings), winning over the _eth settings
+# min_balance_wei = "1000000000000000"
+# critical_balance_wei = "200000000000000"
+# Once below the minimum, the balance is healthy again only from the minimum
+# times this factor, so one hovering around it does not flip every cycle
+# balance_recovery_factor = 1.1
+# Most ETH value one transaction, and all transactions executed over any 24
+# hours, may send, in wei (strings); unlimited unless set
+# max_value_per_tx_wei = "1000000000000000000"
+# max_value_per_day_wei = "5000000000000000000"
+# Most ETH the transactions executed over any 24 hours may pay in gas, in wei
+# (a string); unlimited unless set
+# daily_gas_budget_wei = "50000000000000000"
+# Delegatecalls run another contract's code with the Safe's storage and funds, so
+# they are refused unless allowed, and then only to the listed contracts.
+# Entering pools with an approval batches both through actions.multi_send
+# allow_delegatecall = true
+# delegatecall_targets = ["0x40A2aCCbd92BCA938b02010E17A5b8929b49130D"]
+# Modules enabled on the Safe, and its guard, are checked every balance cycle;
+# any not listed here fails the cycle with a critical alert
+# allowed_modules = ["0x..."]
+# Override runtime.mode for transaction execution (needs runtime.allow_mixed_mode
+# when it disagrees)
+# dry_run = true
+
+# Tip of EIP-1559 transactions in gwei; the median tip of recent blocks when unset
+# priority_fee_gwei = 1.5
+# Gas estimates are multiplied by gas_buffer for the Safe's own overhead, and a
+# transaction needing more than max_gas_limit gas is refused
+# gas_buffer = 1.2
+# max_gas_limit = 5000000
+# "execute" live transactions, "propose" them to the Safe Transaction Service
+# for the other owners to confirm, or only "simulate" them. Executing and
+# proposing sign with signer_key (or SAFE_SIGNER_KEY / PRIVATE_KEY)
+# execution = "propose"
+# signer_key = ""
+# Or sign on a Ledger (SIGNER=ledger, needs the ledger feature), confirming each
+# transaction on the device within ledger_timeout_secs
+# signer = "ledger"
+# ledger_path = "m/44'/60'/0'/0/0"
+# ledger_timeout_secs = 120
+# Blocks an executed transaction waits for, its own included (2 on Ethereum
+# mainnet, 1 elsewhere when unset), and for how long
+# confirmations = 2
+# confirmation_timeout_secs = 300
+# Queued transactions are submitted one at a time, each once the previous one is
+# confirmed, and no more than this many a minute
+# max_submissions_per_minute = 2
+# [safe.transaction_service_urls]
+# Ethereum = "https://safe-transaction-mainnet.safe.global"
+
+# Log each transfer into or out of the Safe (WATCH_TRANSFERS=true): ERC-20
+# Transfer events and ETH balance changes since the previous cycle, also sent as
+# notifications with notify. Logs are fetched max_block_range blocks at a time
+# [safe.transfers]
+# enabled = true
+# notify = false
+# max_block_range = 2000
+
+# Refill a critical balance up to target_balance_eth (safe.min_balance_eth
+# unless given) with ETH from a funding wallet (or TOP_UP_FUNDING_KEY), sending
+# at most max_per_day_eth over any 24 hours and leaving it reserve_eth. Never
+# sent in dry-run or observe mode
+# [safe.top_up]
+# funding_key = ""
+# target_balance_eth = 0.01
+# max_per_day_eth = 0.05
+# reserve_eth = 0.01
+
+# Other accounts whose ETH balances are watched on the home chain, each against
+# its own threshold (safe.min_balance_eth unless given); addresses may be ENS names
+# [[safe.accounts]]
+# address = "0x0000000000000000000000000000000000000000"
+# min_balance_eth = 0.5
+
+# ERC-20 balances to read alongside ETH; decimals are read from the contract
+# unless given
+# [[safe.tokens]]
+# symbol = "USDC"
+# address = "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48"
+# decimals = 6
+
+[optimizer]
+api_url = "https://api.llama.fi/protocols"
+timeout_secs = 10
+# Ignore pools below these values
+min_tvl = 0.0
+min_apy = 0.0
+# Restrict to these chains (empty = all)
+chains = []
+# "apy_log_tvl" (APY weighted by TVL magnitude) or "apy"
+scoring = "apy_log_tvl"
+# Skip pools where the portfolio's USD value would exceed this share of the TVL
+# (0 = no depth check; needs [prices])
+max_pool_share = 0.1
+
+[router]
+min_amount = 0.1
+max_amount = 1000.0
+# Override runtime.mode for bridge execution
+# dry_run = true
+# Refuse a route matching one completed this many seconds ago, amount within 1% (0 disables)
+route_dedup_window_secs = 86400
+
+[[router.chains]]
+name = "Ethereum"
+chain_id = 1
+
+[[router.chains]]
+name = "Arbitrum"
+chain_id = 42161
+# Watch the balance on this chain too (comma separated endpoints fail over in order)
+# rpc_url = "https://arb-mainnet.g.alchemy.com/v2/your-api-key"
+# Threshold on this chain; defaults to safe.min_balance_eth
+# min_balance_eth = 0.01
+
+[[router.chains]]
+name = "Optimism"
+chain_id = 10
+
+[[router.chains]]
+name = "Polygon"
+chain_id = 137
+
+[[router.chains]]
+name = "Fantom"
+chain_id = 250
+# active = false       # disable routing to/from a chain
+# min_transfer = 0.1
+
+[router.status]
+# Poll interval for live routes awaiting the destination chain
+interval_secs = 30
+max_backoff_secs = 600
+lifi_api_url = "https://li.quest"
+socket_api_url = "https://api.socket.tech"
+# socket_api_key = ""
+
+[strategy]
+# Only bridge when the best pool beats the current position by this many APY points
+min_apy_improvement = 1.0
+# Seconds before another route in the same direction may start
+bridge_cooldown_secs = 86400
+# Amount to move per route; defaults to max_deploy_fraction of the balance minus reserve_eth
+# transfer_amount = 1.0
+# ETH always left in the Safe for gas
+reserve_eth = 0.01
+# Share of the balance above the reserve a single route may move
+max_deploy_fraction = 0.9
+# Warn when the recorded position and the on-chain balance differ by more than this
+reconcile_tolerance_eth = 0.05
+# Hold routes back once fees over the last 30 days pass this share of the yield
+# captured in that time; above 1.0 fees may exceed it
+max_cost_ratio = 0.75
+# Skip moves whose protocol exit and re-entry gas takes longer than this many days of
+# the extra yield to earn back; 0 does not check
+max_breakeven_days = 30.0
+
+# Named strategies, each with its own pool filters and thresholds, are evaluated on the
+# same pools every cycle and their decisions recorded. Only one with active = true acts,
+# in place of the [optimizer] filters and [strategy] above.
+# [[strategies]]
+# name = "conservative"
+# active = true
+# optimizer = { min_tvl = 50000000.0, chains = ["Ethereum", "Arbitrum"] }
+# strategy = { min_apy_improvement = 2.0 }
+#
+# [[strategies]]
+# name = "aggressive"
+# optimizer = { scoring = "apy" }
+# strategy = { min_apy_improvement = 0.5, max_deploy_fraction = 1.0 }
+
+[runtime]
+# Seconds between monitoring cycles (minimum 5)
+cycle_interval_secs = 60
+# Optional per-phase cadences, each defaulting to cycle_interval_secs
+# balance_interval_secs = 60
+# pool_interval_secs = 300
+# route_interval_secs = 30
+# Failed cycles are retried with exponential backoff up to this many seconds
+max_backoff_secs = 900
+# A cycle running longer than this is abandoned and counted as failed;
+# defaults to twice cycle_interval_secs
+# cycle_timeout_secs = 120
+# "text" or "json"
+log_format = "text"
+# "observe" (monitor only), "dry_run" (simulate, never broadcast) or "live";
+# defaults to live, or dry_run when safe.dry_run = true
+# mode = "live"
+# allow_mixed_mode = false
+# Report a failed pool fetch as a cycle warning instead of failing the cycle
+# allow_partial_cycles = false
+# log_level = "info"
+# Run the `asam check` checks before the first cycle; refuse to start on a failure
+# preflight = false
+# The config is reloaded on SIGHUP; also reload whenever this file changes
+# watch_config = false
+# "auto" (act without asking), "prompt" (ask y/N on the terminal) or "file"
+# (write the request to approval_file and wait for "yes" or "no" in it)
+approval = "auto"
+# Seconds to wait for an answer before declining
+approval_timeout_secs = 60
+# approval_file = "asam.approval"
+# "deterministic" replays a bundled scenario instead of calling RPC endpoints,
+# the pool API or price sources; runs with the same seed make the same decisions
+# simulation = "off"
+# simulation_seed = 42
+
+[log]
+# Also write logs to this file, rotated by size (stderr only when unset)
+# file_path = "logs/asam.log"
+max_size_mb = 10
+# Files kept, the active one included
+max_files = 5
+
+[http]
+# Serve /healthz and /status on this address (disabled when unset)
+# bind = "127.0.0.1:9090"
+# Enables the /admin endpoints for requests with "Authorization: Bearer <admin_token>"
+# admin_token = "change-me"
+# Append every admin action, with the caller, to this file as JSON lines
+# audit_log_path = "asam-audit.jsonl"
+
+[notify]
+# Events beyond this many waiting for delivery are dropped
+queue_capacity = 100
+# Repeats of an error within this many seconds of its alert are summarized (0 alerts every time)
+suppression_window_secs = 1800
+# Alert about a rebalance to the same pool at most once in this many seconds (0 alerts every time)
+rebalance_alert_cooldown_secs = 21600
+
+[notify.webhook]
+# POST significant events as JSON to this URL (disabled when unset)
+# url = "https://hooks.example.com/asam"
+# Signs the body with HMAC-SHA256 in the X-Asam-Signature header
+# secret = "change-me"
+max_retries = 3
+
+[notify.telegram]
+# Send alerts to a Telegram chat (disabled unless both are set)
+# bot_token = "123456:ABC..."
+# chat_id = "-1001234567890"
+# "info", "warning" or "critical"
+min_severity = "info"
+max_per_minute = 20
+# Events arriving within this window are sent as one digest message
+digest_window_ms = 2000
+
+[notify.discord]
+# Post rich embeds to a Discord webhook (disabled when unset)
+# webhook_url = "https://discord.com/api/webhooks/..."
+min_severity = "info"
+explorer_url = "https://etherscan.io"
+bridge_explorer_url = "https://scan.li.fi"
+max_retries = 3
+
+[notify.email]
+# Email alerts over SMTP (disabled unless smtp_host, from and to are set)
+# smtp_host = "smtp.example.com"
+# Defaults to 587 for starttls and 465 for implicit
+# smtp_port = 587
+# username = "asam"
+# password = ""
+# from = "ASAM <asam@example.com>"
+# to = ["oncall@example.com"]
+# "starttls", "implicit" or "none" (localhost relays only)
+tls = "starttls"
+# Only critical events by default: critical balance, failed routes and
+# transactions, kill switch activation
+min_severity = "critical"
+
+[storage]
+# SQLite database for cycle, route and transaction history (memory only when unset)
+# path = "asam.db"
+# Position value snapshots for realized yield tracking
+snapshot_interval_secs = 3600
+# Append every routing decision and its inputs here, for `asam replay`
+# decision_log_path = "asam-decisions.jsonl"
+
+[kill_switch]
+# Fund movement is paused while this file exists; monitoring carries on
+# path = "asam.pause"
+# Start paused (lifted only by unsetting it and restarting)
+# paused = false
+
+[swap]
+# Asset held between pools; routing into a pool with another underlying is
+# charged the round-trip swap cost quoted by Uniswap V3
+held_asset = "ETH"
+
+[swap.protocol_assets]
+# Underlying asset by protocol; unlisted protocols hold the held asset
+# Aave = "USDC"
+
+[swap.quoters]
+# QuoterV2 address by chain
+Ethereum = "0x61fFE014bA17989E743c5F6cB21bF9697530dD6e"
+
+# [[swap.pairs]]
+# asset = "USDC"
+# chain = "Ethereum"
+# token_in = "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2"  # WETH
+# token_out = "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48" # USDC
+# fee = 500
+
+[actions]
+# MultiSend contract batching an approval with the supply into a pool
+multi_send = "0x40A2aCCbd92BCA938b02010E17A5b8929b49130D"
+
+# Markets the agent can supply to; pools of other protocols, or on chains without
+# a market, are only suggested
+[[actions.aave_v3]]
+chain = "Ethereum"
+pool = "0x87870Bca3F3fD6335C3F4ce8392D69350B4fA4E2"
+asset = "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2"  # WETH
+
+[[actions.compound_v3]]
+chain = "Ethereum"
+comet = "0xA17581A9E3356d9A858b789D68B4d866e593aE94"  # cWETHv3
+asset = "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2"  # WETH
+
+[prices]
+# USD prices for reported values: "composite" (Chainlink, then CoinGecko),
+# "chainlink", "coingecko" or "none". A missing price only leaves the value unknown.
+source = "composite"
+coingecko_url = "https://api.coingecko.com/api/v3"
+# Pro tier key (COINGECKO_API_KEY); use https://pro-api.coingecko.com/api/v3 with it
+# coingecko_api_key = ""
+cache_ttl_secs = 60
+# Spacing between CoinGecko requests, for the free tier's rate limit
+min_request_interval_ms = 2000
+timeout_secs = 10
+# Prices older than this are treated as unknown
+max_age_secs = 3600
+
+[prices.coingecko_ids]
+ETH = "ethereum"
+WETH = "weth"
+USDC = "usd-coin"
+USDT = "tether"
+DAI = "dai"
+WBTC = "wrapped-bitcoin"
+
+[prices.chainlink_feeds]
+# USD feeds on the home chain
+ETH = "0x5f4eC3Df9cbd43714FE2740f5E3616155c5b8419"
+USDC = "0x8fFfFfd4AfB6115b954Bd326cbe7B4BA576818f6"
+USDT = "0x3E7d1eAB13ad0104d2750B8863b489D65364e32D"
+DAI = "0xAed0c38402a5d19df6E4c03F4E2DceD6e29c1ee9"
+WBTC = "0xF4030086522a5bEEa4988F8cA5B36dbC97BeE88c"
+
+[degradation]
+# What a cycle does when a dependency is down: "fail", "skip_phase" (carry on
+# without it), "use_cache" (its last answer up to max_cache_age_secs old, then
+# skip) or "use_last_known" (its last answer at any age). Alerts go out when the
+# fallback in effect changes.
+# Only "fail": nothing can be decided without the balance
+rpc = "fail"
+# Skipping leaves the decision blocked for the cycle
+pool_api = "fail"
+# "fail" or "skip_phase", which holds instead of starting the route
+bridge_api = "fail"
+# Skipping leaves the cycle without USD values
+prices = "skip_phase"
+max_cache_age_secs = 900
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+
+    fn fixture(name: &str) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures/config")
+            .join(name)
+    }
+
+    fn no_env(_: &str) -> Option<String> {
+        None
+    }
+
+    #[test]
+    fn test_load_full_fixture() {
+        let mut config = Config::from_file(&fixture("full.toml")).unwrap();
+        config.apply_overrides(no_env).unwrap();
+        config.validate().unwrap();
+
+        assert_eq!(config.safe.rpc_url.as_deref(), Some("http://localhost:8545"));
+        assert_eq!(config.safe.min_balance(), U256::from(2_000_000_000_000_000_u64));
+        assert_eq!(config.run_mode(), RunMode::DryRun);
+        assert_eq!(config.optimizer.chains, vec!["Ethereum", "Arbitrum"]);
+        assert_eq!(config.optimizer.scoring, ScoringMethod::Apy);
+        assert_eq!(config.router.chains.len(), 2);
+        assert!(!config.router.chains[1].active);
+        assert_eq!(config.router.chains[1].rpc_urls(), vec!["http://localhost:8547"]);
+        assert_eq!(config.router.chains[1].min_balance_wei(&config.safe), U256::exp10(16) * 5);
+        // Without its own threshold a chain uses the Safe's
+        assert_eq!(config.router.chains[0].min_balance_wei(&config.safe), config.safe.min_balance());
+        assert_eq!(config.runtime.cycle_interval_secs, 30);
+    }
+
+    #[test]
+    fn test_minimal_fixture_uses_defaults() {
+        let config = Config::from_file(&fixture("minimal.toml")).unwrap();
+        config.validate().unwrap();
+        assert_eq!(config.optimizer.api_url, "https://api.llama.fi/protocols");
+        assert_eq!(config.router.chains.len(), 5);
+        assert_eq!(config.runtime.cycle_interval_secs, 60);
+    }
+
+    #[test]
+    fn test_env_overrides_take_precedence() {
+        let env: HashMap<&str, &str> = [
+            ("ETH_RPC_URL", "http://override:8545"),
+            ("CYCLE_INTERVAL_SECS", "120"),
+            ("RPC_TIMEOUT_SECS", "3"),
+            ("DRY_RUN", "false"),
+            ("SIMULATION_MODE", "Deterministic"),
+        ]
+        .into_iter()
+        .collect();
+
+        let mut config = Config::from_file(&fixture("full.toml")).unwrap();
+        config.apply_overrides(|k| env.get(k).map(|v| v.to_string())).unwrap();
+        assert_eq!(config.safe.rpc_url.as_deref(), Some("http://override:8545"));
+        assert_eq!(config.runtime.cycle_interval_secs, 120);
+        assert_eq!(config.safe.rpc_timeout_secs, 3);
+        assert_eq!(config.safe.dry_run, Some(false));
+        assert_eq!(config.run_mode(), RunMode::Live);
+        assert_eq!(config.runtime.simulation, SimulationMode::Deterministic);
+
+        let err = config
+            .apply_overrides(|k| (k == "SIMULATION_MODE").then(|| "random".to_string()))
+            .unwrap_err();
+        assert!(err.to_string().contains("runtime.simulation"), "{}", err);
+        let err = config
+            .apply_overrides(|k| (k == "API_TIMEOUT_SECS").then(|| "soon".to_string()))
+            .unwrap_err();
+        assert!(err.to_string().contains("optimizer.timeout_secs"));
+        assert!(err.to_string().contains("API_TIMEOUT_SECS"));
+    }
+
+    #[test]
+    fn test_invalid_fixture_names_offending_key() {
+        let config = Config::from_file(&fixture("invalid.toml")).unwrap();
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("router.max_amount"), "{}", err);
+
+        let err = Config::from_toml_str("[safe]\nadress = \"0x0\"\n").unwrap_err();
+        assert!(err.to_string().contains("adress"), "{}", err);
+
+        let err = Config::from_toml_str("[safe]\naddress = \"0x1234\"\n")
+            .unwrap()
+            .validate()
+            .unwrap_err();
+        assert!(err.to_string().contains("safe.address"), "{}", err);
+    }
+
+    #[test]
+    fn test_interval_safety_floor() {
+        let err = Config::from_toml_str("[runtime]\npool_interval_secs = 2\n")
+            .unwrap()
+            .validate()
+            .unwrap_err();
+        assert!(err.to_string().contains("runtime.pool_interval_secs"), "{}", err);
+
+        let config = Config::from_toml_str("[runtime]\nbalance_interval_secs = 5\n").unwrap();
+        config.validate().unwrap();
+        assert_eq!(config.runtime.balance_interval_secs, Some(5));
+
+        // The cycle timeout follows the interval unless set
+        let config = Config::from_toml_str("[runtime]\ncycle_interval_secs = 30\n").unwrap();
+        assert_eq!(config.runtime.cycle_timeout(), Duration::from_secs(60));
+        let config = Config::from_toml_str("[runtime]\ncycle_timeout_secs = 1\n").unwrap();
+        assert!(config.validate().unwrap_err().to_string().contains("runtime.cycle_timeout_secs"));
+    }
+
+    #[test]
+    fn test_run_mode_plan() {
+        let config = Config::default();
+        assert_eq!(config.mode_plan().unwrap(), ModePlan::uniform(RunMode::Live));
+
+        // The legacy flag alone still selects dry-run everywhere
+        let mut config = Config::default();
+        config.apply_overrides(|k| (k == "DRY_RUN").then(|| "true".to_string())).unwrap();
+        assert_eq!(config.mode_plan().unwrap(), ModePlan::uniform(RunMode::DryRun));
+
+        let mut config = Config::from_toml_str("[runtime]\nmode = \"observe\"\n").unwrap();
+        config.safe.dry_run = Some(true);
+        assert_eq!(config.mode_plan().unwrap(), ModePlan::uniform(RunMode::Observe));
+
+        // A live router under a dry-run agent is refused unless explicitly allowed
+        let mut config = Config::from_toml_str("[router]\ndry_run = false\n").unwrap();
+        config.apply_overrides(|k| (k == "RUN_MODE").then(|| "dry-run".to_string())).unwrap();
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("router.dry_run"), "{}", err);
+        config.runtime.allow_mixed_mode = true;
+        let plan = config.mode_plan().unwrap();
+        assert_eq!((plan.global, plan.safe, plan.router), (RunMode::DryRun, RunMode::DryRun, RunMode::Live));
+        assert!(plan.is_mixed());
+    }
+
+    #[test]
+    fn test_profile_defaults() {
+        let config = Config::from_toml_str("profile = \"testnet\"\n").unwrap();
+        assert_eq!(config.router.chains[0].name, "Ethereum");
+        assert_eq!(config.router.chains[0].chain_id, 11155111);
+        assert!(config.optimizer.use_mock);
+        assert_eq!(config.router.status.lifi_api_url, "https://testnet.li.quest");
+        assert_eq!(config.prices.source, PriceSourceKind::None);
+        assert!(config.actions.aave_v3.is_empty());
+        assert!(config.actions.compound_v3.is_empty());
+
+        // Anything the file sets wins over the profile's defaults
+        let config = Config::from_toml_str(
+            "profile = \"local\"\n[optimizer]\nuse_mock = false\n[[router.chains]]\nname = \"Ethereum\"\nchain_id = 1\n",
+        )
+        .unwrap();
+        assert!(!config.optimizer.use_mock);
+        assert_eq!(config.router.chains.len(), 1);
+        assert_eq!(config.router.chains[0].chain_id, 1);
+
+        let config = Config::from_toml_str("").unwrap();
+        assert_eq!(config.profile, Profile::Mainnet);
+        assert_eq!(config.router.chains.len(), 5);
+        assert_eq!(config.prices.source, PriceSourceKind::Composite);
+        assert_eq!(config.actions.aave_v3[0].pool, MAINNET_AAVE_V3_POOL);
+        assert_eq!(config.actions.compound_v3[0].comet, MAINNET_COMET_WETH);
+    }
+
+    #[test]
+    fn test_profile_interlocks() {
+        let with = |profile: Profile, mode: RunMode, acknowledged: bool| {
+            let mut config = Config { profile, i_understand_this_moves_real_funds: acknowledged, ..Default::default() };
+            config.runtime.mode = Some(mode);
+            config
+        };
+
+        // Testnet settings pointed at mainnet
+        assert!(Profile::Testnet.check_chain_id(1).is_err());
+        Profile::Testnet.check_chain_id(11155111).unwrap();
+        Profile::Mainnet.check_chain_id(1).unwrap();
+        Profile::Local.check_chain_id(1).unwrap();
+
+        // Live mode on the local profile runs as dry-run
+        let config = with(Profile::Local, RunMode::Live, false);
+        assert_eq!(config.mode_plan().unwrap(), ModePlan::uniform(RunMode::DryRun));
+        assert!(config.live_downgraded());
+        config.check_live_acknowledged().unwrap();
+        let config = with(Profile::Local, RunMode::Observe, false);
+        assert_eq!(config.mode_plan().unwrap(), ModePlan::uniform(RunMode::Observe));
+        assert!(!config.live_downgraded());
+
+        // Live on mainnet needs the acknowledgment, including a live component alone
+        let err = with(Profile::Mainnet, RunMode::Live, false).check_live_acknowledged().unwrap_err();
+        assert!(err.to_string().contains("i_understand_this_moves_real_funds"), "{}", err);
+        with(Profile::Mainnet, RunMode::Live, true).check_live_acknowledged().unwrap();
+        with(Profile::Mainnet, RunMode::DryRun, false).check_live_acknowledged().unwrap();
+        with(Profile::Testnet, RunMode::Live, false).check_live_acknowledged().unwrap();
+        let mut config = with(Profile::Mainnet, RunMode::DryRun, false);
+        config.router.dry_run = Some(false);
+        config.runtime.allow_mixed_mode = true;
+        assert!(config.check_live_acknowledged().is_err());
+        assert!(!config.live_downgraded());
+    }
+
+    #[test]
+    fn test_log_format_and_secrets() {
+        let env: HashMap<&str, &str> = [
+            ("LOG_FORMAT", "JSON"),
+            ("ETH_RPC_URL", "https://mainnet.infura.io/v3/0123456789abcdef0123"),
+            ("TELEGRAM_BOT_TOKEN", "123456:telegram-token"),
+            ("SOCKET_API_KEY", "socket-key"),
+        ]
+        .into_iter()
+        .collect();
+
+        let mut config = Config::default();
+        config.apply_overrides(|k| env.get(k).map(|v| v.to_string())).unwrap();
+        assert_eq!(config.runtime.log_format, LogFormat::Json);
+        let secrets = config.secrets();
+        assert_eq!(secrets.len(), 3);
+        assert!(secrets.contains(&"0123456789abcdef0123".to_string()));
+        assert!(secrets.contains(&"123456:telegram-token".to_string()));
+        assert!(!secrets.iter().any(|s| s.contains("infura")));
+    }
+
+    #[test]
+    fn test_secrets_never_print() {
+        let mut config = Config::default();
+        let values = [
+            "socket-key-0123",
+            "webhook-secret-0123",
+            "admin-token-0123",
+            "123456:telegram-token-0123",
+            "https://discord.com/api/webhooks/1/discord-token-0123",
+            "smtp-password-0123",
+            "coingecko-key-0123",
+            "4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318",
+            "59c6995e998f97a5a0044966f0945389dc9e86dae88c7a8412f4603b6b78690d",
+        ];
+        config.router.status.socket_api_key = Some(values[0].into());
+        config.notify.webhook.secret = Some(values[1].into());
+        config.http.admin_token = Some(values[2].into());
+        config.notify.telegram.bot_token = Some(values[3].into());
+        config.notify.discord.webhook_url = Some(values[4].into());
+        config.notify.email.password = Some(values[5].into());
+        config.prices.coingecko_api_key = Some(values[6].into());
+        config.safe.signer_key = Some(values[7].into());
+        config.safe.top_up = Some(TopUpConfig { funding_key: Some(values[8].into()), ..TopUpConfig::default() });
+        assert_eq!(config.secret_fields().iter().filter(|(_, secret)| secret.is_some()).count(), values.len());
+
+        let outputs = [
+            format!("{:?}", config),
+            format!("{:#?}", config),
+            serde_json::to_string(&config).unwrap(),
+            toml::to_string(&config).unwrap(),
+        ];
+        for output in &outputs {
+            for value in values {
+                assert!(!output.contains(value), "{} leaked", value);
+            }
+        }
+        assert!(outputs[2].contains(r#""admin_token":"[REDACTED]""#));
+        // The config still reads them from files
+        let parsed = Config::from_toml_str("[http]\nadmin_token = \"admin-token-0123\"\n").unwrap();
+        assert_eq!(parsed.http.admin_token.unwrap().expose(), "admin-token-0123");
+    }
+
+    #[test]
+    fn test_missing_required_settings() {
+        let config = Config::default();
+        let err = config.safe.rpc_urls().unwrap_err();
+        assert!(err.to_string().contains("ETH_RPC_URL"));
+        let err = config.safe.address().unwrap_err();
+        assert!(err.to_string().contains("safe.address"));
+    }
+
+    #[test]
+    fn test_rpc_url_lists() {
+        let mut config =
+            Config::from_toml_str("[safe]\nrpc_urls = [\"https://a.example\", \"https://b.example/v2/key\"]\n").unwrap();
+        assert_eq!(config.safe.rpc_urls().unwrap(), vec!["https://a.example", "https://b.example/v2/key"]);
+
+        // ETH_RPC_URL takes precedence over the array from the file
+        config
+            .apply_overrides(|k| (k == "ETH_RPC_URL").then(|| "http://primary:8545, http://backup:8545".to_string()))
+            .unwrap();
+        assert_eq!(config.safe.rpc_urls().unwrap(), vec!["http://primary:8545", "http://backup:8545"]);
+        config.validate().unwrap();
+        let env: HashMap<&str, &str> =
+            [("ETH_RPC_URL", "http://old:8545"), ("ETH_RPC_URLS", "http://a:8545,http://b:8545")].into_iter().collect();
+        config.apply_overrides(|k| env.get(k).map(|v| v.to_string())).unwrap();
+        assert_eq!(config.safe.rpc_urls().unwrap(), vec!["http://a:8545", "http://b:8545"]);
+
+        // Websockets and IPC sockets are endpoints too
+        config.safe.rpc_url = Some("http://primary:8545,ws://backup:8546,/var/run/geth.ipc".to_string());
+        config.validate().unwrap();
+        config.safe.rpc_url = Some("http://primary:8545,ftp://backup:2121".to_string());
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("ftp://backup:2121"), "{}", err);
+
+        config.safe.rpc_url = Some("http://primary:8545".to_string());
+        config.router.chains[1].rpc_url = Some("https://arb.example, arb.example".to_string());
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("router.chains[1].rpc_url"), "{}", err);
+    }
+
+    #[test]
+    fn test_named_strategies() {
+        let strategies = r#"
+[optimizer]
+api_url = "http://pools.example"
+min_apy = 1.0
+
+[[strategies]]
+name = "conservative"
+active = true
+optimizer = { min_tvl = 50000000.0, chains = ["Ethereum"] }
+strategy = { min_apy_improvement = 2.0 }
+
+[[strategies]]
+name = "aggressive"
+"#;
+        let config = Config::from_toml_str(strategies).unwrap();
+        config.validate().unwrap();
+        assert_eq!(config.active_strategy().unwrap().name, "conservative");
+        assert_eq!(config.acting_strategy().min_apy_improvement, 2.0);
+        // The pool source stays the shared one; only the filters are the strategy's
+        let acting = config.acting_optimizer();
+        assert_eq!((acting.api_url.as_str(), acting.min_tvl, acting.min_apy), ("http://pools.example", 50_000_000.0, 0.0));
+        assert_eq!(acting.chains, vec!["Ethereum"]);
+
+        let both_active = strategies.replace("name = \"aggressive\"", "name = \"aggressive\"\nactive = true");
+        let err = Config::from_toml_str(&both_active).unwrap().validate().unwrap_err();
+        assert!(err.to_string().contains("at most one strategy may be active"), "{}", err);
+
+        let twice = strategies.replace("\"aggressive\"", "\"conservative\"");
+        let err = Config::from_toml_str(&twice).unwrap().validate().unwrap_err();
+        assert!(err.to_string().contains("strategies[1].name"), "{}", err);
+
+        let reserved = strategies.replace("\"aggressive\"", "\"default\"");
+        assert!(Config::from_toml_str(&reserved).unwrap().validate().is_err());
+
+        let invalid = strategies.replace("min_apy_improvement = 2.0", "max_deploy_fraction = 1.5");
+        let err = Config::from_toml_str(&invalid).unwrap().validate().unwrap_err();
+        assert!(err.to_string().contains("strategies[0].strategy.max_deploy_fraction"), "{}", err);
+    }
+
+    #[test]
+    fn test_template_is_valid() {
+        let config = Config::from_toml_str(CONFIG_TEMPLATE).unwrap();
+        config.validate().unwrap();
+        assert_eq!(config.router.chains.len(), 5);
+        assert_eq!(config.degradation, DegradationPolicy::default());
+    }
+
+    #[test]
+    fn test_degradation_policy() {
+        let config = Config::from_toml_str("[degradation]\npool_api = \"use_cache\"\nmax_cache_age_secs = 60\n").unwrap();
+        config.validate().unwrap();
+        assert_eq!(config.degradation_policy().fallback(Dependency::PoolApi), Fallback::UseCache);
+        assert_eq!(config.degradation_policy().fallback(Dependency::Prices), Fallback::SkipPhase);
+
+        // The legacy flag only stands in for an unset pool fallback
+        let legacy = Config::from_toml_str("[runtime]\nallow_partial_cycles = true\n").unwrap();
+        assert_eq!(legacy.degradation_policy().pool_api, Fallback::SkipPhase);
+        let both = Config::from_toml_str("[runtime]\nallow_partial_cycles = true\n[degradation]\npool_api = \"use_last_known\"\n");
+        assert_eq!(both.unwrap().degradation_policy().pool_api, Fallback::UseLastKnown);
+
+        for (toml, key) in [
+            ("[degradation]\nrpc = \"skip_phase\"\n", "degradation.rpc"),
+            ("[degradation]\nbridge_api = \"use_cache\"\n", "degradation.bridge_api"),
+        ] {
+            let err = Config::from_toml_str(toml).unwrap().validate().unwrap_err();
+            assert!(err.to_string().contains(key), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_safe_tokens() {
+        let toml = r#"
+[[safe.tokens]]
+symbol = "USDC"
+address = "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48"
+decimals = 6
+
+[[safe.tokens]]
+symbol = "DAI"
+address = "0x6B175474E89094C44Da98b954EedeAC495271d0F"
+"#;
+        let config = Config::from_toml_str(toml).unwrap();
+        config.validate().unwrap();
+        assert_eq!(config.safe.tokens.len(), 2);
+        assert_eq!(config.safe.tokens[0].decimals, Some(6));
+        assert_eq!(config.safe.tokens[1].decimals, None);
+
+        for (toml, key) in [
+            ("[[safe.tokens]]\nsymbol = \"X\"\naddress = \"0x12\"\n", "safe.tokens.X.address"),
+            ("[[safe.tokens]]\nsymbol = \"\"\naddress = \"0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48\"\n", "safe.tokens[0].symbol"),
+            (
+                "[[safe.tokens]]\nsymbol = \"X\"\naddress = \"0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48\"\ndecimals = 80\n",
+                "safe.tokens.X.decimals",
+            ),
+        ] {
+            let err = Config::from_toml_str(toml).unwrap().validate().unwrap_err();
+            assert!(err.to_string().contains(key), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_priority_fee() {
+        let config = Config::from_toml_str("[safe]\npriority_fee_gwei = 1.5\n").unwrap();
+        config.validate().unwrap();
+        assert_eq!(config.safe.priority_fee_wei(), Some(U256::from(1_500_000_000_u64)));
+        assert_eq!(Config::default().safe.priority_fee_wei(), None);
+        let err = Config::from_toml_str("[safe]\npriority_fee_gwei = -1.0\n").unwrap().validate().unwrap_err();
+        assert!(err.to_string().contains("safe.priority_fee_gwei"), "{}", err);
+    }
+
+    #[test]
+    fn test_balance_thresholds() {
+        // Critical defaults to half of the minimum
+        let config = Config::default();
+        assert_eq!(config.safe.critical_balance(), U256::from(500_000_000_000_000_u64));
+
+        let config = Config::from_toml_str("[safe]\nmin_balance_eth = 0.01\ncritical_balance_eth = 0.001\n").unwrap();
+        config.validate().unwrap();
+        assert_eq!(config.safe.min_balance(), U256::exp10(16));
+        assert_eq!(config.safe.critical_balance(), U256::exp10(15));
+
+        // Beyond u64, in wei from the environment; wei wins over ETH
+        let mut config = Config::default();
+        let env: HashMap<&str, &str> = [
+            ("MIN_BALANCE_WEI", "100_000_000_000_000_000_000_000"),
+            ("CRITICAL_BALANCE_WEI", "25000000000000000000000"),
+            ("CRITICAL_BALANCE_ETH", "99999"),
+        ]
+        .into_iter()
+        .collect();
+        config.apply_overrides(|k| env.get(k).map(|v| v.to_string())).unwrap();
+        config.validate().unwrap();
+        assert_eq!(config.safe.min_balance(), U256::exp10(23));
+        assert_eq!(config.safe.critical_balance(), U256::exp10(21) * 25);
+
+        for (toml, key) in [
+            ("critical_balance_eth = 0.001", "safe.critical_balance_eth"),
+            ("critical_balance_eth = 0.002", "safe.critical_balance_eth"),
+            ("critical_balance_eth = -1.0", "safe.critical_balance_eth"),
+            ("critical_balance_wei = \"1000000000000000\"", "safe.critical_balance_wei"),
+            ("critical_balance_wei = \"0.5\"", "safe.critical_balance_wei"),
+            ("min_balance_wei = \"0x10\"", "safe.min_balance_wei"),
+            (
+                "min_balance_wei = \"1000000000000000000000000000000000000000000000000000000000000000000000000000000\"",
+                "safe.min_balance_wei",
+            ),
+        ] {
+            let config = Config::from_toml_str(&format!("[safe]\n{}\n", toml)).unwrap();
+            let err = config.validate().unwrap_err();
+            assert!(err.to_string().contains(key), "{}: {}", toml, err);
+        }
+        let err = Config::from_toml_str("[safe]\ncritical_balance_eth = 0.001\n").unwrap().validate().unwrap_err();
+        assert!(err.to_string().contains("must be below the minimum balance of 1000000000000000 wei"), "{}", err);
+    }
+
+    #[test]
+    fn test_accounts() {
+        let toml = "[safe]\nmin_balance_eth = 0.01\n\n[[safe.accounts]]\naddress = \"0x1111111111111111111111111111111111111111\"\n\n[[safe.accounts]]\naddress = \"0x2222222222222222222222222222222222222222\"\nmin_balance_eth = 1.0\n";
+        let config = Config::from_toml_str(toml).unwrap();
+        config.validate().unwrap();
+        let minimums: Vec<(Address, U256)> =
+            config.safe.accounts.iter().map(|a| (a.address().unwrap(), a.min_balance_wei(&config.safe))).collect();
+        assert_eq!(
+            minimums,
+            [(Address::repeat_byte(0x11), U256::exp10(16)), (Address::repeat_byte(0x22), U256::exp10(18))]
+        );
+
+        let duplicate = toml.replace("0x2222222222222222222222222222222222222222", "0x1111111111111111111111111111111111111111");
+        let negative = toml.replace("min_balance_eth = 1.0", "min_balance_eth = -1.0");
+        for (toml, key) in [
+            (duplicate.as_str(), "safe.accounts[1].address"),
+            (negative.as_str(), "safe.accounts[1].min_balance_eth"),
+            ("[[safe.accounts]]\naddress = \"0x12\"\n", "safe.accounts.address"),
+        ] {
+            let err = Config::from_toml_str(toml).unwrap().validate().unwrap_err();
+            assert!(err.to_string().contains(key), "{}: {}", toml, err);
+        }
+    }
+
+    #[test]
+    fn test_spending_limits() {
+        let config = Config::default();
+        assert_eq!((config.safe.max_value_per_tx(), config.safe.max_value_per_day()), (None, None));
+
+        let mut config = Config::from_toml_str("[safe]\nmax_value_per_tx_wei = \"1_000_000_000_000_000_000\"\n").unwrap();
+        config.apply_overrides(|k| (k == "MAX_VALUE_PER_DAY_WEI").then(|| "5000000000000000000".to_string())).unwrap();
+        config.validate().unwrap();
+        assert_eq!(config.safe.max_value_per_tx(), Some(U256::exp10(18)));
+        assert_eq!(config.safe.max_value_per_day(), Some(U256::exp10(18) * 5));
+
+        let err = Config::from_toml_str("[safe]\nmax_value_per_day_wei = \"1.5\"\n").unwrap().validate().unwrap_err();
+        assert!(err.to_string().contains("safe.max_value_per_day_wei"), "{}", err);
+    }
+
+    #[test]
+    fn test_daily_gas_budget() {
+        assert_eq!(Config::default().safe.daily_gas_budget(), None);
+
+        let mut config = Config::default();
+        config.apply_overrides(|k| (k == "DAILY_GAS_BUDGET_WEI").then(|| "50000000000000000".to_string())).unwrap();
+        config.validate().unwrap();
+        assert_eq!(config.safe.daily_gas_budget(), Some(U256::exp10(16) * 5));
+
+        let err = Config::from_toml_str("[safe]\ndaily_gas_budget_wei = \"-1\"\n").unwrap().validate().unwrap_err();
+        assert!(err.to_string().contains("safe.daily_gas_budget_wei"), "{}", err);
+    }
+
+    #[test]
+    fn test_delegatecall() {
+        let config = Config::default();
+        assert!(!config.safe.allow_delegatecall);
+        assert_eq!(config.safe.delegatecall_targets().unwrap(), [config.actions.multi_send().unwrap()]);
+
+        let mut config = Config::default();
+        config.apply_overrides(|k| (k == "ALLOW_DELEGATECALL").then(|| "true".to_string())).unwrap();
+        config.validate().unwrap();
+        assert!(config.safe.allow_delegatecall);
+
+        // Pool entries batch through actions.multi_send, so it must be allowed
+        config.safe.delegatecall_targets = vec![format!("{:?}", Address::repeat_byte(0x66))];
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("safe.delegatecall_targets"), "{}", err);
+        config.safe.delegatecall_targets = vec!["0x12".to_string()];
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_allowed_modules() {
+        assert!(Config::default().safe.allowed_modules().unwrap().is_empty());
+        let module = Address::repeat_byte(0x77);
+        let config = Config::from_toml_str(&format!("[safe]\nallowed_modules = [\"{:?}\"]\n", module)).unwrap();
+        config.validate().unwrap();
+        assert_eq!(config.safe.allowed_modules().unwrap(), [module]);
+        let err = Config::from_toml_str("[safe]\nallowed_modules = [\"0x12\"]\n").unwrap().validate().unwrap_err();
+        assert!(err.to_string().contains("safe.allowed_modules"), "{}", err);
+    }
+
+    #[test]
+    fn test_balance_recovery_factor() {
+        assert_eq!(Config::default().safe.balance_recovery_factor, 1.1);
+        let mut config = Config::default();
+        config.apply_overrides(|k| (k == "BALANCE_RECOVERY_FACTOR").then(|| "1.25".to_string())).unwrap();
+        config.validate().unwrap();
+        assert_eq!(config.safe.balance_recovery_factor, 1.25);
+
+        let err = Config::from_toml_str("[safe]\nbalance_recovery_factor = 0.9\n").unwrap().validate().unwrap_err();
+        assert!(err.to_string().contains("safe.balance_recovery_factor"), "{}", err);
+    }
+
+    #[test]
+    fn test_gas_limits() {
+        let config = Config::default();
+        assert_eq!((config.safe.gas_buffer, config.safe.max_gas_limit), (1.2, 5_000_000));
+        let mut config = Config::from_toml_str("[safe]\ngas_buffer = 1.5\n").unwrap();
+        config.apply_overrides(|k| (k == "MAX_GAS_LIMIT").then(|| "8000000".to_string())).unwrap();
+        config.validate().unwrap();
+        assert_eq!((config.safe.gas_buffer, config.safe.max_gas_limit), (1.5, 8_000_000));
+
+        for (toml, key) in [("gas_buffer = 0.8", "safe.gas_buffer"), ("max_gas_limit = 20000", "safe.max_gas_limit")] {
+            let err = Config::from_toml_str(&format!("[safe]\n{}\n", toml)).unwrap().validate().unwrap_err();
+            assert!(err.to_string().contains(key), "{}: {}", toml, err);
+        }
+    }
+
+    #[test]
+    fn test_confirmations() {
+        let config = Config::default();
+        assert_eq!((config.safe.confirmations, config.safe.confirmation_timeout_secs), (None, 300));
+        let config = Config::from_toml_str("[safe]\nconfirmations = 0\nconfirmation_timeout_secs = 60\n").unwrap();
+        config.validate().unwrap();
+        assert_eq!(config.safe.confirmations, Some(0));
+        let err = Config::from_toml_str("[safe]\nconfirmation_timeout_secs = 0\n").unwrap().validate().unwrap_err();
+        assert!(err.to_string().contains("safe.confirmation_timeout_secs"), "{}", err);
+        assert_eq!(Config::default().safe.max_submissions_per_minute, 2);
+        let err = Config::from_toml_str("[safe]\nmax_submissions_per_minute = 0\n").unwrap().validate().unwrap_err();
+        assert!(err.to_string().contains("safe.max_submissions_per_minute"), "{}", err);
+    }
+
+    #[test]
+    fn test_safe_execution() {
+        let key = "4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318";
+        let mut config = Config::default();
+        assert_eq!(config.safe.execution, SafeExecution::Execute);
+        assert_eq!(
+            config.safe.transaction_service_url("Ethereum").as_deref(),
+            Some("https://safe-transaction-mainnet.safe.global")
+        );
+        assert_eq!(config.safe.transaction_service_url("Fantom"), None);
+
+        let env: HashMap<&str, &str> = [("SAFE_EXECUTION", "Propose")].into_iter().collect();
+        config.apply_overrides(|k| env.get(k).map(|v| v.to_string())).unwrap();
+        assert_eq!(config.safe.execution, SafeExecution::Propose);
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("safe.signer_key"), "{}", err);
+        config.safe.signer_key = Some(format!("0x{}", key).into());
+        config.validate().unwrap();
+        let Some(OwnerSigner::Key(wallet)) = config.safe.signer().unwrap() else { panic!("expected a key signer") };
+        assert_eq!(wallet.address(), Address::from_str("0x2c7536E3605D9C16a7a3D7b1898e529396a65c23").unwrap());
+        config.safe.signer_key = Some("not a key".into());
+        assert!(config.validate().unwrap_err().to_string().contains("safe.signer_key"));
+
+        let config = Config::from_toml_str(
+            "[safe]\nexecution = \"execute\"\n[safe.transaction_service_urls]\nFantom = \"https://safe.example/\"\n",
+        )
+        .unwrap();
+        config.validate().unwrap();
+        assert_eq!(config.safe.transaction_service_url("Fantom").as_deref(), Some("https://safe.example"));
+        let err = Config::from_toml_str("[safe.transaction_service_urls]\nFantom = \"ftp://x\"\n")
+            .unwrap()
+            .validate()
+            .unwrap_err();
+        assert!(err.to_string().contains("safe.transaction_service_urls.Fantom"), "{}", err);
+        let env: HashMap<&str, &str> = [("SAFE_EXECUTION", "sometimes")].into_iter().collect();
+        assert!(Config::default().apply_overrides(|k| env.get(k).map(|v| v.to_string())).is_err());
+
+        let mut config = Config::default();
+        let env: HashMap<&str, &str> =
+            [("SAFE_EXECUTION", "simulate"), ("PRIVATE_KEY", key)].into_iter().collect();
+        config.apply_overrides(|k| env.get(k).map(|v| v.to_string())).unwrap();
+        assert_eq!(config.safe.execution, SafeExecution::Simulate);
+        assert_eq!(config.safe.signer_key.as_ref().unwrap().expose(), key);
+        let env: HashMap<&str, &str> = [("PRIVATE_KEY", "0x01"), ("SAFE_SIGNER_KEY", key)].into_iter().collect();
+        config.apply_overrides(|k| env.get(k).map(|v| v.to_string())).unwrap();
+        assert_eq!(config.safe.signer_key.as_ref().unwrap().expose(), key);
+    }
+
+    #[test]
+    fn test_ledger_signer() {
+        let env: HashMap<&str, &str> =
+            [("SIGNER", "Ledger"), ("LEDGER_PATH", "m/44'/60'/1'/0/0")].into_iter().collect();
+        let mut config = Config::default();
+        config.apply_overrides(|k| env.get(k).map(|v| v.to_string())).unwrap();
+        assert_eq!((config.safe.signer, config.safe.ledger_path.as_str()), (SignerKind::Ledger, "m/44'/60'/1'/0/0"));
+        // The key is not used, so it is not required either
+        assert!(config.safe.signer_key.is_none());
+        if cfg!(feature = "ledger") {
+            config.validate().unwrap();
+            assert_eq!(config.safe.signer().unwrap().unwrap().to_string(), "Ledger at m/44'/60'/1'/0/0");
+        } else {
+            let err = config.validate().unwrap_err();
+            assert!(err.to_string().contains("safe.signer"), "{}", err);
+            assert!(err.to_string().contains("`ledger` feature"), "{}", err);
+        }
+
+        for path in ["44'/60'/0'/0/0", "m/44'/60'/x/0", "m/"] {
+            let toml = format!("[safe]\nsigner = \"ledger\"\nledger_path = \"{}\"\n", path);
+            let err = Config::from_toml_str(&toml).unwrap().validate().unwrap_err();
+            assert!(err.to_string().contains("safe.ledger_path"), "{}: {}", path, err);
+        }
+        let err = Config::from_toml_str("[safe]\nsigner = \"ledger\"\nledger_timeout_secs = 0\n")
+            .unwrap()
+            .validate()
+            .unwrap_err();
+        assert!(err.to_string().contains("safe.ledger_timeout_secs"), "{}", err);
+        let env: HashMap<&str, &str> = [("SIGNER", "trezor")].into_iter().collect();
+        assert!(Config::default().apply_overrides(|k| env.get(k).map(|v| v.to_string())).is_err());
+    }
+
+    #[test]
+    fn test_home_chain_id() {
+        assert_eq!(Config::default().home_chain_id(), 1);
+        let config = Config::from_toml_str("profile = \"testnet\"\n").unwrap();
+        assert_eq!(config.home_chain_id(), 11155111);
+        let mut config = Config::from_toml_str("profile = \"local\"\n").unwrap();
+        assert_eq!(config.home_chain_id(), 31337);
+        config.apply_overrides(|k| (k == "CHAIN_ID").then(|| "1".to_string())).unwrap();
+        assert_eq!((config.safe.chain_id, config.home_chain_id()), (Some(1), 1));
+        assert!(Config::default().apply_overrides(|k| (k == "CHAIN_ID").then(|| "mainnet".to_string())).is_err());
+    }
+
+    #[test]
+    fn test_transfer_watch() {
+        let mut config = Config::default();
+        assert_eq!(config.safe.transfers, TransferWatchConfig { enabled: false, notify: false, max_block_range: 2_000 });
+        config.apply_overrides(|k| (k == "WATCH_TRANSFERS").then(|| "true".to_string())).unwrap();
+        assert!(config.safe.transfers.enabled);
+        let config = Config::from_toml_str("[safe.transfers]\nenabled = true\nnotify = true\nmax_block_range = 500\n").unwrap();
+        config.validate().unwrap();
+        assert_eq!(config.safe.transfers, TransferWatchConfig { enabled: true, notify: true, max_block_range: 500 });
+        let err = Config::from_toml_str("[safe.transfers]\nmax_block_range = 0\n").unwrap().validate().unwrap_err();
+        assert!(err.to_string().contains("safe.transfers.max_block_range"), "{}", err);
+    }
+
+    #[test]
+    fn test_top_up() {
+        let key = "59c6995e998f97a5a0044966f0945389dc9e86dae88c7a8412f4603b6b78690d";
+        assert!(Config::default().safe.top_up().unwrap().is_none());
+
+        let section = "[safe]\nmin_balance_eth = 0.01\n\n[safe.top_up]\n";
+        let funded = |rest: &str| {
+            let mut config = Config::from_toml_str(&format!("{}{}", section, rest)).unwrap();
+            config.apply_overrides(|k| (k == "TOP_UP_FUNDING_KEY").then(|| format!("0x{}", key))).unwrap();
+            config
+        };
+        let mut config = Config::from_toml_str(&format!("{}max_per_day_eth = 0.05\n", section)).unwrap();
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("TOP_UP_FUNDING_KEY"), "{}", err);
+        config = funded("max_per_day_eth = 0.05\n");
+        config.validate().unwrap();
+        let top_up = config.safe.top_up().unwrap().unwrap();
+        assert_eq!(top_up.funder.to_string(), "key of 0x70997970c51812dc3a010c7d01b50e0d17dc79c8");
+        // Up to the minimum balance unless given, keeping 0.01 ETH
+        assert_eq!(top_up.target, ethers::utils::parse_ether(0.01).unwrap());
+        assert_eq!(top_up.max_per_day, ethers::utils::parse_ether(0.05).unwrap());
+        assert_eq!(top_up.reserve, ethers::utils::parse_ether(0.01).unwrap());
+        assert!(!format!("{:?}", config).contains(key));
+
+        for (rest, key) in [
+            ("", "safe.top_up.max_per_day_eth"),
+            ("max_per_day_eth = 0.0\n", "safe.top_up.max_per_day_eth"),
+            ("max_per_day_eth = -1.0\n", "safe.top_up.max_per_day_eth"),
+            ("max_per_day_eth = 0.05\ntarget_balance_eth = 0.005\n", "safe.top_up.target_balance_eth"),
+            ("max_per_day_eth = 0.05\nreserve_eth = -0.1\n", "safe.top_up.reserve_eth"),
+        ] {
+            let err = funded(rest).validate().unwrap_err();
+            assert!(err.to_string().contains(key), "{:?}: {}", rest, err);
+        }
+        // The funding wallet cannot be the Safe itself
+        config.safe.address = Some("0x70997970C51812dc3A010C7d01b50e0d17dc79C8".to_string());
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("Safe's own address"), "{}", err);
+    }
+}
@@ -0,0 +1,269 @@
+//! Append-only log of routing decisions and the inputs behind them.
+//!
+//! With `storage.decision_log_path` set, every full cycle that had pool data appends
+//! one [`DecisionRecord`] to that file as a JSON line. `asam replay` reads it back and
+//! decides again on a recorded input set under the current config, to show whether
+//! changed thresholds would have changed the outcome.
+
+use anyhow::{Context, Result};
+use ethers::core::types::U256;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::report::{decimal, from_decimal, CycleDecision, DecisionInputs, RouteResult, ScoredPool, StrategyDecision};
+use crate::status::BalanceStatus;
+use crate::strategy::Strategy;
+
+/// Best ranked pools kept per record.
+pub const CANDIDATE_POOLS: usize = 5;
+
+/// Gas price when the decision was made.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GasSnapshot {
+    pub chain: String,
+    #[serde(serialize_with = "decimal", deserialize_with = "from_decimal")]
+    pub gas_price_wei: U256,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DecisionRecord {
+    pub timestamp: u64,
+    /// Batch number since the agent started.
+    pub cycle_id: u64,
+    #[serde(serialize_with = "decimal", deserialize_with = "from_decimal")]
+    pub balance: U256,
+    pub balance_status: BalanceStatus,
+    /// Top pools, best first.
+    pub candidate_pools: Vec<ScoredPool>,
+    /// Chain holding most of the funds.
+    pub current_position: String,
+    pub gas_snapshot: Option<GasSnapshot>,
+    /// The route that was started or simulated, if any.
+    pub route_preview: Option<RouteResult>,
+    /// What the strategy decided, before operator approval or observe mode.
+    pub decision: CycleDecision,
+    pub reason: String,
+    /// Action builder that can enter the best pool; unset when it is only suggested.
+    #[serde(default)]
+    pub pool_action: Option<String>,
+    pub inputs: DecisionInputs,
+    /// What every configured strategy decided on the same pools, when several are.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub strategies: Vec<StrategyDecision>,
+}
+
+/// One line on why `decision` was reached from `inputs`.
+pub fn explain(decision: &CycleDecision, inputs: &DecisionInputs) -> String {
+    match decision {
+        CycleDecision::NoAction if inputs.candidate_chain == inputs.current_chain => {
+            format!("best pool is on {} where the funds are", inputs.current_chain)
+        }
+        CycleDecision::NoAction => format!(
+            "{} beats {} by {:.2} APY points, {:.2} required",
+            inputs.candidate_chain,
+            inputs.current_chain,
+            inputs.candidate_apy - inputs.current_apy.unwrap_or(0.0),
+            inputs.min_apy_improvement
+        ),
+        CycleDecision::NotWorthMoving => match &inputs.sizing {
+            Some(sizing) => format!("{} is below the bridge minimum {}", sizing.amount, sizing.minimum),
+            None => format!(
+                "candidate APY {:.2}% or TVL ${:.0} too low",
+                inputs.candidate_apy, inputs.candidate_tvl
+            ),
+        },
+        CycleDecision::RouteInitiated => format!(
+            "route {} from {} to {}",
+            inputs.amount.unwrap_or_default(),
+            inputs.current_chain,
+            inputs.candidate_chain
+        ),
+        CycleDecision::Blocked(reason) | CycleDecision::Declined(reason) => reason.clone(),
+    }
+}
+
+/// Cloneable handle that appends records when a path is configured.
+#[derive(Debug, Clone, Default)]
+pub struct DecisionLog {
+    path: Option<PathBuf>,
+}
+
+impl DecisionLog {
+    pub fn new(path: Option<PathBuf>) -> Self {
+        Self { path }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.path.is_some()
+    }
+
+    /// Failures are logged; they never fail the cycle.
+    pub fn append(&self, record: &DecisionRecord) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let written = serde_json::to_string(record).map_err(std::io::Error::from).and_then(|line| {
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .and_then(|mut file| writeln!(file, "{}", line))
+        });
+        if let Err(e) = written {
+            warn!("Failed to append to decision log {}: {}", path.display(), e);
+        }
+    }
+}
+
+/// Reads every record in `path`, oldest first.
+pub fn read(path: &Path) -> Result<Vec<DecisionRecord>> {
+    let contents =
+        std::fs::read_to_string(path).with_context(|| format!("Failed to read decision log {}", path.display()))?;
+    contents
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            serde_json::from_str(line).with_context(|| format!("{}:{}: invalid decision record", path.display(), i + 1))
+        })
+        .collect()
+}
+
+/// The last record made at or before `at`.
+pub fn find_at(records: &[DecisionRecord], at: u64) -> Option<&DecisionRecord> {
+    records.iter().filter(|record| record.timestamp <= at).max_by_key(|record| record.timestamp)
+}
+
+/// A recorded decision next to the one the current config makes on the same inputs.
+#[derive(Debug, Clone)]
+pub struct Replay {
+    pub recorded: CycleDecision,
+    pub replayed: CycleDecision,
+    pub reason: String,
+    /// The recorded inputs with the derived fields from the current config.
+    pub inputs: DecisionInputs,
+}
+
+impl Replay {
+    pub fn differs(&self) -> bool {
+        self.recorded != self.replayed
+    }
+}
+
+pub fn replay(record: &DecisionRecord, strategy: &Strategy) -> Replay {
+    let (replayed, inputs) = strategy.decide(record.inputs.clone());
+    Replay {
+        recorded: record.decision.clone(),
+        reason: explain(&replayed, &inputs),
+        replayed,
+        inputs,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::storage::MemoryStore;
+    use std::sync::Arc;
+
+    fn strategy(config: &Config) -> Strategy {
+        Strategy::from_config(&config.strategy, &config.router, Arc::new(MemoryStore::new()))
+    }
+
+    fn inputs(candidate_apy: f64) -> DecisionInputs {
+        DecisionInputs {
+            current_chain: "Ethereum".to_string(),
+            current_apy: Some(4.0),
+            candidate_chain: "Arbitrum".to_string(),
+            candidate_apy,
+            candidate_tvl: 5_000_000.0,
+            balance: U256::exp10(18) * 2,
+            paused: false,
+            pending_route: None,
+            last_route_at: Some(1_700_000_000),
+            observed_at: 1_700_090_000,
+            recent_route: None,
+            rebalance_gas: None,
+            gas_price_gwei: None,
+            min_apy_improvement: 0.0,
+            cooldown_remaining_secs: None,
+            amount: None,
+            sizing: None,
+            breakeven_days: None,
+        }
+    }
+
+    fn record(config: &Config, timestamp: u64, candidate_apy: f64) -> DecisionRecord {
+        let (decision, inputs) = strategy(config).decide(inputs(candidate_apy));
+        DecisionRecord {
+            timestamp,
+            cycle_id: 7,
+            balance: inputs.balance,
+            balance_status: BalanceStatus::Healthy,
+            candidate_pools: Vec::new(),
+            current_position: inputs.current_chain.clone(),
+            gas_snapshot: Some(GasSnapshot { chain: "Ethereum".to_string(), gas_price_wei: U256::from(30_000_000_000u64) }),
+            route_preview: None,
+            reason: explain(&decision, &inputs),
+            pool_action: None,
+            decision,
+            inputs,
+            strategies: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_records_round_trip_through_the_log() {
+        let path = std::env::temp_dir().join(format!("asam-decisions-{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let config = Config::default();
+        let log = DecisionLog::new(Some(path.clone()));
+        log.append(&record(&config, 100, 9.0));
+        log.append(&record(&config, 200, 4.5));
+
+        let records = read(&path).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].decision, CycleDecision::RouteInitiated);
+        assert_eq!(records[0].gas_snapshot.as_ref().unwrap().gas_price_wei, U256::from(30_000_000_000u64));
+        assert_eq!(records[1].decision, CycleDecision::NoAction);
+        assert_eq!(records[1].reason, "Arbitrum beats Ethereum by 0.50 APY points, 1.00 required");
+        // Derived fields are decided again rather than read back
+        assert_eq!(records[0].inputs.balance, U256::exp10(18) * 2);
+        assert!(records[0].inputs.sizing.is_none());
+
+        assert_eq!(find_at(&records, 150).unwrap().timestamp, 100);
+        assert_eq!(find_at(&records, 200).unwrap().timestamp, 200);
+        assert!(find_at(&records, 99).is_none());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_replay_is_deterministic_for_a_fixed_config() {
+        let mut config = Config::default();
+        let original = record(&config, 100, 9.0);
+        let line = serde_json::to_string(&original).unwrap();
+        let recorded: DecisionRecord = serde_json::from_str(&line).unwrap();
+
+        let first = replay(&recorded, &strategy(&config));
+        let second = replay(&recorded, &strategy(&config));
+        assert!(!first.differs());
+        assert_eq!(first.replayed, second.replayed);
+        assert_eq!(first.inputs, second.inputs);
+        assert_eq!(first.inputs, original.inputs);
+
+        config.strategy.min_apy_improvement = 6.0;
+        let stricter = replay(&recorded, &strategy(&config));
+        assert!(stricter.differs());
+        assert_eq!(stricter.replayed, CycleDecision::NoAction);
+
+        let mut out = Vec::new();
+        crate::cli::print_replay(&recorded, &stricter, &mut out).unwrap();
+        let printed = String::from_utf8(out).unwrap();
+        assert!(printed.contains("Replayed: NoAction - Arbitrum beats Ethereum by 5.00 APY points, 6.00 required"), "{}", printed);
+        assert!(printed.ends_with("The current config decides differently\n"), "{}", printed);
+    }
+}
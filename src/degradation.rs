@@ -0,0 +1,329 @@
+//! What a cycle does when one of its external dependencies is down.
+//!
+//! [`DegradationPolicy`] maps each [`Dependency`] to a [`Fallback`]: fail the cycle,
+//! carry on without the dependency, or carry on with its last answer. Every attempt
+//! to reach a dependency is recorded in its [`DependencyHealth`], served in
+//! `/status`; the fallbacks a cycle took are listed in its report, and an alert goes
+//! out when the fallback in effect for a dependency changes rather than on every
+//! failed attempt.
+
+use anyhow::Result;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::sync::Mutex;
+
+use crate::config::{DegradationPolicy, Fallback};
+use crate::errors::ErrorCategory;
+use crate::notifier::{AgentEvent, EventSender};
+use crate::status::StatusHandle;
+use crate::util::unix_now;
+
+/// Attempts per dependency its health is derived from.
+pub const HEALTH_WINDOW: usize = 10;
+
+/// An external service a cycle depends on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Dependency {
+    /// The RPC endpoints of the home chain.
+    Rpc,
+    /// The pool data API.
+    PoolApi,
+    /// The bridge provider starting routes.
+    BridgeApi,
+    /// The price sources valuing the portfolio.
+    Prices,
+}
+
+impl fmt::Display for Dependency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Dependency::Rpc => "rpc",
+            Dependency::PoolApi => "pool_api",
+            Dependency::BridgeApi => "bridge_api",
+            Dependency::Prices => "prices",
+        })
+    }
+}
+
+impl Dependency {
+    /// The dependency `err` says is down; `None` for errors no dependency is to blame
+    /// for, such as a critical balance.
+    pub fn of(err: &anyhow::Error) -> Option<Self> {
+        match ErrorCategory::of(err) {
+            ErrorCategory::RpcTransport | ErrorCategory::RpcRateLimit => Some(Dependency::Rpc),
+            ErrorCategory::DefiApi => Some(Dependency::PoolApi),
+            ErrorCategory::BridgeApi => Some(Dependency::BridgeApi),
+            ErrorCategory::Validation | ErrorCategory::Internal => None,
+        }
+    }
+}
+
+/// A fallback a cycle took for a dependency that failed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Degradation {
+    pub dependency: Dependency,
+    /// Never `fail`: a failing dependency under `fail` fails the cycle instead.
+    pub fallback: Fallback,
+    /// The error, and how old the answer used instead is.
+    pub detail: String,
+}
+
+/// Whether a dependency answered its recent attempts.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthState {
+    /// Nothing has been attempted yet.
+    #[default]
+    Unknown,
+    /// Every recent attempt succeeded.
+    Up,
+    /// The latest attempt succeeded, an earlier recent one failed.
+    Flaky,
+    /// The latest attempt failed.
+    Down,
+}
+
+/// Recent attempts on one dependency.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct DependencyHealth {
+    pub state: HealthState,
+    /// Failures among the last [`HEALTH_WINDOW`] attempts.
+    pub recent_failures: usize,
+    pub last_success_at: Option<u64>,
+    pub last_failure_at: Option<u64>,
+    pub last_error: Option<String>,
+    /// Fallback in effect since the dependency last failed; unset while it answers.
+    pub fallback: Option<Fallback>,
+    /// Whether each recent attempt succeeded, oldest first.
+    #[serde(skip)]
+    recent: VecDeque<bool>,
+}
+
+impl DependencyHealth {
+    fn attempted(&mut self, ok: bool) {
+        if self.recent.len() == HEALTH_WINDOW {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(ok);
+        self.recent_failures = self.recent.iter().filter(|ok| !**ok).count();
+        self.state = match (ok, self.recent_failures) {
+            (false, _) => HealthState::Down,
+            (true, 0) => HealthState::Up,
+            (true, _) => HealthState::Flaky,
+        };
+    }
+}
+
+/// Health of every dependency attempted since startup.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(transparent)]
+pub struct Dependencies(BTreeMap<Dependency, DependencyHealth>);
+
+impl Dependencies {
+    pub fn get(&self, dependency: Dependency) -> Option<&DependencyHealth> {
+        self.0.get(&dependency)
+    }
+
+    /// Records that `dependency` answered at `now`. Returns the alert when that ends
+    /// a fallback.
+    pub fn answered(&mut self, dependency: Dependency, now: u64) -> Option<AgentEvent> {
+        let health = self.0.entry(dependency).or_default();
+        health.attempted(true);
+        health.last_success_at = Some(now);
+        health.fallback.take().map(|_| AgentEvent::DegradationCleared { dependency })
+    }
+
+    /// Records that `dependency` failed at `now` with `error`, and the fallback the
+    /// cycle took; `None` when the failure failed the cycle, which leaves the fallback
+    /// in effect as it was. Returns the alert when the fallback changed to another one.
+    pub fn failed(
+        &mut self,
+        dependency: Dependency,
+        now: u64,
+        error: String,
+        degradation: Option<&Degradation>,
+    ) -> Option<AgentEvent> {
+        let health = self.0.entry(dependency).or_default();
+        health.attempted(false);
+        health.last_failure_at = Some(now);
+        health.last_error = Some(error);
+        let degradation = degradation?;
+        if health.fallback.replace(degradation.fallback) == Some(degradation.fallback) {
+            return None;
+        }
+        Some(AgentEvent::DegradationApplied {
+            dependency,
+            fallback: degradation.fallback,
+            detail: degradation.detail.clone(),
+        })
+    }
+}
+
+/// Applies a [`DegradationPolicy`] within one cycle: records each attempt on a
+/// dependency in the status snapshot, alerts on changed fallbacks and collects the
+/// degradations for the report. Shared by the phases running side by side.
+pub(crate) struct Degrader<'a> {
+    policy: &'a DegradationPolicy,
+    status: &'a StatusHandle,
+    events: &'a EventSender,
+    applied: Mutex<Vec<Degradation>>,
+}
+
+impl<'a> Degrader<'a> {
+    pub fn new(policy: &'a DegradationPolicy, status: &'a StatusHandle, events: &'a EventSender) -> Self {
+        Self { policy, status, events, applied: Mutex::default() }
+    }
+
+    /// Records that `dependency` answered.
+    pub fn answered(&self, dependency: Dependency) {
+        let mut event = None;
+        self.status.update(|s| event = s.dependencies.answered(dependency, unix_now()));
+        if let Some(event) = event {
+            self.events.emit(event);
+        }
+    }
+
+    /// What the cycle goes on with after `dependency` failed with `error`, as the
+    /// policy says: the `last` answer, given with its Unix time, while it is younger
+    /// than `max_cache_age_secs` under `use_cache` or at any age under
+    /// `use_last_known`; nothing under `skip_phase` or without a usable answer. Under
+    /// `fail` the error is returned.
+    pub fn fall_back<T>(&self, dependency: Dependency, error: anyhow::Error, last: Option<(u64, T)>) -> Result<Option<T>> {
+        let now = unix_now();
+        let reason = format!("{:#}", error);
+        let configured = self.policy.fallback(dependency);
+        let (fallback, kept, detail) = match (configured, last) {
+            (Fallback::Fail, _) => {
+                self.record_failure(dependency, reason, None);
+                return Err(error);
+            }
+            (Fallback::SkipPhase, _) => (Fallback::SkipPhase, None, reason.clone()),
+            (Fallback::UseCache | Fallback::UseLastKnown, Some((at, value)))
+                if configured == Fallback::UseLastKnown || now.saturating_sub(at) <= self.policy.max_cache_age_secs =>
+            {
+                let detail = format!("{}; using the answer from {}s ago", reason, now.saturating_sub(at));
+                (configured, Some(value), detail)
+            }
+            (_, Some((at, _))) => {
+                let detail = format!("{}; the last answer is {}s old", reason, now.saturating_sub(at));
+                (Fallback::SkipPhase, None, detail)
+            }
+            (_, None) => (Fallback::SkipPhase, None, format!("{}; no earlier answer", reason)),
+        };
+        warn!("{} unavailable, falling back to {}: {}", dependency, fallback, detail);
+        let degradation = Degradation { dependency, fallback, detail };
+        self.record_failure(dependency, reason, Some(&degradation));
+        self.applied.lock().unwrap_or_else(|e| e.into_inner()).push(degradation);
+        Ok(kept)
+    }
+
+    fn record_failure(&self, dependency: Dependency, error: String, degradation: Option<&Degradation>) {
+        let mut event = None;
+        self.status.update(|s| event = s.dependencies.failed(dependency, unix_now(), error, degradation));
+        if let Some(event) = event {
+            self.events.emit(event);
+        }
+    }
+
+    /// The degradations applied so far, in the order they were.
+    pub fn applied(&self) -> Vec<Degradation> {
+        self.applied.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agents::cross_chain_router::CrossChainError;
+    use crate::agents::defi_optimizer::DefiError;
+    use anyhow::anyhow;
+
+    fn pool_error() -> anyhow::Error {
+        DefiError::ApiError("status 503".to_string()).into()
+    }
+
+    #[test]
+    fn test_fallbacks_follow_the_policy() {
+        let status = StatusHandle::new();
+        let (events, mut rx) = EventSender::channel(16);
+        let now = unix_now();
+        let fresh = Some((now - 60, "fresh"));
+        let stale = Some((now - 3600, "stale"));
+
+        // (configured, last answer, kept, fallback taken)
+        for (configured, last, kept, taken) in [
+            (Fallback::SkipPhase, fresh, None, Fallback::SkipPhase),
+            (Fallback::UseCache, fresh, Some("fresh"), Fallback::UseCache),
+            (Fallback::UseCache, stale, None, Fallback::SkipPhase),
+            (Fallback::UseCache, None, None, Fallback::SkipPhase),
+            (Fallback::UseLastKnown, stale, Some("stale"), Fallback::UseLastKnown),
+        ] {
+            let policy = DegradationPolicy { pool_api: configured, max_cache_age_secs: 900, ..DegradationPolicy::default() };
+            let degrader = Degrader::new(&policy, &status, &events);
+            assert_eq!(degrader.fall_back(Dependency::PoolApi, pool_error(), last).unwrap(), kept, "{}", configured);
+            let applied = degrader.applied();
+            assert_eq!(applied.len(), 1);
+            assert_eq!(applied[0].fallback, taken, "{}", configured);
+            assert!(applied[0].detail.contains("status 503"), "{}", applied[0].detail);
+        }
+
+        let policy = DegradationPolicy::default();
+        let degrader = Degrader::new(&policy, &status, &events);
+        let bridge = anyhow::Error::new(CrossChainError::BridgeError("503".to_string()));
+        assert!(degrader.fall_back(Dependency::BridgeApi, bridge, None::<(u64, ())>).is_err());
+        assert!(degrader.applied().is_empty());
+        degrader.answered(Dependency::PoolApi);
+
+        // One alert per change of fallback, and one when the dependency answers again
+        let sent: Vec<AgentEvent> = std::iter::from_fn(|| rx.try_recv().ok()).collect();
+        let kinds: Vec<(&str, Option<Fallback>)> = sent
+            .iter()
+            .map(|event| match event {
+                AgentEvent::DegradationApplied { fallback, .. } => ("applied", Some(*fallback)),
+                other => (other.kind(), None),
+            })
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ("applied", Some(Fallback::SkipPhase)),
+                ("applied", Some(Fallback::UseCache)),
+                ("applied", Some(Fallback::SkipPhase)),
+                ("applied", Some(Fallback::UseLastKnown)),
+                ("degradation_cleared", None),
+            ]
+        );
+
+        let dependencies = status.snapshot().dependencies;
+        let pools = dependencies.get(Dependency::PoolApi).unwrap();
+        assert_eq!((pools.state, pools.recent_failures, pools.fallback), (HealthState::Flaky, 5, None));
+        assert_eq!(dependencies.get(Dependency::BridgeApi).unwrap().state, HealthState::Down);
+        assert_eq!(dependencies.get(Dependency::Rpc), None);
+    }
+
+    #[test]
+    fn test_health_covers_recent_attempts() {
+        let mut dependencies = Dependencies::default();
+        dependencies.failed(Dependency::Prices, 1, "timeout".to_string(), None);
+        for at in 2..=HEALTH_WINDOW as u64 {
+            dependencies.answered(Dependency::Prices, at);
+        }
+        assert_eq!(dependencies.get(Dependency::Prices).unwrap().state, HealthState::Flaky);
+        // The failure leaves the window
+        dependencies.answered(Dependency::Prices, 11);
+        let prices = dependencies.get(Dependency::Prices).unwrap();
+        assert_eq!((prices.state, prices.recent_failures), (HealthState::Up, 0));
+        assert_eq!((prices.last_success_at, prices.last_failure_at), (Some(11), Some(1)));
+
+        let json = serde_json::to_value(&dependencies).unwrap();
+        assert_eq!(json["prices"]["state"], "up");
+        assert_eq!(json["prices"]["last_error"], "timeout");
+        assert!(json["prices"].get("recent").is_none());
+
+        assert_eq!(Dependency::of(&pool_error()), Some(Dependency::PoolApi));
+        assert_eq!(Dependency::of(&anyhow!("odd")), None);
+    }
+}
@@ -0,0 +1,323 @@
+//! ENS names in place of configured addresses.
+//!
+//! `safe.address` and the `safe.accounts` addresses may be ENS names such as
+//! `treasury.mydao.eth`. [`resolve_config`] resolves them through the home chain's
+//! registry once at startup and keeps the name in the config next to what it
+//! resolved to, so a reload does not see a changed address. A name that resolves
+//! to the zero address, or a chain without a registry (most L2s), stops the agent
+//! with an [`EnsError`] instead of monitoring the wrong account.
+//!
+//! [`EnsResolver::display`] goes the other way for logs: an address with a reverse
+//! record that resolves back to it shows as `treasury.mydao.eth (0x2c75…5c23)`.
+//! Each answer is cached, so the lookup is not repeated every cycle.
+
+use anyhow::{Context, Result};
+use ethers::abi::{ParamType, Token};
+use ethers::core::types::{Address, TransactionRequest};
+use ethers::providers::{ens, Middleware};
+use log::{debug, info};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use thiserror::Error;
+
+use crate::config::Config;
+use crate::rpc::{FailoverClient, RpcProvider};
+
+#[derive(Debug, Error, PartialEq)]
+pub enum EnsError {
+    #[error("Chain {chain_id} has no ENS registry to resolve '{name}'; configure the hex address instead")]
+    NoRegistry { name: String, chain_id: u64 },
+    #[error("ENS name '{0}' has no resolver")]
+    NoResolver(String),
+    #[error("ENS name '{0}' resolves to the zero address")]
+    ZeroAddress(String),
+    #[error("ENS lookup of '{name}' failed: {reason}")]
+    LookupFailed { name: String, reason: String },
+}
+
+/// Whether `raw` is meant as an ENS name rather than a hex address: dotted labels
+/// that are not empty, e.g. `treasury.mydao.eth`.
+pub fn is_ens_name(raw: &str) -> bool {
+    let raw = raw.trim();
+    !raw.starts_with("0x")
+        && raw.contains('.')
+        && raw.split('.').all(|label| {
+            !label.is_empty() && label.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_' || !c.is_ascii())
+        })
+}
+
+/// `name (0x2c75…5c23)` when the address has a name, the full address otherwise.
+pub fn label(address: Address, name: Option<&str>) -> String {
+    match name {
+        Some(name) => {
+            let hex = format!("{:?}", address);
+            format!("{} ({}…{})", name, &hex[..6], &hex[hex.len() - 4..])
+        }
+        None => format!("{:?}", address),
+    }
+}
+
+/// Forward and reverse ENS lookups against the registry of the provider's chain.
+#[derive(Debug)]
+pub struct EnsResolver {
+    provider: RpcProvider,
+    /// Whether the chain has a registry, once known.
+    registry: Mutex<Option<bool>>,
+    /// Names resolved or found in a verified reverse record; `None` for addresses
+    /// without one.
+    names: Mutex<HashMap<Address, Option<String>>>,
+}
+
+impl EnsResolver {
+    pub fn new(provider: RpcProvider) -> Self {
+        Self { provider, registry: Mutex::default(), names: Mutex::default() }
+    }
+
+    /// The address `name` resolves to. The name is remembered for the address, so
+    /// displaying it needs no reverse record.
+    pub async fn resolve(&self, name: &str) -> Result<Address> {
+        let name = name.trim();
+        if !self.has_registry(name).await? {
+            let chain_id = self.provider.get_chainid().await.map(|id| id.low_u64()).unwrap_or_default();
+            return Err(EnsError::NoRegistry { name: name.to_string(), chain_id }.into());
+        }
+        let address = self.forward(name).await?;
+        self.remember(address, name);
+        Ok(address)
+    }
+
+    /// Shows `address` as `name` from now on, without a reverse lookup.
+    pub fn remember(&self, address: Address, name: &str) {
+        self.names.lock().unwrap_or_else(|e| e.into_inner()).insert(address, Some(name.trim().to_string()));
+    }
+
+    /// The name of `address` from its reverse record, as long as the name resolves
+    /// back to it. Failed lookups are tried again next time; answers are cached.
+    pub async fn lookup(&self, address: Address) -> Option<String> {
+        if let Some(name) = self.names.lock().unwrap_or_else(|e| e.into_inner()).get(&address) {
+            return name.clone();
+        }
+        match self.reverse(address).await {
+            Ok(name) => {
+                self.names.lock().unwrap_or_else(|e| e.into_inner()).insert(address, name.clone());
+                name
+            }
+            Err(e) => {
+                debug!("Reverse ENS lookup of {:?} failed: {:#}", address, e);
+                None
+            }
+        }
+    }
+
+    /// `address` for logs, with its name when it has one; see [`label`].
+    pub async fn display(&self, address: Address) -> String {
+        label(address, self.lookup(address).await.as_deref())
+    }
+
+    async fn has_registry(&self, name: &str) -> Result<bool> {
+        if let Some(known) = *self.registry.lock().unwrap_or_else(|e| e.into_inner()) {
+            return Ok(known);
+        }
+        let code = self
+            .provider
+            .get_code(ens::ENS_ADDRESS, None)
+            .await
+            .map_err(|e| EnsError::LookupFailed { name: name.to_string(), reason: e.to_string() })?;
+        *self.registry.lock().unwrap_or_else(|e| e.into_inner()) = Some(!code.is_empty());
+        Ok(!code.is_empty())
+    }
+
+    async fn forward(&self, name: &str) -> Result<Address> {
+        let resolver = self.resolver(name).await?.ok_or_else(|| EnsError::NoResolver(name.to_string()))?;
+        let token = self.call(name, ens::resolve(resolver, ens::ADDR_SELECTOR, name, None), ParamType::Address).await?;
+        match token.into_address() {
+            Some(address) if !address.is_zero() => Ok(address),
+            _ => Err(EnsError::ZeroAddress(name.to_string()).into()),
+        }
+    }
+
+    async fn reverse(&self, address: Address) -> Result<Option<String>> {
+        let reverse = ens::reverse_address(address);
+        if !self.has_registry(&reverse).await? {
+            return Ok(None);
+        }
+        let Some(resolver) = self.resolver(&reverse).await? else { return Ok(None) };
+        let token = self.call(&reverse, ens::resolve(resolver, ens::NAME_SELECTOR, &reverse, None), ParamType::String).await?;
+        let Some(name) = token.into_string().filter(|name| !name.is_empty()) else { return Ok(None) };
+        // Anyone can claim any name in a reverse record; only one pointing back counts
+        match self.forward(&name).await {
+            Ok(forward) => Ok((forward == address).then_some(name)),
+            Err(e) if matches!(e.downcast_ref(), Some(EnsError::NoResolver(_) | EnsError::ZeroAddress(_))) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn resolver(&self, name: &str) -> Result<Option<Address>> {
+        let token = self.call(name, ens::get_resolver(ens::ENS_ADDRESS, name), ParamType::Address).await?;
+        Ok(token.into_address().filter(|resolver| !resolver.is_zero()))
+    }
+
+    async fn call(&self, name: &str, tx: TransactionRequest, output: ParamType) -> Result<Token> {
+        let failed = |reason: String| EnsError::LookupFailed { name: name.to_string(), reason };
+        let raw = self.provider.call(&tx.into(), None).await.map_err(|e| failed(e.to_string()))?;
+        let mut tokens = ethers::abi::decode(&[output], &raw).map_err(|e| failed(e.to_string()))?;
+        Ok(tokens.remove(0))
+    }
+}
+
+/// Resolves the ENS names among `safe.address` and the `safe.accounts` addresses
+/// through the home chain, logging each, then checks the accounts again now that
+/// their addresses are known. Connects only when there is a name to resolve.
+pub async fn resolve_config(config: &mut Config) -> Result<()> {
+    let safe = &config.safe;
+    let named = safe.address.as_deref().is_some_and(is_ens_name) || safe.accounts.iter().any(|a| is_ens_name(&a.address));
+    if !named {
+        return Ok(());
+    }
+    let (provider, _) = FailoverClient::from_config(&config.safe)?.into_provider();
+    let resolver = EnsResolver::new(provider);
+    let safe = &mut config.safe;
+    if let Some(name) = safe.address.clone().filter(|raw| is_ens_name(raw)) {
+        let address = resolver.resolve(&name).await.with_context(|| format!("Failed to resolve safe.address '{}'", name))?;
+        info!("Resolved safe.address {} to {:?}", name, address);
+        safe.resolved_address = Some(address);
+    }
+    for account in safe.accounts.iter_mut().filter(|account| is_ens_name(&account.address)) {
+        let address = resolver
+            .resolve(&account.address)
+            .await
+            .with_context(|| format!("Failed to resolve safe.accounts address '{}'", account.address))?;
+        info!("Resolved safe.accounts address {} to {:?}", account.address, address);
+        account.resolved = Some(address);
+    }
+    config.validate()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agents::test_utils::test_provider;
+    use crate::config::AccountConfig;
+    use ethers::abi::encode;
+    use std::str::FromStr;
+    use wiremock::matchers::body_string_contains;
+    use wiremock::{Mock, MockServer, Request, ResponseTemplate};
+
+    const TREASURY: &str = "0x2c7536e3605d9c16a7a3d7b1898e529396a65c23";
+    const RESOLVER: &str = "0x4976fb03c32e5b8cfe2b6ccb31c09ba78ebaba41";
+
+    fn reply(result: serde_json::Value) -> ResponseTemplate {
+        ResponseTemplate::new(200).set_body_json(serde_json::json!({ "jsonrpc": "2.0", "id": 1, "result": result }))
+    }
+
+    /// A mainnet-like node where `treasury.mydao.eth` resolves to [`TREASURY`], which
+    /// has it as reverse record, and `empty.mydao.eth` to the zero address.
+    async fn mock_ens(server: &MockServer, registry: bool) {
+        let code = if registry { "0x6080" } else { "0x" };
+        Mock::given(body_string_contains("eth_getCode")).respond_with(reply(code.into())).mount(server).await;
+        Mock::given(body_string_contains("eth_chainId")).respond_with(reply("0xa4b1".into())).mount(server).await;
+        let treasury = Address::from_str(TREASURY).unwrap();
+        let resolver = Address::from_str(RESOLVER).unwrap();
+        let answers = [
+            (ens::namehash("treasury.mydao.eth"), Token::Address(treasury)),
+            (ens::namehash("empty.mydao.eth"), Token::Address(Address::zero())),
+            (ens::namehash(&ens::reverse_address(treasury)), Token::String("treasury.mydao.eth".to_string())),
+        ];
+        Mock::given(body_string_contains("eth_call"))
+            .respond_with(move |request: &Request| {
+                let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+                let data = body["params"][0]["data"].as_str().unwrap_or_default().to_string();
+                let to = body["params"][0]["to"].as_str().unwrap_or_default().to_lowercase();
+                let node = answers.iter().find(|(node, _)| data.contains(&hex::encode(node)));
+                let token = match node {
+                    None => Token::Address(Address::zero()),
+                    // The registry names the resolver, the resolver the answer
+                    Some(_) if to == format!("{:?}", ens::ENS_ADDRESS) => Token::Address(resolver),
+                    Some((_, answer)) => answer.clone(),
+                };
+                reply(format!("0x{}", hex::encode(encode(&[token]))).into())
+            })
+            .mount(server)
+            .await;
+    }
+
+    #[test]
+    fn test_is_ens_name() {
+        assert!(is_ens_name("treasury.mydao.eth"));
+        assert!(is_ens_name(" vitalik.eth "));
+        assert!(!is_ens_name(TREASURY));
+        assert!(!is_ens_name("treasury"));
+        assert!(!is_ens_name("treasury..eth"));
+        assert!(!is_ens_name("not an.eth"));
+    }
+
+    #[tokio::test]
+    async fn test_resolve_and_display() {
+        let server = MockServer::start().await;
+        mock_ens(&server, true).await;
+        let resolver = EnsResolver::new(test_provider(&server.uri()));
+        let treasury = Address::from_str(TREASURY).unwrap();
+
+        assert_eq!(resolver.resolve("treasury.mydao.eth").await.unwrap(), treasury);
+        let err = resolver.resolve("empty.mydao.eth").await.unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(&EnsError::ZeroAddress("empty.mydao.eth".to_string())));
+        let err = resolver.resolve("unknown.eth").await.unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(&EnsError::NoResolver("unknown.eth".to_string())));
+
+        // From the reverse record, checked against the forward one, then cached
+        let fresh = EnsResolver::new(test_provider(&server.uri()));
+        assert_eq!(fresh.display(treasury).await, "treasury.mydao.eth (0x2c75…5c23)");
+        let calls = server.received_requests().await.unwrap().len();
+        assert_eq!(fresh.display(treasury).await, "treasury.mydao.eth (0x2c75…5c23)");
+        let nameless = Address::repeat_byte(0x11);
+        assert_eq!(fresh.display(nameless).await, format!("{:?}", nameless));
+        assert_eq!(fresh.display(nameless).await, format!("{:?}", nameless));
+        // Only the first lookup of the nameless address asked the node
+        assert_eq!(server.received_requests().await.unwrap().len(), calls + 1);
+    }
+
+    #[tokio::test]
+    async fn test_no_registry() {
+        let server = MockServer::start().await;
+        mock_ens(&server, false).await;
+        let resolver = EnsResolver::new(test_provider(&server.uri()));
+        let err = resolver.resolve("treasury.mydao.eth").await.unwrap_err();
+        assert_eq!(
+            err.downcast_ref(),
+            Some(&EnsError::NoRegistry { name: "treasury.mydao.eth".to_string(), chain_id: 42161 })
+        );
+        assert_eq!(resolver.display(Address::from_str(TREASURY).unwrap()).await, TREASURY);
+    }
+
+    #[tokio::test]
+    async fn test_resolve_config() {
+        let server = MockServer::start().await;
+        mock_ens(&server, true).await;
+        let mut config = Config::from_toml_str(&format!(
+            "[safe]\naddress = \"treasury.mydao.eth\"\nrpc_url = \"{}\"\n\n[[safe.accounts]]\naddress = \"{}\"\n",
+            server.uri(),
+            "0x70997970c51812dc3a010c7d01b50e0d17dc79c8"
+        ))
+        .unwrap();
+        assert!(config.safe.address().unwrap_err().to_string().contains("has not been resolved"));
+        resolve_config(&mut config).await.unwrap();
+        assert_eq!(config.safe.address().unwrap(), Address::from_str(TREASURY).unwrap());
+        assert_eq!(config.safe.address.as_deref(), Some("treasury.mydao.eth"));
+
+        // An account resolving to the Safe itself is caught once resolved
+        config.safe.accounts.push(AccountConfig {
+            address: "treasury.mydao.eth".to_string(),
+            resolved: None,
+            min_balance_eth: None,
+        });
+        config.validate().unwrap();
+        let err = resolve_config(&mut config).await.unwrap_err();
+        assert!(err.to_string().contains("already monitored"), "{}", err);
+
+        config.safe.accounts.pop();
+        config.safe.address = Some("empty.mydao.eth".to_string());
+        config.safe.resolved_address = None;
+        let err = resolve_config(&mut config).await.unwrap_err();
+        assert!(format!("{:#}", err).contains("resolves to the zero address"), "{:#}", err);
+    }
+}
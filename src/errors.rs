@@ -0,0 +1,150 @@
+//! Classification of errors into alerting categories.
+//!
+//! Each failed cycle is counted under one [`ErrorCategory`] in `/status` and raises an
+//! `error_raised` event, which the notifier deduplicates per category.
+
+use serde::Serialize;
+use std::fmt;
+
+use crate::agents::cross_chain_router::CrossChainError;
+use crate::agents::defi_optimizer::DefiError;
+use crate::agents::route_status::RouteStatusError;
+use crate::agents::safe_manager::SafeError;
+use crate::config::ConfigError;
+use crate::kill_switch::KillSwitchError;
+use crate::rpc::RpcError;
+use crate::scheduler::{CycleError, FailureClass};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCategory {
+    /// The Ethereum node could not be reached or answered with an error.
+    RpcTransport,
+    /// The Ethereum node asked us to slow down.
+    RpcRateLimit,
+    /// The pool data source failed or returned nothing usable.
+    DefiApi,
+    /// A bridge provider failed.
+    BridgeApi,
+    /// A check refused to go ahead: thresholds, amounts, unknown chains, configuration.
+    Validation,
+    /// Anything else.
+    Internal,
+}
+
+impl fmt::Display for ErrorCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ErrorCategory::RpcTransport => "rpc_transport",
+            ErrorCategory::RpcRateLimit => "rpc_rate_limit",
+            ErrorCategory::DefiApi => "defi_api",
+            ErrorCategory::BridgeApi => "bridge_api",
+            ErrorCategory::Validation => "validation",
+            ErrorCategory::Internal => "internal",
+        })
+    }
+}
+
+impl ErrorCategory {
+    /// Classifies `err` by the first error in its chain that belongs to a known type.
+    pub fn of(err: &anyhow::Error) -> Self {
+        let category = err.chain().find_map(|cause| {
+            if let Some(e) = cause.downcast_ref::<SafeError>() {
+                return Some(match e {
+                    SafeError::ProviderError(_)
+                    | SafeError::Timeout { .. }
+                    | SafeError::GasEstimationFailed(_)
+                    | SafeError::TransactionServiceUnavailable(_)
+                    | SafeError::HistoricalDataUnavailable { .. } => ErrorCategory::RpcTransport,
+                    SafeError::TransactionFailed(_)
+                    | SafeError::SigningFailed(_)
+                    | SafeError::SignerUnavailable(_)
+                    | SafeError::NotConfirmed { .. } => ErrorCategory::Internal,
+                    _ => ErrorCategory::Validation,
+                });
+            }
+            if cause.is::<DefiError>() {
+                return Some(ErrorCategory::DefiApi);
+            }
+            if let Some(e) = cause.downcast_ref::<CrossChainError>() {
+                return Some(match e {
+                    CrossChainError::BridgeError(_) => ErrorCategory::BridgeApi,
+                    _ => ErrorCategory::Validation,
+                });
+            }
+            if let Some(e) = cause.downcast_ref::<RouteStatusError>() {
+                return Some(match e {
+                    RouteStatusError::RequestFailed { .. } => ErrorCategory::BridgeApi,
+                    _ => ErrorCategory::Validation,
+                });
+            }
+            if let Some(e) = cause.downcast_ref::<RpcError>() {
+                return Some(match e {
+                    RpcError::ChainIdMismatch { .. } => ErrorCategory::RpcTransport,
+                    _ => ErrorCategory::Validation,
+                });
+            }
+            if let Some(e) = cause.downcast_ref::<CycleError>() {
+                let phase = match e {
+                    CycleError::PhaseTimedOut { phase, .. } => Some(*phase),
+                    CycleError::TimedOut { phases, .. } => phases.last().copied(),
+                };
+                return Some(match phase {
+                    Some("balance") => ErrorCategory::RpcTransport,
+                    Some("pools") => ErrorCategory::DefiApi,
+                    _ => ErrorCategory::Internal,
+                });
+            }
+            if cause.is::<ConfigError>() || cause.is::<KillSwitchError>() {
+                return Some(ErrorCategory::Validation);
+            }
+            if cause.is::<ethers::providers::ProviderError>() {
+                return Some(ErrorCategory::RpcTransport);
+            }
+            None
+        });
+        match category {
+            // Rate limiting shows up as a transport error with a telling message
+            Some(ErrorCategory::RpcTransport) | None if FailureClass::of(err) == FailureClass::RateLimited => {
+                ErrorCategory::RpcRateLimit
+            }
+            Some(category) => category,
+            None => ErrorCategory::Internal,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::{anyhow, Context};
+    use std::time::Duration;
+
+    #[test]
+    fn test_classification() {
+        let balance = Err::<(), _>(SafeError::ProviderError("connection refused".into()))
+            .context("Failed to get balance")
+            .unwrap_err();
+        assert_eq!(ErrorCategory::of(&balance), ErrorCategory::RpcTransport);
+
+        let limited = anyhow::Error::new(SafeError::ProviderError("429 Too Many Requests".into()));
+        assert_eq!(ErrorCategory::of(&limited), ErrorCategory::RpcRateLimit);
+
+        let timeout = SafeError::Timeout { operation: "eth_getBalance".into(), after: Duration::from_secs(10) };
+        assert_eq!(ErrorCategory::of(&anyhow::Error::new(timeout)), ErrorCategory::RpcTransport);
+
+        let pools = anyhow::Error::new(DefiError::ApiError("timeout".into())).context("Failed to fetch pools");
+        assert_eq!(ErrorCategory::of(&pools), ErrorCategory::DefiApi);
+
+        let bridge = anyhow::Error::new(CrossChainError::BridgeError("reverted".into()));
+        assert_eq!(ErrorCategory::of(&bridge), ErrorCategory::BridgeApi);
+
+        let amount = anyhow::Error::new(CrossChainError::AmountTooLow { amount: 0.01, minimum: 0.1 });
+        assert_eq!(ErrorCategory::of(&amount), ErrorCategory::Validation);
+
+        let hung = anyhow::Error::new(CycleError::TimedOut { phases: vec!["cycle", "pools"], after: Duration::ZERO });
+        assert_eq!(ErrorCategory::of(&hung), ErrorCategory::DefiApi);
+
+        assert_eq!(ErrorCategory::of(&anyhow!("something odd")), ErrorCategory::Internal);
+    }
+}
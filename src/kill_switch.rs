@@ -0,0 +1,203 @@
+//! Pauses every fund-moving action while monitoring carries on.
+//!
+//! The switch is on while a file exists at `kill_switch.path`, while `kill_switch.paused`
+//! (`PAUSED`) is set, or after `POST /admin/pause`. Admin pauses create the file when a
+//! path is configured, so they survive restarts; otherwise they last until the process
+//! exits or `POST /admin/resume`.
+
+use anyhow::{Context, Result};
+use log::{info, warn};
+use serde::Serialize;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use thiserror::Error;
+
+use crate::config::KillSwitchConfig;
+use crate::notifier::{AgentEvent, EventSender};
+
+#[derive(Error, Debug)]
+#[non_exhaustive]
+pub enum KillSwitchError {
+    #[error("Fund movement is paused ({0})")]
+    Paused(PauseSource),
+    #[error("Paused by kill_switch.paused (PAUSED); unset it and restart to resume")]
+    PinnedByConfig,
+}
+
+/// What turned the switch on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PauseSource {
+    Config,
+    File,
+    Admin,
+}
+
+impl fmt::Display for PauseSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            PauseSource::Config => "kill_switch.paused",
+            PauseSource::File => "kill switch file",
+            PauseSource::Admin => "admin pause",
+        })
+    }
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    path: Option<PathBuf>,
+    pinned: bool,
+    admin: AtomicBool,
+    /// Whether the last check found the switch on, to notify on changes only.
+    was_paused: AtomicBool,
+    events: Mutex<EventSender>,
+}
+
+/// Cloneable handle shared by everything that moves funds.
+#[derive(Debug, Clone, Default)]
+pub struct KillSwitch {
+    inner: Arc<Inner>,
+}
+
+impl KillSwitch {
+    pub fn from_config(config: &KillSwitchConfig) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                path: config.path.clone(),
+                pinned: config.paused,
+                ..Inner::default()
+            }),
+        }
+    }
+
+    /// Pause and resume events go to `events`.
+    pub fn set_events(&self, events: EventSender) {
+        *self.inner.events.lock().unwrap_or_else(|e| e.into_inner()) = events;
+    }
+
+    /// What keeps the switch on, without notifying.
+    pub fn source(&self) -> Option<PauseSource> {
+        if self.inner.pinned {
+            Some(PauseSource::Config)
+        } else if self.inner.path.as_ref().is_some_and(|path| path.exists()) {
+            Some(PauseSource::File)
+        } else if self.inner.admin.load(Ordering::SeqCst) {
+            Some(PauseSource::Admin)
+        } else {
+            None
+        }
+    }
+
+    /// Checks the switch before moving funds. The first check after it flips emits a
+    /// `Paused` or `Resumed` event.
+    pub fn check(&self) -> Option<PauseSource> {
+        let source = self.source();
+        let was_paused = self.inner.was_paused.swap(source.is_some(), Ordering::SeqCst);
+        let events = self.inner.events.lock().unwrap_or_else(|e| e.into_inner());
+        match (was_paused, source) {
+            (false, Some(source)) => {
+                warn!("Kill switch on ({}): fund movement paused, monitoring continues", source);
+                events.emit(AgentEvent::Paused { source: source.to_string() });
+            }
+            (true, None) => {
+                warn!("Kill switch off: fund movement resumed");
+                events.emit(AgentEvent::Resumed);
+            }
+            _ => {}
+        }
+        source
+    }
+
+    /// Errors while the switch is on.
+    pub fn ensure_running(&self) -> Result<()> {
+        match self.check() {
+            Some(source) => Err(KillSwitchError::Paused(source).into()),
+            None => Ok(()),
+        }
+    }
+
+    pub fn pause(&self) -> Result<()> {
+        match &self.inner.path {
+            Some(path) => {
+                std::fs::write(path, b"paused via /admin/pause\n")
+                    .with_context(|| format!("Failed to create kill switch file {}", path.display()))?;
+            }
+            None => self.inner.admin.store(true, Ordering::SeqCst),
+        }
+        info!("Kill switch turned on by admin request");
+        self.check();
+        Ok(())
+    }
+
+    pub fn resume(&self) -> Result<()> {
+        if self.inner.pinned {
+            return Err(KillSwitchError::PinnedByConfig.into());
+        }
+        if let Some(path) = self.inner.path.as_ref().filter(|path| path.exists()) {
+            std::fs::remove_file(path)
+                .with_context(|| format!("Failed to remove kill switch file {}", path.display()))?;
+        }
+        self.inner.admin.store(false, Ordering::SeqCst);
+        info!("Kill switch turned off by admin request");
+        self.check();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("asam-kill-switch-{}-{}", name, std::process::id()))
+    }
+
+    #[test]
+    fn test_file_switch_notifies_once_per_change() {
+        let path = temp_path("file");
+        let _ = std::fs::remove_file(&path);
+        let switch = KillSwitch::from_config(&KillSwitchConfig { path: Some(path.clone()), paused: false });
+        let (events, mut rx) = EventSender::channel(8);
+        switch.set_events(events);
+
+        assert_eq!(switch.check(), None);
+        std::fs::write(&path, b"").unwrap();
+        assert_eq!(switch.check(), Some(PauseSource::File));
+        assert_eq!(switch.check(), Some(PauseSource::File));
+        assert!(switch.ensure_running().is_err());
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(switch.check(), None);
+        switch.ensure_running().unwrap();
+
+        let kinds: Vec<_> = std::iter::from_fn(|| rx.try_recv().ok()).map(|e| e.kind()).collect();
+        assert_eq!(kinds, vec!["paused", "resumed"]);
+    }
+
+    #[test]
+    fn test_admin_pause_persists_through_file() {
+        let path = temp_path("admin");
+        let _ = std::fs::remove_file(&path);
+        let config = KillSwitchConfig { path: Some(path.clone()), paused: false };
+        KillSwitch::from_config(&config).pause().unwrap();
+
+        // A restarted agent still finds the switch on
+        let restarted = KillSwitch::from_config(&config);
+        assert_eq!(restarted.check(), Some(PauseSource::File));
+        restarted.resume().unwrap();
+        assert!(!path.exists());
+        assert_eq!(restarted.check(), None);
+
+        // Without a path the pause lives in memory
+        let switch = KillSwitch::default();
+        switch.pause().unwrap();
+        assert_eq!(switch.check(), Some(PauseSource::Admin));
+        switch.resume().unwrap();
+        assert_eq!(switch.check(), None);
+
+        let pinned = KillSwitch::from_config(&KillSwitchConfig { path: None, paused: true });
+        assert_eq!(pinned.check(), Some(PauseSource::Config));
+        assert!(pinned.resume().is_err());
+    }
+}
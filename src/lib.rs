@@ -0,0 +1,50 @@
+//! Autonomous Smart Account Manager.
+//!
+//! The agents in [`agents`] can be embedded on their own: [`agents::safe_manager`]
+//! watches and spends from a Safe, [`agents::defi_optimizer`] ranks yield pools and
+//! [`agents::cross_chain_router`] moves funds between chains. A [`Runner`] drives all
+//! of them from a [`config::Config`], as the `asam` binary does.
+
+pub mod agents;
+pub mod approval;
+pub mod audit;
+pub mod backtest;
+pub mod budget;
+pub mod cli;
+pub mod comparison;
+pub mod config;
+pub mod decision_log;
+pub mod degradation;
+pub mod ens;
+pub mod errors;
+pub mod kill_switch;
+pub mod logging;
+pub mod monitor;
+pub mod notifier;
+pub mod portfolio;
+pub mod preflight;
+pub mod prices;
+pub mod protocol_actions;
+pub mod rebalance;
+pub mod recent_actions;
+pub mod reload;
+pub mod report;
+pub mod rpc;
+pub mod scheduler;
+pub mod secret;
+pub mod simulation;
+pub mod status;
+pub mod storage;
+pub mod strategy;
+pub mod supervisor;
+pub mod test_support;
+pub mod units;
+pub mod util;
+pub mod valuation;
+pub mod version;
+pub mod yield_tracker;
+
+pub use agents::defi_optimizer::PoolData;
+pub use agents::safe_manager::SafeTransaction;
+pub use monitor::Runner;
+pub use report::{CycleReport, RouteResult};
@@ -0,0 +1,386 @@
+//! Log output in env_logger style text or JSON.
+//!
+//! JSON mode bridges the `log` macros into `tracing`, so every line carries the fields
+//! of the spans it was emitted in (safe address, cycle number, route id, chains,
+//! amounts). Both formats write through [`RedactingWriter`], which replaces configured
+//! secrets before anything reaches stderr.
+//!
+//! With `log.file_path` set, the same output also goes to a [`RotatingFile`]. File
+//! writes happen on a background thread, so a slow disk never holds up the loop.
+
+use anyhow::{Context, Result};
+use std::borrow::Cow;
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tracing::Subscriber;
+use tracing_appender::non_blocking::{NonBlocking, WorkerGuard};
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::EnvFilter;
+
+use crate::config::{LogConfig, LogFormat};
+use crate::secret::REDACTED;
+
+/// Replaces known secret values in log output.
+#[derive(Debug, Default)]
+pub struct Redactor {
+    secrets: Vec<String>,
+}
+
+impl Redactor {
+    pub fn new(secrets: impl IntoIterator<Item = String>) -> Self {
+        let mut secrets: Vec<String> = secrets.into_iter().filter(|s| !s.is_empty()).collect();
+        // Longest first so a secret containing another one is replaced whole
+        secrets.sort_by(|a, b| b.len().cmp(&a.len()).then_with(|| a.cmp(b)));
+        secrets.dedup();
+        Self { secrets }
+    }
+
+    pub fn redact<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let mut text = Cow::Borrowed(text);
+        for secret in &self.secrets {
+            if text.contains(secret.as_str()) {
+                text = Cow::Owned(text.replace(secret.as_str(), REDACTED));
+            }
+        }
+        text
+    }
+}
+
+/// Scrubs secrets from each write. Both formatters write a whole record at once.
+pub struct RedactingWriter<W> {
+    inner: W,
+    redactor: Arc<Redactor>,
+}
+
+impl<W: Write> RedactingWriter<W> {
+    pub fn new(inner: W, redactor: Arc<Redactor>) -> Self {
+        Self { inner, redactor }
+    }
+}
+
+impl<W: Write> Write for RedactingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let text = String::from_utf8_lossy(buf);
+        self.inner.write_all(self.redactor.redact(&text).as_bytes())?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Log file that moves to `<path>.1` once it would grow past `max_bytes`, shifting
+/// older files up to `<path>.<max_files - 1>`. Records are written whole, so none
+/// is split across two files.
+pub struct RotatingFile {
+    path: PathBuf,
+    max_bytes: u64,
+    max_files: usize,
+    file: File,
+    written: u64,
+}
+
+impl RotatingFile {
+    /// Opens `path` for appending, creating its directory if needed.
+    pub fn open(path: &Path, max_bytes: u64, max_files: usize) -> Result<Self> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("Cannot create log directory {}", dir.display()))?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Log file {} is not writable; check log.file_path", path.display()))?;
+        let written = file.metadata().map(|m| m.len()).unwrap_or(0);
+        Ok(Self {
+            path: path.to_path_buf(),
+            max_bytes,
+            max_files,
+            file,
+            written,
+        })
+    }
+
+    fn rotated(&self, n: usize) -> PathBuf {
+        let mut name = OsString::from(self.path.as_os_str());
+        name.push(format!(".{}", n));
+        PathBuf::from(name)
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        if self.max_files > 1 {
+            let _ = fs::remove_file(self.rotated(self.max_files - 1));
+            for n in (1..self.max_files - 1).rev() {
+                let from = self.rotated(n);
+                if from.exists() {
+                    fs::rename(&from, self.rotated(n + 1))?;
+                }
+            }
+            fs::rename(&self.path, self.rotated(1))?;
+        }
+        self.file = File::create(&self.path)?;
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.written > 0 && self.written + buf.len() as u64 > self.max_bytes {
+            // Keep appending to the current file rather than dropping the record
+            if let Err(e) = self.rotate() {
+                eprintln!("Failed to rotate log file {}: {}", self.path.display(), e);
+            }
+            self.written = 0;
+        }
+        self.file.write_all(buf)?;
+        self.written += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// Writes each record to stderr and, with file logging on, to the log file queue.
+struct Tee<W> {
+    console: W,
+    file: Option<NonBlocking>,
+}
+
+impl<W: Write> Write for Tee<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.console.write_all(buf)?;
+        if let Some(file) = &mut self.file {
+            file.write_all(buf)?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.console.flush()?;
+        if let Some(file) = &mut self.file {
+            file.flush()?;
+        }
+        Ok(())
+    }
+}
+
+/// Starts the background writer for `log.file_path`, if set.
+fn file_writer(config: &LogConfig) -> Result<Option<(NonBlocking, WorkerGuard)>> {
+    let Some(path) = &config.file_path else {
+        return Ok(None);
+    };
+    let file = RotatingFile::open(path, config.max_size_bytes(), config.max_files)?;
+    Ok(Some(tracing_appender::non_blocking(file)))
+}
+
+/// Keeps the log file writer running; dropping it flushes the records still queued.
+#[must_use = "dropping the guard stops file logging"]
+pub struct LogGuard {
+    _file: Option<WorkerGuard>,
+}
+
+fn text_logger<W: Write + Send + 'static>(filter: &str, writer: RedactingWriter<W>) -> env_logger::Logger {
+    env_logger::Builder::new()
+        .parse_filters(filter)
+        .format_timestamp_secs()
+        .target(env_logger::Target::Pipe(Box::new(writer)))
+        .build()
+}
+
+fn json_subscriber<M>(filter: &str, make_writer: M) -> Result<impl Subscriber + Send + Sync>
+where
+    M: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    let filter = EnvFilter::try_new(filter).with_context(|| format!("Invalid log filter '{}'", filter))?;
+    Ok(tracing_subscriber::fmt()
+        .json()
+        .flatten_event(true)
+        .with_env_filter(filter)
+        .with_writer(make_writer)
+        .finish())
+}
+
+/// Installs the global logger. `filter` uses `RUST_LOG` syntax, e.g. "info" or "asam=debug".
+/// Fails when `log.file_path` cannot be written. Hold on to the returned guard until exit.
+pub fn init(format: LogFormat, filter: &str, redactor: Redactor, config: &LogConfig) -> Result<LogGuard> {
+    let redactor = Arc::new(redactor);
+    let (file, guard) = file_writer(config)?.unzip();
+    match format {
+        LogFormat::Text => {
+            let tee = Tee { console: io::stderr(), file };
+            let logger = text_logger(filter, RedactingWriter::new(tee, redactor));
+            log::set_max_level(logger.filter());
+            log::set_boxed_logger(Box::new(logger)).context("Logger already initialized")?;
+        }
+        LogFormat::Json => {
+            let make_writer = move || {
+                let tee = Tee { console: io::stderr(), file: file.clone() };
+                RedactingWriter::new(tee, redactor.clone())
+            };
+            // Also routes `log` records into tracing
+            json_subscriber(filter, make_writer)?
+                .try_init()
+                .context("Logger already initialized")?;
+        }
+    }
+    Ok(LogGuard { _file: guard })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use log::Log;
+    use std::sync::Mutex;
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Buffer {
+        fn contents(&self) -> String {
+            String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+        }
+    }
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn capture_json(redactor: Redactor, emit: impl FnOnce()) -> String {
+        let buffer = Buffer::default();
+        let redactor = Arc::new(redactor);
+        let writer = buffer.clone();
+        let subscriber =
+            json_subscriber("debug", move || RedactingWriter::new(writer.clone(), redactor.clone())).unwrap();
+        tracing::subscriber::with_default(subscriber, emit);
+        buffer.contents()
+    }
+
+    #[test]
+    fn test_json_output_has_fields() {
+        let output = capture_json(Redactor::default(), || {
+            let cycle = tracing::info_span!("cycle", cycle = 7, safe = "0x1111");
+            let _cycle = cycle.enter();
+            let route = tracing::info_span!("route_funds", route_id = "sim-1", target_chain = "Arbitrum", amount = 1.5);
+            let _route = route.enter();
+            tracing::info!("Routing funds");
+        });
+
+        let line: serde_json::Value = serde_json::from_str(output.trim()).unwrap();
+        assert!(line["timestamp"].is_string());
+        assert_eq!(line["level"], "INFO");
+        assert_eq!(line["target"], "asam::logging::tests");
+        assert_eq!(line["message"], "Routing funds");
+        assert_eq!(line["span"]["route_id"], "sim-1");
+        assert_eq!(line["span"]["amount"], 1.5);
+        let spans = line["spans"].as_array().unwrap();
+        assert_eq!(spans[0]["cycle"], 7);
+        assert_eq!(spans[0]["safe"], "0x1111");
+        assert_eq!(spans[1]["target_chain"], "Arbitrum");
+    }
+
+    #[test]
+    fn test_secrets_are_redacted_in_both_formats() {
+        let secrets = || Redactor::new(["sk-live-0123456789".to_string(), "sk-live".to_string()]);
+
+        let output = capture_json(secrets(), || {
+            let span = tracing::info_span!("request", api_key = "sk-live-0123456789");
+            let _span = span.enter();
+            tracing::warn!(url = "https://api/?key=sk-live-0123456789", "Request with sk-live-0123456789 failed");
+        });
+        assert!(!output.contains("sk-live"), "{}", output);
+        assert_eq!(output.matches(REDACTED).count(), 4, "{}", output);
+
+        let buffer = Buffer::default();
+        let logger = text_logger("info", RedactingWriter::new(buffer.clone(), Arc::new(secrets())));
+        logger.log(
+            &log::Record::builder()
+                .args(format_args!("Connecting with key sk-live-0123456789"))
+                .level(log::Level::Info)
+                .target("asam")
+                .build(),
+        );
+        let output = buffer.contents();
+        assert!(output.starts_with('['), "{}", output);
+        assert!(output.contains("INFO"), "{}", output);
+        assert!(output.ends_with("] Connecting with key [REDACTED]\n"), "{}", output);
+    }
+
+    #[test]
+    fn test_logged_secret_fields_are_redacted() {
+        let key = crate::secret::Secret::from("cg-pro-0123456789");
+        // No configured secrets: the type alone keeps the value out
+        let output = capture_json(Redactor::default(), || {
+            let span = tracing::info_span!("prices", api_key = %key);
+            let _span = span.enter();
+            tracing::info!(api_key = ?key, "Fetching prices with {}", key);
+        });
+        assert!(!output.contains("cg-pro"), "{}", output);
+        assert_eq!(output.matches(REDACTED).count(), 4, "{}", output);
+    }
+
+    #[test]
+    fn test_file_rotation_keeps_recent_entries() {
+        let dir = std::env::temp_dir().join(format!("asam-logs-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let path = dir.join("nested").join("asam.log");
+        let file = RotatingFile::open(&path, 4096, 3).unwrap();
+        let (writer, guard) = tracing_appender::non_blocking(file);
+
+        let buffer = Buffer::default();
+        let tee = Tee { console: buffer.clone(), file: Some(writer) };
+        let logger = text_logger("info", RedactingWriter::new(tee, Arc::new(Redactor::default())));
+        for i in 0..500 {
+            logger.log(
+                &log::Record::builder()
+                    .args(format_args!("synthetic entry {:04}", i))
+                    .level(log::Level::Info)
+                    .target("asam")
+                    .build(),
+            );
+        }
+        drop(logger);
+        drop(guard);
+
+        // The active file and two rotated ones; older files were deleted
+        let read = |name: &str| fs::read_to_string(dir.join("nested").join(name)).unwrap();
+        let files = ["asam.log.2", "asam.log.1", "asam.log"].map(read);
+        assert!(!dir.join("nested/asam.log.3").exists());
+        assert!(files.iter().all(|f| !f.is_empty() && f.len() <= 4096));
+        assert!(files[2].trim_end().ends_with("synthetic entry 0499"), "{}", files[2]);
+
+        // Nothing was lost at the switchovers: the kept files hold consecutive entries
+        let numbers: Vec<u32> = files
+            .concat()
+            .lines()
+            .map(|line| line.rsplit(' ').next().unwrap().parse().unwrap())
+            .collect();
+        assert_eq!(*numbers.last().unwrap(), 499);
+        assert!(numbers.windows(2).all(|w| w[1] == w[0] + 1), "{:?}", numbers);
+        assert_eq!(buffer.contents().lines().count(), 500);
+        fs::remove_dir_all(&dir).unwrap();
+
+        // A path below a regular file cannot be created
+        let blocker = std::env::temp_dir().join(format!("asam-log-blocker-{}", std::process::id()));
+        fs::write(&blocker, b"").unwrap();
+        let config = LogConfig { file_path: Some(blocker.join("asam.log")), ..LogConfig::default() };
+        let err = file_writer(&config).unwrap_err();
+        assert!(err.to_string().contains("Cannot create log directory"), "{:#}", err);
+        fs::remove_file(&blocker).unwrap();
+    }
+}
@@ -1,228 +1,321 @@
-mod agents;
-
 use anyhow::{Context, Result};
-use dotenv::dotenv;
-use ethers::core::types::{Address, U256};
-use ethers::providers::{Http, Provider};
-use log::{debug, error, info, warn};
-use std::{env, str::FromStr};
-use tokio::time::{sleep, Duration};
-use agents::{
-    safe_manager::SafeManager,
-    defi_optimizer::DefiOptimizer,
-    cross_chain_router::CrossChainRouter,
+use asam::agents::{cross_chain_router::CrossChainRouter, defi_optimizer::DefiOptimizer, safe_manager::SafeManager};
+use asam::backtest::{self, BacktestOptions, ChartCache, History};
+use asam::cli::{
+    self, BacktestArgs, ChainsArgs, CheckArgs, Cli, Command, ConfigCommand, ConfigInitArgs, PoolsArgs, Probe, ReplayArgs,
+    RouteArgs, SimulateArgs,
 };
+use asam::config::{Config, RunMode, CONFIG_TEMPLATE};
+use asam::decision_log;
+use asam::ens;
+use asam::kill_switch::KillSwitch;
+use asam::logging::{self, Redactor};
+use asam::monitor::{connect, Runner};
+use asam::preflight::{self, PreflightOptions};
+use asam::prices;
+use asam::recent_actions::RecentActions;
+use asam::reload::ConfigSource;
+use asam::scheduler::CycleTrigger;
+use asam::simulation::{self, Scenario};
+use asam::storage;
+use asam::strategy::Strategy;
+use asam::test_support::AnvilFork;
+use asam::units::format_units_prec;
+use clap::Parser;
+use dotenv::dotenv;
+use log::{info, warn};
+use std::env;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::signal::unix::{signal, SignalKind};
+use tokio_util::sync::CancellationToken;
 
-async fn monitor_and_optimize(
-    safe_manager: &SafeManager,
-    defi_optimizer: &DefiOptimizer,
-    cross_chain_router: &CrossChainRouter,
-) -> Result<()> {
-    debug!("Starting monitoring cycle...");
-    
-    // Monitor account balance with enhanced error handling
-    match safe_manager.get_balance().await {
-        Ok(balance) => {
-            let balance_eth = format_eth(balance);
-            info!("Current balance: {:.6} ETH ({} wei)", balance_eth, balance);
-
-            // Check balance threshold with proper error handling
-            match safe_manager.check_balance_threshold().await {
-                Ok(is_below) => {
-                    if is_below {
-                        warn!("Balance is below minimum threshold - initiating optimization process");
-                        debug!("Searching for optimization opportunities...");
-                    } else {
-                        debug!("Balance is within acceptable range");
-                    }
-                }
-                Err(e) => {
-                    error!("Critical balance check failed: {}", e);
-                    error!("Action required: Please fund the account to continue operations");
-                    return Err(e);
+/// Runs the agent until Ctrl+C, with a cycle on every SIGUSR1.
+async fn run_agent(config: Config, source: ConfigSource) -> Result<()> {
+    let mut runner = Runner::new(config)?;
+    runner.set_config_source(source);
+    let shutdown = CancellationToken::new();
+    {
+        let shutdown = shutdown.clone();
+        tokio::spawn(async move {
+            if tokio::signal::ctrl_c().await.is_ok() {
+                info!("Shutdown signal received, stopping ASAM...");
+                shutdown.cancel();
+            }
+        });
+    }
+    let signals = async {
+        match signal(SignalKind::user_defined1()) {
+            Ok(mut usr1) => {
+                while usr1.recv().await.is_some() {
+                    info!("SIGUSR1 received, running a cycle now");
+                    runner.request_cycle(CycleTrigger::Signal);
                 }
             }
+            Err(e) => warn!("Cannot listen for SIGUSR1, signal-triggered cycles are disabled: {}", e),
         }
-        Err(e) => {
-            error!("Failed to get balance: {}", e);
-            error!("Check your node connection and try again");
-            return Err(e);
-        }
+        std::future::pending().await
+    };
+    tokio::select! {
+        result = runner.run(shutdown) => result,
+        () = signals => unreachable!("the signal listener never finishes"),
     }
+}
 
-    // Find best DeFi pool with enhanced validation and logging
-    debug!("Analyzing DeFi opportunities across chains...");
-    match defi_optimizer.get_best_pool().await {
-        Ok(pool) => {
-            let apy = pool.apy.unwrap_or(0.0);
-            
-            if apy > 0.0 && pool.tvl > 0.0 {
-                info!(
-                    "Found optimal pool: {} on {} (APY: {:.2}%, TVL: ${:.2})",
-                    pool.protocol,
-                    pool.chain,
-                    apy,
-                    pool.tvl
-                );
-
-                if pool.chain != "Ethereum" {
-                    info!("Initiating cross-chain optimization to {}", pool.chain);
-                    debug!("Starting bridge transaction simulation");
-                    match cross_chain_router
-                        .route_funds(100.0, "Ethereum", &pool.chain)
-                        .await 
-                    {
-                        Ok(_) => {
-                            info!("Successfully routed funds to {}", pool.chain);
-                            debug!("Bridge transaction completed successfully");
-                        }
-                        Err(e) => {
-                            error!("Cross-chain routing failed: {}", e);
-                            error!("Bridge transaction simulation failed - check network conditions");
-                            return Err(e);
-                        }
-                    }
-                } else {
-                    debug!("Optimal pool is on Ethereum - no bridge required");
-                }
-            } else {
-                warn!(
-                    "Skipping pool {} due to insufficient metrics (APY: {:.2}%, TVL: ${:.2})",
-                    pool.protocol,
-                    apy,
-                    pool.tvl
-                );
-                debug!("Pool metrics below threshold - continuing search");
+async fn print_balance(config: Config) -> Result<()> {
+    let (provider, _) = connect(&config).await?;
+    let mut safe_manager = SafeManager::from_config(&config.safe, provider.clone())
+        .context("Failed to initialize SafeManager")?;
+    safe_manager.set_expected_chain_id(config.home_chain_id());
+    safe_manager.init().await?;
+    if let Some(prices) = prices::from_config(&config.prices, Some(provider))? {
+        safe_manager.set_prices(prices);
+    }
+    let balance = safe_manager.get_balance().await?;
+    println!("Address: {:?} ({})", safe_manager.get_address(), safe_manager.deployment_status());
+    println!("ETH:     {} ({} wei)", format_units_prec(balance, 18, 6), balance);
+    match safe_manager.value_usd(balance).await {
+        Some(usd) => println!("USD:     {:.2}", usd),
+        None => println!("USD:     unknown"),
+    }
+    for token in safe_manager.get_all_balances().await? {
+        let amount = token.amount;
+        println!("{:<8} {} ({} base units)", format!("{}:", token.symbol), format_units_prec(amount.raw, amount.decimals, 6), amount.raw);
+    }
+    Ok(())
+}
+
+async fn print_pools(config: Config, args: PoolsArgs) -> Result<()> {
+    let defi_optimizer = DefiOptimizer::from_config(&config.acting_optimizer());
+    let all = defi_optimizer.fetch_all().await?;
+    // The chain filter comes after ranking, so it needs every ranked pool
+    let count = if args.chain.is_some() { usize::MAX } else { args.top };
+    let ranked = defi_optimizer.rank_top(&all, None, count)?.into_iter().cloned().collect();
+    let pools = cli::select_pools(ranked, &args);
+    cli::print_pools(&pools, &mut std::io::stdout())?;
+    if let Some(path) = &args.export {
+        cli::export_pools(&pools, path)?;
+        println!("Exported {} pools to {}", pools.len(), path.display());
+    }
+    Ok(())
+}
+
+async fn print_position(config: Config) -> Result<()> {
+    let store = storage::open_store(&config.storage)?;
+    let portfolio = store.portfolio().await?;
+    cli::print_portfolio(&portfolio, &mut std::io::stdout())
+}
+
+fn run_replay(config: Config, args: ReplayArgs) -> Result<()> {
+    let records = decision_log::read(&args.file)?;
+    let record = match args.at {
+        Some(at) => decision_log::find_at(&records, at)
+            .with_context(|| format!("No decision recorded at or before {}", at))?,
+        None => records.last().context("The decision log is empty")?,
+    };
+    // Decisions only read the config, never the store
+    let strategy = Strategy::from_config(config.acting_strategy(), &config.router, Arc::new(storage::MemoryStore::new()));
+    let replay = decision_log::replay(record, &strategy);
+    cli::print_replay(record, &replay, &mut std::io::stdout())
+}
+
+async fn run_route(config: Config, args: RouteArgs) -> Result<()> {
+    let mode = config.mode_plan()?.router;
+    config.check_live_acknowledged()?;
+    let mut cross_chain_router = CrossChainRouter::from_config(&config.router);
+    cross_chain_router.set_mode(mode);
+    cross_chain_router.set_kill_switch(KillSwitch::from_config(&config.kill_switch));
+    let store = storage::open_store(&config.storage)?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let recent_actions = RecentActions::load(store, config.recent_action_retention(), now).await?;
+    cross_chain_router.set_recent_actions(recent_actions);
+    if args.force {
+        cross_chain_router.route_funds_forced(args.amount, &args.from, &args.to).await?;
+    } else {
+        cross_chain_router.route_funds(args.amount, &args.from, &args.to).await?;
+    }
+    match mode {
+        RunMode::Observe => println!("Observe mode: route {} -> {} was not prepared", args.from, args.to),
+        RunMode::DryRun => println!("Route preview OK: {} tokens {} -> {}", args.amount, args.from, args.to),
+        RunMode::Live => println!("Routed {} tokens {} -> {}", args.amount, args.from, args.to),
+    }
+    Ok(())
+}
+
+async fn print_chains(config: Config, args: ChainsArgs) -> Result<()> {
+    // The same registry the running agent routes with
+    let cross_chain_router = CrossChainRouter::from_config(&config.router);
+    let mut rows = cli::chain_rows(&cross_chain_router, &config);
+    if args.probe {
+        for row in &mut rows {
+            let Some(chain) = config.router.chains.iter().find(|c| c.name == row.name) else {
+                continue;
+            };
+            if row.rpc_configured {
+                row.rpc = Some(Probe::of(&preflight::probe_rpc(&config, chain).await.map(|()| "ok")));
+            }
+            if row.name != asam::portfolio::HOME_CHAIN {
+                row.quote = Some(Probe::of(&preflight::probe_quote(&config, chain).await));
             }
         }
-        Err(e) => {
-            error!("Failed to find optimal pool: {}", e);
-            error!("DeFi optimization process failed - check API connectivity");
-            return Err(e);
-        }
     }
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&rows)?);
+        return Ok(());
+    }
+    cli::print_chains(&rows, &mut std::io::stdout())
+}
 
-    debug!("Monitoring cycle completed successfully");
+async fn run_simulation(config: Config, args: SimulateArgs) -> Result<()> {
+    let fork = match &args.fork {
+        Some(url) => {
+            let fork = AnvilFork::spawn(url).await?;
+            Some(simulation::check_fork(&fork, config.safe.address()?).await?)
+        }
+        None => None,
+    };
+    let summary = match &args.scenario {
+        Some(path) => Some(simulation::simulate(&config, &Scenario::load(path)?)?),
+        None => None,
+    };
+    if args.json {
+        let json = match (&fork, &summary) {
+            (Some(fork), Some(summary)) => serde_json::json!({ "fork": fork, "simulation": summary }),
+            (Some(fork), None) => serde_json::to_value(fork)?,
+            (None, _) => serde_json::to_value(&summary)?,
+        };
+        println!("{}", serde_json::to_string_pretty(&json)?);
+        return Ok(());
+    }
+    if let Some(report) = &fork {
+        cli::print_fork_report(report, &mut std::io::stdout())?;
+    }
+    if let Some(summary) = &summary {
+        cli::print_simulation(summary, &mut std::io::stdout())?;
+    }
     Ok(())
 }
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    // Initialize environment
-    dotenv().ok();
-    
-    // Configure logging with a more explicit setup
-    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("debug"))
-        .format_timestamp_secs()
-        .init();
-    
-    info!("Starting ASAM with enhanced monitoring...");
-    debug!("Initializing environment variables and connections...");
-
-    // Get and validate environment variables
-    let rpc_url = env::var("ETH_RPC_URL")
-        .context("ETH_RPC_URL must be set")?;
-    let account_address_str = env::var("ACCOUNT_ADDRESS")
-        .context("ACCOUNT_ADDRESS must be set")?;
-    let account_address = Address::from_str(&account_address_str)
-        .context("Invalid account address format")?;
-
-    debug!("Environment variables loaded successfully");
-
-    // Configure API timeout with validation
-    let api_timeout = env::var("API_TIMEOUT_SECS")
-        .ok()
-        .and_then(|s| s.parse().ok())
-        .unwrap_or(10);
-    if api_timeout < 5 {
-        warn!("API timeout is set below recommended minimum (5s). Current: {}s", api_timeout);
-    }
-    debug!("API timeout configured: {}s", api_timeout);
-
-    // Initialize provider with timeout
-    let provider = Provider::<Http>::try_from(rpc_url.clone())
-        .context("Failed to initialize provider")?;
-    info!("Successfully connected to Ethereum node at {}", rpc_url);
-
-    // Initialize agents with enhanced error handling
-    debug!("Initializing ASAM components...");
-    let safe_manager = SafeManager::new(account_address, provider.clone())
-        .context("Failed to initialize SafeManager")?;
-    let defi_optimizer = DefiOptimizer::new();
-    let cross_chain_router = CrossChainRouter::new();
-    debug!("All components initialized successfully");
-
-    info!("ASAM initialized successfully");
-    info!("Monitoring address: {}", account_address);
-    info!("API timeout: {}s", api_timeout);
-
-    // Main monitoring loop with enhanced error handling
-    loop {
-        match monitor_and_optimize(&safe_manager, &defi_optimizer, &cross_chain_router).await {
-            Ok(_) => debug!("Monitoring cycle completed successfully"),
-            Err(e) => {
-                error!("Error in monitoring cycle: {}", e);
-                error!("Error details: {:?}", e);
-                error!("Will retry in 60 seconds...");
-            }
+async fn run_backtest(mut config: Config, args: BacktestArgs) -> Result<()> {
+    if let Some(name) = &args.strategy {
+        backtest::select_strategy(&mut config, name)?;
+    }
+    let labels = args.pools.iter().map(|pool| backtest::parse_pool_arg(pool)).collect::<Result<_>>()?;
+    let history = match &args.csv {
+        Some(path) => History::load_csv(path, labels)?,
+        None => {
+            let max_age = if args.refresh { Duration::ZERO } else { Duration::from_secs(24 * 60 * 60) };
+            let cache = ChartCache { dir: args.cache_dir.clone(), max_age };
+            backtest::fetch_llama(&args.yields_url, labels, &cache).await?
         }
+    };
+    let from = args.from.as_deref().map(backtest::parse_date).transpose()?;
+    let to = args.to.as_deref().map(backtest::parse_date).transpose()?;
+    let options =
+        BacktestOptions { balance_eth: args.balance, bridge_fee_eth: args.bridge_fee, swap_cost_pct: args.swap_cost };
+    let report = backtest::backtest(&config, &history.between(from, to)?, &options)?;
+    if let Some(path) = &args.timeline {
+        backtest::export_timeline(&report, path)?;
+    }
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+    cli::print_backtest(&report, &mut std::io::stdout())
+}
 
-        info!("Waiting 60 seconds before next monitoring cycle...");
-        sleep(Duration::from_secs(60)).await;
+async fn run_check(loaded: Result<Config>, args: CheckArgs) -> Result<()> {
+    let mut results = cli::check_config(&loaded);
+
+    if let Ok(config) = &loaded {
+        let options = PreflightOptions {
+            expected_chain_id: env::var("EXPECTED_CHAIN_ID").ok().and_then(|s| s.parse().ok()),
+            send_test_notification: args.notify,
+        };
+        results.extend(preflight::run(config, &options).await);
+    }
+
+    cli::print_checks(&results, &mut std::io::stdout())?;
+    if cli::has_failures(&results) {
+        anyhow::bail!("Preflight checks failed");
     }
+    Ok(())
 }
 
-fn format_eth(wei: U256) -> f64 {
-    let wei_f: f64 = wei.as_u128() as f64;
-    wei_f / 1_000_000_000_000_000_000.0
+fn init_config(args: ConfigInitArgs) -> Result<()> {
+    if args.output.exists() && !args.force {
+        anyhow::bail!(
+            "{} already exists; pass --force to overwrite it",
+            args.output.display()
+        );
+    }
+    std::fs::write(&args.output, CONFIG_TEMPLATE)
+        .with_context(|| format!("Failed to write {}", args.output.display()))?;
+    println!("Wrote configuration template to {}", args.output.display());
+    Ok(())
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::agents::test_utils::{get_test_address, setup_test_env};
-
-    #[tokio::test]
-    async fn test_monitor_and_optimize_integration() {
-        setup_test_env();
-        
-        let provider = Provider::<Http>::try_from("http://localhost:8545")
-            .expect("Failed to create provider");
-        let mut safe_manager = SafeManager::new(get_test_address(), provider.clone())
-            .expect("Failed to create SafeManager");
-        
-        // Set a reasonable minimum balance
-        safe_manager.set_min_balance(U256::from(100_000_000_000_000_u64)); // 0.0001 ETH
-        
-        let mut defi_optimizer = DefiOptimizer::with_mock();
-        defi_optimizer.use_mock = true;
-        let cross_chain_router = CrossChainRouter::new();
-
-        // Since we're testing integration, we only care that it doesn't panic
-        let _ = monitor_and_optimize(&safe_manager, &defi_optimizer, &cross_chain_router).await;
-        assert!(true);
-    }
-
-
-    #[tokio::test]
-    async fn test_low_balance_handling() {
-        setup_test_env();
-        
-        let provider = Provider::<Http>::try_from("http://localhost:8545")
-            .expect("Failed to create provider");
-        let mut safe_manager = SafeManager::new(get_test_address(), provider.clone())
-            .expect("Failed to create SafeManager");
-        
-        // Set a high minimum balance to trigger low balance warning
-        safe_manager.set_min_balance(U256::from(10_000_000_000_000_000_000_u64)); // 10 ETH
-        
-        let defi_optimizer = DefiOptimizer::with_mock();
-        let cross_chain_router = CrossChainRouter::new();
-
-        let result = monitor_and_optimize(&safe_manager, &defi_optimizer, &cross_chain_router).await;
-        assert!(result.is_err());
+/// Loads the config file and environment, then applies command line overrides.
+fn load_config(cli: &Cli) -> Result<Config> {
+    let mut config = Config::load(cli.config.as_deref())?;
+    if cli.dry_run {
+        config.runtime.mode = Some(RunMode::DryRun);
     }
+    if cli.log_level.is_some() {
+        config.runtime.log_level = cli.log_level.clone();
+    }
+    Ok(config)
 }
 
+#[tokio::main]
+async fn main() -> Result<()> {
+    // Initialize environment before parsing so ASAM_CONFIG can come from .env
+    dotenv().ok();
+    let cli = Cli::parse();
 
+    if let Some(Command::Config(ConfigCommand::Init(args))) = cli.command {
+        return init_config(args);
+    }
+    let loaded = load_config(&cli);
+    
+    // Configure logging; --log-level beats RUST_LOG beats the config file
+    let runtime = loaded.as_ref().map(|c| c.runtime.clone()).unwrap_or_default();
+    let filter = cli
+        .log_level
+        .clone()
+        .or_else(|| env::var("RUST_LOG").ok())
+        .or(runtime.log_level)
+        .unwrap_or_else(|| "debug".to_string());
+    let secrets = loaded.as_ref().map(Config::secrets).unwrap_or_default();
+    let log = loaded.as_ref().map(|c| c.log.clone()).unwrap_or_default();
+    let _log_guard = logging::init(runtime.log_format, &filter, Redactor::new(secrets), &log)?;
 
+    let reload_cli = cli.clone();
+    let command = cli.command.unwrap_or(Command::Run);
+    // Offline commands never look at the chain, so their ENS names stay unresolved
+    let loaded = match loaded {
+        Ok(mut config) if !matches!(command, Command::Replay(_) | Command::Simulate(_) | Command::Backtest(_)) => {
+            ens::resolve_config(&mut config).await.map(|()| config)
+        }
+        other => other,
+    };
+    match command {
+        Command::Run => {
+            let source = ConfigSource {
+                path: reload_cli.config.clone(),
+                load: Box::new(move || load_config(&reload_cli)),
+            };
+            run_agent(loaded?, source).await
+        }
+        Command::Balance => print_balance(loaded?).await,
+        Command::Pools(args) => print_pools(loaded?, args).await,
+        Command::Route(args) => run_route(loaded?, args).await,
+        Command::Check(args) => run_check(loaded, args).await,
+        Command::Position => print_position(loaded?).await,
+        Command::Replay(args) => run_replay(loaded?, args),
+        Command::Chains(args) => print_chains(loaded?, args).await,
+        Command::Simulate(args) => run_simulation(loaded?, args).await,
+        Command::Backtest(args) => run_backtest(loaded?, args).await,
+        Command::Config(_) => unreachable!("config subcommands are handled before loading"),
+    }
+}
@@ -0,0 +1,2837 @@
+//! The monitoring loop: runs the scheduled phases, acts on the strategy's decisions
+//! and records what happened in the status snapshot and storage.
+
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use ethers::core::types::{Address, U256};
+use log::{debug, error, info, warn};
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::Arc;
+use tokio::sync::{broadcast, mpsc, watch};
+use tokio::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+use tracing::Instrument;
+
+use crate::agents::{
+    cross_chain_router::{CrossChainRouter, RouteState, RouteTracker},
+    defi_optimizer::{DefiOptimizer, PoolData},
+    route_status::{RouteEvent, RouteStatusPoller, StatusPollerConfig},
+    safe_manager::{SafeError, SafeManager, SafeTransaction},
+    swap::SwapCosts,
+};
+use crate::approval::{ApprovalDecision, ApprovalRequest, Approver};
+use crate::audit::AuditLog;
+use crate::budget::{self, BudgetCheck, GuardChange};
+use crate::comparison::SUMMARY_INTERVAL_SECS;
+use crate::config::{Config, DegradationPolicy, NotifyConfig, Profile, RunMode, SafeExecution, SignerKind};
+use crate::decision_log::{self, DecisionLog, DecisionRecord, GasSnapshot, CANDIDATE_POOLS};
+use crate::degradation::{Degrader, Dependency};
+use crate::errors::ErrorCategory;
+use crate::kill_switch::KillSwitch;
+use crate::notifier::{
+    self, AgentEvent, CompositeNotifier, DeliveryFailures, DiscordNotifier, EmailNotifier, EventSender,
+    RebalanceCooldown, SwappableNotifier, TelegramNotifier, WebhookNotifier,
+};
+use crate::portfolio::{Mismatch, PortfolioState, PositionEntry, HOME_CHAIN, TOKEN};
+use crate::preflight;
+use crate::prices;
+use crate::protocol_actions::{ActionOutcome, ProtocolError, ProtocolRegistry};
+use crate::rebalance::{self, PlanExecutor, PlanStage, RebalancePlan, RouteRequest};
+use crate::recent_actions::RecentActions;
+use crate::reload::{self, ConfigSource, ConfigUpdate, ConfigVersion};
+use crate::report::{ChainBalance, CycleDecision, CycleReport, PhaseTimings, RouteResult, ScoredPool, StrategyDecision};
+use crate::rpc::{FailoverClient, RpcProvider};
+use crate::scheduler::{run_phases, Backoff, CycleError, CycleTrigger, Outcome, Phase, PhaseSchedule};
+use crate::simulation::SimulatedWorld;
+use crate::status::{self, AdminCommand, BalanceStatus, StatusHandle, StatusSnapshot, StatusState};
+use crate::storage::{self, CycleRecord, MemoryStore, Store};
+use crate::strategy::Strategy;
+use crate::supervisor::Supervisor;
+use crate::units::format_eth;
+use crate::util::{unix_now, with_simulated_clock};
+use crate::valuation::{self, PortfolioValuation};
+use crate::version;
+use crate::yield_tracker::YieldTracker;
+
+/// Runs a full cycle: the balance check and pool fetch side by side, then the routing
+/// decision. A dependency that is down fails the cycle or is fallen back on as
+/// `policy` says; the fallbacks taken are listed in the report.
+pub(crate) async fn monitor_and_optimize(
+    safe_manager: &SafeManager,
+    defi_optimizer: &DefiOptimizer,
+    cross_chain_router: &CrossChainRouter,
+    strategy: &Strategy,
+    status: &StatusHandle,
+    events: &EventSender,
+    policy: &DegradationPolicy,
+) -> Result<CycleReport> {
+    debug!("Starting monitoring cycle...");
+    let cycle_start = Instant::now();
+    let degrader = Degrader::new(policy, status, events);
+    let agents = Agents { safe_manager, defi_optimizer, cross_chain_router, degrader: &degrader };
+    // Pools are ranked against the previous cycle's valuation, this one's needs the balances
+    let position_usd = status.snapshot().valuation.and_then(|v| v.position_usd());
+    // The balance check and pool download are independent, so run them side by side
+    let ((balance, balance_time), (pools, pools_time), gas_price) = tokio::join!(
+        run_phase(status, "balance", check_balance_phase(safe_manager, status, events)),
+        run_phase(status, "pools", fetch_pools(defi_optimizer, position_usd)),
+        decision_gas_price(safe_manager, strategy),
+    );
+
+    // A failed balance check fails the cycle; a critical balance only blocks fund movement
+    let (balance, balance_status) = balance?;
+    let pools = pools_or_fallback(pools, agents, position_usd, status)?;
+    let valuation = value_portfolio(safe_manager, status, &degrader).await?;
+    // Without prices for the valuation there is none for the balance either
+    let prices_down = degrader.applied().iter().any(|d| d.dependency == Dependency::Prices);
+    let balance_usd = if prices_down { None } else { safe_manager.value_usd(balance).await };
+    let balances = status.snapshot().balances;
+    let mut warnings = Vec::new();
+    for (chain, entry) in &balances {
+        match entry.status {
+            BalanceStatus::Healthy => {}
+            BalanceStatus::Low => warnings.push(format!("balance on {} below minimum threshold", chain)),
+            BalanceStatus::Critical => warnings.push(format!("balance on {} below critical threshold", chain)),
+            BalanceStatus::Unknown => warnings.push(format!(
+                "balance on {} unknown: {}",
+                chain,
+                entry.error.as_deref().unwrap_or("not read")
+            )),
+        }
+    }
+    for (account, entry) in &status.snapshot().accounts {
+        match entry.status {
+            BalanceStatus::Healthy => {}
+            BalanceStatus::Low => warnings.push(format!("balance of {:?} below minimum threshold", account)),
+            BalanceStatus::Critical => warnings.push(format!("balance of {:?} below critical threshold", account)),
+            BalanceStatus::Unknown => warnings.push(format!("balance of {:?} unknown", account)),
+        }
+    }
+
+    let (best_pool, decision, decision_inputs, route, strategies, decision_time) = match pools {
+        Some(pools) => {
+            let (acted, decision_time) = run_phase(
+                status,
+                "decision",
+                act_on_pools(pools, balance, gas_price, agents, strategy, status, events),
+            )
+            .await;
+            let (pool, decision, mut record) = acted?;
+            record.gas_snapshot =
+                gas_price.map(|gas_price_wei| GasSnapshot { chain: HOME_CHAIN.to_string(), gas_price_wei });
+            strategy.record_decision(&record);
+            (Some(pool), decision, Some(record.inputs), record.route_preview, record.strategies, decision_time)
+        }
+        None => {
+            warn!("Continuing the cycle without pool data");
+            let decision = CycleDecision::Blocked("pool data unavailable".to_string());
+            (None, decision, None, None, Vec::new(), Duration::ZERO)
+        }
+    };
+    let degradations = degrader.applied();
+    for degradation in &degradations {
+        warnings.push(format!(
+            "{} unavailable, fell back to {}: {}",
+            degradation.dependency, degradation.fallback, degradation.detail
+        ));
+    }
+
+    let timings = PhaseTimings {
+        balance_ms: balance_time.as_millis() as u64,
+        pools_ms: pools_time.as_millis() as u64,
+        decision_ms: decision_time.as_millis() as u64,
+        total_ms: cycle_start.elapsed().as_millis() as u64,
+    };
+    debug!(
+        "Cycle timings: balance {}ms, pools {}ms, decision {}ms, total {}ms",
+        timings.balance_ms, timings.pools_ms, timings.decision_ms, timings.total_ms
+    );
+    debug!("Monitoring cycle completed successfully");
+    Ok(CycleReport {
+        // The loop fills in what woke it
+        trigger: CycleTrigger::Interval,
+        balance,
+        balance_status,
+        balance_usd,
+        valuation,
+        balances,
+        best_pool,
+        decision,
+        decision_inputs,
+        route,
+        strategies,
+        timings,
+        warnings,
+        degradations,
+    })
+}
+
+/// Values the balances just read and the recorded positions, when prices are
+/// configured, and counts the assets left unpriced. When the price source is down,
+/// the valuation is what `degradation.prices` falls back on.
+async fn value_portfolio(
+    safe_manager: &SafeManager,
+    status: &StatusHandle,
+    degrader: &Degrader<'_>,
+) -> Result<Option<PortfolioValuation>> {
+    let Some(source) = safe_manager.prices() else {
+        return Ok(None);
+    };
+    let snapshot = status.snapshot();
+    let holdings = valuation::holdings(&snapshot.balances, &snapshot.portfolio);
+    let valued = match valuation::value(&holdings, source, unix_now()).await {
+        Ok(valued) => valued,
+        Err(e) => {
+            let last = snapshot.valuation.map(|v| (v.priced_at, v));
+            let kept = degrader.fall_back(Dependency::Prices, e, last)?;
+            status.update(|s| s.valuation = kept.clone());
+            return Ok(kept);
+        }
+    };
+    degrader.answered(Dependency::Prices);
+    let unpriced = valued.unpriced().count() as u64;
+    status.update(|s| {
+        s.valuation = Some(valued.clone());
+        s.unpriced_assets += unpriced;
+    });
+    Ok(Some(valued))
+}
+
+/// Supplies `amount` of the market's asset from the Safe into `pool`, approving it
+/// first when the allowance is short, in one MultiSend batch. A live supply is
+/// recorded as the position on the pool's chain. A pool no registered builder
+/// covers is left suggest-only.
+pub async fn enter_position(
+    safe_manager: &SafeManager,
+    protocols: &ProtocolRegistry,
+    pool: &PoolData,
+    amount: U256,
+    status: &StatusHandle,
+) -> Result<ActionOutcome> {
+    let Some(builder) = actionable(protocols, pool)? else {
+        return Ok(ActionOutcome::suggest_only(pool));
+    };
+    let calls = protocols.entry_calls(safe_manager.provider(), safe_manager.get_address(), pool, amount).await?;
+    info!("Entering {} on {} through {} with {} calls", pool.protocol, pool.chain, builder, calls.len());
+    let outcome = safe_manager.execute_transaction(protocols.batch(&calls)?).await?;
+    if let Some(result) = outcome.result() {
+        info!("Entered {} on {}: {}", pool.protocol, pool.chain, result);
+    }
+    if safe_manager.mode() == RunMode::Live {
+        status.update(|s| {
+            s.portfolio.entered(&pool.chain, &pool.protocol);
+        });
+    }
+    Ok(ActionOutcome::Executed { builder, calls: calls.len() })
+}
+
+/// Withdraws `amount` of the market's asset from `pool` back to the Safe;
+/// `U256::MAX` withdraws everything. A live withdrawal is recorded on the pool's
+/// chain. A pool no registered builder covers is left suggest-only.
+pub async fn exit_position(
+    safe_manager: &SafeManager,
+    protocols: &ProtocolRegistry,
+    pool: &PoolData,
+    amount: U256,
+    status: &StatusHandle,
+) -> Result<ActionOutcome> {
+    let Some(builder) = actionable(protocols, pool)? else {
+        return Ok(ActionOutcome::suggest_only(pool));
+    };
+    let calls = protocols.exit_calls(safe_manager.get_address(), pool, amount)?;
+    info!("Exiting {} on {} through {}", pool.protocol, pool.chain, builder);
+    let outcome = safe_manager.execute_transaction(protocols.batch(&calls)?).await?;
+    if let Some(result) = outcome.result() {
+        info!("Exited {} on {}: {}", pool.protocol, pool.chain, result);
+    }
+    if safe_manager.mode() == RunMode::Live {
+        status.update(|s| {
+            s.portfolio.exited(&pool.chain);
+        });
+    }
+    Ok(ActionOutcome::Executed { builder, calls: calls.len() })
+}
+
+/// The builder acting on `pool`; `None` when no registered builder covers it.
+fn actionable(protocols: &ProtocolRegistry, pool: &PoolData) -> Result<Option<&'static str>> {
+    let Some(builder) = protocols.builder_for(pool) else {
+        info!("{} on {} is a suggest-only protocol; not acting on it", pool.protocol, pool.chain);
+        return Ok(None);
+    };
+    // The Safe only transacts on the home chain
+    if pool.chain != HOME_CHAIN {
+        return Err(ProtocolError::ForeignChain(pool.chain.clone()).into());
+    }
+    Ok(Some(builder))
+}
+
+/// Gas price for the decision log and the breakeven check; only read while the
+/// strategy needs it.
+async fn decision_gas_price(safe_manager: &SafeManager, strategy: &Strategy) -> Option<U256> {
+    if !strategy.needs_gas_price() {
+        return None;
+    }
+    safe_manager
+        .gas_price()
+        .await
+        .map_err(|e| debug!("No gas price for the decision: {:#}", e))
+        .ok()
+}
+
+/// Runs one phase of a cycle and times it. While the phase runs it is listed in
+/// `active_phases`; within a cycle deadline it gets 90% of the time left, so it fails
+/// with its own error before the whole cycle is abandoned.
+async fn run_phase<T>(
+    status: &StatusHandle,
+    phase: &'static str,
+    future: impl Future<Output = Result<T>>,
+) -> (Result<T>, Duration) {
+    let start = Instant::now();
+    let budget = status
+        .snapshot()
+        .cycle_deadline
+        .map(|deadline| deadline.saturating_duration_since(start) * 9 / 10);
+    status.update(|s| s.active_phases.push(phase));
+    let output = match budget {
+        Some(budget) => match tokio::time::timeout(budget, future).await {
+            Ok(output) => output,
+            Err(_) => Err(CycleError::PhaseTimedOut { phase, after: budget }.into()),
+        },
+        None => future.await,
+    };
+    status.update(|s| {
+        if let Some(i) = s.active_phases.iter().rposition(|p| *p == phase) {
+            s.active_phases.remove(i);
+        }
+    });
+    (output, start.elapsed())
+}
+
+/// Runs the phases of one cycle, abandoning them after `timeout`. The error then
+/// names the phases that were still in progress.
+async fn with_cycle_timeout<T>(
+    status: &StatusHandle,
+    timeout: Duration,
+    cycle: impl Future<Output = Result<T>>,
+) -> Result<T> {
+    status.update(|s| s.cycle_deadline = Some(Instant::now() + timeout));
+    let result = match tokio::time::timeout(timeout, cycle).await {
+        Ok(result) => result,
+        Err(_) => {
+            let phases = status.snapshot().active_phases;
+            Err(CycleError::TimedOut { phases, after: timeout }.into())
+        }
+    };
+    if let Some(e) = result.as_ref().err().and_then(|e| e.downcast_ref::<CycleError>()) {
+        error!("Abandoning cycle: {}", e);
+    }
+    status.update(|s| {
+        s.cycle_deadline = None;
+        s.active_phases.clear();
+    });
+    result
+}
+
+/// Records routes the tracker still has in flight, but that never made it into the
+/// portfolio because their cycle was cut short, as pending and persists them. The
+/// status poller picks them up from there.
+async fn keep_routes_resumable(route_tracker: &RouteTracker, status: &StatusHandle, store: &dyn Store) {
+    let portfolio = status.snapshot().portfolio;
+    for route in route_tracker.in_flight() {
+        if portfolio.pending_routes().any(|id| id == route.route_id) {
+            continue;
+        }
+        warn!(
+            "Route {} was left {:?} by an interrupted cycle; recording it as pending",
+            route.route_id, route.state
+        );
+        status.update(|s| {
+            s.portfolio.route_started(&route, None, unix_now());
+            s.yields.flow(&route.source_chain, -route.amount);
+        });
+        if let Err(e) = store.upsert_route(&route, route.updated_at).await {
+            warn!("Failed to persist route {}: {:#}", route.route_id, e);
+        }
+    }
+}
+
+/// Fetches the balance on the home chain, every watched chain and every other
+/// monitored account concurrently and evaluates each against its own threshold. Only
+/// the home chain can fail the phase, when its endpoint cannot be reached; other
+/// chains and accounts that cannot be read are recorded as unknown.
+async fn check_balance_phase(
+    safe_manager: &SafeManager,
+    status: &StatusHandle,
+    events: &EventSender,
+) -> Result<(U256, BalanceStatus)> {
+    let (home, mut balances, accounts) = tokio::join!(
+        check_home_balance(safe_manager, status, events),
+        safe_manager.watched_balances(),
+        safe_manager.account_balances()
+    );
+
+    let StatusSnapshot { balances: previous, accounts: previous_accounts, .. } = status.snapshot();
+    for (account, balance) in &accounts {
+        let was = previous_accounts.get(account).map_or(BalanceStatus::Unknown, |b| b.status);
+        let Some(wei) = balance.balance else { continue };
+        match balance.status {
+            BalanceStatus::Critical => {
+                let account = safe_manager.display_name(*account).await;
+                error!("CRITICAL: Balance of {} extremely low: {} ETH", account, format_eth(wei))
+            }
+            BalanceStatus::Low => {
+                let account = safe_manager.display_name(*account).await;
+                warn!("Balance of {} is below its minimum threshold: {} ETH", account, format_eth(wei))
+            }
+            _ => {}
+        }
+        if let Some(event) = balance_event(was, balance.status, HOME_CHAIN, *account, wei) {
+            events.emit(event);
+        }
+    }
+    let address = safe_manager.get_address();
+    for (chain, balance) in &balances {
+        let was = previous.get(chain).map_or(BalanceStatus::Unknown, |b| b.status);
+        let Some(wei) = balance.balance else { continue };
+        match balance.status {
+            BalanceStatus::Critical => error!("CRITICAL: Balance on {} extremely low: {} ETH", chain, format_eth(wei)),
+            BalanceStatus::Low => warn!("Balance on {} is below the minimum threshold: {} ETH", chain, format_eth(wei)),
+            _ => {}
+        }
+        if let Some(event) = balance_event(was, balance.status, chain, address, wei) {
+            events.emit(event);
+        }
+    }
+
+    let min_balance = safe_manager.min_balance();
+    let home_balance = match &home {
+        Ok((balance, status)) => ChainBalance { status: *status, ..ChainBalance::observed(*balance, min_balance) },
+        Err(e) => ChainBalance::unknown(min_balance, format!("{:#}", e)),
+    };
+    balances.insert(HOME_CHAIN.to_string(), home_balance);
+    if balances.len() > 1 {
+        let summary: Vec<String> = balances
+            .iter()
+            .map(|(chain, b)| match b.balance {
+                Some(wei) => format!("{} {} ETH ({:?})", chain, format_eth(wei), b.status),
+                None => format!("{} unknown", chain),
+            })
+            .collect();
+        info!("Balances: {}", summary.join(", "));
+    }
+    safe_manager.publish_increases(&previous, &balances);
+    status.update(|s| {
+        s.balances = balances;
+        s.accounts = accounts;
+    });
+    home
+}
+
+/// Fetches the home chain balance and evaluates the thresholds. Errors only when the
+/// balance cannot be read.
+async fn check_home_balance(
+    safe_manager: &SafeManager,
+    status: &StatusHandle,
+    events: &EventSender,
+) -> Result<(U256, BalanceStatus)> {
+    // Monitor account balance with enhanced error handling
+    match safe_manager.get_balance().await {
+        Ok(balance) => {
+            info!("Current balance: {} ETH ({} wei)", format_eth(balance), balance);
+            status.update(|s| {
+                s.rpc_reachable = true;
+                s.balance = Some(balance);
+                // Nothing can stand in for the balance, so the RPC has no fallback
+                s.dependencies.answered(Dependency::Rpc, unix_now());
+            });
+
+            let balance_status = safe_manager.update_balance_status(balance);
+            let mut previous = BalanceStatus::Unknown;
+            status.update(|s| {
+                previous = s.balance_status;
+                s.balance_status = balance_status;
+            });
+            let funding_request = match balance_status {
+                BalanceStatus::Critical => match safe_manager.funding_request_for(balance).await {
+                    Ok(uri) => Some(uri),
+                    Err(e) => {
+                        warn!("Could not build the funding request: {:#}", e);
+                        None
+                    }
+                },
+                _ => None,
+            };
+            if let Some(mut event) =
+                balance_event(previous, balance_status, HOME_CHAIN, safe_manager.get_address(), balance)
+            {
+                if let AgentEvent::CriticalBalance { funding_request: request, .. } = &mut event {
+                    request.clone_from(&funding_request);
+                }
+                events.emit(event);
+            }
+            match balance_status {
+                BalanceStatus::Critical => {
+                    error!("CRITICAL: Balance on {} below the critical threshold - fund movement is blocked", HOME_CHAIN);
+                    if safe_manager.tops_up() {
+                        if let Err(e) = safe_manager.top_up(balance).await {
+                            error!("Topping up the Safe failed: {:#}", e);
+                        }
+                    } else {
+                        let shortfall = format_eth(safe_manager.funding_shortfall(balance));
+                        match &funding_request {
+                            Some(uri) => error!("Action required: fund the account with {} ETH: {}", shortfall, uri),
+                            None => error!("Action required: fund the account with {} ETH", shortfall),
+                        }
+                    }
+                }
+                BalanceStatus::Low if previous != BalanceStatus::Low => {
+                    warn!("Balance on {} is below minimum threshold - initiating optimization process", HOME_CHAIN);
+                    debug!("Searching for optimization opportunities...");
+                }
+                BalanceStatus::Low => debug!("Balance on {} still below minimum threshold", HOME_CHAIN),
+                _ => {
+                    debug!("Balance is within acceptable range");
+                    if let Some(left) = safe_manager.estimated_depletion() {
+                        info!(
+                            "At current burn rate, balance drops below minimum in ~{:.1} hours",
+                            left.as_secs_f64() / 3600.0
+                        );
+                    }
+                }
+            }
+            Ok((balance, balance_status))
+        }
+        Err(e) => {
+            status.update(|s| {
+                s.rpc_reachable = false;
+                s.dependencies.failed(Dependency::Rpc, unix_now(), format!("{:#}", e), None);
+            });
+            error!("Failed to get balance on {}: {}", HOME_CHAIN, e);
+            error!("Check your node connection and try again");
+            Err(e)
+        }
+    }
+}
+
+/// Event to notify about when the balance status changes, if any.
+fn balance_event(
+    previous: BalanceStatus,
+    current: BalanceStatus,
+    chain: &str,
+    address: Address,
+    balance_wei: U256,
+) -> Option<AgentEvent> {
+    let chain = chain.to_string();
+    match (previous, current) {
+        (p, c) if p == c => None,
+        (_, BalanceStatus::Critical) => {
+            Some(AgentEvent::CriticalBalance { chain, address, balance_wei, funding_request: None })
+        }
+        (BalanceStatus::Healthy | BalanceStatus::Unknown, BalanceStatus::Low) => {
+            Some(AgentEvent::BelowMinimum { chain, address, balance_wei })
+        }
+        (BalanceStatus::Low | BalanceStatus::Critical, BalanceStatus::Healthy) => {
+            Some(AgentEvent::Recovered { chain, address, balance_wei })
+        }
+        _ => None,
+    }
+}
+
+/// Refreshes pools and routes funds towards the best one when it lives on another chain.
+async fn optimize_phase(agents: Agents<'_>, strategy: &Strategy, status: &StatusHandle, events: &EventSender) -> Result<()> {
+    let position_usd = status.snapshot().valuation.and_then(|v| v.position_usd());
+    let (pools, gas_price) = tokio::join!(
+        run_phase(status, "pools", fetch_pools(agents.defi_optimizer, position_usd)),
+        decision_gas_price(agents.safe_manager, strategy),
+    );
+    let Some(pools) = pools_or_fallback(pools.0, agents, position_usd, status)? else {
+        warn!("Skipping the decision without pool data");
+        return Ok(());
+    };
+    // Pool-only runs size transfers from the last balance check
+    let balance = status.snapshot().balance.unwrap_or_default();
+    let acted = act_on_pools(pools, balance, gas_price, agents, strategy, status, events);
+    run_phase(status, "decision", acted).await.0?;
+    Ok(())
+}
+
+/// One fetch of the pool source, shared by every configured strategy.
+struct PoolSnapshot {
+    /// Every pool the source returned.
+    all: Arc<[PoolData]>,
+    /// Positions in `all` of the pools passing the acting strategy's filters, best first.
+    ranked: Vec<usize>,
+}
+
+/// Fetches and ranks the pools; with the position's USD value, pools too shallow for
+/// it are left out.
+async fn fetch_pools(defi_optimizer: &DefiOptimizer, position_usd: Option<f64>) -> Result<PoolSnapshot> {
+    // Find best DeFi pool with enhanced validation and logging
+    debug!("Analyzing DeFi opportunities across chains...");
+    let all = defi_optimizer.fetch_all().await;
+    let snapshot = all.and_then(|all| {
+        let ranked = defi_optimizer.ranked_indices(&all, position_usd, usize::MAX)?;
+        Ok(PoolSnapshot { all: all.into(), ranked })
+    });
+    snapshot.map_err(|e| {
+        error!("Failed to find optimal pool: {}", e);
+        error!("DeFi optimization process failed - check API connectivity");
+        e
+    })
+}
+
+/// The pools to decide on: the `fetched` ones, kept for the pool API fallbacks, or
+/// when the fetch failed, what `degradation.pool_api` falls back on, ranked again
+/// for this cycle's position. `None` skips the decision.
+fn pools_or_fallback(
+    fetched: Result<PoolSnapshot>,
+    agents: Agents<'_>,
+    position_usd: Option<f64>,
+    status: &StatusHandle,
+) -> Result<Option<PoolSnapshot>> {
+    let error = match fetched {
+        Ok(snapshot) => {
+            agents.degrader.answered(Dependency::PoolApi);
+            status.update(|s| s.last_pools = Some((unix_now(), snapshot.all.clone())));
+            return Ok(Some(snapshot));
+        }
+        Err(e) => e,
+    };
+    let last = status.snapshot().last_pools;
+    let Some(all) = agents.degrader.fall_back(Dependency::PoolApi, error, last)? else {
+        return Ok(None);
+    };
+    let ranked = agents.defi_optimizer.ranked_indices(&all, position_usd, usize::MAX)?;
+    Ok(Some(PoolSnapshot { all, ranked }))
+}
+
+/// What every configured strategy decides on `pools`, the acting one first. Empty
+/// unless the acting strategy has shadows.
+async fn compare_strategies(
+    acting: StrategyDecision,
+    strategy: &Strategy,
+    pools: &[PoolData],
+    portfolio: &PortfolioState,
+    balance: U256,
+) -> Result<Vec<StrategyDecision>> {
+    if strategy.shadows().is_empty() {
+        return Ok(Vec::new());
+    }
+    let mut decisions = vec![acting];
+    for shadow in strategy.shadows() {
+        let decided = shadow.evaluate(pools, portfolio, balance).await?;
+        info!("Strategy {} would decide {:?}: {}", decided.name, decided.decision, decided.reason);
+        decisions.push(decided);
+    }
+    Ok(decisions)
+}
+
+/// The agents a decision acts through, and what stands in for their dependencies
+/// when those are down.
+#[derive(Clone, Copy)]
+struct Agents<'a> {
+    safe_manager: &'a SafeManager,
+    defi_optimizer: &'a DefiOptimizer,
+    cross_chain_router: &'a CrossChainRouter,
+    degrader: &'a Degrader<'a>,
+}
+
+/// Records the best pool and routes funds towards it when the strategy says the move
+/// is worth it, first carrying on any open rebalance plan. A move that withdraws from
+/// a protocol or supplies to one runs as a [`RebalancePlan`]. While the last balance
+/// check found the balance critical, nothing is routed or resumed.
+async fn act_on_pools(
+    snapshot: PoolSnapshot,
+    balance: U256,
+    gas_price: Option<U256>,
+    agents: Agents<'_>,
+    strategy: &Strategy,
+    status: &StatusHandle,
+    events: &EventSender,
+) -> Result<(ScoredPool, CycleDecision, DecisionRecord)> {
+    let Agents { defi_optimizer, cross_chain_router, .. } = agents;
+    let critical = status.snapshot().balance_status == BalanceStatus::Critical;
+    if critical {
+        info!("Not carrying on any rebalance plan while the balance is critical");
+    } else {
+        resume_plan(agents, strategy, status).await;
+    }
+    let pools: Vec<&PoolData> = snapshot.ranked.iter().map(|&i| &snapshot.all[i]).collect();
+    let pool = pools[0].clone();
+    let apy = pool.apy.unwrap_or(0.0);
+    let scored = ScoredPool {
+        score: defi_optimizer.score(&pool),
+        pool,
+    };
+    status.update(|s| s.last_pool = Some(scored.clone()));
+    let (pool, score) = (&scored.pool, scored.score);
+    let portfolio = status.snapshot().portfolio;
+    let position = portfolio.current_chain().to_string();
+
+    let mut observed = strategy.observe(&pools, &portfolio, balance).await?;
+    observed.gas_price_gwei = gas_price.map(|wei| wei.low_u128() as f64 / 1e9);
+    let (mut decision, inputs) = strategy.decide(observed);
+    let decided = decision.clone();
+    let acting = StrategyDecision {
+        name: strategy.name().to_string(),
+        active: true,
+        best_pool: Some(scored.clone()),
+        decision: decided.clone(),
+        reason: decision_log::explain(&decided, &inputs),
+        inputs: Some(inputs.clone()),
+    };
+    let strategies = compare_strategies(acting, strategy, &snapshot.all, &portfolio, balance).await?;
+    let mut summary = None;
+    let mut held_back = false;
+    status.update(|s| {
+        s.strategies.record(&strategies, inputs.observed_at);
+        summary = s
+            .strategies
+            .take_summary(inputs.observed_at, SUMMARY_INTERVAL_SECS)
+            .map(|standings| (standings, s.budget.last_check.clone()));
+        held_back = s.budget.blocks();
+    });
+    if let Some((strategies, budget)) = summary {
+        let since = inputs.observed_at.saturating_sub(SUMMARY_INTERVAL_SECS);
+        let valuation = valuation::change_between(strategy.store(), since, inputs.observed_at).await;
+        events.emit(AgentEvent::DailySummary { strategies, budget, valuation });
+    }
+    // Only live routes move funds, so only they wait for the operator
+    if decision == CycleDecision::RouteInitiated && cross_chain_router.mode() == RunMode::Live && !held_back && !critical
+    {
+        let amount = inputs.amount.unwrap_or_default();
+        let request = ApprovalRequest {
+            amount,
+            source_chain: position.clone(),
+            target_chain: pool.chain.clone(),
+            protocol: pool.protocol.clone(),
+            apy,
+            current_apy: inputs.current_apy,
+            fee: None,
+            swap_cost: strategy.swap_cost(pool, amount).await,
+        };
+        match strategy.approve(&request).await {
+            ApprovalDecision::Approved => {}
+            ApprovalDecision::Declined => decision = CycleDecision::Declined("declined by operator".to_string()),
+            ApprovalDecision::TimedOut => {
+                decision = CycleDecision::Declined("no operator answer before the approval timeout".to_string())
+            }
+        }
+    }
+    let mut action = "hold".to_string();
+    let mut route = None;
+    match decision {
+        CycleDecision::NotWorthMoving if inputs.sizing.is_some() => {
+            info!(
+                "Not routing to {}: {} is below the bridge minimum {}",
+                pool.chain,
+                inputs.amount.unwrap_or_default(),
+                inputs.sizing.as_ref().map(|s| s.minimum.as_f64()).unwrap_or_default()
+            );
+        }
+        CycleDecision::NotWorthMoving => {
+            warn!(
+                "Skipping pool {} due to insufficient metrics (APY: {:.2}%, TVL: ${:.2})",
+                pool.protocol,
+                apy,
+                pool.tvl
+            );
+            debug!("Pool metrics below threshold - continuing search");
+        }
+        CycleDecision::NoAction if pool.chain == position => {
+            info!("Found optimal pool: {} on {} (APY: {:.2}%)", pool.protocol, pool.chain, apy);
+            debug!("Optimal pool is on {} where the funds are - no bridge required", position);
+        }
+        CycleDecision::NoAction => {
+            info!(
+                "{} on {} (APY: {:.2}%) does not beat {} (APY: {:.2}%) by {:.2} points - holding",
+                pool.protocol,
+                pool.chain,
+                apy,
+                position,
+                inputs.current_apy.unwrap_or(0.0),
+                inputs.min_apy_improvement
+            );
+        }
+        CycleDecision::Blocked(ref reason) if reason == "paused" => {
+            info!("[PAUSED] Not routing to {} while the kill switch is on", pool.chain);
+            action = "paused".to_string();
+        }
+        CycleDecision::Blocked(ref reason) => {
+            info!("Not routing to {}: {}", pool.chain, reason);
+        }
+        CycleDecision::Declined(ref reason) => {
+            info!("Not routing to {}: {}", pool.chain, reason);
+            action = format!("operator declined route to {}", pool.chain);
+        }
+        CycleDecision::RouteInitiated if critical => {
+            warn!("Would rebalance to {} on {}, but the balance is critical", pool.protocol, pool.chain);
+            action = format!("critical balance: would route to {}", pool.chain);
+            decision = CycleDecision::Blocked("critical balance".to_string());
+        }
+        CycleDecision::RouteInitiated if held_back => {
+            info!("[BUDGET] Would rebalance to {} on {}; fees outweigh the yield", pool.protocol, pool.chain);
+            action = format!("budget: would route to {}", pool.chain);
+            decision = CycleDecision::Blocked("budget exceeded".to_string());
+        }
+        CycleDecision::RouteInitiated if cross_chain_router.mode() == RunMode::Observe => {
+            info!("[OBSERVE] Would rebalance to {} on {}", pool.protocol, pool.chain);
+            action = format!("observe: would route to {}", pool.chain);
+            decision = CycleDecision::Blocked("observe mode".to_string());
+        }
+        CycleDecision::RouteInitiated => {
+            let amount = inputs.amount.unwrap_or_default();
+            info!(
+                "Initiating cross-chain optimization of {} from {} to {} (APY: {:.2}% vs {:.2}%)",
+                amount,
+                position,
+                pool.chain,
+                apy,
+                inputs.current_apy.unwrap_or(0.0)
+            );
+            events.emit(AgentEvent::RebalanceDecided {
+                protocol: pool.protocol.clone(),
+                chain: pool.chain.clone(),
+                apy,
+                score,
+            });
+            debug!("Starting bridge transaction simulation");
+            let request = RouteRequest { amount, source_chain: position.clone(), target_chain: pool.chain.clone() };
+            let plan = match strategy.protocols() {
+                Some(registry) => {
+                    let held = portfolio.settled(&position).cloned().unwrap_or_else(|| PositionEntry {
+                        chain: position.clone(),
+                        protocol: None,
+                        token: TOKEN.to_string(),
+                        amount: 0.0,
+                        since: inputs.observed_at,
+                        pending_route: None,
+                    });
+                    let plan = RebalancePlan::build(
+                        registry,
+                        agents.safe_manager.get_address(),
+                        &held,
+                        pool,
+                        amount,
+                        inputs.observed_at,
+                    )?;
+                    plan.unwinds_or_enters().then_some(plan)
+                }
+                None => None,
+            };
+            let routed = match plan {
+                Some(mut plan) => {
+                    info!(
+                        "Planned {} as {} exit calls, a route and {} enter calls",
+                        plan.describe(),
+                        plan.exit.len(),
+                        plan.enter.len()
+                    );
+                    strategy.store().save_plan(&plan).await?;
+                    let executor = SafeExecutor { agents, strategy, status, protocol: plan.to_protocol.clone(), apy: pool.apy };
+                    run_phase(status, "route", run_plan(&mut plan, &executor, strategy, status))
+                        .await
+                        .0
+                        .map(|_| plan.route_id)
+                }
+                None => {
+                    let started = start_route(cross_chain_router, strategy, status, &request, &pool.protocol, pool.apy);
+                    run_phase(status, "route", started).await.0
+                }
+            };
+            let routed = match routed {
+                Err(e) if Dependency::of(&e) == Some(Dependency::BridgeApi) => {
+                    agents.degrader.fall_back(Dependency::BridgeApi, e, None::<(u64, Option<String>)>)?
+                }
+                routed => Some(routed.map_err(|e| {
+                    error!("Cross-chain routing failed: {}", e);
+                    error!("Bridge transaction simulation failed - check network conditions");
+                    e
+                })?),
+            };
+            match routed {
+                None => {
+                    info!("Not routing to {}: the bridge API is unavailable", pool.chain);
+                    decision = CycleDecision::Blocked("bridge API unavailable".to_string());
+                }
+                Some(route_id) => {
+                    // Only live routes reach the bridge
+                    if route_id.is_some() {
+                        agents.degrader.answered(Dependency::BridgeApi);
+                    }
+                    info!("Successfully routed funds to {}", pool.chain);
+                    debug!("Bridge transaction completed successfully");
+                    if cross_chain_router.mode() == RunMode::DryRun {
+                        action = format!("dry-run route to {}", pool.chain);
+                    } else {
+                        action = format!("routed to {}", pool.chain);
+                    }
+                    route = Some(RouteResult {
+                        route_id,
+                        amount,
+                        source_chain: position.clone(),
+                        target_chain: pool.chain.clone(),
+                        mode: cross_chain_router.mode(),
+                    });
+                }
+            }
+        }
+    }
+    status.update(|s| s.last_action = Some(action));
+
+    let pool_action = strategy.action_for(pool);
+    let mut reason = decision_log::explain(&decided, &inputs);
+    if pool_action.is_none() {
+        reason.push_str(&format!("; {}", ActionOutcome::suggest_only(pool)));
+    }
+    let snapshot = status.snapshot();
+    let record = DecisionRecord {
+        timestamp: inputs.observed_at,
+        cycle_id: snapshot.cycle,
+        balance,
+        balance_status: snapshot.balance_status,
+        candidate_pools: pools
+            .iter()
+            .take(CANDIDATE_POOLS)
+            .map(|&pool| ScoredPool { score: defi_optimizer.score(pool), pool: pool.clone() })
+            .collect(),
+        current_position: position,
+        gas_snapshot: None,
+        route_preview: route,
+        reason,
+        pool_action: pool_action.map(str::to_string),
+        decision: decided,
+        inputs,
+        strategies,
+    };
+    Ok((scored, decision, record))
+}
+
+/// Starts `request` and records it: the funds leave the source chain for the target,
+/// where they are expected to earn `apy` in `protocol`.
+async fn start_route(
+    cross_chain_router: &CrossChainRouter,
+    strategy: &Strategy,
+    status: &StatusHandle,
+    request: &RouteRequest,
+    protocol: &str,
+    apy: Option<f64>,
+) -> Result<Option<String>> {
+    let route_id = cross_chain_router
+        .route_funds(request.amount, &request.source_chain, &request.target_chain)
+        .await?;
+    if let Some(receipt) = route_id.as_deref().and_then(|id| cross_chain_router.tracker().get(id)) {
+        status.update(|s| {
+            let now = unix_now();
+            s.portfolio.route_started(&receipt, Some(protocol.to_string()), now);
+            s.yields.flow(&receipt.source_chain, -receipt.amount);
+            if let Some(apy) = apy {
+                s.yields.set_projected(&receipt.target_chain, apy);
+            }
+            // Simulated routes settle before route_funds returns
+            if receipt.state == RouteState::Completed {
+                s.portfolio.route_completed(&receipt.route_id, now);
+                s.yields.flow(&receipt.target_chain, receipt.amount);
+            }
+        });
+        if let Err(e) = strategy.route_started(&receipt).await {
+            warn!("Failed to persist route {}: {:#}", receipt.route_id, e);
+        }
+    }
+    Ok(route_id)
+}
+
+/// Runs rebalance plans through the Safe and the router.
+struct SafeExecutor<'a> {
+    agents: Agents<'a>,
+    strategy: &'a Strategy,
+    status: &'a StatusHandle,
+    /// Protocol the plan enters.
+    protocol: String,
+    /// APY of the target pool, when the plan was made this cycle.
+    apy: Option<f64>,
+}
+
+#[async_trait]
+impl PlanExecutor for SafeExecutor<'_> {
+    async fn execute(&self, chain: &str, calls: &[SafeTransaction]) -> Result<()> {
+        // The Safe only transacts on the home chain
+        if chain != HOME_CHAIN {
+            return Err(ProtocolError::ForeignChain(chain.to_string()).into());
+        }
+        let registry = self.strategy.protocols().ok_or_else(|| anyhow!("no protocols registered"))?;
+        self.agents.safe_manager.execute_transaction(registry.batch(calls)?).await.map(|_| ())
+    }
+
+    async fn start_route(&self, route: &RouteRequest) -> Result<Option<String>> {
+        start_route(self.agents.cross_chain_router, self.strategy, self.status, route, &self.protocol, self.apy).await
+    }
+
+    fn route_state(&self, route_id: &str) -> Option<RouteState> {
+        self.agents.cross_chain_router.tracker().get(route_id).map(|receipt| receipt.state)
+    }
+}
+
+/// Advances `plan` and, for live plans, records the withdrawal and the supply in the
+/// portfolio.
+async fn run_plan(
+    plan: &mut RebalancePlan,
+    executor: &SafeExecutor<'_>,
+    strategy: &Strategy,
+    status: &StatusHandle,
+) -> Result<PlanStage> {
+    let from = plan.stage;
+    let advanced = rebalance::advance(plan, executor, strategy.store()).await;
+    if executor.agents.safe_manager.mode() == RunMode::Live {
+        let exited = from == PlanStage::Exit && plan.stage != PlanStage::Exit;
+        status.update(|s| {
+            if exited && !plan.exit.is_empty() {
+                s.portfolio.exited(&plan.route.source_chain);
+            }
+            if plan.stage == PlanStage::Done && !plan.enter.is_empty() {
+                s.portfolio.entered(&plan.route.target_chain, &plan.to_protocol);
+            }
+        });
+    }
+    advanced
+}
+
+/// Carries an open rebalance plan on from its stored stage, e.g. into the pool once
+/// its route has landed. Failures are logged and retried next cycle.
+async fn resume_plan(agents: Agents<'_>, strategy: &Strategy, status: &StatusHandle) {
+    if strategy.protocols().is_none() || agents.safe_manager.mode() == RunMode::Observe {
+        return;
+    }
+    let mut plan = match strategy.store().open_plan().await {
+        Ok(Some(plan)) => plan,
+        Ok(None) => return,
+        Err(e) => {
+            warn!("Failed to load the open rebalance plan: {:#}", e);
+            return;
+        }
+    };
+    let executor = SafeExecutor { agents, strategy, status, protocol: plan.to_protocol.clone(), apy: None };
+    match run_plan(&mut plan, &executor, strategy, status).await {
+        Ok(PlanStage::Done) => info!("Plan {} done: {}", plan.id, plan.describe()),
+        Ok(stage) => debug!("Plan {} waiting at {:?}", plan.id, stage),
+        Err(e) => warn!("Plan {} stopped at {:?}: {:#}", plan.id, plan.stage, e),
+    }
+}
+
+/// Weighs the fees of the last 30 days against the yield captured in them and engages
+/// or releases the budget guard.
+async fn check_budget(store: &dyn Store, status: &StatusHandle, events: &EventSender, max_ratio: f64) {
+    let now = unix_now();
+    let fees = match store.total_fees_since(now.saturating_sub(budget::WINDOW_SECS)).await {
+        Ok(fees) => fees,
+        Err(e) => {
+            warn!("Failed to load fees for the budget check: {:#}", e);
+            return;
+        }
+    };
+    let check = BudgetCheck::compute(fees, &status.snapshot().yields.reports(), now, max_ratio);
+    debug!("Budget: {}", check.describe());
+    let mut change = None;
+    status.update(|s| change = s.budget.record(check.clone()));
+    match change {
+        Some(GuardChange::Engaged) => {
+            warn!("[BUDGET] Holding routes back: {}", check.describe());
+            events.emit(AgentEvent::BudgetExceeded { check });
+        }
+        Some(GuardChange::Released) => {
+            info!("[BUDGET] Routes resume: {}", check.describe());
+            events.emit(AgentEvent::BudgetRecovered { check });
+        }
+        None => {}
+    }
+}
+
+/// Compares the recorded funds on the home chain with the last balance read and adopts
+/// the balance, warning when the two differ by more than `tolerance` ETH.
+fn reconcile_portfolio(status: &StatusHandle, events: &EventSender, tolerance: f64) {
+    let Some(balance) = status.snapshot().balance else {
+        return;
+    };
+    let Ok(observed) = format_eth(balance).parse::<f64>() else {
+        return;
+    };
+    let mut mismatch = None;
+    status.update(|s| {
+        mismatch = s.portfolio.reconcile(HOME_CHAIN, observed, tolerance, unix_now());
+        // Funds that came or went outside our routes adjust the cost basis
+        if let Some(Mismatch { chain, recorded, observed }) = &mismatch {
+            s.yields.flow(chain, observed - recorded);
+        }
+    });
+    if let Some(Mismatch { chain, recorded, observed }) = mismatch {
+        warn!(
+            "Recorded position on {} is {} ETH but the chain shows {} ETH; adopting the on-chain balance",
+            chain, recorded, observed
+        );
+        events.emit(AgentEvent::ReconciliationMismatch { chain, recorded, observed });
+    }
+}
+
+/// Logs how the balance just read compares with a day ago, when the node can say.
+/// Runs after the phases, so a slow lookup never holds up a cycle.
+async fn log_balance_change(safe_manager: &SafeManager, status: &StatusHandle) {
+    let Some(balance) = status.snapshot().balance else { return };
+    let Some(day_ago) = safe_manager.balance_day_ago().await else { return };
+    let change = if balance >= day_ago {
+        format!("+{}", format_eth(balance - day_ago))
+    } else {
+        format!("-{}", format_eth(day_ago - balance))
+    };
+    info!("Balance {} ETH, {} ETH over the last 24 hours", format_eth(balance), change);
+}
+
+/// Logs the transfers into and out of the Safe since the last scan, and sends each
+/// as a notification when `notify` is set. A failed scan is retried over the same
+/// blocks next cycle.
+async fn report_transfers(safe_manager: &SafeManager, events: &EventSender, notify: bool) {
+    let transfers = match safe_manager.watch_transfers().await {
+        Ok(transfers) => transfers,
+        Err(e) => {
+            warn!("Failed to scan for transfers: {:#}", e);
+            return;
+        }
+    };
+    for transfer in transfers {
+        info!("Transfer of {}", transfer.describe());
+        if notify {
+            events.emit(AgentEvent::TransferObserved { transfer });
+        }
+    }
+}
+
+/// Snapshots the position values for yield tracking once the interval has passed.
+async fn snapshot_yields(store: &dyn Store, status: &StatusHandle, interval_secs: u64) {
+    let now = unix_now();
+    let mut snapshots = Vec::new();
+    status.update(|s| {
+        if s.yields.is_due(now, interval_secs) {
+            snapshots = s.yields.snapshot(&s.portfolio, now);
+        }
+    });
+    if snapshots.is_empty() {
+        return;
+    }
+    debug!("Recorded {} yield snapshots", snapshots.len());
+    if let Err(e) = store.record_yield_snapshots(&snapshots).await {
+        warn!("Failed to persist yield snapshots: {:#}", e);
+    }
+}
+
+/// Records the outcome of a batch that ran the balance phase. Storage errors are only logged.
+async fn persist_cycle(store: &dyn Store, status: &StatusHandle, outcome: &Result<()>, ran_pools: bool) {
+    let snapshot = status.snapshot();
+    let action = match outcome {
+        Err(e) => format!("error: {}", e),
+        Ok(()) if ran_pools => snapshot.last_action.clone().unwrap_or_else(|| "hold".to_string()),
+        Ok(()) => "balance check".to_string(),
+    };
+    let pool = snapshot.last_pool.as_ref();
+    let record = CycleRecord {
+        timestamp: unix_now(),
+        balance_wei: snapshot.balance,
+        balance_status: snapshot.balance_status,
+        pool_protocol: pool.map(|p| p.pool.protocol.clone()),
+        pool_chain: pool.map(|p| p.pool.chain.clone()),
+        pool_score: pool.map(|p| p.score),
+        action,
+    };
+    if let Err(e) = store.record_cycle(&record).await {
+        warn!("Failed to record cycle: {:#}", e);
+    }
+}
+
+/// Stores the valuation of the full cycle that just succeeded. Storage errors are only logged.
+async fn persist_valuation(store: &dyn Store, status: &StatusHandle) {
+    let Some(valuation) = status.snapshot().last_report.and_then(|r| r.valuation) else {
+        return;
+    };
+    if let Err(e) = store.record_valuation(&valuation).await {
+        warn!("Failed to record portfolio valuation: {:#}", e);
+    }
+}
+
+/// Counts and alerts a failed batch under its error category. A successful batch
+/// clears each category whose failing phases all ran again.
+fn track_errors(
+    result: &Result<()>,
+    due: &[Phase],
+    failing: &mut BTreeMap<ErrorCategory, Vec<Phase>>,
+    status: &StatusHandle,
+    events: &EventSender,
+) {
+    match result {
+        Err(e) => {
+            let category = ErrorCategory::of(e);
+            error!("Cycle failed with a {} error", category);
+            status.update(|s| *s.error_counts.entry(category).or_default() += 1);
+            failing.insert(category, due.to_vec());
+            events.emit(AgentEvent::ErrorRaised { category, detail: format!("{:#}", e) });
+        }
+        Ok(()) => failing.retain(|&category, phases| {
+            let recovered = phases.iter().all(|phase| due.contains(phase));
+            if recovered {
+                info!("{} errors cleared", category);
+                events.emit(AgentEvent::ErrorCleared { category });
+            }
+            !recovered
+        }),
+    }
+}
+
+fn report_routes_phase(route_tracker: &RouteTracker) {
+    let in_flight = route_tracker.in_flight();
+    if !in_flight.is_empty() {
+        info!("Routes in flight: {}", in_flight.len());
+        for route in &in_flight {
+            debug!(
+                "Route {}: {} -> {} ({:?})",
+                route.route_id, route.source_chain, route.target_chain, route.state
+            );
+        }
+    }
+}
+
+/// Builds the notifiers enabled in `notify`.
+fn build_notifiers(
+    notify: &NotifyConfig,
+    profile: Profile,
+    account_address: Address,
+    failures: &DeliveryFailures,
+) -> CompositeNotifier {
+    let mut notifier = CompositeNotifier::new();
+    if let Some(webhook) = WebhookNotifier::from_config(&notify.webhook) {
+        info!("Webhook notifications enabled");
+        notifier.push(Arc::new(webhook));
+    }
+    if let Some(telegram) = TelegramNotifier::from_config(&notify.telegram) {
+        info!("Telegram notifications enabled (min severity: {:?})", notify.telegram.min_severity);
+        notifier.push(Arc::new(telegram));
+    }
+    if let Some(discord) = DiscordNotifier::from_config(&notify.discord) {
+        info!("Discord notifications enabled (min severity: {:?})", notify.discord.min_severity);
+        notifier.push(Arc::new(discord.with_agent(account_address)));
+    }
+    match EmailNotifier::from_config(&notify.email, profile, failures.clone()) {
+        Ok(Some(email)) => {
+            info!("Email notifications enabled (min severity: {:?})", notify.email.min_severity);
+            notifier.push(Arc::new(email));
+        }
+        Ok(None) => {}
+        Err(e) => error!("Email notifications disabled: {:#}", e),
+    }
+    notifier
+}
+
+/// Pushes the live-applicable settings of a reloaded config into the running components.
+fn apply_config(
+    config: &Config,
+    safe_manager: &mut SafeManager,
+    defi_optimizer: &mut DefiOptimizer,
+    cross_chain_router: &mut CrossChainRouter,
+    notifier: &SwappableNotifier,
+    account_address: Address,
+    failures: &DeliveryFailures,
+) {
+    defi_optimizer.set_config(&config.acting_optimizer());
+    if let Err(e) = safe_manager.set_balance_thresholds(config.safe.min_balance(), config.safe.critical_balance()) {
+        warn!("Keeping the balance thresholds: {:#}", e);
+    }
+    safe_manager.set_spending_limits(config.safe.max_value_per_tx(), config.safe.max_value_per_day());
+    safe_manager.set_daily_gas_budget(config.safe.daily_gas_budget());
+    if let Err(e) = safe_manager.set_recovery_factor(config.safe.balance_recovery_factor) {
+        warn!("Keeping the balance recovery factor: {:#}", e);
+    }
+    if let Err(e) = safe_manager.set_gas_buffer(config.safe.gas_buffer) {
+        warn!("Keeping the gas buffer: {:#}", e);
+    }
+    safe_manager.set_max_gas_limit(Some(config.safe.max_gas_limit.into()));
+    // Validated already, so every target parses
+    safe_manager.set_delegatecalls(config.safe.allow_delegatecall, config.safe.delegatecall_targets().unwrap_or_default());
+    safe_manager.set_allowed_modules(config.safe.allowed_modules().unwrap_or_default());
+    for chain in &config.router.chains {
+        cross_chain_router.set_chain_active(&chain.name, chain.active);
+        safe_manager.set_watched_min_balance(&chain.name, chain.min_balance_wei(&config.safe));
+    }
+    // Validated already, so every address parses
+    let accounts: Vec<(Address, U256)> = config
+        .safe
+        .accounts
+        .iter()
+        .filter_map(|account| Some((account.address().ok()?, account.min_balance_wei(&config.safe))))
+        .collect();
+    for address in safe_manager.accounts().into_iter().skip(1) {
+        if !accounts.iter().any(|(configured, _)| *configured == address) {
+            safe_manager.remove_account(address);
+        }
+    }
+    for (address, min_balance) in accounts {
+        safe_manager.add_account(address);
+        safe_manager.set_account_min_balance(address, min_balance);
+    }
+    notifier.replace(Arc::new(build_notifiers(&config.notify, config.profile, account_address, failures)));
+}
+
+/// Builds the failover provider and checks that every endpoint serves the same chain,
+/// one that belongs to the configured profile.
+pub async fn connect(config: &Config) -> Result<(RpcProvider, Arc<FailoverClient>)> {
+    let (provider, client) = FailoverClient::from_config(&config.safe)
+        .context("Failed to initialize provider")?
+        .into_provider();
+    match client.verify_chain_id().await? {
+        Some(chain_id) => {
+            config.profile.check_chain_id(chain_id.low_u64())?;
+            info!(
+                "Successfully connected to {} RPC endpoint(s) on chain {}",
+                config.safe.rpc_urls()?.len(),
+                chain_id
+            )
+        }
+        None => warn!("No RPC endpoint reachable yet; the chain id is verified once one answers"),
+    }
+    Ok((provider, client))
+}
+
+/// Checks the Safe manager's endpoint serves the configured chain. Only a mismatch
+/// stops the agent; an endpoint not answering yet is checked on first use instead.
+async fn init_chain(safe_manager: &SafeManager) -> Result<()> {
+    match safe_manager.init().await {
+        Err(e) if matches!(e.downcast_ref(), Some(SafeError::ChainIdMismatch { .. })) => Err(e),
+        Err(e) => {
+            warn!("Could not read the chain id yet: {:#}", e);
+            Ok(())
+        }
+        Ok(_) => Ok(()),
+    }
+}
+
+/// Builds a provider for every other active chain with an `rpc_url` and has the Safe
+/// manager watch the balance there. An endpoint serving a different chain than
+/// configured is an error; an unreachable one is verified once it answers.
+async fn watch_chains(config: &Config, safe_manager: &mut SafeManager) -> Result<Vec<(String, Arc<FailoverClient>)>> {
+    let mut clients = Vec::new();
+    for chain in config.router.chains.iter().filter(|c| c.active && c.name != HOME_CHAIN) {
+        let urls = chain.rpc_urls();
+        if urls.is_empty() {
+            continue;
+        }
+        let (provider, client) = FailoverClient::new(
+            &urls,
+            Duration::from_secs(config.safe.rpc_timeout_secs),
+            Duration::from_secs(config.safe.rpc_probe_interval_secs),
+        )
+        .with_context(|| format!("Failed to initialize provider for {}", chain.name))?
+        .into_provider();
+        match client.verify_chain_id().await? {
+            Some(chain_id) if chain_id != chain.chain_id.into() => {
+                return Err(anyhow!(
+                    "RPC endpoint for {} serves chain {} instead of {}",
+                    chain.name,
+                    chain_id,
+                    chain.chain_id
+                ))
+            }
+            Some(_) => {}
+            None => warn!("No RPC endpoint for {} reachable yet; its balance is unknown until one answers", chain.name),
+        }
+        safe_manager.watch_chain(&chain.name, provider, chain.min_balance_wei(&config.safe));
+        clients.push((chain.name.clone(), client));
+    }
+    Ok(clients)
+}
+
+/// The whole agent, as `asam run` drives it, for embedding in another application.
+///
+/// [`Runner::run`] connects, starts the background tasks and runs the monitoring loop
+/// until the given token fires; everything it started stops with it. While it runs,
+/// the host can ask for a cycle, read the status snapshot and subscribe to events
+/// instead of going through the HTTP server.
+pub struct Runner {
+    config: Config,
+    status: StatusHandle,
+    triggers: mpsc::Sender<CycleTrigger>,
+    events: broadcast::Sender<AgentEvent>,
+    /// Number of the last cycle that finished.
+    finished: watch::Sender<u64>,
+    /// Taken by the one run a runner allows.
+    pending: std::sync::Mutex<Option<PendingRun>>,
+}
+
+struct PendingRun {
+    approver: Approver,
+    triggers: mpsc::Receiver<CycleTrigger>,
+    source: Option<ConfigSource>,
+}
+
+impl Runner {
+    /// Checks what can be checked without the network; connecting waits for [`Runner::run`].
+    pub fn new(config: Config) -> Result<Self> {
+        config.safe.address()?;
+        config.mode_plan()?;
+        config.check_live_acknowledged()?;
+        // Before anything slow, so a detached prompt-mode agent fails right away
+        let approver = Approver::from_config(&config.runtime)?;
+        // Requests from the host, the admin endpoints and SIGUSR1; one pending request is enough
+        let (triggers_tx, triggers) = mpsc::channel(1);
+        let (events, _) = broadcast::channel(config.notify.queue_capacity.max(1));
+        Ok(Self {
+            config,
+            status: StatusHandle::new(),
+            triggers: triggers_tx,
+            events,
+            finished: watch::Sender::new(0),
+            pending: std::sync::Mutex::new(Some(PendingRun { approver, triggers, source: None })),
+        })
+    }
+
+    /// Reloads the config from `source` on SIGHUP while running (see [`reload`]).
+    pub fn set_config_source(&mut self, source: ConfigSource) {
+        if let Some(pending) = self.pending.get_mut().unwrap_or_else(|e| e.into_inner()) {
+            pending.source = Some(source);
+        }
+    }
+
+    /// Asks for a full cycle now. False when one is already pending or the loop stopped.
+    pub fn trigger_cycle(&self) -> bool {
+        self.request_cycle(CycleTrigger::Api)
+    }
+
+    /// Asks for a full cycle now, recording `trigger` as the reason.
+    pub fn request_cycle(&self, trigger: CycleTrigger) -> bool {
+        self.triggers.try_send(trigger).is_ok()
+    }
+
+    /// The same snapshot `/status` is built from.
+    pub fn status(&self) -> StatusSnapshot {
+        self.status.snapshot()
+    }
+
+    /// Every event the agent emits from now on, before the notification filters.
+    pub fn events(&self) -> broadcast::Receiver<AgentEvent> {
+        self.events.subscribe()
+    }
+
+    /// Number of the last cycle that finished, successfully or not; it changes once
+    /// the cycle's status, report and events are all published.
+    pub fn finished_cycles(&self) -> watch::Receiver<u64> {
+        self.finished.subscribe()
+    }
+
+    /// Runs the monitoring loop until `shutdown` fires. A runner runs once.
+    pub async fn run(&self, shutdown: CancellationToken) -> Result<()> {
+        let pending = self.pending.lock().unwrap_or_else(|e| e.into_inner()).take();
+        let Some(PendingRun { approver, triggers, source }) = pending else {
+            return Err(anyhow!("The runner has already been started"));
+        };
+        // Background tasks also stop when setup fails or the run is dropped
+        let shutdown = shutdown.child_token();
+        let _stop = shutdown.clone().drop_guard();
+        let world = SimulatedWorld::from_config(&self.config)?;
+        let start = world.as_ref().map(SimulatedWorld::start);
+        // Boxed: the whole agent is too large a future for a test thread's stack
+        let run = Box::pin(run_agent(&self.config, source, approver, triggers, self, world, shutdown));
+        // A simulated run's decisions see the scenario's time, not the wall clock
+        match start {
+            Some(start) => with_simulated_clock(start, run).await,
+            None => run.await,
+        }
+    }
+}
+
+// Batches run one at a time and reloads only borrow the components between them
+#[allow(clippy::await_holding_refcell_ref)]
+async fn run_agent(
+    config: &Config,
+    source: Option<ConfigSource>,
+    approver: Approver,
+    trigger_rx: mpsc::Receiver<CycleTrigger>,
+    runner: &Runner,
+    world: Option<SimulatedWorld>,
+    shutdown: CancellationToken,
+) -> Result<()> {
+    info!("Starting ASAM with enhanced monitoring...");
+    let account_address = config.safe.address()?;
+    let mode = config.mode_plan()?;
+    if config.live_downgraded() {
+        warn!("Live mode requested on the local profile; running in dry-run mode instead");
+    }
+
+    if config.optimizer.timeout_secs < 5 {
+        warn!("API timeout is set below recommended minimum (5s). Current: {}s", config.optimizer.timeout_secs);
+    }
+    if let Some(world) = &world {
+        warn!(
+            "SIMULATED run: scenario {} with seed {}; nothing is read from or sent to the network",
+            world.model().scenario().name,
+            world.model().seed()
+        );
+    } else if config.runtime.preflight {
+        preflight::run_before_start(config).await?;
+    }
+
+    // Initialize provider with timeout
+    let (provider, rpc_client) = match &world {
+        Some(world) => world.provider(),
+        None => connect(config).await?,
+    };
+
+    // Initialize agents with enhanced error handling
+    debug!("Initializing ASAM components...");
+    let mut safe_manager = SafeManager::from_config(&config.safe, provider.clone())
+        .context("Failed to initialize SafeManager")?;
+    safe_manager.set_expected_chain_id(config.home_chain_id());
+    init_chain(&safe_manager).await?;
+    let mut defi_optimizer = DefiOptimizer::from_config(&config.acting_optimizer());
+    let mut cross_chain_router = CrossChainRouter::from_config(&config.router);
+    safe_manager.set_mode(mode.safe);
+    cross_chain_router.set_mode(mode.router);
+    if world.is_some() {
+        // The scripted node cannot take signed transactions
+        safe_manager.set_execution(SafeExecution::Simulate)?;
+        // and holds no funds a batch could misuse
+        safe_manager.set_delegatecalls(true, vec![config.actions.multi_send()?]);
+    } else if mode.safe == RunMode::Live
+        && config.safe.execution == SafeExecution::Execute
+        && config.safe.signer == SignerKind::Key
+        && config.safe.signer_key.is_none()
+    {
+        warn!("No safe.signer_key (or PRIVATE_KEY) is set: live transactions will fail until one is");
+    } else if mode.safe == RunMode::Live
+        && config.safe.execution != SafeExecution::Simulate
+        && config.safe.signer == SignerKind::Ledger
+    {
+        // A Ledger that is not ready yet is retried with every transaction
+        if let Err(e) = safe_manager.signer_address().await {
+            warn!("{:#}: connect and unlock the Ledger and open the Ethereum app before transactions are due", e);
+        }
+    }
+    // Transactions are refused later if the address is not a Safe
+    if let Err(e) = safe_manager.inspect_safe().await {
+        warn!("Could not read the Safe's owners and threshold: {:#}", e);
+    }
+    // The simulation scripts the home chain only
+    let watched_clients = match &world {
+        Some(world) => {
+            defi_optimizer.set_source(world.pools());
+            Vec::new()
+        }
+        None => watch_chains(config, &mut safe_manager).await?,
+    };
+    let balance_increases = safe_manager.subscribe_increases();
+
+    // Always started, so a reload can enable notifications that were off
+    let delivery_failures = DeliveryFailures::default();
+    let notifier = SwappableNotifier::new(Arc::new(build_notifiers(
+        &config.notify,
+        config.profile,
+        account_address,
+        &delivery_failures,
+    )));
+    // A simulated run starts from nothing every time
+    let store = match &world {
+        Some(_) => Arc::new(MemoryStore::new()) as Arc<dyn Store>,
+        None => storage::open_store(&config.storage)?,
+    };
+    // Shared by the rebalance alert cooldown and the repeated route checks
+    let max_age = config.recent_action_retention();
+    let recent_actions = RecentActions::load(store.clone(), max_age, unix_now()).await.unwrap_or_else(|e| {
+        warn!("Failed to load recent actions: {:#}", e);
+        RecentActions::new(max_age)
+    });
+    let events = notifier::spawn_worker(
+        Arc::new(notifier.clone()),
+        config.notify.queue_capacity,
+        config.notify.suppression_window(),
+        RebalanceCooldown::new(recent_actions.clone(), config.notify.rebalance_alert_cooldown()),
+    )
+    .with_subscribers(runner.events.clone());
+    safe_manager.set_store(store.clone());
+    if let Err(e) = safe_manager.report_unsent().await {
+        warn!("Failed to read the transactions left unsent at the last shutdown: {:#}", e);
+    }
+    let prices = match &world {
+        Some(world) => Some(world.prices()),
+        None => prices::from_config(&config.prices, Some(provider.clone()))?,
+    };
+    if let Some(prices) = prices {
+        safe_manager.set_prices(prices);
+    }
+    cross_chain_router.set_recent_actions(recent_actions.clone());
+    let mut strategy = Strategy::acting_from_config(config, store.clone());
+    if !strategy.shadows().is_empty() {
+        let shadows: Vec<&str> = strategy.shadows().iter().map(|shadow| shadow.name()).collect();
+        info!("Strategy {} acts; {} only record their decisions", strategy.name(), shadows.join(", "));
+    }
+    strategy.set_recent_actions(recent_actions.clone());
+    strategy.set_approver(approver);
+    strategy.set_decision_log(DecisionLog::new(config.storage.decision_log_path.clone()));
+    let swap_costs = match &world {
+        Some(_) => SwapCosts::with_provider(config, provider.clone())?,
+        None => SwapCosts::from_config(config)?,
+    };
+    strategy.set_swap_costs(Arc::new(swap_costs));
+    strategy.set_protocols(Arc::new(ProtocolRegistry::from_config(&config.actions)?));
+    let kill_switch = KillSwitch::from_config(&config.kill_switch);
+    kill_switch.set_events(events.clone());
+    safe_manager.set_kill_switch(kill_switch.clone());
+    cross_chain_router.set_kill_switch(kill_switch.clone());
+    strategy.set_kill_switch(kill_switch.clone());
+    safe_manager.set_events(events.clone());
+    defi_optimizer.set_events(events.clone());
+    cross_chain_router.set_events(events.clone());
+    debug!("All components initialized successfully");
+
+    // Funds start out on the chain the Safe lives on unless a previous run moved them;
+    // the first balance check fills in an empty portfolio
+    let status = runner.status.clone();
+    let portfolio = store.portfolio().await.unwrap_or_else(|e| {
+        warn!("Failed to load stored portfolio: {:#}", e);
+        Default::default()
+    });
+    if !portfolio.entries.is_empty() {
+        info!("Restored position on {} ({} entries)", portfolio.current_chain(), portfolio.entries.len());
+    }
+    let persisted_portfolio = RefCell::new(portfolio.clone());
+    let yields = match store.yield_snapshots().await {
+        Ok(snapshots) => YieldTracker::restore(&snapshots),
+        Err(e) => {
+            warn!("Failed to load stored yield snapshots: {:#}", e);
+            YieldTracker::default()
+        }
+    };
+    status.update(|s| {
+        s.portfolio = portfolio;
+        s.yields = yields;
+    });
+
+    // Background tasks stop when the shutdown token fires
+    let route_tracker = cross_chain_router.tracker();
+    let poller = RouteStatusPoller::new(
+        route_tracker.clone(),
+        cross_chain_router.chain_ids(),
+        StatusPollerConfig::from_config(&config.router.status),
+    );
+    // Panicking background tasks are restarted
+    let supervisor = Supervisor::new(status.clone(), events.clone());
+    {
+        let (poller, shutdown) = (poller.clone(), shutdown.clone());
+        supervisor.supervise("route_poller", move || poller.clone().run(shutdown.clone()));
+    }
+    let probed = watched_clients.into_iter().map(|(chain, client)| (format!("rpc_probes:{}", chain), client));
+    for (name, rpc_client) in probed.chain([("rpc_probes".to_string(), rpc_client.clone())]) {
+        let shutdown = shutdown.clone();
+        supervisor.supervise(name, move || {
+            let (rpc_client, shutdown) = (rpc_client.clone(), shutdown.clone());
+            async move { rpc_client.run_probes(shutdown).await }
+        });
+    }
+    supervisor.supervise("route_events", {
+        let events = events.clone();
+        let status = status.clone();
+        let route_tracker = route_tracker.clone();
+        let recent_actions = recent_actions.clone();
+        let shutdown = shutdown.clone();
+        move || {
+            let mut route_events = poller.subscribe();
+            let (events, status, route_tracker, recent_actions, shutdown) =
+                (events.clone(), status.clone(), route_tracker.clone(), recent_actions.clone(), shutdown.clone());
+            async move {
+                while let Some(Ok(event)) = shutdown.run_until_cancelled(route_events.recv()).await {
+                    match event {
+                        RouteEvent::Completed { route_id, destination_tx_hash } => {
+                            info!("Route {} completed (destination tx: {:?})", route_id, destination_tx_hash);
+                            let route = route_tracker.get(&route_id);
+                            if let Some(route) = &route {
+                                recent_actions.route_completed(route).await;
+                            }
+                            status.update(|s| {
+                                if s.portfolio.route_completed(&route_id, unix_now()) {
+                                    if let Some(route) = &route {
+                                        s.yields.flow(&route.target_chain, route.amount);
+                                    }
+                                }
+                            });
+                            events.emit(AgentEvent::RouteCompleted { route_id, destination_tx_hash });
+                        }
+                        RouteEvent::Failed { route_id, reason } => {
+                            error!("Route {} failed: {}", route_id, reason);
+                            if let Some(receipt) = route_tracker.get(&route_id) {
+                                status.update(|s| {
+                                    if s.portfolio.route_failed(&receipt, unix_now()) {
+                                        s.yields.flow(&receipt.source_chain, receipt.amount);
+                                    }
+                                });
+                            }
+                            events.emit(AgentEvent::RouteFailed { route_id, reason });
+                        }
+                    }
+                }
+            }
+        }
+    });
+    info!("ASAM initialized successfully");
+    let chain_id = rpc_client.chain_id().and_then(|id| u64::try_from(id).ok());
+    let simulation_seed = world.as_ref().map(|world| world.model().seed());
+    for line in version::banner(config.profile, mode, chain_id, account_address, simulation_seed).lines() {
+        info!("{}", line);
+    }
+    info!("API timeout: {}s", config.optimizer.timeout_secs);
+    if mode.is_mixed() {
+        warn!("Components run in different modes (runtime.allow_mixed_mode is set)");
+    }
+
+    let schedule = PhaseSchedule::from_config(&config.runtime);
+    debug!(
+        "Phase schedule - balance: {:?}, pools: {:?}, routes: {:?}",
+        schedule.balance, schedule.pools, schedule.routes
+    );
+
+    let (updates_tx, mut updates) = watch::channel(ConfigUpdate {
+        version: ConfigVersion::INITIAL,
+        config: Arc::new(config.clone()),
+    });
+    let (commands_tx, mut commands) = mpsc::unbounded_channel();
+    if let Some(bind) = config.http.bind_addr()? {
+        let state = StatusState {
+            status: status.clone(),
+            tracker: route_tracker.clone(),
+            store: store.clone(),
+            max_cycle_age: schedule.balance * 3,
+            mode,
+            profile: config.profile,
+            rpc: Some(rpc_client.clone()),
+            kill_switch: kill_switch.clone(),
+            admin_token: config.http.admin_token.clone(),
+            audit: AuditLog::new(config.http.audit_log_path.clone()),
+            cycle_trigger: Some(runner.triggers.clone()),
+            commands: Some(commands_tx),
+            delivery_failures: delivery_failures.clone(),
+            config: Some(updates.clone()),
+        };
+        let shutdown = shutdown.clone();
+        tokio::spawn(async move {
+            if let Err(e) = status::serve(bind, state, shutdown).await {
+                error!("Status server stopped: {:#}", e);
+            }
+        });
+    }
+
+    // Reloads reach the scheduler right away and the components before the next batch
+    let (schedule_tx, schedule_rx) = watch::channel(schedule);
+    if let Some(source) = source {
+        tokio::spawn(reload::watch(source, config.clone(), updates_tx, shutdown.clone()));
+        let mut updates = updates.clone();
+        tokio::spawn(async move {
+            while updates.changed().await.is_ok() {
+                let schedule = PhaseSchedule::from_config(&updates.borrow_and_update().config.runtime);
+                schedule_tx.send_if_modified(|current| std::mem::replace(current, schedule) != schedule);
+            }
+        });
+    }
+
+    // Main monitoring loop with enhanced error handling. With the default schedule all
+    // phases come due together and form one full monitoring cycle. Pool-only runs wait
+    // until the most recent balance check has succeeded.
+    let balance_ok = Cell::new(false);
+    let cycle = Cell::new(0u64);
+    let degradation = config.degradation_policy();
+    let reconcile_tolerance = config.acting_strategy().reconcile_tolerance_eth;
+    let max_cost_ratio = config.acting_strategy().max_cost_ratio;
+    let snapshot_interval_secs = config.storage.snapshot_interval_secs;
+    // The scripted node of a simulated run keeps no logs, modules or guard
+    let watch_transfers = config.safe.transfers.enabled && world.is_none();
+    let check_modules = world.is_none();
+    let notify_transfers = config.safe.transfers.notify;
+    let cycle_timeout = Cell::new(config.runtime.cycle_timeout());
+    let config_version = Cell::new(ConfigVersion::INITIAL);
+    let safe_manager = RefCell::new(safe_manager);
+    let defi_optimizer = RefCell::new(defi_optimizer);
+    let cross_chain_router = RefCell::new(cross_chain_router);
+    let failing = RefCell::new(BTreeMap::new());
+    let backoff = Backoff::from_config(&config.runtime);
+    run_phases(schedule_rx, trigger_rx, balance_increases, backoff, &status, shutdown.clone(), |due, trigger| {
+        if updates.has_changed().unwrap_or(false) {
+            let update = updates.borrow_and_update().clone();
+            apply_config(
+                &update.config,
+                &mut safe_manager.borrow_mut(),
+                &mut defi_optimizer.borrow_mut(),
+                &mut cross_chain_router.borrow_mut(),
+                &notifier,
+                account_address,
+                &delivery_failures,
+            );
+            cycle_timeout.set(update.config.runtime.cycle_timeout());
+            config_version.set(update.version);
+            status.update(|s| s.config_version = update.version);
+        }
+        while let Ok(command) = commands.try_recv() {
+            match command {
+                AdminCommand::SetChainActive { chain, active } => {
+                    info!("Operator set chain {} {}", chain, if active { "active" } else { "inactive" });
+                    cross_chain_router.borrow_mut().set_chain_active(&chain, active);
+                }
+            }
+        }
+        cycle.set(cycle.get() + 1);
+        let cycle = cycle.get();
+        status.update(|s| s.cycle = cycle);
+        if let Some(world) = &world {
+            world.advance(cycle);
+        }
+        let config_version = config_version.get();
+        let cycle_timeout = cycle_timeout.get();
+        let span = tracing::info_span!(
+            "cycle",
+            cycle,
+            config_version = config_version.0,
+            safe = ?account_address,
+            mode = %mode.global,
+            trigger = %trigger
+        );
+        let run_balance = due.contains(&Phase::Balance);
+        let run_pools = due.contains(&Phase::Pools);
+        let run_routes = due.contains(&Phase::Routes);
+        let safe_manager = &safe_manager;
+        let defi_optimizer = &defi_optimizer;
+        let cross_chain_router = &cross_chain_router;
+        let strategy = &strategy;
+        let route_tracker = &route_tracker;
+        let balance_ok = &balance_ok;
+        let status = &status;
+        let events = &events;
+        let store = store.as_ref();
+        let persisted_portfolio = &persisted_portfolio;
+        let failing = &failing;
+        let degradation = &degradation;
+
+        let kill_switch = &kill_switch;
+        let supervisor = &supervisor;
+        let finished = &runner.finished;
+
+        async move {
+            let (safe_manager, defi_optimizer, cross_chain_router) =
+                (safe_manager.borrow(), defi_optimizer.borrow(), cross_chain_router.borrow());
+            let (safe_manager, defi_optimizer, cross_chain_router) =
+                (&*safe_manager, &*defi_optimizer, &*cross_chain_router);
+            info!(
+                "Cycle {} starting in {} mode (config version {}, trigger: {}): {:?}",
+                cycle, mode, config_version, trigger, due
+            );
+            if let Some(source) = kill_switch.check() {
+                info!("[PAUSED] Running read-only, fund movement paused by {}", source);
+            }
+            if run_pools {
+                check_budget(store, status, events, max_cost_ratio).await;
+            }
+            let phases = async {
+                // A module or guard nobody allowed can move funds past the owners, so
+                // the cycle stops there; a Safe that cannot be read is checked next time
+                if run_balance && check_modules {
+                    match safe_manager.check_modules().await {
+                        Err(e) if matches!(e.downcast_ref(), Some(SafeError::UnexpectedModule(_))) => return Err(e),
+                        Err(e) => warn!("Could not check the Safe's modules: {:#}", e),
+                        Ok(()) => {}
+                    }
+                }
+                if run_balance && run_pools {
+                    monitor_and_optimize(
+                        safe_manager,
+                        defi_optimizer,
+                        cross_chain_router,
+                        strategy,
+                        status,
+                        events,
+                        degradation,
+                    )
+                    .await
+                    .map(|mut report| {
+                        report.trigger = trigger;
+                        for warning in &report.warnings {
+                            warn!("Cycle warning: {}", warning);
+                        }
+                        status.update(|s| s.last_report = Some(report));
+                    })
+                } else if run_balance {
+                    run_phase(status, "balance", check_balance_phase(safe_manager, status, events))
+                        .await
+                        .0
+                        .map(|_| ())
+                } else if run_pools {
+                    if balance_ok.get() {
+                        let degrader = Degrader::new(degradation, status, events);
+                        let agents = Agents { safe_manager, defi_optimizer, cross_chain_router, degrader: &degrader };
+                        optimize_phase(agents, strategy, status, events).await
+                    } else {
+                        warn!("Skipping pool phase until the balance check passes");
+                        Ok(())
+                    }
+                } else {
+                    Ok(())
+                }
+            };
+            let result = with_cycle_timeout(status, cycle_timeout, supervisor.catch("cycle", phases)).await;
+            // Balance failures surface before any pool error, so a failed full cycle
+            // only leaves the balance good when it got as far as a report
+            if run_balance {
+                balance_ok.set(result.is_ok());
+            }
+            if result.is_err() {
+                keep_routes_resumable(route_tracker, status, store).await;
+            }
+
+            if run_balance {
+                persist_cycle(store, status, &result, run_pools).await;
+            }
+            if run_balance && run_pools && result.is_ok() {
+                persist_valuation(store, status).await;
+            }
+            if run_balance && balance_ok.get() {
+                reconcile_portfolio(status, events, reconcile_tolerance);
+                log_balance_change(safe_manager, status).await;
+            }
+            if run_balance && watch_transfers {
+                report_transfers(safe_manager, events, notify_transfers).await;
+            }
+            let portfolio = status.snapshot().portfolio;
+            if portfolio != *persisted_portfolio.borrow() {
+                match store.set_portfolio(&portfolio).await {
+                    Ok(()) => *persisted_portfolio.borrow_mut() = portfolio,
+                    Err(e) => warn!("Failed to persist portfolio: {:#}", e),
+                }
+            }
+            if run_balance && balance_ok.get() {
+                snapshot_yields(store, status, snapshot_interval_secs).await;
+            }
+
+            let outcome = Outcome::of(&result);
+            track_errors(&result, &due, &mut failing.borrow_mut(), status, events);
+            match result {
+                Ok(_) => debug!("Monitoring phases {:?} completed successfully", due),
+                Err(e) => {
+                    error!("Error in monitoring cycle: {}", e);
+                    error!("Error details: {:?}", e);
+                }
+            }
+
+            if run_balance {
+                status.update(|s| s.last_cycle_at = Some(unix_now()));
+            }
+            if run_routes {
+                report_routes_phase(route_tracker);
+                for route in route_tracker.all() {
+                    if let Err(e) = store.upsert_route(&route, route.updated_at).await {
+                        warn!("Failed to persist route {}: {:#}", route.route_id, e);
+                    }
+                }
+            }
+            finished.send_replace(cycle);
+            outcome
+        }
+        .instrument(span)
+    })
+    .await;
+
+    safe_manager.borrow().shutdown_queue().await;
+    info!("ASAM stopped");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agents::test_utils::{get_test_address, test_provider};
+    use crate::config::{Fallback, OptimizerConfig, Profile, RouterConfig, SimulationMode, StrategyConfig};
+    use crate::degradation::HealthState;
+    use crate::notifier::{AlertDeduplicator, Notifier};
+    use crate::rpc::RetryPolicy;
+    use crate::util::MockClock;
+    use crate::yield_tracker::YieldSnapshot;
+    use wiremock::matchers::{body_partial_json, method};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn test_strategy() -> Strategy {
+        let store = Arc::new(storage::MemoryStore::new());
+        Strategy::from_config(&StrategyConfig::default(), &RouterConfig::default(), store)
+    }
+
+    /// The default policy, finishing the cycle without pools when their fetch fails.
+    fn skip_pools() -> DegradationPolicy {
+        DegradationPolicy { pool_api: Fallback::SkipPhase, ..DegradationPolicy::default() }
+    }
+
+    /// Prices ETH at $2000 until knocked out.
+    #[derive(Default)]
+    struct FlakyPrices {
+        down: std::sync::atomic::AtomicBool,
+    }
+
+    #[async_trait]
+    impl prices::PriceSource for FlakyPrices {
+        fn name(&self) -> &str {
+            "flaky"
+        }
+
+        async fn prices(&self, symbols: &[&str]) -> Result<std::collections::HashMap<String, prices::Price>> {
+            if self.down.load(std::sync::atomic::Ordering::SeqCst) {
+                return Err(anyhow!("price API unreachable"));
+            }
+            let price = prices::Price { usd: 2000.0, updated_at: unix_now(), source: "flaky".to_string() };
+            Ok(symbols.iter().filter(|s| **s == "ETH").map(|s| (s.to_string(), price.clone())).collect())
+        }
+    }
+
+    /// Pool API answering every request with `status`.
+    async fn pool_api_status(status: u16) -> (MockServer, DefiOptimizer) {
+        let server = MockServer::start().await;
+        Mock::given(method("GET")).respond_with(ResponseTemplate::new(status)).mount(&server).await;
+        let optimizer = DefiOptimizer::from_config(&OptimizerConfig { api_url: server.uri(), ..OptimizerConfig::default() });
+        (server, optimizer)
+    }
+
+    /// A router whose simulated bridge settles at once, on a mock clock set to now.
+    fn test_router() -> CrossChainRouter {
+        CrossChainRouter::with_clock(&RouterConfig::default(), MockClock::new(unix_now()).shared())
+    }
+
+    /// JSON-RPC node answering every call with `balance_hex` after `delay`.
+    async fn slow_node(balance_hex: &str, delay: Duration) -> MockServer {
+        let server = MockServer::start().await;
+        // A mainnet node, whatever the balance
+        Mock::given(body_partial_json(serde_json::json!({ "method": "eth_chainId" })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({ "jsonrpc": "2.0", "id": 1, "result": "0x1" })))
+            .mount(&server)
+            .await;
+        // With the Safe deployed
+        Mock::given(body_partial_json(serde_json::json!({ "method": "eth_getCode" })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({ "jsonrpc": "2.0", "id": 1, "result": "0x6080" })))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(serde_json::json!({ "jsonrpc": "2.0", "id": 1, "result": balance_hex }))
+                    .set_delay(delay),
+            )
+            .mount(&server)
+            .await;
+        server
+    }
+
+    /// Pool API returning a single pool on `chain` after `delay`.
+    async fn slow_pool_api(chain: &str, delay: Duration) -> (MockServer, DefiOptimizer) {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(serde_json::json!([{ "name": "Aave", "chain": chain, "tvl": 1e6, "apy": 5.2 }]))
+                    .set_delay(delay),
+            )
+            .mount(&server)
+            .await;
+        let optimizer = DefiOptimizer::from_config(&OptimizerConfig {
+            api_url: server.uri(),
+            ..OptimizerConfig::default()
+        });
+        (server, optimizer)
+    }
+
+    #[tokio::test]
+    async fn test_monitor_and_optimize_integration() {
+        let node = slow_node("0xde0b6b3a7640000", Duration::ZERO).await; // 1 ETH
+        let (_api, defi_optimizer) = slow_pool_api("Arbitrum", Duration::ZERO).await;
+        let provider = test_provider(&node.uri());
+        let mut safe_manager = SafeManager::new(get_test_address(), provider)
+            .expect("Failed to create SafeManager");
+        
+        // Set a reasonable minimum balance
+        safe_manager.set_min_balance(U256::from(100_000_000_000_000_u64)); // 0.0001 ETH
+        
+        let cross_chain_router = test_router();
+
+        let report = monitor_and_optimize(&safe_manager, &defi_optimizer, &cross_chain_router, &test_strategy(), &StatusHandle::new(), &EventSender::disabled(), &DegradationPolicy::default())
+            .await
+            .unwrap();
+        assert_eq!(report.balance, U256::exp10(18));
+        assert_eq!(report.balance_status, BalanceStatus::Healthy);
+        assert!(report.warnings.is_empty(), "{:?}", report.warnings);
+        let best_pool = report.best_pool.unwrap();
+        assert_eq!(best_pool.pool.chain, "Arbitrum");
+        assert!(best_pool.score > 0.0);
+        assert_eq!(report.decision, CycleDecision::RouteInitiated);
+        let route = report.route.unwrap();
+        assert!(route.route_id.is_some());
+        assert_eq!((route.source_chain.as_str(), route.target_chain.as_str()), ("Ethereum", "Arbitrum"));
+        assert_eq!(route.mode, RunMode::Live);
+        // 90% of the balance minus the default 0.01 ETH reserve
+        assert_eq!(route.amount, 0.891);
+        let inputs = report.decision_inputs.unwrap();
+        assert_eq!((inputs.current_chain.as_str(), inputs.current_apy), ("Ethereum", None));
+        assert_eq!(inputs.amount, Some(0.891));
+    }
+
+
+    #[tokio::test]
+    async fn test_low_balance_handling() {
+        let node = slow_node("0xde0b6b3a7640000", Duration::ZERO).await; // 1 ETH
+        let (_api, defi_optimizer) = slow_pool_api("Ethereum", Duration::ZERO).await;
+        let provider = test_provider(&node.uri());
+        let mut safe_manager = SafeManager::new(get_test_address(), provider)
+            .expect("Failed to create SafeManager");
+        let cross_chain_router = CrossChainRouter::new();
+
+        // Below the minimum but above the critical half: the cycle completes with a warning
+        safe_manager.set_min_balance(U256::exp10(18) * 3 / 2); // 1.5 ETH
+        let report = monitor_and_optimize(&safe_manager, &defi_optimizer, &cross_chain_router, &test_strategy(), &StatusHandle::new(), &EventSender::disabled(), &DegradationPolicy::default())
+            .await
+            .unwrap();
+        assert_eq!(report.balance_status, BalanceStatus::Low);
+        assert_eq!(report.warnings, vec!["balance on Ethereum below minimum threshold".to_string()]);
+        assert_eq!(report.decision, CycleDecision::NoAction);
+        assert!(report.route.is_none());
+
+        // Set a high minimum balance so the balance is critical: still a report, not an error
+        safe_manager.set_min_balance(U256::from(10_000_000_000_000_000_000_u64)); // 10 ETH
+        let report = monitor_and_optimize(&safe_manager, &defi_optimizer, &cross_chain_router, &test_strategy(), &StatusHandle::new(), &EventSender::disabled(), &DegradationPolicy::default())
+            .await
+            .unwrap();
+        assert_eq!(report.balance_status, BalanceStatus::Critical);
+        assert_eq!(report.warnings, vec!["balance on Ethereum below critical threshold".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_multi_chain_balances() {
+        let home = slow_node("0xde0b6b3a7640000", Duration::ZERO).await; // 1 ETH
+        let arbitrum = slow_node("0x429d069189e0000", Duration::ZERO).await; // 0.3 ETH
+        let optimism = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(500))
+            .mount(&optimism)
+            .await;
+        let (_api, defi_optimizer) = slow_pool_api("Ethereum", Duration::ZERO).await;
+
+        let half_eth = U256::exp10(17) * 5;
+        let mut safe_manager = SafeManager::new(get_test_address(), test_provider(&home.uri())).unwrap();
+        safe_manager.set_min_balance(half_eth);
+        safe_manager.watch_chain("Arbitrum", test_provider(&arbitrum.uri()), half_eth);
+        safe_manager.watch_chain("Optimism", test_provider(&optimism.uri()), U256::exp10(16));
+        let status = StatusHandle::new();
+        let (events, mut rx) = EventSender::channel(16);
+
+        for _ in 0..2 {
+            let report =
+                monitor_and_optimize(&safe_manager, &defi_optimizer, &CrossChainRouter::new(), &test_strategy(), &status, &events, &DegradationPolicy::default())
+                    .await
+                    .unwrap();
+
+            // The failing chain does not fail the cycle or the other chains
+            let balances = &report.balances;
+            assert_eq!(balances.keys().collect::<Vec<_>>(), vec!["Arbitrum", "Ethereum", "Optimism"]);
+            assert_eq!(balances["Ethereum"].balance, Some(U256::exp10(18)));
+            assert_eq!(balances["Ethereum"].status, BalanceStatus::Healthy);
+            assert_eq!(balances["Arbitrum"].balance, Some(U256::exp10(17) * 3));
+            assert_eq!(balances["Arbitrum"].status, BalanceStatus::Low);
+            assert_eq!(balances["Optimism"].balance, None);
+            assert_eq!(balances["Optimism"].status, BalanceStatus::Unknown);
+            assert!(balances["Optimism"].error.is_some());
+            assert_eq!(balances["Optimism"].min_balance, U256::exp10(16));
+            assert_eq!(report.balance_status, BalanceStatus::Healthy);
+            assert_eq!(report.warnings.len(), 2, "{:?}", report.warnings);
+            assert_eq!(report.warnings[0], "balance on Arbitrum below minimum threshold");
+            assert!(report.warnings[1].starts_with("balance on Optimism unknown"), "{:?}", report.warnings);
+            assert_eq!(status.snapshot().balances, report.balances);
+        }
+
+        // Alerts name the chain and fire once per change
+        let alerts: Vec<AgentEvent> = std::iter::from_fn(|| rx.try_recv().ok()).collect();
+        assert_eq!(alerts.len(), 1, "{:?}", alerts);
+        match &alerts[0] {
+            AgentEvent::BelowMinimum { chain, balance_wei, .. } => {
+                assert_eq!((chain.as_str(), *balance_wei), ("Arbitrum", U256::exp10(17) * 3))
+            }
+            other => panic!("unexpected event {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pool_failure_with_partial_cycles() {
+        let node = slow_node("0xde0b6b3a7640000", Duration::ZERO).await;
+        let api = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(500))
+            .mount(&api)
+            .await;
+        let defi_optimizer = DefiOptimizer::from_config(&OptimizerConfig {
+            api_url: api.uri(),
+            ..OptimizerConfig::default()
+        });
+        let provider = test_provider(&node.uri());
+        let safe_manager = SafeManager::new(get_test_address(), provider).unwrap();
+        let cross_chain_router = CrossChainRouter::new();
+        let status = StatusHandle::new();
+
+        let result =
+            monitor_and_optimize(&safe_manager, &defi_optimizer, &cross_chain_router, &test_strategy(), &status, &EventSender::disabled(), &DegradationPolicy::default())
+                .await;
+        assert!(result.is_err());
+
+        let report =
+            monitor_and_optimize(&safe_manager, &defi_optimizer, &cross_chain_router, &test_strategy(), &status, &EventSender::disabled(), &skip_pools())
+                .await
+                .unwrap();
+        assert_eq!(report.balance_status, BalanceStatus::Healthy);
+        assert!(report.best_pool.is_none());
+        assert_eq!(report.decision, CycleDecision::Blocked("pool data unavailable".to_string()));
+        assert_eq!(report.warnings.len(), 1);
+        assert!(report.warnings[0].starts_with("pool_api unavailable, fell back to skip_phase"), "{:?}", report.warnings);
+        assert!(cross_chain_router.tracker().all().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_degradation_matrix() {
+        let node = slow_node("0xde0b6b3a7640000", Duration::ZERO).await; // 1 ETH
+        let (_up, pools_up) = slow_pool_api("Ethereum", Duration::ZERO).await;
+        let (_down, pools_down) = pool_api_status(503).await;
+        let prices = Arc::new(FlakyPrices::default());
+        let mut safe_manager = SafeManager::new(get_test_address(), test_provider(&node.uri())).unwrap();
+        safe_manager.set_prices(prices.clone());
+        let router = CrossChainRouter::new();
+        let strategy = test_strategy();
+        let status = StatusHandle::new();
+        let (events, mut rx) = EventSender::channel(32);
+        let policy = DegradationPolicy {
+            pool_api: Fallback::UseCache,
+            prices: Fallback::UseLastKnown,
+            ..DegradationPolicy::default()
+        };
+        let defaults = DegradationPolicy::default();
+        let failing = DegradationPolicy { prices: Fallback::Fail, ..DegradationPolicy::default() };
+        let cycle = |optimizer, policy| monitor_and_optimize(&safe_manager, optimizer, &router, &strategy, &status, &events, policy);
+        let fallbacks = |report: &CycleReport| -> Vec<(Dependency, Fallback)> {
+            report.degradations.iter().map(|d| (d.dependency, d.fallback)).collect()
+        };
+
+        let report = cycle(&pools_up, &policy).await.unwrap();
+        assert!(report.degradations.is_empty(), "{:?}", report.degradations);
+        assert_eq!(report.valuation.as_ref().unwrap().total_usd, 2000.0);
+
+        // Pool API down: the cached pools decide while fresh enough, then the decision is skipped
+        let report = cycle(&pools_down, &policy).await.unwrap();
+        assert_eq!(fallbacks(&report), vec![(Dependency::PoolApi, Fallback::UseCache)]);
+        assert_eq!(report.best_pool.unwrap().pool.chain, "Ethereum");
+        assert_eq!(report.decision, CycleDecision::NoAction);
+        status.update(|s| s.last_pools.as_mut().unwrap().0 -= 3600);
+        let report = cycle(&pools_down, &policy).await.unwrap();
+        assert_eq!(fallbacks(&report), vec![(Dependency::PoolApi, Fallback::SkipPhase)]);
+        assert_eq!(report.decision, CycleDecision::Blocked("pool data unavailable".to_string()));
+        assert!(cycle(&pools_down, &defaults).await.is_err());
+
+        // Price source down: the last valuation stands in, or the cycle goes on without USD values
+        prices.down.store(true, std::sync::atomic::Ordering::SeqCst);
+        let report = cycle(&pools_up, &policy).await.unwrap();
+        assert_eq!(fallbacks(&report), vec![(Dependency::Prices, Fallback::UseLastKnown)]);
+        assert_eq!(report.valuation.unwrap().total_usd, 2000.0);
+        let report = cycle(&pools_up, &defaults).await.unwrap();
+        assert_eq!(fallbacks(&report), vec![(Dependency::Prices, Fallback::SkipPhase)]);
+        assert_eq!((report.valuation, report.balance_usd), (None, None));
+        assert_eq!(report.decision, CycleDecision::NoAction);
+        assert!(cycle(&pools_up, &failing).await.is_err());
+        prices.down.store(false, std::sync::atomic::Ordering::SeqCst);
+        let report = cycle(&pools_up, &policy).await.unwrap();
+        assert!(report.degradations.is_empty());
+
+        // RPC down: nothing stands in for the balance
+        let down = MockServer::start().await;
+        Mock::given(method("POST")).respond_with(ResponseTemplate::new(500)).mount(&down).await;
+        let no_retries = RetryPolicy { max_attempts: 1, ..RetryPolicy::default() };
+        let broken = SafeManager::with_retry(get_test_address(), test_provider(&down.uri()), no_retries).unwrap();
+        let result = monitor_and_optimize(&broken, &pools_up, &router, &strategy, &status, &events, &policy).await;
+        assert_eq!(ErrorCategory::of(&result.unwrap_err()), ErrorCategory::RpcTransport);
+
+        // Alerts on changes of fallback only
+        let alerts: Vec<String> = std::iter::from_fn(|| rx.try_recv().ok())
+            .filter(|e| e.kind().starts_with("degradation"))
+            .map(|e| e.summary())
+            .collect();
+        assert_eq!(alerts.len(), 6, "{:#?}", alerts);
+        assert!(alerts[0].starts_with("pool_api is down, cycles fall back to use_cache"), "{}", alerts[0]);
+        assert!(alerts[1].starts_with("pool_api is down, cycles fall back to skip_phase"), "{}", alerts[1]);
+        assert_eq!(alerts[2], "pool_api is answering again");
+        assert!(alerts[3].starts_with("prices is down, cycles fall back to use_last_known"), "{}", alerts[3]);
+        assert!(alerts[4].starts_with("prices is down, cycles fall back to skip_phase"), "{}", alerts[4]);
+        assert_eq!(alerts[5], "prices is answering again");
+
+        let dependencies = status.snapshot().dependencies;
+        let health = |dependency| dependencies.get(dependency).unwrap().state;
+        assert_eq!(health(Dependency::Rpc), HealthState::Down);
+        assert_eq!(health(Dependency::PoolApi), HealthState::Flaky);
+        assert_eq!(health(Dependency::Prices), HealthState::Flaky);
+        assert_eq!(dependencies.get(Dependency::Prices).unwrap().recent_failures, 3);
+    }
+
+    #[tokio::test]
+    async fn test_repeated_failures_alert_once_and_clear() {
+        let down = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(500))
+            .mount(&down)
+            .await;
+        let up = slow_node("0xde0b6b3a7640000", Duration::ZERO).await;
+        let (_api, defi_optimizer) = slow_pool_api("Ethereum", Duration::ZERO).await;
+        let no_retries = RetryPolicy { max_attempts: 1, ..RetryPolicy::default() };
+        let broken = SafeManager::with_retry(get_test_address(), test_provider(&down.uri()), no_retries).unwrap();
+        let fixed = SafeManager::new(get_test_address(), test_provider(&up.uri())).unwrap();
+        let cross_chain_router = CrossChainRouter::new();
+        let status = StatusHandle::new();
+        let (events, mut rx) = EventSender::channel(20);
+        let mut failing = BTreeMap::new();
+        let full = [Phase::Balance, Phase::Pools, Phase::Routes];
+
+        for _ in 0..4 {
+            let result = monitor_and_optimize(&broken, &defi_optimizer, &cross_chain_router, &test_strategy(), &status, &events, &DegradationPolicy::default())
+                .await
+                .map(|_| ());
+            track_errors(&result, &full, &mut failing, &status, &events);
+        }
+        // A pool-only batch says nothing about the node
+        track_errors(&Ok(()), &[Phase::Pools], &mut failing, &status, &events);
+        let result = monitor_and_optimize(&fixed, &defi_optimizer, &cross_chain_router, &test_strategy(), &status, &events, &DegradationPolicy::default())
+            .await
+            .map(|_| ());
+        track_errors(&result, &full, &mut failing, &status, &events);
+        assert!(failing.is_empty());
+        assert_eq!(status.snapshot().error_counts, BTreeMap::from([(ErrorCategory::RpcTransport, 4)]));
+
+        let mut dedup = AlertDeduplicator::new(Duration::from_secs(30 * 60));
+        let now = Instant::now();
+        let mut sent = Vec::new();
+        while let Ok(event) = rx.try_recv() {
+            sent.extend(dedup.filter(event, now));
+        }
+        let kinds: Vec<&str> = sent.iter().map(AgentEvent::kind).collect();
+        assert_eq!(kinds, vec!["error_raised", "error_repeated", "error_cleared"]);
+        assert!(sent[0].summary().starts_with("Error rpc_transport: "), "{}", sent[0].summary());
+        assert_eq!(sent[1], AgentEvent::ErrorRepeated { category: ErrorCategory::RpcTransport, count: 3 });
+    }
+
+    #[tokio::test]
+    async fn test_balance_and_pools_run_concurrently() {
+        // The balance phase makes one RPC call, so both sides take about 400ms
+        let node = slow_node("0xde0b6b3a7640000", Duration::from_millis(400)).await;
+        let (_api, defi_optimizer) = slow_pool_api("Ethereum", Duration::from_millis(400)).await;
+        let provider = test_provider(&node.uri());
+        let safe_manager = SafeManager::new(get_test_address(), provider).unwrap();
+        let status = StatusHandle::new();
+
+        let start = Instant::now();
+        let report =
+            monitor_and_optimize(&safe_manager, &defi_optimizer, &CrossChainRouter::new(), &test_strategy(), &status, &EventSender::disabled(), &DegradationPolicy::default())
+                .await
+                .unwrap();
+        let elapsed = start.elapsed();
+
+        let timings = report.timings;
+        assert!(timings.balance_ms >= 400 && timings.pools_ms >= 400, "{:?}", timings);
+        assert!(elapsed < Duration::from_millis(700), "cycle took {:?}, {:?}", elapsed, timings);
+        assert!(timings.total_ms < timings.balance_ms + timings.pools_ms, "{:?}", timings);
+        assert_eq!(status.snapshot().last_action.as_deref(), Some("hold"));
+    }
+
+    #[tokio::test]
+    async fn test_strategies_decide_on_one_fetch() {
+        let node = slow_node("0xde0b6b3a7640000", Duration::ZERO).await;
+        let api = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+                { "name": "Aave", "chain": "Ethereum", "tvl": 5e7, "apy": 4.8 },
+                { "name": "Radiant", "chain": "Arbitrum", "tvl": 2e6, "apy": 7.5 },
+            ])))
+            .expect(1)
+            .mount(&api)
+            .await;
+        let mut config = Config::from_toml_str(
+            r#"
+[strategy]
+min_apy_improvement = 5.0
+
+[[strategies]]
+name = "conservative"
+active = true
+optimizer = { min_tvl = 10000000.0 }
+
+[[strategies]]
+name = "aggressive"
+strategy = { min_apy_improvement = 0.5 }
+"#,
+        )
+        .unwrap();
+        config.optimizer.api_url = api.uri();
+        config.validate().unwrap();
+        let path = std::env::temp_dir().join(format!("asam-strategies-{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let safe_manager = SafeManager::new(get_test_address(), test_provider(&node.uri())).unwrap();
+        let defi_optimizer = DefiOptimizer::from_config(&config.acting_optimizer());
+        let cross_chain_router = CrossChainRouter::new();
+        let mut strategy = Strategy::acting_from_config(&config, Arc::new(storage::MemoryStore::new()));
+        strategy.set_decision_log(DecisionLog::new(Some(path.clone())));
+        let status = StatusHandle::new();
+        let report = monitor_and_optimize(&safe_manager, &defi_optimizer, &cross_chain_router, &strategy, &status, &EventSender::disabled(), &DegradationPolicy::default())
+            .await
+            .unwrap();
+
+        // Only the active strategy's decision is acted on
+        assert_eq!(report.decision, CycleDecision::NoAction);
+        assert_eq!(report.best_pool.unwrap().pool.chain, "Ethereum");
+        assert!(report.route.is_none());
+        assert!(cross_chain_router.tracker().all().is_empty());
+        assert_eq!(status.snapshot().portfolio.current_chain(), HOME_CHAIN);
+
+        let decided: Vec<(&str, bool, &CycleDecision)> =
+            report.strategies.iter().map(|s| (s.name.as_str(), s.active, &s.decision)).collect();
+        assert_eq!(
+            decided,
+            vec![
+                ("conservative", true, &CycleDecision::NoAction),
+                ("default", false, &CycleDecision::NoAction),
+                ("aggressive", false, &CycleDecision::RouteInitiated),
+            ]
+        );
+        let aggressive = &report.strategies[2];
+        assert_eq!(aggressive.best_pool.as_ref().unwrap().pool.chain, "Arbitrum");
+        assert_eq!(aggressive.inputs.as_ref().unwrap().amount, Some(0.891));
+        assert_eq!(report.strategies[1].reason, "Arbitrum beats Ethereum by 2.70 APY points, 5.00 required");
+
+        let records = decision_log::read(&path).unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].decision, CycleDecision::NoAction);
+        assert_eq!(records[0].strategies[2].decision, CycleDecision::RouteInitiated);
+        let standings = status.snapshot().strategies.standings();
+        assert_eq!(standings.iter().find(|s| s.name == "aggressive").unwrap().position, "Arbitrum");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_critical_balance_blocks_routing() {
+        // Enough to route 1 ETH, but critical against a 10 ETH minimum
+        let node = slow_node("0xde0b6b3a7640000", Duration::from_millis(10)).await;
+        let (_api, defi_optimizer) = slow_pool_api("Arbitrum", Duration::from_millis(10)).await;
+        let provider = test_provider(&node.uri());
+        let mut safe_manager = SafeManager::new(get_test_address(), provider).unwrap();
+        safe_manager.set_min_balance(U256::exp10(19));
+        let cross_chain_router = CrossChainRouter::new();
+        let status = StatusHandle::new();
+
+        let (events, mut rx) = EventSender::channel(16);
+        let report =
+            monitor_and_optimize(&safe_manager, &defi_optimizer, &cross_chain_router, &test_strategy(), &status, &events, &skip_pools())
+                .await
+                .unwrap();
+        assert_eq!(report.decision, CycleDecision::Blocked("critical balance".to_string()));
+        assert!(report.route.is_none());
+        assert!(cross_chain_router.tracker().all().is_empty());
+
+        let snapshot = status.snapshot();
+        assert_eq!(snapshot.balance_status, BalanceStatus::Critical);
+        assert_eq!(snapshot.last_action.as_deref(), Some("critical balance: would route to Arbitrum"));
+        let mut kinds = Vec::new();
+        while let Ok(event) = rx.try_recv() {
+            kinds.push(event.kind());
+        }
+        assert!(kinds.contains(&"critical_balance"), "{:?}", kinds);
+        assert!(!kinds.contains(&"rebalance_decided"), "{:?}", kinds);
+    }
+
+    #[tokio::test]
+    async fn test_run_modes_on_full_cycle() {
+        let node = slow_node("0xde0b6b3a7640000", Duration::ZERO).await;
+        let (_api, defi_optimizer) = slow_pool_api("Arbitrum", Duration::ZERO).await;
+
+        // (mode, bridge executions, recorded action, decision)
+        for (mode, executions, action, decision) in [
+            (RunMode::Observe, 0, "observe: would route to Arbitrum", CycleDecision::Blocked("observe mode".to_string())),
+            (RunMode::DryRun, 0, "dry-run route to Arbitrum", CycleDecision::RouteInitiated),
+            (RunMode::Live, 1, "routed to Arbitrum", CycleDecision::RouteInitiated),
+        ] {
+            let provider = test_provider(&node.uri());
+            let mut safe_manager = SafeManager::new(get_test_address(), provider).unwrap();
+            safe_manager.set_mode(mode);
+            let mut cross_chain_router = test_router();
+            cross_chain_router.set_mode(mode);
+            let (events, mut rx) = EventSender::channel(16);
+            cross_chain_router.set_events(events.clone());
+            let status = StatusHandle::new();
+
+            let report = monitor_and_optimize(&safe_manager, &defi_optimizer, &cross_chain_router, &test_strategy(), &status, &events, &DegradationPolicy::default())
+                .await
+                .unwrap();
+            assert_eq!(report.decision, decision, "{}", mode);
+            assert_eq!(report.route.is_some(), mode != RunMode::Observe, "{}", mode);
+            assert_eq!(report.route.and_then(|r| r.route_id).is_some(), mode == RunMode::Live, "{}", mode);
+
+            let mut started = 0;
+            while let Ok(event) = rx.try_recv() {
+                if event.kind() == "route_started" {
+                    started += 1;
+                }
+            }
+            assert_eq!(started, executions, "{}", mode);
+            assert_eq!(cross_chain_router.tracker().all().len(), executions, "{}", mode);
+            let snapshot = status.snapshot();
+            assert_eq!(snapshot.last_action.as_deref(), Some(action));
+            let position = if mode == RunMode::Live { "Arbitrum" } else { HOME_CHAIN };
+            assert_eq!(snapshot.portfolio.current_chain(), position, "{}", mode);
+            assert_eq!(snapshot.portfolio.pending_routes().count(), 0, "{}", mode);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_budget_guard_holds_routes_until_overridden() {
+        let node = slow_node("0xde0b6b3a7640000", Duration::ZERO).await;
+        let (_api, defi_optimizer) = slow_pool_api("Arbitrum", Duration::ZERO).await;
+        let safe_manager = SafeManager::new(get_test_address(), test_provider(&node.uri())).unwrap();
+        let mut cross_chain_router = CrossChainRouter::new();
+        cross_chain_router.set_mode(RunMode::DryRun);
+        let (events, mut rx) = EventSender::channel(16);
+        let status = StatusHandle::new();
+        let store = storage::MemoryStore::new();
+        let strategy = test_strategy();
+        let policy = DegradationPolicy::default();
+        let cycle = || monitor_and_optimize(&safe_manager, &defi_optimizer, &cross_chain_router, &strategy, &status, &events, &policy);
+
+        // Fees and no yield yet: the guard engages once and routes are only reported
+        store.record_transaction(&storage::tests::transaction(unix_now(), 1_000_000_000_000_000)).await.unwrap();
+        check_budget(&store, &status, &events, 0.75).await;
+        check_budget(&store, &status, &events, 0.75).await;
+        let report = cycle().await.unwrap();
+        assert_eq!(report.decision, CycleDecision::Blocked("budget exceeded".to_string()));
+        assert!(report.route.is_none());
+        assert_eq!(status.snapshot().last_action.as_deref(), Some("budget: would route to Arbitrum"));
+        let kinds: Vec<&str> = std::iter::from_fn(|| rx.try_recv().ok()).map(|e| e.kind()).collect();
+        assert_eq!(kinds, ["budget_exceeded"]);
+
+        // The operator lets routes through while the ratio stays high
+        status.update(|s| assert!(s.budget.override_guard()));
+        check_budget(&store, &status, &events, 0.75).await;
+        assert_eq!(cycle().await.unwrap().decision, CycleDecision::RouteInitiated);
+
+        // Enough projected yield brings the ratio back under the limit
+        let now = unix_now();
+        let held = |timestamp, flow| YieldSnapshot {
+            timestamp,
+            chain: "Arbitrum".to_string(),
+            protocol: None,
+            value: 10.0,
+            flow,
+            fees: 0.0,
+            projected_apy: Some(7.3),
+        };
+        status.update(|s| {
+            s.yields.observe(&held(now - budget::WINDOW_SECS, 10.0));
+            s.yields.observe(&held(now, 0.0));
+        });
+        check_budget(&store, &status, &events, 0.75).await;
+        let budget = status.snapshot().budget;
+        assert!(!budget.engaged && !budget.overridden, "{:?}", budget);
+        assert!(std::iter::from_fn(|| rx.try_recv().ok()).any(|e| e.kind() == "budget_recovered"));
+    }
+
+    #[tokio::test]
+    async fn test_operator_decline_is_recorded() {
+        let node = slow_node("0xde0b6b3a7640000", Duration::ZERO).await;
+        let (_api, defi_optimizer) = slow_pool_api("Arbitrum", Duration::ZERO).await;
+        let safe_manager = SafeManager::new(get_test_address(), test_provider(&node.uri())).unwrap();
+        let store = Arc::new(storage::MemoryStore::new());
+        let mut strategy = Strategy::from_config(&StrategyConfig::default(), &RouterConfig::default(), store.clone());
+        strategy.set_approver(Approver::with_reader(&b"n\ny\n"[..], Duration::from_secs(5)));
+
+        let cross_chain_router = CrossChainRouter::new();
+        let status = StatusHandle::new();
+        let report = monitor_and_optimize(&safe_manager, &defi_optimizer, &cross_chain_router, &strategy, &status, &EventSender::disabled(), &DegradationPolicy::default())
+            .await
+            .unwrap();
+        assert_eq!(report.decision, CycleDecision::Declined("declined by operator".to_string()));
+        assert!(report.route.is_none());
+        assert!(cross_chain_router.tracker().all().is_empty());
+        persist_cycle(store.as_ref(), &status, &Ok(()), true).await;
+        let history = store.recent_cycles(1).await.unwrap();
+        assert_eq!(history[0].action, "operator declined route to Arbitrum");
+
+        // The next answer approves
+        let report = monitor_and_optimize(&safe_manager, &defi_optimizer, &cross_chain_router, &strategy, &status, &EventSender::disabled(), &DegradationPolicy::default())
+            .await
+            .unwrap();
+        assert_eq!(report.decision, CycleDecision::RouteInitiated);
+        assert_eq!(cross_chain_router.tracker().all().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_cycle_timeout_names_hung_phase() {
+        let fast_node = slow_node("0xde0b6b3a7640000", Duration::ZERO).await;
+        let hung_node = slow_node("0xde0b6b3a7640000", Duration::from_secs(60)).await;
+        let (_fast_api, fast_pools) = slow_pool_api("Arbitrum", Duration::ZERO).await;
+        let (_hung_api, hung_pools) = slow_pool_api("Arbitrum", Duration::from_secs(60)).await;
+
+        // The live route simulation takes 3s, longer than the whole cycle may run
+        for (node, defi_optimizer, mode, timeout, phase) in [
+            (&hung_node, &fast_pools, RunMode::DryRun, Duration::from_secs(1), "balance"),
+            (&fast_node, &hung_pools, RunMode::DryRun, Duration::from_secs(1), "pools"),
+            (&fast_node, &fast_pools, RunMode::Live, Duration::from_secs(2), "route"),
+        ] {
+            let mut safe_manager = SafeManager::new(get_test_address(), test_provider(&node.uri())).unwrap();
+            safe_manager.set_mode(mode);
+            let mut cross_chain_router = CrossChainRouter::new();
+            cross_chain_router.set_mode(mode);
+            let status = StatusHandle::new();
+            let events = EventSender::disabled();
+            let strategy = test_strategy();
+
+            let started = Instant::now();
+            let policy = DegradationPolicy::default();
+            let cycle = monitor_and_optimize(&safe_manager, defi_optimizer, &cross_chain_router, &strategy, &status, &events, &policy);
+            let err = with_cycle_timeout(&status, timeout, cycle).await.unwrap_err();
+            assert!(started.elapsed() < timeout, "{}: {:?}", phase, started.elapsed());
+            match err.downcast_ref::<CycleError>() {
+                Some(CycleError::PhaseTimedOut { phase: timed_out, .. }) => assert_eq!(*timed_out, phase),
+                other => panic!("{}: unexpected error {:?}", phase, other),
+            }
+            assert_eq!(Outcome::of(&Err::<(), _>(err)), Outcome::Failure(crate::scheduler::FailureClass::Other));
+            let snapshot = status.snapshot();
+            assert!(snapshot.active_phases.is_empty(), "{:?}", snapshot.active_phases);
+            assert_eq!(snapshot.cycle_deadline, None);
+
+            // The interrupted route stays pending in the portfolio and in storage
+            let store = storage::MemoryStore::new();
+            keep_routes_resumable(&cross_chain_router.tracker(), &status, &store).await;
+            let in_flight = cross_chain_router.tracker().in_flight();
+            assert_eq!(in_flight.len(), usize::from(phase == "route"), "{}", phase);
+            let pending: Vec<String> = status.snapshot().portfolio.pending_routes().map(str::to_string).collect();
+            assert_eq!(pending, in_flight.iter().map(|r| r.route_id.clone()).collect::<Vec<_>>());
+            assert_eq!(store.routes_between(0, u64::MAX).await.unwrap().len(), in_flight.len());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_kill_switch_toggled_mid_run() {
+        let node = slow_node("0xde0b6b3a7640000", Duration::ZERO).await;
+        let (_api, defi_optimizer) = slow_pool_api("Arbitrum", Duration::ZERO).await;
+        let path = std::env::temp_dir().join(format!("asam-pause-{}", std::process::id()));
+        let kill_switch = KillSwitch::from_config(&crate::config::KillSwitchConfig { path: Some(path.clone()), paused: false });
+        let (events, mut rx) = EventSender::channel(32);
+        kill_switch.set_events(events.clone());
+
+        let mut safe_manager = SafeManager::new(get_test_address(), test_provider(&node.uri())).unwrap();
+        safe_manager.set_events(events.clone());
+        safe_manager.set_kill_switch(kill_switch.clone());
+        let mut cross_chain_router = test_router();
+        cross_chain_router.set_events(events.clone());
+        cross_chain_router.set_kill_switch(kill_switch.clone());
+        let mut strategy = test_strategy();
+        strategy.set_kill_switch(kill_switch.clone());
+        let status = StatusHandle::new();
+        let mut kinds = || std::iter::from_fn(|| rx.try_recv().ok()).map(|e| e.kind()).collect::<Vec<_>>();
+
+        std::fs::write(&path, b"").unwrap();
+        for _ in 0..2 {
+            let report = monitor_and_optimize(&safe_manager, &defi_optimizer, &cross_chain_router, &strategy, &status, &events, &DegradationPolicy::default())
+                .await
+                .unwrap();
+            assert_eq!(report.decision, CycleDecision::Blocked("paused".to_string()));
+            assert!(report.route.is_none());
+            // Telemetry keeps flowing while paused
+            assert_eq!(report.balance, U256::exp10(18));
+            assert_eq!(report.decision_inputs.unwrap().candidate_apy, 5.2);
+        }
+        let tx = crate::agents::safe_manager::SafeTransaction {
+            to: get_test_address(),
+            value: U256::one(),
+            data: vec![],
+            operation: 0,
+            safe_tx_gas: U256::zero(),
+            nonce: None,
+        };
+        assert!(safe_manager.execute_transaction(tx).await.unwrap_err().to_string().contains("paused"));
+        assert!(cross_chain_router.route_funds(1.0, "Ethereum", "Arbitrum").await.is_err());
+        assert!(cross_chain_router.tracker().all().is_empty());
+        assert_eq!(status.snapshot().last_action.as_deref(), Some("paused"));
+        assert_eq!(kinds(), vec!["paused"]);
+
+        std::fs::remove_file(&path).unwrap();
+        let report = monitor_and_optimize(&safe_manager, &defi_optimizer, &cross_chain_router, &strategy, &status, &events, &DegradationPolicy::default())
+            .await
+            .unwrap();
+        assert_eq!(report.decision, CycleDecision::RouteInitiated);
+        assert_eq!(cross_chain_router.tracker().all().len(), 1);
+        let kinds = kinds();
+        assert_eq!(kinds.first(), Some(&"resumed"));
+        assert!(kinds.contains(&"route_started"));
+    }
+
+    #[tokio::test]
+    async fn test_reloaded_config_reaches_components() {
+        let hook = MockServer::start().await;
+        Mock::given(method("POST")).respond_with(ResponseTemplate::new(200)).expect(1).mount(&hook).await;
+        let mut running = Config::default();
+        running.optimizer.use_mock = true;
+        running.safe.rpc_url = Some("http://localhost:8545".to_string());
+        running.safe.address = Some(format!("{:?}", get_test_address()));
+        let mut safe_manager = SafeManager::from_config(&running.safe, test_provider("http://localhost:8545")).unwrap();
+        safe_manager.watch_chain("Arbitrum", test_provider("http://localhost:8547"), running.safe.min_balance());
+        let mut defi_optimizer = DefiOptimizer::from_config(&running.optimizer);
+        let mut cross_chain_router = CrossChainRouter::from_config(&running.router);
+        let notifier = SwappableNotifier::new(Arc::new(CompositeNotifier::new()));
+
+        let mut loaded = running.clone();
+        loaded.optimizer.min_apy = 5.0;
+        loaded.safe.min_balance_eth = 0.5;
+        loaded.router.chains[1].active = false;
+        loaded.router.chains[1].min_balance_eth = Some(0.2);
+        loaded.notify.webhook.url = Some(hook.uri());
+        loaded.safe.rpc_url = Some("http://localhost:8546".to_string());
+        loaded.storage.path = Some("other.db".into());
+        let reload = reload::Reload::new(&running, &loaded);
+        apply_config(
+            &reload.config,
+            &mut safe_manager,
+            &mut defi_optimizer,
+            &mut cross_chain_router,
+            &notifier,
+            get_test_address(),
+            &DeliveryFailures::default(),
+        );
+
+        assert_eq!(safe_manager.min_balance(), ethers::utils::parse_ether(0.5).unwrap());
+        assert_eq!(safe_manager.watched_min_balance("Arbitrum"), Some(ethers::utils::parse_ether(0.2).unwrap()));
+        assert!(!cross_chain_router.is_chain_active("Arbitrum"));
+        assert!(cross_chain_router.is_chain_active("Optimism"));
+        let pools = defi_optimizer.get_ranked_pools().await.unwrap();
+        assert_eq!(pools.len(), 1, "{:?}", pools);
+        notifier.notify(&AgentEvent::TestMessage).await.unwrap();
+        // Restart-only settings keep their running values
+        assert_eq!(reload.rejected, vec!["safe.rpc_url", "storage.path"]);
+        assert_eq!(reload.config.safe.rpc_url, running.safe.rpc_url);
+        assert_eq!(reload.config.storage.path, None);
+    }
+
+    #[tokio::test]
+    async fn test_runner_cycles_and_shuts_down() {
+        let node = slow_node("0xde0b6b3a7640000", Duration::ZERO).await; // 1 ETH
+        let mut config = Config::default();
+        config.safe.rpc_url = Some(node.uri());
+        config.safe.address = Some(format!("{:?}", get_test_address()));
+        config.optimizer.use_mock = true;
+        config.runtime.mode = Some(RunMode::DryRun);
+        config.runtime.cycle_interval_secs = 3600;
+        // Low, so the first balance check raises an alert
+        config.safe.min_balance_eth = 1.5;
+        config.prices.source = crate::config::PriceSourceKind::None;
+        let runner = Runner::new(config).unwrap();
+        let mut events = runner.events();
+        let shutdown = CancellationToken::new();
+
+        let runner = &runner;
+        let host = async {
+            let cycles = |n: u64| async move {
+                while runner.status().cycle < n || runner.status().last_report.is_none() {
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+            };
+            tokio::time::timeout(Duration::from_secs(10), cycles(1)).await.expect("no startup cycle");
+            assert!(runner.trigger_cycle());
+            tokio::time::timeout(Duration::from_secs(10), cycles(2)).await.expect("no triggered cycle");
+            shutdown.cancel();
+        };
+        let (result, ()) = tokio::join!(runner.run(shutdown.clone()), host);
+        result.unwrap();
+
+        let report = runner.status().last_report.unwrap();
+        assert_eq!(report.trigger, CycleTrigger::Api);
+        let mut kinds = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            kinds.push(event.kind());
+        }
+        assert!(kinds.contains(&"below_minimum"), "{:?}", kinds);
+        // A runner runs once; the loop is gone
+        assert!(runner.run(CancellationToken::new()).await.is_err());
+        assert!(!runner.trigger_cycle());
+    }
+
+    /// The decision records of the first `cycles` cycles of a live simulated run.
+    async fn simulated_decisions(name: &str, seed: u64, cycles: u64) -> Vec<String> {
+        let path = std::env::temp_dir().join(format!("asam-simulated-{}-{}.jsonl", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut config = Config::default();
+        config.safe.address = Some(format!("{:?}", get_test_address()));
+        config.runtime.simulation = SimulationMode::Deterministic;
+        config.runtime.simulation_seed = seed;
+        config.runtime.mode = Some(RunMode::Live);
+        config.storage.decision_log_path = Some(path.clone());
+        let runner = Runner::new(config).unwrap();
+        let shutdown = CancellationToken::new();
+
+        let host = async {
+            let done = async {
+                while runner.status().cycle <= cycles {
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                }
+            };
+            tokio::time::timeout(Duration::from_secs(cycles * 600), done).await.expect("simulation stalled");
+            shutdown.cancel();
+        };
+        let (result, ()) = tokio::join!(runner.run(shutdown.clone()), host);
+        result.unwrap();
+
+        let log = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        log.lines()
+            .filter(|line| serde_json::from_str::<DecisionRecord>(line).unwrap().cycle_id <= cycles)
+            .map(str::to_string)
+            .collect()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_simulated_runs_are_reproducible() {
+        // The whole two weeks of the demo scenario
+        let first = simulated_decisions("first", 42, 56).await;
+        let second = simulated_decisions("second", 42, 56).await;
+        assert_eq!(first.len(), 56);
+        assert_eq!(first, second);
+        assert!(first.iter().any(|line| line.contains(r#""decision":{"kind":"route_initiated"}"#)), "{:#?}", first);
+
+        let reseeded = simulated_decisions("reseeded", 7, 56).await;
+        assert_ne!(first, reseeded);
+    }
+
+    #[tokio::test]
+    async fn test_testnet_profile_refuses_mainnet_rpc() {
+        // The node answers 0x1 to every call, eth_chainId included
+        let node = slow_node("0x1", Duration::ZERO).await;
+        let mut config = Config::default();
+        config.safe.rpc_url = Some(node.uri());
+        connect(&config).await.unwrap();
+
+        config.profile = Profile::Testnet;
+        let err = connect(&config).await.unwrap_err();
+        assert!(err.to_string().contains("chain id 1"), "{}", err);
+    }
+
+    #[test]
+    fn test_balance_event_transitions() {
+        let addr = get_test_address();
+        let wei = U256::from(1);
+        let event = |from, to| balance_event(from, to, HOME_CHAIN, addr, wei).map(|e| e.kind());
+
+        assert_eq!(event(BalanceStatus::Unknown, BalanceStatus::Healthy), None);
+        assert_eq!(event(BalanceStatus::Healthy, BalanceStatus::Low), Some("below_minimum"));
+        assert_eq!(event(BalanceStatus::Low, BalanceStatus::Low), None);
+        assert_eq!(event(BalanceStatus::Low, BalanceStatus::Critical), Some("critical_balance"));
+        assert_eq!(event(BalanceStatus::Critical, BalanceStatus::Low), None);
+        assert_eq!(event(BalanceStatus::Critical, BalanceStatus::Healthy), Some("recovered"));
+    }
+}
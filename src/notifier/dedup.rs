@@ -0,0 +1,223 @@
+//! Suppression of repeated error alerts.
+//!
+//! The first [`AgentEvent::ErrorRaised`] of a category goes out right away and opens
+//! a suppression window. Further errors of that category inside the window are only
+//! counted; when the window closes, or the category clears first, the count goes
+//! out as one [`AgentEvent::ErrorRepeated`]. [`AgentEvent::ErrorCleared`] always
+//! goes out. Other events pass through untouched.
+//!
+//! [`RebalanceCooldown`] separately drops an [`AgentEvent::RebalanceDecided`] for a
+//! pool that was alerted about within its window.
+
+use log::debug;
+use std::collections::BTreeMap;
+use std::time::Duration;
+use tokio::time::Instant;
+
+use super::AgentEvent;
+use crate::errors::ErrorCategory;
+use crate::recent_actions::{rebalance_alert_key, RecentAction, RecentActions};
+
+/// A category that failed and has not cleared yet.
+#[derive(Debug, Clone, Copy)]
+struct Failing {
+    /// When the current suppression window closes; unset between windows.
+    window_ends: Option<Instant>,
+    suppressed: u64,
+}
+
+#[derive(Debug)]
+pub struct AlertDeduplicator {
+    window: Duration,
+    failing: BTreeMap<ErrorCategory, Failing>,
+}
+
+impl AlertDeduplicator {
+    /// A zero `window` lets every alert through.
+    pub fn new(window: Duration) -> Self {
+        Self { window, failing: BTreeMap::new() }
+    }
+
+    /// Returns the events to deliver for `event`, preceded by the summaries of any
+    /// windows that closed by `now`.
+    pub fn filter(&mut self, event: AgentEvent, now: Instant) -> Vec<AgentEvent> {
+        let mut out = self.expire(now);
+        match event {
+            AgentEvent::ErrorRaised { category, .. } => {
+                let failing = self.failing.entry(category).or_insert(Failing { window_ends: None, suppressed: 0 });
+                if failing.window_ends.is_some() {
+                    failing.suppressed += 1;
+                } else {
+                    if !self.window.is_zero() {
+                        failing.window_ends = Some(now + self.window);
+                    }
+                    out.push(event);
+                }
+            }
+            AgentEvent::ErrorCleared { category } => {
+                if let Some(failing) = self.failing.remove(&category) {
+                    out.extend(summary(category, failing));
+                }
+                out.push(event);
+            }
+            event => out.push(event),
+        }
+        out
+    }
+
+    /// Closes the windows that ended by `now`, returning their summaries.
+    pub fn expire(&mut self, now: Instant) -> Vec<AgentEvent> {
+        let mut out = Vec::new();
+        for (category, failing) in &mut self.failing {
+            if failing.window_ends.is_some_and(|ends| ends <= now) {
+                out.extend(summary(*category, *failing));
+                *failing = Failing { window_ends: None, suppressed: 0 };
+            }
+        }
+        out
+    }
+
+    /// When the next open window closes.
+    pub fn next_expiry(&self) -> Option<Instant> {
+        self.failing.values().filter_map(|failing| failing.window_ends).min()
+    }
+}
+
+fn summary(category: ErrorCategory, failing: Failing) -> Option<AgentEvent> {
+    (failing.suppressed > 0).then_some(AgentEvent::ErrorRepeated { category, count: failing.suppressed })
+}
+
+/// Holds back repeated rebalance alerts for the same pool.
+#[derive(Clone)]
+pub struct RebalanceCooldown {
+    actions: RecentActions,
+    window: Duration,
+}
+
+impl RebalanceCooldown {
+    /// A zero `window` lets every alert through.
+    pub fn new(actions: RecentActions, window: Duration) -> Self {
+        Self { actions, window }
+    }
+
+    /// Whether `event` should go out at unix time `now`; records the alerts let through.
+    pub async fn admit(&self, event: &AgentEvent, now: u64) -> bool {
+        let AgentEvent::RebalanceDecided { protocol, chain, .. } = event else {
+            return true;
+        };
+        if self.window.is_zero() {
+            return true;
+        }
+        let key = rebalance_alert_key(protocol, chain);
+        if let Some(last) = self.actions.within(&key, self.window, now) {
+            debug!("Rebalance to {} on {} already alerted at {}, not alerting again", protocol, chain, last.at);
+            return false;
+        }
+        self.actions.record(RecentAction { key, at: now, amount: None }).await;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WINDOW: Duration = Duration::from_secs(30 * 60);
+
+    fn raised(category: ErrorCategory) -> AgentEvent {
+        AgentEvent::ErrorRaised { category, detail: "Failed to get balance".to_string() }
+    }
+
+    /// Feeds one error per minute starting at `start` and collects what gets through.
+    fn fail_every_minute(
+        dedup: &mut AlertDeduplicator,
+        category: ErrorCategory,
+        start: Instant,
+        minutes: u64,
+    ) -> Vec<AgentEvent> {
+        (0..minutes)
+            .flat_map(|i| dedup.filter(raised(category), start + Duration::from_secs(60 * i)))
+            .collect()
+    }
+
+    #[test]
+    fn test_repeats_are_summarized_on_recovery() {
+        let start = Instant::now();
+        let mut dedup = AlertDeduplicator::new(WINDOW);
+        let sent = fail_every_minute(&mut dedup, ErrorCategory::RpcTransport, start, 28);
+        assert_eq!(sent, vec![raised(ErrorCategory::RpcTransport)]);
+        assert_eq!(dedup.next_expiry(), Some(start + WINDOW));
+
+        let cleared = AgentEvent::ErrorCleared { category: ErrorCategory::RpcTransport };
+        let sent = dedup.filter(cleared.clone(), start + Duration::from_secs(28 * 60));
+        assert_eq!(
+            sent,
+            vec![AgentEvent::ErrorRepeated { category: ErrorCategory::RpcTransport, count: 27 }, cleared.clone()]
+        );
+        assert_eq!(sent[0].summary(), "Error rpc_transport occurred 27 more times");
+        assert_eq!(dedup.next_expiry(), None);
+
+        // After clearing, the next failure alerts right away again
+        let sent = dedup.filter(raised(ErrorCategory::RpcTransport), start + Duration::from_secs(29 * 60));
+        assert_eq!(sent, vec![raised(ErrorCategory::RpcTransport)]);
+    }
+
+    #[test]
+    fn test_window_close_sends_summary_and_reopens() {
+        let start = Instant::now();
+        let mut dedup = AlertDeduplicator::new(WINDOW);
+        fail_every_minute(&mut dedup, ErrorCategory::DefiApi, start, 10);
+        assert!(dedup.expire(start + WINDOW - Duration::from_secs(1)).is_empty());
+        assert_eq!(
+            dedup.expire(start + WINDOW),
+            vec![AgentEvent::ErrorRepeated { category: ErrorCategory::DefiApi, count: 9 }]
+        );
+        assert_eq!(dedup.next_expiry(), None);
+
+        // Still failing: the next error starts a new window with a fresh alert, and
+        // clearing without repeats sends only the clear
+        let later = start + WINDOW + Duration::from_secs(60);
+        assert_eq!(dedup.filter(raised(ErrorCategory::DefiApi), later), vec![raised(ErrorCategory::DefiApi)]);
+        let cleared = AgentEvent::ErrorCleared { category: ErrorCategory::DefiApi };
+        assert_eq!(dedup.filter(cleared.clone(), later), vec![cleared]);
+    }
+
+    #[test]
+    fn test_categories_and_other_events_are_independent() {
+        let start = Instant::now();
+        let mut dedup = AlertDeduplicator::new(WINDOW);
+        assert_eq!(dedup.filter(raised(ErrorCategory::RpcTransport), start).len(), 1);
+        assert_eq!(dedup.filter(raised(ErrorCategory::BridgeApi), start).len(), 1);
+        assert!(dedup.filter(raised(ErrorCategory::RpcTransport), start).is_empty());
+        assert_eq!(dedup.filter(AgentEvent::Resumed, start), vec![AgentEvent::Resumed]);
+
+        // Without a window nothing is held back
+        let mut dedup = AlertDeduplicator::new(Duration::ZERO);
+        let sent = fail_every_minute(&mut dedup, ErrorCategory::Internal, start, 3);
+        assert_eq!(sent.len(), 3);
+        assert_eq!(dedup.next_expiry(), None);
+    }
+
+    #[tokio::test]
+    async fn test_rebalance_alerts_cool_down_per_pool() {
+        let rebalance = |chain: &str| AgentEvent::RebalanceDecided {
+            protocol: "Aave".to_string(),
+            chain: chain.to_string(),
+            apy: 7.5,
+            score: 31.0,
+        };
+        let hours = |n: u64| n * 60 * 60;
+        let cooldown = RebalanceCooldown::new(RecentActions::new(Duration::from_secs(hours(6))), Duration::from_secs(hours(6)));
+        assert!(cooldown.admit(&rebalance("Arbitrum"), 0).await);
+        assert!(!cooldown.admit(&rebalance("Arbitrum"), hours(6) - 1).await);
+        assert!(cooldown.admit(&rebalance("Optimism"), hours(1)).await);
+        assert!(cooldown.admit(&AgentEvent::Resumed, hours(1)).await);
+        // Allowed again once the window has passed, which starts a new one
+        assert!(cooldown.admit(&rebalance("Arbitrum"), hours(6)).await);
+        assert!(!cooldown.admit(&rebalance("Arbitrum"), hours(7)).await);
+
+        let off = RebalanceCooldown::new(RecentActions::new(Duration::ZERO), Duration::ZERO);
+        assert!(off.admit(&rebalance("Arbitrum"), 0).await);
+        assert!(off.admit(&rebalance("Arbitrum"), 0).await);
+    }
+}
@@ -0,0 +1,428 @@
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use ethers::core::types::{Address, H256};
+use log::{debug, warn};
+use reqwest::header::HeaderMap;
+use reqwest::{Client, StatusCode};
+use serde_json::{json, Value};
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::time::{sleep_until, Instant};
+
+use super::{AgentEvent, Notifier, Severity};
+use crate::budget::BudgetCheck;
+use crate::config::DiscordConfig;
+use crate::portfolio::HOME_CHAIN;
+use crate::secret::Secret;
+use crate::version::{USER_AGENT, VERSION};
+
+/// Discord rejects field values longer than this.
+const MAX_FIELD_LEN: usize = 1024;
+
+/// Posts events to a Discord webhook as rich embeds.
+pub struct DiscordNotifier {
+    client: Client,
+    webhook_url: Secret<String>,
+    min_severity: Severity,
+    explorer_url: String,
+    bridge_explorer_url: String,
+    max_retries: u32,
+    agent: Option<Address>,
+    /// Set when Discord reports the bucket is exhausted; sends wait until then.
+    blocked_until: Mutex<Option<Instant>>,
+}
+
+fn budget_fields(check: &BudgetCheck) -> Vec<(&'static str, String, bool)> {
+    vec![
+        ("Fees", format!("{:.6} ETH", check.costs()), true),
+        ("Realized gain", format!("{:.6} ETH", check.realized_gain), true),
+        ("Projected gain", format!("{:.6} ETH", check.projected_gain), true),
+        (
+            "Cost ratio",
+            format!(
+                "{} (limit {:.2})",
+                check.ratio.map_or("n/a".to_string(), |r| format!("{:.2}", r)),
+                check.max_ratio
+            ),
+            true,
+        ),
+    ]
+}
+
+impl DiscordNotifier {
+    /// Returns `None` when no webhook URL is configured.
+    pub fn from_config(config: &DiscordConfig) -> Option<Self> {
+        Some(Self {
+            client: Client::builder()
+                .timeout(Duration::from_secs(10))
+                .user_agent(USER_AGENT)
+                .build()
+                .unwrap_or_default(),
+            webhook_url: config.webhook_url.clone()?,
+            min_severity: config.min_severity,
+            explorer_url: config.explorer_url.trim_end_matches('/').to_string(),
+            bridge_explorer_url: config.bridge_explorer_url.trim_end_matches('/').to_string(),
+            max_retries: config.max_retries,
+            agent: None,
+            blocked_until: Mutex::new(None),
+        })
+    }
+
+    /// Address shown in the embed footer.
+    pub fn with_agent(mut self, address: Address) -> Self {
+        self.agent = Some(address);
+        self
+    }
+
+    fn color(severity: Severity) -> u32 {
+        match severity {
+            Severity::Info => 0x3498db,
+            Severity::Warning => 0xf1c40f,
+            Severity::Critical => 0xe74c3c,
+        }
+    }
+
+    fn tx_link(&self, hash: &H256) -> String {
+        format!("[{:?}]({}/tx/{:?})", hash, self.explorer_url, hash)
+    }
+
+    fn address_link(&self, address: &Address) -> String {
+        format!("[{:?}]({}/address/{:?})", address, self.explorer_url, address)
+    }
+
+    /// Live route ids are source transaction hashes and link to the bridge explorer.
+    fn route_link(&self, route_id: &str) -> String {
+        match H256::from_str(route_id) {
+            Ok(hash) => format!("[{}]({}/tx/{:?})", route_id, self.bridge_explorer_url, hash),
+            Err(_) => route_id.to_string(),
+        }
+    }
+
+    fn fields(&self, event: &AgentEvent) -> Vec<(&'static str, String, bool)> {
+        let eth = |wei| format!("{} ETH", ethers::utils::format_ether(wei));
+        match event {
+            AgentEvent::CriticalBalance { chain, address, balance_wei, .. }
+            | AgentEvent::BelowMinimum { chain, address, balance_wei }
+            | AgentEvent::Recovered { chain, address, balance_wei } => {
+                let mut fields = vec![
+                    // The explorer only covers the home chain
+                    (
+                        "Address",
+                        if chain == HOME_CHAIN { self.address_link(address) } else { format!("{:?}", address) },
+                        false,
+                    ),
+                    ("Chain", chain.clone(), true),
+                    ("Balance", eth(*balance_wei), true),
+                ];
+                if let AgentEvent::CriticalBalance { funding_request: Some(uri), .. } = event {
+                    fields.push(("Funding request", format!("`{}`", uri), false));
+                }
+                fields
+            }
+            AgentEvent::RebalanceDecided { protocol, chain, apy, score } => vec![
+                ("Protocol", protocol.clone(), true),
+                ("Chain", chain.clone(), true),
+                ("APY", format!("{:.2}%", apy), true),
+                ("Score", format!("{:.2}", score), true),
+            ],
+            AgentEvent::RouteStarted { route_id, amount, source_chain, target_chain } => vec![
+                ("Route", self.route_link(route_id), false),
+                ("Amount", amount.to_string(), true),
+                ("From", source_chain.clone(), true),
+                ("To", target_chain.clone(), true),
+            ],
+            AgentEvent::RouteCompleted { route_id, destination_tx_hash } => {
+                let mut fields = vec![("Route", self.route_link(route_id), false)];
+                if let Some(hash) = destination_tx_hash {
+                    fields.push(("Destination tx", self.tx_link(hash), false));
+                }
+                fields
+            }
+            AgentEvent::RouteFailed { route_id, reason } => vec![
+                ("Route", self.route_link(route_id), false),
+                ("Reason", reason.clone(), false),
+            ],
+            AgentEvent::TransactionExecuted { to, value_wei, tx_hash } => {
+                let mut fields = vec![
+                    ("To", self.address_link(to), false),
+                    ("Value", eth(*value_wei), true),
+                ];
+                if let Some(hash) = tx_hash {
+                    fields.push(("Transaction", self.tx_link(hash), false));
+                }
+                fields
+            }
+            AgentEvent::TransactionProposed { to, value_wei, nonce, safe_tx_hash } => vec![
+                ("To", self.address_link(to), false),
+                ("Value", eth(*value_wei), true),
+                ("Nonce", nonce.to_string(), true),
+                ("safeTxHash", format!("{:?}", safe_tx_hash), false),
+            ],
+            AgentEvent::TransactionFailed { to, value_wei, reason } => vec![
+                ("To", self.address_link(to), false),
+                ("Value", eth(*value_wei), true),
+                ("Reason", reason.clone(), false),
+            ],
+            AgentEvent::SchemaDriftSuspected { source, detail } => vec![
+                ("Source", source.clone(), false),
+                ("Detail", detail.clone(), false),
+            ],
+            AgentEvent::ReconciliationMismatch { chain, recorded, observed } => vec![
+                ("Chain", chain.clone(), true),
+                ("Recorded", format!("{} ETH", recorded), true),
+                ("Observed", format!("{} ETH", observed), true),
+            ],
+            AgentEvent::Paused { source } => vec![("Source", source.clone(), true)],
+            AgentEvent::Resumed | AgentEvent::TestMessage => Vec::new(),
+            AgentEvent::CircuitOpened { component, reason } => vec![
+                ("Component", component.clone(), true),
+                ("Reason", reason.clone(), false),
+            ],
+            AgentEvent::ErrorRaised { category, detail } => vec![
+                ("Category", category.to_string(), true),
+                ("Detail", detail.clone(), false),
+            ],
+            AgentEvent::ErrorRepeated { category, count } => vec![
+                ("Category", category.to_string(), true),
+                ("Repeats", count.to_string(), true),
+            ],
+            AgentEvent::ErrorCleared { category } => vec![("Category", category.to_string(), true)],
+            AgentEvent::DailySummary { strategies, budget, valuation } => {
+                let mut fields: Vec<_> = strategies
+                    .iter()
+                    .map(|s| {
+                        let name = if s.active { "Active strategy" } else { "Strategy" };
+                        let value = format!(
+                            "{}: {:.4}% on {} ({} routes in {} cycles)",
+                            s.name, s.accrued_pct, s.position, s.routes, s.cycles
+                        );
+                        (name, value, false)
+                    })
+                    .collect();
+                if let Some(budget) = budget {
+                    fields.extend(budget_fields(budget));
+                }
+                if let Some(valuation) = valuation {
+                    fields.push(("Portfolio", valuation.describe(), true));
+                }
+                fields
+            }
+            AgentEvent::BudgetExceeded { check } | AgentEvent::BudgetRecovered { check } => budget_fields(check),
+            AgentEvent::DegradationApplied { dependency, fallback, detail } => vec![
+                ("Dependency", dependency.to_string(), true),
+                ("Fallback", fallback.to_string(), true),
+                ("Detail", detail.clone(), false),
+            ],
+            AgentEvent::DegradationCleared { dependency } => vec![("Dependency", dependency.to_string(), true)],
+            AgentEvent::TransferObserved { transfer } => {
+                let mut fields = vec![(
+                    "Amount",
+                    match transfer.token {
+                        Some(token) => format!("{} of {}", transfer.amount, self.address_link(&token)),
+                        None => eth(transfer.amount),
+                    },
+                    true,
+                )];
+                if let Some(from) = &transfer.from {
+                    fields.push(("From", self.address_link(from), false));
+                }
+                if let Some(to) = &transfer.to {
+                    fields.push(("To", self.address_link(to), false));
+                }
+                fields.push(("Block", transfer.block.to_string(), true));
+                if let Some(hash) = &transfer.tx_hash {
+                    fields.push(("Transaction", self.tx_link(hash), false));
+                }
+                fields
+            }
+        }
+    }
+
+    /// Builds the webhook body carrying a single embed for `event`.
+    pub fn build_payload(&self, event: &AgentEvent) -> Value {
+        let title = {
+            let kind = event.kind().replace('_', " ");
+            let mut chars = kind.chars();
+            chars
+                .next()
+                .map(|c| c.to_uppercase().collect::<String>() + chars.as_str())
+                .unwrap_or_default()
+        };
+        let fields: Vec<Value> = self
+            .fields(event)
+            .into_iter()
+            .map(|(name, value, inline)| {
+                let value: String = value.chars().take(MAX_FIELD_LEN).collect();
+                json!({ "name": name, "value": value, "inline": inline })
+            })
+            .collect();
+        let footer = match &self.agent {
+            Some(address) => format!("asam v{} • {:?}", VERSION, address),
+            None => format!("asam v{}", VERSION),
+        };
+
+        json!({
+            "username": "ASAM",
+            "embeds": [{
+                "title": title,
+                "description": event.summary(),
+                "color": Self::color(event.severity()),
+                "fields": fields,
+                "footer": { "text": footer },
+            }]
+        })
+    }
+
+    /// Seconds to wait from a `Retry-After` style header value.
+    fn header_secs(headers: &HeaderMap, name: &str) -> Option<Duration> {
+        headers
+            .get(name)?
+            .to_str()
+            .ok()?
+            .parse::<f64>()
+            .ok()
+            .filter(|s| s.is_finite() && *s >= 0.0)
+            .map(Duration::from_secs_f64)
+    }
+
+    fn block_for(&self, wait: Duration) {
+        *self.blocked_until.lock().unwrap_or_else(|e| e.into_inner()) = Some(Instant::now() + wait);
+    }
+}
+
+#[async_trait]
+impl Notifier for DiscordNotifier {
+    async fn notify(&self, event: &AgentEvent) -> Result<()> {
+        if event.severity() < self.min_severity {
+            debug!("Skipping {} event for Discord (below {:?})", event.kind(), self.min_severity);
+            return Ok(());
+        }
+        let payload = self.build_payload(event);
+
+        let mut attempt = 0;
+        loop {
+            let blocked_until = *self.blocked_until.lock().unwrap_or_else(|e| e.into_inner());
+            if let Some(until) = blocked_until {
+                sleep_until(until).await;
+            }
+
+            let response = self
+                .client
+                .post(self.webhook_url.expose())
+                .json(&payload)
+                .send()
+                .await
+                .context("Discord webhook request failed")?;
+            let status = response.status();
+            let headers = response.headers().clone();
+
+            if headers.get("X-RateLimit-Remaining").and_then(|v| v.to_str().ok()) == Some("0") {
+                let reset = Self::header_secs(&headers, "X-RateLimit-Reset-After").unwrap_or(Duration::from_secs(1));
+                debug!("Discord rate limit bucket exhausted, pausing for {:?}", reset);
+                self.block_for(reset);
+            }
+
+            if status.is_success() {
+                debug!("Delivered {} event to Discord", event.kind());
+                return Ok(());
+            }
+            if attempt >= self.max_retries {
+                return Err(anyhow!("Discord webhook returned status {}", status));
+            }
+            attempt += 1;
+
+            if status == StatusCode::TOO_MANY_REQUESTS {
+                let wait = Self::header_secs(&headers, "Retry-After").unwrap_or(Duration::from_secs(1));
+                warn!("Discord rate limited the webhook, retrying in {:?}", wait);
+                self.block_for(wait);
+            } else if status.is_server_error() {
+                let wait = Duration::from_secs(1) * 2u32.saturating_pow(attempt - 1);
+                warn!("Discord returned {}, retrying in {:?}", status, wait);
+                self.block_for(wait);
+            } else {
+                return Err(anyhow!("Discord webhook returned status {}", status));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::method;
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn notifier(url: String) -> DiscordNotifier {
+        DiscordNotifier::from_config(&DiscordConfig {
+            webhook_url: Some(url.into()),
+            ..DiscordConfig::default()
+        })
+        .unwrap()
+        .with_agent(Address::repeat_byte(0x11))
+    }
+
+    #[test]
+    fn test_route_failed_embed() {
+        let hash = H256::repeat_byte(0xab);
+        let event = AgentEvent::RouteFailed {
+            route_id: format!("{:?}", hash),
+            reason: "x".repeat(2000),
+        };
+        let payload = notifier("http://localhost/hook".to_string()).build_payload(&event);
+        let embed = &payload["embeds"][0];
+
+        assert_eq!(embed["title"], "Route failed");
+        assert_eq!(embed["color"], 0xe74c3c);
+        assert!(embed["description"].as_str().unwrap().starts_with("Route 0xabab"));
+
+        let fields = embed["fields"].as_array().unwrap();
+        assert_eq!(fields.len(), 2);
+        assert_eq!(fields[0]["name"], "Route");
+        assert_eq!(
+            fields[0]["value"],
+            format!("[{:?}](https://scan.li.fi/tx/{:?})", hash, hash)
+        );
+        assert_eq!(fields[1]["name"], "Reason");
+        assert_eq!(fields[1]["value"].as_str().unwrap().len(), MAX_FIELD_LEN);
+
+        let footer = embed["footer"]["text"].as_str().unwrap();
+        assert!(footer.contains(VERSION));
+        assert!(footer.contains("0x1111111111111111111111111111111111111111"));
+    }
+
+    #[tokio::test]
+    async fn test_retry_after_is_honored() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "0.2"))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(
+                ResponseTemplate::new(204)
+                    .insert_header("X-RateLimit-Remaining", "0")
+                    .insert_header("X-RateLimit-Reset-After", "0.2"),
+            )
+            .mount(&server)
+            .await;
+
+        let notifier = notifier(server.uri());
+        let event = AgentEvent::RouteCompleted {
+            route_id: "sim-1".to_string(),
+            destination_tx_hash: None,
+        };
+
+        let start = std::time::Instant::now();
+        notifier.notify(&event).await.unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(200));
+        assert_eq!(server.received_requests().await.unwrap().len(), 2);
+
+        // The exhausted bucket delays the next message as well
+        let start = std::time::Instant::now();
+        notifier.notify(&event).await.unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(150));
+        assert_eq!(server.received_requests().await.unwrap().len(), 3);
+    }
+}
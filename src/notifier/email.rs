@@ -0,0 +1,261 @@
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::transport::smtp::client::{Tls, TlsParameters};
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use log::{debug, warn};
+use serde_json::Value;
+use std::time::Duration;
+
+use super::{AgentEvent, DeliveryFailures, Notifier, Severity};
+use crate::util::unix_now;
+use crate::config::{EmailConfig, Profile, SmtpTls};
+
+/// Sends after the first failed attempt.
+const RETRIES: u32 = 2;
+const RETRY_DELAY: Duration = Duration::from_secs(2);
+const SMTP_TIMEOUT: Duration = Duration::from_secs(30);
+/// Key in [`DeliveryFailures`].
+const CHANNEL: &str = "email";
+
+fn eth(wei: ethers::core::types::U256) -> String {
+    let eth = ethers::utils::format_ether(wei);
+    eth.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+/// Subject line, e.g. `[ASAM][CRITICAL] Ethereum balance critical at 0.0005 ETH on mainnet`.
+pub fn subject(event: &AgentEvent, profile: Profile) -> String {
+    let headline = match event {
+        AgentEvent::CriticalBalance { chain, balance_wei, .. } => {
+            format!("{} balance critical at {} ETH", chain, eth(*balance_wei))
+        }
+        AgentEvent::RouteFailed { route_id, .. } => format!("Route {} failed", route_id),
+        AgentEvent::TransactionFailed { to, .. } => format!("Transaction to {:?} failed", to),
+        AgentEvent::Paused { source } => format!("Fund movement paused by {}", source),
+        event => event.summary(),
+    };
+    format!("[ASAM][{}] {} on {}", event.severity().label(), headline, profile)
+}
+
+/// Plain-text body: the summary, then every field of the event.
+pub fn body(event: &AgentEvent, profile: Profile, timestamp: u64) -> String {
+    let mut body = format!(
+        "{}\n\nEvent:    {}\nSeverity: {}\nProfile:  {}\nTime:     {} (unix)\n",
+        event.summary(),
+        event.kind(),
+        event.severity().label(),
+        profile,
+        timestamp
+    );
+    if let Ok(Value::Object(fields)) = serde_json::to_value(event) {
+        let fields: Vec<_> = fields.into_iter().filter(|(key, _)| key != "type").collect();
+        if !fields.is_empty() {
+            body.push('\n');
+        }
+        for (key, value) in fields {
+            let value = match value {
+                Value::String(s) => s,
+                Value::Null => "-".to_string(),
+                value => value.to_string(),
+            };
+            body.push_str(&format!("{}: {}\n", key, value));
+        }
+    }
+    body
+}
+
+/// Emails alerts over SMTP, by default only critical ones.
+///
+/// A failed send is retried twice; after that it counts in [`DeliveryFailures`] and
+/// is returned as an error. Credentials only go to the transport and are never logged.
+pub struct EmailNotifier<T = AsyncSmtpTransport<Tokio1Executor>> {
+    transport: T,
+    from: Mailbox,
+    to: Vec<Mailbox>,
+    min_severity: Severity,
+    profile: Profile,
+    failures: DeliveryFailures,
+}
+
+impl EmailNotifier {
+    /// Returns `None` unless the SMTP host, sender and recipients are configured.
+    pub fn from_config(config: &EmailConfig, profile: Profile, failures: DeliveryFailures) -> Result<Option<Self>> {
+        let Some(host) = config.smtp_host.as_deref() else {
+            return Ok(None);
+        };
+        if config.from.is_none() || config.to.is_empty() {
+            return Ok(None);
+        }
+        let tls = || TlsParameters::new(host.to_string()).context("Invalid SMTP TLS parameters");
+        let (tls, port) = match config.tls {
+            SmtpTls::Starttls => (Tls::Required(tls()?), 587),
+            SmtpTls::Implicit => (Tls::Wrapper(tls()?), 465),
+            SmtpTls::None => (Tls::None, 25),
+        };
+        let mut builder = AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host)
+            .port(config.smtp_port.unwrap_or(port))
+            .tls(tls)
+            .timeout(Some(SMTP_TIMEOUT));
+        if let (Some(username), Some(password)) = (&config.username, &config.password) {
+            builder = builder.credentials(Credentials::new(username.clone(), password.expose().clone()));
+        }
+        Self::with_transport(builder.build(), config, profile, failures).map(Some)
+    }
+}
+
+impl<T> EmailNotifier<T>
+where
+    T: AsyncTransport + Send + Sync,
+    T::Error: std::error::Error + Send + Sync + 'static,
+{
+    /// Sends through `transport` with the addresses and filter from `config`.
+    pub fn with_transport(
+        transport: T,
+        config: &EmailConfig,
+        profile: Profile,
+        failures: DeliveryFailures,
+    ) -> Result<Self> {
+        let parse = |address: &str| {
+            address
+                .parse::<Mailbox>()
+                .with_context(|| format!("Invalid email address '{}'", address))
+        };
+        Ok(Self {
+            transport,
+            from: parse(config.from.as_deref().unwrap_or_default())?,
+            to: config.to.iter().map(|to| parse(to)).collect::<Result<_>>()?,
+            min_severity: config.min_severity,
+            profile,
+            failures,
+        })
+    }
+
+    fn message(&self, event: &AgentEvent) -> Result<Message> {
+        let mut builder = Message::builder().from(self.from.clone()).subject(subject(event, self.profile));
+        for to in &self.to {
+            builder = builder.to(to.clone());
+        }
+        builder
+            .body(body(event, self.profile, unix_now()))
+            .context("Failed to build email")
+    }
+}
+
+#[async_trait]
+impl<T> Notifier for EmailNotifier<T>
+where
+    T: AsyncTransport + Send + Sync,
+    T::Ok: Send,
+    T::Error: std::error::Error + Send + Sync + 'static,
+{
+    async fn notify(&self, event: &AgentEvent) -> Result<()> {
+        if event.severity() < self.min_severity {
+            debug!("Skipping {} event for email (below {:?})", event.kind(), self.min_severity);
+            return Ok(());
+        }
+        let message = self.message(event)?;
+        let mut attempt = 0;
+        loop {
+            match self.transport.send(message.clone()).await {
+                Ok(_) => {
+                    debug!("Sent {} email to {} recipient(s)", event.kind(), self.to.len());
+                    return Ok(());
+                }
+                Err(e) if attempt < RETRIES => {
+                    attempt += 1;
+                    warn!("Email delivery failed (attempt {} of {}): {}", attempt, RETRIES + 1, e);
+                    tokio::time::sleep(RETRY_DELAY * attempt).await;
+                }
+                Err(e) => {
+                    self.failures.record(CHANNEL);
+                    return Err(anyhow!("Email delivery failed after {} attempts: {}", RETRIES + 1, e));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::core::types::{Address, U256};
+    use lettre::transport::stub::AsyncStubTransport;
+
+    fn config() -> EmailConfig {
+        EmailConfig {
+            smtp_host: Some("smtp.example.com".to_string()),
+            from: Some("ASAM <asam@example.com>".to_string()),
+            to: vec!["oncall@example.com".to_string(), "backup@example.com".to_string()],
+            ..EmailConfig::default()
+        }
+    }
+
+    fn critical_balance() -> AgentEvent {
+        AgentEvent::CriticalBalance {
+            chain: "Ethereum".to_string(),
+            address: Address::repeat_byte(0x11),
+            balance_wei: U256::exp10(15) / 2,
+            funding_request: None,
+        }
+    }
+
+    #[test]
+    fn test_subject_and_body() {
+        assert_eq!(
+            subject(&critical_balance(), Profile::Mainnet),
+            "[ASAM][CRITICAL] Ethereum balance critical at 0.0005 ETH on mainnet"
+        );
+        let paused = AgentEvent::Paused { source: "admin".to_string() };
+        assert_eq!(subject(&paused, Profile::Testnet), "[ASAM][CRITICAL] Fund movement paused by admin on testnet");
+
+        let body = body(&critical_balance(), Profile::Mainnet, 1_700_000_000);
+        assert!(body.starts_with("Balance of 0x1111"), "{}", body);
+        assert!(body.contains("Event:    critical_balance\n"), "{}", body);
+        assert!(body.contains("Time:     1700000000 (unix)\n"), "{}", body);
+        assert!(body.contains("chain: Ethereum\n"), "{}", body);
+        assert!(body.contains("balance_wei: 500000000000000\n"), "{}", body);
+        assert!(!body.contains("type:"), "{}", body);
+    }
+
+    #[tokio::test]
+    async fn test_only_critical_events_are_sent() {
+        let notifier =
+            EmailNotifier::with_transport(AsyncStubTransport::new_ok(), &config(), Profile::Mainnet, Default::default())
+                .unwrap();
+        notifier.notify(&AgentEvent::Resumed).await.unwrap();
+        notifier
+            .notify(&AgentEvent::ErrorRaised { category: crate::errors::ErrorCategory::DefiApi, detail: "timeout".into() })
+            .await
+            .unwrap();
+        notifier.notify(&critical_balance()).await.unwrap();
+
+        let messages = notifier.transport.messages().await;
+        assert_eq!(messages.len(), 1);
+        let (envelope, raw) = &messages[0];
+        assert_eq!(envelope.to().len(), 2);
+        assert!(raw.contains("Subject: [ASAM][CRITICAL] Ethereum balance critical at 0.0005 ETH on mainnet"), "{}", raw);
+        assert!(raw.contains("From: ASAM <asam@example.com>"), "{}", raw);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_failures_are_retried_then_counted() {
+        let failures = DeliveryFailures::default();
+        let notifier =
+            EmailNotifier::with_transport(AsyncStubTransport::new_error(), &config(), Profile::Mainnet, failures.clone())
+                .unwrap();
+        let err = notifier.notify(&critical_balance()).await.unwrap_err();
+        assert!(err.to_string().contains("after 3 attempts"), "{}", err);
+        assert_eq!(notifier.transport.messages().await.len(), 3);
+        assert_eq!(failures.counts().get("email"), Some(&1));
+    }
+
+    #[test]
+    fn test_disabled_without_recipients() {
+        let config = EmailConfig { to: Vec::new(), ..config() };
+        assert!(EmailNotifier::from_config(&config, Profile::Mainnet, Default::default()).unwrap().is_none());
+        assert!(EmailNotifier::from_config(&EmailConfig::default(), Profile::Mainnet, Default::default())
+            .unwrap()
+            .is_none());
+    }
+}
@@ -0,0 +1,675 @@
+//! Outbound notifications for significant agent events.
+//!
+//! Components emit [`AgentEvent`]s through an [`EventSender`], which only pushes
+//! onto a bounded queue. A worker task drains the queue into a [`Notifier`], so a
+//! slow or failing endpoint never blocks the monitoring cycle.
+
+pub mod dedup;
+pub mod discord;
+pub mod email;
+pub mod telegram;
+pub mod webhook;
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use ethers::core::types::{Address, H256, U256};
+use log::{debug, error, warn};
+use serde::{Deserialize, Serialize, Serializer};
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio::sync::mpsc::{self, error::TrySendError};
+use tokio::time::{sleep_until, Instant};
+
+use crate::util::unix_now;
+use crate::agents::safe_manager::TransferEvent;
+use crate::budget::BudgetCheck;
+use crate::comparison::StrategyPerformance;
+use crate::config::Fallback;
+use crate::degradation::Dependency;
+use crate::errors::ErrorCategory;
+use crate::valuation::ValuationChange;
+
+pub use dedup::{AlertDeduplicator, RebalanceCooldown};
+pub use discord::DiscordNotifier;
+pub use email::EmailNotifier;
+pub use telegram::TelegramNotifier;
+pub use webhook::WebhookNotifier;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Info,
+    Warning,
+    Critical,
+}
+
+impl Severity {
+    /// Upper-case name used in message titles.
+    pub fn label(self) -> &'static str {
+        match self {
+            Severity::Info => "INFO",
+            Severity::Warning => "WARNING",
+            Severity::Critical => "CRITICAL",
+        }
+    }
+}
+
+/// Events worth telling an operator about.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AgentEvent {
+    /// Balance dropped below the critical threshold.
+    CriticalBalance {
+        chain: String,
+        address: Address,
+        #[serde(serialize_with = "decimal")]
+        balance_wei: U256,
+        /// EIP-681 URI paying what brings the Safe back to its minimum balance.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        funding_request: Option<String>,
+    },
+    /// Balance dropped below the minimum threshold.
+    BelowMinimum {
+        chain: String,
+        address: Address,
+        #[serde(serialize_with = "decimal")]
+        balance_wei: U256,
+    },
+    /// Balance is back above the minimum threshold.
+    Recovered {
+        chain: String,
+        address: Address,
+        #[serde(serialize_with = "decimal")]
+        balance_wei: U256,
+    },
+    /// The optimizer picked a pool on another chain and funds will be moved.
+    RebalanceDecided {
+        protocol: String,
+        chain: String,
+        apy: f64,
+        score: f64,
+    },
+    RouteStarted {
+        route_id: String,
+        amount: f64,
+        source_chain: String,
+        target_chain: String,
+    },
+    RouteCompleted {
+        route_id: String,
+        destination_tx_hash: Option<H256>,
+    },
+    RouteFailed {
+        route_id: String,
+        reason: String,
+    },
+    TransactionExecuted {
+        to: Address,
+        #[serde(serialize_with = "decimal")]
+        value_wei: U256,
+        tx_hash: Option<H256>,
+    },
+    /// A live transaction was signed and proposed; the other owners confirm it.
+    TransactionProposed {
+        to: Address,
+        #[serde(serialize_with = "decimal")]
+        value_wei: U256,
+        #[serde(serialize_with = "decimal")]
+        nonce: U256,
+        safe_tx_hash: H256,
+    },
+    /// A live transaction could not be executed.
+    TransactionFailed {
+        to: Address,
+        #[serde(serialize_with = "decimal")]
+        value_wei: U256,
+        reason: String,
+    },
+    /// An external data source answered with an unexpected shape.
+    SchemaDriftSuspected {
+        source: String,
+        detail: String,
+    },
+    /// The recorded portfolio disagrees with the on-chain balance.
+    ReconciliationMismatch {
+        chain: String,
+        recorded: f64,
+        observed: f64,
+    },
+    /// The kill switch turned on; fund movement stops until it is turned off.
+    Paused {
+        source: String,
+    },
+    /// The kill switch turned off.
+    Resumed,
+    /// Sent by `asam check --notify` to confirm delivery works.
+    TestMessage,
+    /// A cycle failed. Repeats of the same category are held back by the [`AlertDeduplicator`].
+    ErrorRaised {
+        category: ErrorCategory,
+        detail: String,
+    },
+    /// How often a category failed again after its first alert.
+    ErrorRepeated {
+        category: ErrorCategory,
+        count: u64,
+    },
+    /// A cycle succeeded after failing with this category.
+    ErrorCleared {
+        category: ErrorCategory,
+    },
+    /// Once a day: the hypothetical performance of the configured strategies, best
+    /// first, the latest budget check and the portfolio's USD value over the day.
+    DailySummary {
+        strategies: Vec<StrategyPerformance>,
+        budget: Option<BudgetCheck>,
+        valuation: Option<ValuationChange>,
+    },
+    /// Fees passed the allowed share of the yield; routes are held back until the
+    /// ratio recovers or an operator overrides the guard.
+    BudgetExceeded {
+        check: BudgetCheck,
+    },
+    /// The fee ratio is back under the limit and routes resume.
+    BudgetRecovered {
+        check: BudgetCheck,
+    },
+    /// A dependency is down and cycles go on with a different fallback than before.
+    DegradationApplied {
+        dependency: Dependency,
+        fallback: Fallback,
+        detail: String,
+    },
+    /// A dependency answered again after cycles fell back for it.
+    DegradationCleared {
+        dependency: Dependency,
+    },
+    /// Funds moved into or out of the Safe.
+    TransferObserved {
+        transfer: TransferEvent,
+    },
+    /// A component stopped calling a failing dependency. Nothing trips breakers yet.
+    #[allow(dead_code)]
+    CircuitOpened {
+        component: String,
+        reason: String,
+    },
+}
+
+fn decimal<S: Serializer>(value: &U256, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+    serializer.serialize_str(&value.to_string())
+}
+
+impl AgentEvent {
+    /// Snake-case event name, as used in the `type` field of the JSON payload.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            AgentEvent::CriticalBalance { .. } => "critical_balance",
+            AgentEvent::BelowMinimum { .. } => "below_minimum",
+            AgentEvent::Recovered { .. } => "recovered",
+            AgentEvent::RebalanceDecided { .. } => "rebalance_decided",
+            AgentEvent::RouteStarted { .. } => "route_started",
+            AgentEvent::RouteCompleted { .. } => "route_completed",
+            AgentEvent::RouteFailed { .. } => "route_failed",
+            AgentEvent::TransactionExecuted { .. } => "transaction_executed",
+            AgentEvent::TransactionProposed { .. } => "transaction_proposed",
+            AgentEvent::TransactionFailed { .. } => "transaction_failed",
+            AgentEvent::SchemaDriftSuspected { .. } => "schema_drift_suspected",
+            AgentEvent::ReconciliationMismatch { .. } => "reconciliation_mismatch",
+            AgentEvent::Paused { .. } => "paused",
+            AgentEvent::Resumed => "resumed",
+            AgentEvent::TestMessage => "test_message",
+            AgentEvent::ErrorRaised { .. } => "error_raised",
+            AgentEvent::ErrorRepeated { .. } => "error_repeated",
+            AgentEvent::ErrorCleared { .. } => "error_cleared",
+            AgentEvent::DailySummary { .. } => "daily_summary",
+            AgentEvent::BudgetExceeded { .. } => "budget_exceeded",
+            AgentEvent::BudgetRecovered { .. } => "budget_recovered",
+            AgentEvent::DegradationApplied { .. } => "degradation_applied",
+            AgentEvent::DegradationCleared { .. } => "degradation_cleared",
+            AgentEvent::TransferObserved { .. } => "transfer_observed",
+            AgentEvent::CircuitOpened { .. } => "circuit_opened",
+        }
+    }
+
+    /// One-line human readable description.
+    pub fn summary(&self) -> String {
+        match self {
+            AgentEvent::CriticalBalance { chain, address, balance_wei, funding_request } => format!(
+                "Balance of {:?} on {} is critically low: {} ETH{}",
+                address,
+                chain,
+                ethers::utils::format_ether(*balance_wei),
+                funding_request.as_ref().map(|uri| format!("; fund it with {}", uri)).unwrap_or_default()
+            ),
+            AgentEvent::BelowMinimum { chain, address, balance_wei } => format!(
+                "Balance of {:?} on {} is below the minimum: {} ETH",
+                address,
+                chain,
+                ethers::utils::format_ether(*balance_wei)
+            ),
+            AgentEvent::Recovered { chain, address, balance_wei } => format!(
+                "Balance of {:?} on {} recovered: {} ETH",
+                address,
+                chain,
+                ethers::utils::format_ether(*balance_wei)
+            ),
+            AgentEvent::RebalanceDecided { protocol, chain, apy, .. } => {
+                format!("Rebalancing into {} on {} ({:.2}% APY)", protocol, chain, apy)
+            }
+            AgentEvent::RouteStarted { route_id, amount, source_chain, target_chain } => format!(
+                "Route {} started: {} from {} to {}",
+                route_id, amount, source_chain, target_chain
+            ),
+            AgentEvent::RouteCompleted { route_id, destination_tx_hash } => match destination_tx_hash {
+                Some(hash) => format!("Route {} completed (destination tx {:?})", route_id, hash),
+                None => format!("Route {} completed", route_id),
+            },
+            AgentEvent::RouteFailed { route_id, reason } => format!("Route {} failed: {}", route_id, reason),
+            AgentEvent::TransactionExecuted { to, value_wei, tx_hash } => format!(
+                "Transaction to {:?} for {} ETH executed{}",
+                to,
+                ethers::utils::format_ether(*value_wei),
+                tx_hash.map(|h| format!(" ({:?})", h)).unwrap_or_default()
+            ),
+            AgentEvent::TransactionProposed { to, value_wei, nonce, safe_tx_hash } => format!(
+                "Transaction to {:?} for {} ETH proposed as Safe nonce {} ({:?}), awaiting confirmations",
+                to,
+                ethers::utils::format_ether(*value_wei),
+                nonce,
+                safe_tx_hash
+            ),
+            AgentEvent::TransactionFailed { to, value_wei, reason } => format!(
+                "Transaction to {:?} for {} ETH failed: {}",
+                to,
+                ethers::utils::format_ether(*value_wei),
+                reason
+            ),
+            AgentEvent::SchemaDriftSuspected { source, detail } => {
+                format!("Unexpected response from {}: {}", source, detail)
+            }
+            AgentEvent::ReconciliationMismatch { chain, recorded, observed } => format!(
+                "Recorded position on {} is {} ETH but the chain shows {} ETH",
+                chain, recorded, observed
+            ),
+            AgentEvent::Paused { source } => {
+                format!("Fund movement paused by {}; monitoring continues", source)
+            }
+            AgentEvent::Resumed => "Fund movement resumed".to_string(),
+            AgentEvent::TestMessage => "Test message from asam check; notifications are working".to_string(),
+            AgentEvent::ErrorRaised { category, detail } => format!("Error {}: {}", category, detail),
+            AgentEvent::ErrorRepeated { category, count } => {
+                format!("Error {} occurred {} more time{}", category, count, if *count == 1 { "" } else { "s" })
+            }
+            AgentEvent::ErrorCleared { category } => format!("Error {} cleared", category),
+            AgentEvent::DailySummary { strategies, budget, valuation } => {
+                let mut parts: Vec<String> = strategies
+                    .iter()
+                    .map(|s| {
+                        format!(
+                            "{}{} {:.4}% on {}",
+                            s.name,
+                            if s.active { " (active)" } else { "" },
+                            s.accrued_pct,
+                            s.position
+                        )
+                    })
+                    .collect();
+                if let Some(budget) = budget {
+                    parts.push(format!("budget: {}", budget.describe()));
+                }
+                if let Some(valuation) = valuation {
+                    parts.push(format!("portfolio: {}", valuation.describe()));
+                }
+                format!("Daily summary: {}", parts.join(", "))
+            }
+            AgentEvent::BudgetExceeded { check } => {
+                format!("Routing held back, costs outweigh yield: {}", check.describe())
+            }
+            AgentEvent::BudgetRecovered { check } => format!("Routing resumed, costs back in budget: {}", check.describe()),
+            AgentEvent::CircuitOpened { component, reason } => {
+                format!("Circuit opened for {}: {}", component, reason)
+            }
+            AgentEvent::DegradationApplied { dependency, fallback, detail } => {
+                format!("{} is down, cycles fall back to {}: {}", dependency, fallback, detail)
+            }
+            AgentEvent::DegradationCleared { dependency } => format!("{} is answering again", dependency),
+            AgentEvent::TransferObserved { transfer } => format!("Transfer of {}", transfer.describe()),
+        }
+    }
+
+    pub fn severity(&self) -> Severity {
+        match self {
+            AgentEvent::CriticalBalance { .. }
+            | AgentEvent::RouteFailed { .. }
+            | AgentEvent::TransactionFailed { .. }
+            | AgentEvent::Paused { .. } => Severity::Critical,
+            AgentEvent::BelowMinimum { .. }
+            | AgentEvent::SchemaDriftSuspected { .. }
+            | AgentEvent::ReconciliationMismatch { .. }
+            | AgentEvent::Resumed
+            | AgentEvent::CircuitOpened { .. }
+            | AgentEvent::ErrorRaised { .. }
+            | AgentEvent::ErrorRepeated { .. }
+            | AgentEvent::BudgetExceeded { .. }
+            | AgentEvent::DegradationApplied { .. } => Severity::Warning,
+            AgentEvent::Recovered { .. }
+            | AgentEvent::RebalanceDecided { .. }
+            | AgentEvent::RouteStarted { .. }
+            | AgentEvent::RouteCompleted { .. }
+            | AgentEvent::TransactionExecuted { .. }
+            | AgentEvent::TransactionProposed { .. }
+            | AgentEvent::TestMessage
+            | AgentEvent::DailySummary { .. }
+            | AgentEvent::BudgetRecovered { .. }
+            | AgentEvent::ErrorCleared { .. }
+            | AgentEvent::DegradationCleared { .. }
+            | AgentEvent::TransferObserved { .. } => Severity::Info,
+        }
+    }
+}
+
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    async fn notify(&self, event: &AgentEvent) -> Result<()>;
+}
+
+/// Delivers each event to several notifiers concurrently.
+///
+/// A failing or slow notifier does not stop the others from receiving the event.
+#[derive(Default)]
+pub struct CompositeNotifier {
+    notifiers: Vec<Arc<dyn Notifier>>,
+}
+
+impl CompositeNotifier {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, notifier: Arc<dyn Notifier>) {
+        self.notifiers.push(notifier);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.notifiers.is_empty()
+    }
+}
+
+#[async_trait]
+impl Notifier for CompositeNotifier {
+    async fn notify(&self, event: &AgentEvent) -> Result<()> {
+        let deliveries: Vec<_> = self
+            .notifiers
+            .iter()
+            .map(|notifier| {
+                let notifier = notifier.clone();
+                let event = event.clone();
+                tokio::spawn(async move { notifier.notify(&event).await })
+            })
+            .collect();
+
+        let mut failures = Vec::new();
+        for delivery in deliveries {
+            match delivery.await {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => failures.push(format!("{:#}", e)),
+                Err(e) => failures.push(format!("notifier task failed: {}", e)),
+            }
+        }
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow!(
+                "{} of {} notifiers failed: {}",
+                failures.len(),
+                self.notifiers.len(),
+                failures.join("; ")
+            ))
+        }
+    }
+}
+
+/// Forwards events to a notifier that can be replaced while the agent runs, so a
+/// config reload can change the targets without restarting the delivery worker.
+#[derive(Clone)]
+pub struct SwappableNotifier {
+    current: Arc<RwLock<Arc<dyn Notifier>>>,
+}
+
+impl SwappableNotifier {
+    pub fn new(notifier: Arc<dyn Notifier>) -> Self {
+        Self { current: Arc::new(RwLock::new(notifier)) }
+    }
+
+    /// Events queued from now on go to `notifier`.
+    pub fn replace(&self, notifier: Arc<dyn Notifier>) {
+        *self.current.write().unwrap_or_else(|e| e.into_inner()) = notifier;
+    }
+}
+
+#[async_trait]
+impl Notifier for SwappableNotifier {
+    async fn notify(&self, event: &AgentEvent) -> Result<()> {
+        let notifier = self.current.read().unwrap_or_else(|e| e.into_inner()).clone();
+        notifier.notify(event).await
+    }
+}
+
+/// Notifications given up on after retrying, per channel, for `/status`.
+#[derive(Debug, Clone, Default)]
+pub struct DeliveryFailures {
+    counts: Arc<std::sync::Mutex<BTreeMap<&'static str, u64>>>,
+}
+
+impl DeliveryFailures {
+    pub fn record(&self, channel: &'static str) {
+        *self.counts.lock().unwrap_or_else(|e| e.into_inner()).entry(channel).or_default() += 1;
+    }
+
+    pub fn counts(&self) -> BTreeMap<&'static str, u64> {
+        self.counts.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+}
+
+/// Cheap, cloneable handle for emitting events. The default handle drops everything.
+#[derive(Debug, Clone, Default)]
+pub struct EventSender {
+    tx: Option<mpsc::Sender<AgentEvent>>,
+    subscribers: Option<broadcast::Sender<AgentEvent>>,
+}
+
+impl EventSender {
+    pub fn disabled() -> Self {
+        Self::default()
+    }
+
+    /// Creates a sender backed by a queue of `capacity` events.
+    pub fn channel(capacity: usize) -> (Self, mpsc::Receiver<AgentEvent>) {
+        let (tx, rx) = mpsc::channel(capacity.max(1));
+        (Self { tx: Some(tx), subscribers: None }, rx)
+    }
+
+    /// Also hands every event to `subscribers`, ahead of the queue and its filters.
+    pub fn with_subscribers(mut self, subscribers: broadcast::Sender<AgentEvent>) -> Self {
+        self.subscribers = Some(subscribers);
+        self
+    }
+
+    /// Queues `event` without waiting; drops it with a warning when the queue is full.
+    pub fn emit(&self, event: AgentEvent) {
+        if let Some(subscribers) = &self.subscribers {
+            // No receivers is fine
+            let _ = subscribers.send(event.clone());
+        }
+        let Some(tx) = &self.tx else { return };
+        match tx.try_send(event) {
+            Ok(()) => {}
+            Err(TrySendError::Full(event)) => {
+                warn!("Notification queue full, dropping {} event", event.kind())
+            }
+            Err(TrySendError::Closed(event)) => {
+                debug!("Notification worker stopped, dropping {} event", event.kind())
+            }
+        }
+    }
+}
+
+/// Spawns a worker delivering queued events to `notifier`, returning the sender feeding it.
+/// Error alerts pass through an [`AlertDeduplicator`] with `suppression_window` first,
+/// rebalance alerts through `cooldown`.
+pub fn spawn_worker(
+    notifier: Arc<dyn Notifier>,
+    capacity: usize,
+    suppression_window: Duration,
+    cooldown: RebalanceCooldown,
+) -> EventSender {
+    let (sender, mut rx) = EventSender::channel(capacity);
+    let mut dedup = AlertDeduplicator::new(suppression_window);
+    tokio::spawn(async move {
+        loop {
+            let expiry = dedup.next_expiry();
+            let events = tokio::select! {
+                event = rx.recv() => match event {
+                    Some(event) if cooldown.admit(&event, unix_now()).await => dedup.filter(event, Instant::now()),
+                    Some(_) => Vec::new(),
+                    None => break,
+                },
+                _ = sleep_until(expiry.unwrap_or_else(Instant::now)), if expiry.is_some() => {
+                    dedup.expire(Instant::now())
+                }
+            };
+            for event in events {
+                if let Err(e) = notifier.notify(&event).await {
+                    error!("Failed to deliver {} notification: {:#}", event.kind(), e);
+                }
+            }
+        }
+        debug!("Notification worker stopped");
+    });
+    sender
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recent_actions::RecentActions;
+
+    fn route_failed() -> AgentEvent {
+        AgentEvent::RouteFailed {
+            route_id: "route-1".to_string(),
+            reason: "bridge reverted".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_event_serialization() {
+        let event = AgentEvent::CriticalBalance {
+            chain: "Arbitrum".to_string(),
+            address: Address::zero(),
+            balance_wei: U256::exp10(15),
+            funding_request: None,
+        };
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["type"], event.kind());
+        assert_eq!(json["balance_wei"], "1000000000000000");
+        assert_eq!(json["chain"], "Arbitrum");
+        assert!(json.get("funding_request").is_none(), "{}", json);
+        assert!(event.summary().contains("on Arbitrum"), "{}", event.summary());
+        assert_eq!(event.severity(), Severity::Critical);
+        assert!(Severity::Critical > Severity::Warning);
+
+        let event = AgentEvent::TransferObserved {
+            transfer: TransferEvent {
+                token: Some(Address::repeat_byte(0x70)),
+                from: Some(Address::repeat_byte(0x22)),
+                to: Some(Address::repeat_byte(0x11)),
+                amount: U256::exp10(21),
+                tx_hash: Some(H256::repeat_byte(0x42)),
+                block: 19_000_000,
+            },
+        };
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["type"], "transfer_observed");
+        assert_eq!(json["transfer"]["amount"], "1000000000000000000000");
+        assert_eq!(json["transfer"]["block"], 19_000_000);
+        assert!(event.summary().contains("in block 19000000"), "{}", event.summary());
+        assert_eq!(event.severity(), Severity::Info);
+    }
+
+    struct Recorder(std::sync::Mutex<Vec<AgentEvent>>);
+
+    #[async_trait]
+    impl Notifier for Recorder {
+        async fn notify(&self, event: &AgentEvent) -> Result<()> {
+            self.0.lock().unwrap().push(event.clone());
+            Ok(())
+        }
+    }
+
+    struct Failing;
+
+    #[async_trait]
+    impl Notifier for Failing {
+        async fn notify(&self, _: &AgentEvent) -> Result<()> {
+            Err(anyhow!("endpoint down"))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_composite_delivers_despite_failures() {
+        let recorder = Arc::new(Recorder(Default::default()));
+        let mut composite = CompositeNotifier::new();
+        composite.push(Arc::new(Failing));
+        composite.push(recorder.clone());
+
+        let err = composite.notify(&route_failed()).await.unwrap_err();
+        assert!(err.to_string().contains("1 of 2 notifiers failed: endpoint down"));
+        assert_eq!(*recorder.0.lock().unwrap(), vec![route_failed()]);
+    }
+
+    #[tokio::test]
+    async fn test_emit_drops_on_overflow() {
+        let (sender, mut rx) = EventSender::channel(1);
+        sender.emit(route_failed());
+        sender.emit(route_failed());
+        assert_eq!(rx.recv().await, Some(route_failed()));
+        assert!(rx.try_recv().is_err());
+
+        // A disabled sender silently ignores events
+        EventSender::disabled().emit(route_failed());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_worker_suppresses_repeated_errors() {
+
+        let recorder = Arc::new(Recorder(Default::default()));
+        let window = Duration::from_secs(30 * 60);
+        let cooldown = RebalanceCooldown::new(RecentActions::new(window), window);
+        let events = spawn_worker(recorder.clone(), 10, window, cooldown);
+        let raised = |category| AgentEvent::ErrorRaised { category, detail: "connection refused".to_string() };
+
+        for _ in 0..5 {
+            events.emit(raised(ErrorCategory::RpcTransport));
+            tokio::time::sleep(Duration::from_secs(60)).await;
+        }
+        assert_eq!(*recorder.0.lock().unwrap(), vec![raised(ErrorCategory::RpcTransport)]);
+
+        // The window closes without new events and the summary goes out on its own
+        tokio::time::sleep(window).await;
+        events.emit(raised(ErrorCategory::RpcTransport));
+        events.emit(AgentEvent::ErrorCleared { category: ErrorCategory::RpcTransport });
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        assert_eq!(
+            *recorder.0.lock().unwrap(),
+            vec![
+                raised(ErrorCategory::RpcTransport),
+                AgentEvent::ErrorRepeated { category: ErrorCategory::RpcTransport, count: 4 },
+                raised(ErrorCategory::RpcTransport),
+                AgentEvent::ErrorCleared { category: ErrorCategory::RpcTransport },
+            ]
+        );
+    }
+}
@@ -0,0 +1,277 @@
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use log::{debug, error, warn};
+use reqwest::Client;
+use serde_json::json;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio::time::{sleep_until, Instant};
+
+use super::{AgentEvent, Notifier, Severity};
+use crate::config::TelegramConfig;
+use crate::secret::Secret;
+use crate::version::USER_AGENT;
+
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+
+/// Escapes text for Telegram's MarkdownV2 parse mode.
+pub fn escape_markdown_v2(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(
+            c,
+            '_' | '*' | '[' | ']' | '(' | ')' | '~' | '`' | '>' | '#' | '+' | '-' | '=' | '|' | '{' | '}' | '.' | '!' | '\\'
+        ) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Formats one event, or a digest when several queued up, as a MarkdownV2 message.
+pub fn format_message(events: &[AgentEvent]) -> String {
+    match events {
+        [event] => format!(
+            "*\\[{}\\] {}*\n{}",
+            event.severity().label(),
+            escape_markdown_v2(event.kind()),
+            escape_markdown_v2(&event.summary())
+        ),
+        events => {
+            let lines: Vec<String> = events
+                .iter()
+                .map(|e| {
+                    format!(
+                        "• \\[{}\\] {}",
+                        e.severity().label(),
+                        escape_markdown_v2(&e.summary())
+                    )
+                })
+                .collect();
+            format!("*ASAM digest \\({} events\\)*\n{}", events.len(), lines.join("\n"))
+        }
+    }
+}
+
+struct Shared {
+    client: Client,
+    url: Secret<String>,
+    chat_id: String,
+    max_per_minute: usize,
+    digest_window: Duration,
+    pending: Mutex<Vec<AgentEvent>>,
+    wake: Notify,
+}
+
+/// Sends alerts to a Telegram chat through the Bot API.
+///
+/// `notify` only buffers the event. A flusher task waits `digest_window` for more
+/// events, then sends everything pending as one message while staying under
+/// `max_per_minute` messages.
+pub struct TelegramNotifier {
+    shared: Arc<Shared>,
+    min_severity: Severity,
+    flusher_started: AtomicBool,
+}
+
+impl TelegramNotifier {
+    /// Returns `None` unless both the bot token and chat id are configured.
+    pub fn from_config(config: &TelegramConfig) -> Option<Self> {
+        let token = config.bot_token.as_ref()?.expose();
+        let chat_id = config.chat_id.clone()?;
+        Some(Self {
+            shared: Arc::new(Shared {
+                client: Client::builder()
+                    .timeout(Duration::from_secs(10))
+                    .user_agent(USER_AGENT)
+                    .build()
+                    .unwrap_or_default(),
+                url: format!("{}/bot{}/sendMessage", config.api_url.trim_end_matches('/'), token).into(),
+                chat_id,
+                max_per_minute: config.max_per_minute.max(1) as usize,
+                digest_window: Duration::from_millis(config.digest_window_ms),
+                pending: Mutex::new(Vec::new()),
+                wake: Notify::new(),
+            }),
+            min_severity: config.min_severity,
+            flusher_started: AtomicBool::new(false),
+        })
+    }
+
+    async fn flush_loop(shared: Arc<Shared>) {
+        let mut sent_at: VecDeque<Instant> = VecDeque::new();
+        loop {
+            shared.wake.notified().await;
+            tokio::time::sleep(shared.digest_window).await;
+
+            loop {
+                // Wait for a free slot in the rolling one-minute window
+                while sent_at.front().is_some_and(|t| t.elapsed() >= RATE_WINDOW) {
+                    sent_at.pop_front();
+                }
+                if sent_at.len() >= shared.max_per_minute {
+                    if let Some(oldest) = sent_at.front() {
+                        debug!("Telegram rate limit reached, holding messages");
+                        sleep_until(*oldest + RATE_WINDOW).await;
+                    }
+                    continue;
+                }
+
+                let batch = std::mem::take(&mut *shared.pending.lock().unwrap_or_else(|e| e.into_inner()));
+                if batch.is_empty() {
+                    break;
+                }
+                sent_at.push_back(Instant::now());
+                if let Err(e) = Self::send(&shared, &format_message(&batch)).await {
+                    error!("Failed to send {} event(s) to Telegram: {:#}", batch.len(), e);
+                }
+            }
+        }
+    }
+
+    async fn send(shared: &Shared, text: &str) -> Result<()> {
+        let response = shared
+            .client
+            .post(shared.url.expose())
+            .json(&json!({
+                "chat_id": shared.chat_id,
+                "text": text,
+                "parse_mode": "MarkdownV2",
+                "disable_web_page_preview": true,
+            }))
+            .send()
+            .await
+            .context("Telegram request failed")?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(anyhow!("Telegram returned status {}: {}", status, body));
+        }
+        debug!("Sent Telegram message");
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Notifier for TelegramNotifier {
+    async fn notify(&self, event: &AgentEvent) -> Result<()> {
+        if event.severity() < self.min_severity {
+            debug!("Skipping {} event for Telegram (below {:?})", event.kind(), self.min_severity);
+            return Ok(());
+        }
+        if !self.flusher_started.swap(true, Ordering::SeqCst) {
+            tokio::spawn(Self::flush_loop(self.shared.clone()));
+        }
+
+        let queued = {
+            let mut pending = self.shared.pending.lock().unwrap_or_else(|e| e.into_inner());
+            pending.push(event.clone());
+            pending.len()
+        };
+        if queued > self.shared.max_per_minute * 5 {
+            warn!("{} Telegram messages waiting on the rate limit", queued);
+        }
+        self.shared.wake.notify_one();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn config(server: &MockServer) -> TelegramConfig {
+        TelegramConfig {
+            bot_token: Some("123:abc".into()),
+            chat_id: Some("-100".to_string()),
+            api_url: server.uri(),
+            digest_window_ms: 50,
+            ..TelegramConfig::default()
+        }
+    }
+
+    fn decided(protocol: &str) -> AgentEvent {
+        AgentEvent::RebalanceDecided {
+            protocol: protocol.to_string(),
+            chain: "Arbitrum".to_string(),
+            apy: 5.25,
+            score: 31.5,
+        }
+    }
+
+    #[test]
+    fn test_escape_markdown_v2() {
+        assert_eq!(escape_markdown_v2("plain text"), "plain text");
+        assert_eq!(
+            escape_markdown_v2("Curve (v2) [stable]_pool*1.5x!"),
+            "Curve \\(v2\\) \\[stable\\]\\_pool\\*1\\.5x\\!"
+        );
+        assert_eq!(escape_markdown_v2("a\\b`c"), "a\\\\b\\`c");
+        assert_eq!(escape_markdown_v2("0xAbC-12#=|{}~>+"), "0xAbC\\-12\\#\\=\\|\\{\\}\\~\\>\\+");
+
+        let message = format_message(&[decided("Uni.v3")]);
+        assert!(message.starts_with("*\\[INFO\\] rebalance\\_decided*\n"));
+        assert!(message.contains("Uni\\.v3"));
+    }
+
+    #[tokio::test]
+    async fn test_burst_is_coalesced_into_digest() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/bot123:abc/sendMessage"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+
+        let notifier = TelegramNotifier::from_config(&config(&server)).unwrap();
+        for i in 0..5 {
+            notifier.notify(&decided(&format!("Pool {}", i))).await.unwrap();
+        }
+        tokio::time::sleep(Duration::from_millis(300)).await;
+
+        let requests = server.received_requests().await.unwrap();
+        assert_eq!(requests.len(), 1);
+        let body: serde_json::Value = serde_json::from_slice(&requests[0].body).unwrap();
+        assert_eq!(body["chat_id"], "-100");
+        assert_eq!(body["parse_mode"], "MarkdownV2");
+        let text = body["text"].as_str().unwrap();
+        assert!(text.starts_with("*ASAM digest \\(5 events\\)*"), "{}", text);
+        assert_eq!(text.lines().count(), 6);
+    }
+
+    #[tokio::test]
+    async fn test_min_severity_filters_events() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+
+        let notifier = TelegramNotifier::from_config(&TelegramConfig {
+            min_severity: Severity::Warning,
+            ..config(&server)
+        })
+        .unwrap();
+        notifier.notify(&decided("Aave")).await.unwrap();
+        notifier
+            .notify(&AgentEvent::RouteFailed {
+                route_id: "route-1".to_string(),
+                reason: "timeout".to_string(),
+            })
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(300)).await;
+
+        let requests = server.received_requests().await.unwrap();
+        assert_eq!(requests.len(), 1);
+        let body: serde_json::Value = serde_json::from_slice(&requests[0].body).unwrap();
+        assert!(body["text"].as_str().unwrap().contains("route\\_failed"));
+    }
+}
@@ -0,0 +1,205 @@
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use hmac::{Hmac, Mac};
+use log::{debug, warn};
+use reqwest::{Client, StatusCode};
+use serde::Serialize;
+use sha2::Sha256;
+use std::time::Duration;
+
+use super::{AgentEvent, Notifier, Severity};
+use crate::secret::Secret;
+use crate::util::unix_now;
+use crate::config::WebhookConfig;
+use crate::version::USER_AGENT;
+
+/// Header carrying `sha256=<hex HMAC of the body>` when a secret is configured.
+pub const SIGNATURE_HEADER: &str = "X-Asam-Signature";
+pub const EVENT_HEADER: &str = "X-Asam-Event";
+
+#[derive(Debug, Serialize)]
+struct WebhookPayload<'a> {
+    timestamp: u64,
+    severity: Severity,
+    event: &'a AgentEvent,
+}
+
+/// POSTs events as JSON to a generic webhook endpoint.
+pub struct WebhookNotifier {
+    client: Client,
+    url: String,
+    secret: Option<Secret<String>>,
+    max_retries: u32,
+    retry_base: Duration,
+}
+
+impl WebhookNotifier {
+    pub fn new(url: impl Into<String>, secret: Option<Secret<String>>) -> Self {
+        Self {
+            client: Client::builder()
+                .timeout(Duration::from_secs(10))
+                .user_agent(USER_AGENT)
+                .build()
+                .unwrap_or_default(),
+            url: url.into(),
+            secret,
+            max_retries: 3,
+            retry_base: Duration::from_secs(1),
+        }
+    }
+
+    /// Returns `None` when no webhook URL is configured.
+    pub fn from_config(config: &WebhookConfig) -> Option<Self> {
+        let url = config.url.as_deref()?;
+        Some(Self::new(url, config.secret.clone()).with_retry(config.max_retries, Duration::from_secs(1)))
+    }
+
+    /// Retries transient failures up to `max_retries` times, doubling the delay from `base`.
+    pub fn with_retry(mut self, max_retries: u32, base: Duration) -> Self {
+        self.max_retries = max_retries;
+        self.retry_base = base;
+        self
+    }
+
+    pub fn sign(secret: &str, body: &[u8]) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+            .expect("HMAC accepts keys of any length");
+        mac.update(body);
+        format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+    }
+
+    async fn post(&self, event: &AgentEvent, body: &[u8]) -> std::result::Result<(), (bool, anyhow::Error)> {
+        let mut request = self
+            .client
+            .post(&self.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(EVENT_HEADER, event.kind())
+            .body(body.to_vec());
+        if let Some(secret) = &self.secret {
+            request = request.header(SIGNATURE_HEADER, Self::sign(secret.expose(), body));
+        }
+
+        let response = request
+            .send()
+            .await
+            .map_err(|e| (true, anyhow!("Webhook request failed: {}", e)))?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(());
+        }
+        let transient = status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS;
+        Err((transient, anyhow!("Webhook returned status {}", status)))
+    }
+}
+
+#[async_trait]
+impl Notifier for WebhookNotifier {
+    async fn notify(&self, event: &AgentEvent) -> Result<()> {
+        let payload = WebhookPayload {
+            timestamp: unix_now(),
+            severity: event.severity(),
+            event,
+        };
+        let body = serde_json::to_vec(&payload).context("Failed to serialize webhook payload")?;
+
+        let mut attempt = 0;
+        loop {
+            match self.post(event, &body).await {
+                Ok(()) => {
+                    debug!("Delivered {} event to webhook", event.kind());
+                    return Ok(());
+                }
+                Err((true, e)) if attempt < self.max_retries => {
+                    let delay = self.retry_base * 2u32.saturating_pow(attempt);
+                    attempt += 1;
+                    warn!("{} - retrying in {:?} (attempt {}/{})", e, delay, attempt, self.max_retries);
+                    tokio::time::sleep(delay).await;
+                }
+                Err((_, e)) => return Err(e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{header, header_exists, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn event() -> AgentEvent {
+        AgentEvent::RouteStarted {
+            route_id: "route-1".to_string(),
+            amount: 100.0,
+            source_chain: "Ethereum".to_string(),
+            target_chain: "Arbitrum".to_string(),
+        }
+    }
+
+    fn notifier(server: &MockServer) -> WebhookNotifier {
+        WebhookNotifier::new(format!("{}/hook", server.uri()), Some("s3cret".into()))
+            .with_retry(2, Duration::from_millis(10))
+    }
+
+    #[tokio::test]
+    async fn test_payload_and_signature() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/hook"))
+            .and(header(EVENT_HEADER, "route_started"))
+            .and(header_exists(SIGNATURE_HEADER))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        notifier(&server).notify(&event()).await.unwrap();
+
+        let request = &server.received_requests().await.unwrap()[0];
+        let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+        assert_eq!(body["severity"], "info");
+        assert_eq!(body["event"]["type"], "route_started");
+        assert_eq!(body["event"]["target_chain"], "Arbitrum");
+        assert!(body["timestamp"].as_u64().unwrap() > 0);
+
+        let expected = WebhookNotifier::sign("s3cret", &request.body);
+        let signature = request.headers.get(&SIGNATURE_HEADER.into()).unwrap();
+        assert_eq!(signature.as_str(), expected);
+    }
+
+    #[tokio::test]
+    async fn test_transient_failures_are_retried() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(2)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+
+        notifier(&server).notify(&event()).await.unwrap();
+        assert_eq!(server.received_requests().await.unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_on_client_errors_and_exhausted_retries() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(400))
+            .mount(&server)
+            .await;
+        assert!(notifier(&server).notify(&event()).await.is_err());
+        assert_eq!(server.received_requests().await.unwrap().len(), 1);
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(500))
+            .mount(&server)
+            .await;
+        assert!(notifier(&server).notify(&event()).await.is_err());
+        assert_eq!(server.received_requests().await.unwrap().len(), 3);
+    }
+}
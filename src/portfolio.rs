@@ -0,0 +1,251 @@
+//! Where the funds currently live.
+//!
+//! [`PortfolioState`] moves funds between chains as routes start, complete or fail.
+//! It is persisted through the [`Store`](crate::storage::Store), restored at startup
+//! and reconciled against the on-chain balance after every balance check.
+
+use serde::{Deserialize, Serialize};
+
+use crate::agents::cross_chain_router::RouteReceipt;
+
+/// Chain the Safe lives on and whose balance the agent can read.
+pub const HOME_CHAIN: &str = "Ethereum";
+/// Token the agent moves.
+pub const TOKEN: &str = "ETH";
+
+/// Amounts below this are treated as empty.
+const DUST: f64 = 1e-9;
+
+/// Funds of one token held on one chain.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PositionEntry {
+    pub chain: String,
+    pub protocol: Option<String>,
+    pub token: String,
+    pub amount: f64,
+    /// Unix time the funds arrived, or the route carrying them started while pending.
+    pub since: u64,
+    /// Route still carrying these funds to `chain`.
+    pub pending_route: Option<String>,
+}
+
+impl PositionEntry {
+    fn is_settled(&self) -> bool {
+        self.pending_route.is_none()
+    }
+}
+
+/// Recorded amount that disagrees with the chain by more than the tolerance.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mismatch {
+    pub chain: String,
+    pub recorded: f64,
+    pub observed: f64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PortfolioState {
+    pub entries: Vec<PositionEntry>,
+}
+
+impl PortfolioState {
+    /// Chain holding the largest settled amount, or the home chain when nothing is recorded.
+    pub fn current_chain(&self) -> &str {
+        self.entries
+            .iter()
+            .filter(|e| e.is_settled())
+            .max_by(|a, b| a.amount.total_cmp(&b.amount))
+            .map(|e| e.chain.as_str())
+            .unwrap_or(HOME_CHAIN)
+    }
+
+    pub fn pending_routes(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter().filter_map(|e| e.pending_route.as_deref())
+    }
+
+    /// The funds that have landed on `chain`.
+    pub fn settled(&self, chain: &str) -> Option<&PositionEntry> {
+        self.entries.iter().find(|e| e.chain == chain && e.is_settled())
+    }
+
+    fn settled_mut(&mut self, chain: &str) -> Option<&mut PositionEntry> {
+        self.entries.iter_mut().find(|e| e.chain == chain && e.is_settled())
+    }
+
+    fn add_settled(&mut self, mut entry: PositionEntry) {
+        entry.pending_route = None;
+        match self.settled_mut(&entry.chain) {
+            Some(existing) => {
+                existing.amount += entry.amount;
+                existing.protocol = entry.protocol.or(existing.protocol.take());
+                existing.since = entry.since;
+            }
+            None => self.entries.push(entry),
+        }
+    }
+
+    fn drop_empty(&mut self) {
+        self.entries.retain(|e| e.amount > DUST || !e.is_settled());
+    }
+
+    /// Takes the routed amount off the source chain and records it as pending on the target.
+    pub fn route_started(&mut self, route: &RouteReceipt, protocol: Option<String>, now: u64) {
+        if let Some(source) = self.settled_mut(&route.source_chain) {
+            source.amount = (source.amount - route.amount).max(0.0);
+        }
+        self.drop_empty();
+        self.entries.push(PositionEntry {
+            chain: route.target_chain.clone(),
+            protocol,
+            token: TOKEN.to_string(),
+            amount: route.amount,
+            since: now,
+            pending_route: Some(route.route_id.clone()),
+        });
+    }
+
+    /// Records the settled funds on `chain` as supplied to `protocol`. Returns false
+    /// when nothing is settled there.
+    pub fn entered(&mut self, chain: &str, protocol: &str) -> bool {
+        let Some(entry) = self.settled_mut(chain) else {
+            return false;
+        };
+        entry.protocol = Some(protocol.to_string());
+        true
+    }
+
+    /// Records the settled funds on `chain` as back in the Safe. Returns false when
+    /// nothing is settled there.
+    pub fn exited(&mut self, chain: &str) -> bool {
+        let Some(entry) = self.settled_mut(chain) else {
+            return false;
+        };
+        entry.protocol = None;
+        true
+    }
+
+    /// Settles the funds a route delivered. Returns false for unknown routes.
+    pub fn route_completed(&mut self, route_id: &str, now: u64) -> bool {
+        let Some(index) = self.entries.iter().position(|e| e.pending_route.as_deref() == Some(route_id)) else {
+            return false;
+        };
+        let entry = self.entries.remove(index);
+        self.add_settled(PositionEntry { since: now, ..entry });
+        true
+    }
+
+    /// Returns the funds of a failed route to its source chain. Returns false for unknown routes.
+    pub fn route_failed(&mut self, route: &RouteReceipt, now: u64) -> bool {
+        let Some(index) = self
+            .entries
+            .iter()
+            .position(|e| e.pending_route.as_deref() == Some(route.route_id.as_str()))
+        else {
+            return false;
+        };
+        let entry = self.entries.remove(index);
+        self.add_settled(PositionEntry {
+            chain: route.source_chain.clone(),
+            protocol: None,
+            since: now,
+            ..entry
+        });
+        true
+    }
+
+    /// Compares the settled amount on `chain` with `observed` and adopts the observed
+    /// value. Returns the mismatch when they differ by more than `tolerance`; an empty
+    /// portfolio simply adopts the balance.
+    pub fn reconcile(&mut self, chain: &str, observed: f64, tolerance: f64, now: u64) -> Option<Mismatch> {
+        if self.entries.is_empty() {
+            if observed > DUST {
+                self.add_settled(PositionEntry {
+                    chain: chain.to_string(),
+                    protocol: None,
+                    token: TOKEN.to_string(),
+                    amount: observed,
+                    since: now,
+                    pending_route: None,
+                });
+            }
+            return None;
+        }
+
+        let recorded = self.settled_mut(chain).map(|e| e.amount).unwrap_or(0.0);
+        match self.settled_mut(chain) {
+            Some(entry) => entry.amount = observed,
+            None => self.add_settled(PositionEntry {
+                chain: chain.to_string(),
+                protocol: None,
+                token: TOKEN.to_string(),
+                amount: observed,
+                since: now,
+                pending_route: None,
+            }),
+        }
+        self.drop_empty();
+
+        ((observed - recorded).abs() > tolerance).then(|| Mismatch {
+            chain: chain.to_string(),
+            recorded,
+            observed,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agents::cross_chain_router::RouteState;
+    use crate::storage::tests::route;
+
+    #[test]
+    fn test_route_lifecycle_and_reconciliation() {
+        let mut portfolio = PortfolioState::default();
+        assert_eq!(portfolio.current_chain(), HOME_CHAIN);
+
+        // The first balance check seeds the portfolio without a warning
+        assert_eq!(portfolio.reconcile(HOME_CHAIN, 150.0, 0.05, 10), None);
+        assert_eq!(portfolio.entries.len(), 1);
+
+        // Route start: 100 leaves Ethereum and is pending on Arbitrum
+        let receipt = route("r-1", RouteState::Pending);
+        portfolio.route_started(&receipt, Some("Aave".to_string()), 20);
+        assert_eq!(portfolio.pending_routes().collect::<Vec<_>>(), vec!["r-1"]);
+        assert_eq!(portfolio.current_chain(), HOME_CHAIN);
+        let pending = &portfolio.entries[1];
+        assert_eq!((pending.chain.as_str(), pending.amount, pending.since), ("Arbitrum", 100.0, 20));
+
+        // Route complete: the funds settle on Arbitrum, which now holds the most
+        assert!(portfolio.route_completed("r-1", 30));
+        assert!(!portfolio.route_completed("r-1", 31));
+        assert_eq!(portfolio.pending_routes().count(), 0);
+        assert_eq!(portfolio.current_chain(), "Arbitrum");
+        let arbitrum = portfolio.entries.iter().find(|e| e.chain == "Arbitrum").unwrap();
+        assert_eq!((arbitrum.protocol.as_deref(), arbitrum.since), (Some("Aave"), 30));
+
+        // Gas spent on Ethereum stays within the tolerance
+        assert_eq!(portfolio.reconcile(HOME_CHAIN, 49.98, 0.05, 40), None);
+
+        // The chain shows far more than recorded: the route never left
+        let mismatch = portfolio.reconcile(HOME_CHAIN, 149.98, 0.05, 50).unwrap();
+        assert_eq!((mismatch.recorded, mismatch.observed), (49.98, 149.98));
+        assert_eq!(portfolio.current_chain(), HOME_CHAIN);
+        assert_eq!(portfolio.reconcile(HOME_CHAIN, 149.98, 0.05, 60), None);
+    }
+
+    #[test]
+    fn test_failed_route_returns_funds() {
+        let mut portfolio = PortfolioState::default();
+        portfolio.reconcile(HOME_CHAIN, 100.0, 0.05, 10);
+        let receipt = route("r-1", RouteState::Pending);
+        portfolio.route_started(&receipt, None, 20);
+        assert!(portfolio.entries.iter().all(|e| e.chain == "Arbitrum"));
+
+        assert!(portfolio.route_failed(&receipt, 30));
+        assert_eq!(portfolio.entries.len(), 1);
+        assert_eq!(portfolio.entries[0].chain, HOME_CHAIN);
+        assert_eq!(portfolio.entries[0].amount, 100.0);
+        assert_eq!(portfolio.pending_routes().count(), 0);
+    }
+}
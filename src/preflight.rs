@@ -0,0 +1,490 @@
+//! Preflight checks: everything a deployment needs before the first cycle.
+//!
+//! `asam check` prints them as a table; with `runtime.preflight = true` the agent runs
+//! them before entering the loop and refuses to start on a failure. `FAIL` marks hard
+//! checks that would break a cycle, `WARN` degraded but workable setups.
+
+use anyhow::{anyhow, Result};
+use ethers::core::types::U256;
+use ethers::providers::Middleware;
+use log::{error, info, warn};
+use std::fs::OpenOptions;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::agents::defi_optimizer::DefiOptimizer;
+use crate::cli::{check_provider, CheckResult, CheckStatus};
+use crate::config::{ChainConfig, Config, RunMode};
+use crate::notifier::{AgentEvent, DiscordNotifier, Notifier, TelegramNotifier, WebhookNotifier};
+use crate::portfolio::HOME_CHAIN;
+use crate::rpc::FailoverClient;
+use crate::version::USER_AGENT;
+
+/// Amount asked for in the test bridge quote, in wei (0.001 ETH).
+const TEST_QUOTE_WEI: u64 = 1_000_000_000_000_000;
+/// Token address LI.FI uses for a chain's native currency.
+const NATIVE_TOKEN: &str = "0x0000000000000000000000000000000000000000";
+
+#[derive(Debug, Clone, Default)]
+pub struct PreflightOptions {
+    /// Chain id the RPC must report; defaults to the configured home chain's.
+    pub expected_chain_id: Option<u64>,
+    /// Send a test message through every configured notifier.
+    pub send_test_notification: bool,
+}
+
+/// Runs every check against a validated config. Checks do not stop at the first
+/// failure, so one run shows everything that needs fixing.
+pub async fn run(config: &Config, options: &PreflightOptions) -> Vec<CheckResult> {
+    let mut results = check_rpc(config, options).await;
+    results.extend(check_watched_chains(config).await);
+    results.push(check_pool_api(config).await);
+    results.push(check_bridge_quote(config).await);
+    results.extend(check_paths(config));
+    results.extend(check_notifiers(config, options.send_test_notification).await);
+    results
+}
+
+/// Runs the checks before the loop starts, logging each one. Errors on any failure.
+pub async fn run_before_start(config: &Config) -> Result<()> {
+    info!("Running preflight checks...");
+    let results = run(config, &PreflightOptions::default()).await;
+    for result in &results {
+        match result.status {
+            CheckStatus::Pass => info!("Preflight [{}] {}: {}", result.status, result.name, result.detail),
+            CheckStatus::Warn => warn!("Preflight [{}] {}: {}", result.status, result.name, result.detail),
+            CheckStatus::Fail => error!("Preflight [{}] {}: {}", result.status, result.name, result.detail),
+        }
+    }
+    let failed: Vec<&str> = results
+        .iter()
+        .filter(|r| r.status == CheckStatus::Fail)
+        .map(|r| r.name.as_str())
+        .collect();
+    if !failed.is_empty() {
+        return Err(anyhow!("Preflight checks failed: {}", failed.join(", ")));
+    }
+    Ok(())
+}
+
+fn chain_id_of(config: &Config, chain: &str) -> Option<u64> {
+    config.router.chains.iter().find(|c| c.name == chain).map(|c| c.chain_id)
+}
+
+async fn check_rpc(config: &Config, options: &PreflightOptions) -> Vec<CheckResult> {
+    let account = match config.safe.address() {
+        Ok(account) => account,
+        Err(e) => return vec![CheckResult::new("rpc: connectivity", CheckStatus::Fail, format!("{:#}", e))],
+    };
+    let client = match FailoverClient::from_config(&config.safe) {
+        Ok(client) => client,
+        Err(e) => return vec![CheckResult::new("rpc: connectivity", CheckStatus::Fail, format!("{:#}", e))],
+    };
+    let (provider, _) = client.into_provider();
+    let expected = options.expected_chain_id.or_else(|| chain_id_of(config, HOME_CHAIN));
+    let mut results = check_provider(&provider, account, expected).await;
+    let chain_id = match results[0].status {
+        CheckStatus::Fail => None,
+        _ => provider.get_chainid().await.ok().map(|id| id.low_u64()),
+    };
+    results.push(check_profile(config, chain_id));
+    results
+}
+
+/// Applies the profile's interlocks to the chain the RPC serves and the run mode.
+fn check_profile(config: &Config, chain_id: Option<u64>) -> CheckResult {
+    let name = "profile";
+    if let Some(Err(e)) = chain_id.map(|id| config.profile.check_chain_id(id)) {
+        return CheckResult::new(name, CheckStatus::Fail, format!("{:#}", e));
+    }
+    if let Err(e) = config.check_live_acknowledged() {
+        return CheckResult::new(name, CheckStatus::Fail, format!("{:#}", e));
+    }
+    if config.live_downgraded() {
+        return CheckResult::new(name, CheckStatus::Warn, "live mode requested; the local profile runs it as dry-run");
+    }
+    let chain_id = chain_id.map(|id| id.to_string()).unwrap_or_else(|| "unknown".to_string());
+    CheckResult::new(name, CheckStatus::Pass, format!("{}, chain id {}", config.profile, chain_id))
+}
+
+/// Chain id of every other chain whose balance is watched.
+async fn check_watched_chains(config: &Config) -> Vec<CheckResult> {
+    let mut results = Vec::new();
+    for chain in config.router.chains.iter().filter(|c| c.active && c.name != HOME_CHAIN) {
+        let urls = chain.rpc_urls();
+        if urls.is_empty() {
+            continue;
+        }
+        let name = format!("rpc: {}", chain.name);
+        let client = match FailoverClient::new(
+            &urls,
+            Duration::from_secs(config.safe.rpc_timeout_secs),
+            Duration::from_secs(config.safe.rpc_probe_interval_secs),
+        ) {
+            Ok(client) => client,
+            Err(e) => {
+                results.push(CheckResult::new(name, CheckStatus::Fail, format!("{:#}", e)));
+                continue;
+            }
+        };
+        let (provider, _) = client.into_provider();
+        results.push(match provider.get_chainid().await {
+            Ok(id) if u64::try_from(id).ok() == Some(chain.chain_id) => {
+                CheckResult::new(name, CheckStatus::Pass, format!("chain id {}", id))
+            }
+            Ok(id) => CheckResult::new(
+                name,
+                CheckStatus::Fail,
+                format!("expected chain id {}, node reports {}", chain.chain_id, id),
+            ),
+            // Only this chain's balance becomes unknown; cycles still run
+            Err(e) => CheckResult::new(name, CheckStatus::Warn, format!("unreachable: {}", e)),
+        });
+    }
+    results
+}
+
+/// Fetches the pool list once and checks that it parses into usable pools.
+async fn check_pool_api(config: &Config) -> CheckResult {
+    let name = "defi: pool api";
+    if config.optimizer.use_mock {
+        return CheckResult::new(name, CheckStatus::Pass, "using built-in mock pools");
+    }
+    match DefiOptimizer::from_config(&config.optimizer).get_ranked_pools().await {
+        Ok(pools) => CheckResult::new(
+            name,
+            CheckStatus::Pass,
+            format!("{} usable pools, best {} on {}", pools.len(), pools[0].protocol, pools[0].chain),
+        ),
+        Err(e) => CheckResult::new(name, CheckStatus::Fail, format!("{:#}", e)),
+    }
+}
+
+/// Asks LI.FI for a quote-only transfer of [`TEST_QUOTE_WEI`] from the home chain to
+/// the first other active chain. Nothing is signed or sent. Only fails in live mode,
+/// where routes depend on the bridge API.
+async fn check_bridge_quote(config: &Config) -> CheckResult {
+    let name = "bridge: quote";
+    let live = config.mode_plan().map(|plan| plan.router == RunMode::Live).unwrap_or(false);
+    let unreachable = if live { CheckStatus::Fail } else { CheckStatus::Warn };
+    let (Some(from_chain), Some(to_chain)) = (
+        chain_id_of(config, HOME_CHAIN),
+        config.router.chains.iter().find(|c| c.active && c.name != HOME_CHAIN),
+    ) else {
+        return CheckResult::new(name, CheckStatus::Warn, "no destination chain configured");
+    };
+    let (url, response) = request_quote(config, from_chain, to_chain.chain_id).await;
+    let response = match response {
+        Ok(response) => response,
+        Err(e) => return CheckResult::new(name, unreachable, format!("{} unreachable: {}", url, e)),
+    };
+
+    let status = response.status();
+    let body: serde_json::Value = response.json().await.unwrap_or_default();
+    if status.is_server_error() {
+        return CheckResult::new(name, unreachable, format!("{} answered {}", url, status));
+    }
+    if !status.is_success() {
+        // Reachable, but no route for the tiny amount is not a deployment problem
+        let message = body["message"].as_str().unwrap_or("no message");
+        return CheckResult::new(name, CheckStatus::Warn, format!("quote refused ({}): {}", status, message));
+    }
+    match quote_amount(&body) {
+        Some(amount) => CheckResult::new(
+            name,
+            CheckStatus::Pass,
+            format!("{} -> {}: {} wei in, {} wei out", HOME_CHAIN, to_chain.name, TEST_QUOTE_WEI, amount),
+        ),
+        None => CheckResult::new(name, unreachable, "quote response has no valid estimate.toAmount"),
+    }
+}
+
+/// Asks the bridge API for a quote-only transfer of [`TEST_QUOTE_WEI`] between two
+/// chain ids, returning the URL asked.
+async fn request_quote(config: &Config, from_chain: u64, to_chain: u64) -> (String, reqwest::Result<reqwest::Response>) {
+    let from_address = config.safe.address().map(|a| format!("{:?}", a)).unwrap_or_else(|_| NATIVE_TOKEN.to_string());
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(config.optimizer.timeout_secs))
+        .user_agent(USER_AGENT)
+        .build()
+        .unwrap_or_default();
+    let url = format!("{}/v1/quote", config.router.status.lifi_api_url.trim_end_matches('/'));
+    let response = client
+        .get(&url)
+        .query(&[
+            ("fromChain", from_chain.to_string()),
+            ("toChain", to_chain.to_string()),
+            ("fromToken", NATIVE_TOKEN.to_string()),
+            ("toToken", NATIVE_TOKEN.to_string()),
+            ("fromAmount", TEST_QUOTE_WEI.to_string()),
+            ("fromAddress", from_address),
+        ])
+        .send()
+        .await;
+    (url, response)
+}
+
+/// Asks the endpoints configured for `chain` for their chain id, through the same
+/// failover client the agent uses. Errors when none answers or the id is another chain's.
+pub async fn probe_rpc(config: &Config, chain: &ChainConfig) -> Result<()> {
+    let client = if chain.name == HOME_CHAIN && chain.rpc_url.is_none() {
+        FailoverClient::from_config(&config.safe)?
+    } else {
+        FailoverClient::new(
+            &chain.rpc_urls(),
+            Duration::from_secs(config.safe.rpc_timeout_secs),
+            Duration::from_secs(config.safe.rpc_probe_interval_secs),
+        )?
+    };
+    let (provider, _) = client.into_provider();
+    let chain_id = provider.get_chainid().await?;
+    if chain_id != chain.chain_id.into() {
+        return Err(anyhow!("expected chain id {}, node reports {}", chain.chain_id, chain_id));
+    }
+    Ok(())
+}
+
+/// Asks the bridge API for a test quote from the home chain to `chain`, returning the
+/// amount it would deliver in wei.
+pub async fn probe_quote(config: &Config, chain: &ChainConfig) -> Result<String> {
+    let from_chain = chain_id_of(config, HOME_CHAIN).ok_or_else(|| anyhow!("{} is not configured", HOME_CHAIN))?;
+    let (url, response) = request_quote(config, from_chain, chain.chain_id).await;
+    let response = response.map_err(|e| anyhow!("{} unreachable: {}", url, e))?;
+    let status = response.status();
+    let body: serde_json::Value = response.json().await.unwrap_or_default();
+    match quote_amount(&body) {
+        Some(amount) if status.is_success() => Ok(amount.to_string()),
+        _ => Err(anyhow!("{} answered {}: {}", url, status, body["message"].as_str().unwrap_or("no estimate"))),
+    }
+}
+
+/// Amount a bridge quote would deliver, in wei: `estimate.toAmount` when it is a
+/// non-empty decimal string that fits a `U256`. Anything else in the body is ignored.
+fn quote_amount(body: &serde_json::Value) -> Option<U256> {
+    let amount = body["estimate"]["toAmount"].as_str()?;
+    if amount.is_empty() || !amount.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    U256::from_dec_str(amount).ok()
+}
+
+/// Checks that `path` can be appended to, or created when it does not exist yet,
+/// without leaving anything behind.
+fn check_writable(path: &Path) -> Result<()> {
+    if path.exists() {
+        OpenOptions::new().append(true).open(path)?;
+        return Ok(());
+    }
+    let dir = path.parent().filter(|d| !d.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    if !dir.is_dir() {
+        return Err(anyhow!("directory {} does not exist", dir.display()));
+    }
+    let probe = dir.join(format!(".asam-preflight-{}", std::process::id()));
+    OpenOptions::new().create_new(true).write(true).open(&probe)?;
+    std::fs::remove_file(&probe)?;
+    Ok(())
+}
+
+fn check_paths(config: &Config) -> Vec<CheckResult> {
+    let paths = [
+        ("storage: path", config.storage.path.as_deref()),
+        ("kill switch: path", config.kill_switch.path.as_deref()),
+        ("log: file_path", config.log.file_path.as_deref()),
+    ];
+    paths
+        .into_iter()
+        .filter_map(|(name, path)| {
+            let path = path?;
+            Some(match check_writable(path) {
+                Ok(()) => CheckResult::new(name, CheckStatus::Pass, format!("{} is writable", path.display())),
+                Err(e) => CheckResult::new(name, CheckStatus::Fail, format!("{} is not writable: {:#}", path.display(), e)),
+            })
+        })
+        .collect()
+}
+
+/// Lists the configured notifiers and, with `send`, delivers a test message to each.
+async fn check_notifiers(config: &Config, send: bool) -> Vec<CheckResult> {
+    let mut notifiers: Vec<(&str, Arc<dyn Notifier>)> = Vec::new();
+    if let Some(webhook) = WebhookNotifier::from_config(&config.notify.webhook) {
+        notifiers.push(("notify: webhook", Arc::new(webhook.with_retry(0, Duration::ZERO))));
+    }
+    if let Some(telegram) = TelegramNotifier::from_config(&config.notify.telegram) {
+        notifiers.push(("notify: telegram", Arc::new(telegram)));
+    }
+    if let Some(discord) = DiscordNotifier::from_config(&config.notify.discord) {
+        notifiers.push(("notify: discord", Arc::new(discord)));
+    }
+
+    let mut results = Vec::new();
+    for (name, notifier) in notifiers {
+        if !send {
+            results.push(CheckResult::new(name, CheckStatus::Pass, "configured; pass --notify to send a test message"));
+            continue;
+        }
+        // Notifications never stop the agent, so delivery problems only warn
+        results.push(match notifier.notify(&AgentEvent::TestMessage).await {
+            Ok(()) => CheckResult::new(name, CheckStatus::Pass, "test message delivered"),
+            Err(e) => CheckResult::new(name, CheckStatus::Warn, format!("test message failed: {:#}", e)),
+        });
+    }
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agents::test_utils::arbitrary_json;
+    use crate::cli::has_failures;
+    use proptest::prelude::*;
+    use wiremock::matchers::{body_partial_json, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    /// JSON-RPC node for mainnet where the account is a deployed contract.
+    async fn rpc_node() -> MockServer {
+        let server = MockServer::start().await;
+        for (rpc_method, result) in [("eth_blockNumber", "0x1234"), ("eth_chainId", "0x1"), ("eth_getCode", "0x6080")] {
+            Mock::given(method("POST"))
+                .and(body_partial_json(serde_json::json!({ "method": rpc_method })))
+                .respond_with(
+                    ResponseTemplate::new(200).set_body_json(serde_json::json!({ "jsonrpc": "2.0", "id": 1, "result": result })),
+                )
+                .mount(&server)
+                .await;
+        }
+        server
+    }
+
+    /// Pool API, LI.FI quote endpoint and webhook on one server; the webhook must
+    /// receive exactly `notifications` test messages.
+    async fn services(notifications: u64) -> MockServer {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/protocols"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(serde_json::json!([{ "name": "Aave", "chain": "Arbitrum", "tvl": 1e6, "apy": 5.2 }])),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/v1/quote"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({ "estimate": { "toAmount": "990000000000000" } })))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/hook"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(notifications)
+            .mount(&server)
+            .await;
+        server
+    }
+
+    fn config(rpc: &str, services: &str, dir: &Path) -> Config {
+        let mut config = Config::default();
+        config.safe.address = Some("0x0000000000000000000000000000000000000001".to_string());
+        config.safe.rpc_url = Some(rpc.to_string());
+        config.optimizer.api_url = format!("{}/protocols", services);
+        config.router.status.lifi_api_url = services.to_string();
+        config.notify.webhook.url = Some(format!("{}/hook", services));
+        config.storage.path = Some(dir.join("asam.db"));
+        config.kill_switch.path = Some(dir.join("asam.pause"));
+        config.i_understand_this_moves_real_funds = true;
+        config.validate().unwrap();
+        config
+    }
+
+    #[tokio::test]
+    async fn test_preflight_in_healthy_environment() {
+        let (node, services) = (rpc_node().await, services(1).await);
+        let dir = std::env::temp_dir();
+        let config = config(&node.uri(), &services.uri(), &dir);
+
+        let options = PreflightOptions { send_test_notification: true, ..Default::default() };
+        let results = run(&config, &options).await;
+        let names: Vec<&str> = results.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "rpc: connectivity",
+                "rpc: chain id",
+                "safe: contract",
+                "profile",
+                "defi: pool api",
+                "bridge: quote",
+                "storage: path",
+                "kill switch: path",
+                "notify: webhook",
+            ]
+        );
+        assert!(results.iter().all(|r| r.status == CheckStatus::Pass), "{:#?}", results);
+        assert!(results[5].detail.contains("990000000000000"), "{}", results[5].detail);
+        run_before_start(&config).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_preflight_with_rpc_failing() {
+        let node = MockServer::start().await;
+        Mock::given(method("POST")).respond_with(ResponseTemplate::new(500)).mount(&node).await;
+        let services = services(0).await;
+        let dir = std::env::temp_dir().join(format!("asam-preflight-missing-{}", std::process::id()));
+        let config = config(&node.uri(), &services.uri(), &dir);
+
+        let results = run(&config, &PreflightOptions::default()).await;
+        assert!(has_failures(&results));
+        let status = |name: &str| results.iter().find(|r| r.name == name).map(|r| r.status);
+        assert_eq!(status("rpc: connectivity"), Some(CheckStatus::Fail));
+        assert_eq!(status("rpc: chain id"), None);
+        // The other checks still ran
+        assert_eq!(status("defi: pool api"), Some(CheckStatus::Pass));
+        assert_eq!(status("storage: path"), Some(CheckStatus::Fail));
+        assert_eq!(status("notify: webhook"), Some(CheckStatus::Pass));
+
+        let err = run_before_start(&config).await.unwrap_err();
+        assert!(err.to_string().contains("rpc: connectivity"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_probes_per_chain() {
+        let (node, services) = (rpc_node().await, services(0).await);
+        let config = config(&node.uri(), &services.uri(), &std::env::temp_dir());
+        let chain = |name: &str| config.router.chains.iter().find(|c| c.name == name).unwrap().clone();
+
+        probe_rpc(&config, &chain(HOME_CHAIN)).await.unwrap();
+        assert_eq!(probe_quote(&config, &chain("Arbitrum")).await.unwrap(), "990000000000000");
+
+        // The mock node is chain 1, so it is no Arbitrum endpoint
+        let arbitrum = ChainConfig { rpc_url: Some(node.uri()), ..chain("Arbitrum") };
+        let err = probe_rpc(&config, &arbitrum).await.unwrap_err();
+        assert!(err.to_string().contains("expected chain id 42161"), "{}", err);
+    }
+
+    proptest! {
+        /// A quote only counts when `estimate.toAmount` is a decimal string that fits a
+        /// `U256`; any other body, however malformed, is no quote.
+        #[test]
+        fn test_quote_amount_needs_a_decimal_string(
+            body in arbitrary_json(&["estimate", "toAmount", "message"]),
+            amount in prop_oneof![".{0,80}", "[0-9]{1,90}"],
+        ) {
+            let is_number = |a: &str| !a.is_empty() && a.bytes().all(|b| b.is_ascii_digit());
+            if let Some(wei) = quote_amount(&body) {
+                let amount = body["estimate"]["toAmount"].as_str().unwrap();
+                prop_assert!(is_number(amount) && U256::from_dec_str(amount).ok() == Some(wei));
+            }
+
+            let quote = serde_json::json!({ "estimate": { "toAmount": amount } });
+            match quote_amount(&quote) {
+                Some(wei) => {
+                    let significant = amount.trim_start_matches('0');
+                    prop_assert!(is_number(&amount));
+                    prop_assert_eq!(wei.to_string(), if significant.is_empty() { "0" } else { significant });
+                }
+                // Only non-numbers and numbers beyond 2^256 are refused
+                None => prop_assert!(!is_number(&amount) || amount.trim_start_matches('0').len() >= 78),
+            }
+        }
+    }
+}
@@ -0,0 +1,499 @@
+//! USD prices of tokens, for the parts of the agent that report values in dollars.
+//!
+//! A [`PriceSource`] answers for a batch of symbols and leaves out the ones it has
+//! no fresh price for. [`ChainlinkSource`] reads the aggregator feeds on the home
+//! chain, [`CoinGeckoSource`] asks `/simple/price` for every symbol in one request,
+//! caches the answers for `prices.cache_ttl_secs` and spaces its requests to stay
+//! within the free tier. [`CompositeSource`] asks each source in turn for what the
+//! previous ones could not price; the default is Chainlink, then CoinGecko.
+//! [`FixedSource`] always answers with the same prices, for simulations.
+//!
+//! Prices older than `prices.max_age_secs` are dropped. A missing price only leaves
+//! the value unknown: use [`usd_price`]. The pool depth check, the one decision on a
+//! USD value, is skipped when the portfolio could not be valued.
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use ethers::abi::ParamType;
+use ethers::core::types::{Address, Bytes, TransactionRequest, U256};
+use ethers::providers::Middleware;
+use ethers::types::transaction::eip2718::TypedTransaction;
+use log::{debug, warn};
+use reqwest::Client;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use thiserror::Error;
+use tokio::time::{Duration, Instant};
+
+use crate::secret::Secret;
+use crate::util::unix_now;
+use crate::config::{PriceSourceKind, PricesConfig};
+use crate::rpc::RpcProvider;
+use crate::version::USER_AGENT;
+
+#[derive(Error, Debug)]
+#[non_exhaustive]
+pub enum PriceError {
+    #[error("Price request to {source_name} failed: {reason}")]
+    RequestFailed { source_name: String, reason: String },
+    #[error("Unexpected price response from {source_name}: {reason}")]
+    InvalidResponse { source_name: String, reason: String },
+}
+
+/// A token's USD price and when the source last updated it.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Price {
+    pub usd: f64,
+    /// Unix time of the source's last update.
+    pub updated_at: u64,
+    pub source: String,
+}
+
+#[async_trait]
+pub trait PriceSource: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// Prices of `symbols` by symbol; those without a fresh price are left out.
+    async fn prices(&self, symbols: &[&str]) -> Result<HashMap<String, Price>>;
+}
+
+/// USD price of `symbol`, or `None` when `source` fails or has no fresh one.
+pub async fn usd_price(source: &dyn PriceSource, symbol: &str) -> Option<f64> {
+    match source.prices(&[symbol]).await {
+        Ok(mut prices) => prices.remove(symbol).map(|price| price.usd),
+        Err(e) => {
+            debug!("No {} price from {}: {:#}", symbol, source.name(), e);
+            None
+        }
+    }
+}
+
+/// The price source configured in `[prices]`; `None` when disabled. Chainlink feeds
+/// are read through `provider`, and left out without one.
+pub fn from_config(config: &PricesConfig, provider: Option<RpcProvider>) -> Result<Option<Arc<dyn PriceSource>>> {
+    config.validate_feeds()?;
+    let chainlink =
+        provider.map(|provider| Arc::new(ChainlinkSource::from_config(config, provider)) as Arc<dyn PriceSource>);
+    let coingecko = || Arc::new(CoinGeckoSource::from_config(config)) as Arc<dyn PriceSource>;
+    Ok(match config.source {
+        PriceSourceKind::None => None,
+        PriceSourceKind::Chainlink => chainlink,
+        PriceSourceKind::Coingecko => Some(coingecko()),
+        PriceSourceKind::Composite => {
+            let sources = chainlink.into_iter().chain([coingecko()]).collect();
+            Some(Arc::new(CompositeSource::new(sources)))
+        }
+    })
+}
+
+fn is_fresh(updated_at: u64, max_age_secs: u64) -> bool {
+    unix_now().saturating_sub(updated_at) <= max_age_secs
+}
+
+/// CoinGecko's `/simple/price`, batched, cached and rate limited.
+pub struct CoinGeckoSource {
+    client: Client,
+    api_url: String,
+    api_key: Option<Secret<String>>,
+    /// CoinGecko id by symbol.
+    ids: BTreeMap<String, String>,
+    cache_ttl: Duration,
+    min_interval: Duration,
+    max_age_secs: u64,
+    cache: Mutex<HashMap<String, (Price, Instant)>>,
+    /// Held for the length of a request, so concurrent callers queue up.
+    last_request: tokio::sync::Mutex<Option<Instant>>,
+}
+
+impl CoinGeckoSource {
+    pub fn from_config(config: &PricesConfig) -> Self {
+        Self {
+            client: Client::builder()
+                .timeout(std::time::Duration::from_secs(config.timeout_secs))
+                .user_agent(USER_AGENT)
+                .build()
+                .unwrap_or_default(),
+            api_url: config.coingecko_url.trim_end_matches('/').to_string(),
+            api_key: config.coingecko_api_key.clone(),
+            ids: config.coingecko_ids.clone(),
+            cache_ttl: Duration::from_secs(config.cache_ttl_secs),
+            min_interval: Duration::from_millis(config.min_request_interval_ms),
+            max_age_secs: config.max_age_secs,
+            cache: Mutex::default(),
+            last_request: tokio::sync::Mutex::new(None),
+        }
+    }
+
+    fn cached(&self, symbol: &str) -> Option<Price> {
+        let cache = self.cache.lock().unwrap_or_else(|e| e.into_inner());
+        cache.get(symbol).filter(|(_, at)| at.elapsed() < self.cache_ttl).map(|(price, _)| price.clone())
+    }
+
+    async fn fetch(&self, ids: &[&str]) -> Result<serde_json::Value> {
+        let mut last_request = self.last_request.lock().await;
+        if let Some(at) = *last_request {
+            tokio::time::sleep_until(at + self.min_interval).await;
+        }
+        *last_request = Some(Instant::now());
+        let mut request = self.client.get(format!("{}/simple/price", self.api_url)).query(&[
+            ("ids", ids.join(",").as_str()),
+            ("vs_currencies", "usd"),
+            ("include_last_updated_at", "true"),
+        ]);
+        if let Some(key) = &self.api_key {
+            request = request.header("x-cg-pro-api-key", key.expose());
+        }
+        let failed = |reason: String| PriceError::RequestFailed { source_name: self.name().to_string(), reason };
+        let response = request.send().await.map_err(|e| failed(e.to_string()))?;
+        if !response.status().is_success() {
+            return Err(failed(format!("status {}", response.status())).into());
+        }
+        response.json().await.context("Failed to parse the CoinGecko response")
+    }
+}
+
+#[async_trait]
+impl PriceSource for CoinGeckoSource {
+    fn name(&self) -> &str {
+        "coingecko"
+    }
+
+    async fn prices(&self, symbols: &[&str]) -> Result<HashMap<String, Price>> {
+        let mut prices = HashMap::new();
+        let mut missing = Vec::new();
+        for symbol in symbols {
+            match self.cached(symbol) {
+                Some(price) => {
+                    prices.insert(symbol.to_string(), price);
+                }
+                None => {
+                    if let Some(id) = self.ids.get(*symbol) {
+                        missing.push((*symbol, id.as_str()));
+                    }
+                }
+            }
+        }
+        if !missing.is_empty() {
+            let ids: Vec<&str> = missing.iter().map(|(_, id)| *id).collect();
+            let body = self.fetch(&ids).await?;
+            let mut cache = self.cache.lock().unwrap_or_else(|e| e.into_inner());
+            for (symbol, id) in missing {
+                let Some(usd) = body[id]["usd"].as_f64() else {
+                    debug!("CoinGecko has no USD price for {} ({})", symbol, id);
+                    continue;
+                };
+                let updated_at = body[id]["last_updated_at"].as_u64().unwrap_or_else(unix_now);
+                let price = Price { usd, updated_at, source: self.name().to_string() };
+                cache.insert(symbol.to_string(), (price.clone(), Instant::now()));
+                prices.insert(symbol.to_string(), price);
+            }
+        }
+        prices.retain(|_, price| is_fresh(price.updated_at, self.max_age_secs));
+        Ok(prices)
+    }
+}
+
+/// Chainlink aggregator feeds on the home chain.
+pub struct ChainlinkSource {
+    provider: RpcProvider,
+    /// USD feed by symbol.
+    feeds: BTreeMap<String, Address>,
+    max_age_secs: u64,
+    decimals: Mutex<HashMap<Address, u32>>,
+}
+
+impl ChainlinkSource {
+    pub fn new(feeds: BTreeMap<String, Address>, provider: RpcProvider, max_age_secs: u64) -> Self {
+        Self { provider, feeds, max_age_secs, decimals: Mutex::default() }
+    }
+
+    /// Feeds that do not parse are left out; `[prices]` validation reports them.
+    pub fn from_config(config: &PricesConfig, provider: RpcProvider) -> Self {
+        let feeds =
+            config.chainlink_feeds.iter().filter_map(|(symbol, raw)| Some((symbol.clone(), raw.parse().ok()?))).collect();
+        Self::new(feeds, provider, config.max_age_secs)
+    }
+
+    async fn call(&self, feed: Address, signature: &str, output: &[ParamType]) -> Result<Vec<ethers::abi::Token>> {
+        let data = Bytes::from(ethers::utils::id(signature).to_vec());
+        let tx = TypedTransaction::Legacy(TransactionRequest::new().to(feed).data(data));
+        let raw = self.provider.call(&tx, None).await.map_err(|e| PriceError::RequestFailed {
+            source_name: self.name().to_string(),
+            reason: e.to_string(),
+        })?;
+        ethers::abi::decode(output, &raw).map_err(|e| {
+            PriceError::InvalidResponse { source_name: self.name().to_string(), reason: e.to_string() }.into()
+        })
+    }
+
+    async fn feed_decimals(&self, feed: Address) -> Result<u32> {
+        if let Some(decimals) = self.decimals.lock().unwrap_or_else(|e| e.into_inner()).get(&feed) {
+            return Ok(*decimals);
+        }
+        let tokens = self.call(feed, "decimals()", &[ParamType::Uint(8)]).await?;
+        let decimals = tokens[0].clone().into_uint().unwrap_or_default().low_u32();
+        self.decimals.lock().unwrap_or_else(|e| e.into_inner()).insert(feed, decimals);
+        Ok(decimals)
+    }
+
+    async fn read(&self, feed: Address) -> Result<Option<Price>> {
+        let decimals = self.feed_decimals(feed).await?;
+        let round = [ParamType::Uint(80), ParamType::Int(256), ParamType::Uint(256), ParamType::Uint(256), ParamType::Uint(80)];
+        let tokens = self.call(feed, "latestRoundData()", &round).await?;
+        let answer = tokens[1].clone().into_int().unwrap_or_default();
+        // A negative answer has the top bit set
+        if answer.is_zero() || answer.bit(255) {
+            return Ok(None);
+        }
+        let updated_at = tokens[3].clone().into_uint().unwrap_or_default().low_u64();
+        let usd = answer.to_string().parse::<f64>().unwrap_or_default() / 10f64.powi(decimals as i32);
+        Ok(Some(Price { usd, updated_at, source: self.name().to_string() }))
+    }
+}
+
+#[async_trait]
+impl PriceSource for ChainlinkSource {
+    fn name(&self) -> &str {
+        "chainlink"
+    }
+
+    async fn prices(&self, symbols: &[&str]) -> Result<HashMap<String, Price>> {
+        let mut prices = HashMap::new();
+        let (mut answered, mut failure) = (false, None);
+        for symbol in symbols {
+            let Some(feed) = self.feeds.get(*symbol) else {
+                continue;
+            };
+            let read = self.read(*feed).await;
+            answered |= read.is_ok();
+            match read {
+                Ok(Some(price)) if is_fresh(price.updated_at, self.max_age_secs) => {
+                    prices.insert(symbol.to_string(), price);
+                }
+                Ok(Some(price)) => debug!("Chainlink {} price is stale (updated at {})", symbol, price.updated_at),
+                Ok(None) => debug!("Chainlink {} feed has no positive answer", symbol),
+                Err(e) => {
+                    warn!("Failed to read the Chainlink {} feed: {:#}", symbol, e);
+                    failure = Some(e);
+                }
+            }
+        }
+        // Down only when no feed could be read
+        match failure {
+            Some(e) if !answered => Err(e),
+            _ => Ok(prices),
+        }
+    }
+}
+
+/// Asks each source in order for the symbols the earlier ones left out.
+pub struct CompositeSource {
+    sources: Vec<Arc<dyn PriceSource>>,
+}
+
+impl CompositeSource {
+    pub fn new(sources: Vec<Arc<dyn PriceSource>>) -> Self {
+        Self { sources }
+    }
+}
+
+#[async_trait]
+impl PriceSource for CompositeSource {
+    fn name(&self) -> &str {
+        "composite"
+    }
+
+    async fn prices(&self, symbols: &[&str]) -> Result<HashMap<String, Price>> {
+        let mut prices = HashMap::new();
+        let (mut answered, mut failure) = (false, None);
+        for source in &self.sources {
+            let missing: Vec<&str> = symbols.iter().copied().filter(|s| !prices.contains_key(*s)).collect();
+            if missing.is_empty() {
+                break;
+            }
+            match source.prices(&missing).await {
+                Ok(found) => {
+                    answered = true;
+                    prices.extend(found);
+                }
+                Err(e) => {
+                    debug!("{} could not price {}: {:#}", source.name(), missing.join(", "), e);
+                    failure = Some(e);
+                }
+            }
+        }
+        // The composite only fails when every source it asked did
+        match failure {
+            Some(e) if !answered => Err(e),
+            _ => Ok(prices),
+        }
+    }
+}
+
+/// The same USD price for a symbol on every call, e.g. for a simulation.
+pub struct FixedSource {
+    prices: BTreeMap<String, f64>,
+}
+
+impl FixedSource {
+    pub fn new(prices: BTreeMap<String, f64>) -> Self {
+        Self { prices }
+    }
+}
+
+#[async_trait]
+impl PriceSource for FixedSource {
+    fn name(&self) -> &str {
+        "fixed"
+    }
+
+    async fn prices(&self, symbols: &[&str]) -> Result<HashMap<String, Price>> {
+        let updated_at = unix_now();
+        Ok(symbols
+            .iter()
+            .filter_map(|symbol| {
+                let usd = *self.prices.get(*symbol)?;
+                Some((symbol.to_string(), Price { usd, updated_at, source: self.name().to_string() }))
+            })
+            .collect())
+    }
+}
+
+/// Value of `wei` at the ETH price of `source`, when known.
+pub async fn eth_value_usd(source: &dyn PriceSource, wei: U256) -> Option<f64> {
+    let eth: f64 = crate::units::format_eth(wei).parse().ok()?;
+    usd_price(source, "ETH").await.map(|usd| eth * usd)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agents::test_utils::test_provider;
+    use wiremock::matchers::{body_string_contains, header, method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn config(server: &MockServer) -> PricesConfig {
+        PricesConfig { coingecko_url: server.uri(), min_request_interval_ms: 0, ..PricesConfig::default() }
+    }
+
+    async fn mock_coingecko(server: &MockServer, ids: &str, body: serde_json::Value, expected: u64) {
+        Mock::given(method("GET"))
+            .and(path("/simple/price"))
+            .and(query_param("ids", ids))
+            .respond_with(ResponseTemplate::new(200).set_body_json(body))
+            .expect(expected)
+            .mount(server)
+            .await;
+    }
+
+    #[tokio::test]
+    async fn test_coingecko_batches_and_caches() {
+        let server = MockServer::start().await;
+        let now = unix_now();
+        let body = serde_json::json!({
+            "ethereum": { "usd": 3000.0, "last_updated_at": now },
+            "usd-coin": { "usd": 1.0, "last_updated_at": now },
+        });
+        mock_coingecko(&server, "ethereum,usd-coin", body, 1).await;
+        let source = CoinGeckoSource::from_config(&config(&server));
+
+        let prices = source.prices(&["ETH", "USDC", "UNKNOWN"]).await.unwrap();
+        assert_eq!(prices.len(), 2);
+        assert_eq!(prices["ETH"].usd, 3000.0);
+        // Served from the cache: the mock expects a single request
+        let cached = source.prices(&["USDC", "ETH"]).await.unwrap();
+        assert_eq!(cached["USDC"], prices["USDC"]);
+        assert_eq!(usd_price(&source, "ETH").await, Some(3000.0));
+        assert_eq!(eth_value_usd(&source, U256::exp10(18) / 2).await, Some(1500.0));
+    }
+
+    #[tokio::test]
+    async fn test_coingecko_key_staleness_and_failure() {
+        let server = MockServer::start().await;
+        Mock::given(path("/simple/price"))
+            .and(header("x-cg-pro-api-key", "pro-key"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "ethereum": { "usd": 3000.0, "last_updated_at": unix_now() - 7200 },
+            })))
+            .mount(&server)
+            .await;
+        let keyed = CoinGeckoSource::from_config(&PricesConfig {
+            coingecko_api_key: Some("pro-key".into()),
+            ..config(&server)
+        });
+        // An hour past the default maximum age
+        assert!(keyed.prices(&["ETH"]).await.unwrap().is_empty());
+
+        // Without the key the mock does not match: the USD value is unknown
+        let unkeyed = CoinGeckoSource::from_config(&config(&server));
+        assert!(unkeyed.prices(&["ETH"]).await.is_err());
+        assert_eq!(usd_price(&unkeyed, "ETH").await, None);
+    }
+
+    #[tokio::test]
+    async fn test_coingecko_spaces_requests() {
+        let server = MockServer::start().await;
+        mock_coingecko(&server, "dai", serde_json::json!({ "dai": { "usd": 1.0 } }), 2).await;
+        let source = CoinGeckoSource::from_config(&PricesConfig {
+            cache_ttl_secs: 0,
+            min_request_interval_ms: 300,
+            ..config(&server)
+        });
+        source.prices(&["DAI"]).await.unwrap();
+        let started = Instant::now();
+        source.prices(&["DAI"]).await.unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(300));
+    }
+
+    fn word(value: U256) -> String {
+        format!("{:064x}", value)
+    }
+
+    #[tokio::test]
+    async fn test_composite_prefers_chainlink() {
+        let rpc = MockServer::start().await;
+        // decimals() and latestRoundData() of the ETH feed
+        for (selector, result) in [
+            ("313ce567", word(U256::from(8))),
+            (
+                "feaf968c",
+                [U256::one(), U256::from(3100) * U256::exp10(8), U256::zero(), U256::from(unix_now()), U256::one()]
+                    .map(word)
+                    .concat(),
+            ),
+        ] {
+            Mock::given(body_string_contains(selector))
+                .respond_with(ResponseTemplate::new(200).set_body_json(
+                    serde_json::json!({ "jsonrpc": "2.0", "id": 1, "result": format!("0x{}", result) }),
+                ))
+                .mount(&rpc)
+                .await;
+        }
+        let gecko = MockServer::start().await;
+        // ETH is priced by Chainlink, so only USDC is asked for
+        mock_coingecko(&gecko, "usd-coin", serde_json::json!({ "usd-coin": { "usd": 0.999 } }), 2).await;
+
+        let feeds = BTreeMap::from([("ETH".to_string(), Address::repeat_byte(0xee))]);
+        let composite = CompositeSource::new(vec![
+            Arc::new(ChainlinkSource::new(feeds, test_provider(&rpc.uri()), 3600)),
+            Arc::new(CoinGeckoSource::from_config(&config(&gecko))),
+        ]);
+        let prices = composite.prices(&["ETH", "USDC"]).await.unwrap();
+        assert_eq!((prices["ETH"].usd, prices["ETH"].source.as_str()), (3100.0, "chainlink"));
+        assert_eq!((prices["USDC"].usd, prices["USDC"].source.as_str()), (0.999, "coingecko"));
+
+        // A failing feed falls through to the next source
+        let broken = BTreeMap::from([("USDC".to_string(), Address::repeat_byte(0x01))]);
+        let closed = ChainlinkSource::new(broken, test_provider("http://127.0.0.1:9"), 3600);
+        let composite = CompositeSource::new(vec![Arc::new(closed), Arc::new(CoinGeckoSource::from_config(&config(&gecko)))]);
+        assert_eq!(usd_price(&composite, "USDC").await, Some(0.999));
+
+        // Down when every source is
+        let broken = BTreeMap::from([("USDC".to_string(), Address::repeat_byte(0x01))]);
+        let closed = ChainlinkSource::new(broken, test_provider("http://127.0.0.1:9"), 3600);
+        let down = CoinGeckoSource::from_config(&config(&MockServer::start().await));
+        let composite = CompositeSource::new(vec![Arc::new(closed), Arc::new(down)]);
+        assert!(composite.prices(&["USDC"]).await.is_err());
+    }
+}
@@ -0,0 +1,126 @@
+//! Aave v3 Pool calls.
+
+use ethers::abi::AbiEncode;
+use ethers::contract::abigen;
+use ethers::core::types::{Address, U256};
+
+use super::{call, ActionBuilder, Market};
+use crate::agents::safe_manager::SafeTransaction;
+
+abigen!(
+    AavePool,
+    r#"[
+        function supply(address asset, uint256 amount, address onBehalfOf, uint16 referralCode) external
+        function withdraw(address asset, uint256 amount, address to) external returns (uint256)
+    ]"#
+);
+
+/// Name of this action builder in decision records.
+pub const NAME: &str = "aave_v3";
+
+/// Whether pools of `protocol`, as the pool source names it, are Aave v3's.
+pub fn handles(protocol: &str) -> bool {
+    let protocol = protocol.to_ascii_lowercase();
+    protocol.starts_with("aave") && !protocol.contains("v2")
+}
+
+/// Supplies `amount` of `asset` to the Pool at `pool`, credited to `on_behalf_of`.
+/// The Pool pulls the asset, so it needs the allowance first.
+pub fn supply(pool: Address, asset: Address, amount: U256, on_behalf_of: Address) -> SafeTransaction {
+    let data = SupplyCall { asset, amount, on_behalf_of, referral_code: 0 }.encode();
+    call(pool, data)
+}
+
+/// Withdraws `amount` of `asset` from the Pool at `pool` to `to`; `U256::MAX`
+/// withdraws everything.
+pub fn withdraw(pool: Address, asset: Address, amount: U256, to: Address) -> SafeTransaction {
+    let data = WithdrawCall { asset, amount, to }.encode();
+    call(pool, data)
+}
+
+/// The configured Aave v3 markets; `Market::contract` is the Pool.
+pub struct AaveV3 {
+    markets: Vec<Market>,
+}
+
+impl AaveV3 {
+    pub fn new(markets: Vec<Market>) -> Self {
+        Self { markets }
+    }
+}
+
+impl ActionBuilder for AaveV3 {
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    fn handles(&self, protocol: &str) -> bool {
+        handles(protocol)
+    }
+
+    fn markets(&self) -> &[Market] {
+        &self.markets
+    }
+
+    fn supply(&self, market: &Market, amount: U256, safe: Address) -> SafeTransaction {
+        supply(market.contract, market.asset, amount, safe)
+    }
+
+    fn withdraw(&self, market: &Market, amount: U256, safe: Address) -> SafeTransaction {
+        withdraw(market.contract, market.asset, amount, safe)
+    }
+
+    fn supply_gas(&self) -> u64 {
+        250_000
+    }
+
+    fn withdraw_gas(&self) -> u64 {
+        250_000
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_supply_and_withdraw_calldata() {
+        let pool: Address = crate::config::MAINNET_AAVE_V3_POOL.parse().unwrap();
+        let weth: Address = crate::config::MAINNET_WETH.parse().unwrap();
+        let safe = Address::repeat_byte(0x11);
+
+        let tx = supply(pool, weth, U256::exp10(18), safe);
+        assert_eq!(tx.to, pool);
+        assert_eq!(tx.value, U256::zero());
+        assert_eq!(tx.operation, 0);
+        assert_eq!(
+            hex::encode(&tx.data),
+            concat!(
+                "617ba037",
+                "000000000000000000000000c02aaa39b223fe8d0a0e5c4f27ead9083c756cc2",
+                "0000000000000000000000000000000000000000000000000de0b6b3a7640000",
+                "0000000000000000000000001111111111111111111111111111111111111111",
+                "0000000000000000000000000000000000000000000000000000000000000000",
+            )
+        );
+
+        let tx = withdraw(pool, weth, U256::MAX, safe);
+        assert_eq!(
+            hex::encode(&tx.data),
+            concat!(
+                "69328dec",
+                "000000000000000000000000c02aaa39b223fe8d0a0e5c4f27ead9083c756cc2",
+                "ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff",
+                "0000000000000000000000001111111111111111111111111111111111111111",
+            )
+        );
+    }
+
+    #[test]
+    fn test_handles_aave_pools() {
+        assert!(handles("Aave"));
+        assert!(handles("aave-v3"));
+        assert!(!handles("aave-v2"));
+        assert!(!handles("Compound"));
+    }
+}
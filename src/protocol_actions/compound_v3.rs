@@ -0,0 +1,121 @@
+//! Compound v3 (Comet) calls.
+
+use ethers::abi::AbiEncode;
+use ethers::contract::abigen;
+use ethers::core::types::{Address, U256};
+
+use super::{call, ActionBuilder, Market};
+use crate::agents::safe_manager::SafeTransaction;
+
+abigen!(
+    Comet,
+    r#"[
+        function supply(address asset, uint256 amount) external
+        function withdraw(address asset, uint256 amount) external
+        function balanceOf(address account) external view returns (uint256)
+    ]"#
+);
+
+/// Name of this action builder in decision records.
+pub const NAME: &str = "compound_v3";
+
+/// Whether pools of `protocol`, as the pool source names it, are Compound v3's.
+pub fn handles(protocol: &str) -> bool {
+    let protocol = protocol.to_ascii_lowercase();
+    protocol.starts_with("compound") && !protocol.contains("v2")
+}
+
+/// Supplies `amount` of `asset` from the Safe to the Comet at `comet`. Comet pulls
+/// the asset, so it needs the allowance first.
+pub fn supply(comet: Address, asset: Address, amount: U256) -> SafeTransaction {
+    call(comet, SupplyCall { asset, amount }.encode())
+}
+
+/// Withdraws `amount` of `asset` from the Comet at `comet` back to the Safe;
+/// `U256::MAX` withdraws the whole base asset balance.
+pub fn withdraw(comet: Address, asset: Address, amount: U256) -> SafeTransaction {
+    call(comet, WithdrawCall { asset, amount }.encode())
+}
+
+/// The configured Comet markets; `Market::contract` is the Comet proxy.
+pub struct CompoundV3 {
+    markets: Vec<Market>,
+}
+
+impl CompoundV3 {
+    pub fn new(markets: Vec<Market>) -> Self {
+        Self { markets }
+    }
+}
+
+impl ActionBuilder for CompoundV3 {
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    fn handles(&self, protocol: &str) -> bool {
+        handles(protocol)
+    }
+
+    fn markets(&self) -> &[Market] {
+        &self.markets
+    }
+
+    fn supply(&self, market: &Market, amount: U256, _safe: Address) -> SafeTransaction {
+        supply(market.contract, market.asset, amount)
+    }
+
+    fn withdraw(&self, market: &Market, amount: U256, _safe: Address) -> SafeTransaction {
+        withdraw(market.contract, market.asset, amount)
+    }
+
+    fn supply_gas(&self) -> u64 {
+        150_000
+    }
+
+    fn withdraw_gas(&self) -> u64 {
+        150_000
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{MAINNET_COMET_WETH, MAINNET_WETH};
+
+    #[test]
+    fn test_supply_and_withdraw_calldata() {
+        let comet: Address = MAINNET_COMET_WETH.parse().unwrap();
+        let weth: Address = MAINNET_WETH.parse().unwrap();
+
+        let tx = supply(comet, weth, U256::exp10(18));
+        assert_eq!(tx.to, comet);
+        assert_eq!(tx.value, U256::zero());
+        assert_eq!(
+            hex::encode(&tx.data),
+            concat!(
+                "f2b9fdb8",
+                "000000000000000000000000c02aaa39b223fe8d0a0e5c4f27ead9083c756cc2",
+                "0000000000000000000000000000000000000000000000000de0b6b3a7640000",
+            )
+        );
+
+        let tx = withdraw(comet, weth, U256::MAX);
+        assert_eq!(
+            hex::encode(&tx.data),
+            concat!(
+                "f3fef3a3",
+                "000000000000000000000000c02aaa39b223fe8d0a0e5c4f27ead9083c756cc2",
+                "ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff",
+            )
+        );
+    }
+
+    #[test]
+    fn test_handles_compound_pools() {
+        assert!(handles("Compound"));
+        assert!(handles("compound-v3"));
+        assert!(!handles("compound-v2"));
+        assert!(!handles("Aave"));
+    }
+}
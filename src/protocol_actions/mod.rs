@@ -0,0 +1,527 @@
+//! Safe transactions that enter and leave pools.
+//!
+//! Each supported protocol has a module building its calls as [`SafeTransaction`]s
+//! from ABI bindings, and an [`ActionBuilder`] holding its configured markets. The
+//! [`ProtocolRegistry`] maps a pool's protocol and chain to the builder and market
+//! acting on it; [`ProtocolRegistry::entry_calls`] builds the calls entering it, an
+//! ERC-20 approval first when the allowance is short. [`multi_send`] batches them
+//! into one delegatecall to Safe's MultiSend, so the approval and the supply go
+//! through together or not at all. Supporting another protocol means adding a
+//! builder and registering it.
+//!
+//! Pools no builder covers are only suggested; the decision log says so.
+
+pub mod aave_v3;
+pub mod compound_v3;
+
+use anyhow::Result;
+use ethers::abi::AbiEncode;
+use ethers::contract::abigen;
+use ethers::core::types::{Address, Bytes, U256};
+use log::debug;
+use std::fmt;
+use std::sync::Arc;
+use thiserror::Error;
+
+use crate::agents::defi_optimizer::PoolData;
+use crate::agents::safe_manager::SafeTransaction;
+use crate::config::ActionsConfig;
+use crate::portfolio::PositionEntry;
+use crate::rpc::RpcProvider;
+
+abigen!(
+    Erc20,
+    r#"[
+        function approve(address spender, uint256 amount) external returns (bool)
+        function allowance(address owner, address spender) external view returns (uint256)
+        function balanceOf(address account) external view returns (uint256)
+        function decimals() external view returns (uint8)
+    ]"#
+);
+
+abigen!(MultiSendCallOnly, r#"[function multiSend(bytes transactions) external payable]"#);
+
+abigen!(
+    CreateCall,
+    r#"[function performCreate(uint256 value, bytes deploymentData) external returns (address newContract)]"#
+);
+
+/// `SafeTransaction::operation` of a plain call.
+pub const CALL: u8 = 0;
+/// `SafeTransaction::operation` of a delegatecall.
+pub const DELEGATE_CALL: u8 = 1;
+/// Gas of an ERC-20 approval, for cost estimates.
+pub const APPROVE_GAS: u64 = 50_000;
+
+#[derive(Error, Debug)]
+#[non_exhaustive]
+pub enum ProtocolError {
+    #[error("No action builder for {protocol} pools on {chain}; suggest-only protocol")]
+    NotActionable { protocol: String, chain: String },
+    #[error("The Safe cannot transact on {0}")]
+    ForeignChain(String),
+    #[error("The funds on {0} are still in flight")]
+    InFlight(String),
+    #[error("Failed to read the allowance of {token:?}: {reason}")]
+    AllowanceFailed { token: Address, reason: String },
+    #[error("MultiSendCallOnly cannot batch delegatecalls")]
+    DelegateCallInBatch,
+    #[error("Nothing to batch")]
+    EmptyBatch,
+}
+
+/// A plain call from the Safe to `to` with `data`.
+pub fn call(to: Address, data: impl Into<Bytes>) -> SafeTransaction {
+    SafeTransaction {
+        to,
+        value: U256::zero(),
+        data: data.into().to_vec(),
+        operation: CALL,
+        safe_tx_gas: U256::zero(),
+        nonce: None,
+    }
+}
+
+/// Approves `spender` to pull `amount` of `token` from the Safe.
+pub fn approve(token: Address, spender: Address, amount: U256) -> SafeTransaction {
+    call(token, ApproveCall { spender, amount }.encode())
+}
+
+/// How much of a token an approval lets the spender pull.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Allowance {
+    /// Exactly this amount, in the token's smallest unit.
+    Exact(U256),
+    /// The largest allowance, which most tokens do not spend down, so one approval
+    /// lasts.
+    Unlimited,
+}
+
+impl Allowance {
+    /// The `amount` of the `approve` call.
+    pub fn amount(self) -> U256 {
+        match self {
+            Allowance::Exact(amount) => amount,
+            Allowance::Unlimited => U256::MAX,
+        }
+    }
+}
+
+impl From<U256> for Allowance {
+    fn from(amount: U256) -> Self {
+        Allowance::Exact(amount)
+    }
+}
+
+/// The approvals letting `spender` pull `amount` of `token` from `owner`; empty when
+/// the allowance already covers it. A non-zero allowance is reset to zero first, as
+/// tokens like USDT refuse to change one directly.
+pub async fn ensure_allowance(
+    provider: &RpcProvider,
+    token: Address,
+    owner: Address,
+    spender: Address,
+    amount: U256,
+) -> Result<Vec<SafeTransaction>> {
+    let allowance = Erc20::new(token, Arc::new(provider.clone()))
+        .allowance(owner, spender)
+        .call()
+        .await
+        .map_err(|e| ProtocolError::AllowanceFailed { token, reason: e.to_string() })?;
+    Ok(approvals(token, spender, allowance, amount))
+}
+
+/// The approvals taking an `allowance` of `token` for `spender` to `amount`; empty
+/// when it already covers it. A non-zero allowance is reset to zero first.
+pub fn approvals(token: Address, spender: Address, allowance: U256, amount: U256) -> Vec<SafeTransaction> {
+    if allowance >= amount {
+        debug!("Allowance of {:?} for {:?} already covers {}", token, spender, amount);
+        return Vec::new();
+    }
+    let mut approvals = Vec::new();
+    if !allowance.is_zero() {
+        approvals.push(approve(token, spender, U256::zero()));
+    }
+    approvals.push(approve(token, spender, amount));
+    approvals
+}
+
+/// `calls` packed as MultiSend expects them: operation, target, value, data length
+/// and data of each, back to back.
+pub fn encode_multi_send(calls: &[SafeTransaction]) -> Vec<u8> {
+    let mut packed = Vec::new();
+    for tx in calls {
+        packed.push(tx.operation);
+        packed.extend_from_slice(tx.to.as_bytes());
+        packed.extend_from_slice(&<[u8; 32]>::from(tx.value));
+        packed.extend_from_slice(&<[u8; 32]>::from(U256::from(tx.data.len())));
+        packed.extend_from_slice(&tx.data);
+    }
+    packed
+}
+
+/// `calls` as one delegatecall to the MultiSend contract at `multi_send`; a single
+/// call is returned as it is.
+pub fn multi_send(multi_send: Address, calls: &[SafeTransaction]) -> Result<SafeTransaction> {
+    match calls {
+        [] => Err(ProtocolError::EmptyBatch.into()),
+        [single] => Ok(single.clone()),
+        _ if calls.iter().any(|tx| tx.operation != CALL) => Err(ProtocolError::DelegateCallInBatch.into()),
+        _ => Ok(SafeTransaction {
+            operation: DELEGATE_CALL,
+            ..call(multi_send, MultiSendCall { transactions: encode_multi_send(calls).into() }.encode())
+        }),
+    }
+}
+
+/// Deploys a contract from the Safe with `init_code`, funded with `value` wei of the
+/// Safe's balance: a delegatecall to Safe's CreateCall at `create_call`, so the Safe
+/// itself is the creator. The delegatecall carries no value of its own.
+pub fn deploy(create_call: Address, value: U256, init_code: impl Into<Bytes>) -> SafeTransaction {
+    SafeTransaction {
+        operation: DELEGATE_CALL,
+        ..call(create_call, PerformCreateCall { value, deployment_data: init_code.into() }.encode())
+    }
+}
+
+/// A protocol's contract on one chain and the asset supplied to it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Market {
+    pub chain: String,
+    /// The contract pulling the asset on supply, e.g. the Aave Pool or a Comet.
+    pub contract: Address,
+    pub asset: Address,
+}
+
+/// Builds the calls entering and leaving one protocol's markets.
+pub trait ActionBuilder: Send + Sync {
+    /// Name in decision records, e.g. `aave_v3`.
+    fn name(&self) -> &'static str;
+    /// Whether pools of `protocol`, as the pool source names it, belong to this builder.
+    fn handles(&self, protocol: &str) -> bool;
+    fn markets(&self) -> &[Market];
+    /// Supplies `amount` of the market's asset from `safe`.
+    fn supply(&self, market: &Market, amount: U256, safe: Address) -> SafeTransaction;
+    /// Withdraws `amount` of the market's asset back to `safe`.
+    fn withdraw(&self, market: &Market, amount: U256, safe: Address) -> SafeTransaction;
+    /// Typical gas of a supply, for cost estimates.
+    fn supply_gas(&self) -> u64;
+    /// Typical gas of a withdrawal, for cost estimates.
+    fn withdraw_gas(&self) -> u64;
+}
+
+/// What acting on a pool came to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ActionOutcome {
+    /// The calls went to the Safe, batched when there were several.
+    Executed { builder: &'static str, calls: usize },
+    /// No registered builder has a market for the pool; it is only suggested.
+    SuggestOnly { protocol: String, chain: String },
+}
+
+impl ActionOutcome {
+    pub fn suggest_only(pool: &PoolData) -> Self {
+        ActionOutcome::SuggestOnly { protocol: pool.protocol.clone(), chain: pool.chain.clone() }
+    }
+}
+
+impl fmt::Display for ActionOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ActionOutcome::Executed { builder, calls } => write!(f, "{} calls through {}", calls, builder),
+            ActionOutcome::SuggestOnly { protocol, chain } => {
+                write!(f, "suggest-only protocol: {} on {}", protocol, chain)
+            }
+        }
+    }
+}
+
+/// The action builders the agent knows, each with its configured markets. A pool is
+/// actionable when a builder handles its protocol and has a market on its chain.
+pub struct ProtocolRegistry {
+    multi_send: Address,
+    builders: Vec<Box<dyn ActionBuilder>>,
+}
+
+impl ProtocolRegistry {
+    /// A registry without builders, batching through the MultiSend at `multi_send`.
+    pub fn new(multi_send: Address) -> Self {
+        Self { multi_send, builders: Vec::new() }
+    }
+
+    /// Every built-in builder with the markets from `[actions]`.
+    pub fn from_config(config: &ActionsConfig) -> Result<Self> {
+        let mut registry = Self::new(config.multi_send()?);
+        let aave = config
+            .aave_v3
+            .iter()
+            .map(|m| Ok(Market { chain: m.chain.clone(), contract: m.pool()?, asset: m.asset()? }))
+            .collect::<Result<_>>()?;
+        registry.register(Box::new(aave_v3::AaveV3::new(aave)));
+        let compound = config
+            .compound_v3
+            .iter()
+            .map(|m| Ok(Market { chain: m.chain.clone(), contract: m.comet()?, asset: m.asset()? }))
+            .collect::<Result<_>>()?;
+        registry.register(Box::new(compound_v3::CompoundV3::new(compound)));
+        Ok(registry)
+    }
+
+    pub fn register(&mut self, builder: Box<dyn ActionBuilder>) {
+        self.builders.push(builder);
+    }
+
+    /// The builder and market acting on `pool`.
+    pub fn resolve(&self, pool: &PoolData) -> Option<(&dyn ActionBuilder, &Market)> {
+        self.market(&pool.protocol, &pool.chain)
+    }
+
+    fn market(&self, protocol: &str, chain: &str) -> Option<(&dyn ActionBuilder, &Market)> {
+        self.builders.iter().filter(|b| b.handles(protocol)).find_map(|builder| {
+            let market = builder.markets().iter().find(|m| m.chain.eq_ignore_ascii_case(chain))?;
+            Some((builder.as_ref(), market))
+        })
+    }
+
+    /// The builder and market holding `position`; `None` when its funds sit in the
+    /// Safe, or were routed to a suggest-only pool and never supplied.
+    fn holding(&self, position: &PositionEntry) -> Option<(&dyn ActionBuilder, &Market)> {
+        self.market(position.protocol.as_deref()?, &position.chain)
+    }
+
+    /// Name of the builder that can enter `pool`; `None` leaves it suggest-only.
+    pub fn builder_for(&self, pool: &PoolData) -> Option<&'static str> {
+        self.resolve(pool).map(|(builder, _)| builder.name())
+    }
+
+    /// The calls supplying `amount` of the market's asset from `safe` into `pool`,
+    /// after the approvals the allowance needs.
+    pub async fn entry_calls(
+        &self,
+        provider: &RpcProvider,
+        safe: Address,
+        pool: &PoolData,
+        amount: U256,
+    ) -> Result<Vec<SafeTransaction>> {
+        let (builder, market) = self.resolve(pool).ok_or_else(|| not_actionable(pool))?;
+        let mut calls = ensure_allowance(provider, market.asset, safe, market.contract, amount).await?;
+        calls.push(builder.supply(market, amount, safe));
+        Ok(calls)
+    }
+
+    /// The calls withdrawing `amount` of the market's asset from `pool` to `safe`.
+    pub fn exit_calls(&self, safe: Address, pool: &PoolData, amount: U256) -> Result<Vec<SafeTransaction>> {
+        let (builder, market) = self.resolve(pool).ok_or_else(|| not_actionable(pool))?;
+        Ok(vec![builder.withdraw(market, amount, safe)])
+    }
+
+    /// The calls supplying `amount` from `safe` into `pool` without reading the
+    /// allowance: an approval of exactly `amount`, which the supply uses up, then the
+    /// supply. For calls planned before the funds are there; empty when no builder
+    /// covers the pool.
+    pub fn supply_calls(&self, safe: Address, pool: &PoolData, amount: U256) -> Vec<SafeTransaction> {
+        self.resolve(pool)
+            .map(|(builder, market)| {
+                vec![approve(market.asset, market.contract, amount), builder.supply(market, amount, safe)]
+            })
+            .unwrap_or_default()
+    }
+
+    /// Typical gas of [`ProtocolRegistry::supply_calls`] for `pool`.
+    pub fn entry_gas(&self, pool: &PoolData) -> u64 {
+        self.resolve(pool).map_or(0, |(builder, _)| APPROVE_GAS + builder.supply_gas())
+    }
+
+    /// The calls withdrawing everything `position` has supplied back to `safe`; empty
+    /// when it holds nothing a registered builder supplied.
+    pub fn exit_position(&self, safe: Address, position: &PositionEntry) -> Result<Vec<SafeTransaction>> {
+        if position.pending_route.is_some() {
+            return Err(ProtocolError::InFlight(position.chain.clone()).into());
+        }
+        Ok(self
+            .holding(position)
+            .map(|(builder, market)| vec![builder.withdraw(market, U256::MAX, safe)])
+            .unwrap_or_default())
+    }
+
+    /// Typical gas of [`ProtocolRegistry::exit_position`] for `position`.
+    pub fn exit_gas(&self, position: &PositionEntry) -> u64 {
+        self.holding(position).map_or(0, |(builder, _)| builder.withdraw_gas())
+    }
+
+    /// `calls` batched through the configured MultiSend.
+    pub fn batch(&self, calls: &[SafeTransaction]) -> Result<SafeTransaction> {
+        multi_send(self.multi_send, calls)
+    }
+}
+
+fn not_actionable(pool: &PoolData) -> anyhow::Error {
+    ProtocolError::NotActionable { protocol: pool.protocol.clone(), chain: pool.chain.clone() }.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{MAINNET_AAVE_V3_POOL, MAINNET_COMET_WETH, MAINNET_WETH, MULTI_SEND_CALL_ONLY};
+    use ethers::core::types::TransactionRequest;
+
+    fn pool(protocol: &str, chain: &str) -> PoolData {
+        PoolData { protocol: protocol.to_string(), chain: chain.to_string(), apy: Some(3.0), tvl: 1e9 }
+    }
+
+    #[test]
+    fn test_approve_calldata() {
+        let tx = approve(Address::repeat_byte(0xaa), Address::repeat_byte(0xbb), U256::from(1_000_000));
+        assert_eq!(tx.to, Address::repeat_byte(0xaa));
+        assert_eq!(
+            hex::encode(&tx.data),
+            concat!(
+                "095ea7b3",
+                "000000000000000000000000bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb",
+                "00000000000000000000000000000000000000000000000000000000000f4240",
+            )
+        );
+    }
+
+    #[test]
+    fn test_multi_send_batch() {
+        let first = call(Address::repeat_byte(0xaa), vec![0x12, 0x34]);
+        let second = SafeTransaction { value: U256::from(5), ..call(Address::repeat_byte(0xbb), Vec::<u8>::new()) };
+        let multi_send_address: Address = MULTI_SEND_CALL_ONLY.parse().unwrap();
+        let batch = multi_send(multi_send_address, &[first.clone(), second]).unwrap();
+        assert_eq!(batch.to, multi_send_address);
+        assert_eq!(batch.operation, DELEGATE_CALL);
+        assert_eq!(batch.value, U256::zero());
+        let packed = concat!(
+            "00",
+            "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa",
+            "0000000000000000000000000000000000000000000000000000000000000000",
+            "0000000000000000000000000000000000000000000000000000000000000002",
+            "1234",
+            "00",
+            "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb",
+            "0000000000000000000000000000000000000000000000000000000000000005",
+            "0000000000000000000000000000000000000000000000000000000000000000",
+        );
+        // 172 packed bytes, padded to 192
+        assert_eq!(
+            hex::encode(&batch.data),
+            format!(
+                "8d80ff0a{}{}{}{}",
+                "0000000000000000000000000000000000000000000000000000000000000020",
+                "00000000000000000000000000000000000000000000000000000000000000ac",
+                packed,
+                "00".repeat(20)
+            )
+        );
+
+        // One call needs no batch, and delegatecalls cannot be batched
+        assert_eq!(multi_send(multi_send_address, std::slice::from_ref(&first)).unwrap().data, first.data);
+        let nested = SafeTransaction { operation: DELEGATE_CALL, ..first.clone() };
+        assert!(multi_send(multi_send_address, &[first, nested]).is_err());
+        assert!(multi_send(multi_send_address, &[]).is_err());
+    }
+
+    #[test]
+    fn test_registry_dispatches_by_protocol_and_chain() {
+        let registry = ProtocolRegistry::from_config(&ActionsConfig::default()).unwrap();
+        assert_eq!(registry.builder_for(&pool("Aave", "Ethereum")), Some(aave_v3::NAME));
+        assert_eq!(registry.builder_for(&pool("compound-v3", "Ethereum")), Some(compound_v3::NAME));
+        assert_eq!(registry.builder_for(&pool("Compound", "Ethereum")), Some(compound_v3::NAME));
+        // No market on the chain, or no builder for the protocol
+        assert_eq!(registry.builder_for(&pool("Aave", "Arbitrum")), None);
+        assert_eq!(registry.builder_for(&pool("Lido", "Ethereum")), None);
+
+        let safe = Address::repeat_byte(0x11);
+        let exit = registry.exit_calls(safe, &pool("Compound", "Ethereum"), U256::MAX).unwrap();
+        let withdraw = compound_v3::withdraw(MAINNET_COMET_WETH.parse().unwrap(), MAINNET_WETH.parse().unwrap(), U256::MAX);
+        assert_eq!(exit.len(), 1);
+        assert_eq!((exit[0].to, &exit[0].data), (withdraw.to, &withdraw.data));
+        let err = registry.exit_calls(safe, &pool("Lido", "Ethereum"), U256::one()).unwrap_err();
+        assert!(err.to_string().contains("suggest-only protocol"), "{}", err);
+        assert_eq!(
+            ActionOutcome::suggest_only(&pool("Lido", "Ethereum")).to_string(),
+            "suggest-only protocol: Lido on Ethereum"
+        );
+
+        // Registering a builder is all another protocol takes
+        let mut registry = ProtocolRegistry::new(MULTI_SEND_CALL_ONLY.parse().unwrap());
+        assert_eq!(registry.builder_for(&pool("Aave", "Ethereum")), None);
+        let market = Market { chain: "Base".to_string(), contract: Address::repeat_byte(1), asset: Address::repeat_byte(2) };
+        registry.register(Box::new(aave_v3::AaveV3::new(vec![market])));
+        assert_eq!(registry.builder_for(&pool("Aave", "Base")), Some(aave_v3::NAME));
+    }
+
+    #[tokio::test]
+    #[ignore = "needs anvil and ASAM_FORK_URL"]
+    async fn fork_supply_weth_to_aave() {
+        let Some(fork) = crate::test_support::AnvilFork::from_env().await.unwrap() else {
+            return;
+        };
+        let provider = fork.provider();
+        let safe = Address::repeat_byte(0x5a);
+        let weth: Address = MAINNET_WETH.parse().unwrap();
+        let a_weth: Address = "0x4d5F47FA6A74757f35C14fD3a6Ef8E3C9BC514E8".parse().unwrap();
+        let amount = U256::exp10(18);
+        fork.set_balance(safe, U256::exp10(19)).await.unwrap();
+        fork.impersonate(safe).await.unwrap();
+        // WETH.deposit() wraps the ether sent with it
+        let wrap = TransactionRequest::new().to(weth).value(amount * 2).data(vec![0xd0, 0xe3, 0x0d, 0xb0]);
+        assert_eq!(fork.send_as(safe, wrap).await.unwrap().status, Some(1.into()));
+
+        let registry = ProtocolRegistry::from_config(&ActionsConfig::default()).unwrap();
+        let calls = registry.entry_calls(&provider, safe, &pool("Aave", "Ethereum"), amount).await.unwrap();
+        assert_eq!(calls.len(), 2, "approval and supply");
+        assert_eq!(calls[0].to, weth);
+        assert_eq!(calls[1].to, MAINNET_AAVE_V3_POOL.parse::<Address>().unwrap());
+        for tx in &calls {
+            let request = TransactionRequest::new().to(tx.to).data(tx.data.clone());
+            assert_eq!(fork.send_as(safe, request).await.unwrap().status, Some(1.into()));
+        }
+
+        let supplied = Erc20::new(a_weth, Arc::new(provider.clone())).balance_of(safe).call().await.unwrap();
+        assert!(supplied >= amount - 1, "{}", supplied);
+        // The supply used up the allowance
+        assert_eq!(ensure_allowance(&provider, weth, safe, calls[1].to, amount).await.unwrap().len(), 1);
+        fork.stop_impersonating(safe).await.unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore = "needs anvil and ASAM_FORK_URL"]
+    async fn fork_supply_and_withdraw_weth_on_compound() {
+        let Some(fork) = crate::test_support::AnvilFork::from_env().await.unwrap() else {
+            return;
+        };
+        let provider = fork.provider();
+        let client = Arc::new(provider.clone());
+        let safe = Address::repeat_byte(0x5b);
+        let weth: Address = MAINNET_WETH.parse().unwrap();
+        let comet: Address = MAINNET_COMET_WETH.parse().unwrap();
+        let amount = U256::exp10(18);
+        fork.set_balance(safe, U256::exp10(19)).await.unwrap();
+        fork.impersonate(safe).await.unwrap();
+        let wrap = TransactionRequest::new().to(weth).value(amount).data(vec![0xd0, 0xe3, 0x0d, 0xb0]);
+        assert_eq!(fork.send_as(safe, wrap).await.unwrap().status, Some(1.into()));
+        let send = |txs: Vec<SafeTransaction>| async {
+            for tx in txs {
+                let request = TransactionRequest::new().to(tx.to).data(tx.data);
+                assert_eq!(fork.send_as(safe, request).await.unwrap().status, Some(1.into()));
+            }
+        };
+
+        let registry = ProtocolRegistry::from_config(&ActionsConfig::default()).unwrap();
+        let compound = pool("Compound", "Ethereum");
+        let calls = registry.entry_calls(&provider, safe, &compound, amount).await.unwrap();
+        assert_eq!(calls.len(), 2, "approval and supply");
+        assert_eq!(calls[1].to, comet);
+        send(calls).await;
+        let supplied = compound_v3::Comet::new(comet, client.clone()).balance_of(safe).call().await.unwrap();
+        assert!(supplied >= amount - 1, "{}", supplied);
+
+        send(registry.exit_calls(safe, &compound, U256::MAX).unwrap()).await;
+        assert!(compound_v3::Comet::new(comet, client.clone()).balance_of(safe).call().await.unwrap().is_zero());
+        let returned = Erc20::new(weth, client).balance_of(safe).call().await.unwrap();
+        assert!(returned >= amount - 1, "{}", returned);
+        fork.stop_impersonating(safe).await.unwrap();
+    }
+}
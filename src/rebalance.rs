@@ -0,0 +1,334 @@
+//! Cross-chain rebalances that unwind before bridging away.
+//!
+//! Funds supplied to a protocol have to be withdrawn before they can be bridged, and
+//! supplied again once they land. A [`RebalancePlan`] holds the three stages in order:
+//! the calls exiting the current position, the route, and the calls entering the new
+//! pool. [`advance`] runs the plan from its current stage and stores it after every
+//! stage, so after a restart the open plan resumes where it stopped instead of
+//! withdrawing or bridging twice. The enter stage waits for the route to land.
+//!
+//! Either end may be empty: idle funds need no exit, and funds bridged to a
+//! suggest-only pool are not supplied. [`breakeven_days`] weighs the gas of both
+//! ends against the yield the move gains.
+
+use anyhow::Result;
+use async_trait::async_trait;
+use ethers::core::types::Address;
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::agents::cross_chain_router::RouteState;
+use crate::agents::defi_optimizer::PoolData;
+use crate::agents::safe_manager::SafeTransaction;
+use crate::portfolio::PositionEntry;
+use crate::protocol_actions::ProtocolRegistry;
+use crate::storage::Store;
+use crate::units::TokenAmount;
+
+/// The bridge leg of a plan.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RouteRequest {
+    pub amount: f64,
+    pub source_chain: String,
+    pub target_chain: String,
+}
+
+/// Next stage of a plan to run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlanStage {
+    Exit,
+    Route,
+    /// Runs once the route has landed.
+    Enter,
+    Done,
+    /// The route failed; the funds are back on the source chain, out of the protocol.
+    Failed,
+}
+
+impl PlanStage {
+    pub fn is_open(&self) -> bool {
+        !matches!(self, PlanStage::Done | PlanStage::Failed)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RebalancePlan {
+    pub id: String,
+    pub created_at: u64,
+    /// Protocol the funds leave, when they were supplied to one.
+    pub from_protocol: Option<String>,
+    pub to_protocol: String,
+    /// Withdrawals on the source chain.
+    pub exit: Vec<SafeTransaction>,
+    pub route: RouteRequest,
+    /// Approval and supply on the target chain.
+    pub enter: Vec<SafeTransaction>,
+    /// Typical gas of the exit and enter calls.
+    pub exit_gas: u64,
+    pub enter_gas: u64,
+    pub stage: PlanStage,
+    /// Route started by the route stage.
+    pub route_id: Option<String>,
+}
+
+impl RebalancePlan {
+    /// Plans moving `amount` from `position` into `target`, unwinding whatever a
+    /// registered builder supplied first.
+    pub fn build(
+        registry: &ProtocolRegistry,
+        safe: Address,
+        position: &PositionEntry,
+        target: &PoolData,
+        amount: f64,
+        now: u64,
+    ) -> Result<Self> {
+        let exit = registry.exit_position(safe, position)?;
+        let enter = registry.supply_calls(safe, target, TokenAmount::native_from_f64(amount).raw);
+        Ok(Self {
+            id: format!("plan-{}-{}-{}", now, position.chain, target.chain).to_lowercase(),
+            created_at: now,
+            from_protocol: position.protocol.clone().filter(|_| !exit.is_empty()),
+            to_protocol: target.protocol.clone(),
+            exit_gas: registry.exit_gas(position),
+            enter_gas: registry.entry_gas(target),
+            exit,
+            route: RouteRequest {
+                amount,
+                source_chain: position.chain.clone(),
+                target_chain: target.chain.clone(),
+            },
+            enter,
+            stage: PlanStage::Exit,
+            route_id: None,
+        })
+    }
+
+    /// Whether the move needs more than a route.
+    pub fn unwinds_or_enters(&self) -> bool {
+        !self.exit.is_empty() || !self.enter.is_empty()
+    }
+
+    pub fn describe(&self) -> String {
+        format!(
+            "{} on {} -> {} on {}",
+            self.from_protocol.as_deref().unwrap_or("idle funds"),
+            self.route.source_chain,
+            self.to_protocol,
+            self.route.target_chain
+        )
+    }
+}
+
+/// Days the extra `apy_gain` points on `amount` take to pay for `gas` at
+/// `gas_price_gwei`; `None` when the move gains nothing.
+pub fn breakeven_days(gas: u64, gas_price_gwei: f64, amount: f64, apy_gain: f64) -> Option<f64> {
+    let daily = amount * apy_gain / 100.0 / 365.0;
+    (daily > 0.0).then(|| gas as f64 * gas_price_gwei * 1e-9 / daily)
+}
+
+/// Carries out the stages of a plan.
+#[async_trait]
+pub trait PlanExecutor: Send + Sync {
+    /// Sends `calls` from the Safe on `chain`.
+    async fn execute(&self, chain: &str, calls: &[SafeTransaction]) -> Result<()>;
+    /// Starts the route, returning its id; `None` when no route was started.
+    async fn start_route(&self, route: &RouteRequest) -> Result<Option<String>>;
+    /// State of a started route; `None` when it is unknown.
+    fn route_state(&self, route_id: &str) -> Option<RouteState>;
+}
+
+/// Runs `plan` from its current stage as far as it goes, storing it after every
+/// stage, and returns the stage it stopped at. A failing stage is left to be retried;
+/// the enter stage waits while the route is in flight.
+pub async fn advance(plan: &mut RebalancePlan, executor: &dyn PlanExecutor, store: &dyn Store) -> Result<PlanStage> {
+    loop {
+        match plan.stage {
+            PlanStage::Exit => {
+                if !plan.exit.is_empty() {
+                    info!("Plan {}: exiting {}", plan.id, plan.describe());
+                    executor.execute(&plan.route.source_chain, &plan.exit).await?;
+                }
+                plan.stage = PlanStage::Route;
+            }
+            PlanStage::Route => {
+                info!(
+                    "Plan {}: routing {} from {} to {}",
+                    plan.id, plan.route.amount, plan.route.source_chain, plan.route.target_chain
+                );
+                plan.route_id = executor.start_route(&plan.route).await?;
+                plan.stage = PlanStage::Enter;
+            }
+            PlanStage::Enter => {
+                if let Some(route_id) = &plan.route_id {
+                    match executor.route_state(route_id) {
+                        Some(RouteState::Completed) => {}
+                        Some(RouteState::Failed) => {
+                            warn!("Plan {}: route {} failed, not entering {}", plan.id, route_id, plan.to_protocol);
+                            plan.stage = PlanStage::Failed;
+                            store.save_plan(plan).await?;
+                            return Ok(plan.stage);
+                        }
+                        state => {
+                            debug!("Plan {}: route {} not landed yet ({:?})", plan.id, route_id, state);
+                            return Ok(plan.stage);
+                        }
+                    }
+                }
+                if !plan.enter.is_empty() {
+                    info!("Plan {}: entering {} on {}", plan.id, plan.to_protocol, plan.route.target_chain);
+                    executor.execute(&plan.route.target_chain, &plan.enter).await?;
+                }
+                plan.stage = PlanStage::Done;
+            }
+            PlanStage::Done | PlanStage::Failed => return Ok(plan.stage),
+        }
+        store.save_plan(plan).await?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{ActionsConfig, CometMarketConfig, MAINNET_AAVE_V3_POOL, MAINNET_WETH};
+    use crate::protocol_actions::{aave_v3, compound_v3};
+    use crate::storage::MemoryStore;
+    use anyhow::anyhow;
+    use ethers::core::types::U256;
+    use std::sync::Mutex;
+
+    const ARBITRUM_COMET_WETH: &str = "0x6f7D514bbD4aFf3BcD1140B7344b32f063dEe486";
+    const ARBITRUM_WETH: &str = "0x82aF49447D8a07e3bd95BD0d56f35241523fBab1";
+
+    /// Records each stage it runs; the route's state and a failing route are set up front.
+    struct Recorder {
+        log: Mutex<Vec<String>>,
+        route_state: Mutex<Option<RouteState>>,
+        fail_route: bool,
+    }
+
+    impl Recorder {
+        fn new(route_state: Option<RouteState>, fail_route: bool) -> Self {
+            Self { log: Mutex::new(Vec::new()), route_state: Mutex::new(route_state), fail_route }
+        }
+
+        fn log(&self) -> Vec<String> {
+            self.log.lock().unwrap().clone()
+        }
+    }
+
+    #[async_trait]
+    impl PlanExecutor for Recorder {
+        async fn execute(&self, chain: &str, calls: &[SafeTransaction]) -> Result<()> {
+            self.log.lock().unwrap().push(format!("execute {} x{}", chain, calls.len()));
+            Ok(())
+        }
+
+        async fn start_route(&self, route: &RouteRequest) -> Result<Option<String>> {
+            if self.fail_route {
+                return Err(anyhow!("bridge unavailable"));
+            }
+            self.log.lock().unwrap().push(format!("route {} -> {}", route.source_chain, route.target_chain));
+            Ok(Some("r-1".to_string()))
+        }
+
+        fn route_state(&self, _route_id: &str) -> Option<RouteState> {
+            *self.route_state.lock().unwrap()
+        }
+    }
+
+    /// Aave on Ethereum held, Compound on Arbitrum the target.
+    fn plan() -> RebalancePlan {
+        let mut actions = ActionsConfig::default();
+        actions.compound_v3.push(CometMarketConfig {
+            chain: "Arbitrum".to_string(),
+            comet: ARBITRUM_COMET_WETH.to_string(),
+            asset: ARBITRUM_WETH.to_string(),
+        });
+        let registry = ProtocolRegistry::from_config(&actions).unwrap();
+        let position = PositionEntry {
+            chain: "Ethereum".to_string(),
+            protocol: Some("Aave".to_string()),
+            token: "ETH".to_string(),
+            amount: 2.0,
+            since: 0,
+            pending_route: None,
+        };
+        let target = PoolData { protocol: "Compound".to_string(), chain: "Arbitrum".to_string(), apy: Some(6.0), tvl: 1e9 };
+        RebalancePlan::build(&registry, Address::repeat_byte(0x5a), &position, &target, 1.5, 1_000).unwrap()
+    }
+
+    #[test]
+    fn test_plan_unwinds_routes_and_enters() {
+        let plan = plan();
+        assert_eq!(plan.stage, PlanStage::Exit);
+        assert_eq!(plan.describe(), "Aave on Ethereum -> Compound on Arbitrum");
+        assert_eq!(plan.exit.len(), 1);
+        let withdraw = aave_v3::withdraw(
+            MAINNET_AAVE_V3_POOL.parse().unwrap(),
+            MAINNET_WETH.parse().unwrap(),
+            U256::MAX,
+            Address::repeat_byte(0x5a),
+        );
+        assert_eq!(plan.exit[0], withdraw);
+        assert_eq!(plan.route, RouteRequest { amount: 1.5, source_chain: "Ethereum".to_string(), target_chain: "Arbitrum".to_string() });
+        let comet: Address = ARBITRUM_COMET_WETH.parse().unwrap();
+        let amount = U256::exp10(18) * 3 / 2;
+        assert_eq!(plan.enter.len(), 2);
+        assert_eq!(plan.enter[0].to, ARBITRUM_WETH.parse::<Address>().unwrap());
+        assert_eq!(plan.enter[1], compound_v3::supply(comet, ARBITRUM_WETH.parse().unwrap(), amount));
+        assert_eq!((plan.exit_gas, plan.enter_gas), (250_000, 200_000));
+    }
+
+    #[tokio::test]
+    async fn test_stages_run_in_order_and_persist() {
+        let store = MemoryStore::new();
+        let mut plan = plan();
+        let executor = Recorder::new(Some(RouteState::Pending), false);
+
+        // Stops at the enter stage while the route is in flight
+        assert_eq!(advance(&mut plan, &executor, &store).await.unwrap(), PlanStage::Enter);
+        assert_eq!(executor.log(), vec!["execute Ethereum x1", "route Ethereum -> Arbitrum"]);
+        let stored = store.open_plan().await.unwrap().unwrap();
+        assert_eq!(stored, plan);
+        assert_eq!(stored.route_id.as_deref(), Some("r-1"));
+
+        *executor.route_state.lock().unwrap() = Some(RouteState::Completed);
+        assert_eq!(advance(&mut plan, &executor, &store).await.unwrap(), PlanStage::Done);
+        assert_eq!(executor.log(), vec!["execute Ethereum x1", "route Ethereum -> Arbitrum", "execute Arbitrum x2"]);
+        assert_eq!(store.open_plan().await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_resumes_from_the_route_stage() {
+        let store = MemoryStore::new();
+        let mut interrupted = plan();
+        // The exit went through, then the bridge call failed
+        let failing = Recorder::new(None, true);
+        assert!(advance(&mut interrupted, &failing, &store).await.is_err());
+        assert_eq!(failing.log(), vec!["execute Ethereum x1"]);
+
+        // After a restart the stored plan picks up at the route, without exiting again
+        let mut resumed = store.open_plan().await.unwrap().unwrap();
+        assert_eq!(resumed.stage, PlanStage::Route);
+        let executor = Recorder::new(Some(RouteState::Completed), false);
+        assert_eq!(advance(&mut resumed, &executor, &store).await.unwrap(), PlanStage::Done);
+        assert_eq!(executor.log(), vec!["route Ethereum -> Arbitrum", "execute Arbitrum x2"]);
+
+        // A failed route leaves the funds where they were
+        let mut failed = plan();
+        let executor = Recorder::new(Some(RouteState::Failed), false);
+        assert_eq!(advance(&mut failed, &executor, &store).await.unwrap(), PlanStage::Failed);
+        assert_eq!(executor.log(), vec!["execute Ethereum x1", "route Ethereum -> Arbitrum"]);
+        assert_eq!(store.open_plan().await.unwrap(), None);
+    }
+
+    #[test]
+    fn test_breakeven_counts_exit_and_enter_gas() {
+        let plan = plan();
+        // 450k gas at 20 gwei is 0.009 ETH; 2 points on 1.5 ETH earn 0.03 ETH a year
+        let days = breakeven_days(plan.exit_gas + plan.enter_gas, 20.0, 1.5, 2.0).unwrap();
+        assert!((days - 109.5).abs() < 1e-9, "{}", days);
+        assert_eq!(breakeven_days(plan.exit_gas, 20.0, 1.5, 0.0), None);
+    }
+}
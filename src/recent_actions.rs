@@ -0,0 +1,177 @@
+//! Recently taken actions, to hold back identical repeats.
+//!
+//! The notifier worker records each rebalance alert under its pool and drops the same
+//! alert again within `notify.rebalance_alert_cooldown_secs`. Completed routes are
+//! recorded under their pair and token; the strategy and the router refuse a route of
+//! about the same amount (within [`AMOUNT_TOLERANCE`]) inside
+//! `router.route_dedup_window_secs`. Entries are saved to the store so the windows
+//! survive restarts, and dropped once older than the longest window.
+
+use anyhow::Result;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::agents::cross_chain_router::RouteReceipt;
+use crate::portfolio::TOKEN;
+use crate::storage::Store;
+
+/// Relative difference under which two route amounts count as the same.
+pub const AMOUNT_TOLERANCE: f64 = 0.01;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecentAction {
+    pub key: String,
+    /// Unix time the action was taken.
+    pub at: u64,
+    /// Amount moved, for routes.
+    pub amount: Option<f64>,
+}
+
+/// Key of the rebalance alert for a pool.
+pub fn rebalance_alert_key(protocol: &str, chain: &str) -> String {
+    format!("alert:rebalance:{}:{}", protocol, chain)
+}
+
+/// Key of a completed route of `token` from `source_chain` to `target_chain`.
+pub fn route_key(source_chain: &str, target_chain: &str, token: &str) -> String {
+    format!("route:{}->{}:{}", source_chain, target_chain, token)
+}
+
+/// Whether `a` and `b` are within [`AMOUNT_TOLERANCE`] of each other.
+pub fn same_amount(a: f64, b: f64) -> bool {
+    (a - b).abs() <= a.abs().max(b.abs()) * AMOUNT_TOLERANCE
+}
+
+/// Shared, cloneable cache of the last action per key.
+#[derive(Clone)]
+pub struct RecentActions {
+    entries: Arc<Mutex<HashMap<String, RecentAction>>>,
+    store: Option<Arc<dyn Store>>,
+    /// Entries older than this are pruned.
+    max_age: Duration,
+}
+
+impl RecentActions {
+    /// Keeps entries in memory only.
+    pub fn new(max_age: Duration) -> Self {
+        Self {
+            entries: Arc::default(),
+            store: None,
+            max_age,
+        }
+    }
+
+    /// Restores the entries saved in `store` that are younger than `max_age` and saves
+    /// new ones there.
+    pub async fn load(store: Arc<dyn Store>, max_age: Duration, now: u64) -> Result<Self> {
+        let since = now.saturating_sub(max_age.as_secs());
+        store.prune_actions(since).await?;
+        let entries = store
+            .recent_actions(since)
+            .await?
+            .into_iter()
+            .map(|action| (action.key.clone(), action))
+            .collect();
+        Ok(Self {
+            entries: Arc::new(Mutex::new(entries)),
+            store: Some(store),
+            max_age,
+        })
+    }
+
+    fn entries(&self) -> std::sync::MutexGuard<'_, HashMap<String, RecentAction>> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// The last action under `key` taken less than `window` before `now`.
+    pub fn within(&self, key: &str, window: Duration, now: u64) -> Option<RecentAction> {
+        self.entries()
+            .get(key)
+            .filter(|action| now.saturating_sub(action.at) < window.as_secs())
+            .cloned()
+    }
+
+    /// Records `action` as the last one under its key and prunes expired entries.
+    /// Storage failures are logged; the entry is kept in memory regardless.
+    pub async fn record(&self, action: RecentAction) {
+        let since = action.at.saturating_sub(self.max_age.as_secs());
+        {
+            let mut entries = self.entries();
+            entries.retain(|_, entry| entry.at >= since);
+            entries.insert(action.key.clone(), action.clone());
+        }
+        if let Some(store) = &self.store {
+            let saved = async {
+                store.record_action(&action).await?;
+                store.prune_actions(since).await
+            };
+            if let Err(e) = saved.await {
+                warn!("Failed to save recent action {}: {:#}", action.key, e);
+            }
+        }
+    }
+
+    /// Records a completed route under its chains and token.
+    pub async fn route_completed(&self, receipt: &RouteReceipt) {
+        self.record(RecentAction {
+            key: route_key(&receipt.source_chain, &receipt.target_chain, TOKEN),
+            at: receipt.updated_at,
+            amount: Some(receipt.amount),
+        })
+        .await;
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryStore;
+
+    const HOUR: Duration = Duration::from_secs(3600);
+
+    fn route(at: u64, amount: f64) -> RecentAction {
+        RecentAction { key: route_key("Ethereum", "Arbitrum", "ETH"), at, amount: Some(amount) }
+    }
+
+    #[tokio::test]
+    async fn test_actions_expire_and_survive_restarts() {
+        let store: Arc<dyn Store> = Arc::new(MemoryStore::new());
+        let actions = RecentActions::load(store.clone(), HOUR * 24, 0).await.unwrap();
+        let key = route_key("Ethereum", "Arbitrum", "ETH");
+        actions.record(route(1_000, 1.0)).await;
+
+        // Suppressed inside the window, allowed once it passed
+        assert_eq!(actions.within(&key, HOUR, 1_000 + 3_599), Some(route(1_000, 1.0)));
+        assert_eq!(actions.within(&key, HOUR, 1_000 + 3_600), None);
+        assert_eq!(actions.within(&route_key("Arbitrum", "Ethereum", "ETH"), HOUR, 1_000), None);
+
+        let restored = RecentActions::load(store.clone(), HOUR * 24, 2_000).await.unwrap();
+        assert_eq!(restored.within(&key, HOUR, 2_000), Some(route(1_000, 1.0)));
+
+        // Anything older than the longest window is pruned, in memory and in the store
+        let alert = RecentAction { key: rebalance_alert_key("Aave", "Arbitrum"), at: 100_000, amount: None };
+        actions.record(alert.clone()).await;
+        assert_eq!(actions.len(), 1);
+        assert_eq!(store.recent_actions(0).await.unwrap(), vec![alert]);
+    }
+
+    #[test]
+    fn test_amounts_within_one_percent_match() {
+        assert!(same_amount(1.0, 1.0));
+        assert!(same_amount(1.0, 1.0099));
+        assert!(same_amount(0.995, 1.0));
+        assert!(!same_amount(1.0, 1.02));
+        assert!(!same_amount(1.0, 0.98));
+    }
+}
@@ -0,0 +1,345 @@
+//! Config hot-reload.
+//!
+//! On SIGHUP, or when the file changes with `runtime.watch_config`, the config is
+//! loaded again and compared with the running one. Changes that can be applied live
+//! are published with a new [`ConfigVersion`]:
+//!
+//! - `[optimizer]`: data source, filters and scoring
+//! - `safe.min_balance_eth` and each chain's `min_balance_eth`
+//! - `[notify]`, except `queue_capacity`, `suppression_window_secs` and
+//!   `rebalance_alert_cooldown_secs`
+//! - the phase intervals and `runtime.cycle_timeout_secs`
+//! - each chain's `active` flag
+//!
+//! Any other change is logged and keeps its running value until a restart.
+
+use anyhow::Result;
+use log::{error, info, warn};
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::signal::unix::{signal, Signal, SignalKind};
+use tokio::sync::watch;
+use tokio_util::sync::CancellationToken;
+
+use crate::config::{Config, NotifyConfig};
+
+/// How often the file's modification time is checked with `runtime.watch_config`.
+const WATCH_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Generation of the running config: 1 at startup, incremented by every reload that
+/// changes something.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(transparent)]
+pub struct ConfigVersion(pub u64);
+
+impl ConfigVersion {
+    pub const INITIAL: ConfigVersion = ConfigVersion(1);
+
+    pub fn next(self) -> Self {
+        ConfigVersion(self.0 + 1)
+    }
+}
+
+impl Default for ConfigVersion {
+    fn default() -> Self {
+        Self::INITIAL
+    }
+}
+
+impl fmt::Display for ConfigVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Loads the config again, with the same sources and command line overrides as at startup.
+pub type ConfigLoader = Box<dyn Fn() -> Result<Config> + Send + Sync>;
+
+/// Where a running agent reloads its config from.
+pub struct ConfigSource {
+    /// Watched for changes with `runtime.watch_config`.
+    pub path: Option<PathBuf>,
+    pub load: ConfigLoader,
+}
+
+/// A config to apply to the running components.
+#[derive(Debug, Clone)]
+pub struct ConfigUpdate {
+    pub version: ConfigVersion,
+    pub config: Arc<Config>,
+}
+
+/// A freshly loaded config compared with the running one.
+#[derive(Debug, Clone)]
+pub struct Reload {
+    /// The running config with every live-applicable change taken over.
+    pub config: Config,
+    /// Changed settings that are applied live.
+    pub applied: Vec<String>,
+    /// Changed settings that need a restart; they keep their running values.
+    pub rejected: Vec<String>,
+}
+
+impl Reload {
+    pub fn new(running: &Config, loaded: &Config) -> Self {
+        let mut config = running.clone();
+        config.optimizer = loaded.optimizer.clone();
+        config.safe.min_balance_eth = loaded.safe.min_balance_eth;
+        config.notify = NotifyConfig {
+            queue_capacity: running.notify.queue_capacity,
+            suppression_window_secs: running.notify.suppression_window_secs,
+            rebalance_alert_cooldown_secs: running.notify.rebalance_alert_cooldown_secs,
+            ..loaded.notify.clone()
+        };
+        let runtime = &mut config.runtime;
+        runtime.cycle_interval_secs = loaded.runtime.cycle_interval_secs;
+        runtime.balance_interval_secs = loaded.runtime.balance_interval_secs;
+        runtime.pool_interval_secs = loaded.runtime.pool_interval_secs;
+        runtime.route_interval_secs = loaded.runtime.route_interval_secs;
+        runtime.cycle_timeout_secs = loaded.runtime.cycle_timeout_secs;
+        // Flags and thresholds follow as long as the chains themselves stay the same
+        let same_chains = running.router.chains.len() == loaded.router.chains.len()
+            && running.router.chains.iter().zip(&loaded.router.chains).all(|(a, b)| {
+                a.name == b.name && a.chain_id == b.chain_id && a.rpc_url == b.rpc_url
+            });
+        if same_chains {
+            for (chain, new) in config.router.chains.iter_mut().zip(&loaded.router.chains) {
+                chain.active = new.active;
+                chain.min_balance_eth = new.min_balance_eth;
+            }
+        }
+
+        Self {
+            applied: changed_keys(running, &config),
+            rejected: changed_keys(&config, loaded),
+            config,
+        }
+    }
+}
+
+/// Dotted paths of the settings that differ between `a` and `b`.
+fn changed_keys(a_config: &Config, b_config: &Config) -> Vec<String> {
+    let (Ok(a), Ok(b)) = (toml::Value::try_from(a_config), toml::Value::try_from(b_config)) else {
+        return vec!["<config>".to_string()];
+    };
+    let mut keys = Vec::new();
+    diff("", &a, &b, &mut keys);
+    let (a_secrets, b_secrets) = (a_config.secret_fields(), b_config.secret_fields());
+    // Secrets serialize redacted, so only a set or unset one shows up in the diff
+    for ((key, x), (_, y)) in a_secrets.into_iter().zip(b_secrets) {
+        if x != y && !keys.iter().any(|k| k == key) {
+            let at = keys.partition_point(|k| k.as_str() < key);
+            keys.insert(at, key.to_string());
+        }
+    }
+    keys
+}
+
+fn diff(path: &str, a: &toml::Value, b: &toml::Value, keys: &mut Vec<String>) {
+    let join = |key: &str| if path.is_empty() { key.to_string() } else { format!("{}.{}", path, key) };
+    match (a, b) {
+        (toml::Value::Table(a), toml::Value::Table(b)) => {
+            for key in a.keys().chain(b.keys()).collect::<BTreeSet<_>>() {
+                match (a.get(key), b.get(key)) {
+                    (Some(x), Some(y)) => diff(&join(key), x, y, keys),
+                    (x, y) if x != y => keys.push(join(key)),
+                    _ => {}
+                }
+            }
+        }
+        (toml::Value::Array(a), toml::Value::Array(b)) if a.len() == b.len() => {
+            for (i, (x, y)) in a.iter().zip(b).enumerate() {
+                diff(&format!("{}[{}]", path, i), x, y, keys);
+            }
+        }
+        _ if a != b => keys.push(path.to_string()),
+        _ => {}
+    }
+}
+
+/// Keeps the running config and its version, and turns reload requests into updates.
+pub struct Reloader {
+    load: ConfigLoader,
+    running: Config,
+    version: ConfigVersion,
+}
+
+impl Reloader {
+    pub fn new(load: ConfigLoader, running: Config) -> Self {
+        Self { load, running, version: ConfigVersion::INITIAL }
+    }
+
+    pub fn version(&self) -> ConfigVersion {
+        self.version
+    }
+
+    /// Loads the config and returns the update to apply, if anything applicable
+    /// changed. A config that fails to load or validate is ignored.
+    pub fn reload(&mut self) -> Option<ConfigUpdate> {
+        let loaded = match (self.load)() {
+            Ok(loaded) => loaded,
+            Err(e) => {
+                error!("Config reload failed, keeping version {}: {:#}", self.version, e);
+                return None;
+            }
+        };
+        let reload = Reload::new(&self.running, &loaded);
+        for key in &reload.rejected {
+            warn!("Config reload: `{}` changed but only takes effect after a restart", key);
+        }
+        if reload.applied.is_empty() {
+            info!("Config reload: nothing to apply, staying at version {}", self.version);
+            return None;
+        }
+        self.version = self.version.next();
+        info!("Config version {} applied: {}", self.version, reload.applied.join(", "));
+        self.running = reload.config;
+        Some(ConfigUpdate { version: self.version, config: Arc::new(self.running.clone()) })
+    }
+}
+
+fn modified_at(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+async fn hangup(signal: &mut Option<Signal>) {
+    match signal {
+        Some(signal) => {
+            signal.recv().await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
+/// Reloads on SIGHUP and, with `runtime.watch_config`, whenever the file's
+/// modification time changes, publishing each update until `shutdown` fires.
+pub async fn watch(
+    source: ConfigSource,
+    running: Config,
+    updates: watch::Sender<ConfigUpdate>,
+    shutdown: CancellationToken,
+) {
+    let watched = match (&source.path, running.runtime.watch_config) {
+        (Some(path), true) => Some(path.clone()),
+        (None, true) => {
+            warn!("runtime.watch_config needs a config file; reloading on SIGHUP only");
+            None
+        }
+        (_, false) => None,
+    };
+    let mut hangups = signal(SignalKind::hangup())
+        .map_err(|e| warn!("Cannot listen for SIGHUP, config reload is disabled: {}", e))
+        .ok();
+    let mut last_modified = watched.as_deref().and_then(modified_at);
+    let mut ticker = tokio::time::interval(WATCH_INTERVAL);
+    let mut reloader = Reloader::new(source.load, running);
+
+    loop {
+        tokio::select! {
+            _ = shutdown.cancelled() => return,
+            _ = hangup(&mut hangups) => info!("SIGHUP received, reloading config"),
+            _ = ticker.tick(), if watched.is_some() => {
+                let path = watched.as_deref().unwrap_or(Path::new(""));
+                let modified = modified_at(path);
+                if modified == last_modified {
+                    continue;
+                }
+                last_modified = modified;
+                info!("{} changed, reloading config", path.display());
+            }
+        }
+        if let Some(update) = reloader.reload() {
+            updates.send_replace(update);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn running() -> Config {
+        let mut config = Config::default();
+        config.safe.rpc_url = Some("http://localhost:8545".to_string());
+        config.storage.path = Some(PathBuf::from("asam.db"));
+        config
+    }
+
+    #[test]
+    fn test_reload_applies_safe_subset() {
+        let running = running();
+        let mut loaded = running.clone();
+        loaded.optimizer.min_apy = 4.0;
+        loaded.safe.min_balance_eth = 0.5;
+        loaded.runtime.pool_interval_secs = Some(300);
+        loaded.router.chains[1].active = false;
+        loaded.notify.webhook.url = Some("http://localhost:9000/hook".to_string());
+        loaded.notify.queue_capacity = 7;
+        loaded.safe.rpc_url = Some("http://localhost:8546".to_string());
+        loaded.storage.path = Some(PathBuf::from("other.db"));
+        loaded.profile = crate::config::Profile::Testnet;
+
+        let reload = Reload::new(&running, &loaded);
+        assert_eq!(
+            reload.applied,
+            vec![
+                "notify.webhook.url",
+                "optimizer.min_apy",
+                "router.chains[1].active",
+                "runtime.pool_interval_secs",
+                "safe.min_balance_eth",
+            ]
+        );
+        assert_eq!(reload.rejected, vec!["notify.queue_capacity", "profile", "safe.rpc_url", "storage.path"]);
+        assert_eq!(reload.config.optimizer.min_apy, 4.0);
+        assert!(!reload.config.router.chains[1].active);
+        assert_eq!(reload.config.safe.rpc_url, running.safe.rpc_url);
+        assert_eq!(reload.config.storage.path, running.storage.path);
+        assert_eq!(reload.config.profile, running.profile);
+        assert_eq!(reload.config.notify.queue_capacity, running.notify.queue_capacity);
+
+        // A changed chain list is not applied at all
+        loaded.router.chains.pop();
+        let reload = Reload::new(&running, &loaded);
+        assert!(reload.rejected.contains(&"router.chains".to_string()), "{:?}", reload.rejected);
+        assert!(reload.config.router.chains[1].active);
+
+        // Secrets serialize redacted, but a rotated one still counts as a change
+        let mut rotated = running.clone();
+        rotated.notify.webhook.secret = Some("new-webhook-secret".into());
+        let mut running = running;
+        running.notify.webhook.secret = Some("old-webhook-secret".into());
+        let reload = Reload::new(&running, &rotated);
+        assert_eq!(reload.applied, vec!["notify.webhook.secret"]);
+        assert_eq!(reload.config.notify.webhook.secret, rotated.notify.webhook.secret);
+    }
+
+    #[test]
+    fn test_reloader_bumps_version_only_on_change() {
+        let next = Arc::new(std::sync::Mutex::new(Ok(running())));
+        let load: ConfigLoader = {
+            let next = next.clone();
+            Box::new(move || next.lock().unwrap().as_ref().cloned().map_err(|e: &String| anyhow::anyhow!("{}", e)))
+        };
+        let mut reloader = Reloader::new(load, running());
+        assert!(reloader.reload().is_none());
+        assert_eq!(reloader.version(), ConfigVersion::INITIAL);
+
+        let mut changed = running();
+        changed.runtime.cycle_interval_secs = 30;
+        *next.lock().unwrap() = Ok(changed);
+        let update = reloader.reload().unwrap();
+        assert_eq!(update.version, ConfigVersion(2));
+        assert_eq!(update.config.runtime.cycle_interval_secs, 30);
+
+        // A broken file keeps the running version
+        *next.lock().unwrap() = Err("parse error".to_string());
+        assert!(reloader.reload().is_none());
+        assert_eq!(reloader.version(), ConfigVersion(2));
+    }
+}
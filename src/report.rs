@@ -0,0 +1,306 @@
+//! Structured outcome of one full monitoring cycle.
+//!
+//! [`CycleReport`] is what `monitor_and_optimize` returns. The loop keeps the
+//! latest one for `/status`, and tests assert on it instead of on log output.
+
+use ethers::core::types::U256;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::BTreeMap;
+
+use crate::agents::defi_optimizer::PoolData;
+use crate::config::RunMode;
+use crate::degradation::Degradation;
+use crate::recent_actions::RecentAction;
+use crate::scheduler::CycleTrigger;
+use crate::status::BalanceStatus;
+use crate::units::TokenAmount;
+use crate::valuation::PortfolioValuation;
+
+pub(crate) fn decimal<S: Serializer>(value: &U256, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&value.to_string())
+}
+
+pub(crate) fn from_decimal<'de, D: Deserializer<'de>>(deserializer: D) -> Result<U256, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    U256::from_dec_str(&value).map_err(serde::de::Error::custom)
+}
+
+fn decimal_opt<S: Serializer>(value: &Option<U256>, serializer: S) -> Result<S::Ok, S::Error> {
+    match value {
+        Some(value) => decimal(value, serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// Native balance of the monitored address on one chain.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[non_exhaustive]
+pub struct ChainBalance {
+    /// Balance in wei as a decimal string; unset when the chain could not be read.
+    #[serde(serialize_with = "decimal_opt")]
+    pub balance: Option<U256>,
+    pub status: BalanceStatus,
+    /// Minimum threshold for this chain in wei; critical is half of it.
+    #[serde(serialize_with = "decimal")]
+    pub min_balance: U256,
+    /// Why the balance could not be read.
+    pub error: Option<String>,
+}
+
+impl ChainBalance {
+    pub fn observed(balance: U256, min_balance: U256) -> Self {
+        Self {
+            balance: Some(balance),
+            status: BalanceStatus::evaluate(balance, min_balance),
+            min_balance,
+            error: None,
+        }
+    }
+
+    pub fn unknown(min_balance: U256, error: impl Into<String>) -> Self {
+        Self {
+            balance: None,
+            status: BalanceStatus::Unknown,
+            min_balance,
+            error: Some(error.into()),
+        }
+    }
+}
+
+/// Balances by chain name, in name order.
+pub type MultiChainBalances = BTreeMap<String, ChainBalance>;
+
+/// A pool together with the score it was ranked by.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScoredPool {
+    #[serde(flatten)]
+    pub pool: PoolData,
+    pub score: f64,
+}
+
+/// What the cycle decided to do with the best pool.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "reason", rename_all = "snake_case")]
+pub enum CycleDecision {
+    /// The best pool is where the funds already are.
+    NoAction,
+    /// The best pool's APY or TVL is too low to justify a move, or the amount the
+    /// balance allows is below the bridge minimum.
+    NotWorthMoving,
+    /// A route towards the best pool was started (or simulated in dry-run mode).
+    RouteInitiated,
+    /// Moving would have made sense but something prevented it.
+    Blocked(String),
+    /// The operator declined the route, or did not answer in time.
+    Declined(String),
+}
+
+/// What the routing decision was based on.
+///
+/// The observed fields are everything [`Strategy::decide`](crate::strategy::Strategy::decide)
+/// looks at; the rest it fills in from the config, so a recorded set can be decided
+/// again. Only the observed fields are read back from a decision log.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct DecisionInputs {
+    /// Chain the funds were on when the decision was made.
+    pub current_chain: String,
+    /// Best APY available on the current chain, if any pool there passed the filters.
+    pub current_apy: Option<f64>,
+    /// Chain of the best ranked pool.
+    pub candidate_chain: String,
+    pub candidate_apy: f64,
+    pub candidate_tvl: f64,
+    /// Balance available on the current chain, in wei as a decimal string.
+    #[serde(serialize_with = "decimal", deserialize_with = "from_decimal")]
+    pub balance: U256,
+    /// Whether the kill switch was on.
+    pub paused: bool,
+    /// A route of the portfolio that had not landed yet.
+    pub pending_route: Option<String>,
+    /// Unix time of the last route from the current to the candidate chain.
+    pub last_route_at: Option<u64>,
+    /// Unix time the inputs were gathered.
+    pub observed_at: u64,
+    /// The last completed route from the current to the candidate chain, if it is
+    /// still inside `router.route_dedup_window_secs`.
+    #[serde(default)]
+    pub recent_route: Option<RecentAction>,
+    /// Gas of exiting the current protocol and entering the candidate pool.
+    #[serde(default)]
+    pub rebalance_gas: Option<u64>,
+    /// Home chain gas price, in gwei.
+    #[serde(default)]
+    pub gas_price_gwei: Option<f64>,
+    #[serde(skip_deserializing)]
+    pub min_apy_improvement: f64,
+    /// Time left before another route in the candidate's direction may start.
+    #[serde(skip_deserializing)]
+    pub cooldown_remaining_secs: Option<u64>,
+    /// Amount that would be moved, once the improvement and cooldown checks passed.
+    #[serde(skip_deserializing)]
+    pub amount: Option<f64>,
+    /// How `amount` was derived from the balance.
+    #[serde(skip_deserializing)]
+    pub sizing: Option<TransferSizing>,
+    /// Days the improvement on `amount` takes to pay for `rebalance_gas`.
+    #[serde(skip_deserializing)]
+    pub breakeven_days: Option<f64>,
+}
+
+/// Inputs and result of sizing a transfer from the available balance.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[non_exhaustive]
+pub struct TransferSizing {
+    pub available: TokenAmount,
+    /// Always left behind for gas.
+    pub reserve: TokenAmount,
+    /// Share of the balance above the reserve that may move.
+    pub deploy_fraction: f64,
+    /// `strategy.transfer_amount`, when set.
+    pub fixed: Option<TokenAmount>,
+    /// Router per-route maximum.
+    pub maximum: TokenAmount,
+    /// Larger of the router minimum and the source chain's bridge minimum.
+    pub minimum: TokenAmount,
+    pub amount: TokenAmount,
+}
+
+impl TransferSizing {
+    pub fn is_worth_moving(&self) -> bool {
+        !self.amount.is_zero() && self.amount >= self.minimum
+    }
+}
+
+/// The route a cycle started.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct RouteResult {
+    /// Tracker id; `None` when the route was only simulated.
+    pub route_id: Option<String>,
+    pub amount: f64,
+    pub source_chain: String,
+    pub target_chain: String,
+    pub mode: RunMode,
+}
+
+/// What one of the configured strategies decided on the cycle's pools.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct StrategyDecision {
+    pub name: String,
+    /// Whether this decision was acted on; the others are only recorded.
+    pub active: bool,
+    /// Best pool under this strategy's filters and scoring.
+    pub best_pool: Option<ScoredPool>,
+    /// Before operator approval or observe mode.
+    pub decision: CycleDecision,
+    pub reason: String,
+    /// Unset when no pool passed this strategy's filters.
+    pub inputs: Option<DecisionInputs>,
+}
+
+/// Wall-clock time spent in each part of a full monitoring cycle, in milliseconds.
+///
+/// The balance check and pool fetch run concurrently, so `total_ms` is close to the
+/// slower of the two plus `decision_ms` rather than their sum.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[non_exhaustive]
+pub struct PhaseTimings {
+    pub balance_ms: u64,
+    pub pools_ms: u64,
+    pub decision_ms: u64,
+    pub total_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[non_exhaustive]
+pub struct CycleReport {
+    /// What woke the loop for this cycle.
+    pub trigger: CycleTrigger,
+    /// Balance in wei, serialized as a decimal string.
+    #[serde(serialize_with = "decimal")]
+    pub balance: U256,
+    pub balance_status: BalanceStatus,
+    /// USD value of `balance`; unset when no fresh ETH price was available.
+    pub balance_usd: Option<f64>,
+    /// USD value of the whole portfolio; unset without `[prices]`.
+    pub valuation: Option<PortfolioValuation>,
+    /// Balance on every monitored chain, the home chain included.
+    pub balances: MultiChainBalances,
+    pub best_pool: Option<ScoredPool>,
+    pub decision: CycleDecision,
+    /// Unset when the cycle had no pool data to decide on.
+    pub decision_inputs: Option<DecisionInputs>,
+    pub route: Option<RouteResult>,
+    /// Every configured strategy's decision, the acting one first; empty without
+    /// `[[strategies]]`.
+    pub strategies: Vec<StrategyDecision>,
+    pub timings: PhaseTimings,
+    /// Problems that did not fail the cycle, e.g. a low balance or a dependency the
+    /// cycle fell back for.
+    pub warnings: Vec<String>,
+    /// Fallbacks taken for dependencies that were down, in the order they were.
+    pub degradations: Vec<Degradation>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Fallback;
+    use crate::degradation::Dependency;
+
+    #[test]
+    fn test_report_serialization() {
+        let report = CycleReport {
+            trigger: CycleTrigger::BalanceIncreased("Arbitrum".to_string()),
+            balance: U256::MAX,
+            balance_status: BalanceStatus::Low,
+            balance_usd: None,
+            valuation: None,
+            balances: MultiChainBalances::from([
+                ("Ethereum".to_string(), ChainBalance::observed(U256::exp10(17), U256::exp10(18))),
+                ("Optimism".to_string(), ChainBalance::unknown(U256::exp10(18), "connection refused")),
+            ]),
+            best_pool: None,
+            decision: CycleDecision::Blocked("observe mode".to_string()),
+            decision_inputs: None,
+            route: Some(RouteResult {
+                route_id: None,
+                amount: 100.0,
+                source_chain: "Ethereum".to_string(),
+                target_chain: "Arbitrum".to_string(),
+                mode: RunMode::DryRun,
+            }),
+            strategies: Vec::new(),
+            timings: PhaseTimings::default(),
+            warnings: vec!["balance below minimum threshold".to_string()],
+            degradations: vec![Degradation {
+                dependency: Dependency::Prices,
+                fallback: Fallback::SkipPhase,
+                detail: "timeout".to_string(),
+            }],
+        };
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["trigger"], serde_json::json!({ "kind": "balance_increased", "chain": "Arbitrum" }));
+        assert_eq!(json["balance"], U256::MAX.to_string());
+        assert_eq!(json["balance_status"], "low");
+        assert_eq!(json["balance_usd"], serde_json::Value::Null);
+        assert_eq!(json["balances"]["Ethereum"]["balance"], "100000000000000000");
+        assert_eq!(json["balances"]["Ethereum"]["status"], "critical");
+        assert_eq!(json["balances"]["Optimism"]["balance"], serde_json::Value::Null);
+        assert_eq!(json["balances"]["Optimism"]["status"], "unknown");
+        assert_eq!(json["decision"]["kind"], "blocked");
+        assert_eq!(json["decision"]["reason"], "observe mode");
+        assert_eq!(json["route"]["mode"], "dry_run");
+        assert_eq!(json["timings"]["total_ms"], 0);
+        assert_eq!(
+            json["degradations"],
+            serde_json::json!([{ "dependency": "prices", "fallback": "skip_phase", "detail": "timeout" }])
+        );
+
+        let json = serde_json::to_value(CycleDecision::NotWorthMoving).unwrap();
+        assert_eq!(json, serde_json::json!({ "kind": "not_worth_moving" }));
+    }
+}
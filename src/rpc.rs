@@ -0,0 +1,818 @@
+//! JSON-RPC transport that fails over between several Ethereum endpoints.
+//!
+//! An endpoint is reached over HTTP, a websocket or a node's IPC socket, by its URL:
+//! `http(s)://`, `ws(s)://`, or a filesystem path. Requests go to the first healthy
+//! endpoint in configured order. Transport errors,
+//! timeouts, unparseable responses and rate limiting demote the endpoint and the
+//! request is retried on the next one. Demoted endpoints are only promoted back by
+//! [`FailoverClient::probe`], which also checks they still serve the expected chain.
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use ethers::core::types::U256;
+use ethers::providers::{
+    Http, HttpClientError, Ipc, IpcError, JsonRpcClient, JsonRpcError, Provider, ProviderError, Ws, WsClientError,
+};
+// Named like this module's own error type
+use ethers::providers::RpcError as _;
+use log::{debug, error, info, warn};
+use reqwest::Url;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fmt::Debug;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use thiserror::Error;
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+
+use crate::config::SafeConfig;
+use crate::version::USER_AGENT;
+
+/// Weight of the newest sample in the moving latency average.
+const LATENCY_WEIGHT: f64 = 0.2;
+
+#[derive(Error, Debug)]
+#[non_exhaustive]
+pub enum RpcError {
+    #[error("No RPC endpoints configured")]
+    NoEndpoints,
+    #[error("Invalid RPC URL: {0}")]
+    InvalidUrl(String),
+    #[error("RPC endpoints disagree on the chain id: {first} reports {expected}, {other} reports {actual}")]
+    ChainIdMismatch {
+        first: String,
+        expected: U256,
+        other: String,
+        actual: U256,
+    },
+}
+
+/// Why a request to one endpoint failed.
+#[derive(Error, Debug)]
+#[non_exhaustive]
+pub enum TransportError {
+    #[error(transparent)]
+    Http(#[from] HttpClientError),
+    #[error(transparent)]
+    Ws(#[from] WsClientError),
+    #[error(transparent)]
+    Ipc(#[from] IpcError),
+    #[error("No answer within {0:?}")]
+    TimedOut(Duration),
+}
+
+impl ethers::providers::RpcError for TransportError {
+    fn as_error_response(&self) -> Option<&JsonRpcError> {
+        match self {
+            TransportError::Http(e) => e.as_error_response(),
+            TransportError::Ws(e) => e.as_error_response(),
+            TransportError::Ipc(e) => e.as_error_response(),
+            TransportError::TimedOut(_) => None,
+        }
+    }
+
+    fn as_serde_error(&self) -> Option<&serde_json::Error> {
+        match self {
+            TransportError::Http(e) => e.as_serde_error(),
+            TransportError::Ws(e) => e.as_serde_error(),
+            TransportError::Ipc(e) => e.as_serde_error(),
+            TransportError::TimedOut(_) => None,
+        }
+    }
+}
+
+impl From<TransportError> for ProviderError {
+    fn from(error: TransportError) -> Self {
+        ProviderError::JsonRpcClientError(Box::new(error))
+    }
+}
+
+/// How an endpoint is reached, going by its URL.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EndpointKind {
+    Http(Url),
+    Ws(Url),
+    /// Path of the node's IPC socket, e.g. `~/.ethereum/geth.ipc`.
+    Ipc(PathBuf),
+}
+
+impl EndpointKind {
+    /// `http(s)://` and `ws(s)://` URLs, and absolute paths or paths ending in `.ipc`
+    /// as IPC sockets. A bare host name is refused rather than taken for a path.
+    pub fn parse(url: &str) -> Result<Self, RpcError> {
+        let url = url.trim();
+        match Url::parse(url) {
+            Ok(parsed) if matches!(parsed.scheme(), "http" | "https") => Ok(EndpointKind::Http(parsed)),
+            Ok(parsed) if matches!(parsed.scheme(), "ws" | "wss") => Ok(EndpointKind::Ws(parsed)),
+            Err(_) if !url.contains("://") && (Path::new(url).is_absolute() || url.ends_with(".ipc")) => {
+                Ok(EndpointKind::Ipc(PathBuf::from(url)))
+            }
+            _ => Err(RpcError::InvalidUrl(url.to_string())),
+        }
+    }
+}
+
+/// Provider used by every component talking to the Ethereum node.
+pub type RpcProvider = Provider<Arc<FailoverClient>>;
+
+#[derive(Debug, Default)]
+struct EndpointStats {
+    requests: u64,
+    errors: u64,
+    avg_latency_ms: Option<f64>,
+    last_error: Option<String>,
+    /// Set while demoted; the endpoint is probed again once this has passed.
+    retry_at: Option<Instant>,
+}
+
+/// Answers JSON-RPC calls in-process in place of a node, e.g. replaying a simulation
+/// scenario. An error answer is returned to the caller as the node's error.
+pub trait StaticNode: Send + Sync + Debug {
+    fn answer(&self, method: &str, params: serde_json::Value) -> Result<serde_json::Value, JsonRpcError>;
+}
+
+/// A connection opened on first use, and opened again after it failed.
+#[derive(Debug)]
+struct Connection<C> {
+    client: Mutex<Option<C>>,
+    timeout: Duration,
+}
+
+impl<C: JsonRpcClient + Clone> Connection<C>
+where
+    TransportError: From<C::Error>,
+{
+    fn new(timeout: Duration) -> Self {
+        Self { client: Mutex::new(None), timeout }
+    }
+
+    async fn request<T, R, F, Fut>(&self, connect: F, method: &str, params: T) -> Result<R, TransportError>
+    where
+        T: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned + Send,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<C, TransportError>>,
+    {
+        let open = self.client.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        let client = match open {
+            Some(client) => client,
+            None => {
+                let client = tokio::time::timeout(self.timeout, connect())
+                    .await
+                    .map_err(|_| TransportError::TimedOut(self.timeout))??;
+                *self.client.lock().unwrap_or_else(|e| e.into_inner()) = Some(client.clone());
+                client
+            }
+        };
+        let result = match tokio::time::timeout(self.timeout, client.request(method, params)).await {
+            Ok(result) => result.map_err(TransportError::from),
+            Err(_) => Err(TransportError::TimedOut(self.timeout)),
+        };
+        // An error answer came over a working connection; anything else reconnects
+        if result.as_ref().is_err_and(|e| e.as_error_response().is_none()) {
+            *self.client.lock().unwrap_or_else(|e| e.into_inner()) = None;
+        }
+        result
+    }
+}
+
+#[derive(Debug)]
+enum Transport {
+    /// With the client's request timeout, which reqwest enforces.
+    Http(Http, Duration),
+    Ws(Url, Connection<Ws>),
+    Ipc(PathBuf, Connection<Ipc>),
+    Static(Arc<dyn StaticNode>),
+}
+
+impl Transport {
+    async fn request<T, R>(&self, method: &str, params: T) -> Result<R, TransportError>
+    where
+        T: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned + Send,
+    {
+        match self {
+            Transport::Http(http, timeout) => match http.request(method, params).await {
+                Err(HttpClientError::ReqwestError(e)) if e.is_timeout() => Err(TransportError::TimedOut(*timeout)),
+                // reqwest names the URL, and with it a hosted endpoint's API key
+                Err(HttpClientError::ReqwestError(e)) => Err(HttpClientError::ReqwestError(e.without_url()).into()),
+                result => Ok(result?),
+            },
+            Transport::Ws(url, connection) => {
+                let connect = || async { Ok(Ws::connect(url.as_str()).await?) };
+                connection.request(connect, method, params).await
+            }
+            Transport::Ipc(path, connection) => {
+                let connect = || async { Ok(Ipc::connect(path).await?) };
+                connection.request(connect, method, params).await
+            }
+            Transport::Static(node) => {
+                let params = serde_json::to_value(params)
+                    .map_err(|err| HttpClientError::SerdeJson { err, text: String::new() })?;
+                let result = node.answer(method, params).map_err(HttpClientError::JsonRpcError)?;
+                Ok(serde_json::from_value(result.clone())
+                    .map_err(|err| HttpClientError::SerdeJson { err, text: result.to_string() })?)
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Endpoint {
+    /// Scheme and host only, since hosted endpoints carry API keys in the path.
+    label: String,
+    transport: Transport,
+    stats: Mutex<EndpointStats>,
+}
+
+impl Endpoint {
+    fn stats(&self) -> std::sync::MutexGuard<'_, EndpointStats> {
+        self.stats.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn record_success(&self, latency: Duration) {
+        let mut stats = self.stats();
+        stats.requests += 1;
+        let sample = latency.as_secs_f64() * 1000.0;
+        stats.avg_latency_ms = Some(match stats.avg_latency_ms {
+            Some(avg) => avg + LATENCY_WEIGHT * (sample - avg),
+            None => sample,
+        });
+    }
+
+    fn record_failure(&self, reason: String, retry_at: Instant) {
+        let mut stats = self.stats();
+        stats.requests += 1;
+        stats.errors += 1;
+        stats.last_error = Some(reason);
+        stats.retry_at = Some(retry_at);
+    }
+
+    fn is_demoted(&self) -> bool {
+        self.stats().retry_at.is_some()
+    }
+}
+
+/// Health of one endpoint as reported in `/status`.
+#[derive(Debug, Clone, Serialize)]
+pub struct EndpointHealth {
+    pub endpoint: String,
+    pub healthy: bool,
+    pub requests: u64,
+    pub errors: u64,
+    pub error_rate: f64,
+    pub avg_latency_ms: Option<f64>,
+    pub last_error: Option<String>,
+}
+
+/// Endpoint health and which endpoint served the most recent request.
+#[derive(Debug, Clone, Serialize)]
+pub struct RpcHealth {
+    pub last_served_by: Option<String>,
+    pub endpoints: Vec<EndpointHealth>,
+}
+
+/// Whether a failed request should be retried on another endpoint. JSON-RPC errors
+/// such as reverts are answers from a working node and are returned as they are.
+fn should_fail_over(error: &TransportError) -> bool {
+    match error.as_error_response() {
+        Some(error) => is_rate_limited(error),
+        None => true,
+    }
+}
+
+/// Whether the node asked us to slow down.
+pub fn is_rate_limited(error: &JsonRpcError) -> bool {
+    let message = error.message.to_lowercase();
+    error.code == 429
+        || error.code == -32005
+        || message.contains("rate limit")
+        || message.contains("too many requests")
+}
+
+/// Whether a failed call may succeed if simply made again: the node timed out, dropped
+/// the connection, answered with something that is not JSON-RPC (such as a 502 page)
+/// or asked us to slow down. Other JSON-RPC errors, e.g. invalid params or a revert,
+/// would fail the same way again.
+pub fn is_transient(error: &ProviderError) -> bool {
+    if let Some(response) = error.as_error_response() {
+        return is_rate_limited(response);
+    }
+    matches!(
+        error,
+        ProviderError::JsonRpcClientError(_) | ProviderError::HTTPError(_) | ProviderError::SerdeJson(_)
+    )
+}
+
+/// How long a request went unanswered before it was given up, when that is why
+/// `error` happened.
+pub fn timed_out(error: &ProviderError) -> Option<Duration> {
+    let ProviderError::JsonRpcClientError(e) = error else {
+        return None;
+    };
+    match (e.as_ref() as &(dyn std::error::Error + 'static)).downcast_ref::<TransportError>() {
+        Some(TransportError::TimedOut(after)) => Some(*after),
+        _ => None,
+    }
+}
+
+/// How a call that failed with a [transient](is_transient) error is retried: up to
+/// `max_attempts` in all, with capped exponential backoff between them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    /// Attempts including the first; 1 never retries.
+    pub max_attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// Relative jitter, e.g. 0.2 for ±20%.
+    pub jitter: f64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(10),
+            jitter: 0.2,
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn from_config(config: &SafeConfig) -> Self {
+        Self {
+            max_attempts: config.rpc_retry_attempts,
+            initial_backoff: Duration::from_millis(config.rpc_retry_backoff_ms),
+            ..Self::default()
+        }
+    }
+
+    /// Delay before retry number `retry`, 1 for the first.
+    ///
+    /// `sample` in `[-1, 1]` picks the point within the jitter range.
+    pub fn delay(&self, retry: u32, sample: f64) -> Duration {
+        let max = self.max_backoff.as_secs_f64();
+        let base = (self.initial_backoff.as_secs_f64() * 2f64.powi(retry.saturating_sub(1) as i32)).min(max);
+        Duration::from_secs_f64((base * (1.0 + self.jitter * sample.clamp(-1.0, 1.0))).min(max))
+    }
+}
+
+fn label(url: &Url) -> String {
+    match (url.host_str(), url.port()) {
+        (Some(host), Some(port)) => format!("{}://{}:{}", url.scheme(), host, port),
+        (Some(host), None) => format!("{}://{}", url.scheme(), host),
+        _ => url.scheme().to_string(),
+    }
+}
+
+#[derive(Debug)]
+pub struct FailoverClient {
+    endpoints: Vec<Endpoint>,
+    probe_interval: Duration,
+    chain_id: Mutex<Option<U256>>,
+    last_served: Mutex<Option<usize>>,
+}
+
+impl FailoverClient {
+    /// `urls` are in priority order; the first one is the primary.
+    pub fn new<S: AsRef<str>>(urls: &[S], timeout: Duration, probe_interval: Duration) -> Result<Self> {
+        if urls.is_empty() {
+            return Err(RpcError::NoEndpoints.into());
+        }
+        let client = reqwest::Client::builder()
+            .timeout(timeout)
+            .user_agent(USER_AGENT)
+            .build()
+            .context("Failed to build RPC HTTP client")?;
+        let endpoints = urls
+            .iter()
+            .map(|url| {
+                let (label, transport) = match EndpointKind::parse(url.as_ref())? {
+                    EndpointKind::Http(url) => {
+                        (label(&url), Transport::Http(Http::new_with_client(url, client.clone()), timeout))
+                    }
+                    EndpointKind::Ws(url) => (label(&url), Transport::Ws(url, Connection::new(timeout))),
+                    EndpointKind::Ipc(path) => {
+                        (format!("ipc:{}", path.display()), Transport::Ipc(path, Connection::new(timeout)))
+                    }
+                };
+                Ok(Endpoint { label, transport, stats: Mutex::default() })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self {
+            endpoints,
+            probe_interval,
+            chain_id: Mutex::new(None),
+            last_served: Mutex::new(None),
+        })
+    }
+
+    /// A client with `node` as its only endpoint, for running without a network.
+    pub fn from_static(node: Arc<dyn StaticNode>) -> Self {
+        Self {
+            endpoints: vec![Endpoint {
+                label: "static".to_string(),
+                transport: Transport::Static(node),
+                stats: Mutex::default(),
+            }],
+            probe_interval: Duration::from_secs(60),
+            chain_id: Mutex::new(None),
+            last_served: Mutex::new(None),
+        }
+    }
+
+    pub fn from_config(config: &SafeConfig) -> Result<Self> {
+        Self::new(
+            &config.rpc_urls()?,
+            Duration::from_secs(config.rpc_timeout_secs),
+            Duration::from_secs(config.rpc_probe_interval_secs),
+        )
+    }
+
+    /// Wraps the client in a provider, keeping a handle for health reporting.
+    pub fn into_provider(self) -> (RpcProvider, Arc<FailoverClient>) {
+        let client = Arc::new(self);
+        (Provider::new(client.clone()), client)
+    }
+
+    /// Fetches the chain id from every endpoint and fails if any two disagree.
+    /// Unreachable endpoints are demoted and verified when they are probed back.
+    pub async fn verify_chain_id(&self) -> Result<Option<U256>> {
+        let mut first: Option<(&str, U256)> = None;
+        for endpoint in &self.endpoints {
+            let chain_id = match endpoint.transport.request::<_, U256>("eth_chainId", ()).await {
+                Ok(chain_id) => chain_id,
+                Err(e) => {
+                    warn!("RPC endpoint {} unreachable at startup: {}", endpoint.label, e);
+                    endpoint.record_failure(e.to_string(), Instant::now() + self.probe_interval);
+                    continue;
+                }
+            };
+            debug!("RPC endpoint {} reports chain id {}", endpoint.label, chain_id);
+            match first {
+                None => first = Some((&endpoint.label, chain_id)),
+                Some((label, expected)) if expected != chain_id => {
+                    return Err(RpcError::ChainIdMismatch {
+                        first: label.to_string(),
+                        expected,
+                        other: endpoint.label.clone(),
+                        actual: chain_id,
+                    }
+                    .into())
+                }
+                Some(_) => {}
+            }
+        }
+        let chain_id = first.map(|(_, chain_id)| chain_id);
+        *self.chain_id.lock().unwrap_or_else(|e| e.into_inner()) = chain_id;
+        Ok(chain_id)
+    }
+
+    /// Chain id the endpoints agreed on; unset until one has answered.
+    pub fn chain_id(&self) -> Option<U256> {
+        *self.chain_id.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Re-checks demoted endpoints whose retry time has passed and promotes the ones
+    /// that answer with the expected chain id.
+    pub async fn probe(&self) {
+        let now = Instant::now();
+        for endpoint in &self.endpoints {
+            if endpoint.stats().retry_at.is_none_or(|at| at > now) {
+                continue;
+            }
+            let retry_at = Instant::now() + self.probe_interval;
+            let chain_id = match endpoint.transport.request::<_, U256>("eth_chainId", ()).await {
+                Ok(chain_id) => chain_id,
+                Err(e) => {
+                    debug!("RPC endpoint {} still failing: {}", endpoint.label, e);
+                    endpoint.stats().retry_at = Some(retry_at);
+                    continue;
+                }
+            };
+            let mut expected = self.chain_id.lock().unwrap_or_else(|e| e.into_inner());
+            match *expected {
+                Some(expected) if expected != chain_id => {
+                    error!(
+                        "RPC endpoint {} now reports chain id {} instead of {}; keeping it demoted",
+                        endpoint.label, chain_id, expected
+                    );
+                    endpoint.stats().retry_at = Some(retry_at);
+                }
+                _ => {
+                    *expected = Some(chain_id);
+                    endpoint.stats().retry_at = None;
+                    info!("RPC endpoint {} recovered and was promoted back", endpoint.label);
+                }
+            }
+        }
+    }
+
+    /// Probes demoted endpoints every probe interval until `shutdown` fires.
+    pub async fn run_probes(&self, shutdown: CancellationToken) {
+        let mut ticker = tokio::time::interval(self.probe_interval);
+        loop {
+            tokio::select! {
+                _ = shutdown.cancelled() => return,
+                _ = ticker.tick() => self.probe().await,
+            }
+        }
+    }
+
+    pub fn health(&self) -> RpcHealth {
+        let last_served = *self.last_served.lock().unwrap_or_else(|e| e.into_inner());
+        RpcHealth {
+            last_served_by: last_served.map(|i| self.endpoints[i].label.clone()),
+            endpoints: self
+                .endpoints
+                .iter()
+                .map(|endpoint| {
+                    let stats = endpoint.stats();
+                    EndpointHealth {
+                        endpoint: endpoint.label.clone(),
+                        healthy: stats.retry_at.is_none(),
+                        requests: stats.requests,
+                        errors: stats.errors,
+                        error_rate: if stats.requests == 0 {
+                            0.0
+                        } else {
+                            stats.errors as f64 / stats.requests as f64
+                        },
+                        avg_latency_ms: stats.avg_latency_ms,
+                        last_error: stats.last_error.clone(),
+                    }
+                })
+                .collect(),
+        }
+    }
+
+    /// Healthy endpoints in priority order, then the demoted ones as a last resort.
+    fn attempt_order(&self) -> Vec<usize> {
+        let (healthy, demoted): (Vec<usize>, Vec<usize>) =
+            (0..self.endpoints.len()).partition(|&i| !self.endpoints[i].is_demoted());
+        healthy.into_iter().chain(demoted).collect()
+    }
+}
+
+#[async_trait]
+impl JsonRpcClient for FailoverClient {
+    type Error = TransportError;
+
+    async fn request<T, R>(&self, method: &str, params: T) -> Result<R, Self::Error>
+    where
+        T: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned + Send,
+    {
+        let mut last_error = None;
+        for index in self.attempt_order() {
+            let endpoint = &self.endpoints[index];
+            let start = Instant::now();
+            match endpoint.transport.request(method, &params).await {
+                Ok(result) => {
+                    endpoint.record_success(start.elapsed());
+                    *self.last_served.lock().unwrap_or_else(|e| e.into_inner()) = Some(index);
+                    debug!("{} served by {}", method, endpoint.label);
+                    return Ok(result);
+                }
+                Err(e) if should_fail_over(&e) => {
+                    if !endpoint.is_demoted() {
+                        warn!("RPC endpoint {} failed, failing over: {}", endpoint.label, e);
+                    }
+                    endpoint.record_failure(e.to_string(), Instant::now() + self.probe_interval);
+                    last_error = Some(e);
+                }
+                Err(e) => {
+                    endpoint.record_success(start.elapsed());
+                    *self.last_served.lock().unwrap_or_else(|e| e.into_inner()) = Some(index);
+                    return Err(e);
+                }
+            }
+        }
+        Err(last_error.expect("at least one endpoint is configured"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::providers::Middleware;
+    use wiremock::matchers::{body_partial_json, method};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn rpc_result(result: &str) -> ResponseTemplate {
+        ResponseTemplate::new(200)
+            .set_body_json(serde_json::json!({ "jsonrpc": "2.0", "id": 1, "result": result }))
+    }
+
+    /// Node answering eth_chainId with `chain_id` and every other call with `balance`.
+    async fn node(chain_id: &str, balance: &str) -> MockServer {
+        let server = MockServer::start().await;
+        Mock::given(body_partial_json(serde_json::json!({ "method": "eth_chainId" })))
+            .respond_with(rpc_result(chain_id))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(rpc_result(balance))
+            .mount(&server)
+            .await;
+        server
+    }
+
+    fn client(servers: &[&MockServer]) -> FailoverClient {
+        let urls: Vec<String> = servers.iter().map(|s| s.uri()).collect();
+        FailoverClient::new(&urls, Duration::from_secs(2), Duration::ZERO).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_fails_over_and_recovers() {
+        let primary = node("0x1", "0x64").await;
+        let backup = node("0x1", "0xc8").await;
+        let (provider, client) = client(&[&primary, &backup]).into_provider();
+        assert_eq!(client.verify_chain_id().await.unwrap(), Some(U256::one()));
+        let address = ethers::core::types::Address::zero();
+
+        assert_eq!(provider.get_balance(address, None).await.unwrap(), U256::from(100));
+        assert_eq!(client.health().last_served_by, Some(label(&Url::parse(&primary.uri()).unwrap())));
+
+        // The primary starts failing: requests transparently move to the backup
+        primary.reset().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(429).set_body_string("Too Many Requests"))
+            .mount(&primary)
+            .await;
+        assert_eq!(provider.get_balance(address, None).await.unwrap(), U256::from(200));
+        assert_eq!(provider.get_balance(address, None).await.unwrap(), U256::from(200));
+        let health = client.health();
+        assert_eq!(health.last_served_by, Some(label(&Url::parse(&backup.uri()).unwrap())));
+        assert!(!health.endpoints[0].healthy);
+        assert_eq!(health.endpoints[0].errors, 1, "a demoted endpoint is not retried");
+        assert_eq!(health.endpoints[1].requests, 2);
+
+        // Probing a still failing endpoint keeps it demoted
+        client.probe().await;
+        assert!(!client.health().endpoints[0].healthy);
+
+        // Once it answers again the probe promotes it back
+        primary.reset().await;
+        Mock::given(method("POST")).respond_with(rpc_result("0x1")).mount(&primary).await;
+        client.probe().await;
+        assert!(client.health().endpoints[0].healthy);
+        assert_eq!(provider.get_block_number().await.unwrap().as_u64(), 1);
+        assert_eq!(client.health().last_served_by, Some(label(&Url::parse(&primary.uri()).unwrap())));
+        assert!(client.health().endpoints[0].error_rate > 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_rpc_errors_do_not_fail_over() {
+        let primary = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "jsonrpc": "2.0", "id": 1, "error": { "code": -32000, "message": "execution reverted" }
+            })))
+            .mount(&primary)
+            .await;
+        let backup = node("0x1", "0xc8").await;
+        let (provider, client) = client(&[&primary, &backup]).into_provider();
+
+        assert!(provider.get_balance(ethers::core::types::Address::zero(), None).await.is_err());
+        assert!(backup.received_requests().await.unwrap().is_empty());
+        assert!(client.health().endpoints[0].healthy);
+    }
+
+    #[test]
+    fn test_endpoint_kinds() {
+        assert!(matches!(EndpointKind::parse("https://mainnet.example/v3/key"), Ok(EndpointKind::Http(_))));
+        assert!(matches!(EndpointKind::parse("wss://mainnet.example/ws"), Ok(EndpointKind::Ws(_))));
+        assert_eq!(
+            EndpointKind::parse(" /home/eth/.ethereum/geth.ipc ").unwrap(),
+            EndpointKind::Ipc(PathBuf::from("/home/eth/.ethereum/geth.ipc"))
+        );
+        assert!(matches!(EndpointKind::parse("reth.ipc"), Ok(EndpointKind::Ipc(_))));
+        for invalid in ["mainnet.example", "ftp://mainnet.example", ""] {
+            assert!(EndpointKind::parse(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    /// Serves JSON-RPC on a Unix socket at `path`, answering eth_chainId with 1 and every
+    /// other call with 100, one connection at a time.
+    #[cfg(unix)]
+    async fn ipc_node(path: &Path) {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let _ = std::fs::remove_file(path);
+        let listener = tokio::net::UnixListener::bind(path).unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut buffer = Vec::new();
+                let mut chunk = [0u8; 4096];
+                while let Ok(read) = stream.read(&mut chunk).await {
+                    if read == 0 {
+                        break;
+                    }
+                    buffer.extend_from_slice(&chunk[..read]);
+                    let mut requests = serde_json::Deserializer::from_slice(&buffer).into_iter::<serde_json::Value>();
+                    let mut answers = Vec::new();
+                    for request in requests.by_ref() {
+                        let Ok(request) = request else { break };
+                        let result = if request["method"] == "eth_chainId" { "0x1" } else { "0x64" };
+                        answers.push(serde_json::json!({ "jsonrpc": "2.0", "id": request["id"], "result": result }));
+                    }
+                    let consumed = requests.byte_offset();
+                    buffer.drain(..consumed);
+                    for answer in answers {
+                        stream.write_all(answer.to_string().as_bytes()).await.unwrap();
+                    }
+                }
+            }
+        });
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_ipc_endpoint() {
+        let path = std::env::temp_dir().join(format!("asam-rpc-{}.ipc", std::process::id()));
+        ipc_node(&path).await;
+        let (provider, client) =
+            FailoverClient::new(&[path.to_str().unwrap()], Duration::from_secs(2), Duration::ZERO).unwrap().into_provider();
+
+        assert_eq!(client.verify_chain_id().await.unwrap(), Some(U256::one()));
+        let address = ethers::core::types::Address::zero();
+        assert_eq!(provider.get_balance(address, None).await.unwrap(), U256::from(100));
+        assert_eq!(client.health().last_served_by, Some(format!("ipc:{}", path.display())));
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn test_websocket_endpoint_fails_over() {
+        // Nothing listens on the websocket's port: the HTTP backup answers
+        let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("ws://{}", closed.local_addr().unwrap());
+        drop(closed);
+        let backup = node("0x1", "0xc8").await;
+        let (provider, client) =
+            FailoverClient::new(&[url, backup.uri()], Duration::from_secs(2), Duration::ZERO).unwrap().into_provider();
+
+        let address = ethers::core::types::Address::zero();
+        assert_eq!(provider.get_balance(address, None).await.unwrap(), U256::from(200));
+        let health = client.health();
+        assert!(!health.endpoints[0].healthy);
+        assert!(health.endpoints[0].endpoint.starts_with("ws://127.0.0.1:"), "{}", health.endpoints[0].endpoint);
+    }
+
+    #[tokio::test]
+    async fn test_transient_errors() {
+        let server = MockServer::start().await;
+        let (provider, _client) = client(&[&server]).into_provider();
+        let address = ethers::core::types::Address::zero();
+        let answers = [
+            (ResponseTemplate::new(429).set_body_string("Too Many Requests"), true),
+            (ResponseTemplate::new(502).set_body_string("<html>Bad Gateway</html>"), true),
+            (
+                ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                    "jsonrpc": "2.0", "id": 1, "error": { "code": -32005, "message": "daily request count exceeded" }
+                })),
+                true,
+            ),
+            (
+                ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                    "jsonrpc": "2.0", "id": 1, "error": { "code": -32602, "message": "invalid params" }
+                })),
+                false,
+            ),
+        ];
+        for (answer, transient) in answers {
+            server.reset().await;
+            Mock::given(method("POST")).respond_with(answer).mount(&server).await;
+            let error = provider.get_balance(address, None).await.unwrap_err();
+            assert_eq!(is_transient(&error), transient, "{}", error);
+        }
+    }
+
+    #[test]
+    fn test_retry_backoff() {
+        let policy = RetryPolicy {
+            max_attempts: 5,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(3),
+            jitter: 0.2,
+        };
+        let delays: Vec<Duration> = (1..=4).map(|retry| policy.delay(retry, 0.0)).collect();
+        assert_eq!(delays, [500, 1000, 2000, 3000].map(Duration::from_millis));
+        assert_eq!(policy.delay(1, -1.0), Duration::from_millis(400));
+        assert_eq!(policy.delay(1, 1.0), Duration::from_millis(600));
+        // Jitter never goes past the cap
+        assert_eq!(policy.delay(4, 1.0), Duration::from_secs(3));
+    }
+
+    #[tokio::test]
+    async fn test_chain_id_mismatch_is_an_error() {
+        let mainnet = node("0x1", "0x0").await;
+        let arbitrum = node("0xa4b1", "0x0").await;
+        let err = client(&[&mainnet, &arbitrum]).verify_chain_id().await.unwrap_err();
+        assert!(matches!(err.downcast_ref::<RpcError>(), Some(RpcError::ChainIdMismatch { .. })), "{}", err);
+    }
+}
@@ -0,0 +1,617 @@
+use log::{info, warn};
+use rand::Rng;
+use serde::Serialize;
+use std::fmt;
+use std::future::Future;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::sync::broadcast::{self, error::TryRecvError};
+use tokio::sync::{mpsc, watch};
+use tokio::time::{sleep_until, Instant};
+use tokio_util::sync::CancellationToken;
+
+use crate::util::unix_now;
+use crate::agents::safe_manager::BalanceIncrease;
+use crate::config::RuntimeConfig;
+use crate::status::StatusHandle;
+
+/// Independent pieces of work the monitoring loop performs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Phase {
+    /// Balance fetch and threshold evaluation.
+    Balance,
+    /// Pool refresh and the routing decision.
+    Pools,
+    /// Reporting on in-flight routes.
+    Routes,
+}
+
+impl Phase {
+    pub const ALL: [Phase; 3] = [Phase::Balance, Phase::Pools, Phase::Routes];
+}
+
+/// Cadence of each phase.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PhaseSchedule {
+    pub balance: Duration,
+    pub pools: Duration,
+    pub routes: Duration,
+}
+
+impl PhaseSchedule {
+    pub fn from_config(config: &RuntimeConfig) -> Self {
+        let cycle = config.cycle_interval_secs;
+        Self {
+            balance: Duration::from_secs(config.balance_interval_secs.unwrap_or(cycle)),
+            pools: Duration::from_secs(config.pool_interval_secs.unwrap_or(cycle)),
+            routes: Duration::from_secs(config.route_interval_secs.unwrap_or(cycle)),
+        }
+    }
+
+    pub fn interval(&self, phase: Phase) -> Duration {
+        match phase {
+            Phase::Balance => self.balance,
+            Phase::Pools => self.pools,
+            Phase::Routes => self.routes,
+        }
+    }
+}
+
+/// What woke the loop for a batch.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", content = "chain", rename_all = "snake_case")]
+pub enum CycleTrigger {
+    /// The first batch after start.
+    Startup,
+    /// A phase's timer came due, or its retry after a failure.
+    #[default]
+    Interval,
+    /// The balance on a chain rose, e.g. a deposit or a bridge landing.
+    BalanceIncreased(String),
+    /// `POST /admin/cycle`.
+    Admin,
+    /// An operator re-enabled a chain through `/admin/chains`.
+    ChainEnabled(String),
+    /// SIGUSR1.
+    Signal,
+    /// [`Runner::trigger_cycle`](crate::monitor::Runner::trigger_cycle) from an embedding application.
+    Api,
+}
+
+impl CycleTrigger {
+    /// Whether a batch of `phases` already did what this trigger asks for. A balance
+    /// rise seen by a batch that also decided on the pools needs no rerun.
+    fn satisfied_by(&self, phases: &[Phase]) -> bool {
+        matches!(self, CycleTrigger::BalanceIncreased(_)) && phases.contains(&Phase::Pools)
+    }
+}
+
+impl fmt::Display for CycleTrigger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CycleTrigger::Startup => write!(f, "startup"),
+            CycleTrigger::Interval => write!(f, "interval"),
+            CycleTrigger::BalanceIncreased(chain) => write!(f, "balance increase on {}", chain),
+            CycleTrigger::Admin => write!(f, "admin request"),
+            CycleTrigger::ChainEnabled(chain) => write!(f, "{} enabled", chain),
+            CycleTrigger::Signal => write!(f, "SIGUSR1"),
+            CycleTrigger::Api => write!(f, "API request"),
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+#[non_exhaustive]
+pub enum CycleError {
+    /// A phase ran out of its share of the cycle deadline.
+    #[error("{phase} phase timed out after {after:.1?}")]
+    PhaseTimedOut { phase: &'static str, after: Duration },
+    /// The cycle as a whole ran past `runtime.cycle_timeout_secs`.
+    #[error("cycle timed out after {after:.1?} during {}", phase_list(.phases))]
+    TimedOut { phases: Vec<&'static str>, after: Duration },
+}
+
+fn phase_list(phases: &[&str]) -> String {
+    if phases.is_empty() {
+        "no tracked phase".to_string()
+    } else {
+        phases.join(" > ")
+    }
+}
+
+/// Kind of failure, which decides how quickly the retry delay grows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailureClass {
+    /// The RPC node or an API asked us to slow down.
+    RateLimited,
+    /// Anything else: unreachable endpoints, parse errors, failed checks.
+    Other,
+}
+
+impl FailureClass {
+    pub fn of(err: &anyhow::Error) -> Self {
+        let message = format!("{:#}", err).to_lowercase();
+        if ["too many requests", "rate limit", "rate-limit", "ratelimit"]
+            .iter()
+            .any(|needle| message.contains(needle))
+        {
+            FailureClass::RateLimited
+        } else {
+            FailureClass::Other
+        }
+    }
+
+    /// Factor the delay grows by with each consecutive failure.
+    fn growth(self) -> f64 {
+        match self {
+            FailureClass::RateLimited => 4.0,
+            FailureClass::Other => 2.0,
+        }
+    }
+}
+
+/// Result of one batch of phases.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Success,
+    Failure(FailureClass),
+}
+
+impl Outcome {
+    pub fn of<T>(result: &anyhow::Result<T>) -> Self {
+        match result {
+            Ok(_) => Outcome::Success,
+            Err(e) => Outcome::Failure(FailureClass::of(e)),
+        }
+    }
+}
+
+/// Capped exponential backoff applied after consecutive failed batches.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Backoff {
+    pub max: Duration,
+    /// Relative jitter, e.g. 0.2 for ±20%.
+    pub jitter: f64,
+}
+
+impl Backoff {
+    pub fn from_config(config: &RuntimeConfig) -> Self {
+        Self {
+            max: Duration::from_secs(config.max_backoff_secs),
+            jitter: 0.2,
+        }
+    }
+
+    /// Delay before the next attempt after `streak` consecutive failures.
+    ///
+    /// `sample` in `[-1, 1]` picks the point within the jitter range.
+    pub fn delay(&self, interval: Duration, streak: u32, class: FailureClass, sample: f64) -> Duration {
+        let max = self.max.max(interval).as_secs_f64();
+        let base = (interval.as_secs_f64() * class.growth().powi(streak as i32)).min(max);
+        let jittered = base * (1.0 + self.jitter * sample.clamp(-1.0, 1.0));
+        Duration::from_secs_f64(jittered.min(max))
+    }
+}
+
+/// Runs `run` with the phases that are due until `shutdown` fires.
+///
+/// Every phase is due immediately on start. Phases that come due at the same
+/// instant are handed over together, so with equal intervals this behaves like
+/// a single loop that runs a full cycle and then sleeps for the interval.
+/// After a failed batch its phases are retried on the `backoff` schedule instead,
+/// until a batch succeeds again. The failure streak and next retry time are
+/// published on `status`. A new `schedule` reschedules the pending runs of phases
+/// that are not backing off from their last run.
+///
+/// A message on `trigger` or a rise on `balances` makes every phase due right away,
+/// as if its timer had fired, and `run` is told which woke it. Those arriving while
+/// a batch runs are folded into a single rerun right after it, under the first one's
+/// reason; a balance rise seen by a batch that ran [`Phase::Pools`] needs none.
+pub async fn run_phases<F, Fut>(
+    mut schedule: watch::Receiver<PhaseSchedule>,
+    mut trigger: mpsc::Receiver<CycleTrigger>,
+    mut balances: broadcast::Receiver<BalanceIncrease>,
+    backoff: Backoff,
+    status: &StatusHandle,
+    shutdown: CancellationToken,
+    mut run: F,
+) where
+    F: FnMut(Vec<Phase>, CycleTrigger) -> Fut,
+    Fut: Future<Output = Outcome>,
+{
+    let start = Instant::now();
+    let mut next_due = [start; 3];
+    let mut last_run: [Option<Instant>; 3] = [None; 3];
+    let mut streak = 0u32;
+    let mut reason = CycleTrigger::Startup;
+
+    loop {
+        let now = Instant::now();
+        let due: Vec<Phase> = Phase::ALL
+            .iter()
+            .zip(next_due.iter())
+            .filter(|(_, at)| **at <= now)
+            .map(|(phase, _)| *phase)
+            .collect();
+
+        if !due.is_empty() {
+            let outcome = run(due.clone(), std::mem::take(&mut reason)).await;
+            let pending = pending_trigger(&mut trigger, &mut balances, &due);
+
+            // Schedule from the time the work finished, like a sleep after each cycle
+            let finished = Instant::now();
+            let failure = match outcome {
+                Outcome::Success => {
+                    streak = 0;
+                    None
+                }
+                Outcome::Failure(class) => {
+                    streak = streak.saturating_add(1);
+                    Some(class)
+                }
+            };
+            let sample = rand::thread_rng().gen_range(-1.0..=1.0);
+            let mut retry_in = Duration::ZERO;
+            let current = *schedule.borrow();
+            for phase in due {
+                let i = Phase::ALL.iter().position(|p| *p == phase).unwrap_or_default();
+                let delay = match failure {
+                    Some(class) => backoff.delay(current.interval(phase), streak, class, sample),
+                    None => current.interval(phase),
+                };
+                retry_in = retry_in.max(delay);
+                next_due[i] = finished + delay;
+                last_run[i] = Some(finished);
+            }
+
+            if let Some(class) = failure {
+                warn!(
+                    "{} consecutive failed cycle(s) ({:?}), next attempt in {:.0?}",
+                    streak, class, retry_in
+                );
+            }
+            status.update(|s| {
+                s.failure_streak = streak;
+                s.next_retry_at = failure.map(|_| unix_now() + retry_in.as_secs());
+            });
+            if let Some(pending) = pending {
+                next_due = [Instant::now(); 3];
+                reason = pending;
+                continue;
+            }
+        }
+
+        let wake_at = next_due.iter().min().copied().unwrap_or(now);
+        tokio::select! {
+            _ = shutdown.cancelled() => break,
+            _ = sleep_until(wake_at) => {}
+            Some(woken) = trigger.recv() => {
+                info!("Running all phases now: {}", woken);
+                next_due = [Instant::now(); 3];
+                reason = woken;
+            }
+            Ok(increase) = balances.recv() => {
+                info!("Running all phases now: balance on {} rose to {} wei", increase.chain, increase.current);
+                next_due = [Instant::now(); 3];
+                reason = CycleTrigger::BalanceIncreased(increase.chain);
+            }
+            Ok(()) = schedule.changed() => {
+                let current = *schedule.borrow_and_update();
+                if streak == 0 {
+                    for (i, phase) in Phase::ALL.iter().enumerate() {
+                        if let Some(at) = last_run[i] {
+                            next_due[i] = at + current.interval(*phase);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The trigger for a rerun, if any of those that came in while `ran` was running
+/// still needs one. The others are folded into it.
+fn pending_trigger(
+    trigger: &mut mpsc::Receiver<CycleTrigger>,
+    balances: &mut broadcast::Receiver<BalanceIncrease>,
+    ran: &[Phase],
+) -> Option<CycleTrigger> {
+    let mut arrived = Vec::new();
+    while let Ok(woken) = trigger.try_recv() {
+        arrived.push(woken);
+    }
+    loop {
+        match balances.try_recv() {
+            Ok(increase) => arrived.push(CycleTrigger::BalanceIncreased(increase.chain)),
+            Err(TryRecvError::Lagged(_)) => continue,
+            Err(_) => break,
+        }
+    }
+    let mut pending = arrived.into_iter().filter(|woken| !woken.satisfied_by(ran));
+    let first = pending.next()?;
+    let folded = pending.count();
+    if folded > 0 {
+        info!("{} more trigger(s) arrived during the batch; folded into one rerun", folded);
+    }
+    info!("Running all phases again: {}", first);
+    Some(first)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+
+    fn backoff() -> Backoff {
+        Backoff::from_config(&RuntimeConfig::default())
+    }
+
+    async fn count_phases(schedule: PhaseSchedule, run_for: Duration) -> HashMap<Phase, usize> {
+        let counts: Arc<Mutex<HashMap<Phase, usize>>> = Arc::default();
+        let shutdown = CancellationToken::new();
+
+        let task = {
+            let counts = counts.clone();
+            let shutdown = shutdown.clone();
+            tokio::spawn(async move {
+                run_phases(watch::channel(schedule).1, mpsc::channel(1).1, broadcast::channel(1).1, backoff(), &StatusHandle::new(), shutdown, move |due, _| {
+                    let counts = counts.clone();
+                    async move {
+                        let mut counts = counts.lock().unwrap();
+                        for phase in due {
+                            *counts.entry(phase).or_default() += 1;
+                        }
+                        Outcome::Success
+                    }
+                })
+                .await
+            })
+        };
+
+        tokio::time::sleep(run_for).await;
+        shutdown.cancel();
+        task.await.unwrap();
+        let counts = counts.lock().unwrap().clone();
+        counts
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_each_phase_runs_at_its_cadence() {
+        let schedule = PhaseSchedule {
+            balance: Duration::from_secs(5),
+            pools: Duration::from_secs(10),
+            routes: Duration::from_secs(15),
+        };
+        // Phases fire at t=0 and then every interval up to t=59s
+        let counts = count_phases(schedule, Duration::from_millis(59_500)).await;
+        assert_eq!(counts[&Phase::Balance], 12);
+        assert_eq!(counts[&Phase::Pools], 6);
+        assert_eq!(counts[&Phase::Routes], 4);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_new_schedule_replaces_pending_sleep() {
+        let (tx, rx) = watch::channel(PhaseSchedule::from_config(&RuntimeConfig::default()));
+        let runs = Arc::new(Mutex::new(0));
+        let shutdown = CancellationToken::new();
+        let task = {
+            let (runs, shutdown) = (runs.clone(), shutdown.clone());
+            tokio::spawn(async move {
+                run_phases(rx, mpsc::channel(1).1, broadcast::channel(1).1, backoff(), &StatusHandle::new(), shutdown, move |_, _| {
+                    *runs.lock().unwrap() += 1;
+                    async { Outcome::Success }
+                })
+                .await
+            })
+        };
+
+        // The first batch sleeps 60s; 5s intervals make it due again at t=5s
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        let every = Duration::from_secs(5);
+        tx.send_replace(PhaseSchedule { balance: every, pools: every, routes: every });
+        tokio::time::sleep(Duration::from_millis(11_500)).await;
+        shutdown.cancel();
+        task.await.unwrap();
+        assert_eq!(*runs.lock().unwrap(), 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_trigger_runs_all_phases_now() {
+        let schedule = PhaseSchedule::from_config(&RuntimeConfig::default());
+        let (trigger, triggers) = mpsc::channel(1);
+        let batches: Arc<Mutex<Vec<Vec<Phase>>>> = Arc::default();
+        let shutdown = CancellationToken::new();
+        let task = {
+            let (batches, shutdown) = (batches.clone(), shutdown.clone());
+            tokio::spawn(async move {
+                run_phases(watch::channel(schedule).1, triggers, broadcast::channel(1).1, backoff(), &StatusHandle::new(), shutdown, move |due, _| {
+                    batches.lock().unwrap().push(due);
+                    async { Outcome::Success }
+                })
+                .await
+            })
+        };
+
+        tokio::time::sleep(Duration::from_secs(10)).await;
+        trigger.send(CycleTrigger::Admin).await.unwrap();
+        // The timer restarts from the triggered run: the next one is at t=70s, not t=60s
+        tokio::time::sleep(Duration::from_secs(55)).await;
+        assert_eq!(batches.lock().unwrap().len(), 2);
+        tokio::time::sleep(Duration::from_secs(10)).await;
+        shutdown.cancel();
+        task.await.unwrap();
+        let batches = batches.lock().unwrap();
+        assert_eq!(batches.len(), 3);
+        assert!(batches.iter().all(|due| due.len() == Phase::ALL.len()));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_triggers_during_a_batch_fold_into_one_rerun() {
+        let every = Duration::from_secs(60);
+        let schedule = PhaseSchedule { balance: Duration::from_secs(20), pools: every, routes: every };
+        let (trigger, triggers) = mpsc::channel(1);
+        let (balance_tx, balances) = broadcast::channel(16);
+        let increase = |chain: &str| BalanceIncrease {
+            chain: chain.to_string(),
+            previous: Default::default(),
+            current: Default::default(),
+        };
+        let batches = Arc::new(Mutex::new(Vec::<(CycleTrigger, Vec<Phase>)>::new()));
+        let shutdown = CancellationToken::new();
+        let task = {
+            let (batches, shutdown) = (batches.clone(), shutdown.clone());
+            tokio::spawn(async move {
+                run_phases(watch::channel(schedule).1, triggers, balances, backoff(), &StatusHandle::new(), shutdown, move |due, reason| {
+                    batches.lock().unwrap().push((reason, due));
+                    // Every batch takes a second
+                    async {
+                        tokio::time::sleep(Duration::from_secs(1)).await;
+                        Outcome::Success
+                    }
+                })
+                .await
+            })
+        };
+        let reasons = || batches.lock().unwrap().iter().map(|(reason, _)| reason.clone()).collect::<Vec<_>>();
+
+        // Sources firing during the first batch: one rerun under the first reason, and
+        // the balance rise is covered by the batch's own pool phase
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        trigger.try_send(CycleTrigger::Admin).unwrap();
+        assert!(trigger.try_send(CycleTrigger::Signal).is_err());
+        balance_tx.send(increase("Ethereum")).unwrap();
+        tokio::time::sleep(Duration::from_secs(5)).await;
+        assert_eq!(reasons(), [CycleTrigger::Startup, CycleTrigger::Admin]);
+
+        // While idle each source wakes the loop on its own
+        balance_tx.send(increase("Arbitrum")).unwrap();
+        tokio::time::sleep(Duration::from_secs(5)).await;
+        trigger.try_send(CycleTrigger::ChainEnabled("Optimism".to_string())).unwrap();
+        tokio::time::sleep(Duration::from_secs(5)).await;
+        assert_eq!(reasons().len(), 4);
+
+        // A rise during a balance-only batch (t=31.5s to 32.5s) still needs the pool phase
+        tokio::time::sleep(Duration::from_millis(16_500)).await;
+        assert_eq!(batches.lock().unwrap().last().unwrap(), &(CycleTrigger::Interval, vec![Phase::Balance]));
+        balance_tx.send(increase("Base")).unwrap();
+        tokio::time::sleep(Duration::from_secs(5)).await;
+        shutdown.cancel();
+        task.await.unwrap();
+
+        assert_eq!(
+            reasons(),
+            [
+                CycleTrigger::Startup,
+                CycleTrigger::Admin,
+                CycleTrigger::BalanceIncreased("Arbitrum".to_string()),
+                CycleTrigger::ChainEnabled("Optimism".to_string()),
+                CycleTrigger::Interval,
+                CycleTrigger::BalanceIncreased("Base".to_string()),
+            ]
+        );
+        assert_eq!(batches.lock().unwrap()[5].1, Phase::ALL.to_vec());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_default_schedule_runs_phases_together() {
+        let schedule = PhaseSchedule::from_config(&RuntimeConfig::default());
+        assert_eq!(schedule.balance, Duration::from_secs(60));
+
+        let batches: Arc<Mutex<Vec<Vec<Phase>>>> = Arc::default();
+        let shutdown = CancellationToken::new();
+        let task = {
+            let batches = batches.clone();
+            let shutdown = shutdown.clone();
+            tokio::spawn(async move {
+                run_phases(watch::channel(schedule).1, mpsc::channel(1).1, broadcast::channel(1).1, backoff(), &StatusHandle::new(), shutdown, move |due, _| {
+                    let batches = batches.clone();
+                    async move {
+                        batches.lock().unwrap().push(due);
+                        Outcome::Success
+                    }
+                })
+                .await
+            })
+        };
+
+        tokio::time::sleep(Duration::from_secs(150)).await;
+        shutdown.cancel();
+        task.await.unwrap();
+
+        let batches = batches.lock().unwrap();
+        assert_eq!(batches.len(), 3);
+        assert!(batches.iter().all(|b| b == &Phase::ALL.to_vec()));
+    }
+
+    #[test]
+    fn test_backoff_delay() {
+        let backoff = Backoff { max: Duration::from_secs(900), jitter: 0.2 };
+        let interval = Duration::from_secs(60);
+        let delay = |streak, class, sample| backoff.delay(interval, streak, class, sample).as_secs();
+
+        assert_eq!(delay(1, FailureClass::Other, 0.0), 120);
+        assert_eq!(delay(3, FailureClass::Other, 0.0), 480);
+        assert_eq!(delay(3, FailureClass::Other, -1.0), 384);
+        assert_eq!(delay(3, FailureClass::Other, 1.0), 576);
+        // Capped, and jitter never pushes past the cap
+        assert_eq!(delay(10, FailureClass::Other, 1.0), 900);
+        assert_eq!(delay(10, FailureClass::Other, -1.0), 720);
+        // Rate limits grow twice as fast per failure
+        assert_eq!(delay(1, FailureClass::RateLimited, 0.0), 240);
+        assert_eq!(delay(2, FailureClass::RateLimited, 0.0), 900);
+
+        let rate_limited = anyhow::anyhow!("API request failed with status: 429 Too Many Requests");
+        assert_eq!(FailureClass::of(&rate_limited), FailureClass::RateLimited);
+        let parse = anyhow::anyhow!("Failed to parse API response");
+        assert_eq!(FailureClass::of(&parse), FailureClass::Other);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_failures_back_off_until_success() {
+        let schedule = PhaseSchedule::from_config(&RuntimeConfig::default());
+        let status = StatusHandle::new();
+        let shutdown = CancellationToken::new();
+        // Five failures, then success
+        let runs: Arc<Mutex<Vec<Instant>>> = Arc::default();
+
+        let task = {
+            let runs = runs.clone();
+            let status = status.clone();
+            let shutdown = shutdown.clone();
+            tokio::spawn(async move {
+                run_phases(watch::channel(schedule).1, mpsc::channel(1).1, broadcast::channel(1).1, backoff(), &status, shutdown, move |_, _| {
+                    let runs = runs.clone();
+                    async move {
+                        let mut runs = runs.lock().unwrap();
+                        runs.push(Instant::now());
+                        if runs.len() <= 5 {
+                            Outcome::Failure(FailureClass::Other)
+                        } else {
+                            Outcome::Success
+                        }
+                    }
+                })
+                .await
+            })
+        };
+
+        // 2+4+8+15+15 minutes of backoff at most, then one regular interval
+        tokio::time::sleep(Duration::from_secs(60 * 60)).await;
+        let snapshot = status.snapshot();
+        shutdown.cancel();
+        task.await.unwrap();
+
+        let runs = runs.lock().unwrap();
+        let gaps: Vec<f64> = runs.windows(2).map(|w| (w[1] - w[0]).as_secs_f64()).collect();
+        for (i, gap) in gaps.iter().take(5).enumerate() {
+            let base = (60.0 * 2f64.powi(i as i32 + 1)).min(900.0);
+            assert!(*gap >= base * 0.8 - 0.001, "gap {} was {}s, expected about {}s", i, gap, base);
+            assert!(*gap <= (base * 1.2).min(900.0) + 0.001, "gap {} was {}s, expected about {}s", i, gap, base);
+        }
+        // Success resets the streak and the regular interval resumes
+        assert!(gaps[5..].iter().all(|gap| (*gap - 60.0).abs() < 0.001), "{:?}", gaps);
+        assert_eq!(snapshot.failure_streak, 0);
+        assert_eq!(snapshot.next_retry_at, None);
+    }
+}
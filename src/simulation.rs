@@ -0,0 +1,861 @@
+//! Replays a scenario through the real decision path on a simulated clock.
+//!
+//! A [`Scenario`] describes how the world changes: the Safe's balance, pool APYs by
+//! protocol and chain, bridge fees and RPC outages, each as levels that hold from a
+//! given day on. [`run`] steps through it one `interval_secs` at a time, answers
+//! balance calls from an in-process [`StaticNode`], hands the pools to the optimizer
+//! through a [`StaticSource`] and runs a full monitoring cycle on each step. Routes
+//! settle through the router's simulated bridge, so nothing leaves the process.
+//!
+//! Time is tokio's clock, which [`run`] expects to be paused: a 30-day scenario then
+//! takes seconds. [`simulate`] runs it on a runtime of its own set up that way.
+//!
+//! With `runtime.simulation = "deterministic"` the agent itself runs on a scenario:
+//! [`SimulatedWorld`] stands in for the RPC endpoint, the pool API, the price source
+//! and the swap quoter, and a seeded [`SimulationModel`] decides what each of them
+//! serves on every cycle, so two runs with the same seed make the same decisions.
+//!
+//! [`check_fork`] is the other half of `asam simulate`: with `--fork` it drives the
+//! Safe's transaction paths against real contracts on an Anvil fork.
+
+use anyhow::{bail, Context, Result};
+use ethers::abi::Token;
+use ethers::core::types::{Address, TransactionRequest, H256, U256};
+use ethers::providers::{JsonRpcError, Middleware};
+use ethers::types::transaction::eip2718::TypedTransaction;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tokio::time::{Duration, Instant};
+
+use crate::agents::cross_chain_router::CrossChainRouter;
+use crate::util::with_simulated_clock;
+use crate::agents::defi_optimizer::{DefiOptimizer, PoolData, StaticSource};
+use crate::agents::safe_manager::{SafeManager, SafeTransaction};
+use crate::config::{Config, RunMode, SafeExecution, SimulationMode};
+use crate::monitor::monitor_and_optimize;
+use crate::notifier::EventSender;
+use crate::portfolio::HOME_CHAIN;
+use crate::prices::{FixedSource, PriceSource};
+use crate::recent_actions::RecentActions;
+use crate::report::CycleDecision;
+use crate::rpc::{FailoverClient, RpcProvider, StaticNode};
+use crate::status::StatusHandle;
+use crate::storage::MemoryStore;
+use crate::strategy::Strategy;
+use crate::test_support::AnvilFork;
+
+const SECS_PER_DAY: f64 = 24.0 * 60.0 * 60.0;
+const DAYS_PER_YEAR: f64 = 365.0;
+
+/// The scenario `runtime.simulation = "deterministic"` runs on.
+pub const DEMO_SCENARIO: &str = include_str!("../tests/fixtures/scenarios/demo.toml");
+/// Largest change, in APY points, the seed makes to a pool's scripted APY.
+const APY_JITTER: f64 = 0.15;
+/// Gas price the simulated node quotes, in gwei.
+const SIMULATED_GAS_PRICE_GWEI: u64 = 8;
+/// Gas the simulated node estimates for any transaction.
+const SIMULATED_GAS_LIMIT: u64 = 150_000;
+/// Gas QuoterV2 reports for a simulated swap quote.
+const SIMULATED_QUOTE_GAS: u64 = 100_000;
+
+fn default_interval_secs() -> u64 {
+    3600
+}
+
+fn default_start() -> u64 {
+    // 2023-11-14, any fixed point works
+    1_700_000_000
+}
+
+/// Safe balance from `day` on.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BalanceLevel {
+    pub day: f64,
+    pub eth: f64,
+}
+
+/// A pool's metrics from `day` on; replaces the earlier level of the same protocol
+/// and chain.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PoolLevel {
+    pub day: f64,
+    pub protocol: String,
+    pub chain: String,
+    pub apy: Option<f64>,
+    pub tvl: f64,
+    /// Underlying asset; defaults to `swap.protocol_assets` of the config.
+    #[serde(default)]
+    pub asset: Option<String>,
+}
+
+/// Fee of one bridge route from `day` on.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FeeLevel {
+    pub day: f64,
+    pub eth: f64,
+}
+
+/// Round-trip swap cost from `day` on, in percent of the amount routed into a pool
+/// whose underlying is not the held asset.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SwapCostLevel {
+    pub day: f64,
+    pub pct: f64,
+}
+
+/// The RPC endpoint fails every call from `from_day` until `to_day`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Outage {
+    pub from_day: f64,
+    pub to_day: f64,
+}
+
+/// Everything that happens during a simulation, loaded from a TOML file.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    pub name: String,
+    pub days: f64,
+    /// Seconds between cycles.
+    #[serde(default = "default_interval_secs")]
+    pub interval_secs: u64,
+    /// Unix time of day 0.
+    #[serde(default = "default_start")]
+    pub start: u64,
+    #[serde(default)]
+    pub balances: Vec<BalanceLevel>,
+    #[serde(default)]
+    pub pools: Vec<PoolLevel>,
+    #[serde(default)]
+    pub bridge_fees: Vec<FeeLevel>,
+    #[serde(default)]
+    pub swap_costs: Vec<SwapCostLevel>,
+    #[serde(default)]
+    pub outages: Vec<Outage>,
+    /// USD price by symbol for the whole scenario.
+    #[serde(default)]
+    pub prices: BTreeMap<String, f64>,
+}
+
+/// The latest of `levels` that started by `day`.
+fn level_at<T>(levels: &[T], day: f64, start: impl Fn(&T) -> f64) -> Option<&T> {
+    levels.iter().filter(|level| start(level) <= day).max_by(|a, b| start(a).total_cmp(&start(b)))
+}
+
+impl Scenario {
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        Self::parse(&text).with_context(|| format!("Invalid scenario {}", path.display()))
+    }
+
+    pub fn parse(text: &str) -> Result<Self> {
+        let scenario: Self = toml::from_str(text)?;
+        scenario.validate()?;
+        Ok(scenario)
+    }
+
+    fn validate(&self) -> Result<()> {
+        if !(self.days.is_finite() && self.days > 0.0) {
+            bail!("days must be positive");
+        }
+        if self.interval_secs == 0 {
+            bail!("interval_secs must be positive");
+        }
+        if self.balances.is_empty() || self.pools.is_empty() {
+            bail!("a scenario needs at least one balance and one pool");
+        }
+        if self.balances.iter().any(|level| level.eth < 0.0) || self.bridge_fees.iter().any(|level| level.eth < 0.0) {
+            bail!("balances and bridge fees cannot be negative");
+        }
+        if self.swap_costs.iter().any(|level| !(0.0..100.0).contains(&level.pct)) {
+            bail!("swap costs must be at least 0 and below 100 percent");
+        }
+        if let Some(outage) = self.outages.iter().find(|outage| outage.to_day <= outage.from_day) {
+            bail!("outage from day {} must end after it starts", outage.from_day);
+        }
+        if self.prices.values().any(|usd| !(usd.is_finite() && *usd > 0.0)) {
+            bail!("prices must be positive");
+        }
+        Ok(())
+    }
+
+    pub fn balance_at(&self, day: f64) -> f64 {
+        level_at(&self.balances, day, |level| level.day).map_or(0.0, |level| level.eth)
+    }
+
+    /// Every pool's latest level by `day`, in the order the pools first appear.
+    pub fn pools_at(&self, day: f64) -> Vec<PoolData> {
+        let mut pools: Vec<PoolData> = Vec::new();
+        for level in &self.pools {
+            if pools.iter().any(|p| p.protocol == level.protocol && p.chain == level.chain) {
+                continue;
+            }
+            let same = |l: &&PoolLevel| l.protocol == level.protocol && l.chain == level.chain;
+            let levels: Vec<&PoolLevel> = self.pools.iter().filter(same).collect();
+            if let Some(latest) = level_at(&levels, day, |l| l.day) {
+                pools.push(PoolData {
+                    protocol: latest.protocol.clone(),
+                    chain: latest.chain.clone(),
+                    apy: latest.apy,
+                    tvl: latest.tvl,
+                });
+            }
+        }
+        pools
+    }
+
+    pub fn bridge_fee_at(&self, day: f64) -> f64 {
+        level_at(&self.bridge_fees, day, |level| level.day).map_or(0.0, |level| level.eth)
+    }
+
+    /// Round-trip swap cost in percent at `day`.
+    pub fn swap_cost_at(&self, day: f64) -> f64 {
+        level_at(&self.swap_costs, day, |level| level.day).map_or(0.0, |level| level.pct)
+    }
+
+    /// Underlying asset of a pool, when one of its levels names it.
+    pub fn pool_asset(&self, protocol: &str, chain: &str) -> Option<&str> {
+        self.pools
+            .iter()
+            .filter(|level| level.protocol == protocol && level.chain == chain)
+            .find_map(|level| level.asset.as_deref())
+    }
+
+    pub fn rpc_down_at(&self, day: f64) -> bool {
+        self.outages.iter().any(|outage| outage.from_day <= day && day < outage.to_day)
+    }
+}
+
+/// What the simulated node serves right now.
+#[derive(Debug, Default)]
+struct NodeState {
+    balance: U256,
+    down: bool,
+}
+
+/// JSON-RPC node answering from the scenario's current state, with the account a
+/// 1-of-1 Safe. Without a gas price it answers balance reads and Safe calls only;
+/// with one it also estimates gas, runs any other call without reverting or
+/// returning data, and quotes swaps, every swap at one to one, so a quote costs
+/// exactly the pool's fee tier.
+#[derive(Debug)]
+struct ScenarioNode {
+    chain_id: u64,
+    gas_price: Option<U256>,
+    state: Mutex<NodeState>,
+}
+
+impl ScenarioNode {
+    fn new(chain_id: u64, gas_price: Option<U256>) -> Self {
+        Self { chain_id, gas_price, state: Mutex::default() }
+    }
+
+    fn set(&self, balance: U256, down: bool) {
+        *self.state.lock().unwrap_or_else(|e| e.into_inner()) = NodeState { balance, down };
+    }
+
+    /// The Safe's answer to `getThreshold`, `getOwners`, `nonce`, `VERSION` and the
+    /// fallback handler's `simulate`.
+    fn safe_call(params: &serde_json::Value) -> Option<serde_json::Value> {
+        let tx = params.get(0)?;
+        let data = tx.get("data").or_else(|| tx.get("input"))?.as_str()?;
+        let data = hex::decode(data.trim_start_matches("0x")).ok()?;
+        let selector = data.get(..4)?;
+        let output = if selector == ethers::utils::id("getThreshold()") {
+            ethers::abi::encode(&[Token::Uint(U256::one())])
+        } else if selector == ethers::utils::id("getOwners()") {
+            ethers::abi::encode(&[Token::Array(vec![Token::Address(Address::from_low_u64_be(1))])])
+        } else if selector == ethers::utils::id("nonce()") {
+            ethers::abi::encode(&[Token::Uint(U256::zero())])
+        } else if selector == ethers::utils::id("VERSION()") {
+            ethers::abi::encode(&[Token::String("1.3.0".to_string())])
+        } else if selector == ethers::utils::id("simulate(address,bytes)") {
+            // The accessor's (estimate, success, returnData), as a delegatecall batch runs
+            let simulated = ethers::abi::encode(&[
+                Token::Uint(SIMULATED_GAS_LIMIT.into()),
+                Token::Bool(true),
+                Token::Bytes(Vec::new()),
+            ]);
+            ethers::abi::encode(&[Token::Bytes(simulated)])
+        } else {
+            return None;
+        };
+        Some(serde_json::json!(format!("0x{}", hex::encode(output))))
+    }
+
+    /// QuoterV2's answer to `quoteExactInputSingle`: as much out as in.
+    fn quote(params: &serde_json::Value) -> Option<serde_json::Value> {
+        let tx = params.get(0)?;
+        let data = tx.get("data").or_else(|| tx.get("input"))?.as_str()?;
+        let data = hex::decode(data.trim_start_matches("0x")).ok()?;
+        let selector = ethers::utils::id("quoteExactInputSingle((address,address,uint256,uint24,uint160))");
+        if data.get(..4)? != selector.as_slice() {
+            return None;
+        }
+        // tokenIn, tokenOut, then amountIn
+        let amount_in = U256::from_big_endian(data.get(4 + 64..4 + 96)?);
+        let output = ethers::abi::encode(&[
+            Token::Uint(amount_in),
+            Token::Uint(U256::one()),
+            Token::Uint(U256::zero()),
+            Token::Uint(SIMULATED_QUOTE_GAS.into()),
+        ]);
+        Some(serde_json::json!(format!("0x{}", hex::encode(output))))
+    }
+}
+
+impl StaticNode for ScenarioNode {
+    fn answer(&self, method: &str, params: serde_json::Value) -> Result<serde_json::Value, JsonRpcError> {
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if state.down {
+            return Err(JsonRpcError { code: -32000, message: "simulated RPC outage".to_string(), data: None });
+        }
+        let not_simulated =
+            || JsonRpcError { code: -32601, message: format!("{} is not simulated", method), data: None };
+        match (method, self.gas_price) {
+            ("eth_chainId", _) => Ok(serde_json::json!(format!("{:#x}", self.chain_id))),
+            ("eth_getBalance", _) => Ok(serde_json::json!(format!("{:#x}", state.balance))),
+            ("eth_gasPrice", Some(gas_price)) => Ok(serde_json::json!(format!("{:#x}", gas_price))),
+            ("eth_estimateGas", Some(_)) => Ok(serde_json::json!(format!("{:#x}", SIMULATED_GAS_LIMIT))),
+            ("eth_call", None) => Self::safe_call(&params).ok_or_else(not_simulated),
+            ("eth_call", Some(_)) => {
+                Ok(Self::safe_call(&params).or_else(|| Self::quote(&params)).unwrap_or_else(|| serde_json::json!("0x")))
+            }
+            _ => Err(not_simulated()),
+        }
+    }
+}
+
+/// A route the simulation took.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SimulatedRoute {
+    pub day: f64,
+    pub source_chain: String,
+    pub target_chain: String,
+    pub amount: f64,
+    pub apy: Option<f64>,
+    pub fee_eth: f64,
+    /// Round-trip swap cost, when the target pool's underlying is not the held asset.
+    pub swap_cost_eth: f64,
+}
+
+/// A cycle whose outcome differed from the one before it; `None` is a failed cycle.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DecisionChange {
+    pub day: f64,
+    pub decision: Option<CycleDecision>,
+    pub detail: String,
+}
+
+/// One cycle's outcome and the position held after it.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CycleOutcome {
+    pub day: f64,
+    pub decision: Option<CycleDecision>,
+    pub detail: String,
+    /// Chain holding the funds after the cycle.
+    pub chain: String,
+    /// Best APY on that chain, the rate the position earns until the next cycle.
+    pub apy: Option<f64>,
+}
+
+/// Outcome of a simulation.
+#[derive(Debug, Clone, Serialize)]
+pub struct SimulationSummary {
+    pub scenario: String,
+    pub days: f64,
+    pub cycles: u64,
+    /// Cycles by decision kind, `failed` for cycles that errored.
+    pub decisions: BTreeMap<String, u64>,
+    pub changes: Vec<DecisionChange>,
+    pub routes: Vec<SimulatedRoute>,
+    /// Bridge fees of every route taken.
+    pub fees_eth: f64,
+    /// Round-trip swap costs of the routes into other underlyings.
+    pub swap_costs_eth: f64,
+    /// What the held position earned at the scenario's APYs.
+    pub earned_eth: f64,
+    pub final_chain: String,
+    pub final_protocol: Option<String>,
+    /// Every cycle, where `changes` only has those that differ from the one before.
+    #[serde(skip)]
+    pub timeline: Vec<CycleOutcome>,
+}
+
+pub(crate) fn decision_kind(decision: Option<&CycleDecision>) -> &'static str {
+    match decision {
+        None => "failed",
+        Some(CycleDecision::NoAction) => "no_action",
+        Some(CycleDecision::NotWorthMoving) => "not_worth_moving",
+        Some(CycleDecision::RouteInitiated) => "route_initiated",
+        Some(CycleDecision::Blocked(_)) => "blocked",
+        Some(CycleDecision::Declined(_)) => "declined",
+    }
+}
+
+/// Best APY among `pools` on `chain`.
+fn apy_on(pools: &[PoolData], chain: &str) -> Option<f64> {
+    pools.iter().filter(|p| p.chain == chain).filter_map(|p| p.apy).max_by(f64::total_cmp)
+}
+
+/// Runs `scenario` with the decision settings of `config`. Needs a paused tokio clock.
+pub async fn run(config: &Config, scenario: &Scenario) -> Result<SimulationSummary> {
+    with_simulated_clock(scenario.start, simulate_cycles(config, scenario)).await
+}
+
+/// Runs `scenario` on a runtime of its own with the clock paused, from sync or async
+/// code alike.
+pub fn simulate(config: &Config, scenario: &Scenario) -> Result<SimulationSummary> {
+    std::thread::scope(|scope| {
+        scope
+            .spawn(|| {
+                tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .start_paused(true)
+                    .build()
+                    .context("Failed to start the simulation runtime")?
+                    .block_on(run(config, scenario))
+            })
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    })
+}
+
+async fn simulate_cycles(config: &Config, scenario: &Scenario) -> Result<SimulationSummary> {
+    let node = Arc::new(ScenarioNode::new(1, None));
+    let (provider, _) = FailoverClient::from_static(node.clone()).into_provider();
+    let mut safe_manager = SafeManager::new(config.safe.address().unwrap_or(Address::zero()), provider)?;
+    safe_manager.set_balance_thresholds(config.safe.min_balance(), config.safe.critical_balance())?;
+    let pools = StaticSource::default();
+    let mut defi_optimizer = DefiOptimizer::from_config(&config.acting_optimizer());
+    defi_optimizer.set_source(pools.clone());
+    // The router only has a simulated bridge; live mode lets its routes settle
+    let mut cross_chain_router = CrossChainRouter::from_config(&config.router);
+    cross_chain_router.set_mode(RunMode::Live);
+    let recent_actions = RecentActions::new(config.recent_action_retention());
+    cross_chain_router.set_recent_actions(recent_actions.clone());
+    let mut strategy = Strategy::acting_from_config(config, Arc::new(MemoryStore::new()));
+    strategy.set_recent_actions(recent_actions);
+    let status = StatusHandle::new();
+    let events = EventSender::disabled();
+    let policy = config.degradation_policy();
+
+    let mut summary = SimulationSummary {
+        scenario: scenario.name.clone(),
+        days: scenario.days,
+        cycles: 0,
+        decisions: BTreeMap::new(),
+        changes: Vec::new(),
+        routes: Vec::new(),
+        fees_eth: 0.0,
+        swap_costs_eth: 0.0,
+        earned_eth: 0.0,
+        final_chain: HOME_CHAIN.to_string(),
+        final_protocol: None,
+        timeline: Vec::new(),
+    };
+    let started = Instant::now();
+    let end = started + Duration::from_secs_f64(scenario.days * SECS_PER_DAY);
+    let interval = Duration::from_secs(scenario.interval_secs);
+    let mut tick = started;
+    let mut previous = None;
+    while tick < end {
+        tokio::time::sleep_until(tick).await;
+        let day = tick.duration_since(started).as_secs_f64() / SECS_PER_DAY;
+        let balance = scenario.balance_at(day);
+        let current = scenario.pools_at(day);
+        node.set(ethers::utils::parse_ether(balance).unwrap_or_default(), scenario.rpc_down_at(day));
+        pools.set(current.clone());
+
+        let result = monitor_and_optimize(
+            &safe_manager,
+            &defi_optimizer,
+            &cross_chain_router,
+            &strategy,
+            &status,
+            &events,
+            &policy,
+        )
+        .await;
+        summary.cycles += 1;
+        let (decision, detail) = match &result {
+            Ok(report) => {
+                let detail = match (&report.route, &report.best_pool) {
+                    (Some(route), _) => format!("route {} -> {}", route.source_chain, route.target_chain),
+                    (None, Some(best)) => format!("best pool {} on {}", best.pool.protocol, best.pool.chain),
+                    (None, None) => String::new(),
+                };
+                (Some(report.decision.clone()), detail)
+            }
+            Err(e) => (None, format!("{:#}", e)),
+        };
+        *summary.decisions.entry(decision_kind(decision.as_ref()).to_string()).or_default() += 1;
+        if let Ok(report) = &result {
+            if let Some(route) = &report.route {
+                let fee_eth = scenario.bridge_fee_at(day);
+                summary.fees_eth += fee_eth;
+                let swaps = report.best_pool.as_ref().is_some_and(|best| {
+                    let asset = scenario
+                        .pool_asset(&best.pool.protocol, &best.pool.chain)
+                        .unwrap_or(config.swap.asset_of(&best.pool.protocol));
+                    !asset.eq_ignore_ascii_case(&config.swap.held_asset)
+                });
+                let swap_cost_eth = if swaps { route.amount * scenario.swap_cost_at(day) / 100.0 } else { 0.0 };
+                summary.swap_costs_eth += swap_cost_eth;
+                summary.routes.push(SimulatedRoute {
+                    day,
+                    source_chain: route.source_chain.clone(),
+                    target_chain: route.target_chain.clone(),
+                    amount: route.amount,
+                    apy: report.best_pool.as_ref().and_then(|best| best.pool.apy),
+                    fee_eth,
+                    swap_cost_eth,
+                });
+            }
+        }
+        if previous.as_ref() != Some(&decision) {
+            summary.changes.push(DecisionChange { day, decision: decision.clone(), detail: detail.clone() });
+        }
+
+        // The position earns until the next cycle
+        let held = status.snapshot().portfolio.current_chain().to_string();
+        let apy = apy_on(&current, &held);
+        let next = (tick + interval).min(end);
+        let years = next.duration_since(tick).as_secs_f64() / SECS_PER_DAY / DAYS_PER_YEAR;
+        summary.earned_eth += balance * apy.unwrap_or(0.0) / 100.0 * years;
+        summary.timeline.push(CycleOutcome { day, decision: decision.clone(), detail, chain: held, apy });
+        previous = Some(decision);
+        tick = next;
+    }
+
+    let portfolio = status.snapshot().portfolio;
+    summary.final_chain = portfolio.current_chain().to_string();
+    summary.final_protocol = portfolio
+        .entries
+        .iter()
+        .find(|entry| entry.chain == summary.final_chain)
+        .and_then(|entry| entry.protocol.clone());
+    Ok(summary)
+}
+
+/// A scenario as the agent's runner sees it: one scenario step per cycle, starting
+/// over after the last day, with every pool's APY moved by up to [`APY_JITTER`]
+/// points. The moves depend on the seed and the cycle only, never on what ran before.
+#[derive(Debug, Clone)]
+pub struct SimulationModel {
+    scenario: Scenario,
+    seed: u64,
+}
+
+impl SimulationModel {
+    pub fn new(scenario: Scenario, seed: u64) -> Self {
+        Self { scenario, seed }
+    }
+
+    /// The bundled [`DEMO_SCENARIO`].
+    pub fn demo(seed: u64) -> Result<Self> {
+        let scenario = Scenario::parse(DEMO_SCENARIO).context("Invalid bundled demo scenario")?;
+        Ok(Self::new(scenario, seed))
+    }
+
+    pub fn scenario(&self) -> &Scenario {
+        &self.scenario
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Scenario day of `cycle`, counting cycles from 1.
+    pub fn day(&self, cycle: u64) -> f64 {
+        let step = self.scenario.interval_secs as f64 / SECS_PER_DAY;
+        let steps = (self.scenario.days / step).ceil().max(1.0) as u64;
+        (cycle.saturating_sub(1) % steps) as f64 * step
+    }
+
+    pub fn balance(&self, cycle: u64) -> U256 {
+        ethers::utils::parse_ether(self.scenario.balance_at(self.day(cycle))).unwrap_or_default()
+    }
+
+    /// The scenario's pools on `cycle`, APYs rounded to hundredths of a point.
+    pub fn pools(&self, cycle: u64) -> Vec<PoolData> {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(self.seed ^ cycle.wrapping_mul(0x9e37_79b9_7f4a_7c15));
+        let mut pools = self.scenario.pools_at(self.day(cycle));
+        for pool in &mut pools {
+            let jitter = rng.gen_range(-APY_JITTER..=APY_JITTER);
+            pool.apy = pool.apy.map(|apy| ((apy + jitter).max(0.0) * 100.0).round() / 100.0);
+        }
+        pools
+    }
+
+    pub fn rpc_down(&self, cycle: u64) -> bool {
+        self.scenario.rpc_down_at(self.day(cycle))
+    }
+}
+
+/// Everything the runner takes from outside the process, answered from a
+/// [`SimulationModel`] instead: the home chain's node, the pools, the USD prices and,
+/// through the node, the swap quotes. The runner advances it to each cycle before the
+/// cycle runs.
+pub struct SimulatedWorld {
+    model: SimulationModel,
+    node: Arc<ScenarioNode>,
+    pools: StaticSource,
+}
+
+impl SimulatedWorld {
+    /// Serves `model` as the node of chain `chain_id`.
+    pub fn new(model: SimulationModel, chain_id: u64) -> Self {
+        let gas_price = U256::from(SIMULATED_GAS_PRICE_GWEI) * U256::exp10(9);
+        let node = Arc::new(ScenarioNode::new(chain_id, Some(gas_price)));
+        let world = Self { model, node, pools: StaticSource::default() };
+        world.advance(1);
+        world
+    }
+
+    /// The bundled demo scenario on the configured home chain, seeded with
+    /// `runtime.simulation_seed`; `None` unless the simulation is on.
+    pub fn from_config(config: &Config) -> Result<Option<Self>> {
+        if config.runtime.simulation == SimulationMode::Off {
+            return Ok(None);
+        }
+        let chain_id = config.router.chains.iter().find(|c| c.name == HOME_CHAIN).map_or(1, |c| c.chain_id);
+        Ok(Some(Self::new(SimulationModel::demo(config.runtime.simulation_seed)?, chain_id)))
+    }
+
+    pub fn model(&self) -> &SimulationModel {
+        &self.model
+    }
+
+    /// Unix time the simulated clock starts at.
+    pub fn start(&self) -> u64 {
+        self.model.scenario.start
+    }
+
+    pub fn provider(&self) -> (RpcProvider, Arc<FailoverClient>) {
+        FailoverClient::from_static(self.node.clone()).into_provider()
+    }
+
+    pub fn pools(&self) -> StaticSource {
+        self.pools.clone()
+    }
+
+    pub fn prices(&self) -> Arc<dyn PriceSource> {
+        Arc::new(FixedSource::new(self.model.scenario.prices.clone()))
+    }
+
+    /// Serves what the model holds for `cycle`.
+    pub fn advance(&self, cycle: u64) {
+        self.node.set(self.model.balance(cycle), self.model.rpc_down(cycle));
+        self.pools.set(self.model.pools(cycle));
+    }
+}
+
+/// USDC on Ethereum mainnet, approved by [`check_fork`] on mainnet forks.
+const MAINNET_USDC: &str = "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48";
+/// Uniswap V3's swap router, the spender of that approval.
+const MAINNET_SPENDER: &str = "0xE592427A0AEce92De3Edee1F18E0157C05861564";
+
+/// Outcome of [`check_fork`].
+#[derive(Debug, Clone, Serialize)]
+pub struct ForkReport {
+    pub chain_id: u64,
+    pub block: u64,
+    pub safe: Address,
+    /// Gas estimated for a 1 ETH transfer out of the Safe.
+    pub transfer_gas: U256,
+    /// Gas estimated for the USDC approval; unset off mainnet.
+    pub approve_gas: Option<U256>,
+    pub approve_tx: Option<H256>,
+    /// Allowance read back from the token after the approval.
+    pub allowance: Option<U256>,
+}
+
+/// Runs the Safe's transaction paths against real bytecode on `fork`.
+///
+/// The Safe is funded with 10 ETH and impersonated. A transfer out of it is simulated
+/// and prepared through a live [`SafeManager`], which has no owner key to send it
+/// with; on a mainnet fork a USDC approval is
+/// then estimated by the manager, sent from the Safe and checked against the token's
+/// allowance.
+pub async fn check_fork(fork: &AnvilFork, safe: Address) -> Result<ForkReport> {
+    let provider = fork.provider();
+    let chain_id = provider.get_chainid().await.context("Failed to read the fork's chain id")?.low_u64();
+    let block = provider.get_block_number().await.context("Failed to read the fork's block")?.as_u64();
+    fork.set_balance(safe, U256::exp10(19)).await?;
+    fork.impersonate(safe).await?;
+
+    let mut manager = SafeManager::new(safe, provider.clone())?;
+    manager.set_execution(SafeExecution::Simulate)?;
+    let transfer = SafeTransaction {
+        to: Address::from_low_u64_be(0xa5a),
+        value: U256::exp10(18),
+        data: Vec::new(),
+        operation: 0,
+        safe_tx_gas: U256::zero(),
+        nonce: None,
+    };
+    let transfer_gas = manager.simulate_transaction(&transfer).await?;
+    manager.execute_transaction(transfer).await?;
+    let mut report = ForkReport {
+        chain_id,
+        block,
+        safe,
+        transfer_gas,
+        approve_gas: None,
+        approve_tx: None,
+        allowance: None,
+    };
+    if chain_id != 1 {
+        fork.stop_impersonating(safe).await?;
+        return Ok(report);
+    }
+
+    let token: Address = MAINNET_USDC.parse()?;
+    let spender: Address = MAINNET_SPENDER.parse()?;
+    let amount = U256::from(1_000_000_u64);
+    let mut data = ethers::utils::id("approve(address,uint256)").to_vec();
+    data.extend(ethers::abi::encode(&[Token::Address(spender), Token::Uint(amount)]));
+    let approve = SafeTransaction {
+        to: token,
+        value: U256::zero(),
+        data: data.clone(),
+        operation: 0,
+        safe_tx_gas: U256::zero(),
+        nonce: None,
+    };
+    report.approve_gas = Some(manager.simulate_transaction(&approve).await?);
+    let receipt = fork.send_as(safe, TransactionRequest::new().to(token).data(data)).await?;
+    if receipt.status != Some(1.into()) {
+        bail!("approval {:?} reverted", receipt.transaction_hash);
+    }
+    report.approve_tx = Some(receipt.transaction_hash);
+
+    let mut call = ethers::utils::id("allowance(address,address)").to_vec();
+    call.extend(ethers::abi::encode(&[Token::Address(safe), Token::Address(spender)]));
+    let call = TypedTransaction::Legacy(TransactionRequest::new().to(token).data(call));
+    let allowance = U256::from_big_endian(&provider.call(&call, None).await.context("Failed to read the allowance")?);
+    if allowance != amount {
+        bail!("allowance is {} after approving {}", allowance, amount);
+    }
+    report.allowance = Some(allowance);
+    fork.stop_impersonating(safe).await?;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The Safe v1.3.0 singleton, a Safe contract present on mainnet and its forks.
+    const FORK_SAFE: &str = "0xd9Db270c1B5E3Bd161E8c8503c55cEABeE709552";
+
+    fn scenario(name: &str) -> Scenario {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/scenarios").join(name);
+        Scenario::load(&path).unwrap()
+    }
+
+    #[test]
+    fn test_scenario_levels() {
+        let scenario = scenario("apy_shift.toml");
+        assert_eq!(scenario.interval_secs, 21600);
+        assert_eq!(scenario.balance_at(0.0), 2.0);
+        let pools = scenario.pools_at(4.9);
+        assert_eq!(apy_on(&pools, "Arbitrum"), Some(3.0));
+        assert_eq!(apy_on(&scenario.pools_at(5.0), "Arbitrum"), Some(7.5));
+        assert_eq!(scenario.pools_at(5.0).len(), pools.len());
+        assert_eq!(scenario.pool_asset("Aave", "Arbitrum"), Some("USDC"));
+        assert_eq!(scenario.pool_asset("Aave", "Ethereum"), None);
+        assert_eq!(scenario.swap_cost_at(3.0), 0.2);
+
+        let err = Scenario::parse("name = \"x\"\ndays = 0\n").unwrap_err();
+        assert!(err.to_string().contains("days"), "{}", err);
+        assert!(Scenario::parse("name = \"x\"\ndays = 1\nsurprise = 1\n").is_err());
+    }
+
+    fn routes(summary: &SimulationSummary) -> Vec<(f64, &str)> {
+        summary.routes.iter().map(|route| (route.day, route.target_chain.as_str())).collect()
+    }
+
+    #[test]
+    fn test_apy_shift_routes_once() {
+        let summary = simulate(&Config::default(), &scenario("apy_shift.toml")).unwrap();
+        assert_eq!(summary.cycles, 40);
+        assert_eq!(routes(&summary), [(5.0, "Arbitrum")]);
+        assert_eq!(summary.decisions, BTreeMap::from([("no_action".to_string(), 39), ("route_initiated".to_string(), 1)]));
+        // 90% of the balance minus the reserve, at the fee of day 5
+        assert_eq!((summary.routes[0].amount, summary.routes[0].fee_eth), (1.791, 0.002));
+        assert_eq!(summary.fees_eth, 0.002);
+        // Arbitrum's pool holds USDC: 0.2% of the amount to swap in and out
+        assert!((summary.routes[0].swap_cost_eth - 0.003582).abs() < 1e-12);
+        assert_eq!(summary.swap_costs_eth, summary.routes[0].swap_cost_eth);
+        assert_eq!((summary.final_chain.as_str(), summary.final_protocol.as_deref()), ("Arbitrum", Some("Aave")));
+        // 2 ETH at 4% for 5 days, then at 7.5% for 5 days
+        let expected = 2.0 * (4.0 + 7.5) / 100.0 * 5.0 / DAYS_PER_YEAR;
+        assert!((summary.earned_eth - expected).abs() < 1e-9, "{}", summary.earned_eth);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_whipsaw_waits_out_cooldown_and_outage() {
+        let summary = run(&Config::default(), &scenario("whipsaw_outage.toml")).await.unwrap();
+        assert_eq!(summary.cycles, 16);
+        // Back to Arbitrum only once the cooldown of the first route has passed
+        assert_eq!(routes(&summary), [(0.0, "Arbitrum"), (0.5, "Ethereum"), (1.25, "Arbitrum")]);
+        assert_eq!(summary.decisions["blocked"], 2);
+        assert!(summary.changes.iter().any(|change| {
+            change.day == 0.75
+                && matches!(&change.decision, Some(CycleDecision::Blocked(reason)) if reason.contains("cooldown"))
+        }));
+        // Both cycles inside the outage fail, the next one recovers
+        assert_eq!(summary.decisions["failed"], 2);
+        let failed = summary.changes.iter().position(|change| change.decision.is_none()).unwrap();
+        assert_eq!(summary.changes[failed].day, 2.0);
+        assert_eq!(summary.changes[failed + 1].day, 2.5);
+        assert!((summary.fees_eth - 0.009).abs() < 1e-12);
+        assert_eq!(summary.final_chain, "Arbitrum");
+    }
+
+    #[test]
+    fn test_demo_model_is_seeded_and_wraps() {
+        let model = SimulationModel::demo(42).unwrap();
+        // Four cycles a day for two weeks, then from the start again
+        assert_eq!(model.day(1), 0.0);
+        assert_eq!(model.day(17), 4.0);
+        assert_eq!(model.day(57), 0.0);
+        assert_eq!(model.balance(13), U256::exp10(18) * 25 / 2);
+
+        let pools = model.pools(17);
+        let scripted = model.scenario().pools_at(4.0);
+        for (pool, scripted) in pools.iter().zip(&scripted) {
+            assert!((pool.apy.unwrap() - scripted.apy.unwrap()).abs() <= APY_JITTER + 0.005, "{:?}", pool);
+        }
+        assert_eq!(serde_json::to_string(&pools).unwrap(), serde_json::to_string(&model.pools(17)).unwrap());
+        let reseeded = SimulationModel::demo(7).unwrap().pools(17);
+        assert_ne!(serde_json::to_string(&pools).unwrap(), serde_json::to_string(&reseeded).unwrap());
+    }
+
+    #[tokio::test]
+    #[ignore = "needs anvil and ASAM_FORK_URL"]
+    async fn fork_safe_transfer_and_approval() {
+        let Some(fork) = AnvilFork::from_env().await.unwrap() else {
+            return;
+        };
+        let safe: Address = FORK_SAFE.parse().unwrap();
+        let report = check_fork(&fork, safe).await.unwrap();
+        assert!(report.transfer_gas >= U256::from(21_000), "{:?}", report);
+        assert_eq!(fork.provider().get_balance(safe, None).await.unwrap(), U256::exp10(19));
+        if report.chain_id == 1 {
+            assert!(report.approve_gas.unwrap() > U256::from(21_000));
+            assert!(report.approve_tx.is_some());
+            assert_eq!(report.allowance, Some(U256::from(1_000_000_u64)));
+        }
+    }
+}
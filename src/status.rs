@@ -0,0 +1,1017 @@
+//! Runtime status shared between the monitoring loop and the optional HTTP
+//! server exposing `/healthz` and `/status`.
+//!
+//! The loop records what it observed into a [`StatusHandle`]; the handlers only
+//! read that snapshot and the [`RouteTracker`], they never query the chain.
+//!
+//! With `http.admin_token` set, the `/admin` endpoints take requests carrying it as
+//! a bearer token: pausing and resuming through the [`KillSwitch`], enabling and
+//! disabling chains, running a cycle now, listing and cancelling routes, and showing
+//! the redacted config. Each request is recorded in the [`AuditLog`].
+
+use actix_web::{web, App, HttpRequest, HttpResponse, HttpServer};
+use anyhow::{Context, Result};
+use ethers::core::types::{Address, U256};
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, watch};
+use tokio_util::sync::CancellationToken;
+
+use crate::agents::cross_chain_router::{RouteReceipt, RouteTracker};
+use crate::agents::defi_optimizer::PoolData;
+use crate::secret::Secret;
+use crate::util::unix_now;
+use crate::audit::AuditLog;
+use crate::budget::CostBenefitGuard;
+use crate::comparison::{StrategyComparison, StrategyPerformance};
+use crate::config::{ModePlan, Profile};
+use crate::degradation::Dependencies;
+use crate::errors::ErrorCategory;
+use crate::kill_switch::{KillSwitch, PauseSource};
+use crate::logging::Redactor;
+use crate::notifier::DeliveryFailures;
+use crate::portfolio::PortfolioState;
+use crate::reload::{ConfigUpdate, ConfigVersion};
+use crate::report::{ChainBalance, CycleReport, MultiChainBalances, ScoredPool};
+use crate::rpc::{FailoverClient, RpcHealth};
+use crate::scheduler::CycleTrigger;
+use crate::storage::{CycleRecord, Store};
+use crate::supervisor::TaskStatus;
+use crate::valuation::PortfolioValuation;
+use crate::version::BuildInfo;
+use crate::yield_tracker::{YieldReport, YieldSummary, YieldTracker};
+
+/// Number of stored cycles included in `/status`.
+const RECENT_CYCLES: usize = 5;
+/// Accepted besides `Authorization: Bearer`, for clients of the first admin endpoints.
+const ADMIN_TOKEN_HEADER: &str = "X-Admin-Token";
+
+/// Outcome of the most recent balance threshold evaluation.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BalanceStatus {
+    /// No balance has been read yet.
+    #[default]
+    Unknown,
+    Healthy,
+    /// Below the minimum threshold.
+    Low,
+    /// Below the critical threshold.
+    Critical,
+}
+
+impl BalanceStatus {
+    /// Evaluates `balance` against `min_balance`, critical at half of it as on watched
+    /// chains; [`SafeManager::balance_status`](crate::agents::safe_manager::SafeManager::balance_status)
+    /// applies the home chain's own critical threshold.
+    pub fn evaluate(balance: U256, min_balance: U256) -> Self {
+        if balance <= min_balance / 2 {
+            BalanceStatus::Critical
+        } else if balance < min_balance {
+            BalanceStatus::Low
+        } else {
+            BalanceStatus::Healthy
+        }
+    }
+}
+
+/// What the monitoring loop last observed.
+#[derive(Debug, Clone, Default)]
+pub struct StatusSnapshot {
+    /// Number of the running or last batch since startup.
+    pub cycle: u64,
+    /// Unix time the last monitoring cycle finished.
+    pub last_cycle_at: Option<u64>,
+    pub rpc_reachable: bool,
+    pub balance: Option<U256>,
+    pub balance_status: BalanceStatus,
+    /// Balance on every monitored chain from the last balance check.
+    pub balances: MultiChainBalances,
+    /// Balance of every other monitored account on the home chain.
+    pub accounts: BTreeMap<Address, ChainBalance>,
+    pub last_pool: Option<ScoredPool>,
+    /// Where the funds currently live.
+    pub portfolio: PortfolioState,
+    /// What the last pool phase did, e.g. "hold" or "routed to Arbitrum".
+    pub last_action: Option<String>,
+    /// Outcome of the last full cycle.
+    pub last_report: Option<CycleReport>,
+    /// Phases of the running cycle that started and have not finished, outermost first.
+    pub active_phases: Vec<&'static str>,
+    /// When the running cycle will be abandoned.
+    pub cycle_deadline: Option<tokio::time::Instant>,
+    /// Consecutive failed cycles; reset by the next success.
+    pub failure_streak: u32,
+    /// Unix time of the next attempt while backing off after failures.
+    pub next_retry_at: Option<u64>,
+    /// Component name to breaker state, for components that trip on repeated failures.
+    pub circuit_breakers: BTreeMap<String, String>,
+    /// Generation of the config the loop runs with.
+    pub config_version: ConfigVersion,
+    /// Failed cycles since startup by error category.
+    pub error_counts: BTreeMap<ErrorCategory, u64>,
+    /// Holding periods and the flows since the last yield snapshot.
+    pub yields: YieldTracker,
+    /// Supervised background tasks and the monitoring cycle, by name.
+    pub tasks: BTreeMap<String, TaskStatus>,
+    /// Paper positions of the configured strategies since the last daily summary.
+    pub strategies: StrategyComparison,
+    /// Whether routes are held back because fees outweigh the yield.
+    pub budget: CostBenefitGuard,
+    /// USD value of the portfolio from the last full cycle; unset without `[prices]`.
+    pub valuation: Option<PortfolioValuation>,
+    /// Assets left out of valuations since startup for want of a price, counted per cycle.
+    pub unpriced_assets: u64,
+    /// Recent attempts on each external dependency and the fallback in effect.
+    pub dependencies: Dependencies,
+    /// Every pool of the last successful fetch and its Unix time, for the pool API
+    /// fallbacks.
+    pub last_pools: Option<(u64, Arc<[PoolData]>)>,
+}
+
+/// Cloneable handle the loop writes to and the HTTP handlers read from.
+#[derive(Debug, Clone)]
+pub struct StatusHandle {
+    inner: Arc<RwLock<StatusSnapshot>>,
+    started_at: Instant,
+}
+
+impl Default for StatusHandle {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StatusHandle {
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::default(),
+            started_at: Instant::now(),
+        }
+    }
+
+    pub fn update<F: FnOnce(&mut StatusSnapshot)>(&self, f: F) {
+        let mut snapshot = self.inner.write().unwrap_or_else(|e| e.into_inner());
+        f(&mut snapshot);
+    }
+
+    pub fn snapshot(&self) -> StatusSnapshot {
+        self.inner.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    pub fn uptime(&self) -> Duration {
+        self.started_at.elapsed()
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[non_exhaustive]
+pub struct HealthReport {
+    pub healthy: bool,
+    /// Names of the checks that failed.
+    pub failed: Vec<String>,
+    pub last_cycle_at: Option<u64>,
+}
+
+/// Healthy when the last cycle finished within `max_cycle_age` of `now`, the RPC was reachable
+/// and no supervised task was given up on.
+pub fn check_health(snapshot: &StatusSnapshot, now: u64, max_cycle_age: Duration) -> HealthReport {
+    let mut failed = Vec::new();
+    match snapshot.last_cycle_at {
+        None => failed.push("last_cycle: no cycle completed yet".to_string()),
+        Some(at) if now.saturating_sub(at) > max_cycle_age.as_secs() => failed.push(format!(
+            "last_cycle: completed {}s ago (limit {}s)",
+            now.saturating_sub(at),
+            max_cycle_age.as_secs()
+        )),
+        Some(_) => {}
+    }
+    if !snapshot.rpc_reachable {
+        failed.push("rpc: endpoint unreachable".to_string());
+    }
+    for (name, task) in snapshot.tasks.iter().filter(|(_, task)| task.down) {
+        failed.push(format!(
+            "task {}: down after {} restarts (last panic: {})",
+            name,
+            task.restarts,
+            task.last_panic.as_deref().unwrap_or("unknown")
+        ));
+    }
+
+    HealthReport {
+        healthy: failed.is_empty(),
+        failed,
+        last_cycle_at: snapshot.last_cycle_at,
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[non_exhaustive]
+pub struct StatusReport {
+    /// Version, commit, build time and features.
+    #[serde(flatten)]
+    pub build: BuildInfo,
+    pub mode: ModePlan,
+    pub profile: Profile,
+    /// Chain id the RPC endpoints serve; unset until one has answered.
+    pub chain_id: Option<u64>,
+    /// Generation of the running config; bumped by every applied reload.
+    pub config_version: ConfigVersion,
+    pub uptime_secs: u64,
+    pub last_cycle_at: Option<u64>,
+    pub rpc_reachable: bool,
+    /// Balance in wei as a decimal string.
+    pub balance_wei: Option<String>,
+    pub balance_eth: Option<String>,
+    pub balance_status: BalanceStatus,
+    /// Balance and threshold status on every monitored chain.
+    pub balances: MultiChainBalances,
+    /// Balance and threshold status of every other monitored account.
+    pub accounts: BTreeMap<Address, ChainBalance>,
+    pub last_pool: Option<ScoredPool>,
+    /// Chain holding most of the funds.
+    pub position: String,
+    pub portfolio: PortfolioState,
+    pub last_report: Option<CycleReport>,
+    /// What keeps the kill switch on; unset while funds may move.
+    pub paused: Option<PauseSource>,
+    pub failure_streak: u32,
+    pub next_retry_at: Option<u64>,
+    /// Per-endpoint error rates and latency, and which endpoint served the last request.
+    pub rpc: Option<RpcHealth>,
+    pub in_flight_routes: Vec<RouteReceipt>,
+    pub circuit_breakers: BTreeMap<String, String>,
+    /// Failed cycles since startup by error category.
+    pub error_counts: BTreeMap<ErrorCategory, u64>,
+    /// Alerts each notification channel gave up on after its retries.
+    pub notification_failures: BTreeMap<&'static str, u64>,
+    /// Most recent cycles from storage, newest first.
+    pub recent_cycles: Vec<CycleRecord>,
+    /// Routes started in the last 24 hours.
+    pub routes_24h: usize,
+    /// Transaction fees paid in the last 24 hours, in wei.
+    pub fees_24h_wei: String,
+    /// Realized against projected APY over the tracked holding periods.
+    pub yield_summary: YieldSummary,
+    pub yields: Vec<YieldReport>,
+    pub tasks: BTreeMap<String, TaskStatus>,
+    /// Hypothetical performance of each configured strategy today, best first.
+    pub strategies: Vec<StrategyPerformance>,
+    /// Fees against yield over the last 30 days and whether routes are held back.
+    pub budget: CostBenefitGuard,
+    /// USD value of the portfolio by chain and asset from the last full cycle.
+    pub valuation: Option<PortfolioValuation>,
+    /// Assets left out of valuations since startup for want of a price.
+    pub unpriced_assets: u64,
+    /// Health of each external dependency over its recent attempts.
+    pub dependencies: Dependencies,
+}
+
+/// State handed to the HTTP handlers.
+#[derive(Clone)]
+pub struct StatusState {
+    pub status: StatusHandle,
+    pub tracker: RouteTracker,
+    pub store: Arc<dyn Store>,
+    /// Oldest acceptable last cycle before `/healthz` reports unhealthy.
+    pub max_cycle_age: Duration,
+    pub mode: ModePlan,
+    pub profile: Profile,
+    /// RPC endpoint health; unset when the agent talks to a single fixed provider.
+    pub rpc: Option<Arc<FailoverClient>>,
+    pub kill_switch: KillSwitch,
+    /// Bearer token for the `/admin` endpoints, which are disabled without it.
+    pub admin_token: Option<Secret<String>>,
+    pub audit: AuditLog,
+    /// Makes the loop run every phase now, like its timer does.
+    pub cycle_trigger: Option<mpsc::Sender<CycleTrigger>>,
+    /// Changes the loop applies before its next batch.
+    pub commands: Option<mpsc::UnboundedSender<AdminCommand>>,
+    /// The effective config, for `/admin/config` and the chain names.
+    pub config: Option<watch::Receiver<ConfigUpdate>>,
+    pub delivery_failures: DeliveryFailures,
+}
+
+/// Requests from `/admin` that only the monitoring loop can carry out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AdminCommand {
+    SetChainActive { chain: String, active: bool },
+}
+
+impl StatusState {
+    pub fn health(&self) -> HealthReport {
+        check_health(&self.status.snapshot(), unix_now(), self.max_cycle_age)
+    }
+
+    pub async fn report(&self) -> StatusReport {
+        let snapshot = self.status.snapshot();
+        let recent_cycles = self.store.recent_cycles(RECENT_CYCLES).await.unwrap_or_else(|e| {
+            error!("Failed to load recent cycles: {:#}", e);
+            Vec::new()
+        });
+        let now = unix_now();
+        let day_ago = now.saturating_sub(24 * 60 * 60);
+        let routes_24h = self.store.routes_between(day_ago, now).await.map(|r| r.len()).unwrap_or_else(|e| {
+            error!("Failed to load recent routes: {:#}", e);
+            0
+        });
+        let fees_24h = self.store.total_fees_since(day_ago).await.unwrap_or_else(|e| {
+            error!("Failed to load recent fees: {:#}", e);
+            U256::zero()
+        });
+        StatusReport {
+            build: BuildInfo::current(),
+            mode: self.mode,
+            profile: self.profile,
+            chain_id: self.rpc.as_ref().and_then(|rpc| rpc.chain_id()).map(|id| id.low_u64()),
+            config_version: snapshot.config_version,
+            uptime_secs: self.status.uptime().as_secs(),
+            last_cycle_at: snapshot.last_cycle_at,
+            rpc_reachable: snapshot.rpc_reachable,
+            balance_wei: snapshot.balance.map(|b| b.to_string()),
+            balance_eth: snapshot.balance.map(ethers::utils::format_ether),
+            balance_status: snapshot.balance_status,
+            balances: snapshot.balances,
+            accounts: snapshot.accounts,
+            last_pool: snapshot.last_pool,
+            position: snapshot.portfolio.current_chain().to_string(),
+            portfolio: snapshot.portfolio,
+            last_report: snapshot.last_report,
+            paused: self.kill_switch.source(),
+            failure_streak: snapshot.failure_streak,
+            next_retry_at: snapshot.next_retry_at,
+            rpc: self.rpc.as_ref().map(|rpc| rpc.health()),
+            in_flight_routes: self.tracker.in_flight(),
+            circuit_breakers: snapshot.circuit_breakers,
+            error_counts: snapshot.error_counts,
+            notification_failures: self.delivery_failures.counts(),
+            recent_cycles,
+            routes_24h,
+            fees_24h_wei: fees_24h.to_string(),
+            yield_summary: snapshot.yields.summary(),
+            yields: snapshot.yields.reports(),
+            tasks: snapshot.tasks,
+            strategies: snapshot.strategies.standings(),
+            budget: snapshot.budget,
+            valuation: snapshot.valuation,
+            unpriced_assets: snapshot.unpriced_assets,
+            dependencies: snapshot.dependencies,
+        }
+    }
+}
+
+async fn healthz(state: web::Data<StatusState>) -> HttpResponse {
+    let report = state.health();
+    if report.healthy {
+        HttpResponse::Ok().json(report)
+    } else {
+        HttpResponse::ServiceUnavailable().json(report)
+    }
+}
+
+async fn status(state: web::Data<StatusState>) -> HttpResponse {
+    HttpResponse::Ok().json(state.report().await)
+}
+
+fn caller(req: &HttpRequest) -> String {
+    req.peer_addr().map(|addr| addr.to_string()).unwrap_or_else(|| "unknown".to_string())
+}
+
+fn action(req: &HttpRequest) -> String {
+    format!("{} {}", req.method(), req.path())
+}
+
+/// Compares in time independent of where the inputs first differ.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// Checks the admin token and returns the caller, or the response to send instead.
+/// Rejected requests are audited too.
+fn authorize(state: &StatusState, req: &HttpRequest) -> std::result::Result<String, HttpResponse> {
+    let Some(expected) = &state.admin_token else {
+        return Err(HttpResponse::NotFound().finish());
+    };
+    let headers = req.headers();
+    let given = headers
+        .get(actix_web::http::header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .or_else(|| headers.get(ADMIN_TOKEN_HEADER).and_then(|v| v.to_str().ok()));
+    let caller = caller(req);
+    if !given.is_some_and(|given| constant_time_eq(given.as_bytes(), expected.expose().as_bytes())) {
+        warn!("Rejected {} request from {} without a valid admin token", req.path(), caller);
+        state.audit.record(&caller, &action(req), "unauthorized");
+        return Err(HttpResponse::Unauthorized().finish());
+    }
+    Ok(caller)
+}
+
+/// Audits the outcome of an authorized request and passes its response through.
+fn audited(state: &StatusState, req: &HttpRequest, caller: &str, outcome: &str, response: HttpResponse) -> HttpResponse {
+    state.audit.record(caller, &action(req), outcome);
+    response
+}
+
+fn error_body(message: impl Into<String>) -> serde_json::Value {
+    serde_json::json!({ "error": message.into() })
+}
+
+/// Flips the kill switch.
+fn admin(state: &StatusState, req: &HttpRequest, pause: bool) -> HttpResponse {
+    let caller = match authorize(state, req) {
+        Ok(caller) => caller,
+        Err(response) => return response,
+    };
+    let result = if pause { state.kill_switch.pause() } else { state.kill_switch.resume() };
+    match result {
+        Ok(()) => audited(
+            state,
+            req,
+            &caller,
+            "ok",
+            HttpResponse::Ok().json(serde_json::json!({ "paused": state.kill_switch.source() })),
+        ),
+        Err(e) => {
+            let message = format!("{:#}", e);
+            audited(state, req, &caller, &message, HttpResponse::Conflict().json(error_body(message.clone())))
+        }
+    }
+}
+
+async fn pause(state: web::Data<StatusState>, req: HttpRequest) -> HttpResponse {
+    admin(&state, &req, true)
+}
+
+async fn resume(state: web::Data<StatusState>, req: HttpRequest) -> HttpResponse {
+    admin(&state, &req, false)
+}
+
+async fn set_chain(
+    state: web::Data<StatusState>,
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+) -> HttpResponse {
+    let caller = match authorize(&state, &req) {
+        Ok(caller) => caller,
+        Err(response) => return response,
+    };
+    let (chain, toggle) = path.into_inner();
+    let active = match toggle.as_str() {
+        "enable" => true,
+        "disable" => false,
+        _ => return audited(&state, &req, &caller, "unknown action", HttpResponse::NotFound().finish()),
+    };
+    let known = state
+        .config
+        .as_ref()
+        .is_none_or(|config| config.borrow().config.router.chains.iter().any(|c| c.name == chain));
+    if !known {
+        let message = format!("unknown chain '{}'", chain);
+        return audited(&state, &req, &caller, &message, HttpResponse::NotFound().json(error_body(message.clone())));
+    }
+    let Some(commands) = &state.commands else {
+        return audited(&state, &req, &caller, "loop not running", HttpResponse::ServiceUnavailable().finish());
+    };
+    if commands.send(AdminCommand::SetChainActive { chain: chain.clone(), active }).is_err() {
+        return audited(&state, &req, &caller, "loop stopped", HttpResponse::ServiceUnavailable().finish());
+    }
+    // Routes to a re-enabled chain should not wait for the next interval
+    if let (true, Some(trigger)) = (active, &state.cycle_trigger) {
+        let _ = trigger.try_send(CycleTrigger::ChainEnabled(chain.clone()));
+    }
+    audited(
+        &state,
+        &req,
+        &caller,
+        "ok",
+        HttpResponse::Accepted().json(serde_json::json!({ "chain": chain, "active": active })),
+    )
+}
+
+async fn run_cycle(state: web::Data<StatusState>, req: HttpRequest) -> HttpResponse {
+    let caller = match authorize(&state, &req) {
+        Ok(caller) => caller,
+        Err(response) => return response,
+    };
+    let Some(trigger) = &state.cycle_trigger else {
+        return audited(&state, &req, &caller, "loop not running", HttpResponse::ServiceUnavailable().finish());
+    };
+    match trigger.try_send(CycleTrigger::Admin) {
+        // A full queue means a run is already requested
+        Ok(()) | Err(mpsc::error::TrySendError::Full(_)) => audited(
+            &state,
+            &req,
+            &caller,
+            "ok",
+            HttpResponse::Accepted().json(serde_json::json!({ "triggered": true })),
+        ),
+        Err(mpsc::error::TrySendError::Closed(_)) => {
+            audited(&state, &req, &caller, "loop stopped", HttpResponse::ServiceUnavailable().finish())
+        }
+    }
+}
+
+/// Lets routes through while the budget guard is engaged, until the ratio recovers.
+async fn override_budget(state: web::Data<StatusState>, req: HttpRequest) -> HttpResponse {
+    let caller = match authorize(&state, &req) {
+        Ok(caller) => caller,
+        Err(response) => return response,
+    };
+    let mut overridden = false;
+    state.status.update(|s| overridden = s.budget.override_guard());
+    if !overridden {
+        let message = "budget guard is not engaged".to_string();
+        return audited(&state, &req, &caller, &message, HttpResponse::Conflict().json(error_body(message.clone())));
+    }
+    warn!("Budget guard overridden by {}", caller);
+    audited(&state, &req, &caller, "ok", HttpResponse::Ok().json(state.status.snapshot().budget))
+}
+
+async fn list_routes(state: web::Data<StatusState>, req: HttpRequest) -> HttpResponse {
+    let caller = match authorize(&state, &req) {
+        Ok(caller) => caller,
+        Err(response) => return response,
+    };
+    let mut routes = state.tracker.all();
+    routes.sort_by_key(|route| std::cmp::Reverse(route.updated_at));
+    audited(&state, &req, &caller, "ok", HttpResponse::Ok().json(routes))
+}
+
+/// Stops tracking a route as failed. Funds already sent to a bridge are not recalled.
+async fn cancel_route(state: web::Data<StatusState>, req: HttpRequest, route_id: web::Path<String>) -> HttpResponse {
+    let caller = match authorize(&state, &req) {
+        Ok(caller) => caller,
+        Err(response) => return response,
+    };
+    let Some(receipt) = state.tracker.fail(&route_id, "cancelled by operator") else {
+        let (response, outcome) = match state.tracker.get(&route_id) {
+            Some(route) => {
+                let message = format!("route {} already {:?}", route_id, route.state);
+                (HttpResponse::Conflict().json(error_body(message.clone())), message)
+            }
+            None => (HttpResponse::NotFound().finish(), format!("unknown route {}", route_id)),
+        };
+        return audited(&state, &req, &caller, &outcome, response);
+    };
+    state.status.update(|s| {
+        s.portfolio.route_failed(&receipt, unix_now());
+    });
+    if let Err(e) = state.store.upsert_route(&receipt, receipt.updated_at).await {
+        warn!("Failed to persist cancelled route {}: {:#}", receipt.route_id, e);
+    }
+    audited(&state, &req, &caller, "ok", HttpResponse::Ok().json(receipt))
+}
+
+/// The running config with every secret replaced by `[REDACTED]`.
+async fn show_config(state: web::Data<StatusState>, req: HttpRequest) -> HttpResponse {
+    let caller = match authorize(&state, &req) {
+        Ok(caller) => caller,
+        Err(response) => return response,
+    };
+    let Some(config) = &state.config else {
+        return audited(&state, &req, &caller, "no config", HttpResponse::ServiceUnavailable().finish());
+    };
+    let update = config.borrow().clone();
+    let redactor = Redactor::new(update.config.secrets());
+    let body = serde_json::to_string(&*update.config)
+        .ok()
+        .and_then(|json| serde_json::from_str::<serde_json::Value>(&redactor.redact(&json)).ok());
+    match body {
+        Some(body) => audited(
+            &state,
+            &req,
+            &caller,
+            "ok",
+            HttpResponse::Ok().json(serde_json::json!({ "version": update.version, "config": body })),
+        ),
+        None => audited(&state, &req, &caller, "serialization failed", HttpResponse::InternalServerError().finish()),
+    }
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.route("/healthz", web::get().to(healthz))
+        .route("/status", web::get().to(status))
+        .route("/admin/pause", web::post().to(pause))
+        .route("/admin/resume", web::post().to(resume))
+        .route("/admin/chains/{name}/{action}", web::post().to(set_chain))
+        .route("/admin/cycle", web::post().to(run_cycle))
+        .route("/admin/budget/override", web::post().to(override_budget))
+        .route("/admin/routes", web::get().to(list_routes))
+        .route("/admin/routes/{id}/cancel", web::post().to(cancel_route))
+        .route("/admin/config", web::get().to(show_config));
+}
+
+/// Serves `/healthz`, `/status` and the `/admin` endpoints on `bind` until `shutdown` fires.
+pub async fn serve(bind: SocketAddr, state: StatusState, shutdown: CancellationToken) -> Result<()> {
+    let data = web::Data::new(state);
+    let server = HttpServer::new(move || App::new().app_data(data.clone()).configure(configure))
+        .workers(1)
+        .disable_signals()
+        .bind(bind)
+        .with_context(|| format!("Failed to bind status server to {}", bind))?
+        .run();
+
+    info!("Status server listening on http://{}", bind);
+    let handle = server.handle();
+    tokio::spawn(async move {
+        shutdown.cancelled().await;
+        handle.stop(true).await;
+    });
+
+    server.await.map_err(|e| {
+        error!("Status server failed: {}", e);
+        e.into()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::StatusCode;
+    use actix_web::test as actix_test;
+    use crate::agents::cross_chain_router::{BridgeProvider, RouteState};
+    use crate::agents::defi_optimizer::PoolData;
+    use crate::config::{Config, RunMode};
+    use crate::reload::ConfigVersion;
+    use crate::storage::MemoryStore;
+
+    fn healthy_snapshot() -> StatusSnapshot {
+        StatusSnapshot {
+            last_cycle_at: Some(unix_now()),
+            rpc_reachable: true,
+            balance: Some(U256::exp10(18)),
+            balance_status: BalanceStatus::Healthy,
+            ..StatusSnapshot::default()
+        }
+    }
+
+    fn state_with(snapshot: StatusSnapshot) -> StatusState {
+        let status = StatusHandle::new();
+        status.update(|s| *s = snapshot);
+        StatusState {
+            status,
+            tracker: RouteTracker::default(),
+            store: Arc::new(MemoryStore::new()),
+            max_cycle_age: Duration::from_secs(180),
+            mode: ModePlan::uniform(RunMode::DryRun),
+            profile: Profile::Testnet,
+            rpc: None,
+            kill_switch: KillSwitch::default(),
+            admin_token: Some("secret".into()),
+            audit: AuditLog::default(),
+            cycle_trigger: None,
+            commands: None,
+            config: None,
+            delivery_failures: Default::default(),
+        }
+    }
+
+    fn route(route_id: &str, state: RouteState) -> RouteReceipt {
+        RouteReceipt {
+            route_id: route_id.to_string(),
+            amount: 100.0,
+            source_chain: "Ethereum".to_string(),
+            target_chain: "Arbitrum".to_string(),
+            provider: BridgeProvider::LiFi,
+            state,
+            source_tx_hash: None,
+            destination_tx_hash: None,
+            failure_reason: None,
+            updated_at: unix_now(),
+        }
+    }
+
+    fn admin_request(method: actix_web::http::Method, uri: &str) -> actix_test::TestRequest {
+        actix_test::TestRequest::default()
+            .method(method)
+            .uri(uri)
+            .peer_addr("10.0.0.7:40000".parse().unwrap())
+            .insert_header((actix_web::http::header::AUTHORIZATION, "Bearer secret"))
+    }
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq(b"secret", b"secret"));
+        assert!(!constant_time_eq(b"secret", b"secreT"));
+        assert!(!constant_time_eq(b"secret", b"secret2"));
+        assert!(!constant_time_eq(b"", b"secret"));
+    }
+
+    #[test]
+    fn test_balance_status_thresholds() {
+        let min = U256::from(100);
+        assert_eq!(BalanceStatus::evaluate(U256::from(100), min), BalanceStatus::Healthy);
+        assert_eq!(BalanceStatus::evaluate(U256::from(99), min), BalanceStatus::Low);
+        assert_eq!(BalanceStatus::evaluate(U256::from(51), min), BalanceStatus::Low);
+        assert_eq!(BalanceStatus::evaluate(U256::from(50), min), BalanceStatus::Critical);
+    }
+
+    #[actix_web::test]
+    async fn test_admin_pause_requires_token() {
+        let state = state_with(healthy_snapshot());
+        let kill_switch = state.kill_switch.clone();
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(web::Data::new(state))
+                .configure(configure),
+        )
+        .await;
+        let post = |uri, token: Option<&str>| {
+            let req = actix_test::TestRequest::post().uri(uri);
+            match token {
+                Some(token) => req.insert_header((ADMIN_TOKEN_HEADER, token)),
+                None => req,
+            }
+            .to_request()
+        };
+
+        let resp = actix_test::call_service(&app, post("/admin/pause", None)).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        let resp = actix_test::call_service(&app, post("/admin/pause", Some("wrong"))).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(kill_switch.source(), None);
+
+        let resp = actix_test::call_service(&app, post("/admin/pause", Some("secret"))).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(kill_switch.source(), Some(PauseSource::Admin));
+        let resp = actix_test::call_service(&app, actix_test::TestRequest::get().uri("/status").to_request()).await;
+        let body: serde_json::Value = actix_test::read_body_json(resp).await;
+        assert_eq!(body["paused"], "admin");
+
+        let resp = actix_test::call_service(&app, post("/admin/resume", Some("secret"))).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(kill_switch.source(), None);
+    }
+
+    #[test]
+    fn test_check_health() {
+        let now = 1_000;
+        let max_age = Duration::from_secs(180);
+        let mut snapshot = StatusSnapshot {
+            last_cycle_at: Some(now - 60),
+            rpc_reachable: true,
+            ..StatusSnapshot::default()
+        };
+        assert!(check_health(&snapshot, now, max_age).healthy);
+
+        snapshot.last_cycle_at = Some(now - 181);
+        snapshot.rpc_reachable = false;
+        let report = check_health(&snapshot, now, max_age);
+        assert!(!report.healthy);
+        assert_eq!(report.failed.len(), 2);
+
+        let report = check_health(&StatusSnapshot::default(), now, max_age);
+        assert!(report.failed[0].starts_with("last_cycle"));
+    }
+
+    #[actix_web::test]
+    async fn test_healthz_flips_to_503_when_cycle_is_stale() {
+        let state = state_with(healthy_snapshot());
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(web::Data::new(state.clone()))
+                .configure(configure),
+        )
+        .await;
+
+        let resp = actix_test::call_service(&app, actix_test::TestRequest::get().uri("/healthz").to_request()).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        state.status.update(|s| s.last_cycle_at = Some(unix_now() - 600));
+        let resp = actix_test::call_service(&app, actix_test::TestRequest::get().uri("/healthz").to_request()).await;
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let body: serde_json::Value = actix_test::read_body_json(resp).await;
+        assert_eq!(body["healthy"], false);
+        assert_eq!(body["failed"].as_array().unwrap().len(), 1);
+        assert!(body["failed"][0].as_str().unwrap().starts_with("last_cycle"));
+    }
+
+    #[actix_web::test]
+    async fn test_status_reports_loop_state() {
+        let mut snapshot = healthy_snapshot();
+        snapshot.last_pool = Some(ScoredPool {
+            pool: PoolData {
+                protocol: "Aave".to_string(),
+                chain: "Ethereum".to_string(),
+                apy: Some(5.2),
+                tvl: 1_000_000.0,
+            },
+            score: 31.2,
+        });
+        snapshot.portfolio.reconcile("Ethereum", 1.0, 0.05, unix_now());
+        let state = state_with(snapshot);
+        state.tracker.insert(route("route-1", RouteState::Releasing));
+
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(web::Data::new(state))
+                .configure(configure),
+        )
+        .await;
+        let resp = actix_test::call_service(&app, actix_test::TestRequest::get().uri("/status").to_request()).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let body: serde_json::Value = actix_test::read_body_json(resp).await;
+        assert_eq!(body["version"], crate::version::VERSION);
+        assert_eq!(body["git_hash"], crate::version::GIT_HASH);
+        assert!(body["build_timestamp"].as_u64().unwrap() > 0);
+        assert!(body["features"].is_array());
+        assert_eq!(body["mode"]["global"], "dry_run");
+        assert_eq!(body["profile"], "testnet");
+        assert_eq!(body["config_version"], 1);
+        assert_eq!(body["balance_wei"], "1000000000000000000");
+        assert_eq!(body["balance_status"], "healthy");
+        assert_eq!(body["last_pool"]["protocol"], "Aave");
+        assert_eq!(body["position"], "Ethereum");
+        assert_eq!(body["portfolio"]["entries"][0]["amount"], 1.0);
+        assert_eq!(body["in_flight_routes"].as_array().unwrap().len(), 1);
+    }
+
+    #[actix_web::test]
+    async fn test_status_hides_rpc_keys_in_errors() {
+        use ethers::providers::Middleware;
+
+        let key = "0123456789abcdef0123456789abcdef";
+        let url = format!("http://127.0.0.1:1/v3/{}", key);
+        let (provider, rpc) = FailoverClient::new(&[url], Duration::from_secs(2), Duration::from_secs(60))
+            .unwrap()
+            .into_provider();
+        let error = provider.get_block_number().await.unwrap_err();
+        let mut snapshot = healthy_snapshot();
+        snapshot
+            .balances
+            .insert("Ethereum".to_string(), ChainBalance::unknown(U256::exp10(18), format!("{:#}", error)));
+        let state = StatusState { rpc: Some(rpc), ..state_with(snapshot) };
+        let app = actix_test::init_service(App::new().app_data(web::Data::new(state)).configure(configure)).await;
+
+        let req = actix_test::TestRequest::get().uri("/status").to_request();
+        let body: serde_json::Value = actix_test::call_and_read_body_json(&app, req).await;
+        assert!(body["rpc"]["endpoints"][0]["last_error"].is_string(), "{}", body);
+        assert!(body["balances"]["Ethereum"]["error"].is_string(), "{}", body);
+        assert!(!body.to_string().contains(key), "{}", body);
+    }
+
+    #[actix_web::test]
+    async fn test_admin_bearer_auth_is_audited() {
+        let state = state_with(healthy_snapshot());
+        let audit = state.audit.clone();
+        let app = actix_test::init_service(App::new().app_data(web::Data::new(state)).configure(configure)).await;
+
+        let req = actix_test::TestRequest::get()
+            .uri("/admin/routes")
+            .peer_addr("10.0.0.9:40000".parse().unwrap())
+            .insert_header((actix_web::http::header::AUTHORIZATION, "Bearer wrong"))
+            .to_request();
+        let resp = actix_test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        let req = actix_test::TestRequest::post().uri("/admin/cycle").to_request();
+        let resp = actix_test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+        let req = admin_request(actix_web::http::Method::GET, "/admin/routes").to_request();
+        let resp = actix_test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let entries = audit.recent();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(
+            (entries[0].caller.as_str(), entries[0].action.as_str(), entries[0].outcome.as_str()),
+            ("10.0.0.9:40000", "GET /admin/routes", "unauthorized")
+        );
+        assert_eq!(entries[1].caller, "unknown");
+        assert_eq!(entries[2].caller, "10.0.0.7:40000");
+        assert_eq!(entries[2].outcome, "ok");
+    }
+
+    #[actix_web::test]
+    async fn test_admin_budget_override() {
+        let state = state_with(healthy_snapshot());
+        let status = state.status.clone();
+        let app = actix_test::init_service(App::new().app_data(web::Data::new(state)).configure(configure)).await;
+        let post = || admin_request(actix_web::http::Method::POST, "/admin/budget/override").to_request();
+
+        let resp = actix_test::call_service(&app, post()).await;
+        assert_eq!(resp.status(), StatusCode::CONFLICT);
+
+        status.update(|s| s.budget.engaged = true);
+        assert!(status.snapshot().budget.blocks());
+        let resp = actix_test::call_service(&app, post()).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body: serde_json::Value = actix_test::read_body_json(resp).await;
+        assert_eq!(body["overridden"], true);
+        assert!(!status.snapshot().budget.blocks());
+    }
+
+    #[actix_web::test]
+    async fn test_admin_disabled_without_token() {
+        let state = StatusState { admin_token: None, ..state_with(healthy_snapshot()) };
+        let app = actix_test::init_service(App::new().app_data(web::Data::new(state)).configure(configure)).await;
+        let req = admin_request(actix_web::http::Method::POST, "/admin/cycle").to_request();
+        let resp = actix_test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_web::test]
+    async fn test_admin_chains_and_cycle_reach_the_loop() {
+        let config = Config::default();
+        let (_updates_tx, updates) = watch::channel(ConfigUpdate {
+            version: ConfigVersion::INITIAL,
+            config: Arc::new(config),
+        });
+        let (trigger_tx, mut trigger_rx) = mpsc::channel(1);
+        let (commands_tx, mut commands_rx) = mpsc::unbounded_channel();
+        let state = StatusState {
+            cycle_trigger: Some(trigger_tx),
+            commands: Some(commands_tx),
+            config: Some(updates),
+            ..state_with(healthy_snapshot())
+        };
+        let audit = state.audit.clone();
+        let app = actix_test::init_service(App::new().app_data(web::Data::new(state)).configure(configure)).await;
+        let post = |uri| admin_request(actix_web::http::Method::POST, uri).to_request();
+
+        let resp = actix_test::call_service(&app, post("/admin/chains/Arbitrum/disable")).await;
+        assert_eq!(resp.status(), StatusCode::ACCEPTED);
+        let resp = actix_test::call_service(&app, post("/admin/chains/Arbitrum/enable")).await;
+        assert_eq!(resp.status(), StatusCode::ACCEPTED);
+        assert_eq!(
+            commands_rx.try_recv().unwrap(),
+            AdminCommand::SetChainActive { chain: "Arbitrum".to_string(), active: false }
+        );
+        assert_eq!(
+            commands_rx.try_recv().unwrap(),
+            AdminCommand::SetChainActive { chain: "Arbitrum".to_string(), active: true }
+        );
+        // Re-enabling wakes the loop; disabling does not
+        assert_eq!(trigger_rx.try_recv(), Ok(CycleTrigger::ChainEnabled("Arbitrum".to_string())));
+        let resp = actix_test::call_service(&app, post("/admin/chains/Solana/disable")).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        assert!(commands_rx.try_recv().is_err());
+
+        // A second trigger before the loop picks up the first is folded into it
+        let resp = actix_test::call_service(&app, post("/admin/cycle")).await;
+        assert_eq!(resp.status(), StatusCode::ACCEPTED);
+        let resp = actix_test::call_service(&app, post("/admin/cycle")).await;
+        assert_eq!(resp.status(), StatusCode::ACCEPTED);
+        assert_eq!(trigger_rx.try_recv(), Ok(CycleTrigger::Admin));
+        assert!(trigger_rx.try_recv().is_err());
+
+        let outcomes: Vec<_> = audit.recent().into_iter().map(|e| (e.action, e.outcome)).collect();
+        assert_eq!(outcomes[0], ("POST /admin/chains/Arbitrum/disable".to_string(), "ok".to_string()));
+        assert_eq!(outcomes[2].1, "unknown chain 'Solana'");
+        assert_eq!(outcomes.len(), 5);
+    }
+
+    #[actix_web::test]
+    async fn test_admin_routes_list_and_cancel() {
+        let state = state_with(healthy_snapshot());
+        state.tracker.insert(route("route-1", RouteState::Releasing));
+        state.tracker.insert(route("route-2", RouteState::Completed));
+        state.status.update(|s| s.portfolio.route_started(&route("route-1", RouteState::Releasing), None, unix_now()));
+        let (tracker, store, status) = (state.tracker.clone(), state.store.clone(), state.status.clone());
+        let app = actix_test::init_service(App::new().app_data(web::Data::new(state)).configure(configure)).await;
+
+        let req = admin_request(actix_web::http::Method::GET, "/admin/routes").to_request();
+        let body: serde_json::Value = actix_test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body.as_array().unwrap().len(), 2);
+
+        let post = |uri| admin_request(actix_web::http::Method::POST, uri).to_request();
+        let resp = actix_test::call_service(&app, post("/admin/routes/route-1/cancel")).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let route = tracker.get("route-1").unwrap();
+        assert_eq!(route.state, RouteState::Failed);
+        assert_eq!(route.failure_reason.as_deref(), Some("cancelled by operator"));
+        assert_eq!(store.routes_between(0, u64::MAX).await.unwrap()[0].state, RouteState::Failed);
+        assert_eq!(status.snapshot().portfolio.pending_routes().count(), 0);
+
+        let resp = actix_test::call_service(&app, post("/admin/routes/route-2/cancel")).await;
+        assert_eq!(resp.status(), StatusCode::CONFLICT);
+        let resp = actix_test::call_service(&app, post("/admin/routes/route-9/cancel")).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_web::test]
+    async fn test_admin_config_is_redacted() {
+        let mut config = Config::default();
+        config.http.admin_token = Some("admin-token-0123456789".into());
+        config.notify.telegram.bot_token = Some("123456:telegram-bot-token".into());
+        let (_updates_tx, updates) = watch::channel(ConfigUpdate {
+            version: ConfigVersion::INITIAL,
+            config: Arc::new(config),
+        });
+        let state = StatusState { config: Some(updates), ..state_with(healthy_snapshot()) };
+        let app = actix_test::init_service(App::new().app_data(web::Data::new(state)).configure(configure)).await;
+
+        let req = admin_request(actix_web::http::Method::GET, "/admin/config").to_request();
+        let body: serde_json::Value = actix_test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["version"], 1);
+        assert_eq!(body["config"]["http"]["admin_token"], "[REDACTED]");
+        assert_eq!(body["config"]["notify"]["telegram"]["bot_token"], "[REDACTED]");
+        assert!(!body.to_string().contains("telegram-bot-token"));
+    }
+}
@@ -0,0 +1,500 @@
+//! Persistence for cycle history, routes, executed transactions, the portfolio,
+//! yield snapshots, portfolio valuations, rebalance plans, recent actions, block
+//! cursors and the transactions left unsent at shutdown.
+//!
+//! [`MemoryStore`] keeps everything in process and is used when no storage path is
+//! configured. With the `sqlite` feature, [`SqliteStore`] persists to a database
+//! file created and migrated at startup.
+
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use ethers::core::types::{Address, H256, U256};
+use log::info;
+use serde::{Serialize, Serializer};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use thiserror::Error;
+
+use crate::agents::cross_chain_router::RouteReceipt;
+use crate::agents::safe_manager::SafeTransaction;
+use crate::config::StorageConfig;
+use crate::portfolio::PortfolioState;
+use crate::rebalance::RebalancePlan;
+use crate::recent_actions::RecentAction;
+use crate::status::BalanceStatus;
+use crate::valuation::PortfolioValuation;
+use crate::yield_tracker::YieldSnapshot;
+
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteStore;
+
+#[derive(Error, Debug)]
+#[non_exhaustive]
+pub enum StorageError {
+    #[cfg(feature = "sqlite")]
+    #[error("Database error: {0}")]
+    Database(String),
+    #[cfg(feature = "sqlite")]
+    #[error("Corrupt {column} value in storage: {value}")]
+    Corrupt { column: &'static str, value: String },
+    #[cfg(not(feature = "sqlite"))]
+    #[error("storage.path is set but this build has no SQLite support (enable the `sqlite` feature)")]
+    Unsupported,
+}
+
+fn decimal_opt<S: Serializer>(value: &Option<U256>, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+    match value {
+        Some(value) => serializer.serialize_some(&value.to_string()),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// Outcome of one monitoring cycle.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CycleRecord {
+    pub timestamp: u64,
+    #[serde(serialize_with = "decimal_opt")]
+    pub balance_wei: Option<U256>,
+    pub balance_status: BalanceStatus,
+    pub pool_protocol: Option<String>,
+    pub pool_chain: Option<String>,
+    pub pool_score: Option<f64>,
+    /// What the cycle did, e.g. "hold", "routed to Arbitrum" or "error: ...".
+    pub action: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TransactionRecord {
+    pub timestamp: u64,
+    pub chain: String,
+    pub tx_hash: Option<H256>,
+    pub to: Address,
+    pub value_wei: U256,
+    pub fee_wei: U256,
+}
+
+#[async_trait]
+pub trait Store: Send + Sync {
+    async fn record_cycle(&self, cycle: &CycleRecord) -> Result<()>;
+    /// Inserts or updates a route by id; the first insert fixes its creation time.
+    async fn upsert_route(&self, route: &RouteReceipt, created_at: u64) -> Result<()>;
+    async fn record_transaction(&self, tx: &TransactionRecord) -> Result<()>;
+    /// Replaces the stored portfolio.
+    async fn set_portfolio(&self, portfolio: &PortfolioState) -> Result<()>;
+    async fn portfolio(&self) -> Result<PortfolioState>;
+
+    /// The `n` most recent cycles, newest first.
+    async fn recent_cycles(&self, n: usize) -> Result<Vec<CycleRecord>>;
+    /// Routes created within `[from, to]` (unix seconds), oldest first.
+    async fn routes_between(&self, from: u64, to: u64) -> Result<Vec<RouteReceipt>>;
+    /// Creation time of the most recent route from `source_chain` to `target_chain`.
+    async fn last_route_at(&self, source_chain: &str, target_chain: &str) -> Result<Option<u64>>;
+    /// Sum of transaction fees paid at or after `since`.
+    async fn total_fees_since(&self, since: u64) -> Result<U256>;
+
+    async fn record_yield_snapshots(&self, snapshots: &[YieldSnapshot]) -> Result<()>;
+    /// Every yield snapshot, oldest first.
+    async fn yield_snapshots(&self) -> Result<Vec<YieldSnapshot>>;
+
+    async fn record_valuation(&self, valuation: &PortfolioValuation) -> Result<()>;
+    /// Valuations priced within `[from, to]` (unix seconds), oldest first.
+    async fn valuations_between(&self, from: u64, to: u64) -> Result<Vec<PortfolioValuation>>;
+
+    /// Inserts or replaces a plan by id.
+    async fn save_plan(&self, plan: &RebalancePlan) -> Result<()>;
+    /// The most recently created plan that is neither done nor failed.
+    async fn open_plan(&self) -> Result<Option<RebalancePlan>>;
+
+    /// Replaces the action stored under the same key.
+    async fn record_action(&self, action: &RecentAction) -> Result<()>;
+    /// Actions taken at or after `since`.
+    async fn recent_actions(&self, since: u64) -> Result<Vec<RecentAction>>;
+    /// Deletes actions taken before `before`.
+    async fn prune_actions(&self, before: u64) -> Result<()>;
+
+    /// Last block handled by the scan named `name`, e.g. the Safe's transfers.
+    async fn block_cursor(&self, name: &str) -> Result<Option<u64>>;
+    /// Replaces the block stored under `name`.
+    async fn set_block_cursor(&self, name: &str, block: u64) -> Result<()>;
+
+    /// Transactions still queued when the agent last stopped, in queue order.
+    async fn unsent_transactions(&self) -> Result<Vec<SafeTransaction>>;
+    /// Replaces the unsent transactions; an empty slice clears them.
+    async fn set_unsent_transactions(&self, txs: &[SafeTransaction]) -> Result<()>;
+}
+
+#[derive(Debug, Default)]
+struct MemoryData {
+    cycles: Vec<CycleRecord>,
+    routes: HashMap<String, (u64, RouteReceipt)>,
+    transactions: Vec<TransactionRecord>,
+    portfolio: PortfolioState,
+    yield_snapshots: Vec<YieldSnapshot>,
+    valuations: Vec<PortfolioValuation>,
+    plans: HashMap<String, RebalancePlan>,
+    actions: HashMap<String, RecentAction>,
+    cursors: HashMap<String, u64>,
+    unsent: Vec<SafeTransaction>,
+}
+
+/// Keeps history for the lifetime of the process only.
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    data: Mutex<MemoryData>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn data(&self) -> std::sync::MutexGuard<'_, MemoryData> {
+        self.data.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[async_trait]
+impl Store for MemoryStore {
+    async fn record_cycle(&self, cycle: &CycleRecord) -> Result<()> {
+        self.data().cycles.push(cycle.clone());
+        Ok(())
+    }
+
+    async fn upsert_route(&self, route: &RouteReceipt, created_at: u64) -> Result<()> {
+        let mut data = self.data();
+        let created_at = data
+            .routes
+            .get(&route.route_id)
+            .map(|(created, _)| *created)
+            .unwrap_or(created_at);
+        data.routes.insert(route.route_id.clone(), (created_at, route.clone()));
+        Ok(())
+    }
+
+    async fn record_transaction(&self, tx: &TransactionRecord) -> Result<()> {
+        self.data().transactions.push(tx.clone());
+        Ok(())
+    }
+
+    async fn set_portfolio(&self, portfolio: &PortfolioState) -> Result<()> {
+        self.data().portfolio = portfolio.clone();
+        Ok(())
+    }
+
+    async fn portfolio(&self) -> Result<PortfolioState> {
+        Ok(self.data().portfolio.clone())
+    }
+
+    async fn recent_cycles(&self, n: usize) -> Result<Vec<CycleRecord>> {
+        Ok(self.data().cycles.iter().rev().take(n).cloned().collect())
+    }
+
+    async fn routes_between(&self, from: u64, to: u64) -> Result<Vec<RouteReceipt>> {
+        let data = self.data();
+        let mut routes: Vec<_> = data
+            .routes
+            .values()
+            .filter(|(created, _)| (from..=to).contains(created))
+            .collect();
+        routes.sort_by_key(|(created, route)| (*created, route.route_id.clone()));
+        Ok(routes.into_iter().map(|(_, route)| route.clone()).collect())
+    }
+
+    async fn last_route_at(&self, source_chain: &str, target_chain: &str) -> Result<Option<u64>> {
+        Ok(self
+            .data()
+            .routes
+            .values()
+            .filter(|(_, route)| route.source_chain == source_chain && route.target_chain == target_chain)
+            .map(|(created, _)| *created)
+            .max())
+    }
+
+    async fn total_fees_since(&self, since: u64) -> Result<U256> {
+        Ok(self
+            .data()
+            .transactions
+            .iter()
+            .filter(|tx| tx.timestamp >= since)
+            .fold(U256::zero(), |total, tx| total.saturating_add(tx.fee_wei)))
+    }
+
+    async fn record_yield_snapshots(&self, snapshots: &[YieldSnapshot]) -> Result<()> {
+        self.data().yield_snapshots.extend_from_slice(snapshots);
+        Ok(())
+    }
+
+    async fn yield_snapshots(&self) -> Result<Vec<YieldSnapshot>> {
+        Ok(self.data().yield_snapshots.clone())
+    }
+
+    async fn record_valuation(&self, valuation: &PortfolioValuation) -> Result<()> {
+        self.data().valuations.push(valuation.clone());
+        Ok(())
+    }
+
+    async fn valuations_between(&self, from: u64, to: u64) -> Result<Vec<PortfolioValuation>> {
+        let data = self.data();
+        let mut valuations: Vec<_> =
+            data.valuations.iter().filter(|v| (from..=to).contains(&v.priced_at)).cloned().collect();
+        valuations.sort_by_key(|v| v.priced_at);
+        Ok(valuations)
+    }
+
+    async fn save_plan(&self, plan: &RebalancePlan) -> Result<()> {
+        self.data().plans.insert(plan.id.clone(), plan.clone());
+        Ok(())
+    }
+
+    async fn open_plan(&self) -> Result<Option<RebalancePlan>> {
+        Ok(self
+            .data()
+            .plans
+            .values()
+            .filter(|p| p.stage.is_open())
+            .max_by(|a, b| (a.created_at, &a.id).cmp(&(b.created_at, &b.id)))
+            .cloned())
+    }
+
+    async fn record_action(&self, action: &RecentAction) -> Result<()> {
+        self.data().actions.insert(action.key.clone(), action.clone());
+        Ok(())
+    }
+
+    async fn recent_actions(&self, since: u64) -> Result<Vec<RecentAction>> {
+        let mut actions: Vec<_> = self.data().actions.values().filter(|a| a.at >= since).cloned().collect();
+        actions.sort_by(|a, b| a.key.cmp(&b.key));
+        Ok(actions)
+    }
+
+    async fn prune_actions(&self, before: u64) -> Result<()> {
+        self.data().actions.retain(|_, action| action.at >= before);
+        Ok(())
+    }
+
+    async fn block_cursor(&self, name: &str) -> Result<Option<u64>> {
+        Ok(self.data().cursors.get(name).copied())
+    }
+
+    async fn set_block_cursor(&self, name: &str, block: u64) -> Result<()> {
+        self.data().cursors.insert(name.to_string(), block);
+        Ok(())
+    }
+
+    async fn unsent_transactions(&self) -> Result<Vec<SafeTransaction>> {
+        Ok(self.data().unsent.clone())
+    }
+
+    async fn set_unsent_transactions(&self, txs: &[SafeTransaction]) -> Result<()> {
+        self.data().unsent = txs.to_vec();
+        Ok(())
+    }
+}
+
+/// Opens the configured store: SQLite when `storage.path` is set, memory otherwise.
+pub fn open_store(config: &StorageConfig) -> Result<Arc<dyn Store>> {
+    match &config.path {
+        #[cfg(feature = "sqlite")]
+        Some(path) => {
+            info!("Using SQLite storage at {}", path.display());
+            Ok(Arc::new(SqliteStore::open(path)?))
+        }
+        #[cfg(not(feature = "sqlite"))]
+        Some(_) => Err(StorageError::Unsupported.into()),
+        None => {
+            info!("No storage path configured - history is kept in memory only");
+            Ok(Arc::new(MemoryStore::new()))
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::agents::cross_chain_router::{BridgeProvider, RouteState};
+    use crate::rebalance::{PlanStage, RouteRequest};
+    use crate::valuation::AssetValue;
+
+    pub(crate) fn cycle(timestamp: u64, action: &str) -> CycleRecord {
+        CycleRecord {
+            timestamp,
+            balance_wei: Some(U256::exp10(18)),
+            balance_status: BalanceStatus::Healthy,
+            pool_protocol: Some("Aave".to_string()),
+            pool_chain: Some("Arbitrum".to_string()),
+            pool_score: Some(31.5),
+            action: action.to_string(),
+        }
+    }
+
+    pub(crate) fn route(route_id: &str, state: RouteState) -> RouteReceipt {
+        RouteReceipt {
+            route_id: route_id.to_string(),
+            amount: 100.0,
+            source_chain: "Ethereum".to_string(),
+            target_chain: "Arbitrum".to_string(),
+            provider: BridgeProvider::LiFi,
+            state,
+            source_tx_hash: Some(H256::repeat_byte(1)),
+            destination_tx_hash: None,
+            failure_reason: None,
+            updated_at: 0,
+        }
+    }
+
+    pub(crate) fn transaction(timestamp: u64, fee_wei: u64) -> TransactionRecord {
+        TransactionRecord {
+            timestamp,
+            chain: "Ethereum".to_string(),
+            tx_hash: Some(H256::repeat_byte(2)),
+            to: Address::repeat_byte(3),
+            value_wei: U256::exp10(17),
+            fee_wei: U256::from(fee_wei),
+        }
+    }
+
+    /// Runs a simulated cycle's writes and the query helpers against any backend.
+    pub(crate) async fn exercise_store(store: &dyn Store) {
+        store.record_cycle(&cycle(100, "hold")).await.unwrap();
+        store.record_cycle(&cycle(160, "routed to Arbitrum")).await.unwrap();
+        store.record_cycle(&cycle(220, "error: rpc down")).await.unwrap();
+
+        let recent = store.recent_cycles(2).await.unwrap();
+        assert_eq!(recent.len(), 2);
+        assert_eq!(recent[0], cycle(220, "error: rpc down"));
+        assert_eq!(recent[1].action, "routed to Arbitrum");
+
+        store.upsert_route(&route("a", RouteState::Releasing), 150).await.unwrap();
+        store.upsert_route(&route("b", RouteState::Pending), 300).await.unwrap();
+        // Updating keeps the original creation time
+        store.upsert_route(&route("a", RouteState::Completed), 400).await.unwrap();
+        let routes = store.routes_between(100, 200).await.unwrap();
+        assert_eq!(routes.len(), 1);
+        assert_eq!(routes[0].route_id, "a");
+        assert_eq!(routes[0].state, RouteState::Completed);
+        assert_eq!(routes[0].source_tx_hash, Some(H256::repeat_byte(1)));
+        assert_eq!(store.routes_between(0, 1_000).await.unwrap().len(), 2);
+        assert_eq!(store.last_route_at("Ethereum", "Arbitrum").await.unwrap(), Some(300));
+        assert_eq!(store.last_route_at("Arbitrum", "Ethereum").await.unwrap(), None);
+
+        store.record_transaction(&transaction(100, 21_000)).await.unwrap();
+        store.record_transaction(&transaction(200, 42_000)).await.unwrap();
+        assert_eq!(store.total_fees_since(150).await.unwrap(), U256::from(42_000));
+        assert_eq!(store.total_fees_since(0).await.unwrap(), U256::from(63_000));
+
+        assert_eq!(store.portfolio().await.unwrap(), PortfolioState::default());
+        let mut portfolio = PortfolioState::default();
+        portfolio.reconcile("Ethereum", 150.0, 0.05, 160);
+        portfolio.route_started(&route("c", RouteState::Pending), Some("Aave".to_string()), 170);
+        store.set_portfolio(&portfolio).await.unwrap();
+        assert_eq!(store.portfolio().await.unwrap(), portfolio);
+        // Saving again replaces the previous entries
+        portfolio.route_completed("c", 180);
+        store.set_portfolio(&portfolio).await.unwrap();
+        assert_eq!(store.portfolio().await.unwrap(), portfolio);
+
+        let snapshot = |timestamp, value| YieldSnapshot {
+            timestamp,
+            chain: "Arbitrum".to_string(),
+            protocol: Some("Aave".to_string()),
+            value,
+            flow: 0.0,
+            fees: 0.0,
+            projected_apy: Some(5.2),
+        };
+        store.record_yield_snapshots(&[snapshot(100, 1.0)]).await.unwrap();
+        store.record_yield_snapshots(&[snapshot(200, 1.5), snapshot(200, 0.0)]).await.unwrap();
+        assert_eq!(
+            store.yield_snapshots().await.unwrap(),
+            vec![snapshot(100, 1.0), snapshot(200, 1.5), snapshot(200, 0.0)]
+        );
+
+        let valuation = |priced_at, usd| PortfolioValuation {
+            total_usd: usd,
+            by_chain: [("Ethereum".to_string(), usd)].into(),
+            by_asset: [
+                ("ETH".to_string(), AssetValue { amount: 1.0, usd: Some(usd) }),
+                ("GHO".to_string(), AssetValue { amount: 5.0, usd: None }),
+            ]
+            .into(),
+            priced_at,
+        };
+        store.record_valuation(&valuation(200, 2_100.0)).await.unwrap();
+        store.record_valuation(&valuation(100, 2_000.0)).await.unwrap();
+        store.record_valuation(&valuation(300, 2_050.0)).await.unwrap();
+        assert_eq!(
+            store.valuations_between(100, 200).await.unwrap(),
+            vec![valuation(100, 2_000.0), valuation(200, 2_100.0)]
+        );
+
+        assert_eq!(store.open_plan().await.unwrap(), None);
+        let plan = |id: &str, created_at, stage| RebalancePlan {
+            id: id.to_string(),
+            created_at,
+            from_protocol: Some("Aave".to_string()),
+            to_protocol: "Compound".to_string(),
+            exit: vec![crate::protocol_actions::call(Address::repeat_byte(4), vec![1, 2])],
+            route: RouteRequest { amount: 1.5, source_chain: "Ethereum".to_string(), target_chain: "Arbitrum".to_string() },
+            enter: Vec::new(),
+            exit_gas: 250_000,
+            enter_gas: 0,
+            stage,
+            route_id: None,
+        };
+        store.save_plan(&plan("p-1", 100, PlanStage::Route)).await.unwrap();
+        store.save_plan(&plan("p-2", 200, PlanStage::Done)).await.unwrap();
+        assert_eq!(store.open_plan().await.unwrap(), Some(plan("p-1", 100, PlanStage::Route)));
+        // Saving again moves the plan on
+        let mut entering = plan("p-1", 100, PlanStage::Enter);
+        entering.route_id = Some("r-9".to_string());
+        store.save_plan(&entering).await.unwrap();
+        assert_eq!(store.open_plan().await.unwrap(), Some(entering));
+        store.save_plan(&plan("p-1", 100, PlanStage::Failed)).await.unwrap();
+        assert_eq!(store.open_plan().await.unwrap(), None);
+
+        let action = |key: &str, at, amount| RecentAction { key: key.to_string(), at, amount };
+        store.record_action(&action("alert:rebalance:Aave:Arbitrum", 100, None)).await.unwrap();
+        store.record_action(&action("route:Ethereum->Arbitrum:ETH", 150, Some(1.0))).await.unwrap();
+        // A newer action replaces the one under the same key
+        store.record_action(&action("route:Ethereum->Arbitrum:ETH", 250, Some(2.0))).await.unwrap();
+        assert_eq!(
+            store.recent_actions(0).await.unwrap(),
+            vec![
+                action("alert:rebalance:Aave:Arbitrum", 100, None),
+                action("route:Ethereum->Arbitrum:ETH", 250, Some(2.0)),
+            ]
+        );
+        assert_eq!(store.recent_actions(200).await.unwrap().len(), 1);
+        store.prune_actions(200).await.unwrap();
+        assert_eq!(store.recent_actions(0).await.unwrap(), vec![action("route:Ethereum->Arbitrum:ETH", 250, Some(2.0))]);
+
+        assert_eq!(store.block_cursor("transfers").await.unwrap(), None);
+        store.set_block_cursor("transfers", 100).await.unwrap();
+        store.set_block_cursor("transfers", 120).await.unwrap();
+        store.set_block_cursor("other", 5).await.unwrap();
+        assert_eq!(store.block_cursor("transfers").await.unwrap(), Some(120));
+
+        assert!(store.unsent_transactions().await.unwrap().is_empty());
+        let unsent = [0x11, 0x22].map(|byte| SafeTransaction {
+            to: Address::repeat_byte(byte),
+            value: U256::from(byte),
+            data: vec![0x09, byte],
+            operation: 0,
+            safe_tx_gas: U256::zero(),
+            nonce: None,
+        });
+        store.set_unsent_transactions(&unsent).await.unwrap();
+        store.set_unsent_transactions(&unsent[1..]).await.unwrap();
+        assert_eq!(store.unsent_transactions().await.unwrap(), unsent[1..]);
+        store.set_unsent_transactions(&[]).await.unwrap();
+        assert!(store.unsent_transactions().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_memory_store() {
+        exercise_store(&MemoryStore::new()).await;
+    }
+}
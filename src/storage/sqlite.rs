@@ -0,0 +1,694 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use ethers::core::types::{H256, U256};
+use log::{debug, info};
+use rusqlite::{params, Connection, Row};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+use super::{CycleRecord, Store, StorageError, TransactionRecord};
+use crate::agents::cross_chain_router::RouteReceipt;
+use crate::agents::safe_manager::SafeTransaction;
+use crate::portfolio::{PortfolioState, PositionEntry};
+use crate::rebalance::{PlanStage, RebalancePlan};
+use crate::recent_actions::RecentAction;
+use crate::valuation::PortfolioValuation;
+use crate::yield_tracker::YieldSnapshot;
+
+/// Schema migrations, applied in order. `PRAGMA user_version` records how many ran.
+const MIGRATIONS: &[&str] = &[
+    r#"
+CREATE TABLE cycles (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp INTEGER NOT NULL,
+    balance_wei TEXT,
+    balance_status TEXT NOT NULL,
+    pool_protocol TEXT,
+    pool_chain TEXT,
+    pool_score REAL,
+    action TEXT NOT NULL
+);
+CREATE INDEX cycles_timestamp ON cycles (timestamp);
+
+CREATE TABLE routes (
+    route_id TEXT PRIMARY KEY,
+    created_at INTEGER NOT NULL,
+    updated_at INTEGER NOT NULL,
+    amount REAL NOT NULL,
+    source_chain TEXT NOT NULL,
+    target_chain TEXT NOT NULL,
+    provider TEXT NOT NULL,
+    state TEXT NOT NULL,
+    source_tx_hash TEXT,
+    destination_tx_hash TEXT,
+    failure_reason TEXT
+);
+CREATE INDEX routes_created_at ON routes (created_at);
+
+CREATE TABLE transactions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp INTEGER NOT NULL,
+    chain TEXT NOT NULL,
+    tx_hash TEXT,
+    to_address TEXT NOT NULL,
+    value_wei TEXT NOT NULL,
+    fee_wei TEXT NOT NULL
+);
+CREATE INDEX transactions_timestamp ON transactions (timestamp);
+
+CREATE TABLE position (
+    id INTEGER PRIMARY KEY CHECK (id = 1),
+    chain TEXT NOT NULL,
+    protocol TEXT,
+    updated_at INTEGER NOT NULL
+);
+"#,
+    r#"
+CREATE TABLE portfolio (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    chain TEXT NOT NULL,
+    protocol TEXT,
+    token TEXT NOT NULL,
+    amount REAL NOT NULL,
+    since INTEGER NOT NULL,
+    pending_route TEXT
+);
+-- The single position row carried no amount; reconciliation fills it in
+INSERT INTO portfolio (chain, protocol, token, amount, since)
+    SELECT chain, protocol, 'ETH', 0, updated_at FROM position;
+DROP TABLE position;
+"#,
+    r#"
+CREATE TABLE yield_snapshots (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp INTEGER NOT NULL,
+    chain TEXT NOT NULL,
+    protocol TEXT,
+    value REAL NOT NULL,
+    flow REAL NOT NULL,
+    fees REAL NOT NULL,
+    projected_apy REAL
+);
+"#,
+    r#"
+CREATE TABLE recent_actions (
+    key TEXT PRIMARY KEY,
+    at INTEGER NOT NULL,
+    amount REAL
+);
+"#,
+    r#"
+CREATE TABLE valuations (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    priced_at INTEGER NOT NULL,
+    total_usd REAL NOT NULL,
+    by_chain TEXT NOT NULL,
+    by_asset TEXT NOT NULL
+);
+CREATE INDEX valuations_priced_at ON valuations (priced_at);
+"#,
+    r#"
+CREATE TABLE rebalance_plans (
+    id TEXT PRIMARY KEY,
+    created_at INTEGER NOT NULL,
+    stage TEXT NOT NULL,
+    plan TEXT NOT NULL
+);
+"#,
+    r#"
+CREATE TABLE block_cursors (
+    name TEXT PRIMARY KEY,
+    block INTEGER NOT NULL
+);
+"#,
+    r#"
+CREATE TABLE unsent_transactions (
+    position INTEGER PRIMARY KEY,
+    tx TEXT NOT NULL
+);
+"#,
+];
+
+impl From<rusqlite::Error> for StorageError {
+    fn from(e: rusqlite::Error) -> Self {
+        StorageError::Database(e.to_string())
+    }
+}
+
+/// Stores unit enums by their serde name, e.g. `LiFi` or `healthy`.
+fn enum_to_sql<T: Serialize>(value: &T) -> String {
+    serde_json::to_value(value)
+        .ok()
+        .and_then(|v| v.as_str().map(str::to_string))
+        .unwrap_or_default()
+}
+
+fn enum_from_sql<T: DeserializeOwned>(column: &'static str, raw: String) -> Result<T, StorageError> {
+    serde_json::from_value(serde_json::Value::String(raw.clone()))
+        .map_err(|_| StorageError::Corrupt { column, value: raw })
+}
+
+fn parse_col<T: FromStr>(column: &'static str, raw: String) -> Result<T, StorageError> {
+    raw.parse().map_err(|_| StorageError::Corrupt { column, value: raw })
+}
+
+fn parse_opt<T: FromStr>(column: &'static str, raw: Option<String>) -> Result<Option<T>, StorageError> {
+    raw.map(|raw| parse_col(column, raw)).transpose()
+}
+
+fn json_col<T: DeserializeOwned>(column: &'static str, raw: String) -> Result<T, StorageError> {
+    serde_json::from_str(&raw).map_err(|_| StorageError::Corrupt { column, value: raw })
+}
+
+fn u256_col(column: &'static str, raw: String) -> Result<U256, StorageError> {
+    U256::from_dec_str(&raw).map_err(|_| StorageError::Corrupt { column, value: raw })
+}
+
+/// SQLite-backed [`Store`]. Queries run on the blocking thread pool.
+#[derive(Clone)]
+pub struct SqliteStore {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl SqliteStore {
+    /// Opens (creating if needed) the database at `path` and applies pending migrations.
+    pub fn open(path: &Path) -> Result<Self> {
+        let conn = Connection::open(path)
+            .with_context(|| format!("Failed to open SQLite database at {}", path.display()))?;
+        Self::with_connection(conn)
+    }
+
+    #[cfg(test)]
+    pub fn open_in_memory() -> Result<Self> {
+        Self::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(mut conn: Connection) -> Result<Self> {
+        Self::migrate(&mut conn)?;
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    fn migrate(conn: &mut Connection) -> Result<(), StorageError> {
+        let applied: usize = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        for (version, migration) in MIGRATIONS.iter().enumerate().skip(applied) {
+            info!("Applying storage migration {}", version + 1);
+            let tx = conn.transaction()?;
+            tx.execute_batch(migration)?;
+            tx.pragma_update(None, "user_version", version + 1)?;
+            tx.commit()?;
+        }
+        debug!("Storage schema at version {}", MIGRATIONS.len().max(applied));
+        Ok(())
+    }
+
+    async fn run<T, F>(&self, f: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Connection) -> Result<T, StorageError> + Send + 'static,
+    {
+        let conn = self.conn.clone();
+        let result = tokio::task::spawn_blocking(move || {
+            let conn = conn.lock().unwrap_or_else(|e| e.into_inner());
+            f(&conn)
+        })
+        .await
+        .context("Storage task panicked")?;
+        Ok(result?)
+    }
+
+    fn cycle_from_row(row: &Row) -> Result<CycleRecord, StorageError> {
+        Ok(CycleRecord {
+            timestamp: row.get::<_, i64>(0)? as u64,
+            balance_wei: row.get::<_, Option<String>>(1)?.map(|raw| u256_col("balance_wei", raw)).transpose()?,
+            balance_status: enum_from_sql("balance_status", row.get(2)?)?,
+            pool_protocol: row.get(3)?,
+            pool_chain: row.get(4)?,
+            pool_score: row.get(5)?,
+            action: row.get(6)?,
+        })
+    }
+
+    fn route_from_row(row: &Row) -> Result<RouteReceipt, StorageError> {
+        Ok(RouteReceipt {
+            route_id: row.get(0)?,
+            updated_at: row.get::<_, i64>(1)? as u64,
+            amount: row.get(2)?,
+            source_chain: row.get(3)?,
+            target_chain: row.get(4)?,
+            provider: enum_from_sql("provider", row.get(5)?)?,
+            state: enum_from_sql("state", row.get(6)?)?,
+            source_tx_hash: parse_opt::<H256>("source_tx_hash", row.get(7)?)?,
+            destination_tx_hash: parse_opt::<H256>("destination_tx_hash", row.get(8)?)?,
+            failure_reason: row.get(9)?,
+        })
+    }
+}
+
+#[async_trait]
+impl Store for SqliteStore {
+    async fn record_cycle(&self, cycle: &CycleRecord) -> Result<()> {
+        let cycle = cycle.clone();
+        self.run(move |conn| {
+            conn.execute(
+                "INSERT INTO cycles (timestamp, balance_wei, balance_status, pool_protocol, pool_chain, pool_score, action)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![
+                    cycle.timestamp as i64,
+                    cycle.balance_wei.map(|b| b.to_string()),
+                    enum_to_sql(&cycle.balance_status),
+                    cycle.pool_protocol,
+                    cycle.pool_chain,
+                    cycle.pool_score,
+                    cycle.action,
+                ],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn upsert_route(&self, route: &RouteReceipt, created_at: u64) -> Result<()> {
+        let route = route.clone();
+        self.run(move |conn| {
+            conn.execute(
+                "INSERT INTO routes (route_id, created_at, updated_at, amount, source_chain, target_chain,
+                                     provider, state, source_tx_hash, destination_tx_hash, failure_reason)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)
+                 ON CONFLICT (route_id) DO UPDATE SET
+                     updated_at = excluded.updated_at,
+                     state = excluded.state,
+                     source_tx_hash = excluded.source_tx_hash,
+                     destination_tx_hash = excluded.destination_tx_hash,
+                     failure_reason = excluded.failure_reason",
+                params![
+                    route.route_id,
+                    created_at as i64,
+                    route.updated_at as i64,
+                    route.amount,
+                    route.source_chain,
+                    route.target_chain,
+                    enum_to_sql(&route.provider),
+                    enum_to_sql(&route.state),
+                    route.source_tx_hash.map(|h| format!("{:?}", h)),
+                    route.destination_tx_hash.map(|h| format!("{:?}", h)),
+                    route.failure_reason,
+                ],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn record_transaction(&self, tx: &TransactionRecord) -> Result<()> {
+        let tx = tx.clone();
+        self.run(move |conn| {
+            conn.execute(
+                "INSERT INTO transactions (timestamp, chain, tx_hash, to_address, value_wei, fee_wei)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    tx.timestamp as i64,
+                    tx.chain,
+                    tx.tx_hash.map(|h| format!("{:?}", h)),
+                    format!("{:?}", tx.to),
+                    tx.value_wei.to_string(),
+                    tx.fee_wei.to_string(),
+                ],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn set_portfolio(&self, portfolio: &PortfolioState) -> Result<()> {
+        let portfolio = portfolio.clone();
+        self.run(move |conn| {
+            let tx = conn.unchecked_transaction()?;
+            tx.execute("DELETE FROM portfolio", [])?;
+            for entry in &portfolio.entries {
+                tx.execute(
+                    "INSERT INTO portfolio (chain, protocol, token, amount, since, pending_route)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                    params![
+                        entry.chain,
+                        entry.protocol,
+                        entry.token,
+                        entry.amount,
+                        entry.since as i64,
+                        entry.pending_route,
+                    ],
+                )?;
+            }
+            tx.commit()?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn portfolio(&self) -> Result<PortfolioState> {
+        self.run(|conn| {
+            let mut stmt =
+                conn.prepare("SELECT chain, protocol, token, amount, since, pending_route FROM portfolio ORDER BY id")?;
+            let entries = stmt
+                .query_map([], |row| {
+                    Ok(PositionEntry {
+                        chain: row.get(0)?,
+                        protocol: row.get(1)?,
+                        token: row.get(2)?,
+                        amount: row.get(3)?,
+                        since: row.get::<_, i64>(4)? as u64,
+                        pending_route: row.get(5)?,
+                    })
+                })?
+                .collect::<Result<_, _>>()?;
+            Ok(PortfolioState { entries })
+        })
+        .await
+    }
+
+    async fn recent_cycles(&self, n: usize) -> Result<Vec<CycleRecord>> {
+        self.run(move |conn| {
+            let mut stmt = conn.prepare(
+                "SELECT timestamp, balance_wei, balance_status, pool_protocol, pool_chain, pool_score, action
+                 FROM cycles ORDER BY id DESC LIMIT ?1",
+            )?;
+            let mut rows = stmt.query(params![n as i64])?;
+            let mut cycles = Vec::new();
+            while let Some(row) = rows.next()? {
+                cycles.push(Self::cycle_from_row(row)?);
+            }
+            Ok(cycles)
+        })
+        .await
+    }
+
+    async fn routes_between(&self, from: u64, to: u64) -> Result<Vec<RouteReceipt>> {
+        self.run(move |conn| {
+            let mut stmt = conn.prepare(
+                "SELECT route_id, updated_at, amount, source_chain, target_chain, provider, state,
+                        source_tx_hash, destination_tx_hash, failure_reason
+                 FROM routes WHERE created_at BETWEEN ?1 AND ?2 ORDER BY created_at, route_id",
+            )?;
+            let mut rows = stmt.query(params![from as i64, to as i64])?;
+            let mut routes = Vec::new();
+            while let Some(row) = rows.next()? {
+                routes.push(Self::route_from_row(row)?);
+            }
+            Ok(routes)
+        })
+        .await
+    }
+
+    async fn last_route_at(&self, source_chain: &str, target_chain: &str) -> Result<Option<u64>> {
+        let (source_chain, target_chain) = (source_chain.to_string(), target_chain.to_string());
+        self.run(move |conn| {
+            let created: Option<i64> = conn.query_row(
+                "SELECT MAX(created_at) FROM routes WHERE source_chain = ?1 AND target_chain = ?2",
+                params![source_chain, target_chain],
+                |row| row.get(0),
+            )?;
+            Ok(created.map(|at| at as u64))
+        })
+        .await
+    }
+
+    async fn total_fees_since(&self, since: u64) -> Result<U256> {
+        self.run(move |conn| {
+            // Summed in Rust: wei amounts do not fit SQLite integers
+            let mut stmt = conn.prepare("SELECT fee_wei FROM transactions WHERE timestamp >= ?1")?;
+            let mut rows = stmt.query(params![since as i64])?;
+            let mut total = U256::zero();
+            while let Some(row) = rows.next()? {
+                total = total.saturating_add(u256_col("fee_wei", row.get(0)?)?);
+            }
+            Ok(total)
+        })
+        .await
+    }
+
+    async fn record_yield_snapshots(&self, snapshots: &[YieldSnapshot]) -> Result<()> {
+        let snapshots = snapshots.to_vec();
+        self.run(move |conn| {
+            let tx = conn.unchecked_transaction()?;
+            for snapshot in &snapshots {
+                tx.execute(
+                    "INSERT INTO yield_snapshots (timestamp, chain, protocol, value, flow, fees, projected_apy)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                    params![
+                        snapshot.timestamp as i64,
+                        snapshot.chain,
+                        snapshot.protocol,
+                        snapshot.value,
+                        snapshot.flow,
+                        snapshot.fees,
+                        snapshot.projected_apy,
+                    ],
+                )?;
+            }
+            tx.commit()?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn yield_snapshots(&self) -> Result<Vec<YieldSnapshot>> {
+        self.run(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT timestamp, chain, protocol, value, flow, fees, projected_apy
+                 FROM yield_snapshots ORDER BY id",
+            )?;
+            let snapshots = stmt
+                .query_map([], |row| {
+                    Ok(YieldSnapshot {
+                        timestamp: row.get::<_, i64>(0)? as u64,
+                        chain: row.get(1)?,
+                        protocol: row.get(2)?,
+                        value: row.get(3)?,
+                        flow: row.get(4)?,
+                        fees: row.get(5)?,
+                        projected_apy: row.get(6)?,
+                    })
+                })?
+                .collect::<Result<_, _>>()?;
+            Ok(snapshots)
+        })
+        .await
+    }
+
+    async fn record_valuation(&self, valuation: &PortfolioValuation) -> Result<()> {
+        let valuation = valuation.clone();
+        self.run(move |conn| {
+            conn.execute(
+                "INSERT INTO valuations (priced_at, total_usd, by_chain, by_asset) VALUES (?1, ?2, ?3, ?4)",
+                params![
+                    valuation.priced_at as i64,
+                    valuation.total_usd,
+                    serde_json::to_string(&valuation.by_chain).unwrap_or_default(),
+                    serde_json::to_string(&valuation.by_asset).unwrap_or_default(),
+                ],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn valuations_between(&self, from: u64, to: u64) -> Result<Vec<PortfolioValuation>> {
+        self.run(move |conn| {
+            let mut stmt = conn.prepare(
+                "SELECT priced_at, total_usd, by_chain, by_asset FROM valuations
+                 WHERE priced_at BETWEEN ?1 AND ?2 ORDER BY priced_at, id",
+            )?;
+            let mut rows = stmt.query(params![from as i64, to as i64])?;
+            let mut valuations = Vec::new();
+            while let Some(row) = rows.next()? {
+                valuations.push(PortfolioValuation {
+                    priced_at: row.get::<_, i64>(0)? as u64,
+                    total_usd: row.get(1)?,
+                    by_chain: json_col("by_chain", row.get(2)?)?,
+                    by_asset: json_col("by_asset", row.get(3)?)?,
+                });
+            }
+            Ok(valuations)
+        })
+        .await
+    }
+
+    async fn save_plan(&self, plan: &RebalancePlan) -> Result<()> {
+        let plan = plan.clone();
+        self.run(move |conn| {
+            conn.execute(
+                "INSERT INTO rebalance_plans (id, created_at, stage, plan) VALUES (?1, ?2, ?3, ?4)
+                 ON CONFLICT(id) DO UPDATE SET stage = excluded.stage, plan = excluded.plan",
+                params![
+                    plan.id,
+                    plan.created_at as i64,
+                    enum_to_sql(&plan.stage),
+                    serde_json::to_string(&plan).unwrap_or_default(),
+                ],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn open_plan(&self) -> Result<Option<RebalancePlan>> {
+        self.run(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT plan FROM rebalance_plans WHERE stage NOT IN (?1, ?2) ORDER BY created_at DESC, id DESC LIMIT 1",
+            )?;
+            let mut rows = stmt.query(params![enum_to_sql(&PlanStage::Done), enum_to_sql(&PlanStage::Failed)])?;
+            match rows.next()? {
+                Some(row) => Ok(Some(json_col("plan", row.get(0)?)?)),
+                None => Ok(None),
+            }
+        })
+        .await
+    }
+
+    async fn record_action(&self, action: &RecentAction) -> Result<()> {
+        let action = action.clone();
+        self.run(move |conn| {
+            conn.execute(
+                "INSERT INTO recent_actions (key, at, amount) VALUES (?1, ?2, ?3)
+                 ON CONFLICT(key) DO UPDATE SET at = excluded.at, amount = excluded.amount",
+                params![action.key, action.at as i64, action.amount],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn recent_actions(&self, since: u64) -> Result<Vec<RecentAction>> {
+        self.run(move |conn| {
+            let mut stmt = conn.prepare("SELECT key, at, amount FROM recent_actions WHERE at >= ?1 ORDER BY key")?;
+            let actions = stmt
+                .query_map(params![since as i64], |row| {
+                    Ok(RecentAction {
+                        key: row.get(0)?,
+                        at: row.get::<_, i64>(1)? as u64,
+                        amount: row.get(2)?,
+                    })
+                })?
+                .collect::<Result<_, _>>()?;
+            Ok(actions)
+        })
+        .await
+    }
+
+    async fn prune_actions(&self, before: u64) -> Result<()> {
+        self.run(move |conn| {
+            conn.execute("DELETE FROM recent_actions WHERE at < ?1", params![before as i64])?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn block_cursor(&self, name: &str) -> Result<Option<u64>> {
+        let name = name.to_string();
+        self.run(move |conn| {
+            let mut stmt = conn.prepare("SELECT block FROM block_cursors WHERE name = ?1")?;
+            let mut rows = stmt.query(params![name])?;
+            match rows.next()? {
+                Some(row) => Ok(Some(row.get::<_, i64>(0)? as u64)),
+                None => Ok(None),
+            }
+        })
+        .await
+    }
+
+    async fn set_block_cursor(&self, name: &str, block: u64) -> Result<()> {
+        let name = name.to_string();
+        self.run(move |conn| {
+            conn.execute(
+                "INSERT INTO block_cursors (name, block) VALUES (?1, ?2)
+                 ON CONFLICT(name) DO UPDATE SET block = excluded.block",
+                params![name, block as i64],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn unsent_transactions(&self) -> Result<Vec<SafeTransaction>> {
+        self.run(|conn| {
+            let mut stmt = conn.prepare("SELECT tx FROM unsent_transactions ORDER BY position")?;
+            let mut rows = stmt.query([])?;
+            let mut txs = Vec::new();
+            while let Some(row) = rows.next()? {
+                txs.push(json_col("tx", row.get(0)?)?);
+            }
+            Ok(txs)
+        })
+        .await
+    }
+
+    async fn set_unsent_transactions(&self, txs: &[SafeTransaction]) -> Result<()> {
+        let txs = txs.to_vec();
+        self.run(move |conn| {
+            let tx = conn.unchecked_transaction()?;
+            tx.execute("DELETE FROM unsent_transactions", [])?;
+            for (position, unsent) in txs.iter().enumerate() {
+                tx.execute(
+                    "INSERT INTO unsent_transactions (position, tx) VALUES (?1, ?2)",
+                    params![position as i64, serde_json::to_string(unsent).unwrap_or_default()],
+                )?;
+            }
+            tx.commit()?;
+            Ok(())
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::tests::{cycle, exercise_store};
+
+    #[tokio::test]
+    async fn test_sqlite_store() {
+        exercise_store(&SqliteStore::open_in_memory().unwrap()).await;
+    }
+
+    #[test]
+    fn test_migrations_are_idempotent() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        SqliteStore::migrate(&mut conn).unwrap();
+        SqliteStore::migrate(&mut conn).unwrap();
+        let version: usize = conn.query_row("PRAGMA user_version", [], |row| row.get(0)).unwrap();
+        assert_eq!(version, MIGRATIONS.len());
+    }
+
+    #[tokio::test]
+    async fn test_position_migrates_into_portfolio() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let tx = conn.transaction().unwrap();
+        tx.execute_batch(MIGRATIONS[0]).unwrap();
+        tx.execute("INSERT INTO position (id, chain, protocol, updated_at) VALUES (1, 'Arbitrum', 'Aave', 160)", [])
+            .unwrap();
+        tx.pragma_update(None, "user_version", 1).unwrap();
+        tx.commit().unwrap();
+
+        let store = SqliteStore::with_connection(conn).unwrap();
+        let portfolio = store.portfolio().await.unwrap();
+        assert_eq!(portfolio.current_chain(), "Arbitrum");
+        assert_eq!(portfolio.entries[0].protocol.as_deref(), Some("Aave"));
+        assert_eq!(portfolio.entries[0].since, 160);
+    }
+
+    #[tokio::test]
+    async fn test_reopen_file_keeps_history() {
+        let path = std::env::temp_dir().join(format!("asam-store-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        SqliteStore::open(&path).unwrap().record_cycle(&cycle(1, "hold")).await.unwrap();
+        let cycles = SqliteStore::open(&path).unwrap().recent_cycles(10).await.unwrap();
+        assert_eq!(cycles, vec![cycle(1, "hold")]);
+
+        let _ = std::fs::remove_file(&path);
+    }
+}
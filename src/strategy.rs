@@ -0,0 +1,633 @@
+//! Decides whether the best pool is worth bridging to.
+//!
+//! A route needs the candidate pool to beat the best pool on the current chain by
+//! `strategy.min_apy_improvement` APY points and no route in the same direction
+//! within `strategy.bridge_cooldown_secs`. The last route time comes from storage,
+//! so the cooldown survives restarts. Nothing is routed while the kill switch is on.
+//!
+//! The amount comes from [`Strategy::size_transfer`]: the balance minus
+//! `strategy.reserve_eth`, times `strategy.max_deploy_fraction`, capped by the
+//! router maximum. Below the bridge minimum the move is not worth making, and an
+//! amount matching a route completed in the same direction within
+//! `router.route_dedup_window_secs` is not moved again. When the move means
+//! withdrawing from a protocol or supplying to one, the improvement has to pay for
+//! that gas within `strategy.max_breakeven_days`.
+//!
+//! With `[[strategies]]` configured, the acting strategy carries the others as
+//! [`ShadowStrategy`]s. They decide on the same pools every cycle, but only the
+//! acting one's decision is carried out.
+
+use anyhow::Result;
+use ethers::core::types::U256;
+use log::{debug, info, warn};
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::agents::cross_chain_router::RouteReceipt;
+use crate::util::{Clock, SystemClock};
+use crate::approval::{ApprovalDecision, ApprovalRequest, Approver};
+use crate::agents::defi_optimizer::{DefiOptimizer, PoolData};
+use crate::agents::swap::SwapCosts;
+use crate::protocol_actions::ProtocolRegistry;
+use crate::rebalance;
+use crate::config::{Config, OptimizerConfig, RouterConfig, StrategyConfig, DEFAULT_STRATEGY};
+use crate::decision_log::{self, DecisionLog, DecisionRecord};
+use crate::kill_switch::KillSwitch;
+use crate::portfolio::{PortfolioState, TOKEN};
+use crate::recent_actions::{route_key, same_amount, RecentActions};
+use crate::report::{CycleDecision, DecisionInputs, ScoredPool, StrategyDecision, TransferSizing};
+use crate::storage::Store;
+use crate::units::TokenAmount;
+
+pub struct Strategy {
+    name: String,
+    min_apy_improvement: f64,
+    cooldown_secs: u64,
+    transfer_amount: Option<TokenAmount>,
+    reserve: TokenAmount,
+    deploy_fraction: f64,
+    max_breakeven_days: f64,
+    min_amount: TokenAmount,
+    max_amount: TokenAmount,
+    /// Bridge minimum per source chain.
+    chain_minimums: HashMap<String, TokenAmount>,
+    store: Arc<dyn Store>,
+    kill_switch: KillSwitch,
+    approver: Approver,
+    decisions: DecisionLog,
+    recent_actions: RecentActions,
+    dedup_window: Duration,
+    shadows: Vec<ShadowStrategy>,
+    swap_costs: Option<Arc<SwapCosts>>,
+    protocols: Option<Arc<ProtocolRegistry>>,
+    clock: Arc<dyn Clock>,
+}
+
+/// A strategy that decides on the acting one's pools every cycle without acting.
+pub struct ShadowStrategy {
+    optimizer: DefiOptimizer,
+    strategy: Strategy,
+}
+
+impl ShadowStrategy {
+    /// Ranks pools with the filters and scoring of `optimizer`; its data source is unused.
+    pub fn new(optimizer: &OptimizerConfig, strategy: Strategy) -> Self {
+        Self { optimizer: DefiOptimizer::from_config(optimizer), strategy }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.strategy.name
+    }
+
+    /// What this strategy would do with `pools`, every pool the source returned.
+    pub async fn evaluate(&self, pools: &[PoolData], portfolio: &PortfolioState, balance: U256) -> Result<StrategyDecision> {
+        let Ok(ranked) = self.optimizer.rank(pools) else {
+            let reason = "no pool passes the filters".to_string();
+            return Ok(StrategyDecision {
+                name: self.name().to_string(),
+                active: false,
+                best_pool: None,
+                decision: CycleDecision::Blocked(reason.clone()),
+                reason,
+                inputs: None,
+            });
+        };
+        let (decision, inputs) = self.strategy.evaluate(&ranked, portfolio, balance).await?;
+        Ok(StrategyDecision {
+            name: self.name().to_string(),
+            active: false,
+            best_pool: Some(ScoredPool { score: self.optimizer.score(ranked[0]), pool: ranked[0].clone() }),
+            reason: decision_log::explain(&decision, &inputs),
+            decision,
+            inputs: Some(inputs),
+        })
+    }
+}
+
+impl Strategy {
+    pub fn from_config(config: &StrategyConfig, router: &RouterConfig, store: Arc<dyn Store>) -> Self {
+        Self {
+            name: DEFAULT_STRATEGY.to_string(),
+            min_apy_improvement: config.min_apy_improvement,
+            cooldown_secs: config.bridge_cooldown_secs,
+            transfer_amount: config.transfer_amount.map(TokenAmount::native_from_f64),
+            reserve: TokenAmount::native(config.reserve_wei()),
+            deploy_fraction: config.max_deploy_fraction,
+            max_breakeven_days: config.max_breakeven_days,
+            min_amount: TokenAmount::native_from_f64(router.min_amount),
+            max_amount: TokenAmount::native_from_f64(router.max_amount),
+            chain_minimums: router
+                .chains
+                .iter()
+                .map(|chain| (chain.name.clone(), TokenAmount::native_from_f64(chain.min_transfer)))
+                .collect(),
+            store,
+            kill_switch: KillSwitch::default(),
+            approver: Approver::auto(),
+            decisions: DecisionLog::default(),
+            recent_actions: RecentActions::new(router.route_dedup_window()),
+            dedup_window: router.route_dedup_window(),
+            shadows: Vec::new(),
+            swap_costs: None,
+            protocols: None,
+            clock: SystemClock::shared(),
+        }
+    }
+
+    /// The strategy that acts under `config`, carrying every other configured one as a
+    /// shadow: the `[[strategies]]` entries, and `[strategy]` when one of them is active.
+    pub fn acting_from_config(config: &Config, store: Arc<dyn Store>) -> Self {
+        let active = config.active_strategy().map_or(DEFAULT_STRATEGY, |named| named.name.as_str());
+        let mut strategy = Self::from_config(config.acting_strategy(), &config.router, store.clone());
+        strategy.name = active.to_string();
+        let mut shadows = Vec::new();
+        if active != DEFAULT_STRATEGY {
+            let default = Self::from_config(&config.strategy, &config.router, store.clone());
+            shadows.push(ShadowStrategy::new(&config.optimizer, default));
+        }
+        for named in config.strategies.iter().filter(|named| named.name != active) {
+            let mut shadow = Self::from_config(&named.strategy, &config.router, store.clone());
+            shadow.name = named.name.clone();
+            shadows.push(ShadowStrategy::new(&named.optimizer.apply(&config.optimizer), shadow));
+        }
+        strategy.shadows = shadows;
+        strategy
+    }
+
+    /// `[[strategies]]` name of this strategy, or `"default"`.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The configured strategies that only record their decisions.
+    pub fn shadows(&self) -> &[ShadowStrategy] {
+        &self.shadows
+    }
+
+    /// While `kill_switch` is on every evaluation is `Blocked("paused")`.
+    pub fn set_kill_switch(&mut self, kill_switch: KillSwitch) {
+        for shadow in &mut self.shadows {
+            shadow.strategy.set_kill_switch(kill_switch.clone());
+        }
+        self.kill_switch = kill_switch;
+    }
+
+    /// Asks `approver` before each live route; the default approves without asking.
+    pub fn set_approver(&mut self, approver: Approver) {
+        self.approver = approver;
+    }
+
+    /// Appends each recorded decision to `decisions`; nothing is kept by default.
+    pub fn set_decision_log(&mut self, decisions: DecisionLog) {
+        self.decisions = decisions;
+    }
+
+    /// Looks up completed routes in `recent_actions`, shared with the router.
+    /// Reads the time for cooldowns and dedup windows from `clock`, shadows included.
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        for shadow in &mut self.shadows {
+            shadow.strategy.set_clock(clock.clone());
+        }
+        self.clock = clock;
+    }
+
+    pub fn set_recent_actions(&mut self, recent_actions: RecentActions) {
+        for shadow in &mut self.shadows {
+            shadow.strategy.set_recent_actions(recent_actions.clone());
+        }
+        self.recent_actions = recent_actions;
+    }
+
+    pub fn logs_decisions(&self) -> bool {
+        self.decisions.is_enabled()
+    }
+
+    pub fn record_decision(&self, record: &DecisionRecord) {
+        self.decisions.append(record);
+    }
+
+    /// Prices the swap into pools whose underlying is not the held asset.
+    pub fn set_swap_costs(&mut self, swap_costs: Arc<SwapCosts>) {
+        self.swap_costs = Some(swap_costs);
+    }
+
+    /// Round-trip swap cost of moving `amount` into `pool`, in ETH; `None` when no swap
+    /// is needed or it could not be quoted.
+    pub async fn swap_cost(&self, pool: &PoolData, amount: f64) -> Option<f64> {
+        let swap_costs = self.swap_costs.as_ref()?;
+        swap_costs
+            .round_trip_cost(pool, amount)
+            .await
+            .inspect_err(|e| warn!("Failed to quote the swap into {} on {}: {:#}", pool.protocol, pool.chain, e))
+            .ok()
+            .flatten()
+    }
+
+    /// Builders for the protocols the agent can enter; without them every pool is
+    /// suggest-only.
+    pub fn set_protocols(&mut self, protocols: Arc<ProtocolRegistry>) {
+        self.protocols = Some(protocols);
+    }
+
+    /// Name of the action builder that can enter `pool`; `None` when it is suggest-only.
+    pub fn action_for(&self, pool: &PoolData) -> Option<&'static str> {
+        self.protocols.as_ref()?.builder_for(pool)
+    }
+
+    pub fn protocols(&self) -> Option<&ProtocolRegistry> {
+        self.protocols.as_deref()
+    }
+
+    /// Whether deciding needs the gas price: for the decision log, or to weigh the gas
+    /// of exiting and entering protocols.
+    pub fn needs_gas_price(&self) -> bool {
+        self.logs_decisions() || (self.protocols.is_some() && self.max_breakeven_days > 0.0)
+    }
+
+    /// The operator's answer to `request`.
+    pub async fn approve(&self, request: &ApprovalRequest) -> ApprovalDecision {
+        self.approver.decide(request).await
+    }
+
+    /// Weighs `pools[0]`, the best ranked pool, against the chain holding most of the
+    /// portfolio. `RouteInitiated` means the route should be started with `inputs.amount`.
+    pub async fn evaluate(
+        &self,
+        pools: &[impl Borrow<PoolData> + Sync],
+        portfolio: &PortfolioState,
+        balance: U256,
+    ) -> Result<(CycleDecision, DecisionInputs)> {
+        let inputs = self.observe(pools, portfolio, balance).await?;
+        Ok(self.decide(inputs))
+    }
+
+    /// Gathers what [`Strategy::decide`] needs: the pools, where the funds are, the kill
+    /// switch and the last routes in the candidate's direction.
+    pub async fn observe(
+        &self,
+        pools: &[impl Borrow<PoolData> + Sync],
+        portfolio: &PortfolioState,
+        balance: U256,
+    ) -> Result<DecisionInputs> {
+        let current_chain = portfolio.current_chain();
+        let candidate = pools[0].borrow();
+        let observed_at = self.clock.now_utc();
+        let (last_route_at, recent_route, rebalance_gas) = if candidate.chain == current_chain {
+            (None, None, None)
+        } else {
+            let key = route_key(current_chain, &candidate.chain, TOKEN);
+            let rebalance_gas = self.protocols.as_ref().map(|registry| {
+                let exit = portfolio.settled(current_chain).map_or(0, |position| registry.exit_gas(position));
+                exit + registry.entry_gas(candidate)
+            });
+            (
+                self.store.last_route_at(current_chain, &candidate.chain).await?,
+                self.recent_actions.within(&key, self.dedup_window, observed_at),
+                rebalance_gas.filter(|gas| *gas > 0),
+            )
+        };
+        Ok(DecisionInputs {
+            current_chain: current_chain.to_string(),
+            current_apy: pools
+                .iter()
+                .map(Borrow::borrow)
+                .filter(|p: &&PoolData| p.chain == current_chain)
+                .filter_map(|p| p.apy)
+                .reduce(f64::max),
+            candidate_chain: candidate.chain.clone(),
+            candidate_apy: candidate.apy.unwrap_or(0.0),
+            candidate_tvl: candidate.tvl,
+            balance,
+            paused: self.kill_switch.check().is_some(),
+            pending_route: portfolio.pending_routes().next().map(|id| id.to_string()),
+            last_route_at,
+            observed_at,
+            recent_route,
+            rebalance_gas,
+            // The caller fills it in when it has one
+            gas_price_gwei: None,
+            min_apy_improvement: self.min_apy_improvement,
+            cooldown_remaining_secs: None,
+            amount: None,
+            sizing: None,
+            breakeven_days: None,
+        })
+    }
+
+    /// Decides on observed `inputs` alone, so a recorded set always gets the same
+    /// answer under the same config. Returns them with the derived fields filled in.
+    pub fn decide(&self, mut inputs: DecisionInputs) -> (CycleDecision, DecisionInputs) {
+        inputs.min_apy_improvement = self.min_apy_improvement;
+        inputs.cooldown_remaining_secs = None;
+        inputs.amount = None;
+        inputs.sizing = None;
+        inputs.breakeven_days = None;
+
+        // The inputs above are still reported, so a paused agent keeps its telemetry
+        if inputs.paused {
+            return (CycleDecision::Blocked("paused".to_string()), inputs);
+        }
+        if inputs.candidate_apy <= 0.0 || inputs.candidate_tvl <= 0.0 {
+            return (CycleDecision::NotWorthMoving, inputs);
+        }
+        if inputs.candidate_chain == inputs.current_chain {
+            return (CycleDecision::NoAction, inputs);
+        }
+        if let Some(route_id) = &inputs.pending_route {
+            return (CycleDecision::Blocked(format!("route {} still in flight", route_id)), inputs);
+        }
+        let improvement = inputs.candidate_apy - inputs.current_apy.unwrap_or(0.0);
+        if improvement < self.min_apy_improvement {
+            debug!(
+                "{} improves on {} by {:.2} APY points, below the {:.2} required",
+                inputs.candidate_chain, inputs.current_chain, improvement, self.min_apy_improvement
+            );
+            return (CycleDecision::NoAction, inputs);
+        }
+
+        if let Some(last) = inputs.last_route_at {
+            let elapsed = inputs.observed_at.saturating_sub(last);
+            if elapsed < self.cooldown_secs {
+                let remaining = self.cooldown_secs - elapsed;
+                inputs.cooldown_remaining_secs = Some(remaining);
+                let reason = format!(
+                    "bridge cooldown: {}s left since the last {} -> {} route",
+                    remaining, inputs.current_chain, inputs.candidate_chain
+                );
+                return (CycleDecision::Blocked(reason), inputs);
+            }
+        }
+
+        let sizing = self.size_transfer(inputs.balance, &inputs.current_chain);
+        inputs.amount = Some(sizing.amount.as_f64());
+        let worth_moving = sizing.is_worth_moving();
+        inputs.sizing = Some(sizing);
+        if !worth_moving {
+            return (CycleDecision::NotWorthMoving, inputs);
+        }
+        if let (Some(gas), Some(gwei)) = (inputs.rebalance_gas, inputs.gas_price_gwei) {
+            inputs.breakeven_days =
+                rebalance::breakeven_days(gas, gwei, inputs.amount.unwrap_or_default(), improvement);
+            if let Some(days) = inputs.breakeven_days.filter(|days| self.max_breakeven_days > 0.0 && *days > self.max_breakeven_days) {
+                let reason = format!(
+                    "exiting and entering protocols costs {} gas, {:.1} days of the extra yield; {} allowed",
+                    gas, days, self.max_breakeven_days
+                );
+                return (CycleDecision::Blocked(reason), inputs);
+            }
+        }
+        if let Some(recent) = &inputs.recent_route {
+            let ago = inputs.observed_at.saturating_sub(recent.at);
+            let amount = inputs.amount.unwrap_or_default();
+            if ago < self.dedup_window.as_secs() && recent.amount.is_some_and(|last| same_amount(last, amount)) {
+                let reason = format!(
+                    "identical route of {} from {} to {} completed {}s ago",
+                    amount, inputs.current_chain, inputs.candidate_chain, ago
+                );
+                return (CycleDecision::Blocked(reason), inputs);
+            }
+        }
+        (CycleDecision::RouteInitiated, inputs)
+    }
+
+    /// The history the strategy decides on.
+    pub fn store(&self) -> &dyn Store {
+        self.store.as_ref()
+    }
+
+    /// Sizes a route out of `source_chain` from the `balance` available there.
+    pub fn size_transfer(&self, balance: U256, source_chain: &str) -> TransferSizing {
+        let available = TokenAmount::native(balance);
+        let deployable = balance.saturating_sub(self.reserve.raw);
+        let share = TokenAmount::native(deployable).mul_fraction(self.deploy_fraction).raw;
+        let amount = match self.transfer_amount {
+            Some(fixed) => fixed.raw.min(deployable),
+            None => share,
+        }
+        .min(self.max_amount.raw);
+        let minimum = self
+            .chain_minimums
+            .get(source_chain)
+            .map_or(self.min_amount, |&chain| chain.max(self.min_amount));
+        let sizing = TransferSizing {
+            available,
+            reserve: self.reserve,
+            deploy_fraction: self.deploy_fraction,
+            fixed: self.transfer_amount,
+            maximum: self.max_amount,
+            minimum,
+            amount: TokenAmount::native(amount),
+        };
+        info!(
+            "Transfer sizing on {}: available {}, reserve {}, deploy fraction {}, fixed {}, max {}, min {} -> {}{}",
+            source_chain,
+            sizing.available,
+            sizing.reserve,
+            sizing.deploy_fraction,
+            sizing.fixed.map(|f| f.to_string()).unwrap_or_else(|| "none".to_string()),
+            sizing.maximum,
+            sizing.minimum,
+            sizing.amount,
+            if sizing.is_worth_moving() { "" } else { " (below the minimum, not moving)" }
+        );
+        sizing
+    }
+
+    /// Stores a started route right away, so the cooldown applies even before the
+    /// route phase next persists the tracker.
+    pub async fn route_started(&self, route: &RouteReceipt) -> Result<()> {
+        self.store.upsert_route(route, self.clock.now_utc()).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agents::cross_chain_router::RouteState;
+    use crate::recent_actions::RecentAction;
+    use crate::storage::tests::route;
+    use crate::storage::MemoryStore;
+    use crate::util::MockClock;
+
+    fn pool(chain: &str, apy: f64) -> PoolData {
+        PoolData {
+            protocol: "Aave".to_string(),
+            chain: chain.to_string(),
+            apy: Some(apy),
+            tvl: 1_000_000.0,
+        }
+    }
+
+    fn on(chain: &str) -> PortfolioState {
+        let mut portfolio = PortfolioState::default();
+        portfolio.reconcile(chain, 1.0, 0.05, 0);
+        portfolio
+    }
+
+    fn strategy(store: Arc<dyn Store>) -> Strategy {
+        Strategy::from_config(&StrategyConfig::default(), &RouterConfig::default(), store)
+    }
+
+    #[tokio::test]
+    async fn test_small_improvement_is_no_action() {
+        let strategy = strategy(Arc::new(MemoryStore::new()));
+        let pools = [pool("Arbitrum", 5.5), pool("Ethereum", 4.8)];
+
+        let (decision, inputs) = strategy.evaluate(&pools, &on("Ethereum"), U256::exp10(18)).await.unwrap();
+        assert_eq!(decision, CycleDecision::NoAction);
+        assert_eq!(inputs.current_apy, Some(4.8));
+        assert_eq!(inputs.candidate_apy, 5.5);
+        assert_eq!(inputs.amount, None);
+
+        // Already on the best chain
+        let (decision, _) = strategy.evaluate(&pools, &on("Arbitrum"), U256::exp10(18)).await.unwrap();
+        assert_eq!(decision, CycleDecision::NoAction);
+    }
+
+    #[tokio::test]
+    async fn test_cooldown_blocks_same_direction() {
+        let store: Arc<dyn Store> = Arc::new(MemoryStore::new());
+        let mut strategy = strategy(store.clone());
+        let clock = MockClock::new(1_700_000_000);
+        strategy.set_clock(clock.shared());
+        let mut last = route("r-1", RouteState::Completed);
+        last.target_chain = "Optimism".to_string();
+        strategy.route_started(&last).await.unwrap();
+        clock.advance(Duration::from_secs(60 * 60));
+
+        let optimism = [pool("Optimism", 9.0), pool("Ethereum", 4.8)];
+        let (decision, inputs) = strategy.evaluate(&optimism, &on("Ethereum"), U256::exp10(18)).await.unwrap();
+        assert!(matches!(decision, CycleDecision::Blocked(ref reason) if reason.contains("cooldown")), "{:?}", decision);
+        assert_eq!(inputs.cooldown_remaining_secs, Some(23 * 60 * 60));
+
+        // The same direction goes ahead once the cooldown is over
+        clock.advance(Duration::from_secs(23 * 60 * 60));
+        let (decision, _) = strategy.evaluate(&optimism, &on("Ethereum"), U256::exp10(18)).await.unwrap();
+        assert_eq!(decision, CycleDecision::RouteInitiated);
+
+        // A route in another direction does not count
+        let (decision, _) = strategy
+            .evaluate(&[pool("Arbitrum", 9.0), pool("Ethereum", 4.8)], &on("Ethereum"), U256::exp10(18))
+            .await
+            .unwrap();
+        assert_eq!(decision, CycleDecision::RouteInitiated);
+
+        // Nor does one that started before the cooldown
+        store.upsert_route(&route("r-0", RouteState::Completed), 1_000).await.unwrap();
+        let (decision, inputs) = strategy
+            .evaluate(&[pool("Arbitrum", 9.0), pool("Ethereum", 4.8)], &on("Ethereum"), U256::exp10(18))
+            .await
+            .unwrap();
+        assert_eq!(decision, CycleDecision::RouteInitiated);
+        assert_eq!(inputs.cooldown_remaining_secs, None);
+    }
+
+    #[tokio::test]
+    async fn test_qualifying_move_is_sized_from_balance() {
+        let strategy = strategy(Arc::new(MemoryStore::new()));
+        let pools = [pool("Arbitrum", 7.0), pool("Ethereum", 4.8)];
+
+        // 90% of 1.5 ETH minus the 0.01 ETH reserve
+        let (decision, inputs) = strategy.evaluate(&pools, &on("Ethereum"), U256::exp10(18) * 3 / 2).await.unwrap();
+        assert_eq!(decision, CycleDecision::RouteInitiated);
+        assert_eq!(inputs.amount, Some(1.341));
+        let sizing = inputs.sizing.unwrap();
+        assert_eq!(sizing.amount.to_string(), "1.341000000000000000");
+        assert_eq!(sizing.minimum, TokenAmount::native_from_f64(0.1));
+
+        // Too little left after the reserve: not worth a bridge
+        let (decision, inputs) = strategy.evaluate(&pools, &on("Ethereum"), U256::exp10(16) * 5).await.unwrap();
+        assert_eq!(decision, CycleDecision::NotWorthMoving);
+        assert_eq!(inputs.amount, Some(0.036));
+        assert!(!strategy.size_transfer(U256::exp10(15), "Ethereum").is_worth_moving());
+
+        // Nothing moves while an earlier route is still in flight
+        let mut portfolio = on("Ethereum");
+        portfolio.route_started(&route("r-1", RouteState::Pending), None, 0);
+        let (decision, _) = strategy.evaluate(&pools, &portfolio, U256::exp10(18)).await.unwrap();
+        assert_eq!(decision, CycleDecision::Blocked("route r-1 still in flight".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_identical_recent_route_is_blocked() {
+        let mut strategy = strategy(Arc::new(MemoryStore::new()));
+        let actions = RecentActions::new(Duration::from_secs(24 * 60 * 60));
+        strategy.set_recent_actions(actions.clone());
+        let clock = MockClock::new(1_700_000_000);
+        strategy.set_clock(clock.shared());
+        let pools = [pool("Arbitrum", 7.0), pool("Ethereum", 4.8)];
+        let key = route_key("Ethereum", "Arbitrum", TOKEN);
+
+        // 90% of 1 ETH minus the reserve, completed a minute ago
+        actions.record(RecentAction { key, at: clock.now_utc(), amount: Some(0.89) }).await;
+        clock.advance(Duration::from_secs(60));
+        let (decision, inputs) = strategy.evaluate(&pools, &on("Ethereum"), U256::exp10(18)).await.unwrap();
+        assert!(
+            matches!(decision, CycleDecision::Blocked(ref reason) if reason.starts_with("identical route of 0.891 from Ethereum to Arbitrum")),
+            "{:?}",
+            decision
+        );
+        assert_eq!(inputs.recent_route.unwrap().amount, Some(0.89));
+
+        // A different amount goes ahead
+        let (decision, _) = strategy.evaluate(&pools, &on("Ethereum"), U256::exp10(18) * 2).await.unwrap();
+        assert_eq!(decision, CycleDecision::RouteInitiated);
+
+        // And so does the same amount once the window has passed
+        clock.advance(Duration::from_secs(24 * 60 * 60 - 60));
+        let (decision, inputs) = strategy.evaluate(&pools, &on("Ethereum"), U256::exp10(18)).await.unwrap();
+        assert_eq!(decision, CycleDecision::RouteInitiated);
+        assert_eq!(inputs.recent_route, None);
+    }
+
+    #[tokio::test]
+    async fn test_protocol_gas_must_pay_back_in_time() {
+        let mut strategy = strategy(Arc::new(MemoryStore::new()));
+        let registry = ProtocolRegistry::from_config(&crate::config::ActionsConfig::default()).unwrap();
+        strategy.set_protocols(Arc::new(registry));
+        let pools = [pool("Arbitrum", 7.0), pool("Ethereum", 4.8)];
+        let mut portfolio = on("Ethereum");
+        portfolio.entered("Ethereum", "Aave");
+
+        // Withdrawing from Aave; no market to enter on Arbitrum
+        let observed = strategy.observe(&pools, &portfolio, U256::exp10(18)).await.unwrap();
+        assert_eq!(observed.rebalance_gas, Some(250_000));
+        let at = |gwei| DecisionInputs { gas_price_gwei: Some(gwei), ..observed.clone() };
+
+        // 0.005 ETH of gas against 2.2 points on 0.891 ETH: about 93 days
+        let (decision, inputs) = strategy.decide(at(20.0));
+        assert!(matches!(decision, CycleDecision::Blocked(ref reason) if reason.contains("250000 gas")), "{:?}", decision);
+        assert!((inputs.breakeven_days.unwrap() - 93.1).abs() < 0.1, "{:?}", inputs.breakeven_days);
+        let (decision, inputs) = strategy.decide(at(2.0));
+        assert_eq!(decision, CycleDecision::RouteInitiated);
+        assert!(inputs.breakeven_days.unwrap() < 10.0);
+
+        // Idle funds need no exit
+        let observed = strategy.observe(&pools, &on("Ethereum"), U256::exp10(18)).await.unwrap();
+        assert_eq!(observed.rebalance_gas, None);
+    }
+
+    #[test]
+    fn test_sizing_clamps_to_limits() {
+        let store: Arc<dyn Store> = Arc::new(MemoryStore::new());
+        let strategy = strategy(store.clone());
+        let whale = U256::exp10(18) * 5_000;
+        assert_eq!(strategy.size_transfer(whale, "Ethereum").amount, TokenAmount::native_from_f64(1000.0));
+
+        // A fixed amount is capped by the router maximum and by what the balance allows
+        let config = StrategyConfig { transfer_amount: Some(5_000.0), ..StrategyConfig::default() };
+        let fixed = Strategy::from_config(&config, &RouterConfig::default(), store.clone());
+        assert_eq!(fixed.size_transfer(whale, "Ethereum").amount, TokenAmount::native_from_f64(1000.0));
+        assert_eq!(fixed.size_transfer(U256::exp10(18), "Ethereum").amount, TokenAmount::native_from_f64(0.99));
+
+        // A source chain with a higher bridge minimum raises the bar
+        let mut router = RouterConfig::default();
+        router.chains[0].min_transfer = 2.0;
+        let strict = Strategy::from_config(&StrategyConfig::default(), &router, store);
+        let sizing = strict.size_transfer(U256::exp10(18), &router.chains[0].name);
+        assert_eq!(sizing.minimum, TokenAmount::native_from_f64(2.0));
+        assert!(!sizing.is_worth_moving());
+    }
+}
@@ -0,0 +1,277 @@
+//! Restarts background tasks that panic.
+//!
+//! [`Supervisor::supervise`] runs a task built by a factory and, when it panics, logs the
+//! panic with its backtrace, raises an internal error event and builds a fresh one after
+//! an exponential backoff. A task that keeps panicking is left stopped and makes
+//! `/healthz` fail. [`Supervisor::catch`] does the same for work that cannot be
+//! respawned, like a monitoring cycle, turning one panic into an error.
+
+use anyhow::{anyhow, Result};
+use futures::FutureExt;
+use log::{debug, error, warn};
+use serde::Serialize;
+use std::any::Any;
+use std::backtrace::Backtrace;
+use std::collections::{BTreeSet, VecDeque};
+use std::future::Future;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex, Once};
+use tokio::task::JoinHandle;
+use tokio::time::{sleep, Duration, Instant};
+
+use crate::errors::ErrorCategory;
+use crate::notifier::{AgentEvent, EventSender};
+use crate::status::StatusHandle;
+
+const HOUR: Duration = Duration::from_secs(3600);
+
+tokio::task_local! {
+    /// Name of the supervised task being polled, for the panic hook.
+    static TASK: String;
+}
+
+/// How often and how fast a panicking task is restarted.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RestartPolicy {
+    /// Delay before the first restart; doubled for each one after it.
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// Restarts allowed in any hour before the task is left stopped.
+    pub max_restarts_per_hour: usize,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self {
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(300),
+            max_restarts_per_hour: 5,
+        }
+    }
+}
+
+/// What `/status` shows for a supervised task.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct TaskStatus {
+    /// Panics recovered from since startup.
+    pub restarts: u32,
+    /// Stopped for good after too many restarts.
+    pub down: bool,
+    pub last_panic: Option<String>,
+}
+
+#[derive(Clone)]
+pub struct Supervisor {
+    status: StatusHandle,
+    events: EventSender,
+    policy: RestartPolicy,
+    /// Names passed to [`Supervisor::catch`] whose last run panicked.
+    panicked: Arc<Mutex<BTreeSet<String>>>,
+}
+
+impl Supervisor {
+    pub fn new(status: StatusHandle, events: EventSender) -> Self {
+        Self::with_policy(status, events, RestartPolicy::default())
+    }
+
+    pub fn with_policy(status: StatusHandle, events: EventSender, policy: RestartPolicy) -> Self {
+        install_panic_hook();
+        Self {
+            status,
+            events,
+            policy,
+            panicked: Arc::default(),
+        }
+    }
+
+    /// Spawns `factory()` and builds a new task each time the last one panics. Returns
+    /// when a task finishes without panicking or the task is given up on.
+    pub fn supervise<F, Fut>(&self, name: impl Into<String>, mut factory: F) -> JoinHandle<()>
+    where
+        F: FnMut() -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let name = name.into();
+        let this = self.clone();
+        tokio::spawn(async move {
+            this.status.update(|s| {
+                s.tasks.entry(name.clone()).or_default();
+            });
+            let mut restarts = VecDeque::new();
+            let mut backoff = this.policy.initial_backoff;
+            loop {
+                let started = Instant::now();
+                let panic = match tokio::spawn(TASK.scope(name.clone(), factory())).await {
+                    Ok(()) => {
+                        debug!("Task {} finished", name);
+                        return;
+                    }
+                    Err(e) if e.is_panic() => panic_message(&*e.into_panic()),
+                    Err(_) => return,
+                };
+                // One that ran for a while before crashing starts over from the initial backoff
+                if started.elapsed() > this.policy.max_backoff {
+                    backoff = this.policy.initial_backoff;
+                }
+                let now = Instant::now();
+                while restarts.front().is_some_and(|at| now.duration_since(*at) >= HOUR) {
+                    restarts.pop_front();
+                }
+                this.events.emit(AgentEvent::ErrorRaised {
+                    category: ErrorCategory::Internal,
+                    detail: format!("Task {} panicked: {}", name, panic),
+                });
+                let down = restarts.len() >= this.policy.max_restarts_per_hour;
+                this.status.update(|s| {
+                    let task = s.tasks.entry(name.clone()).or_default();
+                    task.down = down;
+                    task.restarts += u32::from(!down);
+                    task.last_panic = Some(panic.clone());
+                });
+                if down {
+                    error!("Task {} panicked {} times within an hour; leaving it stopped", name, restarts.len() + 1);
+                    return;
+                }
+                restarts.push_back(now);
+                warn!("Restarting task {} in {:?}", name, backoff);
+                sleep(backoff).await;
+                backoff = (backoff * 2).min(this.policy.max_backoff);
+            }
+        })
+    }
+
+    /// Runs `work`, turning a panic into an error. If the previous run under `name`
+    /// panicked too the panic is passed on, as something is wrong beyond one bad run.
+    pub async fn catch<T>(&self, name: &str, work: impl Future<Output = Result<T>>) -> Result<T> {
+        match AssertUnwindSafe(TASK.scope(name.to_string(), work)).catch_unwind().await {
+            Ok(result) => {
+                self.panicked.lock().unwrap_or_else(|e| e.into_inner()).remove(name);
+                result
+            }
+            Err(payload) => {
+                let first = self.panicked.lock().unwrap_or_else(|e| e.into_inner()).insert(name.to_string());
+                if !first {
+                    error!("{} panicked twice in a row; giving up", name);
+                    panic::resume_unwind(payload);
+                }
+                let message = panic_message(&*payload);
+                self.status.update(|s| {
+                    let task = s.tasks.entry(name.to_string()).or_default();
+                    task.restarts += 1;
+                    task.last_panic = Some(message.clone());
+                });
+                Err(anyhow!("{} panicked: {}", name, message))
+            }
+        }
+    }
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "non-string panic payload".to_string())
+}
+
+/// Logs panics in supervised tasks with their name and a backtrace; others go to the
+/// hook that was installed before.
+fn install_panic_hook() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| match TASK.try_with(|name| name.clone()) {
+            Ok(name) => error!("Task {} {}\n{}", name, info, Backtrace::force_capture()),
+            Err(_) => previous(info),
+        }));
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::status::check_health;
+
+    fn policy() -> RestartPolicy {
+        RestartPolicy {
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(4),
+            max_restarts_per_hour: 3,
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_panicking_task_backs_off_then_stays_down() {
+        let status = StatusHandle::new();
+        status.update(|s| {
+            s.last_cycle_at = Some(crate::util::unix_now());
+            s.rpc_reachable = true;
+        });
+        let (events, mut rx) = EventSender::channel(8);
+        let supervisor = Supervisor::with_policy(status.clone(), events, policy());
+        let starts = Arc::new(Mutex::new(Vec::new()));
+
+        let recorded = starts.clone();
+        supervisor
+            .supervise("poller", move || {
+                recorded.lock().unwrap().push(Instant::now());
+                async { panic!("boom") }
+            })
+            .await
+            .unwrap();
+
+        let starts = starts.lock().unwrap();
+        let gaps: Vec<_> = starts.windows(2).map(|w| (w[1] - w[0]).as_secs()).collect();
+        assert_eq!(gaps, [1, 2, 4]);
+        let task = status.snapshot().tasks["poller"].clone();
+        assert_eq!(task, TaskStatus { restarts: 3, down: true, last_panic: Some("boom".to_string()) });
+        let health = check_health(&status.snapshot(), crate::util::unix_now(), HOUR);
+        assert!(!health.healthy);
+        assert_eq!(health.failed, ["task poller: down after 3 restarts (last panic: boom)"]);
+
+        let mut raised = 0;
+        while let Ok(event) = rx.try_recv() {
+            assert!(matches!(event, AgentEvent::ErrorRaised { category: ErrorCategory::Internal, .. }));
+            raised += 1;
+        }
+        assert_eq!(raised, 4);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_task_that_returns_is_not_restarted() {
+        let status = StatusHandle::new();
+        let supervisor = Supervisor::with_policy(status.clone(), EventSender::disabled(), policy());
+        let runs = Arc::new(Mutex::new(0));
+        let counted = runs.clone();
+        supervisor
+            .supervise("probes", move || {
+                *counted.lock().unwrap() += 1;
+                async {}
+            })
+            .await
+            .unwrap();
+        assert_eq!(*runs.lock().unwrap(), 1);
+        assert_eq!(status.snapshot().tasks["probes"], TaskStatus::default());
+    }
+
+    async fn bad_cycle() -> Result<()> {
+        panic!("bad cycle")
+    }
+
+    #[tokio::test]
+    async fn test_catch_recovers_once_then_passes_repeated_panics_on() {
+        let status = StatusHandle::new();
+        let supervisor = Supervisor::with_policy(status.clone(), EventSender::disabled(), policy());
+
+        let err = supervisor.catch("cycle", bad_cycle()).await.unwrap_err();
+        assert_eq!(err.to_string(), "cycle panicked: bad cycle");
+        assert_eq!(status.snapshot().tasks["cycle"].restarts, 1);
+
+        // A good run in between resets it
+        supervisor.catch("cycle", async { Ok(()) }).await.unwrap();
+        assert!(supervisor.catch("cycle", bad_cycle()).await.is_err());
+
+        let repeated = AssertUnwindSafe(supervisor.catch("cycle", bad_cycle())).catch_unwind().await;
+        assert!(repeated.is_err());
+    }
+}
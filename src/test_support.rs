@@ -0,0 +1,180 @@
+//! Local Anvil forks for tests that need real contracts.
+//!
+//! [`AnvilFork`] starts `anvil` forking the chain behind a given RPC URL on a free
+//! port, and stops it when dropped. On the fork any account can be funded with
+//! `anvil_setBalance` and sent from with `anvil_impersonateAccount`, so a Safe that
+//! exists on the forked chain can be driven without its owners' keys.
+//!
+//! Tests using a fork are `#[ignore]`d and named `fork_*`; run them with
+//! `ASAM_FORK_URL` set and `anvil` on the `PATH`:
+//!
+//! ```text
+//! ASAM_FORK_URL=https://eth.llamarpc.com cargo test -- --ignored fork_
+//! ```
+//!
+//! [`AnvilFork::from_env`] returns `None` when either is missing, and the tests
+//! return early instead of failing.
+
+use anyhow::{Context, Result};
+use ethers::core::types::{Address, TransactionReceipt, TransactionRequest, U256};
+use ethers::providers::Middleware;
+use log::{debug, warn};
+use std::process::{Child, Command, Stdio};
+use std::time::Duration;
+use thiserror::Error;
+
+use crate::rpc::{FailoverClient, RpcProvider};
+use crate::secret::redact_url;
+
+/// RPC URL of the chain to fork.
+pub const FORK_URL_ENV: &str = "ASAM_FORK_URL";
+/// Path of the `anvil` binary, when it is not on the `PATH`.
+pub const ANVIL_BIN_ENV: &str = "ASAM_ANVIL_BIN";
+
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Error, Debug)]
+#[non_exhaustive]
+pub enum AnvilError {
+    #[error("anvil not found at '{0}'; install Foundry or set ASAM_ANVIL_BIN")]
+    NotInstalled(String),
+    #[error("anvil exited before serving requests: {0}")]
+    Exited(String),
+    #[error("anvil did not answer within {0:?}")]
+    Timeout(Duration),
+}
+
+/// A running `anvil --fork-url`, killed on drop.
+pub struct AnvilFork {
+    child: Child,
+    url: String,
+    provider: RpcProvider,
+}
+
+impl AnvilFork {
+    /// Forks `fork_url` with the `anvil` from `ASAM_ANVIL_BIN` or the `PATH`.
+    pub async fn spawn(fork_url: &str) -> Result<Self> {
+        let bin = std::env::var(ANVIL_BIN_ENV).unwrap_or_else(|_| "anvil".to_string());
+        Self::spawn_with(&bin, fork_url).await
+    }
+
+    /// Forks `fork_url` with the `anvil` binary at `bin`, and waits until it answers.
+    pub async fn spawn_with(bin: &str, fork_url: &str) -> Result<Self> {
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .and_then(|listener| listener.local_addr())
+            .context("Failed to find a free port for anvil")?
+            .port();
+        let child = Command::new(bin)
+            .args(["--fork-url", fork_url, "--port", &port.to_string(), "--silent"])
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| match e.kind() {
+                std::io::ErrorKind::NotFound => AnvilError::NotInstalled(bin.to_string()).into(),
+                _ => anyhow::Error::new(e).context(format!("Failed to start {}", bin)),
+            })?;
+        let url = format!("http://127.0.0.1:{}", port);
+        let (provider, _) = FailoverClient::new(&[&url], Duration::from_secs(30), Duration::from_secs(60))?.into_provider();
+        let mut fork = Self { child, url, provider };
+        fork.wait_ready().await?;
+        debug!("anvil forking {} at {}", redact_url(fork_url), fork.url);
+        Ok(fork)
+    }
+
+    /// A fork of `ASAM_FORK_URL`; `None`, with a warning, when the variable is unset
+    /// or anvil is not installed.
+    pub async fn from_env() -> Result<Option<Self>> {
+        let Ok(fork_url) = std::env::var(FORK_URL_ENV) else {
+            warn!("{} is not set, skipping", FORK_URL_ENV);
+            return Ok(None);
+        };
+        match Self::spawn(&fork_url).await {
+            Ok(fork) => Ok(Some(fork)),
+            Err(e) if matches!(e.downcast_ref(), Some(AnvilError::NotInstalled(_))) => {
+                warn!("{:#}, skipping", e);
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn wait_ready(&mut self) -> Result<()> {
+        let deadline = tokio::time::Instant::now() + STARTUP_TIMEOUT;
+        loop {
+            if let Some(status) = self.child.try_wait()? {
+                let mut stderr = String::new();
+                if let Some(mut pipe) = self.child.stderr.take() {
+                    std::io::Read::read_to_string(&mut pipe, &mut stderr).ok();
+                }
+                return Err(AnvilError::Exited(format!("{}: {}", status, stderr.trim())).into());
+            }
+            if self.provider.get_chainid().await.is_ok() {
+                return Ok(());
+            }
+            if tokio::time::Instant::now() >= deadline {
+                return Err(AnvilError::Timeout(STARTUP_TIMEOUT).into());
+            }
+            tokio::time::sleep(Duration::from_millis(200)).await;
+        }
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// A provider for the fork, e.g. to build a [`SafeManager`](crate::agents::safe_manager::SafeManager) on.
+    pub fn provider(&self) -> RpcProvider {
+        self.provider.clone()
+    }
+
+    pub async fn set_balance(&self, address: Address, wei: U256) -> Result<()> {
+        self.provider
+            .request::<_, ()>("anvil_setBalance", (address, wei))
+            .await
+            .context("anvil_setBalance failed")
+    }
+
+    /// Lets [`send_as`](Self::send_as) send from `address` without its key.
+    pub async fn impersonate(&self, address: Address) -> Result<()> {
+        self.provider
+            .request::<_, ()>("anvil_impersonateAccount", [address])
+            .await
+            .context("anvil_impersonateAccount failed")
+    }
+
+    pub async fn stop_impersonating(&self, address: Address) -> Result<()> {
+        self.provider
+            .request::<_, ()>("anvil_stopImpersonatingAccount", [address])
+            .await
+            .context("anvil_stopImpersonatingAccount failed")
+    }
+
+    /// Sends `tx` from the impersonated `from` and waits for it to be mined.
+    pub async fn send_as(&self, from: Address, tx: TransactionRequest) -> Result<TransactionReceipt> {
+        self.provider
+            .send_transaction(tx.from(from), None)
+            .await
+            .context("eth_sendTransaction failed")?
+            .await
+            .context("Failed to fetch the receipt")?
+            .context("Transaction was dropped")
+    }
+}
+
+impl Drop for AnvilFork {
+    fn drop(&mut self) {
+        self.child.kill().ok();
+        self.child.wait().ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_missing_anvil_is_reported() {
+        let err = AnvilFork::spawn_with("/nonexistent/anvil", "http://localhost:8545").await.err().unwrap();
+        assert!(matches!(err.downcast_ref(), Some(AnvilError::NotInstalled(bin)) if bin == "/nonexistent/anvil"));
+    }
+}
@@ -0,0 +1,188 @@
+//! Exact decimal formatting for on-chain amounts.
+//!
+//! Amounts are formatted from their integer string form, so any `U256` works
+//! and nothing is rounded through `f64`.
+//!
+//! [`TokenAmount`] arithmetic never panics: conversions from `f64` saturate, a
+//! fraction of an amount is never more than the amount, and the display form holds
+//! every digit of the raw value.
+
+use ethers::core::types::{U256, U512};
+use serde::{Serialize, Serializer};
+use std::fmt;
+
+const ETH_DECIMALS: u32 = 18;
+/// Resolution of [`TokenAmount::mul_fraction`]: fractions are rounded to 1/10000.
+const FRACTION_SCALE: u64 = 10_000;
+
+/// Formats `value` with `decimals` implied decimal places, keeping at most
+/// `precision` fractional digits. Extra digits are truncated, never rounded up,
+/// so a displayed balance is never more than the real one.
+pub fn format_units_prec(value: U256, decimals: u32, precision: usize) -> String {
+    let digits = value.to_string();
+    let decimals = decimals as usize;
+    let padded = if digits.len() <= decimals {
+        format!("{}{}", "0".repeat(decimals + 1 - digits.len()), digits)
+    } else {
+        digits
+    };
+    let (whole, fraction) = padded.split_at(padded.len() - decimals);
+    let fraction = &fraction[..precision.min(fraction.len())];
+    if fraction.is_empty() {
+        whole.to_string()
+    } else {
+        format!("{}.{}", whole, fraction)
+    }
+}
+
+/// Exact ETH amount for a wei value, e.g. "1.500000000000000000".
+pub fn format_eth(wei: U256) -> String {
+    format_units_prec(wei, ETH_DECIMALS, ETH_DECIMALS as usize)
+}
+
+/// An amount of a token in its smallest unit, with the token's decimals.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TokenAmount {
+    pub raw: U256,
+    pub decimals: u32,
+}
+
+impl TokenAmount {
+    /// An amount of the chain's native token, in wei.
+    pub fn native(wei: U256) -> Self {
+        Self { raw: wei, decimals: ETH_DECIMALS }
+    }
+
+    /// Native amount from a decimal number of whole tokens, as used in the config.
+    /// Negative and non-finite amounts become zero, amounts beyond `U256` wei the
+    /// largest one, and digits below a wei are dropped.
+    pub fn native_from_f64(amount: f64) -> Self {
+        if !(amount.is_finite() && amount > 0.0) {
+            return Self::native(U256::zero());
+        }
+        // parse_ether panics rather than failing on overflow; the margin covers the
+        // rounding of the product near 2^256
+        if amount * 1e18 >= 2f64.powi(256) * (1.0 - 1e-12) {
+            return Self::native(U256::MAX);
+        }
+        match ethers::utils::parse_ether(amount) {
+            Ok(wei) => Self::native(wei),
+            // Only overflow fails for whole amounts; below one it is excess decimals
+            Err(_) if amount >= 1.0 => Self::native(U256::MAX),
+            Err(_) => Self::native(U256::zero()),
+        }
+    }
+
+    /// `fraction` of this amount, rounded down; the fraction is clamped to 0..=1 and
+    /// rounded to 1/10000, so the result never exceeds the amount.
+    pub fn mul_fraction(&self, fraction: f64) -> Self {
+        let fraction = if fraction.is_nan() { 0.0 } else { fraction.clamp(0.0, 1.0) };
+        let scaled = (fraction * FRACTION_SCALE as f64).round() as u64;
+        let product = self.raw.full_mul(U256::from(scaled)) / U512::from(FRACTION_SCALE);
+        Self { raw: U256::try_from(product).unwrap_or(self.raw), decimals: self.decimals }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.raw.is_zero()
+    }
+
+    /// Whole tokens as a float, for the router and reports; may lose precision.
+    pub fn as_f64(&self) -> f64 {
+        self.to_string().parse().unwrap_or_default()
+    }
+}
+
+/// Exact decimal string, e.g. "1.500000000000000000".
+impl fmt::Display for TokenAmount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&format_units_prec(self.raw, self.decimals, self.decimals as usize))
+    }
+}
+
+impl Serialize for TokenAmount {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_format_eth_is_exact() {
+        assert_eq!(format_eth(U256::zero()), "0.000000000000000000");
+        assert_eq!(format_eth(U256::from(1)), "0.000000000000000001");
+        assert_eq!(format_eth(U256::exp10(18) * 3 / 2), "1.500000000000000000");
+        assert_eq!(format_eth(U256::from(u128::MAX)), "340282366920938463463.374607431768211455");
+        assert_eq!(
+            format_eth(U256::MAX),
+            "115792089237316195423570985008687907853269984665640564039457.584007913129639935"
+        );
+        assert_eq!(format_eth(U256::exp10(18)), ethers::utils::format_ether(U256::exp10(18)));
+    }
+
+    #[test]
+    fn test_format_units_prec() {
+        // Truncates rather than rounds
+        assert_eq!(format_units_prec(U256::from(1_999_999_999_999_999_999u64), 18, 6), "1.999999");
+        assert_eq!(format_units_prec(U256::from(1_234_567u64), 6, 2), "1.23");
+        assert_eq!(format_units_prec(U256::from(42u64), 6, 6), "0.000042");
+        assert_eq!(format_units_prec(U256::from(42u64), 0, 6), "42");
+        assert_eq!(format_units_prec(U256::from(1_500_000u64), 6, 0), "1");
+        assert!(format_units_prec(U256::MAX, 18, 4).ends_with(".5840"));
+    }
+
+    #[test]
+    fn test_token_amount() {
+        let amount = TokenAmount::native(U256::exp10(18) * 3 / 2);
+        assert_eq!(amount.to_string(), "1.500000000000000000");
+        assert_eq!(amount.as_f64(), 1.5);
+        assert_eq!(TokenAmount::native_from_f64(0.1), TokenAmount::native(U256::exp10(17)));
+        assert!(TokenAmount::native_from_f64(-1.0).is_zero());
+        assert_eq!(serde_json::to_value(amount).unwrap(), "1.500000000000000000");
+    }
+
+    fn any_u256() -> impl Strategy<Value = U256> {
+        prop_oneof![
+            any::<u128>().prop_map(U256::from),
+            any::<[u64; 4]>().prop_map(U256),
+            Just(U256::MAX),
+            Just(U256::zero()),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn test_native_from_f64_saturates(a in any::<f64>(), b in 0.0f64..1e60) {
+            let amount = TokenAmount::native_from_f64(a);
+            prop_assert!(amount.as_f64().is_finite() && amount.as_f64() >= 0.0);
+            if a.is_finite() && a >= 0.0 && b >= a {
+                prop_assert!(TokenAmount::native_from_f64(b) >= amount);
+            }
+            if a > 1e60 {
+                prop_assert_eq!(amount.raw, U256::MAX);
+            }
+        }
+
+        #[test]
+        fn test_mul_fraction_never_exceeds_the_amount(raw in any_u256(), a in any::<f64>(), b in 0.0f64..=1.0) {
+            let amount = TokenAmount::native(raw);
+            prop_assert!(amount.mul_fraction(a) <= amount);
+            prop_assert_eq!(amount.mul_fraction(1.0), amount);
+            prop_assert!(amount.mul_fraction(0.0).is_zero());
+            if a <= b {
+                prop_assert!(amount.mul_fraction(a) <= amount.mul_fraction(b));
+            }
+        }
+
+        #[test]
+        fn test_display_keeps_every_digit(raw in any_u256(), decimals in 0u32..40) {
+            let shown = TokenAmount { raw, decimals }.to_string();
+            let digits = shown.replace('.', "");
+            let digits = digits.trim_start_matches('0');
+            prop_assert_eq!(if digits.is_empty() { "0" } else { digits }, raw.to_string());
+        }
+    }
+}
@@ -0,0 +1,144 @@
+//! Helpers shared across the agents: the clock time-dependent logic reads.
+//!
+//! Components that compare against the current time hold an `Arc<dyn Clock>`, the
+//! [`SystemClock`] unless built `with_clock`. Tests hand them a [`MockClock`] and
+//! advance it instead of sleeping or backdating timestamps; waiting on a mock clock
+//! advances it and returns at once.
+
+use async_trait::async_trait;
+use std::fmt;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::time::{Duration, Instant};
+
+tokio::task_local! {
+    /// Unix time a simulated clock started at, and the tokio instant it started.
+    static SIMULATED_CLOCK: (u64, Instant);
+}
+
+/// Runs `work` with [`unix_now`] following the tokio clock from `start`, so a paused
+/// runtime controls the time decisions see. Only affects the current task.
+pub(crate) async fn with_simulated_clock<F: Future>(start: u64, work: F) -> F::Output {
+    SIMULATED_CLOCK.scope((start, Instant::now()), work).await
+}
+
+pub(crate) fn unix_now() -> u64 {
+    if let Ok(now) = SIMULATED_CLOCK.try_with(|(start, at)| start + at.elapsed().as_secs()) {
+        return now;
+    }
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// Source of the current time.
+#[async_trait]
+pub trait Clock: fmt::Debug + Send + Sync {
+    /// Monotonic time, for windows measured from an earlier instant.
+    fn now_instant(&self) -> Instant;
+    /// Wall-clock time in seconds since the Unix epoch, for timestamps that are
+    /// stored or compared with recorded ones.
+    fn now_utc(&self) -> u64;
+    /// Waits `duration` of this clock's time.
+    async fn sleep(&self, duration: Duration);
+}
+
+/// The real time: tokio's clock and the system's, or a simulated clock's inside
+/// [`with_simulated_clock`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl SystemClock {
+    pub fn shared() -> Arc<dyn Clock> {
+        Arc::new(SystemClock)
+    }
+}
+
+#[async_trait]
+impl Clock for SystemClock {
+    fn now_instant(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn now_utc(&self) -> u64 {
+        unix_now()
+    }
+
+    async fn sleep(&self, duration: Duration) {
+        tokio::time::sleep(duration).await
+    }
+}
+
+/// A clock that only moves when told to. Clones share the time.
+#[derive(Debug, Clone)]
+pub struct MockClock {
+    now: Arc<Mutex<(Instant, u64)>>,
+}
+
+impl MockClock {
+    /// Starts at unix time `utc`.
+    pub fn new(utc: u64) -> Self {
+        Self { now: Arc::new(Mutex::new((Instant::now(), utc))) }
+    }
+
+    /// Moves both times forward by `by`.
+    pub fn advance(&self, by: Duration) {
+        let mut now = self.now.lock().expect("mock clock lock poisoned");
+        now.0 += by;
+        now.1 += by.as_secs();
+    }
+
+    /// Sets the wall-clock time, leaving the monotonic one alone.
+    pub fn set_utc(&self, utc: u64) {
+        self.now.lock().expect("mock clock lock poisoned").1 = utc;
+    }
+
+    pub fn shared(&self) -> Arc<dyn Clock> {
+        Arc::new(self.clone())
+    }
+}
+
+#[async_trait]
+impl Clock for MockClock {
+    fn now_instant(&self) -> Instant {
+        self.now.lock().expect("mock clock lock poisoned").0
+    }
+
+    fn now_utc(&self) -> u64 {
+        self.now.lock().expect("mock clock lock poisoned").1
+    }
+
+    async fn sleep(&self, duration: Duration) {
+        self.advance(duration);
+        tokio::task::yield_now().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_mock_clock_moves_only_when_told() {
+        let clock = MockClock::new(1_700_000_000);
+        let shared = clock.shared();
+        let start = shared.now_instant();
+        assert_eq!(shared.now_utc(), 1_700_000_000);
+
+        clock.advance(Duration::from_secs(90));
+        assert_eq!(shared.now_instant() - start, Duration::from_secs(90));
+        assert_eq!(shared.now_utc(), 1_700_000_090);
+
+        clock.set_utc(1_800_000_000);
+        assert_eq!(shared.now_utc(), 1_800_000_000);
+        assert_eq!(shared.now_instant() - start, Duration::from_secs(90));
+
+        // Sleeping on it takes no real time
+        let started = std::time::Instant::now();
+        shared.sleep(Duration::from_secs(3600)).await;
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert_eq!(shared.now_utc(), 1_800_003_600);
+    }
+}
@@ -0,0 +1,292 @@
+//! What the portfolio is worth in USD.
+//!
+//! Once per full cycle the native balances read on every chain and the tokens the
+//! portfolio records elsewhere (other tokens, funds in flight, chains whose balance
+//! could not be read) are priced with one batched request to the [`PriceSource`].
+//! The resulting [`PortfolioValuation`] is logged, stored, shown in `/status` and
+//! sizes the position for the optimizer's pool depth check. The stored history gives
+//! the daily summary its start and end values.
+//!
+//! An asset the source has no fresh price for is listed with `usd: None`, left out
+//! of the totals and counted in the status. When the source fails altogether,
+//! `degradation.prices` decides what the cycle goes on with. Without `[prices]`
+//! nothing is valued.
+
+use anyhow::{Context, Result};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+use crate::portfolio::{PortfolioState, TOKEN};
+use crate::prices::{Price, PriceSource};
+use crate::report::MultiChainBalances;
+use crate::storage::Store;
+use crate::units::TokenAmount;
+
+/// An amount of one asset held on one chain.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Holding {
+    pub chain: String,
+    pub asset: String,
+    pub amount: f64,
+}
+
+/// The assets to value: the native balance observed on each chain, plus the
+/// portfolio entries no balance covers.
+pub fn holdings(balances: &MultiChainBalances, portfolio: &PortfolioState) -> Vec<Holding> {
+    let observed = |chain: &str| balances.get(chain).is_some_and(|b| b.balance.is_some());
+    let mut holdings: Vec<Holding> = balances
+        .iter()
+        .filter_map(|(chain, b)| {
+            Some(Holding {
+                chain: chain.clone(),
+                asset: TOKEN.to_string(),
+                amount: TokenAmount::native(b.balance?).as_f64(),
+            })
+        })
+        .collect();
+    holdings.extend(
+        portfolio
+            .entries
+            .iter()
+            .filter(|e| e.pending_route.is_some() || e.token != TOKEN || !observed(&e.chain))
+            .map(|e| Holding {
+                chain: e.chain.clone(),
+                asset: e.token.clone(),
+                amount: e.amount,
+            }),
+    );
+    holdings
+}
+
+/// Amount of one asset across chains and its USD value, when priced.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AssetValue {
+    pub amount: f64,
+    pub usd: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PortfolioValuation {
+    /// Sum of the priced assets.
+    pub total_usd: f64,
+    /// USD value of the priced assets on each chain.
+    pub by_chain: BTreeMap<String, f64>,
+    pub by_asset: BTreeMap<String, AssetValue>,
+    /// Unix time of the valuation.
+    pub priced_at: u64,
+}
+
+impl PortfolioValuation {
+    /// Values `holdings` at `prices`, keyed by asset symbol.
+    pub fn compute(holdings: &[Holding], prices: &HashMap<String, Price>, priced_at: u64) -> Self {
+        let mut valuation = Self {
+            total_usd: 0.0,
+            by_chain: BTreeMap::new(),
+            by_asset: BTreeMap::new(),
+            priced_at,
+        };
+        for holding in holdings {
+            let usd = prices.get(&holding.asset).map(|price| holding.amount * price.usd);
+            let asset = valuation
+                .by_asset
+                .entry(holding.asset.clone())
+                .or_insert(AssetValue { amount: 0.0, usd: usd.map(|_| 0.0) });
+            asset.amount += holding.amount;
+            if let (Some(total), Some(usd)) = (&mut asset.usd, usd) {
+                *total += usd;
+                *valuation.by_chain.entry(holding.chain.clone()).or_default() += usd;
+                valuation.total_usd += usd;
+            }
+        }
+        valuation
+    }
+
+    /// Assets left out of the totals for want of a price.
+    pub fn unpriced(&self) -> impl Iterator<Item = &str> {
+        self.by_asset.iter().filter(|(_, v)| v.usd.is_none()).map(|(asset, _)| asset.as_str())
+    }
+
+    /// The position size in USD; unset when no asset could be priced.
+    pub fn position_usd(&self) -> Option<f64> {
+        self.by_asset.values().any(|v| v.usd.is_some()).then_some(self.total_usd)
+    }
+}
+
+/// Values `holdings` with one request to `source`, failing when the source does;
+/// assets it has no price for are left unpriced.
+pub async fn value(holdings: &[Holding], source: &dyn PriceSource, now: u64) -> Result<PortfolioValuation> {
+    let mut symbols: Vec<&str> = holdings.iter().map(|h| h.asset.as_str()).collect();
+    symbols.sort_unstable();
+    symbols.dedup();
+    let prices = if symbols.is_empty() {
+        HashMap::new()
+    } else {
+        source
+            .prices(&symbols)
+            .await
+            .with_context(|| format!("{} could not price {}", source.name(), symbols.join(", ")))?
+    };
+    let valuation = PortfolioValuation::compute(holdings, &prices, now);
+    let chains: Vec<String> = valuation.by_chain.iter().map(|(chain, usd)| format!("{} ${:.2}", chain, usd)).collect();
+    info!("Portfolio worth ${:.2} ({})", valuation.total_usd, chains.join(", "));
+    let unpriced: Vec<&str> = valuation.unpriced().collect();
+    if !unpriced.is_empty() {
+        warn!("No price for {}; left out of the portfolio value", unpriced.join(", "));
+    }
+    Ok(valuation)
+}
+
+/// Portfolio value at the start and end of a period.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ValuationChange {
+    pub start_usd: f64,
+    pub end_usd: f64,
+    pub start_at: u64,
+    pub end_at: u64,
+}
+
+impl ValuationChange {
+    pub fn change_usd(&self) -> f64 {
+        self.end_usd - self.start_usd
+    }
+
+    pub fn describe(&self) -> String {
+        format!("${:.2} -> ${:.2} ({:+.2})", self.start_usd, self.end_usd, self.change_usd())
+    }
+}
+
+/// First and last stored valuation within `[from, to]` that priced anything; `None`
+/// when there are none or storage fails.
+pub async fn change_between(store: &dyn Store, from: u64, to: u64) -> Option<ValuationChange> {
+    let valuations = store.valuations_between(from, to).await.unwrap_or_else(|e| {
+        warn!("Failed to load stored valuations: {:#}", e);
+        Vec::new()
+    });
+    let mut priced = valuations.iter().filter(|v| v.position_usd().is_some());
+    let first = priced.next()?;
+    let last = priced.next_back().unwrap_or(first);
+    Some(ValuationChange {
+        start_usd: first.total_usd,
+        end_usd: last.total_usd,
+        start_at: first.priced_at,
+        end_at: last.priced_at,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::portfolio::PositionEntry;
+    use crate::report::ChainBalance;
+    use crate::storage::MemoryStore;
+    use anyhow::Result;
+    use async_trait::async_trait;
+    use ethers::core::types::U256;
+    use std::sync::Mutex;
+
+    /// Fixed prices, recording every request.
+    struct FixedPrices {
+        prices: HashMap<String, f64>,
+        requests: Mutex<Vec<Vec<String>>>,
+    }
+
+    #[async_trait]
+    impl PriceSource for FixedPrices {
+        fn name(&self) -> &str {
+            "fixed"
+        }
+
+        async fn prices(&self, symbols: &[&str]) -> Result<HashMap<String, Price>> {
+            self.requests.lock().unwrap().push(symbols.iter().map(|s| s.to_string()).collect());
+            Ok(symbols
+                .iter()
+                .filter_map(|s| {
+                    let usd = *self.prices.get(*s)?;
+                    Some((s.to_string(), Price { usd, updated_at: 0, source: "fixed".to_string() }))
+                })
+                .collect())
+        }
+    }
+
+    fn entry(chain: &str, token: &str, amount: f64, pending_route: Option<&str>) -> PositionEntry {
+        PositionEntry {
+            chain: chain.to_string(),
+            protocol: None,
+            token: token.to_string(),
+            amount,
+            since: 0,
+            pending_route: pending_route.map(str::to_string),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_values_two_chains_and_three_tokens() {
+        let min = U256::exp10(17);
+        let balances: MultiChainBalances = [
+            ("Ethereum".to_string(), ChainBalance::observed(U256::exp10(18) * 2, min)),
+            ("Arbitrum".to_string(), ChainBalance::observed(U256::exp10(18), min)),
+        ]
+        .into();
+        let portfolio = PortfolioState {
+            entries: vec![
+                // Covered by the observed Ethereum balance
+                entry("Ethereum", "ETH", 2.0, None),
+                entry("Arbitrum", "USDC", 500.0, None),
+                entry("Ethereum", "USDC", 250.0, None),
+                // In flight, so no balance shows it yet
+                entry("Arbitrum", "ETH", 0.5, Some("r-1")),
+                entry("Arbitrum", "GHO", 100.0, None),
+            ],
+        };
+        let holdings = holdings(&balances, &portfolio);
+        assert_eq!(holdings.len(), 6);
+
+        let source = FixedPrices {
+            prices: [("ETH".to_string(), 2000.0), ("USDC".to_string(), 1.0)].into(),
+            requests: Mutex::new(Vec::new()),
+        };
+        let valuation = value(&holdings, &source, 1_000).await.unwrap();
+        // One batched request for every asset
+        assert_eq!(*source.requests.lock().unwrap(), vec![vec!["ETH", "GHO", "USDC"]]);
+
+        assert_eq!(valuation.priced_at, 1_000);
+        assert_eq!(valuation.by_asset["ETH"], AssetValue { amount: 3.5, usd: Some(7_000.0) });
+        assert_eq!(valuation.by_asset["USDC"], AssetValue { amount: 750.0, usd: Some(750.0) });
+        assert_eq!(valuation.by_asset["GHO"], AssetValue { amount: 100.0, usd: None });
+        assert_eq!(valuation.by_chain["Ethereum"], 4_250.0);
+        assert_eq!(valuation.by_chain["Arbitrum"], 3_500.0);
+        assert_eq!(valuation.total_usd, 7_750.0);
+        assert_eq!(valuation.unpriced().collect::<Vec<_>>(), vec!["GHO"]);
+        assert_eq!(valuation.position_usd(), Some(7_750.0));
+
+        // Nothing priced leaves the position size unknown
+        let none = FixedPrices { prices: HashMap::new(), requests: Mutex::new(Vec::new()) };
+        let unpriced = value(&holdings, &none, 1_000).await.unwrap();
+        assert_eq!(unpriced.total_usd, 0.0);
+        assert!(unpriced.by_chain.is_empty());
+        assert_eq!(unpriced.unpriced().count(), 3);
+        assert_eq!(unpriced.position_usd(), None);
+    }
+
+    #[tokio::test]
+    async fn test_change_between_stored_valuations() {
+        let store = MemoryStore::new();
+        let valuation = |priced_at, total_usd, usd| PortfolioValuation {
+            total_usd,
+            by_chain: BTreeMap::new(),
+            by_asset: [("ETH".to_string(), AssetValue { amount: 1.0, usd })].into(),
+            priced_at,
+        };
+        store.record_valuation(&valuation(100, 1_000.0, Some(1_000.0))).await.unwrap();
+        store.record_valuation(&valuation(200, 1_100.0, Some(1_100.0))).await.unwrap();
+        // An unpriced valuation says nothing about the value
+        store.record_valuation(&valuation(300, 0.0, None)).await.unwrap();
+
+        let change = change_between(&store, 0, 400).await.unwrap();
+        assert_eq!((change.start_usd, change.end_usd, change.start_at, change.end_at), (1_000.0, 1_100.0, 100, 200));
+        assert_eq!(change.change_usd(), 100.0);
+        assert_eq!(change.describe(), "$1000.00 -> $1100.00 (+100.00)");
+        assert!(change_between(&store, 250, 400).await.is_none());
+    }
+}
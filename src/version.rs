@@ -0,0 +1,145 @@
+//! Version and build metadata, embedded at compile time by `build.rs`.
+//!
+//! The same fields appear in the startup banner, `/status`, the audit log header,
+//! `asam --version` and the User-Agent of every outbound HTTP request.
+
+use ethers::core::types::Address;
+use serde::Serialize;
+use std::sync::OnceLock;
+
+use crate::config::{Config, ModePlan, Profile};
+
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+/// Short commit hash, or "unknown" when built outside a git checkout.
+pub const GIT_HASH: &str = env!("ASAM_GIT_HASH");
+/// Shown by `asam -V`.
+pub const SHORT_VERSION: &str = concat!(env!("CARGO_PKG_VERSION"), " (", env!("ASAM_GIT_HASH"), ")");
+/// Sent with every outbound HTTP request.
+pub const USER_AGENT: &str = concat!("asam/", env!("CARGO_PKG_VERSION"), " (", env!("ASAM_GIT_HASH"), ")");
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BuildInfo {
+    pub version: &'static str,
+    pub git_hash: &'static str,
+    /// Unix time of the build.
+    pub build_timestamp: u64,
+    /// Cargo features compiled in.
+    pub features: Vec<&'static str>,
+}
+
+impl BuildInfo {
+    pub fn current() -> Self {
+        Self {
+            version: VERSION,
+            git_hash: GIT_HASH,
+            build_timestamp: env!("ASAM_BUILD_TIMESTAMP").parse().unwrap_or_default(),
+            features: [
+                ("sqlite", cfg!(feature = "sqlite")),
+                ("ledger", cfg!(feature = "ledger")),
+                ("test-utils", cfg!(feature = "test-utils")),
+            ]
+            .into_iter()
+                .filter_map(|(name, enabled)| enabled.then_some(name))
+                .collect(),
+        }
+    }
+
+    fn features(&self) -> String {
+        if self.features.is_empty() {
+            "none".to_string()
+        } else {
+            self.features.join(", ")
+        }
+    }
+}
+
+/// Logged line by line when the agent starts. A simulated run, `simulation_seed` set,
+/// is announced above and below the details so it cannot be taken for a real one.
+pub fn banner(
+    profile: Profile,
+    mode: ModePlan,
+    chain_id: Option<u64>,
+    address: Address,
+    simulation_seed: Option<u64>,
+) -> String {
+    let build = BuildInfo::current();
+    let text = format!(
+        "asam {} ({}, built {})\n  profile:  {}\n  mode:     {}\n  chain id: {}\n  address:  {:?}\n  features: {}",
+        build.version,
+        build.git_hash,
+        build.build_timestamp,
+        profile,
+        mode,
+        chain_id.map(|id| id.to_string()).unwrap_or_else(|| "unknown".to_string()),
+        address,
+        build.features()
+    );
+    match simulation_seed {
+        Some(seed) => {
+            let notice = format!(
+                "*** SIMULATED: deterministic demo data (seed {}), no RPC, pool API or price calls ***",
+                seed
+            );
+            format!("{}\n{}\n{}", notice, text, notice)
+        }
+        None => text,
+    }
+}
+
+/// Shown by `asam --version`: the build, then the chains and data sources used when
+/// the config does not name any.
+pub fn long_version() -> &'static str {
+    static LONG_VERSION: OnceLock<String> = OnceLock::new();
+    LONG_VERSION.get_or_init(|| {
+        let build = BuildInfo::current();
+        let defaults = Config::default();
+        let mut text = format!(
+            "{} ({}, built {})\nfeatures: {}\n",
+            build.version,
+            build.git_hash,
+            build.build_timestamp,
+            build.features()
+        );
+        for profile in [Profile::Mainnet, Profile::Testnet, Profile::Local] {
+            let chains: Vec<String> = profile
+                .default_chains()
+                .iter()
+                .map(|chain| format!("{} ({})", chain.name, chain.chain_id))
+                .collect();
+            text.push_str(&format!("default chains ({}): {}\n", profile, chains.join(", ")));
+        }
+        text.push_str(&format!(
+            "pool data: {}\nroute status: {}, {}",
+            defaults.optimizer.api_url, defaults.router.status.lifi_api_url, defaults.router.status.socket_api_url
+        ));
+        text
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_info_is_populated() {
+        let build = BuildInfo::current();
+        assert_eq!(build.version, env!("CARGO_PKG_VERSION"));
+        assert!(!build.git_hash.is_empty());
+        assert!(build.build_timestamp > 1_600_000_000);
+        assert_eq!(build.features.contains(&"sqlite"), cfg!(feature = "sqlite"));
+        assert_eq!(USER_AGENT, format!("asam/{} ({})", VERSION, GIT_HASH));
+
+        let long = long_version();
+        assert!(long.starts_with(&format!("{} ({}", VERSION, GIT_HASH)), "{}", long);
+        assert!(long.contains("default chains (mainnet): Ethereum (1), Arbitrum (42161)"), "{}", long);
+        assert!(long.contains("pool data: https://api.llama.fi/protocols"), "{}", long);
+
+        let mode = Config::default().mode_plan().unwrap();
+        let plain = banner(Profile::Testnet, mode, Some(11155111), Address::zero(), None);
+        assert!(plain.contains("  chain id: 11155111\n"), "{}", plain);
+        assert!(!plain.contains("SIMULATED"), "{}", plain);
+        let simulated = banner(Profile::Mainnet, mode, Some(1), Address::zero(), Some(7));
+        assert!(simulated.starts_with("*** SIMULATED") && simulated.ends_with("***"), "{}", simulated);
+        assert!(simulated.contains("(seed 7)"), "{}", simulated);
+    }
+}
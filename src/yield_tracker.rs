@@ -0,0 +1,402 @@
+//! Realized versus projected yield.
+//!
+//! At `storage.snapshot_interval_secs` the value held on every chain is recorded as a
+//! [`YieldSnapshot`], together with the funds routed in or out and the fees paid since
+//! the previous one. Snapshots are persisted and replayed at startup, so holding
+//! periods survive restarts.
+//!
+//! A holding period starts with the first snapshot showing funds on a chain and ends
+//! with the one showing them gone. Its realized return uses the modified Dietz method:
+//! the gain after flows and fees over the entry value plus the time-weighted flows, so
+//! a mid-period top-up only counts for the time it was held.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+
+use crate::portfolio::PortfolioState;
+
+/// Values below this are treated as empty.
+const DUST: f64 = 1e-9;
+const SECS_PER_YEAR: f64 = 365.0 * 24.0 * 60.0 * 60.0;
+/// Closed holding periods kept for reports.
+const CLOSED_REPORTS: usize = 50;
+
+/// Value held on one chain at one point in time.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct YieldSnapshot {
+    pub timestamp: u64,
+    pub chain: String,
+    pub protocol: Option<String>,
+    /// Settled funds on the chain, in ETH.
+    pub value: f64,
+    /// Funds moved in (positive) or out (negative) since the previous snapshot,
+    /// already reflected in `value`.
+    pub flow: f64,
+    /// Fees paid for those moves.
+    pub fees: f64,
+    /// APY the optimizer projected when the funds were routed here.
+    pub projected_apy: Option<f64>,
+}
+
+/// Outcome of one holding period, closed or still open.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct YieldReport {
+    pub chain: String,
+    pub protocol: Option<String>,
+    pub entered_at: u64,
+    /// Unset while the funds are still held.
+    pub exited_at: Option<u64>,
+    pub held_secs: u64,
+    pub entry_value: f64,
+    /// Value withdrawn at exit, or held now.
+    pub end_value: f64,
+    /// Funds added (positive) or withdrawn (negative) between entry and end.
+    pub net_flows: f64,
+    pub fees: f64,
+    /// Gain over the time-weighted cost basis, as a fraction.
+    pub realized_return: f64,
+    /// `realized_return` annualized, in percent; unset for periods of zero length.
+    pub realized_apy: Option<f64>,
+    pub projected_apy: Option<f64>,
+    /// The funds were already there when tracking started, so the entry value and
+    /// time are those of the first snapshot.
+    pub partial: bool,
+}
+
+/// Realized against projected yield over all complete holding periods.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct YieldSummary {
+    /// Holding periods with both a realized and a projected APY.
+    pub positions: usize,
+    /// Holding periods left out because tracking started while they were open.
+    pub partial: usize,
+    /// Averages weighted by cost basis and time held.
+    pub realized_apy: Option<f64>,
+    pub projected_apy: Option<f64>,
+    /// Realized over projected APY; 1.0 means the pools delivered what was projected.
+    pub accuracy: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct OpenPosition {
+    protocol: Option<String>,
+    entered_at: u64,
+    entry_value: f64,
+    /// Sum of flows and of flow × time, for the time weighting.
+    flows: f64,
+    time_flows: f64,
+    fees: f64,
+    value: f64,
+    updated_at: u64,
+    projected_apy: Option<f64>,
+    partial: bool,
+}
+
+impl OpenPosition {
+    /// Values the period at `end`. `exit_flow` is the withdrawal that closed it, already
+    /// included in the flows.
+    fn report(&self, chain: &str, end: u64, exit_flow: Option<f64>) -> YieldReport {
+        let held = end.saturating_sub(self.entered_at) as f64;
+        let gain = self.value - self.entry_value - self.flows - self.fees;
+        let weighted_flows = if held > 0.0 { (end as f64 * self.flows - self.time_flows) / held } else { 0.0 };
+        let basis = self.entry_value + weighted_flows;
+        let realized_return = if basis > DUST { gain / basis } else { 0.0 };
+        YieldReport {
+            chain: chain.to_string(),
+            protocol: self.protocol.clone(),
+            entered_at: self.entered_at,
+            exited_at: exit_flow.map(|_| end),
+            held_secs: end.saturating_sub(self.entered_at),
+            entry_value: self.entry_value,
+            end_value: self.value - exit_flow.unwrap_or(0.0),
+            net_flows: self.flows - exit_flow.unwrap_or(0.0),
+            fees: self.fees,
+            realized_return,
+            realized_apy: (held > 0.0).then(|| realized_return * SECS_PER_YEAR / held * 100.0),
+            projected_apy: self.projected_apy,
+            partial: self.partial,
+        }
+    }
+}
+
+/// Flows and fees on a chain not yet in a snapshot.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct Pending {
+    flow: f64,
+    fees: f64,
+}
+
+/// Follows holding periods from snapshots. Lives in the status snapshot, next to the
+/// portfolio whose changes it records as flows.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct YieldTracker {
+    open: BTreeMap<String, OpenPosition>,
+    closed: VecDeque<YieldReport>,
+    pending: BTreeMap<String, Pending>,
+    projected: BTreeMap<String, f64>,
+    last_snapshot_at: Option<u64>,
+}
+
+impl YieldTracker {
+    /// Rebuilds the holding periods from stored snapshots, oldest first.
+    pub fn restore(snapshots: &[YieldSnapshot]) -> Self {
+        let mut tracker = Self::default();
+        for snapshot in snapshots {
+            tracker.observe(snapshot);
+        }
+        tracker
+    }
+
+    /// Records funds routed onto (positive) or off (negative) `chain`.
+    pub fn flow(&mut self, chain: &str, amount: f64) {
+        self.pending.entry(chain.to_string()).or_default().flow += amount;
+    }
+
+    /// Records fees paid for moving funds on `chain`.
+    pub fn fee(&mut self, chain: &str, amount: f64) {
+        self.pending.entry(chain.to_string()).or_default().fees += amount;
+    }
+
+    /// APY projected for funds routed to `chain`, kept until they leave.
+    pub fn set_projected(&mut self, chain: &str, apy: f64) {
+        self.projected.insert(chain.to_string(), apy);
+    }
+
+    pub fn is_due(&self, now: u64, interval_secs: u64) -> bool {
+        self.last_snapshot_at.is_none_or(|last| now >= last + interval_secs)
+    }
+
+    /// Snapshots every chain with settled funds, an open holding period or pending
+    /// flows, and applies the snapshots.
+    pub fn snapshot(&mut self, portfolio: &PortfolioState, now: u64) -> Vec<YieldSnapshot> {
+        let mut held: BTreeMap<String, (f64, Option<String>)> = BTreeMap::new();
+        for entry in portfolio.entries.iter().filter(|e| e.pending_route.is_none()) {
+            let (value, protocol) = held.entry(entry.chain.clone()).or_default();
+            *value += entry.amount;
+            if entry.protocol.is_some() {
+                protocol.clone_from(&entry.protocol);
+            }
+        }
+        for chain in self.open.keys().chain(self.pending.keys()) {
+            held.entry(chain.clone()).or_default();
+        }
+        let snapshots: Vec<YieldSnapshot> = held
+            .into_iter()
+            .map(|(chain, (value, protocol))| {
+                let pending = self.pending.remove(&chain).unwrap_or_default();
+                let open = self.open.get(&chain);
+                YieldSnapshot {
+                    timestamp: now,
+                    protocol: protocol.or_else(|| open.and_then(|o| o.protocol.clone())),
+                    value,
+                    flow: pending.flow,
+                    fees: pending.fees,
+                    projected_apy: self.projected.get(&chain).copied().or(open.and_then(|o| o.projected_apy)),
+                    chain,
+                }
+            })
+            .collect();
+        for snapshot in &snapshots {
+            self.observe(snapshot);
+        }
+        self.last_snapshot_at = Some(now);
+        snapshots
+    }
+
+    /// Applies one snapshot: opens, extends or closes the holding period on its chain.
+    pub fn observe(&mut self, snapshot: &YieldSnapshot) {
+        let t = snapshot.timestamp;
+        self.last_snapshot_at = Some(self.last_snapshot_at.map_or(t, |last| last.max(t)));
+        if let Some(apy) = snapshot.projected_apy {
+            self.projected.insert(snapshot.chain.clone(), apy);
+        }
+        match self.open.get_mut(&snapshot.chain) {
+            Some(open) => {
+                open.flows += snapshot.flow;
+                open.time_flows += t as f64 * snapshot.flow;
+                open.fees += snapshot.fees;
+                open.value = snapshot.value;
+                open.updated_at = t;
+                if snapshot.protocol.is_some() {
+                    open.protocol.clone_from(&snapshot.protocol);
+                }
+                if snapshot.value <= DUST {
+                    let report = open.report(&snapshot.chain, t, Some(snapshot.flow));
+                    self.open.remove(&snapshot.chain);
+                    self.projected.remove(&snapshot.chain);
+                    if self.closed.len() == CLOSED_REPORTS {
+                        self.closed.pop_front();
+                    }
+                    self.closed.push_back(report);
+                }
+            }
+            None if snapshot.value > DUST => {
+                self.open.insert(
+                    snapshot.chain.clone(),
+                    OpenPosition {
+                        protocol: snapshot.protocol.clone(),
+                        entered_at: t,
+                        entry_value: snapshot.value,
+                        flows: 0.0,
+                        time_flows: 0.0,
+                        fees: snapshot.fees,
+                        value: snapshot.value,
+                        updated_at: t,
+                        projected_apy: self.projected.get(&snapshot.chain).copied(),
+                        // Funds that did not arrive by a tracked flow were there before tracking
+                        partial: snapshot.flow <= DUST,
+                    },
+                );
+            }
+            None => {}
+        }
+    }
+
+    /// Closed holding periods followed by the open ones valued at their last snapshot.
+    pub fn reports(&self) -> Vec<YieldReport> {
+        let open = self
+            .open
+            .iter()
+            .map(|(chain, open)| open.report(chain, open.updated_at, None));
+        self.closed.iter().cloned().chain(open).collect()
+    }
+
+    pub fn summary(&self) -> YieldSummary {
+        summarize(&self.reports())
+    }
+}
+
+/// Aggregates complete holding periods, weighting each by cost basis and time held.
+pub fn summarize(reports: &[YieldReport]) -> YieldSummary {
+    let partial = reports.iter().filter(|r| r.partial).count();
+    let mut positions = 0;
+    let (mut weight, mut realized, mut projected) = (0.0, 0.0, 0.0);
+    for report in reports.iter().filter(|r| !r.partial) {
+        let (Some(realized_apy), Some(projected_apy)) = (report.realized_apy, report.projected_apy) else {
+            continue;
+        };
+        positions += 1;
+        let w = report.entry_value * report.held_secs as f64;
+        weight += w;
+        realized += realized_apy * w;
+        projected += projected_apy * w;
+    }
+    let realized_apy = (weight > 0.0).then(|| realized / weight);
+    let projected_apy = (weight > 0.0).then(|| projected / weight);
+    YieldSummary {
+        positions,
+        partial,
+        realized_apy,
+        projected_apy,
+        accuracy: realized_apy.zip(projected_apy).filter(|(_, p)| p.abs() > DUST).map(|(r, p)| r / p),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agents::cross_chain_router::{BridgeProvider, RouteReceipt, RouteState};
+
+    const YEAR: u64 = 365 * 24 * 60 * 60;
+
+    fn snapshot(timestamp: u64, chain: &str, value: f64, flow: f64, fees: f64) -> YieldSnapshot {
+        YieldSnapshot {
+            timestamp,
+            chain: chain.to_string(),
+            protocol: Some("Aave".to_string()),
+            value,
+            flow,
+            fees,
+            projected_apy: (chain == "Arbitrum").then_some(5.0),
+        }
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, "{} != {}", actual, expected);
+    }
+
+    /// 20 ETH already on Ethereum when tracking starts; 10 ETH routed to Arbitrum, topped
+    /// up by 5 ETH half a year in and withdrawn after a year and a half.
+    fn series() -> Vec<YieldSnapshot> {
+        vec![
+            snapshot(0, "Ethereum", 20.0, 0.0, 0.0),
+            snapshot(0, "Arbitrum", 10.0, 10.0, 0.05),
+            snapshot(YEAR / 2, "Ethereum", 5.0, -15.0, 0.0),
+            snapshot(YEAR / 2, "Arbitrum", 15.2, 5.0, 0.0),
+            snapshot(YEAR, "Arbitrum", 15.5, 0.0, 0.0),
+            snapshot(YEAR * 3 / 2, "Arbitrum", 0.0, -15.7, 0.0),
+        ]
+    }
+
+    #[test]
+    fn test_top_up_adjusts_cost_basis() {
+        let tracker = YieldTracker::restore(&series()[..5]);
+        let reports = tracker.reports();
+        let arbitrum = reports.iter().find(|r| r.chain == "Arbitrum").unwrap();
+        assert!(!arbitrum.partial);
+        assert_eq!(arbitrum.exited_at, None);
+        assert_close(arbitrum.net_flows, 5.0);
+        // 15.5 - 10 - 5 - 0.05 gained on 10 held all year plus 5 held for half of it
+        assert_close(arbitrum.realized_return, 0.45 / 12.5);
+        assert_close(arbitrum.realized_apy.unwrap(), 3.6);
+        assert_eq!(arbitrum.projected_apy, Some(5.0));
+
+        let ethereum = reports.iter().find(|r| r.chain == "Ethereum").unwrap();
+        assert!(ethereum.partial);
+        assert_close(ethereum.entry_value, 20.0);
+    }
+
+    #[test]
+    fn test_exit_closes_holding_period() {
+        let tracker = YieldTracker::restore(&series());
+        let closed: Vec<_> = tracker.reports().into_iter().filter(|r| r.exited_at.is_some()).collect();
+        assert_eq!(closed.len(), 1);
+        let report = &closed[0];
+        assert_eq!((report.entered_at, report.exited_at, report.held_secs), (0, Some(YEAR * 3 / 2), YEAR * 3 / 2));
+        assert_close(report.end_value, 15.7);
+        assert_close(report.net_flows, 5.0);
+        // 15.7 - 10 - 5 - 0.05 over 10 for the whole period plus 5 for two thirds of it
+        assert_close(report.realized_return, 0.65 / (10.0 + 5.0 * 2.0 / 3.0));
+        assert_close(report.realized_apy.unwrap(), 3.25);
+
+        let summary = tracker.summary();
+        assert_eq!((summary.positions, summary.partial), (1, 1));
+        assert_close(summary.accuracy.unwrap(), 0.65);
+    }
+
+    #[test]
+    fn test_snapshot_takes_flows_from_portfolio_changes() {
+        let route = RouteReceipt {
+            route_id: "route-1".to_string(),
+            amount: 1.0,
+            source_chain: "Ethereum".to_string(),
+            target_chain: "Arbitrum".to_string(),
+            provider: BridgeProvider::LiFi,
+            state: RouteState::Completed,
+            source_tx_hash: None,
+            destination_tx_hash: None,
+            failure_reason: None,
+            updated_at: 0,
+        };
+        let mut portfolio = PortfolioState::default();
+        portfolio.reconcile("Ethereum", 3.0, 0.05, 0);
+        let mut tracker = YieldTracker::default();
+        assert!(tracker.is_due(0, 3600));
+        assert_eq!(tracker.snapshot(&portfolio, 0).len(), 1);
+        assert!(!tracker.is_due(1800, 3600));
+
+        portfolio.route_started(&route, Some("Aave".to_string()), 100);
+        tracker.flow("Ethereum", -1.0);
+        tracker.set_projected("Arbitrum", 5.0);
+        // In flight funds are not held anywhere yet
+        let snapshots = tracker.snapshot(&portfolio, 3600);
+        assert_eq!(snapshots.len(), 1);
+        assert_close(snapshots[0].flow, -1.0);
+
+        portfolio.route_completed("route-1", 3700);
+        tracker.flow("Arbitrum", 1.0);
+        let snapshots = tracker.snapshot(&portfolio, 7200);
+        assert_eq!(snapshots[0], snapshot(7200, "Arbitrum", 1.0, 1.0, 0.0));
+        assert!(!tracker.reports().iter().find(|r| r.chain == "Arbitrum").unwrap().partial);
+    }
+}
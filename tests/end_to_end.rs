@@ -0,0 +1,175 @@
+//! Whole-agent scenarios against a mocked outside world; see [`harness`].
+
+mod harness;
+
+use asam::config::{Fallback, RunMode};
+use asam::degradation::{Dependency, HealthState};
+use asam::errors::ErrorCategory;
+use asam::notifier::AgentEvent;
+use asam::report::CycleDecision;
+use asam::scheduler::CycleTrigger;
+use asam::status::BalanceStatus;
+use harness::{eth, pool, Agent, World, ETH_USD};
+
+#[tokio::test]
+async fn test_healthy_cycle_takes_no_action() {
+    let world = World::start("1").await;
+    world.set_pools(&[pool("Aave", "Ethereum", 4.0, 5e7), pool("Compound", "Arbitrum", 3.0, 2e7)]).await;
+    let agent = Agent::new(world.config());
+
+    let (startup, triggered) = agent.run_with(async { (agent.startup().await, agent.cycle().await) }).await;
+
+    let report = startup.report();
+    assert_eq!(report.trigger, CycleTrigger::Startup);
+    assert_eq!((report.balance, report.balance_status), (eth("1"), BalanceStatus::Healthy));
+    assert_eq!(report.balance_usd, Some(ETH_USD));
+    assert_eq!(report.valuation.as_ref().unwrap().total_usd, ETH_USD);
+    assert_eq!(report.best_pool.as_ref().unwrap().pool.chain, "Ethereum");
+    assert_eq!(report.decision, CycleDecision::NoAction);
+    assert!(report.route.is_none());
+    assert!(report.warnings.is_empty(), "{:?}", report.warnings);
+    assert!(report.degradations.is_empty());
+    assert!(!startup.kinds().contains(&"rebalance_decided"), "{:?}", startup.kinds());
+
+    let report = triggered.report();
+    assert_eq!(report.trigger, CycleTrigger::Api);
+    assert_eq!(report.decision, CycleDecision::NoAction);
+    assert_eq!(triggered.status.cycle, 2);
+    assert_eq!(triggered.status.dependencies.get(Dependency::PoolApi).unwrap().state, HealthState::Up);
+
+    let decisions = world.decisions();
+    assert_eq!(decisions.len(), 2);
+    assert!(decisions.iter().all(|d| d.decision == CycleDecision::NoAction && d.route_preview.is_none()));
+    assert_eq!(decisions[1].reason, "best pool is on Ethereum where the funds are");
+    assert!(world.node.calls("eth_getBalance").await >= 2);
+    assert!(world.alerts(0).await.is_empty());
+}
+
+#[tokio::test]
+async fn test_dry_run_rebalance_end_to_end() {
+    let world = World::start("1").await;
+    world.set_pools(&[pool("Aave", "Ethereum", 3.0, 5e7), pool("Aave", "Arbitrum", 8.0, 3e7)]).await;
+    let agent = Agent::new(world.config());
+
+    let startup = agent.run_with(agent.startup()).await;
+
+    let report = startup.report();
+    assert_eq!(report.decision, CycleDecision::RouteInitiated);
+    let route = report.route.as_ref().unwrap();
+    // Simulated, so not tracked
+    assert_eq!(route.route_id, None);
+    assert_eq!(route.mode, RunMode::DryRun);
+    assert_eq!((route.source_chain.as_str(), route.target_chain.as_str()), ("Ethereum", "Arbitrum"));
+    // 90% of the balance above the 0.01 ETH reserve
+    assert_eq!(route.amount, 0.891);
+    let inputs = report.decision_inputs.as_ref().unwrap();
+    assert_eq!((inputs.current_chain.as_str(), inputs.current_apy), ("Ethereum", Some(3.0)));
+    assert_eq!((inputs.candidate_chain.as_str(), inputs.candidate_apy), ("Arbitrum", 8.0));
+    assert_eq!(inputs.gas_price_gwei, Some(20.0));
+    assert!(startup.kinds().contains(&"rebalance_decided"), "{:?}", startup.kinds());
+
+    let decisions = world.decisions();
+    assert_eq!(decisions.len(), 1);
+    let decision = &decisions[0];
+    assert_eq!((decision.cycle_id, &decision.decision), (1, &CycleDecision::RouteInitiated));
+    assert_eq!(decision.route_preview.as_ref(), Some(route));
+    assert_eq!(decision.current_position, "Ethereum");
+    assert_eq!(decision.candidate_pools[0].pool.chain, "Arbitrum");
+    assert_eq!(decision.candidate_pools.len(), 2);
+
+    // Nothing was sent anywhere
+    assert_eq!(world.node.calls("eth_sendRawTransaction").await, 0);
+    assert!(world.bridge.received_requests().await.unwrap().is_empty());
+    assert_eq!(world.alerts(1).await, vec!["rebalance_decided"]);
+}
+
+#[tokio::test]
+async fn test_critical_balance_alerts_without_acting() {
+    let world = World::start("0.01").await;
+    world.set_pools(&[pool("Aave", "Arbitrum", 8.0, 3e7)]).await;
+    let agent = Agent::new(world.config());
+
+    let (startup, retried) = agent.run_with(async { (agent.startup().await, agent.cycle().await) }).await;
+
+    // Underfunded is not a failure: the cycle completes, but moves nothing
+    assert!(!startup.failed(), "{:?}", startup.kinds());
+    assert_eq!(startup.status.balance_status, BalanceStatus::Critical);
+    assert_eq!(startup.status.balance, Some(eth("0.01")));
+    let report = startup.report();
+    assert_ne!(report.decision, CycleDecision::RouteInitiated);
+    assert!(report.route.is_none());
+    let kinds = startup.kinds();
+    assert!(kinds.contains(&"critical_balance"), "{:?}", kinds);
+    assert!(!kinds.contains(&"rebalance_decided"), "{:?}", kinds);
+    assert!(startup.events.iter().any(|event| matches!(
+        event,
+        AgentEvent::CriticalBalance { chain, balance_wei, .. } if chain == "Ethereum" && *balance_wei == eth("0.01")
+    )));
+
+    // Still critical: no new balance alert, and the agent keeps cycling
+    assert!(!retried.failed(), "{:?}", retried.kinds());
+    assert!(!retried.kinds().contains(&"critical_balance"), "{:?}", retried.kinds());
+    assert_eq!(retried.status.failure_streak, 0);
+    assert!(world.decisions().iter().all(|record| record.decision != CycleDecision::RouteInitiated));
+    assert_eq!(world.node.calls("eth_sendRawTransaction").await, 0);
+    assert!(world.bridge.received_requests().await.unwrap().is_empty());
+
+    let alerts = world.alerts(1).await;
+    assert!(alerts.contains(&"critical_balance".to_string()), "{:?}", alerts);
+    assert!(!alerts.contains(&"error_raised".to_string()), "{:?}", alerts);
+}
+
+#[tokio::test]
+async fn test_pool_api_outage_follows_the_degradation_policy() {
+    let world = World::start("1").await;
+    world.set_pools(&[pool("Aave", "Ethereum", 4.0, 5e7)]).await;
+    let mut config = world.config();
+    config.degradation.pool_api = Fallback::UseCache;
+    let agent = Agent::new(config);
+
+    let (startup, outage, recovered) = agent
+        .run_with(async {
+            let startup = agent.startup().await;
+            world.set_pools_down(503).await;
+            let outage = agent.cycle().await;
+            world.set_pools(&[pool("Aave", "Ethereum", 4.0, 5e7)]).await;
+            (startup, outage, agent.cycle().await)
+        })
+        .await;
+
+    assert!(startup.report().degradations.is_empty());
+
+    // The cached pools stand in for the API
+    let report = outage.report();
+    assert_eq!(report.degradations.len(), 1);
+    assert_eq!((report.degradations[0].dependency, report.degradations[0].fallback), (Dependency::PoolApi, Fallback::UseCache));
+    assert!(report.warnings[0].starts_with("pool_api unavailable, fell back to use_cache"), "{:?}", report.warnings);
+    assert_eq!(report.best_pool.as_ref().unwrap().pool.chain, "Ethereum");
+    assert_eq!(report.decision, CycleDecision::NoAction);
+    assert!(outage.kinds().contains(&"degradation_applied"), "{:?}", outage.kinds());
+    let health = outage.status.dependencies.get(Dependency::PoolApi).unwrap();
+    assert_eq!((health.state, health.fallback), (HealthState::Down, Some(Fallback::UseCache)));
+
+    assert!(recovered.report().degradations.is_empty());
+    assert!(recovered.kinds().contains(&"degradation_cleared"), "{:?}", recovered.kinds());
+    let health = recovered.status.dependencies.get(Dependency::PoolApi).unwrap();
+    assert_eq!((health.state, health.fallback, health.recent_failures), (HealthState::Flaky, None, 1));
+}
+
+#[tokio::test]
+async fn test_pool_api_outage_fails_the_cycle_by_default() {
+    let world = World::start("1").await;
+    world.set_pools_down(503).await;
+    let agent = Agent::new(world.config());
+
+    let startup = agent.run_with(agent.startup()).await;
+
+    assert!(startup.failed());
+    assert!(startup.events.iter().any(|event| matches!(
+        event,
+        AgentEvent::ErrorRaised { category: ErrorCategory::DefiApi, .. }
+    )), "{:#?}", startup.events);
+    assert!(world.decisions().is_empty());
+    // The balance was still read and is fine
+    assert_eq!(startup.status.balance_status, BalanceStatus::Healthy);
+}
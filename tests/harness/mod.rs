@@ -0,0 +1,332 @@
+//! A mocked outside world for the end-to-end tests.
+//!
+//! [`World`] serves everything the agent talks to from local wiremock servers: a
+//! JSON-RPC node answering each method with a scripted result, the pool API,
+//! CoinGecko's `/simple/price`, the bridge status APIs and a notification webhook.
+//! [`World::config`] is a dry-run config pointing at all of them. [`Agent`] runs a
+//! [`Runner`] on a config one cycle at a time: the startup cycle, then one per
+//! [`Agent::cycle`]. Cycles are triggered rather than waited for, so a test takes
+//! as long as its requests to localhost.
+
+#![allow(dead_code)]
+
+use asam::config::{Config, PriceSourceKind, RunMode};
+use asam::decision_log::DecisionRecord;
+use asam::notifier::AgentEvent;
+use asam::status::StatusSnapshot;
+use asam::{CycleReport, Runner};
+use ethers::core::types::U256;
+use ethers::utils::parse_ether;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast;
+use tokio_util::sync::CancellationToken;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, Request, Respond, ResponseTemplate};
+
+/// The monitored Safe.
+pub const SAFE: &str = "0x5afe00000000000000000000000000000000cafe";
+/// ETH price served by the price API; stablecoins are at $1.
+pub const ETH_USD: f64 = 2500.0;
+/// Longest a cycle may take before a test gives up on it.
+const CYCLE_TIMEOUT: Duration = Duration::from_secs(20);
+
+pub fn eth(amount: &str) -> U256 {
+    parse_ether(amount).unwrap()
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+}
+
+/// A pool as the pool API lists it.
+pub fn pool(name: &str, chain: &str, apy: f64, tvl: f64) -> Value {
+    json!({ "name": name, "chain": chain, "apy": apy, "tvl": tvl })
+}
+
+/// Results of the JSON-RPC methods the node answers, by method name.
+type Script = Arc<Mutex<HashMap<String, Value>>>;
+
+/// Answers each call, batched or not, with its method's scripted result, or a
+/// "method not found" error for a method without one.
+struct Scripted(Script);
+
+impl Respond for Scripted {
+    fn respond(&self, request: &Request) -> ResponseTemplate {
+        let script = self.0.lock().unwrap();
+        let answer = |call: &Value| match call["method"].as_str().and_then(|method| script.get(method)) {
+            Some(result) => json!({ "jsonrpc": "2.0", "id": call["id"], "result": result }),
+            None => json!({
+                "jsonrpc": "2.0",
+                "id": call["id"],
+                "error": { "code": -32601, "message": format!("method not found: {}", call["method"]) },
+            }),
+        };
+        let body = match serde_json::from_slice::<Value>(&request.body) {
+            Ok(Value::Array(calls)) => Value::Array(calls.iter().map(answer).collect()),
+            Ok(call) => answer(&call),
+            Err(_) => return ResponseTemplate::new(400),
+        };
+        ResponseTemplate::new(200).set_body_json(body)
+    }
+}
+
+/// A mainnet node whose answers the test scripts per method.
+pub struct MockNode {
+    server: MockServer,
+    script: Script,
+}
+
+impl MockNode {
+    /// A node holding `balance` for every address, with a 20 gwei gas price.
+    async fn start(balance: U256) -> Self {
+        let server = MockServer::start().await;
+        let script = Script::default();
+        Mock::given(method("POST")).respond_with(Scripted(script.clone())).mount(&server).await;
+        let node = Self { server, script };
+        node.script("eth_chainId", json!("0x1"));
+        node.script("eth_blockNumber", json!("0x1312d00"));
+        node.script("eth_gasPrice", json!(format!("{:#x}", U256::exp10(9) * 20)));
+        node.script("eth_estimateGas", json!("0x5208"));
+        node.script("eth_getTransactionCount", json!("0x0"));
+        node.script("eth_getTransactionReceipt", Value::Null);
+        // Every contract call answers 1, enough for the Safe's threshold
+        node.script("eth_call", json!(format!("0x{:064x}", 1)));
+        node.set_balance(balance);
+        node
+    }
+
+    pub fn uri(&self) -> String {
+        self.server.uri()
+    }
+
+    /// Answers `method` with `result` from now on.
+    pub fn script(&self, method: &str, result: Value) {
+        self.script.lock().unwrap().insert(method.to_string(), result);
+    }
+
+    pub fn set_balance(&self, balance: U256) {
+        self.script("eth_getBalance", json!(format!("{:#x}", balance)));
+    }
+
+    /// Calls of `method` received so far.
+    pub async fn calls(&self, method: &str) -> usize {
+        let requests = self.server.received_requests().await.unwrap_or_default();
+        requests
+            .iter()
+            .filter_map(|request| serde_json::from_slice::<Value>(&request.body).ok())
+            .flat_map(|body| match body {
+                Value::Array(calls) => calls,
+                call => vec![call],
+            })
+            .filter(|call| call["method"] == method)
+            .count()
+    }
+}
+
+/// Everything outside the agent, on localhost.
+pub struct World {
+    pub node: MockNode,
+    pub pools: MockServer,
+    pub prices: MockServer,
+    pub bridge: MockServer,
+    pub webhook: MockServer,
+    pub decision_log: PathBuf,
+}
+
+impl World {
+    /// A world where the Safe holds `balance` on mainnet and the pool API lists no pools.
+    pub async fn start(balance: &str) -> Self {
+        static WORLDS: AtomicUsize = AtomicUsize::new(0);
+        let decision_log = std::env::temp_dir().join(format!(
+            "asam-e2e-{}-{}.jsonl",
+            std::process::id(),
+            WORLDS.fetch_add(1, Ordering::SeqCst)
+        ));
+        let _ = std::fs::remove_file(&decision_log);
+
+        let prices = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/simple/price"))
+            .respond_with(|request: &Request| {
+                let ids = request.url.query_pairs().find(|(key, _)| key == "ids").map(|(_, ids)| ids.into_owned());
+                let prices: serde_json::Map<String, Value> = ids
+                    .unwrap_or_default()
+                    .split(',')
+                    .map(|id| {
+                        let usd = match id {
+                            "ethereum" | "weth" => ETH_USD,
+                            "wrapped-bitcoin" => 60_000.0,
+                            _ => 1.0,
+                        };
+                        (id.to_string(), json!({ "usd": usd, "last_updated_at": unix_now() }))
+                    })
+                    .collect();
+                ResponseTemplate::new(200).set_body_json(prices)
+            })
+            .mount(&prices)
+            .await;
+        let webhook = MockServer::start().await;
+        Mock::given(method("POST")).respond_with(ResponseTemplate::new(204)).mount(&webhook).await;
+
+        let world = Self {
+            node: MockNode::start(eth(balance)).await,
+            pools: MockServer::start().await,
+            prices,
+            bridge: MockServer::start().await,
+            webhook,
+            decision_log,
+        };
+        world.set_pools(&[]).await;
+        world
+    }
+
+    /// A dry-run config for the world's endpoints; the interval is long enough that
+    /// only triggered cycles run.
+    pub fn config(&self) -> Config {
+        let mut config = Config::default();
+        config.safe.address = Some(SAFE.to_string());
+        config.safe.rpc_url = Some(self.node.uri());
+        config.safe.min_balance_eth = 0.1;
+        config.optimizer.api_url = self.pools.uri();
+        config.router.status.lifi_api_url = self.bridge.uri();
+        config.router.status.socket_api_url = self.bridge.uri();
+        config.prices.source = PriceSourceKind::Coingecko;
+        config.prices.coingecko_url = self.prices.uri();
+        config.prices.min_request_interval_ms = 0;
+        config.notify.webhook.url = Some(self.webhook.uri());
+        config.runtime.mode = Some(RunMode::DryRun);
+        config.runtime.cycle_interval_secs = 3600;
+        config.storage.decision_log_path = Some(self.decision_log.clone());
+        config
+    }
+
+    /// Has the pool API list `pools` from now on.
+    pub async fn set_pools(&self, pools: &[Value]) {
+        self.pools.reset().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(pools))
+            .mount(&self.pools)
+            .await;
+    }
+
+    /// Has the pool API answer every request with `status` from now on.
+    pub async fn set_pools_down(&self, status: u16) {
+        self.pools.reset().await;
+        Mock::given(method("GET")).respond_with(ResponseTemplate::new(status)).mount(&self.pools).await;
+    }
+
+    /// Every decision the agent recorded so far, oldest first.
+    pub fn decisions(&self) -> Vec<DecisionRecord> {
+        std::fs::read_to_string(&self.decision_log)
+            .unwrap_or_default()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    /// The `event.type` of every webhook delivery so far, waiting up to a few
+    /// seconds for at least `at_least` of them, since delivery runs in the background.
+    pub async fn alerts(&self, at_least: usize) -> Vec<String> {
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(5);
+        loop {
+            let requests = self.webhook.received_requests().await.unwrap_or_default();
+            let kinds: Vec<String> = requests
+                .iter()
+                .filter_map(|request| serde_json::from_slice::<Value>(&request.body).ok())
+                .filter_map(|body| body["event"]["type"].as_str().map(str::to_string))
+                .collect();
+            if kinds.len() >= at_least || tokio::time::Instant::now() >= deadline {
+                return kinds;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    }
+}
+
+impl Drop for World {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.decision_log);
+    }
+}
+
+/// What one cycle left behind.
+#[derive(Debug)]
+pub struct Cycle {
+    /// The status snapshot right after the cycle.
+    pub status: StatusSnapshot,
+    /// Events emitted during the cycle, in order.
+    pub events: Vec<AgentEvent>,
+}
+
+impl Cycle {
+    pub fn kinds(&self) -> Vec<&'static str> {
+        self.events.iter().map(AgentEvent::kind).collect()
+    }
+
+    pub fn failed(&self) -> bool {
+        self.kinds().contains(&"error_raised")
+    }
+
+    /// The cycle's report; panics when the cycle failed and left none.
+    pub fn report(&self) -> &CycleReport {
+        assert!(!self.failed(), "the cycle failed: {:#?}", self.events);
+        self.status.last_report.as_ref().expect("a full cycle leaves a report")
+    }
+}
+
+/// A [`Runner`] the test drives one cycle at a time.
+pub struct Agent {
+    runner: Runner,
+    events: Mutex<broadcast::Receiver<AgentEvent>>,
+    shutdown: CancellationToken,
+}
+
+impl Agent {
+    pub fn new(config: Config) -> Self {
+        let runner = Runner::new(config).unwrap();
+        let events = Mutex::new(runner.events());
+        Self { runner, events, shutdown: CancellationToken::new() }
+    }
+
+    /// Runs the agent alongside `host`, which drives it, and stops it once `host` is done.
+    pub async fn run_with<T>(&self, host: impl std::future::Future<Output = T>) -> T {
+        let host = async {
+            let result = host.await;
+            self.shutdown.cancel();
+            result
+        };
+        let (run, result) = tokio::join!(self.runner.run(self.shutdown.clone()), host);
+        run.unwrap();
+        result
+    }
+
+    /// Waits for the startup cycle.
+    pub async fn startup(&self) -> Cycle {
+        self.finish(1).await
+    }
+
+    /// Triggers a cycle and waits for it to finish.
+    pub async fn cycle(&self) -> Cycle {
+        let next = *self.runner.finished_cycles().borrow() + 1;
+        assert!(self.runner.trigger_cycle(), "a cycle is already pending");
+        self.finish(next).await
+    }
+
+    async fn finish(&self, cycle: u64) -> Cycle {
+        let mut finished = self.runner.finished_cycles();
+        tokio::time::timeout(CYCLE_TIMEOUT, finished.wait_for(|finished| *finished >= cycle))
+            .await
+            .unwrap_or_else(|_| panic!("cycle {} did not finish", cycle))
+            .unwrap();
+        let mut events = self.events.lock().unwrap();
+        Cycle {
+            status: self.runner.status(),
+            events: std::iter::from_fn(|| events.try_recv().ok()).collect(),
+        }
+    }
+}